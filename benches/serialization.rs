@@ -0,0 +1,105 @@
+//! The hot paths of every response: mapping DB values to the API types and the JSON serialization.
+//! `cargo bench`, compare with the previous run (criterion keeps it in `target/criterion`)
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use near_enhanced_api::api_models::coin::{CoinBalancesResponse, HistoryCause, HistoryResponse};
+
+fn history_response(items: usize) -> HistoryResponse {
+    let history: Vec<_> = (0..items)
+        .map(|i| {
+            serde_json::json!({
+                "involved_account_id": format!("account{}.near", i),
+                "signer_account_id": format!("account{}.near", i),
+                "predecessor_account_id": "router.near",
+                "delta_balance": format!("-{}", 1_000_000_000_000_000_000_000_u128 + i as u128),
+                "balance": format!("{}", 340_282_366_920_938_463_463_374_607_431_768_211_455_u128 - i as u128),
+                "cause": "TRANSFER",
+                "status": "SUCCESS",
+                "coin_metadata": {"name": "NEAR", "symbol": "NEAR", "icon_url": null, "decimals": 24},
+                "block_timestamp_nanos": "1659640914144765589",
+            })
+        })
+        .collect();
+    serde_json::from_value(serde_json::json!({
+        "history": history,
+        "block_timestamp_nanos": "1659640914144765589",
+        "block_height": "71000000",
+        "block_hash": "7Lbf6fo9zAstLpSMnMnWKB9GGCk8tkNmd2eWsSuzyYSM",
+    }))
+    .unwrap()
+}
+
+fn balances_response(coins: usize) -> CoinBalancesResponse {
+    let balances: Vec<_> = (0..coins)
+        .map(|i| {
+            serde_json::json!({
+                "standard": "nep141",
+                "balance": format!("{}", 10_u128.pow(24) * i as u128),
+                "contract_account_id": format!("token{}.near", i),
+                "metadata": {"name": "Token", "symbol": "TKN", "icon_url": format!("/icons/token{}.near", i), "decimals": 18},
+            })
+        })
+        .collect();
+    serde_json::from_value(serde_json::json!({
+        "balances": balances,
+        "block_timestamp_nanos": "1659640914144765589",
+        "block_height": "71000000",
+        "block_hash": "7Lbf6fo9zAstLpSMnMnWKB9GGCk8tkNmd2eWsSuzyYSM",
+    }))
+    .unwrap()
+}
+
+fn serialization(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialize");
+    for items in [20, 100] {
+        let history = history_response(items);
+        group.bench_with_input(
+            BenchmarkId::new("history", items),
+            &history,
+            |b, history| b.iter(|| serde_json::to_vec(black_box(history)).unwrap()),
+        );
+        let balances = balances_response(items);
+        group.bench_with_input(
+            BenchmarkId::new("balances", items),
+            &balances,
+            |b, balances| b.iter(|| serde_json::to_vec(black_box(balances)).unwrap()),
+        );
+    }
+    group.finish();
+
+    // The client and the response cache go the other way
+    let mut group = c.benchmark_group("deserialize");
+    let history = serde_json::to_vec(&history_response(100)).unwrap();
+    group.bench_function("history/100", |b| {
+        b.iter(|| serde_json::from_slice::<HistoryResponse>(black_box(&history)).unwrap())
+    });
+    group.finish();
+}
+
+fn db_mapping(c: &mut Criterion) {
+    // Each history row goes through it, the raw values come from the balances DB and the events tables
+    let causes = [
+        "TRANSACTION",
+        "RECEIPT",
+        "transfer",
+        "GAS_REFUND",
+        "VALIDATORS_REWARD",
+        "SOMETHING_NEW",
+    ];
+    c.bench_function("history_cause_from_db", |b| {
+        b.iter(|| {
+            for cause in causes {
+                black_box(HistoryCause::from(black_box(cause).to_string()));
+            }
+        })
+    });
+    // The same as `types::numeric::to_u128`: every amount comes from the `numeric` column as BigDecimal
+    let amount: sqlx::types::BigDecimal =
+        "340282366920938463463374607431768211455".parse().unwrap();
+    c.bench_function("u128_from_numeric", |b| {
+        b.iter(|| black_box(&amount).to_string().parse::<u128>().unwrap())
+    });
+}
+
+criterion_group!(benches, serialization, db_mapping);
+criterion_main!(benches);
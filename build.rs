@@ -0,0 +1,4 @@
+// `sqlx::migrate!` embeds `migrations/` into the binary, rebuild when they change
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
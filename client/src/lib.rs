@@ -0,0 +1,1174 @@
+//! Typed Rust client for NEAR Enhanced API.
+//!
+//! All the request and response types are taken from the server crate (`api_models`),
+//! so any change in the API breaks the compilation here instead of breaking the users in runtime.
+//! ```ignore
+//! let client = near_enhanced_api_client::Client::new("https://near-enhanced-api.example.com")?;
+//! let balance = client
+//!     .get_near_balance(&account_id, &BlockParams::default())
+//!     .await?;
+//! ```
+pub use near_enhanced_api::api_models;
+pub use near_enhanced_api::errors::Error as ApiError;
+pub use near_enhanced_api::types::{
+    query_params::{
+        BlockParams, FileFormat, FileFormatParams, HistoryPaginationParams, MetadataMode,
+        MetadataParams, PaginationParams, ResponseFormatParams, SkipErrorsParams, TimestampFormat,
+        WindowParams,
+    },
+    AccountId,
+};
+
+#[derive(Debug)]
+pub enum Error {
+    /// The request did not reach the server, or the response could not be parsed
+    Transport(reqwest::Error),
+    /// The server answered with the error
+    Api(ApiError),
+    InvalidUrl(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Transport(err) => write!(f, "Transport error: {}", err),
+            Self::Api(err) => write!(f, "{}", err),
+            Self::InvalidUrl(url) => write!(f, "Invalid URL: {}", url),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<reqwest::Error> for Error {
+    fn from(error: reqwest::Error) -> Self {
+        Self::Transport(error)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Clone)]
+pub struct Client {
+    base_url: reqwest::Url,
+    http: reqwest::Client,
+    signing_secret: Option<String>,
+}
+
+impl Client {
+    pub fn new(base_url: &str) -> Result<Self> {
+        Self::with_http_client(base_url, reqwest::Client::new())
+    }
+
+    /// Use it if you need to set up timeouts, proxies, default headers (e.g. API key), etc.
+    pub fn with_http_client(base_url: &str, http: reqwest::Client) -> Result<Self> {
+        let base_url =
+            reqwest::Url::parse(base_url).map_err(|_| Error::InvalidUrl(base_url.to_string()))?;
+        if base_url.cannot_be_a_base() {
+            return Err(Error::InvalidUrl(base_url.to_string()));
+        }
+        Ok(Self {
+            base_url,
+            http,
+            signing_secret: None,
+        })
+    }
+
+    /// Sign all the requests with HMAC, for the servers with `REQUEST_SIGNING_SECRET`.
+    /// The API key should be set in the default headers of the HTTP client
+    pub fn with_request_signing(mut self, signing_secret: String) -> Self {
+        self.signing_secret = Some(signing_secret);
+        self
+    }
+
+    // *** Coins ***
+
+    pub async fn get_near_balance(
+        &self,
+        account_id: &AccountId,
+        block_params: &BlockParams,
+    ) -> Result<api_models::coin::NearBalanceResponse> {
+        self.send(
+            self.request(&["accounts", &account_id.to_string(), "coins", "NEAR"])
+                .query(block_params),
+        )
+        .await
+    }
+
+    pub async fn get_near_balance_breakdown(
+        &self,
+        account_id: &AccountId,
+        block_params: &BlockParams,
+    ) -> Result<api_models::coin::NearBalanceBreakdownResponse> {
+        self.send(
+            self.request(&[
+                "accounts",
+                &account_id.to_string(),
+                "coins",
+                "NEAR",
+                "breakdown",
+            ])
+            .query(block_params),
+        )
+        .await
+    }
+
+    pub async fn get_coin_balances(
+        &self,
+        account_id: &AccountId,
+        block_params: &BlockParams,
+        pagination_params: &PaginationParams,
+        skip_errors_params: &SkipErrorsParams,
+        metadata_params: &MetadataParams,
+    ) -> Result<api_models::coin::CoinBalancesResponse> {
+        self.send(
+            self.request(&["accounts", &account_id.to_string(), "coins"])
+                .query(block_params)
+                .query(pagination_params)
+                .query(skip_errors_params)
+                .query(metadata_params),
+        )
+        .await
+    }
+
+    pub async fn get_coin_balances_by_contract(
+        &self,
+        account_id: &AccountId,
+        contract_account_id: &AccountId,
+        block_params: &BlockParams,
+        metadata_params: &MetadataParams,
+    ) -> Result<api_models::coin::CoinBalancesResponse> {
+        self.send(
+            self.request(&[
+                "accounts",
+                &account_id.to_string(),
+                "coins",
+                &contract_account_id.to_string(),
+            ])
+            .query(block_params)
+            .query(metadata_params),
+        )
+        .await
+    }
+
+    pub async fn reconcile_coin_balance(
+        &self,
+        account_id: &AccountId,
+        contract_account_id: &AccountId,
+        block_params: &BlockParams,
+    ) -> Result<api_models::coin::BalanceReconciliationResponse> {
+        self.send(
+            self.request(&[
+                "accounts",
+                &account_id.to_string(),
+                "coins",
+                &contract_account_id.to_string(),
+                "reconcile",
+            ])
+            .query(block_params),
+        )
+        .await
+    }
+
+    pub async fn get_balances_diff(
+        &self,
+        account_id: &AccountId,
+        params: &api_models::coin::BalancesDiffParams,
+    ) -> Result<api_models::coin::BalancesDiffResponse> {
+        self.send(
+            self.request(&["accounts", &account_id.to_string(), "coins", "diff"])
+                .query(params),
+        )
+        .await
+    }
+
+    pub async fn get_near_balances_at_blocks(
+        &self,
+        account_id: &AccountId,
+        params: &api_models::coin::BalancesAtBlocksParams,
+    ) -> Result<api_models::coin::BalancesAtBlocksResponse> {
+        self.send(
+            self.request(&[
+                "accounts",
+                &account_id.to_string(),
+                "coins",
+                "NEAR",
+                "at-blocks",
+            ])
+            .query(params),
+        )
+        .await
+    }
+
+    pub async fn get_coin_balances_at_blocks(
+        &self,
+        account_id: &AccountId,
+        contract_account_id: &AccountId,
+        params: &api_models::coin::BalancesAtBlocksParams,
+    ) -> Result<api_models::coin::BalancesAtBlocksResponse> {
+        self.send(
+            self.request(&[
+                "accounts",
+                &account_id.to_string(),
+                "coins",
+                &contract_account_id.to_string(),
+                "at-blocks",
+            ])
+            .query(params),
+        )
+        .await
+    }
+
+    pub async fn get_near_history(
+        &self,
+        account_id: &AccountId,
+        pagination_params: &HistoryPaginationParams,
+    ) -> Result<api_models::coin::HistoryResponse> {
+        self.send(
+            self.request(&[
+                "accounts",
+                &account_id.to_string(),
+                "coins",
+                "NEAR",
+                "history",
+            ])
+            .query(pagination_params),
+        )
+        .await
+    }
+
+    pub async fn get_coin_history(
+        &self,
+        account_id: &AccountId,
+        contract_account_id: &AccountId,
+        pagination_params: &HistoryPaginationParams,
+    ) -> Result<api_models::coin::HistoryResponse> {
+        self.send(
+            self.request(&[
+                "accounts",
+                &account_id.to_string(),
+                "coins",
+                &contract_account_id.to_string(),
+                "history",
+            ])
+            .query(pagination_params),
+        )
+        .await
+    }
+
+    pub async fn get_ft_transfers(
+        &self,
+        contract_account_id: &AccountId,
+        pagination_params: &HistoryPaginationParams,
+    ) -> Result<api_models::coin::FtTransfersResponse> {
+        self.send(
+            self.request(&["nep141", &contract_account_id.to_string(), "transfers"])
+                .query(pagination_params),
+        )
+        .await
+    }
+
+    pub async fn get_ft_holders(
+        &self,
+        contract_account_id: &AccountId,
+        holders_params: &api_models::coin::FtHoldersParams,
+        block_params: &BlockParams,
+        pagination_params: &PaginationParams,
+    ) -> Result<api_models::coin::FtHoldersResponse> {
+        self.send(
+            self.request(&["nep141", &contract_account_id.to_string(), "holders"])
+                .query(holders_params)
+                .query(block_params)
+                .query(pagination_params),
+        )
+        .await
+    }
+
+    /// CSV with all the holders, `account_id,balance` columns
+    pub async fn export_ft_holders(
+        &self,
+        contract_account_id: &AccountId,
+        block_params: &BlockParams,
+    ) -> Result<String> {
+        let response = self
+            .request(&[
+                "nep141",
+                &contract_account_id.to_string(),
+                "holders",
+                "export",
+            ])
+            .query(block_params)
+            .send()
+            .await?;
+        if response.status().is_success() {
+            return Ok(response.text().await?);
+        }
+        Err(Self::api_error(response).await?)
+    }
+
+    /// The same holders as CSV or Parquet file, Parquet requires the server built with `parquet` feature
+    pub async fn export_ft_holders_file(
+        &self,
+        contract_account_id: &AccountId,
+        block_params: &BlockParams,
+        format_params: &FileFormatParams,
+    ) -> Result<Vec<u8>> {
+        let response = self
+            .request(&[
+                "nep141",
+                &contract_account_id.to_string(),
+                "holders",
+                "export",
+            ])
+            .query(block_params)
+            .query(format_params)
+            .send()
+            .await?;
+        if response.status().is_success() {
+            return Ok(response.bytes().await?.to_vec());
+        }
+        Err(Self::api_error(response).await?)
+    }
+
+    pub async fn get_ft_contract_metadata(
+        &self,
+        contract_account_id: &AccountId,
+        block_params: &BlockParams,
+    ) -> Result<api_models::coin::FtContractMetadataResponse> {
+        self.send(
+            self.request(&["nep141", "metadata", &contract_account_id.to_string()])
+                .query(block_params),
+        )
+        .await
+    }
+
+    pub async fn get_ft_contracts_metadata(
+        &self,
+        request: &api_models::coin::FtContractsMetadataRequest,
+    ) -> Result<api_models::coin::FtContractsMetadataResponse> {
+        self.send(
+            self.http
+                .post(self.url(&["nep141", "metadata"]))
+                .json(request),
+        )
+        .await
+    }
+
+    /// Asks the contract for the fresh metadata, bypassing the server caches
+    pub async fn refresh_ft_contract_metadata(
+        &self,
+        contract_account_id: &AccountId,
+    ) -> Result<api_models::coin::FtContractMetadataResponse> {
+        self.send(self.http.post(self.url(&[
+            "nep141",
+            "metadata",
+            &contract_account_id.to_string(),
+            "refresh",
+        ])))
+        .await
+    }
+
+    pub async fn get_price_history(
+        &self,
+        contract_account_id: &AccountId,
+        price_params: &api_models::coin::PriceHistoryParams,
+        block_params: &BlockParams,
+        pagination_params: &PaginationParams,
+    ) -> Result<api_models::coin::PriceHistoryResponse> {
+        self.send(
+            self.request(&[
+                "nep141",
+                &contract_account_id.to_string(),
+                "price",
+                "history",
+            ])
+            .query(price_params)
+            .query(block_params)
+            .query(pagination_params),
+        )
+        .await
+    }
+
+    pub async fn get_portfolio(
+        &self,
+        request: &api_models::coin::PortfolioRequest,
+        block_params: &BlockParams,
+    ) -> Result<api_models::coin::PortfolioResponse> {
+        self.send(
+            self.http
+                .post(self.url(&["portfolio"]))
+                .query(block_params)
+                .json(request),
+        )
+        .await
+    }
+
+    /// Raw bytes of the sanitized FT icon. The redirects to the external icons are followed
+    pub async fn get_ft_icon(&self, contract_account_id: &AccountId) -> Result<Vec<u8>> {
+        let response = self
+            .request(&["icons", &contract_account_id.to_string()])
+            .send()
+            .await?;
+        if response.status().is_success() {
+            return Ok(response.bytes().await?.to_vec());
+        }
+        Err(Self::api_error(response).await?)
+    }
+
+    /// `CoinMetadata::icon_url` is relative to the API root, this gives the full URL
+    pub fn resolve_icon_url(&self, icon_url: &str) -> Result<reqwest::Url> {
+        if icon_url.starts_with("https://") {
+            return reqwest::Url::parse(icon_url)
+                .map_err(|_| Error::InvalidUrl(icon_url.to_string()));
+        }
+        Ok(self.url(&icon_url.split('/').collect::<Vec<&str>>()))
+    }
+
+    pub async fn get_gas_fees(
+        &self,
+        account_id: &AccountId,
+        window_params: &WindowParams,
+        block_params: &BlockParams,
+    ) -> Result<api_models::coin::GasFeesResponse> {
+        self.send(
+            self.request(&["accounts", &account_id.to_string(), "fees"])
+                .query(window_params)
+                .query(block_params),
+        )
+        .await
+    }
+
+    // *** NFT ***
+
+    pub async fn get_nft_collection_overview(
+        &self,
+        account_id: &AccountId,
+        block_params: &BlockParams,
+        pagination_params: &PaginationParams,
+        skip_errors_params: &SkipErrorsParams,
+        hide_spam_params: &api_models::nft::HideSpamParams,
+        metadata_params: &MetadataParams,
+    ) -> Result<api_models::nft::NftCountsResponse> {
+        self.send(
+            self.request(&["accounts", &account_id.to_string(), "NFT"])
+                .query(block_params)
+                .query(pagination_params)
+                .query(skip_errors_params)
+                .query(hide_spam_params)
+                .query(metadata_params),
+        )
+        .await
+    }
+
+    pub async fn get_nft_holdings_diff(
+        &self,
+        account_id: &AccountId,
+        params: &api_models::nft::NftHoldingsDiffParams,
+    ) -> Result<api_models::nft::NftHoldingsDiffResponse> {
+        self.send(
+            self.request(&["accounts", &account_id.to_string(), "NFT", "diff"])
+                .query(params),
+        )
+        .await
+    }
+
+    pub async fn get_nft_collection_by_contract(
+        &self,
+        account_id: &AccountId,
+        contract_account_id: &AccountId,
+        block_params: &BlockParams,
+        pagination_params: &PaginationParams,
+        metadata_params: &MetadataParams,
+    ) -> Result<api_models::nft::NftsResponse> {
+        self.send(
+            self.request(&[
+                "accounts",
+                &account_id.to_string(),
+                "NFT",
+                &contract_account_id.to_string(),
+            ])
+            .query(block_params)
+            .query(pagination_params)
+            .query(metadata_params),
+        )
+        .await
+    }
+
+    pub async fn get_nft(
+        &self,
+        contract_account_id: &AccountId,
+        token_id: &str,
+        block_params: &BlockParams,
+    ) -> Result<api_models::nft::NftResponse> {
+        self.send(
+            self.nft_request(contract_account_id, token_id, None)
+                .query(block_params),
+        )
+        .await
+    }
+
+    pub async fn get_nft_history(
+        &self,
+        contract_account_id: &AccountId,
+        token_id: &str,
+        pagination_params: &HistoryPaginationParams,
+    ) -> Result<api_models::nft::HistoryResponse> {
+        self.send(
+            self.nft_request(contract_account_id, token_id, Some("history"))
+                .query(pagination_params),
+        )
+        .await
+    }
+
+    pub async fn get_nft_sales(
+        &self,
+        contract_account_id: &AccountId,
+        token_id: &str,
+        pagination_params: &HistoryPaginationParams,
+    ) -> Result<api_models::nft::NftSalesResponse> {
+        self.send(
+            self.nft_request(contract_account_id, token_id, Some("sales"))
+                .query(pagination_params),
+        )
+        .await
+    }
+
+    pub async fn get_nft_approvals_history(
+        &self,
+        account_id: &AccountId,
+        pagination_params: &HistoryPaginationParams,
+    ) -> Result<api_models::nft::NftApprovalsHistoryResponse> {
+        self.send(
+            self.request(&[
+                "accounts",
+                &account_id.to_string(),
+                "NFT",
+                "approvals",
+                "history",
+            ])
+            .query(pagination_params),
+        )
+        .await
+    }
+
+    pub async fn get_nft_provenance(
+        &self,
+        contract_account_id: &AccountId,
+        token_id: &str,
+        block_params: &BlockParams,
+    ) -> Result<api_models::nft::NftProvenanceResponse> {
+        self.send(
+            self.nft_request(contract_account_id, token_id, Some("provenance"))
+                .query(block_params),
+        )
+        .await
+    }
+
+    pub async fn get_nft_market_stats(
+        &self,
+        contract_account_id: &AccountId,
+        window_params: &WindowParams,
+        block_params: &BlockParams,
+    ) -> Result<api_models::nft::NftMarketStatsResponse> {
+        self.send(
+            self.request(&["nep171", "market", &contract_account_id.to_string()])
+                .query(window_params)
+                .query(block_params),
+        )
+        .await
+    }
+
+    pub async fn get_nft_contract_metadata(
+        &self,
+        contract_account_id: &AccountId,
+        block_params: &BlockParams,
+    ) -> Result<api_models::nft::MetadataResponse> {
+        self.send(
+            self.request(&["nep171", "metadata", &contract_account_id.to_string()])
+                .query(block_params),
+        )
+        .await
+    }
+
+    pub async fn get_nft_contracts_metadata(
+        &self,
+        request: &api_models::nft::NftContractsMetadataRequest,
+    ) -> Result<api_models::nft::NftContractsMetadataResponse> {
+        self.send(
+            self.http
+                .post(self.url(&["nep171", "metadata"]))
+                .json(request),
+        )
+        .await
+    }
+
+    /// Asks the contract for the fresh metadata, bypassing the server caches
+    pub async fn refresh_nft_contract_metadata(
+        &self,
+        contract_account_id: &AccountId,
+    ) -> Result<api_models::nft::MetadataResponse> {
+        self.send(self.http.post(self.url(&[
+            "nep171",
+            "metadata",
+            &contract_account_id.to_string(),
+            "refresh",
+        ])))
+        .await
+    }
+
+    // *** Staking ***
+
+    pub async fn get_staking_summary(
+        &self,
+        account_id: &AccountId,
+        block_params: &BlockParams,
+    ) -> Result<api_models::staking::StakingSummaryResponse> {
+        self.send(
+            self.request(&["accounts", &account_id.to_string(), "staking", "summary"])
+                .query(block_params),
+        )
+        .await
+    }
+
+    pub async fn get_staking_pools(
+        &self,
+        block_params: &BlockParams,
+    ) -> Result<api_models::staking::StakingPoolsResponse> {
+        self.send(self.request(&["staking-pools"]).query(block_params))
+            .await
+    }
+
+    pub async fn get_supply_stats(
+        &self,
+        block_params: &BlockParams,
+    ) -> Result<api_models::staking::SupplyStatsResponse> {
+        self.send(self.request(&["stats", "supply"]).query(block_params))
+            .await
+    }
+
+    // *** DeFi ***
+
+    pub async fn get_ref_finance_positions(
+        &self,
+        account_id: &AccountId,
+        block_params: &BlockParams,
+    ) -> Result<api_models::defi::RefFinancePositionsResponse> {
+        self.send(
+            self.request(&["accounts", &account_id.to_string(), "defi", "ref"])
+                .query(block_params),
+        )
+        .await
+    }
+
+    // *** Accounts ***
+
+    pub async fn get_account_counters(
+        &self,
+        account_id: &AccountId,
+        block_params: &BlockParams,
+    ) -> Result<api_models::accounts::AccountCountersResponse> {
+        self.send(
+            self.request(&["accounts", &account_id.to_string(), "counters"])
+                .query(block_params),
+        )
+        .await
+    }
+
+    pub async fn get_account_activity_summary(
+        &self,
+        account_id: &AccountId,
+        block_params: &BlockParams,
+    ) -> Result<api_models::accounts::AccountActivitySummaryResponse> {
+        self.send(
+            self.request(&["accounts", &account_id.to_string(), "activity", "summary"])
+                .query(block_params),
+        )
+        .await
+    }
+
+    pub async fn get_deployments(
+        &self,
+        contract_account_id: &AccountId,
+        block_params: &BlockParams,
+        pagination_params: &PaginationParams,
+    ) -> Result<api_models::accounts::DeploymentsResponse> {
+        self.send(
+            self.request(&["contracts", &contract_account_id.to_string(), "deployments"])
+                .query(block_params)
+                .query(pagination_params),
+        )
+        .await
+    }
+
+    pub async fn get_code_history(
+        &self,
+        contract_account_id: &AccountId,
+        block_params: &BlockParams,
+        pagination_params: &PaginationParams,
+    ) -> Result<api_models::accounts::CodeHistoryResponse> {
+        self.send(
+            self.request(&[
+                "contracts",
+                &contract_account_id.to_string(),
+                "code-history",
+            ])
+            .query(block_params)
+            .query(pagination_params),
+        )
+        .await
+    }
+
+    pub async fn get_receipt(
+        &self,
+        receipt_id: &str,
+        block_params: &BlockParams,
+    ) -> Result<api_models::accounts::ReceiptResponse> {
+        self.send(self.request(&["receipts", receipt_id]).query(block_params))
+            .await
+    }
+
+    pub async fn get_gas_profile(
+        &self,
+        tx_hash: &str,
+        block_params: &BlockParams,
+    ) -> Result<api_models::accounts::GasProfileResponse> {
+        self.send(
+            self.request(&["transactions", tx_hash, "gas-profile"])
+                .query(block_params),
+        )
+        .await
+    }
+
+    pub async fn get_access_key_usage(
+        &self,
+        account_id: &AccountId,
+        public_key: &str,
+        block_params: &BlockParams,
+        pagination_params: &PaginationParams,
+    ) -> Result<api_models::accounts::AccessKeyUsageResponse> {
+        self.send(
+            self.request(&[
+                "accounts",
+                &account_id.to_string(),
+                "keys",
+                public_key,
+                "usage",
+            ])
+            .query(block_params)
+            .query(pagination_params),
+        )
+        .await
+    }
+
+    pub async fn get_counterparties(
+        &self,
+        account_id: &AccountId,
+        window_params: &WindowParams,
+        block_params: &BlockParams,
+        pagination_params: &PaginationParams,
+    ) -> Result<api_models::accounts::CounterpartiesResponse> {
+        self.send(
+            self.request(&["accounts", &account_id.to_string(), "counterparties"])
+                .query(window_params)
+                .query(block_params)
+                .query(pagination_params),
+        )
+        .await
+    }
+
+    pub async fn get_account_graph(
+        &self,
+        account_id: &AccountId,
+        graph_params: &api_models::accounts::GraphParams,
+        window_params: &WindowParams,
+        block_params: &BlockParams,
+        pagination_params: &PaginationParams,
+    ) -> Result<api_models::accounts::AccountGraphResponse> {
+        self.send(
+            self.request(&["accounts", &account_id.to_string(), "graph"])
+                .query(graph_params)
+                .query(window_params)
+                .query(block_params)
+                .query(pagination_params),
+        )
+        .await
+    }
+
+    pub async fn get_access_keys_history(
+        &self,
+        account_id: &AccountId,
+        pagination_params: &HistoryPaginationParams,
+    ) -> Result<api_models::accounts::AccessKeysHistoryResponse> {
+        self.send(
+            self.request(&["accounts", &account_id.to_string(), "keys", "history"])
+                .query(pagination_params),
+        )
+        .await
+    }
+
+    /// `block_id` is the block height or the block hash
+    pub async fn get_block_transfers(
+        &self,
+        block_id: &str,
+    ) -> Result<api_models::accounts::BlockTransfersResponse> {
+        self.send(self.request(&["blocks", block_id, "transfers"]))
+            .await
+    }
+
+    // *** Alerts ***
+    // The alerts belong to the API key, it should be set as `X-API-Key` default header
+
+    pub async fn get_alerts(&self) -> Result<api_models::alerts::AlertsResponse> {
+        self.send(self.request(&["alerts"])).await
+    }
+
+    pub async fn create_alert(
+        &self,
+        rule: &api_models::alerts::AlertRule,
+    ) -> Result<api_models::alerts::AlertResponse> {
+        self.send(self.http.post(self.url(&["alerts"])).json(rule))
+            .await
+    }
+
+    pub async fn delete_alert(&self, alert_id: u64) -> Result<api_models::alerts::AlertsResponse> {
+        self.send(
+            self.http
+                .delete(self.url(&["alerts", &alert_id.to_string()])),
+        )
+        .await
+    }
+
+    // *** Watchlists ***
+    // The watchlists belong to the API key, it should be set as `X-API-Key` default header
+
+    pub async fn get_watchlists(&self) -> Result<api_models::watchlists::WatchlistsResponse> {
+        self.send(self.request(&["watchlists"])).await
+    }
+
+    pub async fn get_watchlist(
+        &self,
+        watchlist_id: u64,
+    ) -> Result<api_models::watchlists::WatchlistResponse> {
+        self.send(self.request(&["watchlists", &watchlist_id.to_string()]))
+            .await
+    }
+
+    pub async fn create_watchlist(
+        &self,
+        input: &api_models::watchlists::WatchlistInput,
+    ) -> Result<api_models::watchlists::WatchlistResponse> {
+        self.send(self.http.post(self.url(&["watchlists"])).json(input))
+            .await
+    }
+
+    pub async fn update_watchlist(
+        &self,
+        watchlist_id: u64,
+        input: &api_models::watchlists::WatchlistInput,
+    ) -> Result<api_models::watchlists::WatchlistResponse> {
+        self.send(
+            self.http
+                .put(self.url(&["watchlists", &watchlist_id.to_string()]))
+                .json(input),
+        )
+        .await
+    }
+
+    pub async fn delete_watchlist(
+        &self,
+        watchlist_id: u64,
+    ) -> Result<api_models::watchlists::WatchlistsResponse> {
+        self.send(
+            self.http
+                .delete(self.url(&["watchlists", &watchlist_id.to_string()])),
+        )
+        .await
+    }
+
+    pub async fn get_watchlist_portfolio(
+        &self,
+        watchlist_id: u64,
+        block_params: &BlockParams,
+    ) -> Result<api_models::coin::PortfolioResponse> {
+        self.send(
+            self.request(&["watchlists", &watchlist_id.to_string(), "portfolio"])
+                .query(block_params),
+        )
+        .await
+    }
+
+    pub async fn get_watchlist_activity(
+        &self,
+        watchlist_id: u64,
+        pagination_params: &HistoryPaginationParams,
+    ) -> Result<api_models::watchlists::WatchlistActivityResponse> {
+        self.send(
+            self.request(&["watchlists", &watchlist_id.to_string(), "activity"])
+                .query(pagination_params),
+        )
+        .await
+    }
+
+    // *** Exports ***
+    // The export jobs belong to the API key, it should be set as `X-API-Key` default header
+
+    pub async fn create_export(
+        &self,
+        account_id: &AccountId,
+        input: &api_models::exports::ExportInput,
+    ) -> Result<api_models::exports::ExportJobResponse> {
+        self.send(
+            self.http
+                .post(self.url(&["accounts", &account_id.to_string(), "exports"]))
+                .json(input),
+        )
+        .await
+    }
+
+    /// Poll it until the job has `SUCCEEDED` or `FAILED`
+    pub async fn get_export(&self, job_id: u64) -> Result<api_models::exports::ExportJobResponse> {
+        self.send(self.request(&["exports", &job_id.to_string()]))
+            .await
+    }
+
+    // *** Admin ***
+    // `ADMIN_API_TOKEN` should be set as `Authorization: Bearer <token>` default header
+
+    pub async fn get_usage(
+        &self,
+        window_params: &WindowParams,
+        pagination_params: &PaginationParams,
+    ) -> Result<api_models::admin::UsageResponse> {
+        self.send(
+            self.request(&["admin", "usage"])
+                .query(window_params)
+                .query(pagination_params),
+        )
+        .await
+    }
+
+    pub async fn get_key_usage(
+        &self,
+        key_id: &str,
+        window_params: &WindowParams,
+    ) -> Result<api_models::admin::KeyUsageResponse> {
+        self.send(
+            self.request(&["admin", "usage", key_id])
+                .query(window_params),
+        )
+        .await
+    }
+
+    pub async fn get_audit_records(
+        &self,
+        audit_params: &api_models::admin::AuditParams,
+        pagination_params: &PaginationParams,
+    ) -> Result<api_models::admin::AuditResponse> {
+        self.send(
+            self.request(&["admin", "audit"])
+                .query(audit_params)
+                .query(pagination_params),
+        )
+        .await
+    }
+
+    pub async fn get_signing_secret(
+        &self,
+        key_id: &str,
+    ) -> Result<api_models::admin::SigningSecretResponse> {
+        self.send(self.request(&["admin", "keys", key_id, "signing-secret"]))
+            .await
+    }
+
+    pub async fn get_quota(&self, key_id: &str) -> Result<api_models::admin::QuotaResponse> {
+        self.send(self.request(&["admin", "keys", key_id, "quota"]))
+            .await
+    }
+
+    pub async fn set_quota(
+        &self,
+        key_id: &str,
+        quota: &api_models::admin::Quota,
+    ) -> Result<api_models::admin::QuotaResponse> {
+        self.send(
+            self.http
+                .put(self.url(&["admin", "keys", key_id, "quota"]))
+                .json(quota),
+        )
+        .await
+    }
+
+    pub async fn delete_quota(&self, key_id: &str) -> Result<api_models::admin::QuotaResponse> {
+        self.send(
+            self.http
+                .delete(self.url(&["admin", "keys", key_id, "quota"])),
+        )
+        .await
+    }
+
+    pub async fn get_disabled_routes(&self) -> Result<api_models::admin::DisabledRoutesResponse> {
+        self.send(self.request(&["admin", "routes", "disabled"]))
+            .await
+    }
+
+    pub async fn set_disabled_routes(
+        &self,
+        disabled_routes: &api_models::admin::DisabledRoutesRequest,
+    ) -> Result<api_models::admin::DisabledRoutesResponse> {
+        self.send(
+            self.http
+                .put(self.url(&["admin", "routes", "disabled"]))
+                .json(disabled_routes),
+        )
+        .await
+    }
+
+    /// Only on the servers with `CONSISTENCY_CHECK_INTERVAL_SECS`
+    pub async fn get_consistency_report(
+        &self,
+    ) -> Result<api_models::admin::ConsistencyReportResponse> {
+        self.send(self.request(&["admin", "consistency"])).await
+    }
+
+    pub async fn get_allowed_contracts(
+        &self,
+    ) -> Result<api_models::admin::AllowedContractsResponse> {
+        self.send(self.request(&["admin", "contracts", "allowed"]))
+            .await
+    }
+
+    pub async fn set_allowed_contracts(
+        &self,
+        allowed_contracts: &api_models::admin::AllowedContractsRequest,
+    ) -> Result<api_models::admin::AllowedContractsResponse> {
+        self.send(
+            self.http
+                .put(self.url(&["admin", "contracts", "allowed"]))
+                .json(allowed_contracts),
+        )
+        .await
+    }
+
+    // *** Raw JSON ***
+
+    /// Any GET endpoint as the raw JSON, e.g. `&["accounts", "alice.near", "coins"]`.
+    /// Use it for `timestamp_format`: `iso8601` and `epoch_ms` replace `..._timestamp_nanos` fields,
+    /// the typed responses can't be parsed without them.
+    /// Use it for `amount_float` as well: the typed responses drop the `..._float` fields
+    pub async fn get_json<Q: serde::Serialize + ?Sized>(
+        &self,
+        path_segments: &[&str],
+        query: &Q,
+        response_format_params: &ResponseFormatParams,
+    ) -> Result<serde_json::Value> {
+        self.send(
+            self.request(path_segments)
+                .query(query)
+                .query(response_format_params),
+        )
+        .await
+    }
+
+    // ---
+
+    fn url(&self, path_segments: &[&str]) -> reqwest::Url {
+        let mut url = self.base_url.clone();
+        // We checked that the url can be a base in the constructor.
+        // Segments are percent-encoded here, so token_ids with `/`, spaces etc. are safe
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments.pop_if_empty().extend(path_segments);
+        }
+        url
+    }
+
+    fn request(&self, path_segments: &[&str]) -> reqwest::RequestBuilder {
+        self.http.get(self.url(path_segments))
+    }
+
+    /// `/NFT/{contract_account_id}/{token_id}/{suffix}`. `.` and `..` can't be the path segments
+    /// (`url` drops them), such token_ids go to the query of `/nep171/token/{contract_account_id}/{suffix}`
+    fn nft_request(
+        &self,
+        contract_account_id: &AccountId,
+        token_id: &str,
+        suffix: Option<&str>,
+    ) -> reqwest::RequestBuilder {
+        let contract_account_id = contract_account_id.to_string();
+        if matches!(token_id, "." | "..") {
+            let mut path_segments = vec!["nep171", "token", contract_account_id.as_str()];
+            path_segments.extend(suffix);
+            self.request(&path_segments)
+                .query(&[("token_id", token_id)])
+        } else {
+            let mut path_segments = vec!["NFT", contract_account_id.as_str(), token_id];
+            path_segments.extend(suffix);
+            self.request(&path_segments)
+        }
+    }
+
+    async fn send<T: serde::de::DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<T> {
+        let mut request = request.build()?;
+        if let Some(secret) = &self.signing_secret {
+            let timestamp_secs = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or_default();
+            let url = request.url();
+            let path_and_query = match url.query() {
+                Some(query) => format!("{}?{}", url.path(), query),
+                None => url.path().to_string(),
+            };
+            let body = request
+                .body()
+                .and_then(|body| body.as_bytes())
+                .unwrap_or_default();
+            let signature = near_enhanced_api::signing::sign(
+                secret,
+                request.method().as_str(),
+                &path_and_query,
+                timestamp_secs,
+                body,
+            );
+            let headers = request.headers_mut();
+            headers.insert(
+                near_enhanced_api::signing::TIMESTAMP_HEADER,
+                reqwest::header::HeaderValue::from(timestamp_secs),
+            );
+            headers.insert(
+                near_enhanced_api::signing::SIGNATURE_HEADER,
+                reqwest::header::HeaderValue::from_str(&signature)
+                    .expect("hex is a valid header value"),
+            );
+        }
+        let response = self.http.execute(request).await?;
+        if response.status().is_success() {
+            return Ok(response.json::<T>().await?);
+        }
+        Err(Self::api_error(response).await?)
+    }
+
+    async fn api_error(response: reqwest::Response) -> Result<Error> {
+        let status = response.status();
+        // Only the delay in seconds is supported, not the HTTP date
+        let retry_after_secs = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        let body = response.text().await?;
+        let mut error = serde_json::from_str::<ApiError>(&body).unwrap_or(ApiError {
+            code: status.as_u16() as u32,
+            error_code: String::new(),
+            message: body,
+            retriable: status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS,
+            retry_after_secs: None,
+            details: None,
+            debug_message: None,
+        });
+        error.retry_after_secs = error.retry_after_secs.or(retry_after_secs);
+        Ok(Error::Api(error))
+    }
+}
@@ -0,0 +1,9 @@
+-- Sampled DEX prices for `/nep141/{contract_account_id}/price/history`, filled by `price_sampler`
+CREATE TABLE IF NOT EXISTS token_prices
+(
+    contract_account_id  text           NOT NULL,
+    sampled_at_timestamp numeric(20, 0) NOT NULL,
+    -- in the quote token (USD stablecoin), decimals are already applied
+    price_usd            numeric        NOT NULL,
+    PRIMARY KEY (contract_account_id, sampled_at_timestamp)
+);
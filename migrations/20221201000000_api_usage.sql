@@ -0,0 +1,13 @@
+-- Per API key usage for `/admin/usage`, filled by `usage::UsageRecorder`
+CREATE TABLE IF NOT EXISTS api_usage
+(
+    -- hash of the API key, the keys themselves are never stored
+    key_id         text           NOT NULL,
+    -- the start of the UTC day
+    day_timestamp  numeric(20, 0) NOT NULL,
+    -- route pattern, e.g. `/accounts/{account_id}/coins/NEAR`
+    endpoint       text           NOT NULL,
+    requests_count bigint         NOT NULL,
+    response_bytes bigint         NOT NULL,
+    PRIMARY KEY (key_id, day_timestamp, endpoint)
+);
@@ -0,0 +1,9 @@
+-- Request quotas per API key, managed by `/admin/keys/{key_id}/quota` and enforced by `quotas::QuotaEnforcer`.
+-- `*` row is applied to the keys without their own row. NULL means unlimited
+CREATE TABLE IF NOT EXISTS api_quotas
+(
+    key_id               text           NOT NULL PRIMARY KEY,
+    daily_requests       bigint,
+    monthly_requests     bigint,
+    updated_at_timestamp numeric(20, 0) NOT NULL
+);
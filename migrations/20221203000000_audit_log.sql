@@ -0,0 +1,17 @@
+-- Append-only audit trail of the admin and signed requests, filled by `audit::AuditLogger`, see `/admin/audit`
+CREATE TABLE IF NOT EXISTS audit_log
+(
+    id           bigserial      NOT NULL PRIMARY KEY,
+    timestamp    numeric(20, 0) NOT NULL,
+    -- hash of the API key or of the admin token, see `api_keys`
+    actor_key_id text           NOT NULL,
+    method       text           NOT NULL,
+    -- route pattern, e.g. `/admin/keys/{key_id}/quota`
+    endpoint     text           NOT NULL,
+    path         text           NOT NULL,
+    query        text           NOT NULL,
+    body         text,
+    status       integer        NOT NULL,
+    client_ip    text
+);
+CREATE INDEX IF NOT EXISTS audit_log_actor_key_id_idx ON audit_log (actor_key_id, id);
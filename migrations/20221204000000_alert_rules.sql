@@ -0,0 +1,20 @@
+-- Alert rules of the API keys, managed by `/alerts` and evaluated by `alerts::run`
+CREATE TABLE IF NOT EXISTS alert_rules
+(
+    id                   bigserial      NOT NULL PRIMARY KEY,
+    -- hash of the API key which owns the rule, see `api_keys`
+    key_id               text           NOT NULL,
+    -- `FT_BALANCE_BELOW` or `NFT_TRANSFER`
+    kind                 text           NOT NULL,
+    contract_account_id  text           NOT NULL,
+    account_id           text,
+    token_id             text,
+    threshold            numeric(45, 0),
+    webhook_url          text           NOT NULL,
+    created_at_timestamp numeric(20, 0) NOT NULL,
+    -- the balance rules notify once when the balance goes below the threshold,
+    -- and then only after it goes back above
+    is_triggered         boolean        NOT NULL DEFAULT false
+);
+CREATE INDEX IF NOT EXISTS alert_rules_key_id_idx ON alert_rules (key_id);
+CREATE INDEX IF NOT EXISTS alert_rules_contract_account_id_idx ON alert_rules (contract_account_id);
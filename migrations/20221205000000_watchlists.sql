@@ -0,0 +1,13 @@
+-- Named lists of the accounts of the API keys, managed by `/watchlists`
+CREATE TABLE IF NOT EXISTS watchlists
+(
+    id                   bigserial      NOT NULL PRIMARY KEY,
+    -- hash of the API key which owns the list, see `api_keys`
+    key_id               text           NOT NULL,
+    name                 text           NOT NULL,
+    -- the accounts and the contracts in the order they were given
+    account_ids          text[]         NOT NULL,
+    created_at_timestamp numeric(20, 0) NOT NULL,
+    updated_at_timestamp numeric(20, 0) NOT NULL
+);
+CREATE INDEX IF NOT EXISTS watchlists_key_id_idx ON watchlists (key_id);
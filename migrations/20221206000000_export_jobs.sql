@@ -0,0 +1,21 @@
+-- Export jobs of the API keys, created by `/accounts/{account_id}/exports` and processed by `export_worker`
+CREATE TABLE IF NOT EXISTS export_jobs
+(
+    id                    bigserial      NOT NULL PRIMARY KEY,
+    -- hash of the API key which owns the job, see `api_keys`
+    key_id                text           NOT NULL,
+    -- the account of the history or the FT contract of the holders snapshot
+    account_id            text           NOT NULL,
+    -- `FULL_HISTORY`, `HOLDERS_SNAPSHOT` or `TAX_REPORT`
+    kind                  text           NOT NULL,
+    from_timestamp        numeric(20, 0),
+    to_timestamp          numeric(20, 0),
+    -- `PENDING`, `RUNNING`, `SUCCEEDED` or `FAILED`
+    status                text           NOT NULL,
+    error                 text,
+    created_at_timestamp  numeric(20, 0) NOT NULL,
+    -- the running jobs are updated while they go, the stale ones are taken again after the restart
+    updated_at_timestamp  numeric(20, 0) NOT NULL
+);
+CREATE INDEX IF NOT EXISTS export_jobs_key_id_idx ON export_jobs (key_id);
+CREATE INDEX IF NOT EXISTS export_jobs_status_idx ON export_jobs (status) WHERE status IN ('PENDING', 'RUNNING');
@@ -0,0 +1,2 @@
+-- `csv` or `parquet`, see `types::query_params::FileFormat`
+ALTER TABLE export_jobs ADD COLUMN IF NOT EXISTS format text NOT NULL DEFAULT 'csv';
@@ -0,0 +1,2 @@
+//! End-to-end tests for NEAR Enhanced API, see `tests/sandbox.rs`.
+//! The crate itself is empty.
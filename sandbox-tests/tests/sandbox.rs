@@ -0,0 +1,244 @@
+//! End-to-end tests: deploy the reference FT/NFT contracts to the local sandbox,
+//! put the minimal indexer data into the temp DB, and go through the HTTP endpoints.
+//! The responses are parsed with `api_models`, so any serialization regression fails the test.
+//!
+//! Requires `SANDBOX_DATABASE_URL` pointing to the disposable Postgres DB
+//! and the contracts in `res/` (see `res/README.md`).
+#![cfg(feature = "sandbox")]
+
+use actix_web::test;
+use paperclip::actix::OpenApiExt;
+use sqlx::Executor;
+
+use near_enhanced_api::api_models;
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+const FT_WASM: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/res/fungible_token.wasm");
+const NFT_WASM: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/res/non_fungible_token.wasm");
+const STORAGE_DEPOSIT: u128 = 10_000_000_000_000_000_000_000;
+
+struct SandboxBlock {
+    height: u64,
+    timestamp: u64,
+    hash: String,
+}
+
+async fn init_db() -> sqlx::Pool<sqlx::Postgres> {
+    let db_url = std::env::var("SANDBOX_DATABASE_URL").expect("failed to get sandbox database url");
+    let pool = sqlx::PgPool::connect(&db_url)
+        .await
+        .expect("failed to connect to the sandbox database");
+    pool.execute(include_str!("../../tests/fixtures/indexer_schema.sql"))
+        .await
+        .expect("failed to apply the indexer schema");
+    pool
+}
+
+async fn get_block(
+    worker: &near_workspaces::Worker<near_workspaces::network::Sandbox>,
+) -> Result<SandboxBlock, Box<dyn std::error::Error>> {
+    let block = worker.view_block().await?;
+    Ok(SandboxBlock {
+        height: block.height(),
+        timestamp: block.timestamp(),
+        hash: block.hash().to_string(),
+    })
+}
+
+// We don't run the indexer here, so we put only the rows the API needs to answer
+async fn seed_block(pool: &sqlx::Pool<sqlx::Postgres>, block: &SandboxBlock) {
+    sqlx::query(
+        r"INSERT INTO blocks
+          VALUES ($1::numeric(20, 0), $2, $2, $3::numeric(20, 0), 0, 0, 'test.near')
+          ON CONFLICT DO NOTHING",
+    )
+    .bind(block.height.to_string())
+    .bind(&block.hash)
+    .bind(block.timestamp.to_string())
+    .execute(pool)
+    .await
+    .expect("failed to insert the block");
+}
+
+async fn seed_account(pool: &sqlx::Pool<sqlx::Postgres>, account_id: &str, block: &SandboxBlock) {
+    let receipt_id = format!("create_account_{}", account_id);
+    sqlx::query(
+        r"INSERT INTO execution_outcomes
+          VALUES ($1, $2, $3::numeric(20, 0), 0, 0, 0, $4, 'SUCCESS_VALUE', 0)
+          ON CONFLICT DO NOTHING",
+    )
+    .bind(&receipt_id)
+    .bind(&block.hash)
+    .bind(block.timestamp.to_string())
+    .bind(account_id)
+    .execute(pool)
+    .await
+    .expect("failed to insert the execution outcome");
+    sqlx::query(
+        r"INSERT INTO action_receipt_actions
+          VALUES ($1, 0, 'CREATE_ACCOUNT', '{}', $2, $2, $3::numeric(20, 0))
+          ON CONFLICT DO NOTHING",
+    )
+    .bind(&receipt_id)
+    .bind(account_id)
+    .bind(block.timestamp.to_string())
+    .execute(pool)
+    .await
+    .expect("failed to insert the action");
+}
+
+fn build_context(
+    pool: sqlx::Pool<sqlx::Postgres>,
+    worker: &near_workspaces::Worker<near_workspaces::network::Sandbox>,
+) -> near_enhanced_api::ServerContext {
+    near_enhanced_api::ServerContext::builder()
+        .pool(pool)
+        .rpc_client(near_jsonrpc_client::JsonRpcClient::connect(
+            &worker.rpc_addr(),
+        ))
+        .build()
+        .expect("failed to build the server context")
+}
+
+#[tokio::test]
+async fn test_ft_endpoints() -> TestResult {
+    let pool = init_db().await;
+    let worker = near_workspaces::sandbox().await?;
+    let contract = worker.dev_deploy(&std::fs::read(FT_WASM)?).await?;
+    let user = worker.dev_create_account().await?;
+
+    let outcome = contract
+        .call("new_default_meta")
+        .args_json(serde_json::json!({ "owner_id": contract.id(), "total_supply": "1000000" }))
+        .transact()
+        .await?;
+    assert!(outcome.is_success(), "{:?}", outcome);
+    let outcome = user
+        .call(contract.id(), "storage_deposit")
+        .args_json(serde_json::json!({}))
+        .deposit(STORAGE_DEPOSIT)
+        .transact()
+        .await?;
+    assert!(outcome.is_success(), "{:?}", outcome);
+    let outcome = contract
+        .call("ft_transfer")
+        .args_json(serde_json::json!({ "receiver_id": user.id(), "amount": "100" }))
+        .deposit(1)
+        .transact()
+        .await?;
+    assert!(outcome.is_success(), "{:?}", outcome);
+
+    let block = get_block(&worker).await?;
+    seed_block(&pool, &block).await;
+    seed_account(&pool, user.id().as_str(), &block).await;
+
+    let ctx = build_context(pool, &worker);
+    let app = test::init_service(
+        actix_web::App::new()
+            .wrap_api()
+            .configure(|app| near_enhanced_api::configure(app, &ctx))
+            .build(),
+    )
+    .await;
+
+    let metadata: api_models::coin::FtContractMetadataResponse = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get()
+            .uri(&format!(
+                "/nep141/metadata/{}?block_height={}",
+                contract.id(),
+                block.height
+            ))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(metadata.metadata.spec, "ft-1.0.0");
+    assert_eq!(metadata.meta.block_height.0, block.height);
+    assert_eq!(metadata.meta.block_hash, block.hash);
+
+    let balances: api_models::coin::CoinBalancesResponse = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get()
+            .uri(&format!(
+                "/accounts/{}/coins/{}?block_height={}",
+                user.id(),
+                contract.id(),
+                block.height
+            ))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(balances.balances.len(), 1);
+    assert_eq!(balances.balances[0].balance.0, 100);
+    assert_eq!(balances.balances[0].standard, "nep141");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_nft_endpoints() -> TestResult {
+    let pool = init_db().await;
+    let worker = near_workspaces::sandbox().await?;
+    let contract = worker.dev_deploy(&std::fs::read(NFT_WASM)?).await?;
+    let user = worker.dev_create_account().await?;
+
+    let outcome = contract
+        .call("new_default_meta")
+        .args_json(serde_json::json!({ "owner_id": contract.id() }))
+        .transact()
+        .await?;
+    assert!(outcome.is_success(), "{:?}", outcome);
+    let outcome = contract
+        .call("nft_mint")
+        .args_json(serde_json::json!({
+            "token_id": "1",
+            "receiver_id": user.id(),
+            "token_metadata": { "title": "Sandbox token", "copies": 1 },
+        }))
+        .deposit(STORAGE_DEPOSIT)
+        .transact()
+        .await?;
+    assert!(outcome.is_success(), "{:?}", outcome);
+
+    let block = get_block(&worker).await?;
+    seed_block(&pool, &block).await;
+    seed_account(&pool, user.id().as_str(), &block).await;
+
+    let ctx = build_context(pool, &worker);
+    let app = test::init_service(
+        actix_web::App::new()
+            .wrap_api()
+            .configure(|app| near_enhanced_api::configure(app, &ctx))
+            .build(),
+    )
+    .await;
+
+    let metadata: api_models::nft::MetadataResponse = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get()
+            .uri(&format!(
+                "/nep171/metadata/{}?block_height={}",
+                contract.id(),
+                block.height
+            ))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(metadata.metadata.unwrap().spec, "nft-1.0.0");
+    assert!(metadata.standards_missing.is_empty());
+
+    let nft: api_models::nft::NftResponse = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get()
+            .uri(&format!(
+                "/NFT/{}/1?block_height={}",
+                contract.id(),
+                block.height
+            ))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(nft.nft.owner_account_id, user.id().to_string());
+    assert_eq!(nft.nft.metadata.title, Some("Sandbox token".to_string()));
+    Ok(())
+}
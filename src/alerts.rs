@@ -0,0 +1,272 @@
+//! Evaluates the alert rules (see `/alerts`) against the new FT and NFT events
+//! and sends the notifications to the webhooks.
+use std::collections::HashSet;
+
+use crate::db_helpers;
+use crate::modules::alerts::{data_provider, schemas};
+use crate::modules::coin;
+
+/// If more events arrive between the checks, the rest are skipped with the warning
+const MAX_EVENTS_PER_CHECK: u32 = 10_000;
+const WEBHOOK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+const WEBHOOK_ATTEMPTS: u32 = 3;
+
+/// Never returns. Run it in the separate task, e.g. with `tokio::spawn`.
+/// `pool` is the indexer DB, `pool_aux` has `alert_rules` table (see `migrations/`).
+/// Run it on the one instance only, otherwise the notifications are duplicated
+pub async fn run(
+    pool: sqlx::Pool<sqlx::Postgres>,
+    pool_aux: sqlx::Pool<sqlx::Postgres>,
+    rpc_client: near_jsonrpc_client::JsonRpcClient,
+    check_interval: std::time::Duration,
+) {
+    let http = reqwest::Client::builder()
+        .timeout(WEBHOOK_TIMEOUT)
+        .build()
+        .expect("failed to build the webhook HTTP client");
+    let mut interval = tokio::time::interval(check_interval);
+    // The events which happened before the start are not checked
+    let mut checked_until: Option<u64> = None;
+    loop {
+        interval.tick().await;
+        match check_alerts(&pool, &pool_aux, &rpc_client, &http, checked_until).await {
+            Ok(timestamp) => checked_until = Some(timestamp),
+            Err(err) => tracing::warn!(
+                target: crate::LOGGER_MSG,
+                "Failed to check the alerts: {}",
+                err
+            ),
+        }
+    }
+}
+
+/// Returns the timestamp of the last checked block
+async fn check_alerts(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    rpc_client: &near_jsonrpc_client::JsonRpcClient,
+    http: &reqwest::Client,
+    checked_until: Option<u64>,
+) -> crate::Result<u64> {
+    let block = db_helpers::get_last_block(pool).await?;
+    let from_timestamp = match checked_until {
+        Some(timestamp) if timestamp < block.timestamp => timestamp,
+        Some(timestamp) => return Ok(timestamp),
+        None => return Ok(block.timestamp),
+    };
+
+    let alerts = data_provider::get_all_alerts(pool_aux).await?;
+    let (ft_alerts, nft_alerts): (Vec<_>, Vec<_>) = alerts
+        .into_iter()
+        .partition(|alert| alert.rule.kind == schemas::AlertKind::FtBalanceBelow);
+    if !nft_alerts.is_empty() {
+        check_nft_transfers(pool, http, &nft_alerts, from_timestamp, block.timestamp).await?;
+    }
+    if !ft_alerts.is_empty() {
+        check_ft_balances(
+            pool,
+            pool_aux,
+            rpc_client,
+            http,
+            &ft_alerts,
+            from_timestamp,
+            &block,
+        )
+        .await?;
+    }
+    Ok(block.timestamp)
+}
+
+async fn check_nft_transfers(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    http: &reqwest::Client,
+    alerts: &[schemas::Alert],
+    from_timestamp: u64,
+    to_timestamp: u64,
+) -> crate::Result<()> {
+    let events = data_provider::get_nft_events(
+        pool,
+        &contract_ids(alerts),
+        from_timestamp,
+        to_timestamp,
+        MAX_EVENTS_PER_CHECK,
+    )
+    .await?;
+    warn_if_truncated(events.len());
+
+    for event in events {
+        for alert in alerts {
+            let rule = &alert.rule;
+            if rule.contract_account_id.0.as_str() != event.contract_account_id
+                || rule
+                    .token_id
+                    .as_ref()
+                    .map_or(false, |token_id| *token_id != event.token_id)
+            {
+                continue;
+            }
+            send_notification(
+                http,
+                &rule.webhook_url,
+                schemas::AlertNotification {
+                    alert_id: alert.alert_id,
+                    kind: rule.kind,
+                    contract_account_id: rule.contract_account_id.clone(),
+                    account_id: event
+                        .new_owner_id
+                        .clone()
+                        .map(|account_id| account_id.into()),
+                    token_id: Some(event.token_id.clone()),
+                    balance: None,
+                    threshold: None,
+                    receipt_id: Some(event.receipt_id.clone()),
+                    block_timestamp_nanos: event.block_timestamp.into(),
+                    block_height: event.block_height.into(),
+                    block_hash: event.block_hash.clone(),
+                },
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn check_ft_balances(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    rpc_client: &near_jsonrpc_client::JsonRpcClient,
+    http: &reqwest::Client,
+    alerts: &[schemas::Alert],
+    from_timestamp: u64,
+    block: &db_helpers::Block,
+) -> crate::Result<()> {
+    let changed_accounts: HashSet<(String, String)> = data_provider::get_ft_event_accounts(
+        pool,
+        &contract_ids(alerts),
+        from_timestamp,
+        block.timestamp,
+        MAX_EVENTS_PER_CHECK,
+    )
+    .await?
+    .into_iter()
+    .collect();
+    warn_if_truncated(changed_accounts.len());
+
+    for alert in alerts {
+        let rule = &alert.rule;
+        let (account_id, threshold) = match (&rule.account_id, rule.threshold) {
+            (Some(account_id), Some(threshold)) => (account_id, threshold),
+            _ => continue,
+        };
+        // The new alerts are checked once even without the events
+        let is_new = alert.created_at_timestamp_nanos.0 > from_timestamp;
+        let is_changed = changed_accounts.contains(&(
+            rule.contract_account_id.0.to_string(),
+            account_id.0.to_string(),
+        ));
+        if !is_new && !is_changed {
+            continue;
+        }
+
+        // One broken contract should not stop the others
+        let balance = match coin::data_provider::get_ft_balance_by_contract(
+            rpc_client,
+            rule.contract_account_id.0.clone(),
+            account_id.0.clone(),
+            block.height,
+        )
+        .await
+        {
+            Ok(balance) => balance,
+            Err(err) => {
+                tracing::warn!(
+                    target: crate::LOGGER_MSG,
+                    "Failed to check the balance of {} for alert {}: {}",
+                    account_id.0,
+                    alert.alert_id.0,
+                    err
+                );
+                continue;
+            }
+        };
+        let is_below = balance < threshold.0;
+        if is_below == alert.is_triggered {
+            continue;
+        }
+        data_provider::set_alert_triggered(pool_aux, alert.alert_id.0, is_below).await?;
+        if is_below {
+            send_notification(
+                http,
+                &rule.webhook_url,
+                schemas::AlertNotification {
+                    alert_id: alert.alert_id,
+                    kind: rule.kind,
+                    contract_account_id: rule.contract_account_id.clone(),
+                    account_id: Some(account_id.clone()),
+                    token_id: None,
+                    balance: Some(balance.into()),
+                    threshold: Some(threshold),
+                    receipt_id: None,
+                    block_timestamp_nanos: block.timestamp.into(),
+                    block_height: block.height.into(),
+                    block_hash: block.hash.clone(),
+                },
+            );
+        }
+    }
+    Ok(())
+}
+
+fn contract_ids(alerts: &[schemas::Alert]) -> Vec<String> {
+    let contract_ids: HashSet<String> = alerts
+        .iter()
+        .map(|alert| alert.rule.contract_account_id.0.to_string())
+        .collect();
+    contract_ids.into_iter().collect()
+}
+
+fn warn_if_truncated(events_count: usize) {
+    if events_count >= MAX_EVENTS_PER_CHECK as usize {
+        tracing::warn!(
+            target: crate::LOGGER_MSG,
+            "More than {} alert events between the checks, some of them are skipped. \
+             Decrease ALERTS_CHECK_INTERVAL_SECS",
+            MAX_EVENTS_PER_CHECK
+        );
+    }
+}
+
+/// The webhook is called in the background, so the slow webhooks don't delay the others
+fn send_notification(
+    http: &reqwest::Client,
+    webhook_url: &str,
+    notification: schemas::AlertNotification,
+) {
+    let http = http.clone();
+    let webhook_url = webhook_url.to_string();
+    tokio::spawn(async move {
+        for attempt in 1..=WEBHOOK_ATTEMPTS {
+            let result = http
+                .post(&webhook_url)
+                .json(&notification)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            match result {
+                Ok(_) => return,
+                Err(err) => {
+                    tracing::warn!(
+                        target: crate::LOGGER_MSG,
+                        "Failed to send alert {} to the webhook (attempt {}/{}): {}",
+                        notification.alert_id.0,
+                        attempt,
+                        WEBHOOK_ATTEMPTS,
+                        err
+                    );
+                    if attempt < WEBHOOK_ATTEMPTS {
+                        tokio::time::sleep(std::time::Duration::from_secs(2u64.pow(attempt))).await;
+                    }
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,182 @@
+//! Allow-list mode for the curated deployments (e.g. the regulated or kid-friendly products):
+//! the coin and NFT endpoints serve only the listed contracts, NEAR is always served.
+//! The endpoints about one contract answer `CONTRACT_NOT_ALLOWED` for the rest, the middleware wraps
+//! their resources because `contract_account_id` is known only after the routing.
+//! The lists (balances, NFT overview, bulk metadata, diffs) omit the other contracts in the handlers.
+//! The initial list is taken from `ALLOWED_CONTRACTS`, `/admin/contracts/allowed` changes it.
+//! The list lives in memory, each instance behind the load balancer should be changed separately.
+use std::collections::BTreeSet;
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use futures::future::{ready, LocalBoxFuture, Ready};
+
+use crate::errors;
+
+/// The path segment of the contract, the same in all the coin and NFT routes
+const CONTRACT_PATH_PARAM: &str = "contract_account_id";
+
+/// `None` if the mode is off and all the contracts are served
+type AllowedContracts =
+    std::sync::Arc<std::sync::RwLock<Option<BTreeSet<near_primitives::types::AccountId>>>>;
+
+/// Create it once and clone to all the workers and to the admin endpoints, so they share the list
+#[derive(Clone, Default)]
+pub struct ContractAllowList {
+    allowed: AllowedContracts,
+}
+
+impl ContractAllowList {
+    /// Ordered by account id, `None` if all the contracts are served
+    pub fn allowed_contracts(&self) -> Option<Vec<near_primitives::types::AccountId>> {
+        self.allowed
+            .read()
+            .expect("contract allow-list lock is poisoned")
+            .as_ref()
+            .map(|allowed| allowed.iter().cloned().collect())
+    }
+
+    /// Replaces the whole list. `None` serves all the contracts, the empty list serves only NEAR
+    pub fn set_allowed_contracts(
+        &self,
+        contract_ids: Option<Vec<near_primitives::types::AccountId>>,
+    ) {
+        *self
+            .allowed
+            .write()
+            .expect("contract allow-list lock is poisoned") =
+            contract_ids.map(|contract_ids| contract_ids.into_iter().collect());
+    }
+
+    pub fn is_allowed(&self, contract_id: &near_primitives::types::AccountId) -> bool {
+        self.allowed
+            .read()
+            .expect("contract allow-list lock is poisoned")
+            .as_ref()
+            .map_or(true, |allowed| allowed.contains(contract_id))
+    }
+
+    /// Drops the items of the contracts which are not served, `None` is NEAR
+    pub(crate) fn retain<T>(
+        &self,
+        items: &mut Vec<T>,
+        contract_id: impl Fn(&T) -> Option<&near_primitives::types::AccountId>,
+    ) {
+        items.retain(|item| {
+            contract_id(item).map_or(true, |contract_id| self.is_allowed(contract_id))
+        });
+    }
+
+    fn check_path(&self, req: &ServiceRequest) -> crate::Result<()> {
+        let contract_id = match req.match_info().get(CONTRACT_PATH_PARAM) {
+            Some(contract_id) => contract_id,
+            None => return Ok(()),
+        };
+        // The invalid account ids are rejected by the handlers
+        match contract_id.parse::<near_primitives::types::AccountId>() {
+            Ok(contract_id) if !self.is_allowed(&contract_id) => {
+                Err(errors::ErrorKind::ContractNotAllowed {
+                    contract_id: contract_id.to_string(),
+                }
+                .into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ContractAllowList
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = ContractAllowListMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ContractAllowListMiddleware {
+            service,
+            allow_list: self.clone(),
+        }))
+    }
+}
+
+pub struct ContractAllowListMiddleware<S> {
+    service: S,
+    allow_list: ContractAllowList,
+}
+
+impl<S, B> Service<ServiceRequest> for ContractAllowListMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if let Err(err) = self.allow_list.check_path(&req) {
+            return Box::pin(ready(Ok(req.error_response(err))));
+        }
+        let response = self.service.call(req);
+        Box::pin(async move { Ok(response.await?.map_into_boxed_body()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_contract_allow_list() {
+        let allow_list = ContractAllowList::default();
+        let app = actix_web::test::init_service(
+            actix_web::App::new().service(
+                actix_web::web::resource("/nep141/metadata/{contract_account_id}")
+                    .wrap(allow_list.clone())
+                    .to(|| async { actix_web::HttpResponse::Ok().finish() }),
+            ),
+        )
+        .await;
+        let status = |path: &'static str| {
+            let app = &app;
+            async move {
+                let request = actix_web::test::TestRequest::get().uri(path).to_request();
+                actix_web::test::call_service(app, request).await.status()
+            }
+        };
+
+        // Everything is served by default
+        assert_eq!(status("/nep141/metadata/usn").await, 200);
+
+        allow_list.set_allowed_contracts(Some(vec!["wrap.near".parse().unwrap()]));
+        assert_eq!(status("/nep141/metadata/wrap.near").await, 200);
+        let response = actix_web::test::call_service(
+            &app,
+            actix_web::test::TestRequest::get()
+                .uri("/nep141/metadata/usn")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), 403);
+        let error: errors::Error = actix_web::test::read_body_json(response).await;
+        assert_eq!(error.error_code, "CONTRACT_NOT_ALLOWED");
+
+        let mut coins: Vec<Option<near_primitives::types::AccountId>> = vec![
+            None,
+            Some("usn".parse().unwrap()),
+            Some("wrap.near".parse().unwrap()),
+        ];
+        allow_list.retain(&mut coins, |coin| coin.as_ref());
+        assert_eq!(coins, vec![None, Some("wrap.near".parse().unwrap())]);
+
+        allow_list.set_allowed_contracts(None);
+        assert_eq!(status("/nep141/metadata/usn").await, 200);
+    }
+}
@@ -0,0 +1,88 @@
+//! API keys are sent in `X-API-Key` header or as `Authorization: Bearer <key>`.
+//! We never store the keys, only their hashes (`key_id`).
+use actix_web::http::header;
+
+use crate::errors;
+
+pub(crate) const API_KEY_HEADER: &str = "X-API-Key";
+/// `key_id` of the requests without the API key
+pub(crate) const ANONYMOUS_KEY_ID: &str = "anonymous";
+/// Admin requests are not billed and not limited
+pub(crate) const ADMIN_PATH_SEGMENT: &str = "/admin/";
+
+pub(crate) fn get_api_key(headers: &header::HeaderMap) -> Option<&str> {
+    headers
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .or_else(|| get_bearer_token(headers))
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+}
+
+pub(crate) fn get_bearer_token(headers: &header::HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+pub(crate) fn get_key_id(headers: &header::HeaderMap) -> String {
+    match get_api_key(headers) {
+        Some(api_key) => near_primitives::hash::hash(api_key.as_bytes()).to_string(),
+        None => ANONYMOUS_KEY_ID.to_string(),
+    }
+}
+
+/// `key_id` of the owner of the per-key resources (`what`, e.g. "the alerts"),
+/// the anonymous requests can't have them
+pub(crate) fn require_key_id(headers: &header::HeaderMap, what: &str) -> crate::Result<String> {
+    if get_api_key(headers).is_none() {
+        return Err(
+            errors::ErrorKind::Unauthorized(format!("{} require the API key", what)).into(),
+        );
+    }
+    Ok(get_key_id(headers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_key_id() {
+        let mut headers = header::HeaderMap::new();
+        assert_eq!(get_key_id(&headers), ANONYMOUS_KEY_ID);
+
+        headers.insert(
+            header::AUTHORIZATION,
+            header::HeaderValue::from_static("Bearer secret"),
+        );
+        let bearer_key_id = get_key_id(&headers);
+        assert_ne!(bearer_key_id, ANONYMOUS_KEY_ID);
+        assert!(!bearer_key_id.contains("secret"));
+
+        // `X-API-Key` goes first, but the same key gives the same id
+        headers.insert(
+            header::HeaderName::from_static("x-api-key"),
+            header::HeaderValue::from_static("secret"),
+        );
+        assert_eq!(get_key_id(&headers), bearer_key_id);
+    }
+
+    #[test]
+    fn test_require_key_id() {
+        let mut headers = header::HeaderMap::new();
+        let error = require_key_id(&headers, "the alerts").unwrap_err();
+        assert_eq!(error.code, 401);
+        assert!(error.message.contains("the alerts require the API key"));
+
+        headers.insert(
+            header::HeaderName::from_static("x-api-key"),
+            header::HeaderValue::from_static("secret"),
+        );
+        assert_eq!(
+            require_key_id(&headers, "the alerts").unwrap(),
+            get_key_id(&headers)
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! Actix middleware which writes the append-only audit trail of the admin requests and the signed
+//! requests to `audit_log` table: who (the hash of the API key or of the admin token), when,
+//! which endpoint, with which params and body, and the response status.
+//! The secrets (the headers) are never written.
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use futures::future::{ready, LocalBoxFuture, Ready};
+
+use crate::{api_keys, client_ip, signing, LOGGER_MSG};
+
+/// The bigger bodies are truncated, we don't expect them on the admin endpoints anyway
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+struct AuditRecord {
+    timestamp: u64,
+    actor_key_id: String,
+    method: String,
+    path: String,
+    query: String,
+    body: Option<String>,
+    client_ip: Option<String>,
+}
+
+/// Create it once and clone to all the workers
+#[derive(Clone, Default)]
+pub struct AuditLogger {
+    /// `None` if the audit log is disabled
+    pool: Option<sqlx::Pool<sqlx::Postgres>>,
+}
+
+impl AuditLogger {
+    /// Passes all the requests through
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn new(pool: sqlx::Pool<sqlx::Postgres>) -> Self {
+        Self { pool: Some(pool) }
+    }
+}
+
+fn is_audited(req: &ServiceRequest) -> bool {
+    req.path().contains(api_keys::ADMIN_PATH_SEGMENT)
+        || req.headers().contains_key(signing::SIGNATURE_HEADER)
+}
+
+/// The body is read by us, so we put it back for the handler
+pub(crate) fn bytes_to_payload(bytes: actix_web::web::Bytes) -> actix_web::dev::Payload {
+    let (_, mut payload) = actix_http::h1::Payload::create(true);
+    payload.unread_data(bytes);
+    actix_web::dev::Payload::from(payload)
+}
+
+async fn write_record(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    record: AuditRecord,
+    endpoint: &str,
+    status: u16,
+) {
+    let result = sqlx::query(
+        r"INSERT INTO audit_log (timestamp, actor_key_id, method, endpoint, path, query, body, status, client_ip)
+          VALUES ($1::numeric(20, 0), $2, $3, $4, $5, $6, $7, $8, $9)",
+    )
+    .bind(record.timestamp.to_string())
+    .bind(&record.actor_key_id)
+    .bind(&record.method)
+    .bind(endpoint)
+    .bind(&record.path)
+    .bind(&record.query)
+    .bind(&record.body)
+    .bind(i32::from(status))
+    .bind(&record.client_ip)
+    .execute(pool)
+    .await;
+    if let Err(err) = result {
+        tracing::error!(
+            target: LOGGER_MSG,
+            "Failed to write the audit record of {} {} by {}: {:#?}",
+            record.method,
+            record.path,
+            record.actor_key_id,
+            err
+        );
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for AuditLogger
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = AuditLoggerMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AuditLoggerMiddleware {
+            service: std::rc::Rc::new(service),
+            pool: self.pool.clone(),
+        }))
+    }
+}
+
+pub struct AuditLoggerMiddleware<S> {
+    service: std::rc::Rc<S>,
+    pool: Option<sqlx::Pool<sqlx::Postgres>>,
+}
+
+impl<S, B> Service<ServiceRequest> for AuditLoggerMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let pool = match &self.pool {
+            Some(pool) if is_audited(&req) => pool.clone(),
+            _ => {
+                let response = self.service.call(req);
+                return Box::pin(async move { Ok(response.await?.map_into_boxed_body()) });
+            }
+        };
+
+        let service = self.service.clone();
+        Box::pin(async move {
+            let body = req.extract::<actix_web::web::Bytes>().await?;
+            let mut record = AuditRecord {
+                timestamp: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|duration| duration.as_nanos() as u64)
+                    .unwrap_or_default(),
+                actor_key_id: api_keys::get_key_id(req.headers()),
+                method: req.method().to_string(),
+                path: req.path().to_string(),
+                query: req.query_string().to_string(),
+                body: None,
+                client_ip: client_ip::get_client_ip(&req).map(|ip| ip.to_string()),
+            };
+            if !body.is_empty() {
+                let stored_size = std::cmp::min(body.len(), MAX_BODY_SIZE);
+                record.body = Some(String::from_utf8_lossy(&body[..stored_size]).into_owned());
+                req.set_payload(bytes_to_payload(body));
+            }
+
+            let response = service.call(req).await?;
+            let endpoint = response
+                .request()
+                .match_pattern()
+                .unwrap_or_else(|| record.path.clone());
+            write_record(&pool, record, &endpoint, response.status().as_u16()).await;
+            Ok(response.map_into_boxed_body())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+
+    #[actix_web::test]
+    async fn test_audit_logger_seeded() {
+        let db = init_seeded_db().await;
+        let app = actix_web::test::init_service(
+            actix_web::App::new()
+                .wrap(AuditLogger::new(db.pool.clone()))
+                .route(
+                    "/admin/keys/{key_id}/quota",
+                    actix_web::web::put().to(|body: String| async move { body }),
+                )
+                .route(
+                    "/accounts/{account_id}",
+                    actix_web::web::get().to(|| async { "not audited" }),
+                ),
+        )
+        .await;
+
+        let request = actix_web::test::TestRequest::put()
+            .uri("/admin/keys/key_a/quota?dry_run=true")
+            .insert_header(("Authorization", "Bearer admin_secret"))
+            .set_payload(r#"{"daily_requests":"10"}"#)
+            .to_request();
+        // The handler still gets the body
+        let body = actix_web::test::call_and_read_body(&app, request).await;
+        assert_eq!(body, r#"{"daily_requests":"10"}"#);
+        let request = actix_web::test::TestRequest::get()
+            .uri("/accounts/alice.near")
+            .to_request();
+        actix_web::test::call_service(&app, request).await;
+
+        let records: Vec<(String, String, String, String, Option<String>, i32)> = sqlx::query_as(
+            "SELECT actor_key_id, endpoint, path, query, body, status FROM audit_log ORDER BY id",
+        )
+        .fetch_all(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!(records.len(), 1);
+        let (actor_key_id, endpoint, path, query, body, status) = records[0].clone();
+        assert_ne!(actor_key_id, api_keys::ANONYMOUS_KEY_ID);
+        assert!(!actor_key_id.contains("admin_secret"));
+        assert_eq!(endpoint, "/admin/keys/{key_id}/quota");
+        assert_eq!(path, "/admin/keys/key_a/quota");
+        assert_eq!(query, "dry_run=true");
+        assert_eq!(body.as_deref(), Some(r#"{"daily_requests":"10"}"#));
+        assert_eq!(status, 200);
+    }
+}
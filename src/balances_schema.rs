@@ -0,0 +1,159 @@
+//! The balances DB schema changed between the indexer versions. We detect the version on startup,
+//! the queries read `balance_changes` through the table expression of the detected version,
+//! so the same query works against the old and the new deployments.
+use crate::errors;
+
+const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+/// `balance_changes` is partitioned by `block_timestamp` in the big deployments. The row comparison
+/// of the pagination cursor does not prune the partitions, so the history pages are searched in these windows
+/// going back from the cursor: the active accounts touch only the newest partitions, the rest go further
+const HISTORY_SEARCH_WINDOWS_DAYS: &[u64] = &[1, 7, 30, 365];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BalancesSchema {
+    /// The first deployments: `delta_liquid_amount`, `absolute_liquid_amount`,
+    /// `delta_locked_amount`, `absolute_locked_amount`
+    Legacy,
+    /// `delta_nonstaked_amount`, `absolute_nonstaked_amount`, `delta_staked_amount`, `absolute_staked_amount`
+    Current,
+}
+
+impl Default for BalancesSchema {
+    fn default() -> Self {
+        Self::Current
+    }
+}
+
+/// The legacy columns renamed to the current ones. Postgres inlines the subquery,
+/// so the indexes on `balance_changes` are still used
+const LEGACY_BALANCE_CHANGES: &str = r"(
+    SELECT
+        block_timestamp,
+        receipt_id,
+        transaction_hash,
+        affected_account_id,
+        involved_account_id,
+        direction,
+        cause,
+        status,
+        delta_liquid_amount delta_nonstaked_amount,
+        absolute_liquid_amount absolute_nonstaked_amount,
+        delta_locked_amount delta_staked_amount,
+        absolute_locked_amount absolute_staked_amount,
+        shard_id,
+        index_in_chunk
+    FROM balance_changes
+) balance_changes";
+
+/// Looks at `balance_changes` columns
+pub async fn detect(pool: &sqlx::Pool<sqlx::Postgres>) -> crate::Result<BalancesSchema> {
+    let columns: Vec<(String,)> = sqlx::query_as(
+        r"SELECT column_name::text
+          FROM information_schema.columns
+          WHERE table_name = 'balance_changes'
+              AND table_schema = current_schema()",
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|err| errors::ErrorKind::DBError(err.to_string()))?;
+    let has_column = |name: &str| columns.iter().any(|(column,)| column == name);
+    if has_column("delta_nonstaked_amount") {
+        Ok(BalancesSchema::Current)
+    } else if has_column("delta_liquid_amount") {
+        Ok(BalancesSchema::Legacy)
+    } else {
+        Err(errors::ErrorKind::DBError(
+            "balance_changes table is missing or has unknown schema".to_string(),
+        )
+        .into())
+    }
+}
+
+/// Balances DB with its schema version
+#[derive(Debug, Clone)]
+pub(crate) struct BalancesDb {
+    pub pool: sqlx::Pool<sqlx::Postgres>,
+    pub schema: BalancesSchema,
+}
+
+impl BalancesDb {
+    pub(crate) fn new(pool: sqlx::Pool<sqlx::Postgres>, schema: BalancesSchema) -> Self {
+        Self { pool, schema }
+    }
+
+    /// Use it instead of `balance_changes` in the queries: the table with the current columns
+    pub(crate) fn balance_changes(&self) -> &'static str {
+        match self.schema {
+            BalancesSchema::Legacy => LEGACY_BALANCE_CHANGES,
+            BalancesSchema::Current => "balance_changes",
+        }
+    }
+}
+
+/// Timestamp ranges `(after, until]` which cover everything up to `until`, the newest go first.
+/// The last one is unbounded
+pub(crate) fn history_search_ranges(until: u64) -> Vec<(u64, u64)> {
+    let mut ranges = vec![];
+    let mut upper = until;
+    for days in HISTORY_SEARCH_WINDOWS_DAYS {
+        let window = days * DAY_NANOS;
+        if upper <= window {
+            break;
+        }
+        ranges.push((upper - window, upper));
+        upper -= window;
+    }
+    ranges.push((0, upper));
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+
+    #[test]
+    fn test_history_search_ranges() {
+        let until = 1600000000000000000;
+        let ranges = history_search_ranges(until);
+        assert_eq!(ranges.len(), HISTORY_SEARCH_WINDOWS_DAYS.len() + 1);
+        assert_eq!(ranges[0], (until - DAY_NANOS, until));
+        assert_eq!(ranges[1], (until - 8 * DAY_NANOS, until - DAY_NANOS));
+        assert_eq!(ranges.last().unwrap().0, 0);
+        // No gaps between the windows
+        for pair in ranges.windows(2) {
+            assert_eq!(pair[0].0, pair[1].1);
+        }
+
+        assert_eq!(history_search_ranges(DAY_NANOS), vec![(0, DAY_NANOS)]);
+    }
+
+    #[tokio::test]
+    async fn test_detect_legacy_schema_seeded() {
+        let db = init_seeded_db().await;
+        assert_eq!(detect(&db.pool).await.unwrap(), BalancesSchema::Current);
+
+        sqlx::Executor::execute(
+            &db.pool,
+            r"ALTER TABLE balance_changes RENAME COLUMN delta_nonstaked_amount TO delta_liquid_amount;
+              ALTER TABLE balance_changes RENAME COLUMN absolute_nonstaked_amount TO absolute_liquid_amount;
+              ALTER TABLE balance_changes RENAME COLUMN delta_staked_amount TO delta_locked_amount;
+              ALTER TABLE balance_changes RENAME COLUMN absolute_staked_amount TO absolute_locked_amount",
+        )
+        .await
+        .unwrap();
+        assert_eq!(detect(&db.pool).await.unwrap(), BalancesSchema::Legacy);
+
+        // The same query reads both versions
+        let balances_db = BalancesDb::new(db.pool.clone(), BalancesSchema::Legacy);
+        let query = format!(
+            "SELECT sum(delta_nonstaked_amount + delta_staked_amount)::text FROM {}",
+            balances_db.balance_changes()
+        );
+        let (sum,): (Option<String>,) = sqlx::query_as(&query)
+            .fetch_one(&balances_db.pool)
+            .await
+            .unwrap();
+        assert!(sum.is_some());
+    }
+}
@@ -0,0 +1,162 @@
+//! Replays the recorded traffic against the running instance and prints the latencies.
+//!
+//! `cargo run --release --bin loadgen -- <base_url> <traffic_file> [concurrency] [total_requests]`
+//!
+//! The traffic file has one request per line: the path with the query, optionally prefixed with the method
+//! (`GET` by default), e.g. `GET /accounts/near/coins/NEAR`. Empty lines and `#` comments are skipped.
+//! The lines are replayed in order and looped until `total_requests` (the number of lines by default) are sent.
+//! Set `API_KEY` to send it in `X-API-Key` header.
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const DEFAULT_CONCURRENCY: usize = 10;
+
+struct Request {
+    method: reqwest::Method,
+    path: String,
+}
+
+#[derive(Default)]
+struct Stats {
+    latencies: Vec<Duration>,
+    statuses: std::collections::BTreeMap<String, usize>,
+}
+
+fn parse_traffic(content: &str) -> Vec<Request> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| match line.split_once(' ') {
+            Some((method, path)) => Request {
+                method: method.parse().expect("invalid method in the traffic file"),
+                path: path.trim().to_string(),
+            },
+            None => Request {
+                method: reqwest::Method::GET,
+                path: line.to_string(),
+            },
+        })
+        .collect()
+}
+
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    sorted[(sorted.len() * percent / 100).min(sorted.len() - 1)]
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 3 {
+        eprintln!(
+            "Usage: {} <base_url> <traffic_file> [concurrency] [total_requests]",
+            args[0]
+        );
+        std::process::exit(2);
+    }
+    let base_url = args[1].trim_end_matches('/').to_string();
+    let content = std::fs::read_to_string(&args[2]).expect("failed to read the traffic file");
+    let traffic = Arc::new(parse_traffic(&content));
+    assert!(!traffic.is_empty(), "the traffic file is empty");
+    let concurrency: usize = args
+        .get(3)
+        .map(|value| value.parse().expect("invalid concurrency"))
+        .unwrap_or(DEFAULT_CONCURRENCY);
+    let total_requests: usize = args
+        .get(4)
+        .map(|value| value.parse().expect("invalid total_requests"))
+        .unwrap_or(traffic.len());
+
+    let mut headers = reqwest::header::HeaderMap::new();
+    if let Ok(api_key) = std::env::var("API_KEY") {
+        headers.insert(
+            "x-api-key",
+            api_key.parse().expect("invalid API_KEY header value"),
+        );
+    }
+    let client = reqwest::Client::builder()
+        .default_headers(headers)
+        .build()
+        .expect("failed to build HTTP client");
+
+    let next_request = Arc::new(AtomicUsize::new(0));
+    let started = Instant::now();
+    let workers: Vec<_> = (0..concurrency)
+        .map(|_| {
+            let (client, base_url, traffic, next_request) = (
+                client.clone(),
+                base_url.clone(),
+                traffic.clone(),
+                next_request.clone(),
+            );
+            tokio::spawn(async move {
+                let mut stats = Stats::default();
+                loop {
+                    let i = next_request.fetch_add(1, Ordering::Relaxed);
+                    if i >= total_requests {
+                        return stats;
+                    }
+                    let request = &traffic[i % traffic.len()];
+                    let request_started = Instant::now();
+                    let status = match client
+                        .request(
+                            request.method.clone(),
+                            format!("{}{}", base_url, request.path),
+                        )
+                        .send()
+                        .await
+                    {
+                        // The body is a part of the latency
+                        Ok(response) => {
+                            let status = response.status();
+                            match response.bytes().await {
+                                Ok(_) => status.as_u16().to_string(),
+                                Err(_) => "body error".to_string(),
+                            }
+                        }
+                        Err(_) => "connection error".to_string(),
+                    };
+                    stats.latencies.push(request_started.elapsed());
+                    *stats.statuses.entry(status).or_default() += 1;
+                }
+            })
+        })
+        .collect();
+
+    let mut stats = Stats::default();
+    for worker in workers {
+        let worker_stats = worker.await.expect("worker panicked");
+        stats.latencies.extend(worker_stats.latencies);
+        for (status, count) in worker_stats.statuses {
+            *stats.statuses.entry(status).or_default() += count;
+        }
+    }
+    let elapsed = started.elapsed();
+    stats.latencies.sort();
+
+    println!(
+        "{} requests in {:.2?}, {:.1} req/s, concurrency {}",
+        stats.latencies.len(),
+        elapsed,
+        stats.latencies.len() as f64 / elapsed.as_secs_f64(),
+        concurrency
+    );
+    for (status, count) in &stats.statuses {
+        println!("  {}: {}", status, count);
+    }
+    for percent in [50, 90, 99] {
+        println!(
+            "  p{}: {:.2?}",
+            percent,
+            percentile(&stats.latencies, percent)
+        );
+    }
+    println!(
+        "  max: {:.2?}",
+        stats.latencies.last().copied().unwrap_or_default()
+    );
+}
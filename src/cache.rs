@@ -0,0 +1,74 @@
+//! In-memory cache for the values which are expensive to compute (RPC calls, heavy DB queries).
+//! It lives in the data source, so all the workers share it.
+use std::collections::HashMap;
+use std::hash::Hash;
+
+const DEFAULT_MAX_ITEMS: usize = 10_000;
+
+pub(crate) struct TtlCache<K, V> {
+    items: std::sync::Mutex<HashMap<K, CachedValue<V>>>,
+    max_items: usize,
+}
+
+struct CachedValue<V> {
+    value: V,
+    cached_at: std::time::Instant,
+}
+
+impl<K: Eq + Hash, V: Clone> Default for TtlCache<K, V> {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_ITEMS)
+    }
+}
+
+impl<K: Eq + Hash, V: Clone> TtlCache<K, V> {
+    pub fn new(max_items: usize) -> Self {
+        Self {
+            items: std::sync::Mutex::new(HashMap::new()),
+            max_items,
+        }
+    }
+
+    /// `max_age` is chosen by the caller, so the same cache could keep the values with different TTLs
+    pub fn get(&self, key: &K, max_age: std::time::Duration) -> Option<V> {
+        let items = self.items.lock().unwrap_or_else(|err| err.into_inner());
+        items
+            .get(key)
+            .filter(|item| item.cached_at.elapsed() < max_age)
+            .map(|item| item.value.clone())
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        let mut items = self.items.lock().unwrap_or_else(|err| err.into_inner());
+        if items.len() >= self.max_items {
+            // We don't know the TTL here, so we drop everything. It's rare and only costs a few cache misses
+            items.clear();
+        }
+        items.insert(
+            key,
+            CachedValue {
+                value,
+                cached_at: std::time::Instant::now(),
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ttl_cache() {
+        let cache = TtlCache::new(2);
+        cache.insert("a", 1);
+        assert_eq!(cache.get(&"a", std::time::Duration::from_secs(60)), Some(1));
+        assert_eq!(cache.get(&"a", std::time::Duration::ZERO), None);
+        assert_eq!(cache.get(&"b", std::time::Duration::from_secs(60)), None);
+
+        cache.insert("b", 2);
+        cache.insert("c", 3);
+        assert_eq!(cache.get(&"a", std::time::Duration::from_secs(60)), None);
+        assert_eq!(cache.get(&"c", std::time::Duration::from_secs(60)), Some(3));
+    }
+}
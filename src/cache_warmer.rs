@@ -0,0 +1,165 @@
+//! Keeps the caches of the most requested contracts warm.
+//! The data source counts the lookups, the background task refreshes the hottest entries every `warm_interval`.
+//! With `warm_interval` a bit less than the cache TTL, the popular entries never expire,
+//! and their users never wait for the contract.
+use std::collections::HashMap;
+
+use futures::StreamExt;
+
+use crate::{data_source, LOGGER_MSG};
+
+/// Only the hottest entries are refreshed, the rest could expire as usual
+const MAX_WARM_KEYS: usize = 100;
+/// The refreshes are spread, they should not compete with the user requests for RPC
+const MAX_CONCURRENT_REFRESHES: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum WarmKey {
+    #[cfg(feature = "coin")]
+    FtMetadata(near_primitives::types::AccountId),
+    #[cfg(feature = "nft")]
+    NftMetadata(near_primitives::types::AccountId),
+}
+
+type Hits = std::sync::Arc<std::sync::Mutex<HashMap<WarmKey, u64>>>;
+
+/// Create it once and clone to the data source and the warming task, so they share the counters
+#[derive(Clone, Default)]
+pub(crate) struct CacheWarmer {
+    /// `None` if the warming is disabled
+    hits: Option<Hits>,
+}
+
+impl CacheWarmer {
+    /// Counts nothing
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Counts the lookups, `start` should be called to refresh them
+    pub fn enabled() -> Self {
+        Self {
+            hits: Some(Hits::default()),
+        }
+    }
+
+    pub fn record(&self, key: WarmKey) {
+        if let Some(hits) = &self.hits {
+            *hits
+                .lock()
+                .expect("cache warmer lock is poisoned")
+                .entry(key)
+                .or_default() += 1;
+        }
+    }
+
+    /// Spawns the task which refreshes the hottest entries every `warm_interval`.
+    /// Should be called inside Tokio runtime
+    pub fn start(
+        &self,
+        data_source: data_source::DataSourceRef,
+        warm_interval: std::time::Duration,
+    ) {
+        if self.hits.is_some() {
+            tokio::spawn(run_warmer(self.clone(), data_source, warm_interval));
+        }
+    }
+
+    /// The most requested keys since the previous call, the counters start from zero again.
+    /// So the popularity follows the recent traffic
+    fn take_hottest(&self, limit: usize) -> Vec<WarmKey> {
+        let mut hits: Vec<(WarmKey, u64)> = match &self.hits {
+            Some(hits) => hits
+                .lock()
+                .expect("cache warmer lock is poisoned")
+                .drain()
+                .collect(),
+            None => return vec![],
+        };
+        hits.sort_by(|(_, a), (_, b)| b.cmp(a));
+        hits.into_iter().take(limit).map(|(key, _)| key).collect()
+    }
+}
+
+async fn run_warmer(
+    warmer: CacheWarmer,
+    data_source: data_source::DataSourceRef,
+    warm_interval: std::time::Duration,
+) {
+    let mut interval = tokio::time::interval(warm_interval);
+    // The first tick is immediate, nothing is counted yet
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let keys = warmer.take_hottest(MAX_WARM_KEYS);
+        if keys.is_empty() {
+            continue;
+        }
+        let block = match data_source.get_last_block().await {
+            Ok(block) => block,
+            Err(err) => {
+                tracing::warn!(
+                    target: LOGGER_MSG,
+                    "Failed to warm the caches, no last block: {:#?}",
+                    err
+                );
+                continue;
+            }
+        };
+        let failed = futures::stream::iter(keys)
+            .map(|key| refresh(&data_source, key, block.height))
+            .buffer_unordered(MAX_CONCURRENT_REFRESHES)
+            .filter(|result| futures::future::ready(result.is_err()))
+            .count()
+            .await;
+        if failed > 0 {
+            // The broken contracts are popular too, it's not worth the warning
+            tracing::debug!(
+                target: LOGGER_MSG,
+                "{} cache entries were not warmed",
+                failed
+            );
+        }
+    }
+}
+
+async fn refresh(
+    data_source: &data_source::DataSourceRef,
+    key: WarmKey,
+    block_height: u64,
+) -> crate::Result<()> {
+    match key {
+        #[cfg(feature = "coin")]
+        WarmKey::FtMetadata(contract_id) => data_source
+            .refresh_ft_contract_metadata(&contract_id, block_height)
+            .await
+            .map(drop),
+        #[cfg(feature = "nft")]
+        WarmKey::NftMetadata(contract_id) => data_source
+            .refresh_nft_contract_metadata(&contract_id, block_height)
+            .await
+            .map(drop),
+    }
+}
+
+#[cfg(all(test, feature = "coin"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_hottest() {
+        let warmer = CacheWarmer::enabled();
+        let key = |contract_id: &str| WarmKey::FtMetadata(contract_id.parse().unwrap());
+        for contract_id in ["usn", "wrap.near", "usn", "token.near", "usn", "wrap.near"] {
+            warmer.record(key(contract_id));
+        }
+
+        assert_eq!(warmer.take_hottest(2), vec![key("usn"), key("wrap.near")]);
+        // The counters are reset
+        assert!(warmer.take_hottest(2).is_empty());
+
+        let warmer = CacheWarmer::disabled();
+        warmer.record(key("usn"));
+        assert!(warmer.take_hottest(2).is_empty());
+    }
+}
@@ -0,0 +1,285 @@
+//! The real client IP behind the load balancers. `X-Forwarded-For` and `Forwarded` headers are taken
+//! into account only if the peer is one of `TRUSTED_PROXIES`, otherwise anyone could spoof them.
+//! The chain of the proxies is walked from the right, the first address which is not trusted is the client.
+//! The peer on the Unix domain socket has no address. Only the processes on the same host can connect to it
+//! (usually the reverse proxy), so it's trusted without being listed.
+//! The middleware puts the result into the request extensions for the logger, the quotas and the audit log.
+use std::net::IpAddr;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header;
+use actix_web::HttpMessage;
+use futures::future::{ready, Ready};
+
+/// IP address or the network in CIDR notation, e.g. `10.0.0.0/8`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct IpNetwork {
+    addr: IpAddr,
+    prefix_len: u32,
+}
+
+impl IpNetwork {
+    fn parse(value: &str) -> Option<Self> {
+        let (addr, prefix_len) = match value.split_once('/') {
+            Some((addr, prefix_len)) => (addr.parse::<IpAddr>().ok()?, Some(prefix_len)),
+            None => (value.parse::<IpAddr>().ok()?, None),
+        };
+        let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len.parse::<u32>().ok()?,
+            None => max_prefix_len,
+        };
+        if prefix_len > max_prefix_len {
+            return None;
+        }
+        Some(Self { addr, prefix_len })
+    }
+
+    fn contains(&self, ip: &IpAddr) -> bool {
+        let (network, ip, bits) = match (self.addr, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                (u32::from(network) as u128, u32::from(*ip) as u128, 32)
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => (u128::from(network), u128::from(*ip), 128),
+            _ => return false,
+        };
+        if self.prefix_len == 0 {
+            return true;
+        }
+        let shift = bits - self.prefix_len;
+        network >> shift == ip >> shift
+    }
+}
+
+/// Put to the request extensions by `ClientIpResolver`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+/// The client IP resolved by `ClientIpResolver`, or the peer address if the middleware is not used
+pub fn get_client_ip(req: &ServiceRequest) -> Option<IpAddr> {
+    req.extensions()
+        .get::<ClientIp>()
+        .map(|client_ip| client_ip.0)
+        .or_else(|| req.peer_addr().map(|addr| addr.ip()))
+}
+
+/// `Forwarded: for=192.0.2.60;proto=http, for="[2001:db8::1]:4711"`, `X-Forwarded-For: 192.0.2.60, 10.0.0.1`.
+/// `None` stands for the address we could not parse (e.g. `unknown` or the obfuscated one)
+fn forwarded_chain(headers: &header::HeaderMap) -> Vec<Option<IpAddr>> {
+    let forwarded: Vec<&str> = headers
+        .get_all(header::FORWARDED)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|element| {
+            element
+                .split(';')
+                .find_map(|pair| match pair.trim().split_once('=')? {
+                    (name, value) if name.eq_ignore_ascii_case("for") => Some(value),
+                    _ => None,
+                })
+        })
+        .collect();
+    let addresses = if forwarded.is_empty() {
+        headers
+            .get_all(header::X_FORWARDED_FOR)
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .collect()
+    } else {
+        forwarded
+    };
+    addresses.into_iter().map(parse_node).collect()
+}
+
+/// `192.0.2.60`, `192.0.2.60:4711`, `"[2001:db8::1]:4711"`, `2001:db8::1`
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Ok(ip) = node.parse::<IpAddr>() {
+        return Some(ip);
+    }
+    match node.strip_prefix('[') {
+        Some(rest) => rest.split(']').next()?.parse().ok(),
+        None => node.split(':').next()?.parse().ok(),
+    }
+}
+
+/// Create it once and clone to all the workers. Trusts nobody by default
+#[derive(Debug, Clone, Default)]
+pub struct ClientIpResolver {
+    trusted_proxies: std::sync::Arc<Vec<IpNetwork>>,
+}
+
+impl ClientIpResolver {
+    /// IP addresses or CIDR networks, panics on the invalid ones
+    pub fn new(trusted_proxies: &[String]) -> Self {
+        Self {
+            trusted_proxies: std::sync::Arc::new(
+                trusted_proxies
+                    .iter()
+                    .map(|proxy| {
+                        IpNetwork::parse(proxy)
+                            .unwrap_or_else(|| panic!("invalid trusted proxy {}", proxy))
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    fn is_trusted(&self, ip: &IpAddr) -> bool {
+        self.trusted_proxies
+            .iter()
+            .any(|network| network.contains(ip))
+    }
+
+    /// `peer` is `None` on the Unix domain socket
+    pub fn resolve(&self, peer: Option<IpAddr>, headers: &header::HeaderMap) -> Option<IpAddr> {
+        let mut client = peer;
+        if let Some(peer) = peer {
+            if !self.is_trusted(&peer) {
+                return Some(peer);
+            }
+        }
+        for node in forwarded_chain(headers).into_iter().rev() {
+            match node {
+                Some(ip) => {
+                    client = Some(ip);
+                    if !self.is_trusted(&ip) {
+                        break;
+                    }
+                }
+                // We can't go further, the last proxy we know is the best guess
+                None => break,
+            }
+        }
+        client
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ClientIpResolver
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = ClientIpResolverMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ClientIpResolverMiddleware {
+            service,
+            resolver: self.clone(),
+        }))
+    }
+}
+
+pub struct ClientIpResolverMiddleware<S> {
+    service: S,
+    resolver: ClientIpResolver,
+}
+
+impl<S, B> Service<ServiceRequest> for ClientIpResolverMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = S::Future;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let peer = req.peer_addr().map(|addr| addr.ip());
+        if let Some(client_ip) = self.resolver.resolve(peer, req.headers()) {
+            req.extensions_mut().insert(ClientIp(client_ip));
+        }
+        self.service.call(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(name: header::HeaderName, value: &'static str) -> header::HeaderMap {
+        let mut headers = header::HeaderMap::new();
+        headers.insert(name, header::HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn test_ip_network() {
+        let network = IpNetwork::parse("10.0.0.0/8").unwrap();
+        assert!(network.contains(&"10.1.2.3".parse().unwrap()));
+        assert!(!network.contains(&"11.1.2.3".parse().unwrap()));
+        assert!(!network.contains(&"::1".parse().unwrap()));
+        let network = IpNetwork::parse("2001:db8::/32").unwrap();
+        assert!(network.contains(&"2001:db8::1".parse().unwrap()));
+        assert!(IpNetwork::parse("0.0.0.0/0")
+            .unwrap()
+            .contains(&"1.2.3.4".parse().unwrap()));
+        assert!(IpNetwork::parse("10.0.0.0/33").is_none());
+        assert!(IpNetwork::parse("localhost").is_none());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let resolver = ClientIpResolver::new(&["10.0.0.0/8".to_string()]);
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let stranger: IpAddr = "203.0.113.7".parse().unwrap();
+        let forwarded_for = headers(
+            header::X_FORWARDED_FOR,
+            "198.51.100.1, 192.0.2.60, 10.0.0.2",
+        );
+
+        // The spoofed header is ignored if the peer is not trusted
+        assert_eq!(
+            resolver.resolve(Some(stranger), &forwarded_for),
+            Some(stranger)
+        );
+        // The client could put anything to the left, we stop at the first untrusted hop
+        assert_eq!(
+            resolver.resolve(Some(proxy), &forwarded_for),
+            Some("192.0.2.60".parse().unwrap())
+        );
+        assert_eq!(
+            resolver.resolve(Some(proxy), &header::HeaderMap::new()),
+            Some(proxy)
+        );
+
+        let forwarded = headers(
+            header::FORWARDED,
+            r#"for=192.0.2.43, for="[2001:db8:cafe::17]:4711";proto=https"#,
+        );
+        assert_eq!(
+            resolver.resolve(Some(proxy), &forwarded),
+            Some("2001:db8:cafe::17".parse().unwrap())
+        );
+        let forwarded = headers(header::FORWARDED, "for=192.0.2.43, for=unknown");
+        assert_eq!(resolver.resolve(Some(proxy), &forwarded), Some(proxy));
+    }
+
+    #[test]
+    fn test_resolve_unix_socket() {
+        let forwarded_for = headers(
+            header::X_FORWARDED_FOR,
+            "198.51.100.1, 192.0.2.60, 10.0.0.2",
+        );
+
+        // The socket peer is trusted even if `TRUSTED_PROXIES` is empty
+        let resolver = ClientIpResolver::default();
+        assert_eq!(
+            resolver.resolve(None, &forwarded_for),
+            Some("10.0.0.2".parse().unwrap())
+        );
+        let resolver = ClientIpResolver::new(&["10.0.0.0/8".to_string()]);
+        assert_eq!(
+            resolver.resolve(None, &forwarded_for),
+            Some("192.0.2.60".parse().unwrap())
+        );
+        assert_eq!(resolver.resolve(None, &header::HeaderMap::new()), None);
+    }
+}
@@ -1,30 +1,650 @@
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Config {
+    /// `host:port`, or `unix:/path/to.sock` to listen on the Unix domain socket
     pub addr: String,
-    pub cors_allowed_origins: Vec<String>,
+    #[serde(default)]
+    pub cors: CorsConfig,
+    /// IP addresses or CIDR networks of the load balancers, see `client_ip`.
+    /// The peer on the Unix domain socket is always trusted
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
     #[serde(default)]
     pub limits: LimitsConfig,
+    #[serde(default)]
+    pub http: HttpConfig,
+    /// RPC calls over the limit wait in the queue
+    #[serde(default = "default_max_concurrent_rpc_calls")]
+    pub max_concurrent_rpc_calls: usize,
+    #[serde(default)]
+    pub response_cache: ResponseCacheConfig,
+    /// How often the usage counters per API key are written to DB. No accounting if not set
+    #[serde(default)]
+    pub usage_flush_interval_secs: Option<u64>,
+    /// How often the quotas per API key are reloaded from DB. No quotas if not set
+    #[serde(default)]
+    pub quota_refresh_interval_secs: Option<u64>,
+    /// How often the cached metadata of the most requested contracts is refreshed. No warming if not set
+    #[serde(default)]
+    pub cache_warm_interval_secs: Option<u64>,
+    /// How often one random indexed FT balance is compared with `ft_balance_of`. No checks if not set
+    #[serde(default)]
+    pub consistency_check_interval_secs: Option<u64>,
+    #[serde(default)]
+    pub request_signing: RequestSigningConfig,
+    /// How often the alert rules are checked against the new events. No alerts if not set
+    #[serde(default)]
+    pub alerts_check_interval_secs: Option<u64>,
+    /// Apply `migrations/` to the aux DB on startup
+    #[serde(default)]
+    pub run_migrations: bool,
+    /// The built-in indexer, see `indexer`. Not started if not set
+    #[serde(default)]
+    pub indexer: Option<IndexerConfig>,
+    /// Path prefixes which answer 503 from the start, `/admin/routes/disabled` changes them at runtime
+    #[serde(default)]
+    pub disabled_routes: Vec<String>,
+    /// Only these FT and NFT contracts are served, `/admin/contracts/allowed` changes them at runtime.
+    /// All the contracts if not set
+    #[serde(default)]
+    pub allowed_contracts: Option<Vec<String>>,
+    /// `key_id`s of the API keys which get the raw RPC and DB errors with `?debug=true`, see `error_debug`
+    #[serde(default)]
+    pub debug_api_key_ids: Vec<String>,
+    #[serde(default)]
+    pub log_sampling: LogSamplingConfig,
+    /// The indexer DB queries slower than this are logged with their parameters. Not logged if not set
+    #[serde(default)]
+    pub slow_query_threshold_ms: Option<u64>,
+    /// The RPC calls slower than this are logged with the contract, the method and the block. Not logged if not set
+    #[serde(default)]
+    pub slow_rpc_call_threshold_ms: Option<u64>,
+    /// The bucket for `/accounts/{account_id}/exports`. The exports are disabled if not set
+    #[serde(default)]
+    pub export_storage: Option<ExportStorageConfig>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             addr: "0.0.0.0:3050".to_owned(),
-            cors_allowed_origins: vec!["*".to_owned()],
+            cors: CorsConfig::default(),
+            trusted_proxies: vec![],
             limits: LimitsConfig::default(),
+            http: HttpConfig::default(),
+            max_concurrent_rpc_calls: default_max_concurrent_rpc_calls(),
+            response_cache: ResponseCacheConfig::default(),
+            usage_flush_interval_secs: None,
+            quota_refresh_interval_secs: None,
+            cache_warm_interval_secs: None,
+            consistency_check_interval_secs: None,
+            request_signing: RequestSigningConfig::default(),
+            alerts_check_interval_secs: None,
+            run_migrations: false,
+            indexer: None,
+            disabled_routes: vec![],
+            allowed_contracts: None,
+            debug_api_key_ids: vec![],
+            log_sampling: LogSamplingConfig::default(),
+            slow_query_threshold_ms: None,
+            slow_rpc_call_threshold_ms: None,
+            export_storage: None,
+        }
+    }
+}
+
+impl Config {
+    /// Defaults overridden by the env variables, see README
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        if let Ok(addr) = std::env::var("LISTEN") {
+            config.addr = addr;
+        }
+        if let Ok(path) = std::env::var("CORS_CONFIG") {
+            config.cors = serde_json::from_str(
+                &std::fs::read_to_string(path).expect("failed to read the CORS config"),
+            )
+            .expect("failed to parse the CORS config");
+        }
+        if let Ok(allowed_origins) = std::env::var("CORS_ALLOWED_ORIGINS") {
+            config.cors.allowed_origins = split_comma_separated(&allowed_origins);
+        }
+        if let Ok(trusted_proxies) = std::env::var("TRUSTED_PROXIES") {
+            config.trusted_proxies = split_comma_separated(&trusted_proxies);
+        }
+        if let Some(max_concurrent_rpc_calls) = env_var("MAX_CONCURRENT_RPC_CALLS") {
+            config.max_concurrent_rpc_calls = max_concurrent_rpc_calls;
+        }
+        if let Some(max_items) = env_var("RESPONSE_CACHE_MAX_ITEMS") {
+            config.response_cache.max_items = max_items;
+        }
+        if let Some(ttl_secs) = env_var("RESPONSE_CACHE_TTL_SECS") {
+            config.response_cache.ttl_secs = ttl_secs;
+        }
+        if let Some(ttl_secs) = env_var("RESPONSE_CACHE_HISTORY_TTL_SECS") {
+            config.response_cache.history_ttl_secs = ttl_secs;
+        }
+        if let Ok(path_prefixes) = std::env::var("RESPONSE_CACHE_PATH_PREFIXES") {
+            config.response_cache.path_prefixes = split_comma_separated(&path_prefixes);
+        }
+        if let Some(flush_interval_secs) = env_var("USAGE_FLUSH_INTERVAL_SECS") {
+            config.usage_flush_interval_secs = Some(flush_interval_secs);
+        }
+        if let Some(refresh_interval_secs) = env_var("QUOTA_REFRESH_INTERVAL_SECS") {
+            config.quota_refresh_interval_secs = Some(refresh_interval_secs);
+        }
+        if let Some(warm_interval_secs) = env_var("CACHE_WARM_INTERVAL_SECS") {
+            config.cache_warm_interval_secs = Some(warm_interval_secs);
+        }
+        if let Some(check_interval_secs) = env_var("CONSISTENCY_CHECK_INTERVAL_SECS") {
+            config.consistency_check_interval_secs = Some(check_interval_secs);
+        }
+        if let Ok(path_prefixes) = std::env::var("REQUEST_SIGNING_PATH_PREFIXES") {
+            config.request_signing.path_prefixes = split_comma_separated(&path_prefixes);
+        }
+        if let Some(max_clock_skew_secs) = env_var("REQUEST_SIGNING_MAX_CLOCK_SKEW_SECS") {
+            config.request_signing.max_clock_skew_secs = max_clock_skew_secs;
+        }
+        if let Some(check_interval_secs) = env_var("ALERTS_CHECK_INTERVAL_SECS") {
+            config.alerts_check_interval_secs = Some(check_interval_secs);
+        }
+        if let Ok(path_prefixes) = std::env::var("DISABLED_ROUTES") {
+            config.disabled_routes = split_comma_separated(&path_prefixes);
+        }
+        if let Ok(contract_ids) = std::env::var("ALLOWED_CONTRACTS") {
+            config.allowed_contracts = Some(split_comma_separated(&contract_ids));
+        }
+        if let Ok(key_ids) = std::env::var("DEBUG_API_KEY_IDS") {
+            config.debug_api_key_ids = split_comma_separated(&key_ids);
+        }
+        if let Some(sample_rate) = env_var("LOG_SUCCESS_SAMPLE_RATE") {
+            config.log_sampling.success_sample_rate = sample_rate;
+        }
+        if let Some(sample_rate) = env_var("LOG_ERROR_SAMPLE_RATE") {
+            config.log_sampling.error_sample_rate = sample_rate;
+        }
+        if let Some(threshold_ms) = env_var("LOG_SLOW_REQUEST_THRESHOLD_MS") {
+            config.log_sampling.slow_request_threshold_ms = Some(threshold_ms);
+        }
+        if let Some(threshold_ms) = env_var("SLOW_QUERY_THRESHOLD_MS") {
+            config.slow_query_threshold_ms = Some(threshold_ms);
+        }
+        if let Some(threshold_ms) = env_var("SLOW_RPC_CALL_THRESHOLD_MS") {
+            config.slow_rpc_call_threshold_ms = Some(threshold_ms);
+        }
+        if let Ok(bucket) = std::env::var("EXPORT_STORAGE_BUCKET") {
+            let region =
+                std::env::var("EXPORT_STORAGE_REGION").unwrap_or_else(|_| "us-east-1".to_string());
+            config.export_storage = Some(ExportStorageConfig {
+                endpoint: std::env::var("EXPORT_STORAGE_ENDPOINT")
+                    .unwrap_or_else(|_| format!("https://s3.{}.amazonaws.com", region)),
+                region,
+                bucket,
+                download_url_expires_secs: env_var("EXPORT_DOWNLOAD_URL_EXPIRES_SECS")
+                    .unwrap_or_else(default_download_url_expires_secs),
+            });
+        }
+        if let Some(run_migrations) = env_var("RUN_MIGRATIONS") {
+            config.run_migrations = run_migrations;
+        }
+        if let Ok(network) = std::env::var("INDEXER_NETWORK") {
+            config.indexer = Some(IndexerConfig {
+                network,
+                start_block_height: env_var("INDEXER_START_BLOCK_HEIGHT"),
+                event_sink: std::env::var("EVENT_SINK_URL")
+                    .ok()
+                    .map(|url| EventSinkConfig {
+                        url,
+                        topic_prefix: std::env::var("EVENT_SINK_TOPIC_PREFIX")
+                            .unwrap_or_else(|_| default_event_sink_topic_prefix()),
+                    }),
+            });
+        }
+        if let Ok(path) = std::env::var("LIMITS_CONFIG") {
+            config.limits = serde_json::from_str(
+                &std::fs::read_to_string(path).expect("failed to read the limits config"),
+            )
+            .expect("failed to parse the limits config");
+        }
+        if let Some(workers) = env_var("HTTP_WORKERS") {
+            config.http.workers = Some(workers);
+        }
+        if let Some(keep_alive_secs) = env_var("HTTP_KEEP_ALIVE_SECS") {
+            config.http.keep_alive_secs = keep_alive_secs;
+        }
+        if let Some(timeout_ms) = env_var("HTTP_CLIENT_REQUEST_TIMEOUT_MS") {
+            config.http.client_request_timeout_ms = timeout_ms;
+        }
+        if let Some(max_connections) = env_var("HTTP_MAX_CONNECTIONS") {
+            config.http.max_connections = max_connections;
+        }
+        config
+    }
+}
+
+fn default_max_concurrent_rpc_calls() -> usize {
+    100
+}
+
+/// Comma-separated list, e.g. `/nep141/metadata,/accounts/*/coins`
+fn split_comma_separated(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|prefix| prefix.trim().to_string())
+        .filter(|prefix| !prefix.is_empty())
+        .collect()
+}
+
+fn env_var<T: std::str::FromStr>(name: &str) -> Option<T> {
+    std::env::var(name).ok().map(|value| {
+        value
+            .parse()
+            .unwrap_or_else(|_| panic!("failed to parse {}: {}", name, value))
+    })
+}
+
+/// The defaults allow any origin to call any endpoint
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct CorsConfig {
+    /// Exact origins, e.g. `https://wallet.near.org`. `*` allows any origin
+    pub allowed_origins: Vec<String>,
+    /// Regular expressions for the whole origin, e.g. `https://[a-z0-9-]+\.near\.org`.
+    /// Checked in addition to `allowed_origins`
+    pub allowed_origin_patterns: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    /// The headers which the API itself needs (API key, signatures) are always allowed
+    pub extra_allowed_headers: Vec<String>,
+    /// `Access-Control-Allow-Credentials`: the browsers send the cookies and the auth headers
+    pub supports_credentials: bool,
+    pub max_age_secs: usize,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec!["*".to_owned()],
+            allowed_origin_patterns: vec![],
+            // POST, PUT and DELETE are for the bulk lookups, `/alerts`, `/watchlists` and the admin endpoints
+            allowed_methods: vec![
+                "GET".to_owned(),
+                "POST".to_owned(),
+                "PUT".to_owned(),
+                "DELETE".to_owned(),
+            ],
+            extra_allowed_headers: vec![],
+            supports_credentials: true,
+            max_age_secs: 3600,
+        }
+    }
+}
+
+impl CorsConfig {
+    pub fn allows_any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|origin| origin == "*")
+    }
+
+    /// The patterns should match the whole origin. Compiled once on startup, the invalid ones stop the server
+    pub fn origin_patterns(&self) -> Result<Vec<regex::Regex>, regex::Error> {
+        self.allowed_origin_patterns
+            .iter()
+            .map(|pattern| regex::Regex::new(&format!("^(?:{})$", pattern)))
+            .collect()
+    }
+}
+
+/// actix-web settings. The defaults are the same as actix-web ones
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct HttpConfig {
+    /// Number of the physical CPU cores if not set
+    pub workers: Option<usize>,
+    /// 0 disables keep-alive
+    pub keep_alive_secs: u64,
+    /// Time to receive the request headers, 0 disables the timeout
+    pub client_request_timeout_ms: u64,
+    /// Per worker
+    pub max_connections: usize,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            workers: None,
+            keep_alive_secs: 5,
+            client_request_timeout_ms: 5000,
+            max_connections: 25_000,
         }
     }
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct LimitsConfig {
     pub input_payload_max_size: usize,
+    /// No timeout if not set
+    pub request_timeout_secs: Option<u64>,
+    /// `limit` of the paginated endpoints if it is not given
+    pub default_page_limit: u32,
+    pub max_page_limit: u32,
+    /// Overrides for the groups of routes, e.g. bigger payloads for the batch endpoints.
+    /// The longest matching `path_prefix` wins
+    pub routes: Vec<RouteLimitsConfig>,
 }
 
 impl Default for LimitsConfig {
     fn default() -> Self {
         Self {
             input_payload_max_size: 10 * 1024 * 1024,
+            request_timeout_secs: None,
+            default_page_limit: crate::types::query_params::PageLimits::default().default_limit,
+            max_page_limit: crate::types::query_params::PageLimits::default().max_limit,
+            routes: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RouteLimitsConfig {
+    /// Path segments, `*` matches any segment: `/accounts/*/coins/NEAR/history`
+    pub path_prefix: String,
+    pub input_payload_max_size: Option<usize>,
+    pub request_timeout_secs: Option<u64>,
+    pub default_page_limit: Option<u32>,
+    pub max_page_limit: Option<u32>,
+}
+
+/// The limits applied to the given request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteLimits {
+    pub input_payload_max_size: usize,
+    pub request_timeout: Option<std::time::Duration>,
+    pub page_limits: crate::types::query_params::PageLimits,
+}
+
+impl LimitsConfig {
+    pub fn for_path(&self, path: &str) -> RouteLimits {
+        let route = self
+            .routes
+            .iter()
+            .filter(|route| matches_path_prefix(&route.path_prefix, path))
+            .max_by_key(|route| route.path_prefix.len());
+        RouteLimits {
+            input_payload_max_size: route
+                .and_then(|route| route.input_payload_max_size)
+                .unwrap_or(self.input_payload_max_size),
+            request_timeout: route
+                .and_then(|route| route.request_timeout_secs)
+                .or(self.request_timeout_secs)
+                .map(std::time::Duration::from_secs),
+            page_limits: self.page_limits_for(route),
+        }
+    }
+
+    /// The default is not allowed to exceed the maximum
+    fn page_limits_for(
+        &self,
+        route: Option<&RouteLimitsConfig>,
+    ) -> crate::types::query_params::PageLimits {
+        let max_limit = route
+            .and_then(|route| route.max_page_limit)
+            .unwrap_or(self.max_page_limit)
+            .max(1);
+        let default_limit = route
+            .and_then(|route| route.default_page_limit)
+            .unwrap_or(self.default_page_limit)
+            .clamp(1, max_limit);
+        crate::types::query_params::PageLimits {
+            default_limit,
+            max_limit,
+        }
+    }
+
+    /// The body extractors are configured once for the whole app, they should accept the biggest
+    /// payload allowed anywhere. The smaller limits are checked by `Content-Length`
+    pub fn max_input_payload_size(&self) -> usize {
+        self.routes
+            .iter()
+            .filter_map(|route| route.input_payload_max_size)
+            .fold(self.input_payload_max_size, usize::max)
+    }
+}
+
+/// In-memory cache of the responses for the requests pinned to the past block
+/// (`block_height` or old enough `block_timestamp_nanos`), such responses never change
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ResponseCacheConfig {
+    /// 0 disables the cache
+    pub max_items: usize,
+    pub ttl_secs: u64,
+    /// For the history pages taken by the `cursor` older than the finality, they are requested again on every backfill
+    pub history_ttl_secs: u64,
+    /// The same format as `RouteLimitsConfig::path_prefix`. All the routes if empty
+    pub path_prefixes: Vec<String>,
+}
+
+impl Default for ResponseCacheConfig {
+    fn default() -> Self {
+        Self {
+            max_items: 10_000,
+            ttl_secs: 600,
+            history_ttl_secs: 24 * 60 * 60,
+            path_prefixes: vec![],
+        }
+    }
+}
+
+/// HMAC request signing, see `signing`. Enabled by `REQUEST_SIGNING_SECRET` env variable
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct RequestSigningConfig {
+    /// The same format as `RouteLimitsConfig::path_prefix`. The requests to these routes should be signed,
+    /// the signatures of the other requests are checked if they are given
+    pub path_prefixes: Vec<String>,
+    /// The signatures older than this (or from the future) are rejected
+    pub max_clock_skew_secs: u64,
+}
+
+impl Default for RequestSigningConfig {
+    fn default() -> Self {
+        Self {
+            path_prefixes: vec![],
+            max_clock_skew_secs: 300,
+        }
+    }
+}
+
+/// Which requests get to the access log, see `request_log`. The defaults log everything
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct LogSamplingConfig {
+    /// Share of 2xx and 3xx responses which are logged, from 0 to 1
+    pub success_sample_rate: f64,
+    /// Share of 4xx and 5xx responses which are logged, from 0 to 1
+    pub error_sample_rate: f64,
+    /// The requests slower than this are always logged
+    pub slow_request_threshold_ms: Option<u64>,
+}
+
+impl Default for LogSamplingConfig {
+    fn default() -> Self {
+        Self {
+            success_sample_rate: 1.0,
+            error_sample_rate: 1.0,
+            slow_request_threshold_ms: None,
         }
     }
 }
+
+pub(crate) fn matches_path_prefix(prefix: &str, path: &str) -> bool {
+    let mut path_segments = path.trim_matches('/').split('/');
+    prefix
+        .trim_matches('/')
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .all(|expected| match path_segments.next() {
+            Some(segment) => expected == "*" || expected == segment,
+            None => false,
+        })
+}
+
+/// NEAR Lake source of the built-in indexer
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct IndexerConfig {
+    /// `mainnet` or `testnet`
+    pub network: String,
+    /// Used only if the DB is empty, otherwise we continue after the last written block
+    pub start_block_height: Option<u64>,
+    /// Kafka or NATS where the events of the indexed blocks are published. Not published if not set
+    #[serde(default)]
+    pub event_sink: Option<EventSinkConfig>,
+}
+
+/// S3-compatible bucket for the export files, see `object_storage`.
+/// The credentials are taken from `EXPORT_STORAGE_ACCESS_KEY_ID`, `EXPORT_STORAGE_SECRET_ACCESS_KEY` env variables
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ExportStorageConfig {
+    /// E.g. `https://s3.eu-central-1.amazonaws.com`, `https://storage.googleapis.com`
+    pub endpoint: String,
+    /// `auto` for GCS
+    pub region: String,
+    pub bucket: String,
+    /// How long the download links in `/exports/{job_id}` work
+    #[serde(default = "default_download_url_expires_secs")]
+    pub download_url_expires_secs: u64,
+}
+
+fn default_download_url_expires_secs() -> u64 {
+    3600
+}
+
+/// See `indexer::sink`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct EventSinkConfig {
+    /// `kafka://broker1:9092,broker2:9092` or `nats://host:4222`
+    pub url: String,
+    /// The topics (Kafka) or the subjects (NATS) are `{topic_prefix}.ft_events`, `{topic_prefix}.nft_events`
+    /// and `{topic_prefix}.balance_changes`
+    #[serde(default = "default_event_sink_topic_prefix")]
+    pub topic_prefix: String,
+}
+
+fn default_event_sink_topic_prefix() -> String {
+    "near".to_string()
+}
+
+/// Which tokens to sample for `/nep141/{contract_account_id}/price/history`.
+/// Prices are taken from Ref Finance pools and measured in the quote token (usually USD stablecoin)
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PriceSamplerConfig {
+    #[serde(default = "default_price_sampling_interval_secs")]
+    pub interval_secs: u64,
+    #[serde(default = "default_dex_contract_account_id")]
+    pub dex_contract_account_id: String,
+    pub quote_contract_account_id: String,
+    pub tokens: Vec<PricedTokenConfig>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PricedTokenConfig {
+    pub contract_account_id: String,
+    /// The pool with the token and the quote token
+    pub pool_id: u64,
+}
+
+fn default_price_sampling_interval_secs() -> u64 {
+    300
+}
+
+fn default_dex_contract_account_id() -> String {
+    "v2.ref-finance.near".to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::query_params::PageLimits;
+
+    #[test]
+    fn test_route_limits() {
+        let limits = LimitsConfig {
+            input_payload_max_size: 100,
+            request_timeout_secs: Some(10),
+            default_page_limit: 20,
+            max_page_limit: 100,
+            routes: vec![
+                RouteLimitsConfig {
+                    path_prefix: "/accounts/*/coins".to_string(),
+                    input_payload_max_size: None,
+                    request_timeout_secs: Some(30),
+                    default_page_limit: Some(10),
+                    max_page_limit: Some(50),
+                },
+                RouteLimitsConfig {
+                    path_prefix: "/accounts/*/coins/NEAR/history".to_string(),
+                    input_payload_max_size: Some(1000),
+                    request_timeout_secs: Some(60),
+                    default_page_limit: None,
+                    max_page_limit: Some(5),
+                },
+            ],
+        };
+
+        let default = RouteLimits {
+            input_payload_max_size: 100,
+            request_timeout: Some(std::time::Duration::from_secs(10)),
+            page_limits: PageLimits {
+                default_limit: 20,
+                max_limit: 100,
+            },
+        };
+        assert_eq!(limits.for_path("/nep141/metadata/usn"), default);
+        assert_eq!(limits.for_path("/accounts/alice.near"), default);
+        assert_eq!(
+            limits.for_path("/accounts/alice.near/coins/usn"),
+            RouteLimits {
+                input_payload_max_size: 100,
+                request_timeout: Some(std::time::Duration::from_secs(30)),
+                page_limits: PageLimits {
+                    default_limit: 10,
+                    max_limit: 50,
+                },
+            }
+        );
+        assert_eq!(
+            limits.for_path("/accounts/alice.near/coins/NEAR/history"),
+            RouteLimits {
+                input_payload_max_size: 1000,
+                request_timeout: Some(std::time::Duration::from_secs(60)),
+                // The default one is cut by the maximum
+                page_limits: PageLimits {
+                    default_limit: 5,
+                    max_limit: 5,
+                },
+            }
+        );
+        assert_eq!(limits.max_input_payload_size(), 1000);
+    }
+
+    #[test]
+    fn test_cors_origin_patterns() {
+        let cors = CorsConfig {
+            allowed_origins: vec!["https://wallet.near.org".to_string()],
+            allowed_origin_patterns: vec![r"https://[a-z0-9-]+\.near\.org".to_string()],
+            ..Default::default()
+        };
+        assert!(!cors.allows_any_origin());
+        assert!(CorsConfig::default().allows_any_origin());
+
+        let patterns = cors.origin_patterns().unwrap();
+        let is_allowed = |origin: &str| patterns.iter().any(|pattern| pattern.is_match(origin));
+        assert!(is_allowed("https://explorer.near.org"));
+        assert!(!is_allowed("http://explorer.near.org"));
+        // The whole origin should match
+        assert!(!is_allowed("https://explorer.near.org.evil.com"));
+
+        let cors = CorsConfig {
+            allowed_origin_patterns: vec!["https://(".to_string()],
+            ..Default::default()
+        };
+        assert!(cors.origin_patterns().is_err());
+    }
+}
@@ -0,0 +1,188 @@
+//! Catches the indexer bugs before the users do: the background task takes the random FT balances
+//! from the indexed history and compares them with `ft_balance_of` at the same block, see `/reconcile`.
+//! `/admin/consistency` gives the counters and the last mismatches.
+//! The counters live in memory, each instance counts its own checks from the start.
+use std::collections::VecDeque;
+
+#[cfg(feature = "coin")]
+use crate::modules::coin;
+#[cfg(feature = "coin")]
+use crate::{data_source, types, LOGGER_MSG};
+
+/// The older mismatches are dropped, the counters still have them
+#[cfg(feature = "coin")]
+const MAX_RECENT_MISMATCHES: usize = 100;
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ConsistencyReport {
+    pub started_at_timestamp_nanos: u64,
+    /// The balances which were compared
+    pub checked: u64,
+    pub mismatches: u64,
+    /// The checks which could not get one of the balances, e.g. RPC was not available
+    pub failed: u64,
+    /// The newest go first
+    pub recent_mismatches: VecDeque<BalanceMismatch>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct BalanceMismatch {
+    pub contract_id: near_primitives::types::AccountId,
+    pub account_id: near_primitives::types::AccountId,
+    pub block_height: u64,
+    pub block_hash: String,
+    pub block_timestamp: u64,
+    pub indexed_balance: u128,
+    pub rpc_balance: u128,
+    pub checked_at_timestamp_nanos: u64,
+}
+
+type SharedReport = std::sync::Arc<std::sync::Mutex<ConsistencyReport>>;
+
+/// Create it once and clone to the checking task and to the admin endpoints, so they share the report
+#[derive(Clone, Default)]
+pub(crate) struct ConsistencyChecker {
+    /// `None` if the checks are disabled
+    report: Option<SharedReport>,
+}
+
+impl ConsistencyChecker {
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// `start` should be called to run the checks
+    #[cfg(feature = "coin")]
+    pub fn enabled() -> Self {
+        Self {
+            report: Some(std::sync::Arc::new(std::sync::Mutex::new(
+                ConsistencyReport {
+                    started_at_timestamp_nanos: now_nanos(),
+                    ..Default::default()
+                },
+            ))),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.report.is_some()
+    }
+
+    /// `None` if the checks are disabled
+    pub fn report(&self) -> Option<ConsistencyReport> {
+        self.report.as_ref().map(|report| {
+            report
+                .lock()
+                .expect("consistency report lock is poisoned")
+                .clone()
+        })
+    }
+
+    /// Spawns the task which checks one random balance every `check_interval`.
+    /// Should be called inside Tokio runtime
+    #[cfg(feature = "coin")]
+    pub fn start(
+        &self,
+        data_source: data_source::DataSourceRef,
+        pool: sqlx::Pool<sqlx::Postgres>,
+        check_interval: std::time::Duration,
+    ) {
+        if let Some(report) = &self.report {
+            tokio::spawn(run_checks(
+                report.clone(),
+                data_source,
+                pool,
+                check_interval,
+            ));
+        }
+    }
+}
+
+#[cfg(feature = "coin")]
+async fn run_checks(
+    report: SharedReport,
+    data_source: data_source::DataSourceRef,
+    pool: sqlx::Pool<sqlx::Postgres>,
+    check_interval: std::time::Duration,
+) {
+    let mut interval = tokio::time::interval(check_interval);
+    loop {
+        interval.tick().await;
+        let result = check_random_balance(&data_source, &pool).await;
+        let mut report = report.lock().expect("consistency report lock is poisoned");
+        match result {
+            Ok(CheckResult::NothingToCheck) => {}
+            Ok(CheckResult::Matched) => report.checked += 1,
+            Ok(CheckResult::Mismatched(mismatch)) => {
+                tracing::warn!(
+                    target: LOGGER_MSG,
+                    "FT balance mismatch: {} of {} at block {}, indexed {}, RPC {}",
+                    mismatch.account_id,
+                    mismatch.contract_id,
+                    mismatch.block_height,
+                    mismatch.indexed_balance,
+                    mismatch.rpc_balance
+                );
+                report.checked += 1;
+                report.mismatches += 1;
+                report.recent_mismatches.push_front(mismatch);
+                report.recent_mismatches.truncate(MAX_RECENT_MISMATCHES);
+            }
+            Err(err) => {
+                tracing::debug!(target: LOGGER_MSG, "Failed to check FT balance: {}", err);
+                report.failed += 1;
+            }
+        }
+    }
+}
+
+#[cfg(feature = "coin")]
+enum CheckResult {
+    /// There are no FT events yet
+    NothingToCheck,
+    Matched,
+    Mismatched(BalanceMismatch),
+}
+
+#[cfg(feature = "coin")]
+async fn check_random_balance(
+    data_source: &data_source::DataSourceRef,
+    pool: &sqlx::Pool<sqlx::Postgres>,
+) -> crate::Result<CheckResult> {
+    let sample = match coin::data_provider::get_random_ft_balance_sample(pool).await? {
+        Some(sample) => sample,
+        None => return Ok(CheckResult::NothingToCheck),
+    };
+    let block = data_source
+        .get_block_from_params(&types::query_params::BlockParams {
+            block_timestamp_nanos: Some(sample.block_timestamp.into()),
+            block_height: None,
+        })
+        .await?;
+    let indexed_balance = data_source
+        .get_ft_balance_from_events(&block, &sample.contract_id, &sample.account_id)
+        .await?;
+    let rpc_balance = data_source
+        .get_ft_balance(&block, &sample.contract_id, &sample.account_id)
+        .await?;
+    if indexed_balance == rpc_balance {
+        return Ok(CheckResult::Matched);
+    }
+    Ok(CheckResult::Mismatched(BalanceMismatch {
+        contract_id: sample.contract_id,
+        account_id: sample.account_id,
+        block_height: block.height,
+        block_hash: block.hash,
+        block_timestamp: block.timestamp,
+        indexed_balance,
+        rpc_balance,
+        checked_at_timestamp_nanos: now_nanos(),
+    }))
+}
+
+fn now_nanos() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or_default()
+}
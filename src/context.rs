@@ -0,0 +1,480 @@
+use crate::{
+    allow_list, audit, balances_schema, cache_warmer, config, consistency_check, data_source,
+    db_helpers, error_debug, errors, quotas, response_meta, route_toggles, rpc_helpers, signing,
+    usage,
+};
+#[cfg(feature = "exports")]
+use crate::{export_worker, object_storage};
+
+/// Everything the endpoints need to serve the requests.
+#[derive(Clone)]
+pub struct ServerContext {
+    pub(crate) data_source: data_source::DataSourceRef,
+    /// `/admin/...` endpoints are registered only if the token is set
+    #[cfg(feature = "admin")]
+    pub(crate) admin_token: Option<String>,
+    usage_recorder: usage::UsageRecorder,
+    quota_enforcer: quotas::QuotaEnforcer,
+    pub(crate) signature_verifier: signing::SignatureVerifier,
+    audit_logger: audit::AuditLogger,
+    pub(crate) route_toggles: route_toggles::RouteToggles,
+    pub(crate) contract_allow_list: allow_list::ContractAllowList,
+    error_debug: error_debug::ErrorDebug,
+    response_meta_filler: response_meta::ResponseMetaFiller,
+    /// `/admin/consistency` is registered only if the checks are enabled
+    pub(crate) consistency_checker: consistency_check::ConsistencyChecker,
+    /// `/exports/...` endpoints are registered only if the storage is set
+    #[cfg(feature = "exports")]
+    pub(crate) export_storage: Option<object_storage::ObjectStorage>,
+}
+
+impl ServerContext {
+    pub fn builder() -> ServerContextBuilder {
+        ServerContextBuilder::default()
+    }
+
+    /// Serves the canned responses instead of going to DB and RPC.
+    /// `fixtures_dir` could override the built-in responses, see `data_source::FixtureDataSource`
+    pub fn mock(fixtures_dir: Option<std::path::PathBuf>) -> ServerContext {
+        ServerContext {
+            data_source: std::sync::Arc::new(data_source::FixtureDataSource { fixtures_dir }),
+            #[cfg(feature = "admin")]
+            admin_token: None,
+            usage_recorder: usage::UsageRecorder::disabled(),
+            quota_enforcer: quotas::QuotaEnforcer::disabled(),
+            signature_verifier: signing::SignatureVerifier::disabled(),
+            audit_logger: audit::AuditLogger::disabled(),
+            route_toggles: Default::default(),
+            contract_allow_list: Default::default(),
+            error_debug: Default::default(),
+            response_meta_filler: response_meta::ResponseMetaFiller::new(),
+            consistency_checker: consistency_check::ConsistencyChecker::disabled(),
+            #[cfg(feature = "exports")]
+            export_storage: None,
+        }
+    }
+
+    /// Middleware which counts the requests per API key, should wrap the whole app.
+    /// Does nothing if the accounting is not enabled in the builder
+    pub fn usage_recorder(&self) -> usage::UsageRecorder {
+        self.usage_recorder.clone()
+    }
+
+    /// Middleware which rejects the requests over the API key quota with 429.
+    /// Should be wrapped by `usage_recorder()`. Does nothing if the quotas are not enabled in the builder
+    pub fn quota_enforcer(&self) -> quotas::QuotaEnforcer {
+        self.quota_enforcer.clone()
+    }
+
+    /// Middleware which rejects the requests with the invalid HMAC signature with 401.
+    /// Does nothing if the signing is not enabled in the builder
+    pub fn signature_verifier(&self) -> signing::SignatureVerifier {
+        self.signature_verifier.clone()
+    }
+
+    /// Middleware which writes the admin and signed requests to `audit_log` table.
+    /// Does nothing if the audit log is not enabled in the builder
+    pub fn audit_logger(&self) -> audit::AuditLogger {
+        self.audit_logger.clone()
+    }
+
+    /// Middleware which rejects the requests to the disabled route groups with 503.
+    /// Nothing is disabled until `set_disabled_path_prefixes` is called
+    pub fn route_toggles(&self) -> route_toggles::RouteToggles {
+        self.route_toggles.clone()
+    }
+
+    /// The coin and NFT contracts which are served, shared with `/admin/contracts/allowed`.
+    /// All of them are served until `set_allowed_contracts` is called
+    pub fn contract_allow_list(&self) -> allow_list::ContractAllowList {
+        self.contract_allow_list.clone()
+    }
+
+    /// Middleware which adds `debug_message` to the error responses of the trusted clients with `?debug=true`.
+    /// Only the admin token is trusted until `set_debug_key_ids` is called
+    pub fn error_debug(&self) -> error_debug::ErrorDebug {
+        self.error_debug.clone()
+    }
+
+    /// Middleware which adds `request_id` and the indexer head to `meta` of the responses.
+    /// Should wrap `response_cache` and `head_requests`
+    pub fn response_meta_filler(&self) -> response_meta::ResponseMetaFiller {
+        self.response_meta_filler.clone()
+    }
+}
+
+#[derive(Default)]
+pub struct ServerContextBuilder {
+    data_source: Option<data_source::DataSourceRef>,
+    pool: Option<sqlx::Pool<sqlx::Postgres>>,
+    #[cfg(all(
+        feature = "history",
+        any(feature = "coin", feature = "staking", feature = "accounts")
+    ))]
+    pool_balances: Option<sqlx::Pool<sqlx::Postgres>>,
+    #[cfg(all(
+        feature = "history",
+        any(feature = "coin", feature = "staking", feature = "accounts")
+    ))]
+    balances_schema: balances_schema::BalancesSchema,
+    pool_aux: Option<sqlx::Pool<sqlx::Postgres>>,
+    rpc_client: Option<near_jsonrpc_client::JsonRpcClient>,
+    archival_rpc_client: Option<near_jsonrpc_client::JsonRpcClient>,
+    cache_warm_interval: Option<std::time::Duration>,
+    #[cfg(feature = "coin")]
+    consistency_check_interval: Option<std::time::Duration>,
+    max_concurrent_rpc_calls: Option<usize>,
+    slow_rpc_call_threshold: Option<std::time::Duration>,
+    slow_query_threshold: Option<std::time::Duration>,
+    #[cfg(feature = "admin")]
+    admin_token: Option<String>,
+    usage_flush_interval: Option<std::time::Duration>,
+    quota_refresh_interval: Option<std::time::Duration>,
+    request_signing: Option<(String, config::RequestSigningConfig)>,
+    audit_log: bool,
+    #[cfg(feature = "scylla")]
+    scylla_session: Option<scylla::Session>,
+    #[cfg(feature = "nft")]
+    nft_owner_counts: bool,
+    #[cfg(feature = "nft")]
+    nft_spam_denylist: Vec<String>,
+    #[cfg(feature = "exports")]
+    export_storage: Option<object_storage::ObjectStorage>,
+}
+
+impl ServerContextBuilder {
+    /// Serves the endpoints from another backend (e.g. NEAR Lake) instead of Indexer for Explorer DB + RPC.
+    /// `pool` and `rpc_client` are not required then, `aux_pool` is still needed for the usage, the quotas,
+    /// the audit log and the exports. `scylla_session` is still applied on top of it
+    pub fn data_source(mut self, data_source: std::sync::Arc<dyn data_source::DataSource>) -> Self {
+        self.data_source = Some(data_source);
+        self
+    }
+
+    /// Indexer for Explorer DB
+    pub fn pool(mut self, pool: sqlx::Pool<sqlx::Postgres>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// DB with `balance_changes` table.
+    /// If not provided, the main pool is used
+    #[cfg(all(
+        feature = "history",
+        any(feature = "coin", feature = "staking", feature = "accounts")
+    ))]
+    pub fn balances_pool(mut self, pool: sqlx::Pool<sqlx::Postgres>) -> Self {
+        self.pool_balances = Some(pool);
+        self
+    }
+
+    /// Which columns `balance_changes` has, see `balances_schema::detect`.
+    /// The current schema by default
+    #[cfg(all(
+        feature = "history",
+        any(feature = "coin", feature = "staking", feature = "accounts")
+    ))]
+    pub fn balances_schema(mut self, schema: balances_schema::BalancesSchema) -> Self {
+        self.balances_schema = schema;
+        self
+    }
+
+    /// DB with our own tables (see `migrations/`), e.g. sampled token prices.
+    /// If not provided, the main pool is used
+    pub fn aux_pool(mut self, pool: sqlx::Pool<sqlx::Postgres>) -> Self {
+        self.pool_aux = Some(pool);
+        self
+    }
+
+    /// Archival RPC is required if you want to query the data from the past,
+    /// unless `archival_rpc_client` is given
+    pub fn rpc_client(mut self, rpc_client: near_jsonrpc_client::JsonRpcClient) -> Self {
+        self.rpc_client = Some(rpc_client);
+        self
+    }
+
+    /// The historical calls go here, the recent ones go to the faster `rpc_client`
+    pub fn archival_rpc_client(mut self, rpc_client: near_jsonrpc_client::JsonRpcClient) -> Self {
+        self.archival_rpc_client = Some(rpc_client);
+        self
+    }
+
+    /// Refreshes the cached metadata of the most requested contracts every `warm_interval`.
+    /// Keep it a bit less than the cache TTL (1 hour), so the popular entries never expire. Disabled by default
+    pub fn cache_warm_interval(mut self, warm_interval: std::time::Duration) -> Self {
+        self.cache_warm_interval = Some(warm_interval);
+        self
+    }
+
+    /// Compares one random indexed FT balance with `ft_balance_of` every `check_interval`,
+    /// see `/admin/consistency`. Each check makes the RPC call, often to the archival node. Disabled by default
+    #[cfg(feature = "coin")]
+    pub fn consistency_check_interval(mut self, check_interval: std::time::Duration) -> Self {
+        self.consistency_check_interval = Some(check_interval);
+        self
+    }
+
+    /// The calls over the limit wait for the running ones. Unlimited by default
+    pub fn max_concurrent_rpc_calls(mut self, max_concurrent_rpc_calls: usize) -> Self {
+        self.max_concurrent_rpc_calls = Some(max_concurrent_rpc_calls);
+        self
+    }
+
+    /// Warns about the RPC calls slower than `threshold`, with the contract, the method and the block
+    pub fn slow_rpc_call_threshold(mut self, threshold: std::time::Duration) -> Self {
+        self.slow_rpc_call_threshold = Some(threshold);
+        self
+    }
+
+    /// Warns about the indexer DB queries slower than `threshold`, with the query and its parameters.
+    /// The setting is process-wide, it applies to all the contexts
+    pub fn slow_query_threshold(mut self, threshold: std::time::Duration) -> Self {
+        self.slow_query_threshold = Some(threshold);
+        self
+    }
+
+    /// Enables `/admin/...` endpoints, the requests should have `Authorization: Bearer <token>`
+    #[cfg(feature = "admin")]
+    pub fn admin_token(mut self, admin_token: String) -> Self {
+        self.admin_token = Some(admin_token);
+        self
+    }
+
+    /// Enables the usage accounting per API key, the counters are written to `api_usage` table
+    /// of the aux DB every `flush_interval`. Requires Tokio runtime on `build()`
+    pub fn usage_flush_interval(mut self, flush_interval: std::time::Duration) -> Self {
+        self.usage_flush_interval = Some(flush_interval);
+        self
+    }
+
+    /// Enables the quotas per API key from `api_quotas` table of the aux DB, reloaded every
+    /// `refresh_interval`. The usage is taken from `api_usage`, so the usage accounting should be enabled.
+    /// Requires Tokio runtime on `build()`
+    pub fn quota_refresh_interval(mut self, refresh_interval: std::time::Duration) -> Self {
+        self.quota_refresh_interval = Some(refresh_interval);
+        self
+    }
+
+    /// Enables HMAC request signing, the secrets of the API keys are derived from `master_secret`
+    pub fn request_signing(
+        mut self,
+        master_secret: String,
+        config: config::RequestSigningConfig,
+    ) -> Self {
+        self.request_signing = Some((master_secret, config));
+        self
+    }
+
+    /// Enables the audit trail of the admin and signed requests in `audit_log` table of the aux DB
+    pub fn audit_log(mut self) -> Self {
+        self.audit_log = true;
+        self
+    }
+
+    /// Serves the blocks, NEAR balances and the account existence from ScyllaDB
+    /// (see `scylla/schema.cql`), everything else still goes to Postgres and RPC.
+    /// The keyspace should be already selected
+    #[cfg(feature = "scylla")]
+    pub fn scylla_session(mut self, session: scylla::Session) -> Self {
+        self.scylla_session = Some(session);
+        self
+    }
+
+    /// Serves the NFT overview at the last block from `nft_owner_counts`.
+    /// Enable it only if the built-in indexer writes to the DB
+    #[cfg(feature = "nft")]
+    pub fn nft_owner_counts(mut self, enabled: bool) -> Self {
+        self.nft_owner_counts = enabled;
+        self
+    }
+
+    /// NFT contracts which are always marked with `is_spam` in the NFT overview
+    #[cfg(feature = "nft")]
+    pub fn nft_spam_denylist(mut self, contract_ids: Vec<String>) -> Self {
+        self.nft_spam_denylist = contract_ids;
+        self
+    }
+
+    /// Enables `/exports/...` endpoints, the export files are uploaded to `storage`.
+    /// Starts the export worker on `build()`, so it requires Tokio runtime
+    #[cfg(feature = "exports")]
+    pub fn export_storage(mut self, storage: object_storage::ObjectStorage) -> Self {
+        self.export_storage = Some(storage);
+        self
+    }
+
+    pub fn build(self) -> crate::Result<ServerContext> {
+        if let Some(threshold) = self.slow_query_threshold {
+            db_helpers::set_slow_query_threshold(threshold);
+        }
+        let pool_aux = self.pool_aux.or_else(|| self.pool.clone());
+        let require_pool_aux = || {
+            pool_aux.clone().ok_or_else(|| {
+                errors::Error::from(errors::ErrorKind::InternalError(
+                    "aux DB pool is not provided".to_string(),
+                ))
+            })
+        };
+        // The checks sample the indexer DB directly, the data source owns its pool
+        #[cfg(feature = "coin")]
+        let consistency_pool = self.pool.clone();
+        let usage_recorder = match self.usage_flush_interval {
+            Some(flush_interval) => usage::UsageRecorder::new(require_pool_aux()?, flush_interval),
+            None => usage::UsageRecorder::disabled(),
+        };
+        let quota_enforcer = match self.quota_refresh_interval {
+            Some(refresh_interval) => {
+                quotas::QuotaEnforcer::new(require_pool_aux()?, refresh_interval)
+            }
+            None => quotas::QuotaEnforcer::disabled(),
+        };
+        let audit_logger = if self.audit_log {
+            audit::AuditLogger::new(require_pool_aux()?)
+        } else {
+            audit::AuditLogger::disabled()
+        };
+        let cache_warmer = match self.cache_warm_interval {
+            Some(_) => cache_warmer::CacheWarmer::enabled(),
+            None => cache_warmer::CacheWarmer::disabled(),
+        };
+        let data_source: data_source::DataSourceRef = match self.data_source {
+            Some(data_source) => data_source,
+            None => {
+                let pool = self.pool.ok_or_else(|| {
+                    errors::ErrorKind::InternalError("DB pool is not provided".to_string())
+                })?;
+                let rpc_client = self.rpc_client.ok_or_else(|| {
+                    errors::ErrorKind::InternalError("RPC client is not provided".to_string())
+                })?;
+                let rpc_client = rpc_helpers::ArchivalRoutingRpcClient::new(
+                    rpc_client,
+                    self.archival_rpc_client,
+                );
+                let rpc_client = rpc_helpers::CapabilityCachingRpcClient::new(rpc_client);
+                let rpc_client = rpc_helpers::NegativeCachingRpcClient::new(rpc_client);
+                let rpc_client = rpc_helpers::SlowCallLoggingRpcClient::new(
+                    rpc_client,
+                    self.slow_rpc_call_threshold,
+                );
+                let rpc_client: Box<dyn rpc_helpers::RpcApi> = match self.max_concurrent_rpc_calls {
+                    Some(max_concurrent_calls) => Box::new(rpc_helpers::LimitedRpcClient::new(
+                        rpc_client,
+                        max_concurrent_calls,
+                    )),
+                    None => Box::new(rpc_client),
+                };
+                std::sync::Arc::new(data_source::PostgresDataSource {
+                    #[cfg(all(
+                        feature = "history",
+                        any(feature = "coin", feature = "staking", feature = "accounts")
+                    ))]
+                    pool_balances: balances_schema::BalancesDb::new(
+                        self.pool_balances.unwrap_or_else(|| pool.clone()),
+                        self.balances_schema,
+                    ),
+                    pool_aux: pool_aux.clone().unwrap_or_else(|| pool.clone()),
+                    pool,
+                    rpc_client,
+                    cache_warmer: cache_warmer.clone(),
+                    #[cfg(feature = "coin")]
+                    icon_cache: Default::default(),
+                    #[cfg(feature = "coin")]
+                    ft_metadata_cache: Default::default(),
+                    #[cfg(feature = "coin")]
+                    storage_price_cache: Default::default(),
+                    #[cfg(feature = "accounts")]
+                    counters_cache: Default::default(),
+                    #[cfg(feature = "nft")]
+                    nft_metadata_cache: Default::default(),
+                    #[cfg(feature = "nft")]
+                    nft_owner_counts: self.nft_owner_counts,
+                    #[cfg(feature = "nft")]
+                    nft_spam_denylist: self.nft_spam_denylist,
+                })
+            }
+        };
+        #[cfg(feature = "scylla")]
+        let data_source: data_source::DataSourceRef = match self.scylla_session {
+            Some(session) => std::sync::Arc::new(data_source::ScyllaDataSource {
+                session,
+                fallback: data_source,
+            }),
+            None => data_source,
+        };
+        if let Some(warm_interval) = self.cache_warm_interval {
+            cache_warmer.start(data_source.clone(), warm_interval);
+        }
+        let response_meta_filler = response_meta::ResponseMetaFiller::new();
+        response_meta_filler.start(data_source.clone());
+        #[cfg(feature = "coin")]
+        let consistency_checker = match self.consistency_check_interval {
+            Some(check_interval) => {
+                let consistency_pool = consistency_pool.ok_or_else(|| {
+                    errors::ErrorKind::InternalError("DB pool is not provided".to_string())
+                })?;
+                let checker = consistency_check::ConsistencyChecker::enabled();
+                checker.start(data_source.clone(), consistency_pool, check_interval);
+                checker
+            }
+            None => consistency_check::ConsistencyChecker::disabled(),
+        };
+        #[cfg(not(feature = "coin"))]
+        let consistency_checker = consistency_check::ConsistencyChecker::disabled();
+        #[cfg(feature = "exports")]
+        if let Some(storage) = &self.export_storage {
+            export_worker::start(data_source.clone(), require_pool_aux()?, storage.clone());
+        }
+        Ok(ServerContext {
+            data_source,
+            #[cfg(feature = "admin")]
+            admin_token: self.admin_token.clone(),
+            usage_recorder,
+            quota_enforcer,
+            signature_verifier: match self.request_signing {
+                Some((master_secret, config)) => {
+                    signing::SignatureVerifier::new(master_secret, config)
+                }
+                None => signing::SignatureVerifier::disabled(),
+            },
+            audit_logger,
+            route_toggles: Default::default(),
+            contract_allow_list: Default::default(),
+            #[cfg(feature = "admin")]
+            error_debug: error_debug::ErrorDebug::new(self.admin_token),
+            #[cfg(not(feature = "admin"))]
+            error_debug: Default::default(),
+            response_meta_filler,
+            consistency_checker,
+            #[cfg(feature = "exports")]
+            export_storage: self.export_storage,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_source::DataSource;
+
+    #[tokio::test]
+    async fn test_custom_data_source() {
+        let custom_data_source =
+            || std::sync::Arc::new(data_source::FixtureDataSource { fixtures_dir: None });
+        let ctx = ServerContext::builder()
+            .data_source(custom_data_source())
+            .build()
+            .unwrap();
+        let expected = custom_data_source().get_last_block().await.unwrap();
+        let block = ctx.data_source.get_last_block().await.unwrap();
+        assert_eq!(block.height, expected.height);
+
+        // The usage accounting needs the aux DB anyway
+        assert!(ServerContext::builder()
+            .data_source(custom_data_source())
+            .usage_flush_interval(std::time::Duration::from_secs(60))
+            .build()
+            .is_err());
+        // Neither the pool nor the custom data source
+        assert!(ServerContext::builder().build().is_err());
+    }
+}
@@ -0,0 +1,1466 @@
+use std::path::PathBuf;
+
+#[cfg(feature = "accounts")]
+use crate::modules::accounts;
+#[cfg(feature = "admin")]
+use crate::modules::admin;
+#[cfg(feature = "alerts")]
+use crate::modules::alerts;
+#[cfg(feature = "coin")]
+use crate::modules::coin;
+#[cfg(feature = "defi")]
+use crate::modules::defi;
+#[cfg(feature = "exports")]
+use crate::modules::exports;
+#[cfg(feature = "nft")]
+use crate::modules::nft;
+#[cfg(feature = "staking")]
+use crate::modules::staking;
+#[cfg(feature = "watchlists")]
+use crate::modules::watchlists;
+use crate::{db_helpers, errors, types};
+
+const MOCK_BLOCK_HEIGHT: u64 = 68000000;
+const MOCK_BLOCK_TIMESTAMP: u64 = 1655571176644255779;
+const MOCK_BLOCK_HASH: &str = "CuU3ptAEZ2EpDcwVSDhMkojYHsXG8YSmoKT7KmZXTnX";
+const MOCK_FIRST_SEEN_BLOCK_HASH: &str = "DLYC15rMw5KSvbNvpqUhfxVgkJKZchr9jg8au9ggJVZA";
+
+/// Serves the canned data without DB and RPC (`--mock` mode), so the frontend teams could work offline.
+///
+/// For each method, we look for `{fixtures_dir}/{method}/{key}.json`, then for `{fixtures_dir}/{method}.json`,
+/// where `key` is the account_id (or contract_id, or `{contract_id}_{token_id}` for NFTs).
+/// The file should contain the same JSON as the corresponding part of the API response
+/// (e.g. `get_coin_balances.json` has the list of coins).
+/// If nothing is found, the built-in deterministic data is returned.
+pub(crate) struct FixtureDataSource {
+    pub fixtures_dir: Option<PathBuf>,
+}
+
+impl FixtureDataSource {
+    fn load<T: serde::de::DeserializeOwned>(
+        &self,
+        method: &str,
+        key: &str,
+    ) -> crate::Result<Option<T>> {
+        let fixtures_dir = match &self.fixtures_dir {
+            Some(dir) => dir,
+            None => return Ok(None),
+        };
+        let file_name = format!("{}.json", sanitize_key(key));
+        for path in [
+            fixtures_dir.join(method).join(file_name),
+            fixtures_dir.join(format!("{}.json", method)),
+        ] {
+            if path.exists() {
+                let content = std::fs::read_to_string(&path).map_err(|err| {
+                    errors::ErrorKind::InternalError(format!(
+                        "Failed to read the fixture {}: {}",
+                        path.display(),
+                        err
+                    ))
+                })?;
+                return Ok(Some(serde_json::from_str(&content)?));
+            }
+        }
+        Ok(None)
+    }
+}
+
+// token_id could contain anything, we don't want to go out of the fixtures folder
+fn sanitize_key(key: &str) -> String {
+    key.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn mock_block() -> db_helpers::Block {
+    db_helpers::Block {
+        timestamp: MOCK_BLOCK_TIMESTAMP,
+        height: MOCK_BLOCK_HEIGHT,
+        hash: MOCK_BLOCK_HASH.to_string(),
+    }
+}
+
+/// The fixtures do not have the positions of the events, they are made up in the order of the items
+#[cfg(feature = "history")]
+fn with_mock_event_indexes<T>(
+    items: Vec<T>,
+    block_timestamp: impl Fn(&T) -> u64,
+) -> Vec<(T, types::query_params::EventIndex)> {
+    let count = items.len() as u64;
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let event_index = types::query_params::EventIndex {
+                block_timestamp: block_timestamp(&item),
+                shard_id: 0,
+                index_in_chunk: count - i as u64,
+            };
+            (item, event_index)
+        })
+        .collect()
+}
+
+#[cfg(feature = "coin")]
+fn mock_ft_metadata() -> coin::schemas::FtContractMetadata {
+    coin::schemas::FtContractMetadata {
+        spec: "ft-1.0.0".to_string(),
+        name: "Mock fungible token".to_string(),
+        symbol: "MOCK".to_string(),
+        icon: None,
+        reference: None,
+        reference_hash: None,
+        decimals: 18,
+    }
+}
+
+#[cfg(feature = "nft")]
+fn mock_nft_contract_metadata() -> nft::schemas::NftContractMetadata {
+    nft::schemas::NftContractMetadata {
+        spec: "nft-1.0.0".to_string(),
+        name: "Mock NFT collection".to_string(),
+        symbol: "MOCK".to_string(),
+        icon: None,
+        base_uri: None,
+        reference: None,
+        reference_hash: None,
+    }
+}
+
+#[cfg(feature = "nft")]
+fn mock_nft(token_id: &str, owner_account_id: &str) -> nft::schemas::Nft {
+    nft::schemas::Nft {
+        token_id: token_id.to_string(),
+        owner_account_id: owner_account_id.to_string(),
+        metadata: nft::schemas::NftMetadata {
+            title: Some(format!("Mock token #{}", token_id)),
+            description: Some("Generated by the mock mode".to_string()),
+            media: None,
+            media_hash: None,
+            copies: Some(1),
+            issued_at: None,
+            expires_at: None,
+            starts_at: None,
+            updated_at: None,
+            extra: None,
+            reference: None,
+            reference_hash: None,
+        },
+    }
+}
+
+#[async_trait::async_trait]
+impl super::DataSource for FixtureDataSource {
+    async fn get_block_from_params(
+        &self,
+        params: &types::query_params::BlockParams,
+    ) -> crate::Result<db_helpers::Block> {
+        // We pretend that any block the user asks for exists
+        Ok(if let Some(block_height) = params.block_height {
+            db_helpers::Block {
+                timestamp: MOCK_BLOCK_TIMESTAMP,
+                height: block_height.0,
+                hash: MOCK_BLOCK_HASH.to_string(),
+            }
+        } else if let Some(block_timestamp) = params.block_timestamp_nanos {
+            db_helpers::Block {
+                timestamp: block_timestamp.0,
+                height: MOCK_BLOCK_HEIGHT,
+                hash: MOCK_BLOCK_HASH.to_string(),
+            }
+        } else {
+            mock_block()
+        })
+    }
+
+    async fn get_last_block(&self) -> crate::Result<db_helpers::Block> {
+        Ok(mock_block())
+    }
+
+    async fn get_block_by_id(&self, block_id: &str) -> crate::Result<db_helpers::Block> {
+        Ok(match block_id.parse::<u64>() {
+            Ok(block_height) => db_helpers::Block {
+                timestamp: MOCK_BLOCK_TIMESTAMP,
+                height: block_height,
+                hash: MOCK_BLOCK_HASH.to_string(),
+            },
+            Err(_) => mock_block(),
+        })
+    }
+
+    async fn get_blocks_by_heights(
+        &self,
+        block_heights: &[u64],
+    ) -> crate::Result<Vec<db_helpers::Block>> {
+        let mut block_heights = block_heights.to_vec();
+        block_heights.sort_unstable();
+        block_heights.dedup();
+        Ok(block_heights
+            .into_iter()
+            .map(|height| db_helpers::Block {
+                timestamp: MOCK_BLOCK_TIMESTAMP,
+                height,
+                hash: MOCK_BLOCK_HASH.to_string(),
+            })
+            .collect())
+    }
+
+    async fn does_account_exist(
+        &self,
+        _account_id: &near_primitives::types::AccountId,
+        _block_timestamp: u64,
+    ) -> crate::Result<bool> {
+        Ok(true)
+    }
+
+    async fn get_code_changes(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        _block: &db_helpers::Block,
+        limit: u32,
+    ) -> crate::Result<Vec<types::CodeChange>> {
+        let mut changes = match self.load("get_code_changes", contract_id.as_str())? {
+            Some(changes) => changes,
+            None => vec![types::CodeChange {
+                code_hash: "11111111111111111111111111111111".to_string(),
+                previous_code_hash: None,
+                deployer_account_id: contract_id.clone().into(),
+                receipt_id: "mock_receipt".to_string(),
+                transaction_hash: Some("mock_transaction".to_string()),
+                block_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+                block_height: types::U64(MOCK_BLOCK_HEIGHT),
+                block_hash: MOCK_BLOCK_HASH.to_string(),
+            }],
+        };
+        changes.truncate(limit as usize);
+        Ok(changes)
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_near_balance(
+        &self,
+        block: &db_helpers::Block,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<coin::schemas::NearBalanceResponse> {
+        let balance = self
+            .load::<types::U128>("get_near_balance", account_id.as_str())?
+            .unwrap_or(types::U128(10u128.pow(24)));
+        Ok(coin::schemas::NearBalanceResponse {
+            balance,
+            metadata: coin::data_provider::get_near_metadata(),
+            meta: types::ResponseMeta::new(&block),
+        })
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_near_balance_breakdown(
+        &self,
+        block: &db_helpers::Block,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<coin::schemas::NearBalanceBreakdownResponse> {
+        if let Some(breakdown) = self.load("get_near_balance_breakdown", account_id.as_str())? {
+            return Ok(breakdown);
+        }
+        // The plain account with one full access key, at 0.01 NEAR per KB
+        let balance = self.get_near_balance(block, account_id).await?.balance;
+        let storage_locked_balance = types::U128(182 * 10u128.pow(19));
+        Ok(coin::schemas::NearBalanceBreakdownResponse {
+            total_balance: balance,
+            staked_balance: types::U128(0),
+            storage_usage: types::U64(182),
+            storage_amount_per_byte: types::U128(10u128.pow(19)),
+            storage_locked_balance,
+            available_balance: types::U128(balance.0.saturating_sub(storage_locked_balance.0)),
+            meta: types::ResponseMeta::new(block),
+        })
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_coin_balances(
+        &self,
+        _block: &db_helpers::Block,
+        account_id: &near_primitives::types::AccountId,
+        pagination: &types::query_params::Pagination,
+    ) -> crate::Result<(Vec<coin::schemas::Coin>, Vec<types::FailedContract>)> {
+        let mut balances = match self.load("get_coin_balances", account_id.as_str())? {
+            Some(balances) => balances,
+            None => vec![coin::schemas::Coin {
+                standard: "nep141".to_string(),
+                balance: types::U128(10u128.pow(18)),
+                contract_account_id: Some("mock.near".parse()?),
+                metadata: coin::schemas::CoinMetadata::from_ft_metadata(
+                    &"mock.near".parse()?,
+                    mock_ft_metadata(),
+                ),
+                liquid_staking: None,
+            }],
+        };
+        balances.truncate(pagination.limit as usize);
+        Ok((balances, vec![]))
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_coin_balances_by_contract(
+        &self,
+        _block: &db_helpers::Block,
+        contract_id: &near_primitives::types::AccountId,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<Vec<coin::schemas::Coin>> {
+        let key = format!("{}_{}", contract_id, account_id);
+        Ok(match self.load("get_coin_balances_by_contract", &key)? {
+            Some(balances) => balances,
+            None => vec![coin::schemas::Coin {
+                standard: "nep141".to_string(),
+                balance: types::U128(10u128.pow(18)),
+                contract_account_id: Some(contract_id.clone().into()),
+                metadata: coin::schemas::CoinMetadata::from_ft_metadata(
+                    contract_id,
+                    mock_ft_metadata(),
+                ),
+                liquid_staking: None,
+            }],
+        })
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_ft_balance(
+        &self,
+        _block: &db_helpers::Block,
+        contract_id: &near_primitives::types::AccountId,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<u128> {
+        let key = format!("{}_{}", contract_id, account_id);
+        Ok(self
+            .load::<types::U128>("get_ft_balance", &key)?
+            .unwrap_or(types::U128(10u128.pow(18)))
+            .0)
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_ft_balance_from_events(
+        &self,
+        _block: &db_helpers::Block,
+        contract_id: &near_primitives::types::AccountId,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<u128> {
+        let key = format!("{}_{}", contract_id, account_id);
+        Ok(self
+            .load::<types::U128>("get_ft_balance_from_events", &key)?
+            .unwrap_or(types::U128(10u128.pow(18)))
+            .0)
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_changed_ft_contracts(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        _from_block: &db_helpers::Block,
+        _to_block: &db_helpers::Block,
+        limit: u32,
+    ) -> crate::Result<Vec<coin::data_provider::ChangedFtContract>> {
+        let mut contract_ids: Vec<types::AccountId> =
+            match self.load("get_changed_ft_contracts", account_id.as_str())? {
+                Some(contract_ids) => contract_ids,
+                None => vec!["mock.near".parse()?],
+            };
+        contract_ids.truncate(limit as usize);
+        Ok(contract_ids
+            .into_iter()
+            .map(|contract_id| coin::data_provider::ChangedFtContract {
+                contract_id: contract_id.0,
+                held_before: true,
+            })
+            .collect())
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_near_last_modified(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        _block: &db_helpers::Block,
+    ) -> crate::Result<Option<u64>> {
+        Ok(self
+            .load::<Option<types::U64>>("get_near_last_modified", account_id.as_str())?
+            .unwrap_or(Some(types::U64(MOCK_BLOCK_TIMESTAMP)))
+            .map(|timestamp| timestamp.0))
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_ft_last_modified(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        _contract_id: Option<&near_primitives::types::AccountId>,
+        _block: &db_helpers::Block,
+    ) -> crate::Result<Option<u64>> {
+        Ok(self
+            .load::<Option<types::U64>>("get_ft_last_modified", account_id.as_str())?
+            .unwrap_or(Some(types::U64(MOCK_BLOCK_TIMESTAMP)))
+            .map(|timestamp| timestamp.0))
+    }
+
+    #[cfg(all(feature = "coin", feature = "history"))]
+    async fn get_near_history(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(coin::schemas::HistoryItem, types::query_params::EventIndex)>> {
+        let mut history = match self.load("get_near_history", account_id.as_str())? {
+            Some(history) => history,
+            None => vec![coin::schemas::HistoryItem {
+                involved_account_id: Some("mock.near".parse()?),
+                signer_account_id: Some("mock.near".parse()?),
+                predecessor_account_id: Some("mock.near".parse()?),
+                delta_balance: types::I128(10i128.pow(24)),
+                balance: types::U128(10u128.pow(24)),
+                cause: coin::schemas::HistoryCause::Transfer,
+                status: "SUCCESS".to_string(),
+                coin_metadata: coin::data_provider::get_near_metadata(),
+                block_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+            }],
+        };
+        history.truncate(pagination.limit as usize);
+        Ok(with_mock_event_indexes(history, |item| {
+            item.block_timestamp_nanos.0
+        }))
+    }
+
+    #[cfg(all(feature = "coin", feature = "history"))]
+    async fn get_gas_fees(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+        window_nanos: u64,
+    ) -> crate::Result<coin::schemas::GasFeesResponse> {
+        if let Some(fees) = self.load("get_gas_fees", account_id.as_str())? {
+            return Ok(fees);
+        }
+        let day_nanos = 24 * 60 * 60 * 1_000_000_000;
+        Ok(coin::schemas::GasFeesResponse {
+            total_burnt: types::U128(3 * 10u128.pow(21)),
+            payments_count: 3,
+            daily: vec![coin::schemas::GasFeesBucket {
+                timestamp_nanos: types::U64(block.timestamp / day_nanos * day_nanos),
+                burnt: types::U128(3 * 10u128.pow(21)),
+            }],
+            window_start_timestamp_nanos: types::U64(block.timestamp.saturating_sub(window_nanos)),
+            meta: types::ResponseMeta::new(&block),
+        })
+    }
+
+    #[cfg(all(feature = "coin", feature = "history"))]
+    async fn get_coin_history(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        account_id: &near_primitives::types::AccountId,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(coin::schemas::HistoryItem, types::query_params::EventIndex)>> {
+        let key = format!("{}_{}", contract_id, account_id);
+        let mut history = match self.load("get_coin_history", &key)? {
+            Some(history) => history,
+            None => vec![coin::schemas::HistoryItem {
+                involved_account_id: None,
+                signer_account_id: Some(contract_id.clone().into()),
+                predecessor_account_id: Some(contract_id.clone().into()),
+                delta_balance: types::I128(10i128.pow(18)),
+                balance: types::U128(10u128.pow(18)),
+                cause: coin::schemas::HistoryCause::Mint,
+                status: "SUCCESS".to_string(),
+                coin_metadata: coin::schemas::CoinMetadata::from_ft_metadata(
+                    contract_id,
+                    mock_ft_metadata(),
+                ),
+                block_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+            }],
+        };
+        history.truncate(pagination.limit as usize);
+        Ok(with_mock_event_indexes(history, |item| {
+            item.block_timestamp_nanos.0
+        }))
+    }
+
+    #[cfg(all(feature = "coin", feature = "history"))]
+    async fn get_ft_transfers(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(coin::schemas::FtTransfer, types::query_params::EventIndex)>> {
+        let mut transfers = match self.load("get_ft_transfers", contract_id.as_str())? {
+            Some(transfers) => transfers,
+            None => vec![coin::schemas::FtTransfer {
+                cause: coin::schemas::HistoryCause::Mint,
+                old_account_id: None,
+                new_account_id: Some("mock.near".parse()?),
+                amount: types::U128(10u128.pow(18)),
+                status: "SUCCESS".to_string(),
+                receipt_id: "mock_receipt".to_string(),
+                block_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+                block_height: types::U64(MOCK_BLOCK_HEIGHT),
+                block_hash: MOCK_BLOCK_HASH.to_string(),
+            }],
+        };
+        transfers.truncate(pagination.limit as usize);
+        Ok(with_mock_event_indexes(transfers, |transfer| {
+            transfer.block_timestamp_nanos.0
+        }))
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_ft_holders(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        _block_timestamp: u64,
+        after_account_id: Option<&near_primitives::types::AccountId>,
+        limit: u32,
+    ) -> crate::Result<Vec<coin::schemas::FtHolder>> {
+        let holders: Vec<coin::schemas::FtHolder> =
+            match self.load("get_ft_holders", contract_id.as_str())? {
+                Some(holders) => holders,
+                None => vec![coin::schemas::FtHolder {
+                    account_id: "mock.near".parse()?,
+                    balance: types::U128(10u128.pow(18)),
+                }],
+            };
+        // The export goes through all the pages, so the cursor has to work here as well
+        Ok(holders
+            .into_iter()
+            .filter(|holder| after_account_id.map_or(true, |after| holder.account_id.0 > *after))
+            .take(limit as usize)
+            .collect())
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_ft_contract_metadata(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        _block_height: u64,
+    ) -> crate::Result<coin::schemas::FtContractMetadata> {
+        Ok(self
+            .load("get_ft_contract_metadata", contract_id.as_str())?
+            .unwrap_or_else(mock_ft_metadata))
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_ft_contracts_metadata(
+        &self,
+        contract_ids: &[near_primitives::types::AccountId],
+        block_height: u64,
+    ) -> crate::Result<(
+        Vec<coin::schemas::FtContractMetadataItem>,
+        Vec<types::FailedContract>,
+    )> {
+        let mut items: Vec<coin::schemas::FtContractMetadataItem> = vec![];
+        for contract_id in contract_ids {
+            items.push(coin::schemas::FtContractMetadataItem {
+                contract_account_id: contract_id.clone().into(),
+                metadata: self
+                    .get_ft_contract_metadata(contract_id, block_height)
+                    .await?,
+            });
+        }
+        Ok((items, vec![]))
+    }
+
+    // There are no caches in the mock mode
+    #[cfg(feature = "coin")]
+    async fn refresh_ft_contract_metadata(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block_height: u64,
+    ) -> crate::Result<coin::schemas::FtContractMetadata> {
+        self.get_ft_contract_metadata(contract_id, block_height)
+            .await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_ft_icon(
+        &self,
+        _contract_id: &near_primitives::types::AccountId,
+        _block_height: u64,
+    ) -> crate::Result<Option<coin::data_provider::Icon>> {
+        Ok(Some(coin::data_provider::Icon::Inline {
+            content_type: "image/svg+xml".to_string(),
+            bytes: br#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 2 2"><circle cx="1" cy="1" r="1"/></svg>"#.to_vec(),
+        }))
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_token_price_history(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        bucket: &coin::schemas::PriceBucket,
+        block_timestamp: u64,
+        limit: u32,
+    ) -> crate::Result<Vec<coin::schemas::PricePoint>> {
+        if let Some(prices) = self.load("get_token_price_history", contract_id.as_str())? {
+            return Ok(prices);
+        }
+        let duration = bucket.duration_nanos();
+        let last_bucket = block_timestamp / duration * duration;
+        Ok((0..std::cmp::min(limit as u64, 24))
+            .map(|i| {
+                // Slow saw around 1 USD, the same for every call
+                let cents = 100 + (i % 5);
+                coin::schemas::PricePoint {
+                    timestamp_nanos: types::U64(last_bucket.saturating_sub(i * duration)),
+                    open: format!("{}.{:02}", cents / 100, cents % 100),
+                    high: format!("{}.{:02}", (cents + 1) / 100, (cents + 1) % 100),
+                    low: format!("{}.{:02}", (cents - 1) / 100, (cents - 1) % 100),
+                    close: format!("{}.{:02}", cents / 100, cents % 100),
+                }
+            })
+            .collect())
+    }
+
+    #[cfg(feature = "nft")]
+    async fn get_nfts_count(
+        &self,
+        _block: &db_helpers::Block,
+        account_id: &near_primitives::types::AccountId,
+        pagination_params: types::query_params::PaginationParams,
+    ) -> crate::Result<(Vec<nft::schemas::NftCount>, Vec<types::FailedContract>)> {
+        let pagination = types::query_params::Pagination::from(pagination_params);
+        let mut counts = match self.load("get_nfts_count", account_id.as_str())? {
+            Some(counts) => counts,
+            None => vec![nft::schemas::NftCount {
+                contract_account_id: "mock.near".parse()?,
+                nft_count: 1,
+                last_updated_at_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+                contract_metadata: mock_nft_contract_metadata(),
+                standards_missing: vec![],
+                stale: false,
+                is_spam: false,
+            }],
+        };
+        counts.truncate(pagination.limit as usize);
+        Ok((counts, vec![]))
+    }
+
+    #[cfg(feature = "nft")]
+    async fn get_nfts_by_contract(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        account_id: &near_primitives::types::AccountId,
+        _block_height: u64,
+        limit: u32,
+    ) -> crate::Result<Vec<nft::schemas::Nft>> {
+        let key = format!("{}_{}", contract_id, account_id);
+        let mut nfts = match self.load("get_nfts_by_contract", &key)? {
+            Some(nfts) => nfts,
+            None => vec![mock_nft("1", account_id.as_str())],
+        };
+        nfts.truncate(limit as usize);
+        Ok(nfts)
+    }
+
+    #[cfg(feature = "nft")]
+    async fn get_nft(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        token_id: &str,
+        _block_height: u64,
+    ) -> crate::Result<nft::schemas::Nft> {
+        let key = format!("{}_{}", contract_id, token_id);
+        Ok(self
+            .load("get_nft", &key)?
+            .unwrap_or_else(|| mock_nft(token_id, "mock.near")))
+    }
+
+    #[cfg(all(feature = "nft", feature = "history"))]
+    async fn get_nft_history(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        token_id: &str,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(nft::schemas::HistoryItem, types::query_params::EventIndex)>> {
+        let key = format!("{}_{}", contract_id, token_id);
+        let mut history = match self.load("get_nft_history", &key)? {
+            Some(history) => history,
+            None => vec![nft::schemas::HistoryItem {
+                cause: "MINT".to_string(),
+                old_account_id: None,
+                new_account_id: Some("mock.near".parse()?),
+                status: "SUCCESS".to_string(),
+                block_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+                block_height: types::U64(MOCK_BLOCK_HEIGHT),
+                block_hash: MOCK_BLOCK_HASH.to_string(),
+            }],
+        };
+        history.truncate(pagination.limit as usize);
+        Ok(with_mock_event_indexes(history, |item| {
+            item.block_timestamp_nanos.0
+        }))
+    }
+
+    #[cfg(feature = "nft")]
+    async fn get_nft_contract_metadata(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        _block_height: u64,
+    ) -> crate::Result<Option<nft::schemas::NftContractMetadata>> {
+        Ok(Some(
+            self.load("get_nft_contract_metadata", contract_id.as_str())?
+                .unwrap_or_else(mock_nft_contract_metadata),
+        ))
+    }
+
+    #[cfg(feature = "nft")]
+    async fn get_nft_contracts_metadata(
+        &self,
+        contract_ids: &[near_primitives::types::AccountId],
+        block_height: u64,
+    ) -> crate::Result<(
+        Vec<nft::schemas::NftContractMetadataItem>,
+        Vec<types::FailedContract>,
+    )> {
+        let mut items: Vec<nft::schemas::NftContractMetadataItem> = vec![];
+        for contract_id in contract_ids {
+            let metadata = self
+                .get_nft_contract_metadata(contract_id, block_height)
+                .await?;
+            items.push(nft::schemas::NftContractMetadataItem {
+                contract_account_id: contract_id.clone().into(),
+                standards_missing: nft::schemas::standards_missing(metadata.as_ref()),
+                metadata,
+            });
+        }
+        Ok((items, vec![]))
+    }
+
+    #[cfg(feature = "nft")]
+    async fn refresh_nft_contract_metadata(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block_height: u64,
+    ) -> crate::Result<Option<nft::schemas::NftContractMetadata>> {
+        self.get_nft_contract_metadata(contract_id, block_height)
+            .await
+    }
+
+    #[cfg(all(feature = "nft", feature = "history"))]
+    async fn get_nft_sales(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        token_id: &str,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(nft::schemas::NftSale, types::query_params::EventIndex)>> {
+        let key = format!("{}_{}", contract_id, token_id);
+        let mut sales = match self.load("get_nft_sales", &key)? {
+            Some(sales) => sales,
+            None => vec![nft::schemas::NftSale {
+                marketplace: "paras".to_string(),
+                marketplace_account_id: "marketplace.paras.near".parse()?,
+                buyer_account_id: "mock.near".parse()?,
+                price: types::U128(5 * 10u128.pow(24)),
+                receipt_id: "mock_receipt".to_string(),
+                block_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+            }],
+        };
+        sales.truncate(pagination.limit as usize);
+        Ok(with_mock_event_indexes(sales, |sale| {
+            sale.block_timestamp_nanos.0
+        }))
+    }
+
+    #[cfg(all(feature = "nft", feature = "history"))]
+    async fn get_nft_approvals_history(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(nft::schemas::NftApproval, types::query_params::EventIndex)>> {
+        let mut history = match self.load("get_nft_approvals_history", account_id.as_str())? {
+            Some(history) => history,
+            None => vec![nft::schemas::NftApproval {
+                cause: "APPROVE".to_string(),
+                contract_account_id: "mock.near".parse()?,
+                token_id: "1".to_string(),
+                approved_account_id: Some("marketplace.paras.near".parse()?),
+                status: "SUCCESS".to_string(),
+                receipt_id: "mock_receipt".to_string(),
+                block_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+            }],
+        };
+        history.truncate(pagination.limit as usize);
+        Ok(with_mock_event_indexes(history, |event| {
+            event.block_timestamp_nanos.0
+        }))
+    }
+
+    #[cfg(all(feature = "nft", feature = "history"))]
+    async fn get_nft_provenance(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        token_id: &str,
+        _block: &db_helpers::Block,
+    ) -> crate::Result<Vec<nft::schemas::ProvenanceItem>> {
+        let key = format!("{}_{}", contract_id, token_id);
+        if let Some(provenance) = self.load("get_nft_provenance", &key)? {
+            return Ok(provenance);
+        }
+        Ok(vec![nft::schemas::ProvenanceItem {
+            cause: "MINT".to_string(),
+            from_account_id: None,
+            owner_account_id: Some("mock.near".parse()?),
+            price: None,
+            marketplace: None,
+            block_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+            block_height: types::U64(MOCK_BLOCK_HEIGHT),
+            block_hash: MOCK_BLOCK_HASH.to_string(),
+        }])
+    }
+
+    #[cfg(feature = "nft")]
+    async fn get_nft_market_stats(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+        window_nanos: u64,
+    ) -> crate::Result<nft::schemas::NftMarketStatsResponse> {
+        if let Some(stats) = self.load("get_nft_market_stats", contract_id.as_str())? {
+            return Ok(stats);
+        }
+        Ok(nft::schemas::NftMarketStatsResponse {
+            sales_count: 2,
+            volume: types::U128(8 * 10u128.pow(24)),
+            floor_price: Some(types::U128(3 * 10u128.pow(24))),
+            average_price: Some(types::U128(4 * 10u128.pow(24))),
+            window_start_timestamp_nanos: types::U64(block.timestamp.saturating_sub(window_nanos)),
+            meta: types::ResponseMeta::new(&block),
+        })
+    }
+
+    #[cfg(feature = "nft")]
+    async fn get_nft_holdings_diff(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        _from_block: &db_helpers::Block,
+        _to_block: &db_helpers::Block,
+        limit: u32,
+    ) -> crate::Result<nft::data_provider::NftHoldingsDiff> {
+        let mut gained: Vec<nft::schemas::NftHoldingChange> =
+            match self.load("get_nft_holdings_diff", account_id.as_str())? {
+                Some(gained) => gained,
+                None => vec![nft::schemas::NftHoldingChange {
+                    contract_account_id: "mock.near".parse()?,
+                    token_id: "1".to_string(),
+                }],
+            };
+        gained.truncate(limit as usize);
+        Ok(nft::data_provider::NftHoldingsDiff {
+            gained,
+            lost: vec![],
+        })
+    }
+
+    #[cfg(feature = "staking")]
+    async fn get_staking_summary(
+        &self,
+        block: &db_helpers::Block,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<staking::schemas::StakingSummaryResponse> {
+        if let Some(summary) = self.load("get_staking_summary", account_id.as_str())? {
+            return Ok(summary);
+        }
+        Ok(staking::schemas::StakingSummaryResponse {
+            total_staked_balance: types::U128(100 * 10u128.pow(24)),
+            total_unstaked_balance: types::U128(10 * 10u128.pow(24)),
+            apy: 9.9,
+            delegations: vec![staking::schemas::Delegation {
+                pool_account_id: "mock.poolv1.near".parse()?,
+                staked_balance: types::U128(100 * 10u128.pow(24)),
+                unstaked_balance: types::U128(10 * 10u128.pow(24)),
+                can_withdraw: false,
+                unstake_release_epoch_height: Some(types::U64(1004)),
+                fee: 10.0,
+                apy: 9.9,
+            }],
+            epoch_height: types::U64(1000),
+            meta: types::ResponseMeta::new(&block),
+        })
+    }
+
+    #[cfg(feature = "staking")]
+    async fn get_staking_pools(
+        &self,
+        block: &db_helpers::Block,
+    ) -> crate::Result<staking::schemas::StakingPoolsResponse> {
+        if let Some(pools) = self.load("get_staking_pools", "all")? {
+            return Ok(pools);
+        }
+        Ok(staking::schemas::StakingPoolsResponse {
+            pools: vec![staking::schemas::StakingPool {
+                pool_account_id: "mock.poolv1.near".parse()?,
+                total_staked_balance: types::U128(10_000_000 * 10u128.pow(24)),
+                fee: Some(10.0),
+                delegators_count: Some(42),
+                uptime: 99.5,
+                apy: 9.85,
+                historical_apy: Some(9.7),
+            }],
+            epoch_height: types::U64(1000),
+            meta: types::ResponseMeta::new(&block),
+        })
+    }
+
+    #[cfg(feature = "staking")]
+    async fn get_supply_stats(
+        &self,
+        block: &db_helpers::Block,
+    ) -> crate::Result<staking::schemas::SupplyStatsResponse> {
+        if let Some(stats) = self.load("get_supply_stats", "all")? {
+            return Ok(stats);
+        }
+        Ok(staking::schemas::SupplyStatsResponse {
+            total_supply: types::U128(1_100_000_000 * 10u128.pow(24)),
+            circulating_supply: Some(types::U128(800_000_000 * 10u128.pow(24))),
+            total_staked_balance: types::U128(450_000_000 * 10u128.pow(24)),
+            seat_price: types::U128(50_000 * 10u128.pow(24)),
+            max_inflation_rate: 5.0,
+            network_apy: 11.0,
+            epoch_height: types::U64(1000),
+            meta: types::ResponseMeta::new(&block),
+        })
+    }
+
+    #[cfg(feature = "defi")]
+    async fn get_ref_finance_positions(
+        &self,
+        block: &db_helpers::Block,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<defi::schemas::RefFinancePositionsResponse> {
+        if let Some(positions) = self.load("get_ref_finance_positions", account_id.as_str())? {
+            return Ok(positions);
+        }
+        Ok(defi::schemas::RefFinancePositionsResponse {
+            positions: vec![defi::schemas::LiquidityPosition {
+                pool_id: types::U64(79),
+                pool_kind: "SIMPLE_POOL".to_string(),
+                shares: types::U128(10u128.pow(24)),
+                farming_shares: types::U128(0),
+                shares_total_supply: types::U128(1000 * 10u128.pow(24)),
+                tokens: vec![
+                    defi::schemas::UnderlyingToken {
+                        contract_account_id: "wrap.near".parse()?,
+                        amount: types::U128(5 * 10u128.pow(24)),
+                    },
+                    defi::schemas::UnderlyingToken {
+                        contract_account_id: "usn".parse()?,
+                        amount: types::U128(12 * 10u128.pow(18)),
+                    },
+                ],
+            }],
+            meta: types::ResponseMeta::new(&block),
+        })
+    }
+
+    #[cfg(feature = "accounts")]
+    async fn get_account_counters(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+        _is_latest: bool,
+    ) -> crate::Result<accounts::schemas::AccountCountersResponse> {
+        if let Some(counters) = self.load("get_account_counters", account_id.as_str())? {
+            return Ok(counters);
+        }
+        Ok(accounts::schemas::AccountCountersResponse {
+            transactions_count: 42,
+            contracts_count: 7,
+            first_activity_timestamp_nanos: Some(types::U64(1600000000000000000)),
+            last_activity_timestamp_nanos: Some(types::U64(MOCK_BLOCK_TIMESTAMP)),
+            meta: types::ResponseMeta::new(&block),
+        })
+    }
+
+    #[cfg(feature = "accounts")]
+    async fn get_account_activity_summary(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+    ) -> crate::Result<Option<accounts::schemas::AccountActivitySummaryResponse>> {
+        if let Some(summary) = self.load("get_account_activity_summary", account_id.as_str())? {
+            return Ok(Some(summary));
+        }
+        Ok(Some(accounts::schemas::AccountActivitySummaryResponse {
+            first_seen_timestamp_nanos: types::U64(1600000000000000000),
+            first_seen_block_height: types::U64(9820210),
+            first_seen_block_hash: MOCK_FIRST_SEEN_BLOCK_HASH.to_string(),
+            last_active_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+            last_active_block_height: types::U64(MOCK_BLOCK_HEIGHT),
+            last_active_block_hash: MOCK_BLOCK_HASH.to_string(),
+            is_deleted: false,
+            meta: types::ResponseMeta::new(&block),
+        }))
+    }
+
+    #[cfg(feature = "accounts")]
+    async fn get_deployments(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        _block: &db_helpers::Block,
+        limit: u32,
+    ) -> crate::Result<Vec<accounts::schemas::Deployment>> {
+        let mut deployments = match self.load("get_deployments", contract_id.as_str())? {
+            Some(deployments) => deployments,
+            None => vec![accounts::schemas::Deployment {
+                code_hash: "11111111111111111111111111111111".to_string(),
+                deployer_account_id: contract_id.clone().into(),
+                receipt_id: "mock_receipt".to_string(),
+                status: "SUCCESS".to_string(),
+                block_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+                block_height: types::U64(MOCK_BLOCK_HEIGHT),
+                block_hash: MOCK_BLOCK_HASH.to_string(),
+            }],
+        };
+        deployments.truncate(limit as usize);
+        Ok(deployments)
+    }
+
+    #[cfg(feature = "accounts")]
+    async fn get_receipt(
+        &self,
+        receipt_id: &str,
+        _block: &db_helpers::Block,
+    ) -> crate::Result<Option<accounts::schemas::Receipt>> {
+        if let Some(receipt) = self.load("get_receipt", receipt_id)? {
+            return Ok(Some(receipt));
+        }
+        Ok(Some(accounts::schemas::Receipt {
+            receipt_id: receipt_id.to_string(),
+            predecessor_account_id: "mock.near".parse()?,
+            receiver_account_id: "mock.near".parse()?,
+            status: "SUCCESS".to_string(),
+            actions: vec![accounts::schemas::Action::Transfer {
+                deposit: types::U128(10u128.pow(24)),
+            }],
+            block_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+            block_height: types::U64(MOCK_BLOCK_HEIGHT),
+            block_hash: MOCK_BLOCK_HASH.to_string(),
+        }))
+    }
+
+    #[cfg(feature = "accounts")]
+    async fn get_gas_profile(
+        &self,
+        transaction_hash: &str,
+        _block: &db_helpers::Block,
+    ) -> crate::Result<Option<accounts::schemas::GasProfile>> {
+        if let Some(profile) = self.load("get_gas_profile", transaction_hash)? {
+            return Ok(Some(profile));
+        }
+        Ok(Some(accounts::schemas::GasProfile {
+            transaction_hash: transaction_hash.to_string(),
+            signer_account_id: "mock.near".parse()?,
+            receiver_account_id: "mock.near".parse()?,
+            gas_burnt: types::U64(2_428_000_000_000),
+            tokens_burnt: types::U128(242_800_000_000_000_000_000),
+            receipts: vec![accounts::schemas::ReceiptGas {
+                receipt_id: "11111111111111111111111111111111".to_string(),
+                predecessor_account_id: "mock.near".parse()?,
+                receiver_account_id: "mock.near".parse()?,
+                status: "SUCCESS".to_string(),
+                gas_burnt: types::U64(2_428_000_000_000),
+                tokens_burnt: types::U128(242_800_000_000_000_000_000),
+                actions: vec![accounts::schemas::Action::Transfer {
+                    deposit: types::U128(10u128.pow(24)),
+                }],
+                block_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+                block_height: types::U64(MOCK_BLOCK_HEIGHT),
+                block_hash: MOCK_BLOCK_HASH.to_string(),
+            }],
+            actions: vec![accounts::schemas::ActionGas {
+                kind: "TRANSFER".to_string(),
+                receiver_account_id: "mock.near".parse()?,
+                method_name: None,
+                receipts_count: 1,
+                gas_burnt: types::U64(2_428_000_000_000),
+                tokens_burnt: types::U128(242_800_000_000_000_000_000),
+            }],
+        }))
+    }
+
+    #[cfg(all(feature = "accounts", feature = "history"))]
+    async fn get_counterparties(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        _block: &db_helpers::Block,
+        _window_nanos: u64,
+        limit: u32,
+    ) -> crate::Result<Vec<accounts::schemas::Counterparty>> {
+        let mut counterparties = match self.load("get_counterparties", account_id.as_str())? {
+            Some(counterparties) => counterparties,
+            None => vec![accounts::schemas::Counterparty {
+                account_id: "mock.near".parse()?,
+                sent: types::U128(10u128.pow(24)),
+                received: types::U128(2 * 10u128.pow(24)),
+                total: types::U128(3 * 10u128.pow(24)),
+                transfers_count: 3,
+            }],
+        };
+        counterparties.truncate(limit as usize);
+        Ok(counterparties)
+    }
+
+    #[cfg(all(feature = "accounts", feature = "history"))]
+    async fn get_access_keys_history(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<
+        Vec<(
+            accounts::schemas::AccessKeyChange,
+            types::query_params::EventIndex,
+        )>,
+    > {
+        let mut history = match self.load("get_access_keys_history", account_id.as_str())? {
+            Some(history) => history,
+            None => vec![accounts::schemas::AccessKeyChange {
+                cause: "ADD_KEY".to_string(),
+                public_key: "ed25519:11111111111111111111111111111111".to_string(),
+                permission: Some(accounts::schemas::AccessKeyPermission::FullAccess),
+                transaction_hash: Some("mock_transaction".to_string()),
+                receipt_id: "mock_receipt".to_string(),
+                status: "SUCCESS".to_string(),
+                block_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+            }],
+        };
+        history.truncate(pagination.limit as usize);
+        Ok(with_mock_event_indexes(history, |change| {
+            change.block_timestamp_nanos.0
+        }))
+    }
+
+    #[cfg(all(feature = "accounts", feature = "history"))]
+    async fn get_access_key_usage(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        public_key: &str,
+        _block: &db_helpers::Block,
+        limit: u32,
+    ) -> crate::Result<Option<accounts::schemas::AccessKeyUsage>> {
+        let key = format!("{}_{}", account_id, public_key);
+        let (allowance, spent) = (25 * 10u128.pow(22), 32 * 10u128.pow(20));
+        let mut usage = match self.load("get_access_key_usage", &key)? {
+            Some(usage) => usage,
+            None => accounts::schemas::AccessKeyUsage {
+                public_key: public_key.to_string(),
+                permission: accounts::schemas::AccessKeyPermission::FunctionCall {
+                    allowance: Some(types::U128(allowance)),
+                    receiver_id: "mock.near".parse()?,
+                    method_names: vec![],
+                },
+                added_at_block_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+                allowance: Some(types::U128(allowance)),
+                spent: types::U128(spent),
+                remaining_allowance: Some(types::U128(allowance - spent)),
+                transactions: vec![accounts::schemas::AccessKeyTransaction {
+                    transaction_hash: "mock_transaction".to_string(),
+                    receiver_account_id: "mock.near".parse()?,
+                    spent: types::U128(spent),
+                    total_spent: types::U128(spent),
+                    remaining_allowance: Some(types::U128(allowance - spent)),
+                    block_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+                }],
+            },
+        };
+        usage.transactions.truncate(limit as usize);
+        Ok(Some(usage))
+    }
+
+    #[cfg(all(feature = "accounts", feature = "history"))]
+    async fn get_block_transfers(
+        &self,
+        block: &db_helpers::Block,
+    ) -> crate::Result<Vec<accounts::schemas::BlockTransfer>> {
+        if let Some(transfers) = self.load("get_block_transfers", &block.height.to_string())? {
+            return Ok(transfers);
+        }
+        Ok(vec![accounts::schemas::BlockTransfer {
+            standard: "nearnative".to_string(),
+            contract_account_id: None,
+            token_id: None,
+            old_account_id: Some("mock.near".parse()?),
+            new_account_id: Some("receiver.mock.near".parse()?),
+            amount: Some(types::U128(10u128.pow(24))),
+            cause: "TRANSFER".to_string(),
+            status: "SUCCESS".to_string(),
+            receipt_id: Some("mock_receipt".to_string()),
+        }])
+    }
+
+    #[cfg(feature = "admin")]
+    async fn get_usage(
+        &self,
+        _from_timestamp: u64,
+        _to_timestamp: u64,
+        limit: u32,
+    ) -> crate::Result<Vec<admin::schemas::KeyUsage>> {
+        let mut usage = match self.load("get_usage", "all")? {
+            Some(usage) => usage,
+            None => vec![admin::schemas::KeyUsage {
+                key_id: "mock_key_id".to_string(),
+                requests_count: types::U64(100),
+                response_bytes: types::U64(100_000),
+            }],
+        };
+        usage.truncate(limit as usize);
+        Ok(usage)
+    }
+
+    #[cfg(feature = "admin")]
+    async fn get_key_usage(
+        &self,
+        key_id: &str,
+        _from_timestamp: u64,
+        _to_timestamp: u64,
+    ) -> crate::Result<Vec<admin::schemas::EndpointUsage>> {
+        if let Some(usage) = self.load("get_key_usage", key_id)? {
+            return Ok(usage);
+        }
+        Ok(vec![admin::schemas::EndpointUsage {
+            endpoint: "/accounts/{account_id}/coins/NEAR".to_string(),
+            requests_count: types::U64(100),
+            response_bytes: types::U64(100_000),
+        }])
+    }
+
+    #[cfg(feature = "admin")]
+    async fn get_quota(&self, key_id: &str) -> crate::Result<Option<admin::schemas::Quota>> {
+        if let Some(quota) = self.load("get_quota", key_id)? {
+            return Ok(Some(quota));
+        }
+        Ok(Some(admin::schemas::Quota {
+            daily_requests: Some(types::U64(1000)),
+            monthly_requests: Some(types::U64(20_000)),
+        }))
+    }
+
+    // Nothing is stored in the mock mode
+    #[cfg(feature = "admin")]
+    async fn set_quota(&self, _key_id: &str, _quota: &admin::schemas::Quota) -> crate::Result<()> {
+        Ok(())
+    }
+
+    #[cfg(feature = "admin")]
+    async fn delete_quota(&self, _key_id: &str) -> crate::Result<()> {
+        Ok(())
+    }
+
+    #[cfg(feature = "admin")]
+    async fn get_audit_records(
+        &self,
+        actor_key_id: Option<&str>,
+        _before_id: Option<u64>,
+        limit: u32,
+    ) -> crate::Result<Vec<admin::schemas::AuditRecord>> {
+        let actor_key_id = actor_key_id.unwrap_or("mock_admin_key_id");
+        let mut records = match self.load("get_audit_records", actor_key_id)? {
+            Some(records) => records,
+            None => vec![admin::schemas::AuditRecord {
+                id: types::U64(1),
+                timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+                actor_key_id: actor_key_id.to_string(),
+                method: "PUT".to_string(),
+                endpoint: "/admin/keys/{key_id}/quota".to_string(),
+                path: "/admin/keys/mock_key_id/quota".to_string(),
+                query: "".to_string(),
+                body: Some(r#"{"daily_requests":"1000"}"#.to_string()),
+                status: 200,
+                client_ip: None,
+            }],
+        };
+        records.truncate(limit as usize);
+        Ok(records)
+    }
+
+    #[cfg(feature = "alerts")]
+    async fn get_alerts(&self, key_id: &str) -> crate::Result<Vec<alerts::schemas::Alert>> {
+        Ok(match self.load("get_alerts", key_id)? {
+            Some(alerts) => alerts,
+            None => vec![alerts::schemas::Alert {
+                alert_id: types::U64(1),
+                rule: alerts::schemas::AlertRule {
+                    kind: alerts::schemas::AlertKind::FtBalanceBelow,
+                    contract_account_id: "usn".parse()?,
+                    account_id: Some("mock.near".parse()?),
+                    token_id: None,
+                    threshold: Some(types::U128(10u128.pow(18))),
+                    webhook_url: "https://example.com/hook".to_string(),
+                },
+                is_triggered: false,
+                created_at_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+            }],
+        })
+    }
+
+    // Nothing is stored in the mock mode
+    #[cfg(feature = "alerts")]
+    async fn create_alert(
+        &self,
+        _key_id: &str,
+        rule: &alerts::schemas::AlertRule,
+    ) -> crate::Result<alerts::schemas::Alert> {
+        Ok(alerts::schemas::Alert {
+            alert_id: types::U64(2),
+            rule: rule.clone(),
+            is_triggered: false,
+            created_at_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+        })
+    }
+
+    #[cfg(feature = "alerts")]
+    async fn delete_alert(&self, _key_id: &str, _alert_id: u64) -> crate::Result<bool> {
+        Ok(true)
+    }
+
+    #[cfg(feature = "watchlists")]
+    async fn get_watchlists(
+        &self,
+        key_id: &str,
+    ) -> crate::Result<Vec<watchlists::schemas::Watchlist>> {
+        Ok(match self.load("get_watchlists", key_id)? {
+            Some(watchlists) => watchlists,
+            None => vec![watchlists::schemas::Watchlist {
+                watchlist_id: types::U64(1),
+                name: "mock".to_string(),
+                account_ids: vec!["mock.near".parse()?],
+                created_at_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+                updated_at_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+            }],
+        })
+    }
+
+    #[cfg(feature = "watchlists")]
+    async fn get_watchlist(
+        &self,
+        key_id: &str,
+        watchlist_id: u64,
+    ) -> crate::Result<Option<watchlists::schemas::Watchlist>> {
+        let key = format!("{}_{}", key_id, watchlist_id);
+        Ok(match self.load("get_watchlist", &key)? {
+            Some(watchlist) => watchlist,
+            None => Some(watchlists::schemas::Watchlist {
+                watchlist_id: types::U64(watchlist_id),
+                name: "mock".to_string(),
+                account_ids: vec!["mock.near".parse()?],
+                created_at_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+                updated_at_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+            }),
+        })
+    }
+
+    // Nothing is stored in the mock mode
+    #[cfg(feature = "watchlists")]
+    async fn create_watchlist(
+        &self,
+        _key_id: &str,
+        input: &watchlists::schemas::WatchlistInput,
+    ) -> crate::Result<watchlists::schemas::Watchlist> {
+        Ok(watchlists::schemas::Watchlist {
+            watchlist_id: types::U64(2),
+            name: input.name.clone(),
+            account_ids: input.account_ids.clone(),
+            created_at_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+            updated_at_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+        })
+    }
+
+    #[cfg(feature = "watchlists")]
+    async fn update_watchlist(
+        &self,
+        _key_id: &str,
+        watchlist_id: u64,
+        input: &watchlists::schemas::WatchlistInput,
+    ) -> crate::Result<Option<watchlists::schemas::Watchlist>> {
+        Ok(Some(watchlists::schemas::Watchlist {
+            watchlist_id: types::U64(watchlist_id),
+            name: input.name.clone(),
+            account_ids: input.account_ids.clone(),
+            created_at_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+            updated_at_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+        }))
+    }
+
+    #[cfg(feature = "watchlists")]
+    async fn delete_watchlist(&self, _key_id: &str, _watchlist_id: u64) -> crate::Result<bool> {
+        Ok(true)
+    }
+
+    #[cfg(feature = "exports")]
+    async fn create_export_job(
+        &self,
+        _key_id: &str,
+        account_id: &near_primitives::types::AccountId,
+        input: &exports::schemas::ExportInput,
+    ) -> crate::Result<exports::schemas::ExportJob> {
+        Ok(exports::schemas::ExportJob {
+            job_id: types::U64(2),
+            account_id: account_id.clone().into(),
+            kind: input.kind,
+            from_timestamp_nanos: input.from_timestamp_nanos,
+            to_timestamp_nanos: input.to_timestamp_nanos,
+            format: input.format.unwrap_or_default(),
+            status: exports::schemas::ExportStatus::Pending,
+            error: None,
+            download_url: None,
+            download_url_expires_at_timestamp_nanos: None,
+            created_at_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+            updated_at_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+        })
+    }
+
+    #[cfg(feature = "exports")]
+    async fn get_export_job(
+        &self,
+        key_id: &str,
+        job_id: u64,
+    ) -> crate::Result<Option<exports::schemas::ExportJob>> {
+        let key = format!("{}_{}", key_id, job_id);
+        Ok(match self.load("get_export_job", &key)? {
+            Some(job) => job,
+            None => Some(exports::schemas::ExportJob {
+                job_id: types::U64(job_id),
+                account_id: "mock.near"
+                    .parse::<near_primitives::types::AccountId>()?
+                    .into(),
+                kind: exports::schemas::ExportKind::FullHistory,
+                from_timestamp_nanos: None,
+                to_timestamp_nanos: None,
+                format: types::query_params::FileFormat::Csv,
+                status: exports::schemas::ExportStatus::Succeeded,
+                error: None,
+                download_url: None,
+                download_url_expires_at_timestamp_nanos: None,
+                created_at_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+                updated_at_timestamp_nanos: types::U64(MOCK_BLOCK_TIMESTAMP),
+            }),
+        })
+    }
+
+    #[cfg(feature = "exports")]
+    async fn count_active_export_jobs(&self, key_id: &str) -> crate::Result<u64> {
+        Ok(self
+            .load("count_active_export_jobs", key_id)?
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_key() {
+        assert_eq!(
+            sanitize_key("x.paras.near_293708:1"),
+            "x.paras.near_293708_1"
+        );
+        assert_eq!(sanitize_key("../../etc/passwd"), ".._.._etc_passwd");
+    }
+}
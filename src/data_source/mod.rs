@@ -0,0 +1,571 @@
+#[cfg(feature = "accounts")]
+use crate::modules::accounts;
+#[cfg(feature = "admin")]
+use crate::modules::admin;
+#[cfg(feature = "alerts")]
+use crate::modules::alerts;
+#[cfg(feature = "coin")]
+use crate::modules::coin;
+#[cfg(feature = "defi")]
+use crate::modules::defi;
+#[cfg(feature = "exports")]
+use crate::modules::exports;
+#[cfg(feature = "nft")]
+use crate::modules::nft;
+#[cfg(feature = "staking")]
+use crate::modules::staking;
+#[cfg(feature = "watchlists")]
+use crate::modules::watchlists;
+use crate::{db_helpers, types};
+
+mod fixtures;
+mod postgres;
+#[cfg(feature = "scylla")]
+mod scylla;
+
+pub(crate) use fixtures::FixtureDataSource;
+pub(crate) use postgres::PostgresDataSource;
+#[cfg(feature = "scylla")]
+pub(crate) use self::scylla::ScyllaDataSource;
+
+// The types of the signatures which are not in `api_models`, for the implementations outside of the crate
+pub use crate::db_helpers::Block;
+#[cfg(feature = "coin")]
+pub use crate::modules::coin::data_provider::{ChangedFtContract, Icon};
+#[cfg(feature = "nft")]
+pub use crate::modules::nft::data_provider::NftHoldingsDiff;
+#[cfg(feature = "history")]
+pub use crate::types::query_params::{EventIndex, HistoryPagination};
+pub use crate::types::query_params::{BlockParams, Pagination, PaginationParams};
+pub use crate::types::{CodeChange, FailedContract};
+
+pub type DataSourceRef = std::sync::Arc<dyn DataSource>;
+
+/// Everything the handlers need to know about the blockchain.
+/// The handlers only validate the input and shape the response,
+/// so any other backend (NEAR Lake, the other indexer schema, mocks) could be used instead of
+/// the default Indexer for Explorer DB + RPC implementation, see `ServerContextBuilder::data_source`.
+#[async_trait::async_trait]
+pub trait DataSource: Send + Sync {
+    // *** Blocks and accounts ***
+
+    async fn get_block_from_params(
+        &self,
+        params: &types::query_params::BlockParams,
+    ) -> crate::Result<db_helpers::Block>;
+
+    async fn get_last_block(&self) -> crate::Result<db_helpers::Block>;
+
+    /// `block_id` is the block height or the block hash
+    async fn get_block_by_id(&self, block_id: &str) -> crate::Result<db_helpers::Block>;
+
+    /// For each height, the block at this height or the closest one before it,
+    /// ordered by height without duplicates
+    async fn get_blocks_by_heights(
+        &self,
+        block_heights: &[u64],
+    ) -> crate::Result<Vec<db_helpers::Block>>;
+
+    async fn does_account_exist(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        block_timestamp: u64,
+    ) -> crate::Result<bool>;
+
+    /// The successful deployments which changed the contract code, up to the given block,
+    /// recent changes go first
+    async fn get_code_changes(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+        limit: u32,
+    ) -> crate::Result<Vec<types::CodeChange>>;
+
+    // *** Coins ***
+
+    #[cfg(feature = "coin")]
+    async fn get_near_balance(
+        &self,
+        block: &db_helpers::Block,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<coin::schemas::NearBalanceResponse>;
+
+    /// The storage is priced by the protocol config of the block's epoch
+    #[cfg(feature = "coin")]
+    async fn get_near_balance_breakdown(
+        &self,
+        block: &db_helpers::Block,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<coin::schemas::NearBalanceBreakdownResponse>;
+
+    /// FT balances, the contracts which failed to answer are listed separately
+    #[cfg(feature = "coin")]
+    async fn get_coin_balances(
+        &self,
+        block: &db_helpers::Block,
+        account_id: &near_primitives::types::AccountId,
+        pagination: &types::query_params::Pagination,
+    ) -> crate::Result<(Vec<coin::schemas::Coin>, Vec<types::FailedContract>)>;
+
+    #[cfg(feature = "coin")]
+    async fn get_coin_balances_by_contract(
+        &self,
+        block: &db_helpers::Block,
+        contract_id: &near_primitives::types::AccountId,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<Vec<coin::schemas::Coin>>;
+
+    /// The balance only, without the metadata
+    #[cfg(feature = "coin")]
+    async fn get_ft_balance(
+        &self,
+        block: &db_helpers::Block,
+        contract_id: &near_primitives::types::AccountId,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<u128>;
+
+    /// The balance as the sum of the indexed FT events, `get_ft_balance` asks the contract
+    #[cfg(feature = "coin")]
+    async fn get_ft_balance_from_events(
+        &self,
+        block: &db_helpers::Block,
+        contract_id: &near_primitives::types::AccountId,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<u128>;
+
+    /// FT contracts where the account had the events after `from_block`, up to `to_block`
+    #[cfg(feature = "coin")]
+    async fn get_changed_ft_contracts(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        from_block: &db_helpers::Block,
+        to_block: &db_helpers::Block,
+        limit: u32,
+    ) -> crate::Result<Vec<coin::data_provider::ChangedFtContract>>;
+
+    /// The timestamp of the last NEAR balance change of the account, not later than the given block
+    #[cfg(feature = "coin")]
+    async fn get_near_last_modified(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+    ) -> crate::Result<Option<u64>>;
+
+    /// The timestamp of the last FT event of the account (of the given contract, or of any contract),
+    /// not later than the given block
+    #[cfg(feature = "coin")]
+    async fn get_ft_last_modified(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        contract_id: Option<&near_primitives::types::AccountId>,
+        block: &db_helpers::Block,
+    ) -> crate::Result<Option<u64>>;
+
+    #[cfg(all(feature = "coin", feature = "history"))]
+    async fn get_near_history(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(coin::schemas::HistoryItem, types::query_params::EventIndex)>>;
+
+    #[cfg(all(feature = "coin", feature = "history"))]
+    async fn get_gas_fees(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+        window_nanos: u64,
+    ) -> crate::Result<coin::schemas::GasFeesResponse>;
+
+    #[cfg(all(feature = "coin", feature = "history"))]
+    async fn get_coin_history(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        account_id: &near_primitives::types::AccountId,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(coin::schemas::HistoryItem, types::query_params::EventIndex)>>;
+
+    /// Transfer events of all the accounts, recent events go first.
+    /// Every history method gives the positions of the items for `modules::next_history_cursor`
+    #[cfg(all(feature = "coin", feature = "history"))]
+    async fn get_ft_transfers(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(coin::schemas::FtTransfer, types::query_params::EventIndex)>>;
+
+    /// Holders with the positive balance at the given moment, ordered by account_id
+    #[cfg(feature = "coin")]
+    async fn get_ft_holders(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block_timestamp: u64,
+        after_account_id: Option<&near_primitives::types::AccountId>,
+        limit: u32,
+    ) -> crate::Result<Vec<coin::schemas::FtHolder>>;
+
+    #[cfg(feature = "coin")]
+    async fn get_ft_contract_metadata(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block_height: u64,
+    ) -> crate::Result<coin::schemas::FtContractMetadata>;
+
+    /// The latest metadata of the contracts in the given order, cached for up to 1 hour.
+    /// The contracts which failed to give it are returned separately
+    #[cfg(feature = "coin")]
+    async fn get_ft_contracts_metadata(
+        &self,
+        contract_ids: &[near_primitives::types::AccountId],
+        block_height: u64,
+    ) -> crate::Result<(
+        Vec<coin::schemas::FtContractMetadataItem>,
+        Vec<types::FailedContract>,
+    )>;
+
+    /// Asks the contract bypassing the caches, and puts the fresh metadata (and the icon) into them
+    #[cfg(feature = "coin")]
+    async fn refresh_ft_contract_metadata(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block_height: u64,
+    ) -> crate::Result<coin::schemas::FtContractMetadata>;
+
+    /// Sanitized icon from FT metadata, `None` if there is no icon or it's rejected
+    #[cfg(feature = "coin")]
+    async fn get_ft_icon(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block_height: u64,
+    ) -> crate::Result<Option<coin::data_provider::Icon>>;
+
+    /// Prices sampled not later than `block_timestamp`, recent buckets go first
+    #[cfg(feature = "coin")]
+    async fn get_token_price_history(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        bucket: &coin::schemas::PriceBucket,
+        block_timestamp: u64,
+        limit: u32,
+    ) -> crate::Result<Vec<coin::schemas::PricePoint>>;
+
+    // *** NFT ***
+
+    /// The contracts which failed to give the metadata are listed separately
+    #[cfg(feature = "nft")]
+    async fn get_nfts_count(
+        &self,
+        block: &db_helpers::Block,
+        account_id: &near_primitives::types::AccountId,
+        pagination_params: types::query_params::PaginationParams,
+    ) -> crate::Result<(Vec<nft::schemas::NftCount>, Vec<types::FailedContract>)>;
+
+    #[cfg(feature = "nft")]
+    async fn get_nfts_by_contract(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        account_id: &near_primitives::types::AccountId,
+        block_height: u64,
+        limit: u32,
+    ) -> crate::Result<Vec<nft::schemas::Nft>>;
+
+    #[cfg(feature = "nft")]
+    async fn get_nft(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        token_id: &str,
+        block_height: u64,
+    ) -> crate::Result<nft::schemas::Nft>;
+
+    #[cfg(all(feature = "nft", feature = "history"))]
+    async fn get_nft_history(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        token_id: &str,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(nft::schemas::HistoryItem, types::query_params::EventIndex)>>;
+
+    #[cfg(feature = "nft")]
+    async fn get_nft_contract_metadata(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block_height: u64,
+    ) -> crate::Result<Option<nft::schemas::NftContractMetadata>>;
+
+    /// The latest metadata of the contracts in the given order, cached for up to 1 hour.
+    /// The contracts which failed to give it are returned separately
+    #[cfg(feature = "nft")]
+    async fn get_nft_contracts_metadata(
+        &self,
+        contract_ids: &[near_primitives::types::AccountId],
+        block_height: u64,
+    ) -> crate::Result<(
+        Vec<nft::schemas::NftContractMetadataItem>,
+        Vec<types::FailedContract>,
+    )>;
+
+    /// Asks the contract bypassing the caches, and puts the fresh metadata into them
+    #[cfg(feature = "nft")]
+    async fn refresh_nft_contract_metadata(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block_height: u64,
+    ) -> crate::Result<Option<nft::schemas::NftContractMetadata>>;
+
+    #[cfg(all(feature = "nft", feature = "history"))]
+    async fn get_nft_sales(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        token_id: &str,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(nft::schemas::NftSale, types::query_params::EventIndex)>>;
+
+    /// NEP-178 approve/revoke calls made by the account
+    #[cfg(all(feature = "nft", feature = "history"))]
+    async fn get_nft_approvals_history(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(nft::schemas::NftApproval, types::query_params::EventIndex)>>;
+
+    #[cfg(all(feature = "nft", feature = "history"))]
+    async fn get_nft_provenance(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        token_id: &str,
+        block: &db_helpers::Block,
+    ) -> crate::Result<Vec<nft::schemas::ProvenanceItem>>;
+
+    #[cfg(feature = "nft")]
+    async fn get_nft_market_stats(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+        window_nanos: u64,
+    ) -> crate::Result<nft::schemas::NftMarketStatsResponse>;
+
+    /// NFTs the account got and gave away after `from_block`, up to `to_block`
+    #[cfg(feature = "nft")]
+    async fn get_nft_holdings_diff(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        from_block: &db_helpers::Block,
+        to_block: &db_helpers::Block,
+        limit: u32,
+    ) -> crate::Result<nft::data_provider::NftHoldingsDiff>;
+
+    // *** Staking ***
+
+    #[cfg(feature = "staking")]
+    async fn get_staking_summary(
+        &self,
+        block: &db_helpers::Block,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<staking::schemas::StakingSummaryResponse>;
+
+    #[cfg(feature = "staking")]
+    async fn get_staking_pools(
+        &self,
+        block: &db_helpers::Block,
+    ) -> crate::Result<staking::schemas::StakingPoolsResponse>;
+
+    #[cfg(feature = "staking")]
+    async fn get_supply_stats(
+        &self,
+        block: &db_helpers::Block,
+    ) -> crate::Result<staking::schemas::SupplyStatsResponse>;
+
+    // *** DeFi ***
+
+    #[cfg(feature = "defi")]
+    async fn get_ref_finance_positions(
+        &self,
+        block: &db_helpers::Block,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<defi::schemas::RefFinancePositionsResponse>;
+
+    // *** Accounts ***
+
+    #[cfg(feature = "accounts")]
+    async fn get_account_counters(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+        is_latest: bool,
+    ) -> crate::Result<accounts::schemas::AccountCountersResponse>;
+
+    /// `None` if the account had no activity up to the block
+    #[cfg(feature = "accounts")]
+    async fn get_account_activity_summary(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+    ) -> crate::Result<Option<accounts::schemas::AccountActivitySummaryResponse>>;
+
+    #[cfg(feature = "accounts")]
+    async fn get_deployments(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+        limit: u32,
+    ) -> crate::Result<Vec<accounts::schemas::Deployment>>;
+
+    /// `None` if the receipt is not executed at the given block
+    #[cfg(feature = "accounts")]
+    async fn get_receipt(
+        &self,
+        receipt_id: &str,
+        block: &db_helpers::Block,
+    ) -> crate::Result<Option<accounts::schemas::Receipt>>;
+
+    /// `None` if the transaction is not included at the given block
+    #[cfg(feature = "accounts")]
+    async fn get_gas_profile(
+        &self,
+        transaction_hash: &str,
+        block: &db_helpers::Block,
+    ) -> crate::Result<Option<accounts::schemas::GasProfile>>;
+
+    #[cfg(all(feature = "accounts", feature = "history"))]
+    async fn get_counterparties(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+        window_nanos: u64,
+        limit: u32,
+    ) -> crate::Result<Vec<accounts::schemas::Counterparty>>;
+
+    #[cfg(all(feature = "accounts", feature = "history"))]
+    async fn get_access_keys_history(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(accounts::schemas::AccessKeyChange, types::query_params::EventIndex)>>;
+
+    /// `None` if the key does not exist at the given block, the error for the full access keys
+    #[cfg(all(feature = "accounts", feature = "history"))]
+    async fn get_access_key_usage(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        public_key: &str,
+        block: &db_helpers::Block,
+        limit: u32,
+    ) -> crate::Result<Option<accounts::schemas::AccessKeyUsage>>;
+
+    /// NEAR, FT and NFT transfers of the block
+    #[cfg(all(feature = "accounts", feature = "history"))]
+    async fn get_block_transfers(
+        &self,
+        block: &db_helpers::Block,
+    ) -> crate::Result<Vec<accounts::schemas::BlockTransfer>>;
+
+    // *** Admin ***
+
+    /// API keys sorted by the number of requests, most active go first
+    #[cfg(feature = "admin")]
+    async fn get_usage(
+        &self,
+        from_timestamp: u64,
+        to_timestamp: u64,
+        limit: u32,
+    ) -> crate::Result<Vec<admin::schemas::KeyUsage>>;
+
+    #[cfg(feature = "admin")]
+    async fn get_key_usage(
+        &self,
+        key_id: &str,
+        from_timestamp: u64,
+        to_timestamp: u64,
+    ) -> crate::Result<Vec<admin::schemas::EndpointUsage>>;
+
+    /// `None` if the key has no quota
+    #[cfg(feature = "admin")]
+    async fn get_quota(&self, key_id: &str) -> crate::Result<Option<admin::schemas::Quota>>;
+
+    #[cfg(feature = "admin")]
+    async fn set_quota(&self, key_id: &str, quota: &admin::schemas::Quota) -> crate::Result<()>;
+
+    #[cfg(feature = "admin")]
+    async fn delete_quota(&self, key_id: &str) -> crate::Result<()>;
+
+    /// The newest records go first, `before_id` is exclusive
+    #[cfg(feature = "admin")]
+    async fn get_audit_records(
+        &self,
+        actor_key_id: Option<&str>,
+        before_id: Option<u64>,
+        limit: u32,
+    ) -> crate::Result<Vec<admin::schemas::AuditRecord>>;
+
+    /// The alerts of the API key, the oldest go first
+    #[cfg(feature = "alerts")]
+    async fn get_alerts(&self, key_id: &str) -> crate::Result<Vec<alerts::schemas::Alert>>;
+
+    #[cfg(feature = "alerts")]
+    async fn create_alert(
+        &self,
+        key_id: &str,
+        rule: &alerts::schemas::AlertRule,
+    ) -> crate::Result<alerts::schemas::Alert>;
+
+    /// Returns `false` if the API key has no such alert
+    #[cfg(feature = "alerts")]
+    async fn delete_alert(&self, key_id: &str, alert_id: u64) -> crate::Result<bool>;
+
+    /// The watchlists of the API key, the oldest go first
+    #[cfg(feature = "watchlists")]
+    async fn get_watchlists(
+        &self,
+        key_id: &str,
+    ) -> crate::Result<Vec<watchlists::schemas::Watchlist>>;
+
+    /// `None` if the API key has no such watchlist
+    #[cfg(feature = "watchlists")]
+    async fn get_watchlist(
+        &self,
+        key_id: &str,
+        watchlist_id: u64,
+    ) -> crate::Result<Option<watchlists::schemas::Watchlist>>;
+
+    #[cfg(feature = "watchlists")]
+    async fn create_watchlist(
+        &self,
+        key_id: &str,
+        input: &watchlists::schemas::WatchlistInput,
+    ) -> crate::Result<watchlists::schemas::Watchlist>;
+
+    /// `None` if the API key has no such watchlist
+    #[cfg(feature = "watchlists")]
+    async fn update_watchlist(
+        &self,
+        key_id: &str,
+        watchlist_id: u64,
+        input: &watchlists::schemas::WatchlistInput,
+    ) -> crate::Result<Option<watchlists::schemas::Watchlist>>;
+
+    /// Returns `false` if the API key has no such watchlist
+    #[cfg(feature = "watchlists")]
+    async fn delete_watchlist(&self, key_id: &str, watchlist_id: u64) -> crate::Result<bool>;
+
+    // *** Exports ***
+
+    /// The new job is `PENDING`, the export worker takes it
+    #[cfg(feature = "exports")]
+    async fn create_export_job(
+        &self,
+        key_id: &str,
+        account_id: &near_primitives::types::AccountId,
+        input: &exports::schemas::ExportInput,
+    ) -> crate::Result<exports::schemas::ExportJob>;
+
+    /// `None` if the API key has no such job. `download_url` is not set here
+    #[cfg(feature = "exports")]
+    async fn get_export_job(
+        &self,
+        key_id: &str,
+        job_id: u64,
+    ) -> crate::Result<Option<exports::schemas::ExportJob>>;
+
+    /// `PENDING` and `RUNNING` jobs of the API key
+    #[cfg(feature = "exports")]
+    async fn count_active_export_jobs(&self, key_id: &str) -> crate::Result<u64>;
+}
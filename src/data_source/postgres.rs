@@ -0,0 +1,918 @@
+#[cfg(feature = "accounts")]
+use crate::modules::accounts;
+#[cfg(feature = "admin")]
+use crate::modules::admin;
+#[cfg(feature = "alerts")]
+use crate::modules::alerts;
+#[cfg(feature = "coin")]
+use crate::modules::coin;
+#[cfg(feature = "defi")]
+use crate::modules::defi;
+#[cfg(feature = "exports")]
+use crate::modules::exports;
+#[cfg(feature = "nft")]
+use crate::modules::nft;
+#[cfg(feature = "staking")]
+use crate::modules::staking;
+#[cfg(feature = "watchlists")]
+use crate::modules::watchlists;
+use crate::{balances_schema, cache_warmer, db_helpers, rpc_helpers, types};
+
+/// The default implementation: Indexer for Explorer DB, balances DB and archival RPC
+pub(crate) struct PostgresDataSource {
+    pub pool: sqlx::Pool<sqlx::Postgres>,
+    // our own tables (prices, etc.), see `migrations/`
+    pub pool_aux: sqlx::Pool<sqlx::Postgres>,
+    // temp solution, balance_changes table lives in the other DB
+    #[cfg(all(
+        feature = "history",
+        any(feature = "coin", feature = "staking", feature = "accounts")
+    ))]
+    pub pool_balances: balances_schema::BalancesDb,
+    pub rpc_client: Box<dyn rpc_helpers::RpcApi>,
+    /// Counts the lookups of the cached metadata, so the popular entries are kept warm
+    pub cache_warmer: cache_warmer::CacheWarmer,
+    #[cfg(feature = "coin")]
+    pub icon_cache: coin::data_provider::IconCache,
+    #[cfg(feature = "coin")]
+    pub ft_metadata_cache: coin::data_provider::FtMetadataCache,
+    #[cfg(feature = "coin")]
+    pub storage_price_cache: coin::data_provider::StoragePriceCache,
+    #[cfg(feature = "accounts")]
+    pub counters_cache: accounts::data_provider::CountersCache,
+    #[cfg(feature = "nft")]
+    pub nft_metadata_cache: nft::data_provider::NftMetadataCache,
+    /// `nft_owner_counts` is filled by the built-in indexer
+    #[cfg(feature = "nft")]
+    pub nft_owner_counts: bool,
+    /// NFT contracts which are always marked as spam
+    #[cfg(feature = "nft")]
+    pub nft_spam_denylist: Vec<String>,
+}
+
+#[async_trait::async_trait]
+impl super::DataSource for PostgresDataSource {
+    async fn get_block_from_params(
+        &self,
+        params: &types::query_params::BlockParams,
+    ) -> crate::Result<db_helpers::Block> {
+        db_helpers::get_block_from_params(&self.pool, params).await
+    }
+
+    async fn get_last_block(&self) -> crate::Result<db_helpers::Block> {
+        db_helpers::get_last_block(&self.pool).await
+    }
+
+    async fn get_block_by_id(&self, block_id: &str) -> crate::Result<db_helpers::Block> {
+        db_helpers::get_block_by_id(&self.pool, block_id).await
+    }
+
+    async fn get_blocks_by_heights(
+        &self,
+        block_heights: &[u64],
+    ) -> crate::Result<Vec<db_helpers::Block>> {
+        db_helpers::get_blocks_by_heights(&self.pool, block_heights).await
+    }
+
+    async fn does_account_exist(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        block_timestamp: u64,
+    ) -> crate::Result<bool> {
+        db_helpers::does_account_exist(&self.pool, account_id, block_timestamp).await
+    }
+
+    async fn get_code_changes(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+        limit: u32,
+    ) -> crate::Result<Vec<types::CodeChange>> {
+        db_helpers::get_code_changes(&self.pool, contract_id, block.timestamp, limit).await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_near_balance(
+        &self,
+        block: &db_helpers::Block,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<coin::schemas::NearBalanceResponse> {
+        coin::data_provider::get_near_balance(&self.pool, block, account_id).await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_near_balance_breakdown(
+        &self,
+        block: &db_helpers::Block,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<coin::schemas::NearBalanceBreakdownResponse> {
+        coin::data_provider::get_near_balance_breakdown(
+            &self.pool,
+            self.rpc_client.as_ref(),
+            &self.storage_price_cache,
+            block,
+            account_id,
+        )
+        .await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_coin_balances(
+        &self,
+        block: &db_helpers::Block,
+        account_id: &near_primitives::types::AccountId,
+        pagination: &types::query_params::Pagination,
+    ) -> crate::Result<(Vec<coin::schemas::Coin>, Vec<types::FailedContract>)> {
+        coin::data_provider::get_coin_balances(
+            &self.pool,
+            self.rpc_client.as_ref(),
+            block,
+            account_id,
+            pagination,
+        )
+        .await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_coin_balances_by_contract(
+        &self,
+        block: &db_helpers::Block,
+        contract_id: &near_primitives::types::AccountId,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<Vec<coin::schemas::Coin>> {
+        coin::data_provider::get_coin_balances_by_contract(
+            self.rpc_client.as_ref(),
+            block,
+            contract_id,
+            account_id,
+        )
+        .await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_ft_balance(
+        &self,
+        block: &db_helpers::Block,
+        contract_id: &near_primitives::types::AccountId,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<u128> {
+        coin::data_provider::get_ft_balance_by_contract(
+            self.rpc_client.as_ref(),
+            contract_id.clone(),
+            account_id.clone(),
+            block.height,
+        )
+        .await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_ft_balance_from_events(
+        &self,
+        block: &db_helpers::Block,
+        contract_id: &near_primitives::types::AccountId,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<u128> {
+        coin::data_provider::get_ft_balance_from_events(
+            &self.pool,
+            contract_id,
+            account_id,
+            block.timestamp,
+        )
+        .await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_changed_ft_contracts(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        from_block: &db_helpers::Block,
+        to_block: &db_helpers::Block,
+        limit: u32,
+    ) -> crate::Result<Vec<coin::data_provider::ChangedFtContract>> {
+        coin::data_provider::get_changed_ft_contracts(
+            &self.pool,
+            account_id,
+            from_block.timestamp,
+            to_block.timestamp,
+            limit,
+        )
+        .await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_near_last_modified(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+    ) -> crate::Result<Option<u64>> {
+        coin::data_provider::get_near_last_modified(
+            &self.pool_balances,
+            account_id,
+            block.timestamp,
+        )
+        .await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_ft_last_modified(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        contract_id: Option<&near_primitives::types::AccountId>,
+        block: &db_helpers::Block,
+    ) -> crate::Result<Option<u64>> {
+        coin::data_provider::get_ft_last_modified(
+            &self.pool,
+            account_id,
+            contract_id,
+            block.timestamp,
+        )
+        .await
+    }
+
+    #[cfg(all(feature = "coin", feature = "history"))]
+    async fn get_near_history(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(coin::schemas::HistoryItem, types::query_params::EventIndex)>> {
+        coin::data_provider::get_near_history(
+            &self.pool,
+            &self.pool_balances,
+            account_id,
+            pagination,
+        )
+        .await
+    }
+
+    #[cfg(all(feature = "coin", feature = "history"))]
+    async fn get_gas_fees(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+        window_nanos: u64,
+    ) -> crate::Result<coin::schemas::GasFeesResponse> {
+        coin::data_provider::get_gas_fees(&self.pool_balances, account_id, block, window_nanos)
+            .await
+    }
+
+    #[cfg(all(feature = "coin", feature = "history"))]
+    async fn get_coin_history(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        account_id: &near_primitives::types::AccountId,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(coin::schemas::HistoryItem, types::query_params::EventIndex)>> {
+        coin::data_provider::get_coin_history(
+            &self.pool,
+            self.rpc_client.as_ref(),
+            contract_id,
+            account_id,
+            pagination,
+        )
+        .await
+    }
+
+    #[cfg(all(feature = "coin", feature = "history"))]
+    async fn get_ft_transfers(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(coin::schemas::FtTransfer, types::query_params::EventIndex)>> {
+        coin::data_provider::get_ft_transfers(&self.pool, contract_id, pagination).await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_ft_holders(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block_timestamp: u64,
+        after_account_id: Option<&near_primitives::types::AccountId>,
+        limit: u32,
+    ) -> crate::Result<Vec<coin::schemas::FtHolder>> {
+        coin::data_provider::get_ft_holders(
+            &self.pool,
+            contract_id,
+            block_timestamp,
+            after_account_id,
+            limit,
+        )
+        .await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_ft_contract_metadata(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block_height: u64,
+    ) -> crate::Result<coin::schemas::FtContractMetadata> {
+        coin::data_provider::get_ft_contract_metadata(
+            self.rpc_client.as_ref(),
+            contract_id.clone(),
+            block_height,
+        )
+        .await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn refresh_ft_contract_metadata(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block_height: u64,
+    ) -> crate::Result<coin::schemas::FtContractMetadata> {
+        let metadata = coin::data_provider::get_ft_contract_metadata(
+            self.rpc_client.as_ref(),
+            contract_id.clone(),
+            block_height,
+        )
+        .await?;
+        coin::data_provider::update_cached_ft_icon(&self.icon_cache, contract_id, &metadata);
+        self.ft_metadata_cache
+            .insert(contract_id.clone(), metadata.clone());
+        Ok(metadata)
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_ft_contracts_metadata(
+        &self,
+        contract_ids: &[near_primitives::types::AccountId],
+        block_height: u64,
+    ) -> crate::Result<(
+        Vec<coin::schemas::FtContractMetadataItem>,
+        Vec<types::FailedContract>,
+    )> {
+        for contract_id in contract_ids {
+            self.cache_warmer
+                .record(cache_warmer::WarmKey::FtMetadata(contract_id.clone()));
+        }
+        Ok(coin::data_provider::get_ft_contracts_metadata(
+            self.rpc_client.as_ref(),
+            &self.ft_metadata_cache,
+            contract_ids,
+            block_height,
+        )
+        .await)
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_ft_icon(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block_height: u64,
+    ) -> crate::Result<Option<coin::data_provider::Icon>> {
+        coin::data_provider::get_ft_icon(
+            self.rpc_client.as_ref(),
+            &self.icon_cache,
+            contract_id,
+            block_height,
+        )
+        .await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_token_price_history(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        bucket: &coin::schemas::PriceBucket,
+        block_timestamp: u64,
+        limit: u32,
+    ) -> crate::Result<Vec<coin::schemas::PricePoint>> {
+        coin::data_provider::get_price_history(
+            &self.pool_aux,
+            contract_id,
+            bucket,
+            block_timestamp,
+            limit,
+        )
+        .await
+    }
+
+    #[cfg(feature = "nft")]
+    async fn get_nfts_count(
+        &self,
+        block: &db_helpers::Block,
+        account_id: &near_primitives::types::AccountId,
+        pagination_params: types::query_params::PaginationParams,
+    ) -> crate::Result<(Vec<nft::schemas::NftCount>, Vec<types::FailedContract>)> {
+        // The indexer writes the counts together with the block, they are valid only for the last one
+        let (mut counts, failed_contracts) = if self.nft_owner_counts
+            && db_helpers::get_last_block(&self.pool).await?.height == block.height
+        {
+            nft::data_provider::get_nfts_count_from_owner_counts(
+                &self.pool,
+                self.rpc_client.as_ref(),
+                &self.nft_metadata_cache,
+                block,
+                account_id,
+                pagination_params,
+            )
+            .await?
+        } else {
+            nft::data_provider::get_nfts_count(
+                &self.pool,
+                self.rpc_client.as_ref(),
+                &self.nft_metadata_cache,
+                block,
+                account_id,
+                pagination_params,
+            )
+            .await?
+        };
+        nft::data_provider::mark_spam_collections(
+            &self.pool,
+            &self.nft_spam_denylist,
+            block,
+            &mut counts,
+        )
+        .await?;
+        Ok((counts, failed_contracts))
+    }
+
+    #[cfg(feature = "nft")]
+    async fn get_nfts_by_contract(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        account_id: &near_primitives::types::AccountId,
+        block_height: u64,
+        limit: u32,
+    ) -> crate::Result<Vec<nft::schemas::Nft>> {
+        nft::data_provider::get_nfts_by_contract(
+            self.rpc_client.as_ref(),
+            contract_id.clone(),
+            account_id.clone(),
+            block_height,
+            limit,
+        )
+        .await
+    }
+
+    #[cfg(feature = "nft")]
+    async fn get_nft(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        token_id: &str,
+        block_height: u64,
+    ) -> crate::Result<nft::schemas::Nft> {
+        nft::data_provider::get_nft(
+            self.rpc_client.as_ref(),
+            contract_id.clone(),
+            token_id.to_string(),
+            block_height,
+        )
+        .await
+    }
+
+    #[cfg(all(feature = "nft", feature = "history"))]
+    async fn get_nft_history(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        token_id: &str,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(nft::schemas::HistoryItem, types::query_params::EventIndex)>> {
+        nft::data_provider::get_nft_history(&self.pool, contract_id, token_id, pagination).await
+    }
+
+    #[cfg(feature = "nft")]
+    async fn get_nft_contract_metadata(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block_height: u64,
+    ) -> crate::Result<Option<nft::schemas::NftContractMetadata>> {
+        nft::data_provider::get_nft_contract_metadata(
+            self.rpc_client.as_ref(),
+            contract_id.clone(),
+            block_height,
+        )
+        .await
+    }
+
+    #[cfg(feature = "nft")]
+    async fn get_nft_contracts_metadata(
+        &self,
+        contract_ids: &[near_primitives::types::AccountId],
+        block_height: u64,
+    ) -> crate::Result<(
+        Vec<nft::schemas::NftContractMetadataItem>,
+        Vec<types::FailedContract>,
+    )> {
+        for contract_id in contract_ids {
+            self.cache_warmer
+                .record(cache_warmer::WarmKey::NftMetadata(contract_id.clone()));
+        }
+        Ok(nft::data_provider::get_nft_contracts_metadata(
+            self.rpc_client.as_ref(),
+            &self.nft_metadata_cache,
+            contract_ids,
+            block_height,
+        )
+        .await)
+    }
+
+    #[cfg(feature = "nft")]
+    async fn refresh_nft_contract_metadata(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block_height: u64,
+    ) -> crate::Result<Option<nft::schemas::NftContractMetadata>> {
+        let metadata = nft::data_provider::get_nft_contract_metadata(
+            self.rpc_client.as_ref(),
+            contract_id.clone(),
+            block_height,
+        )
+        .await?;
+        if let Some(metadata) = &metadata {
+            self.nft_metadata_cache
+                .insert(contract_id.clone(), metadata.clone());
+        }
+        Ok(metadata)
+    }
+
+    #[cfg(all(feature = "nft", feature = "history"))]
+    async fn get_nft_sales(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        token_id: &str,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(nft::schemas::NftSale, types::query_params::EventIndex)>> {
+        nft::data_provider::get_nft_sales(&self.pool, contract_id, token_id, pagination).await
+    }
+
+    #[cfg(all(feature = "nft", feature = "history"))]
+    async fn get_nft_approvals_history(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(nft::schemas::NftApproval, types::query_params::EventIndex)>> {
+        nft::data_provider::get_nft_approvals_history(&self.pool, account_id, pagination).await
+    }
+
+    #[cfg(all(feature = "nft", feature = "history"))]
+    async fn get_nft_provenance(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        token_id: &str,
+        block: &db_helpers::Block,
+    ) -> crate::Result<Vec<nft::schemas::ProvenanceItem>> {
+        nft::data_provider::get_nft_provenance(&self.pool, contract_id, token_id, block).await
+    }
+
+    #[cfg(feature = "nft")]
+    async fn get_nft_market_stats(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+        window_nanos: u64,
+    ) -> crate::Result<nft::schemas::NftMarketStatsResponse> {
+        nft::data_provider::get_nft_market_stats(&self.pool, contract_id, block, window_nanos).await
+    }
+
+    #[cfg(feature = "nft")]
+    async fn get_nft_holdings_diff(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        from_block: &db_helpers::Block,
+        to_block: &db_helpers::Block,
+        limit: u32,
+    ) -> crate::Result<nft::data_provider::NftHoldingsDiff> {
+        nft::data_provider::get_nft_holdings_diff(
+            &self.pool,
+            account_id,
+            from_block.timestamp,
+            to_block.timestamp,
+            limit,
+        )
+        .await
+    }
+
+    #[cfg(feature = "staking")]
+    async fn get_staking_summary(
+        &self,
+        block: &db_helpers::Block,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<staking::schemas::StakingSummaryResponse> {
+        staking::data_provider::get_staking_summary(
+            &self.pool,
+            self.rpc_client.as_ref(),
+            block,
+            account_id,
+        )
+        .await
+    }
+
+    #[cfg(feature = "staking")]
+    async fn get_staking_pools(
+        &self,
+        block: &db_helpers::Block,
+    ) -> crate::Result<staking::schemas::StakingPoolsResponse> {
+        #[cfg(feature = "history")]
+        let balances_pool = Some(&self.pool_balances);
+        #[cfg(not(feature = "history"))]
+        let balances_pool = None;
+        staking::data_provider::get_staking_pools(
+            &self.pool,
+            balances_pool,
+            self.rpc_client.as_ref(),
+            block,
+        )
+        .await
+    }
+
+    #[cfg(feature = "staking")]
+    async fn get_supply_stats(
+        &self,
+        block: &db_helpers::Block,
+    ) -> crate::Result<staking::schemas::SupplyStatsResponse> {
+        #[cfg(feature = "history")]
+        let balances_pool = Some(&self.pool_balances);
+        #[cfg(not(feature = "history"))]
+        let balances_pool = None;
+        staking::data_provider::get_supply_stats(
+            &self.pool,
+            balances_pool,
+            self.rpc_client.as_ref(),
+            block,
+        )
+        .await
+    }
+
+    #[cfg(feature = "defi")]
+    async fn get_ref_finance_positions(
+        &self,
+        block: &db_helpers::Block,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<defi::schemas::RefFinancePositionsResponse> {
+        defi::data_provider::get_ref_finance_positions(
+            &self.pool,
+            self.rpc_client.as_ref(),
+            block,
+            account_id,
+        )
+        .await
+    }
+
+    #[cfg(feature = "accounts")]
+    async fn get_account_counters(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+        is_latest: bool,
+    ) -> crate::Result<accounts::schemas::AccountCountersResponse> {
+        accounts::data_provider::get_account_counters(
+            &self.pool,
+            &self.counters_cache,
+            account_id,
+            block,
+            is_latest,
+        )
+        .await
+    }
+
+    #[cfg(feature = "accounts")]
+    async fn get_account_activity_summary(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+    ) -> crate::Result<Option<accounts::schemas::AccountActivitySummaryResponse>> {
+        accounts::data_provider::get_account_activity_summary(&self.pool, account_id, block).await
+    }
+
+    #[cfg(feature = "accounts")]
+    async fn get_deployments(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+        limit: u32,
+    ) -> crate::Result<Vec<accounts::schemas::Deployment>> {
+        accounts::data_provider::get_deployments(&self.pool, contract_id, block, limit).await
+    }
+
+    #[cfg(feature = "accounts")]
+    async fn get_receipt(
+        &self,
+        receipt_id: &str,
+        block: &db_helpers::Block,
+    ) -> crate::Result<Option<accounts::schemas::Receipt>> {
+        accounts::data_provider::get_receipt(&self.pool, receipt_id, block).await
+    }
+
+    #[cfg(feature = "accounts")]
+    async fn get_gas_profile(
+        &self,
+        transaction_hash: &str,
+        block: &db_helpers::Block,
+    ) -> crate::Result<Option<accounts::schemas::GasProfile>> {
+        accounts::data_provider::get_gas_profile(&self.pool, transaction_hash, block).await
+    }
+
+    #[cfg(all(feature = "accounts", feature = "history"))]
+    async fn get_counterparties(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+        window_nanos: u64,
+        limit: u32,
+    ) -> crate::Result<Vec<accounts::schemas::Counterparty>> {
+        accounts::data_provider::get_counterparties(
+            &self.pool_balances,
+            account_id,
+            block,
+            window_nanos,
+            limit,
+        )
+        .await
+    }
+
+    #[cfg(all(feature = "accounts", feature = "history"))]
+    async fn get_access_keys_history(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<
+        Vec<(
+            accounts::schemas::AccessKeyChange,
+            types::query_params::EventIndex,
+        )>,
+    > {
+        accounts::data_provider::get_access_keys_history(&self.pool, account_id, pagination).await
+    }
+
+    #[cfg(all(feature = "accounts", feature = "history"))]
+    async fn get_access_key_usage(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        public_key: &str,
+        block: &db_helpers::Block,
+        limit: u32,
+    ) -> crate::Result<Option<accounts::schemas::AccessKeyUsage>> {
+        accounts::data_provider::get_access_key_usage(
+            &self.pool, account_id, public_key, block, limit,
+        )
+        .await
+    }
+
+    #[cfg(all(feature = "accounts", feature = "history"))]
+    async fn get_block_transfers(
+        &self,
+        block: &db_helpers::Block,
+    ) -> crate::Result<Vec<accounts::schemas::BlockTransfer>> {
+        accounts::data_provider::get_block_transfers(&self.pool, &self.pool_balances, block).await
+    }
+
+    #[cfg(feature = "admin")]
+    async fn get_usage(
+        &self,
+        from_timestamp: u64,
+        to_timestamp: u64,
+        limit: u32,
+    ) -> crate::Result<Vec<admin::schemas::KeyUsage>> {
+        admin::data_provider::get_usage(&self.pool_aux, from_timestamp, to_timestamp, limit).await
+    }
+
+    #[cfg(feature = "admin")]
+    async fn get_key_usage(
+        &self,
+        key_id: &str,
+        from_timestamp: u64,
+        to_timestamp: u64,
+    ) -> crate::Result<Vec<admin::schemas::EndpointUsage>> {
+        admin::data_provider::get_key_usage(&self.pool_aux, key_id, from_timestamp, to_timestamp)
+            .await
+    }
+
+    #[cfg(feature = "admin")]
+    async fn get_quota(&self, key_id: &str) -> crate::Result<Option<admin::schemas::Quota>> {
+        admin::data_provider::get_quota(&self.pool_aux, key_id).await
+    }
+
+    #[cfg(feature = "admin")]
+    async fn set_quota(&self, key_id: &str, quota: &admin::schemas::Quota) -> crate::Result<()> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_nanos() as u64)
+            .unwrap_or_default();
+        admin::data_provider::set_quota(&self.pool_aux, key_id, quota, now).await
+    }
+
+    #[cfg(feature = "admin")]
+    async fn delete_quota(&self, key_id: &str) -> crate::Result<()> {
+        admin::data_provider::delete_quota(&self.pool_aux, key_id).await
+    }
+
+    #[cfg(feature = "admin")]
+    async fn get_audit_records(
+        &self,
+        actor_key_id: Option<&str>,
+        before_id: Option<u64>,
+        limit: u32,
+    ) -> crate::Result<Vec<admin::schemas::AuditRecord>> {
+        admin::data_provider::get_audit_records(&self.pool_aux, actor_key_id, before_id, limit)
+            .await
+    }
+
+    #[cfg(feature = "alerts")]
+    async fn get_alerts(&self, key_id: &str) -> crate::Result<Vec<alerts::schemas::Alert>> {
+        alerts::data_provider::get_alerts(&self.pool_aux, key_id).await
+    }
+
+    #[cfg(feature = "alerts")]
+    async fn create_alert(
+        &self,
+        key_id: &str,
+        rule: &alerts::schemas::AlertRule,
+    ) -> crate::Result<alerts::schemas::Alert> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_nanos() as u64)
+            .unwrap_or_default();
+        alerts::data_provider::create_alert(&self.pool_aux, key_id, rule, now).await
+    }
+
+    #[cfg(feature = "alerts")]
+    async fn delete_alert(&self, key_id: &str, alert_id: u64) -> crate::Result<bool> {
+        alerts::data_provider::delete_alert(&self.pool_aux, key_id, alert_id).await
+    }
+
+    #[cfg(feature = "watchlists")]
+    async fn get_watchlists(
+        &self,
+        key_id: &str,
+    ) -> crate::Result<Vec<watchlists::schemas::Watchlist>> {
+        watchlists::data_provider::get_watchlists(&self.pool_aux, key_id).await
+    }
+
+    #[cfg(feature = "watchlists")]
+    async fn get_watchlist(
+        &self,
+        key_id: &str,
+        watchlist_id: u64,
+    ) -> crate::Result<Option<watchlists::schemas::Watchlist>> {
+        watchlists::data_provider::get_watchlist(&self.pool_aux, key_id, watchlist_id).await
+    }
+
+    #[cfg(feature = "watchlists")]
+    async fn create_watchlist(
+        &self,
+        key_id: &str,
+        input: &watchlists::schemas::WatchlistInput,
+    ) -> crate::Result<watchlists::schemas::Watchlist> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_nanos() as u64)
+            .unwrap_or_default();
+        watchlists::data_provider::create_watchlist(&self.pool_aux, key_id, input, now).await
+    }
+
+    #[cfg(feature = "watchlists")]
+    async fn update_watchlist(
+        &self,
+        key_id: &str,
+        watchlist_id: u64,
+        input: &watchlists::schemas::WatchlistInput,
+    ) -> crate::Result<Option<watchlists::schemas::Watchlist>> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_nanos() as u64)
+            .unwrap_or_default();
+        watchlists::data_provider::update_watchlist(
+            &self.pool_aux,
+            key_id,
+            watchlist_id,
+            input,
+            now,
+        )
+        .await
+    }
+
+    #[cfg(feature = "watchlists")]
+    async fn delete_watchlist(&self, key_id: &str, watchlist_id: u64) -> crate::Result<bool> {
+        watchlists::data_provider::delete_watchlist(&self.pool_aux, key_id, watchlist_id).await
+    }
+
+    #[cfg(feature = "exports")]
+    async fn create_export_job(
+        &self,
+        key_id: &str,
+        account_id: &near_primitives::types::AccountId,
+        input: &exports::schemas::ExportInput,
+    ) -> crate::Result<exports::schemas::ExportJob> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_nanos() as u64)
+            .unwrap_or_default();
+        exports::data_provider::create_export_job(&self.pool_aux, key_id, account_id, input, now)
+            .await
+    }
+
+    #[cfg(feature = "exports")]
+    async fn get_export_job(
+        &self,
+        key_id: &str,
+        job_id: u64,
+    ) -> crate::Result<Option<exports::schemas::ExportJob>> {
+        exports::data_provider::get_export_job(&self.pool_aux, key_id, job_id).await
+    }
+
+    #[cfg(feature = "exports")]
+    async fn count_active_export_jobs(&self, key_id: &str) -> crate::Result<u64> {
+        exports::data_provider::count_active_export_jobs(&self.pool_aux, key_id).await
+    }
+}
@@ -0,0 +1,1019 @@
+//! ScyllaDB (Cassandra) read path, see `scylla/schema.cql` for the expected tables.
+//! Only the key lookups Cassandra is good at are served from Scylla: the blocks by height and hash,
+//! the last block, NEAR balances and the account existence. Everything else goes to `fallback`
+//! (usually `PostgresDataSource`), including the block by timestamp: "the last block before
+//! the timestamp" is the range query over the whole table in Cassandra.
+#[cfg(feature = "accounts")]
+use crate::modules::accounts;
+#[cfg(feature = "admin")]
+use crate::modules::admin;
+#[cfg(feature = "alerts")]
+use crate::modules::alerts;
+#[cfg(feature = "coin")]
+use crate::modules::coin;
+#[cfg(feature = "defi")]
+use crate::modules::defi;
+#[cfg(feature = "exports")]
+use crate::modules::exports;
+#[cfg(feature = "nft")]
+use crate::modules::nft;
+#[cfg(feature = "staking")]
+use crate::modules::staking;
+#[cfg(feature = "watchlists")]
+use crate::modules::watchlists;
+use crate::{db_helpers, errors, types};
+
+pub(crate) struct ScyllaDataSource {
+    /// The keyspace should be already selected
+    pub session: scylla::Session,
+    pub fallback: super::DataSourceRef,
+}
+
+/// The latest change of the account not later than the given moment
+struct AccountState {
+    nonstaked_balance: u128,
+    staked_balance: u128,
+    is_deleted: bool,
+}
+
+impl ScyllaDataSource {
+    async fn get_block_by_height(&self, block_height: u64) -> crate::Result<db_helpers::Block> {
+        let row = self
+            .session
+            .query(
+                "SELECT block_height, block_timestamp, block_hash FROM blocks_by_height WHERE block_height = ?",
+                (block_height as i64,),
+            )
+            .await
+            .map_err(scylla_error)?
+            .maybe_first_row_typed::<(i64, i64, String)>()
+            .map_err(scylla_error)?;
+        match row {
+            None => Err(errors::ErrorKind::BlockNotFound {
+                block_id: block_height.to_string(),
+            }
+            .into()),
+            Some(row) => Ok(to_block(row)),
+        }
+    }
+
+    async fn get_block_by_hash(&self, block_hash: &str) -> crate::Result<db_helpers::Block> {
+        let row = self
+            .session
+            .query(
+                "SELECT block_height, block_timestamp, block_hash FROM blocks WHERE block_hash = ?",
+                (block_hash,),
+            )
+            .await
+            .map_err(scylla_error)?
+            .maybe_first_row_typed::<(i64, i64, String)>()
+            .map_err(scylla_error)?;
+        match row {
+            None => Err(errors::ErrorKind::BlockNotFound {
+                block_id: block_hash.to_string(),
+            }
+            .into()),
+            Some(row) => Ok(to_block(row)),
+        }
+    }
+
+    async fn get_account_state(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        block_timestamp: u64,
+    ) -> crate::Result<Option<AccountState>> {
+        let row = self
+            .session
+            .query(
+                r"SELECT nonstaked_balance, staked_balance, is_deleted
+                  FROM account_changes
+                  WHERE account_id = ? AND block_timestamp <= ?
+                  LIMIT 1",
+                (account_id.as_str(), block_timestamp as i64),
+            )
+            .await
+            .map_err(scylla_error)?
+            .maybe_first_row_typed::<(String, String, bool)>()
+            .map_err(scylla_error)?;
+        row.map(to_account_state).transpose()
+    }
+}
+
+fn to_block((block_height, block_timestamp, block_hash): (i64, i64, String)) -> db_helpers::Block {
+    db_helpers::Block {
+        timestamp: block_timestamp as u64,
+        height: block_height as u64,
+        hash: block_hash,
+    }
+}
+
+fn to_account_state(
+    (nonstaked_balance, staked_balance, is_deleted): (String, String, bool),
+) -> crate::Result<AccountState> {
+    Ok(AccountState {
+        nonstaked_balance: parse_balance(&nonstaked_balance)?,
+        staked_balance: parse_balance(&staked_balance)?,
+        is_deleted,
+    })
+}
+
+/// The balances are stored as the decimal strings, `bigint` is too small for them
+fn parse_balance(balance: &str) -> crate::Result<u128> {
+    balance.parse::<u128>().map_err(|_| {
+        errors::ErrorKind::DBError(format!("balance {} is not a number", balance)).into()
+    })
+}
+
+fn scylla_error(err: impl std::fmt::Display) -> errors::Error {
+    errors::ErrorKind::DBError(err.to_string()).into()
+}
+
+#[async_trait::async_trait]
+impl super::DataSource for ScyllaDataSource {
+    async fn get_block_from_params(
+        &self,
+        params: &types::query_params::BlockParams,
+    ) -> crate::Result<db_helpers::Block> {
+        if let Some(block_height) = params.block_height {
+            self.get_block_by_height(block_height.0).await
+        } else if params.block_timestamp_nanos.is_some() {
+            self.fallback.get_block_from_params(params).await
+        } else {
+            self.get_last_block().await
+        }
+    }
+
+    async fn get_last_block(&self) -> crate::Result<db_helpers::Block> {
+        let row = self
+            .session
+            .query(
+                "SELECT block_height FROM meta WHERE key = 'last_block'",
+                &[],
+            )
+            .await
+            .map_err(scylla_error)?
+            .maybe_first_row_typed::<(i64,)>()
+            .map_err(scylla_error)?;
+        match row {
+            None => Err(errors::ErrorKind::DBError("blocks table is empty".to_string()).into()),
+            Some((block_height,)) => self.get_block_by_height(block_height as u64).await,
+        }
+    }
+
+    async fn get_block_by_id(&self, block_id: &str) -> crate::Result<db_helpers::Block> {
+        if !block_id.is_empty() && block_id.chars().all(|c| c.is_ascii_digit()) {
+            let block_height = block_id.parse::<u64>().map_err(|_| {
+                errors::ErrorKind::InvalidInput(format!("block_height {} is too big", block_id))
+            })?;
+            self.get_block_by_height(block_height).await
+        } else {
+            self.get_block_by_hash(block_id).await
+        }
+    }
+
+    async fn get_blocks_by_heights(
+        &self,
+        block_heights: &[u64],
+    ) -> crate::Result<Vec<db_helpers::Block>> {
+        // The closest block before the skipped height is the range query
+        self.fallback.get_blocks_by_heights(block_heights).await
+    }
+
+    async fn does_account_exist(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        block_timestamp: u64,
+    ) -> crate::Result<bool> {
+        Ok(self
+            .get_account_state(account_id, block_timestamp)
+            .await?
+            .map(|state| !state.is_deleted)
+            .unwrap_or(false))
+    }
+
+    async fn get_code_changes(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+        limit: u32,
+    ) -> crate::Result<Vec<types::CodeChange>> {
+        self.fallback
+            .get_code_changes(contract_id, block, limit)
+            .await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_near_balance(
+        &self,
+        block: &db_helpers::Block,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<coin::schemas::NearBalanceResponse> {
+        match self.get_account_state(account_id, block.timestamp).await? {
+            Some(state) => Ok(coin::schemas::NearBalanceResponse {
+                balance: state
+                    .nonstaked_balance
+                    .saturating_add(state.staked_balance)
+                    .into(),
+                metadata: coin::data_provider::get_near_metadata(),
+                meta: types::ResponseMeta::new(&block),
+            }),
+            None => Err(errors::ErrorKind::DBError(format!(
+                "Could not find the data in account_changes table for account_id {}",
+                account_id
+            ))
+            .into()),
+        }
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_near_balance_breakdown(
+        &self,
+        block: &db_helpers::Block,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<coin::schemas::NearBalanceBreakdownResponse> {
+        // The storage usage is not in `account_state`
+        self.fallback
+            .get_near_balance_breakdown(block, account_id)
+            .await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_coin_balances(
+        &self,
+        block: &db_helpers::Block,
+        account_id: &near_primitives::types::AccountId,
+        pagination: &types::query_params::Pagination,
+    ) -> crate::Result<(Vec<coin::schemas::Coin>, Vec<types::FailedContract>)> {
+        self.fallback
+            .get_coin_balances(block, account_id, pagination)
+            .await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_coin_balances_by_contract(
+        &self,
+        block: &db_helpers::Block,
+        contract_id: &near_primitives::types::AccountId,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<Vec<coin::schemas::Coin>> {
+        self.fallback
+            .get_coin_balances_by_contract(block, contract_id, account_id)
+            .await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_ft_balance(
+        &self,
+        block: &db_helpers::Block,
+        contract_id: &near_primitives::types::AccountId,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<u128> {
+        self.fallback
+            .get_ft_balance(block, contract_id, account_id)
+            .await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_ft_balance_from_events(
+        &self,
+        block: &db_helpers::Block,
+        contract_id: &near_primitives::types::AccountId,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<u128> {
+        self.fallback
+            .get_ft_balance_from_events(block, contract_id, account_id)
+            .await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_changed_ft_contracts(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        from_block: &db_helpers::Block,
+        to_block: &db_helpers::Block,
+        limit: u32,
+    ) -> crate::Result<Vec<coin::data_provider::ChangedFtContract>> {
+        self.fallback
+            .get_changed_ft_contracts(account_id, from_block, to_block, limit)
+            .await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_near_last_modified(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+    ) -> crate::Result<Option<u64>> {
+        self.fallback
+            .get_near_last_modified(account_id, block)
+            .await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_ft_last_modified(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        contract_id: Option<&near_primitives::types::AccountId>,
+        block: &db_helpers::Block,
+    ) -> crate::Result<Option<u64>> {
+        self.fallback
+            .get_ft_last_modified(account_id, contract_id, block)
+            .await
+    }
+
+    #[cfg(all(feature = "coin", feature = "history"))]
+    async fn get_near_history(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(coin::schemas::HistoryItem, types::query_params::EventIndex)>> {
+        self.fallback.get_near_history(account_id, pagination).await
+    }
+
+    #[cfg(all(feature = "coin", feature = "history"))]
+    async fn get_gas_fees(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+        window_nanos: u64,
+    ) -> crate::Result<coin::schemas::GasFeesResponse> {
+        self.fallback
+            .get_gas_fees(account_id, block, window_nanos)
+            .await
+    }
+
+    #[cfg(all(feature = "coin", feature = "history"))]
+    async fn get_coin_history(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        account_id: &near_primitives::types::AccountId,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(coin::schemas::HistoryItem, types::query_params::EventIndex)>> {
+        self.fallback
+            .get_coin_history(contract_id, account_id, pagination)
+            .await
+    }
+
+    #[cfg(all(feature = "coin", feature = "history"))]
+    async fn get_ft_transfers(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(coin::schemas::FtTransfer, types::query_params::EventIndex)>> {
+        self.fallback
+            .get_ft_transfers(contract_id, pagination)
+            .await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_ft_holders(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block_timestamp: u64,
+        after_account_id: Option<&near_primitives::types::AccountId>,
+        limit: u32,
+    ) -> crate::Result<Vec<coin::schemas::FtHolder>> {
+        self.fallback
+            .get_ft_holders(contract_id, block_timestamp, after_account_id, limit)
+            .await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_ft_contract_metadata(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block_height: u64,
+    ) -> crate::Result<coin::schemas::FtContractMetadata> {
+        self.fallback
+            .get_ft_contract_metadata(contract_id, block_height)
+            .await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_ft_contracts_metadata(
+        &self,
+        contract_ids: &[near_primitives::types::AccountId],
+        block_height: u64,
+    ) -> crate::Result<(
+        Vec<coin::schemas::FtContractMetadataItem>,
+        Vec<types::FailedContract>,
+    )> {
+        self.fallback
+            .get_ft_contracts_metadata(contract_ids, block_height)
+            .await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn refresh_ft_contract_metadata(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block_height: u64,
+    ) -> crate::Result<coin::schemas::FtContractMetadata> {
+        self.fallback
+            .refresh_ft_contract_metadata(contract_id, block_height)
+            .await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_ft_icon(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block_height: u64,
+    ) -> crate::Result<Option<coin::data_provider::Icon>> {
+        self.fallback.get_ft_icon(contract_id, block_height).await
+    }
+
+    #[cfg(feature = "coin")]
+    async fn get_token_price_history(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        bucket: &coin::schemas::PriceBucket,
+        block_timestamp: u64,
+        limit: u32,
+    ) -> crate::Result<Vec<coin::schemas::PricePoint>> {
+        self.fallback
+            .get_token_price_history(contract_id, bucket, block_timestamp, limit)
+            .await
+    }
+
+    #[cfg(feature = "nft")]
+    async fn get_nfts_count(
+        &self,
+        block: &db_helpers::Block,
+        account_id: &near_primitives::types::AccountId,
+        pagination_params: types::query_params::PaginationParams,
+    ) -> crate::Result<(Vec<nft::schemas::NftCount>, Vec<types::FailedContract>)> {
+        self.fallback
+            .get_nfts_count(block, account_id, pagination_params)
+            .await
+    }
+
+    #[cfg(feature = "nft")]
+    async fn get_nfts_by_contract(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        account_id: &near_primitives::types::AccountId,
+        block_height: u64,
+        limit: u32,
+    ) -> crate::Result<Vec<nft::schemas::Nft>> {
+        self.fallback
+            .get_nfts_by_contract(contract_id, account_id, block_height, limit)
+            .await
+    }
+
+    #[cfg(feature = "nft")]
+    async fn get_nft(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        token_id: &str,
+        block_height: u64,
+    ) -> crate::Result<nft::schemas::Nft> {
+        self.fallback
+            .get_nft(contract_id, token_id, block_height)
+            .await
+    }
+
+    #[cfg(all(feature = "nft", feature = "history"))]
+    async fn get_nft_history(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        token_id: &str,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(nft::schemas::HistoryItem, types::query_params::EventIndex)>> {
+        self.fallback
+            .get_nft_history(contract_id, token_id, pagination)
+            .await
+    }
+
+    #[cfg(feature = "nft")]
+    async fn get_nft_contract_metadata(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block_height: u64,
+    ) -> crate::Result<Option<nft::schemas::NftContractMetadata>> {
+        self.fallback
+            .get_nft_contract_metadata(contract_id, block_height)
+            .await
+    }
+
+    #[cfg(feature = "nft")]
+    async fn get_nft_contracts_metadata(
+        &self,
+        contract_ids: &[near_primitives::types::AccountId],
+        block_height: u64,
+    ) -> crate::Result<(
+        Vec<nft::schemas::NftContractMetadataItem>,
+        Vec<types::FailedContract>,
+    )> {
+        self.fallback
+            .get_nft_contracts_metadata(contract_ids, block_height)
+            .await
+    }
+
+    #[cfg(feature = "nft")]
+    async fn refresh_nft_contract_metadata(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block_height: u64,
+    ) -> crate::Result<Option<nft::schemas::NftContractMetadata>> {
+        self.fallback
+            .refresh_nft_contract_metadata(contract_id, block_height)
+            .await
+    }
+
+    #[cfg(all(feature = "nft", feature = "history"))]
+    async fn get_nft_sales(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        token_id: &str,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(nft::schemas::NftSale, types::query_params::EventIndex)>> {
+        self.fallback
+            .get_nft_sales(contract_id, token_id, pagination)
+            .await
+    }
+
+    #[cfg(all(feature = "nft", feature = "history"))]
+    async fn get_nft_approvals_history(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<Vec<(nft::schemas::NftApproval, types::query_params::EventIndex)>> {
+        self.fallback
+            .get_nft_approvals_history(account_id, pagination)
+            .await
+    }
+
+    #[cfg(all(feature = "nft", feature = "history"))]
+    async fn get_nft_provenance(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        token_id: &str,
+        block: &db_helpers::Block,
+    ) -> crate::Result<Vec<nft::schemas::ProvenanceItem>> {
+        self.fallback
+            .get_nft_provenance(contract_id, token_id, block)
+            .await
+    }
+
+    #[cfg(feature = "nft")]
+    async fn get_nft_market_stats(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+        window_nanos: u64,
+    ) -> crate::Result<nft::schemas::NftMarketStatsResponse> {
+        self.fallback
+            .get_nft_market_stats(contract_id, block, window_nanos)
+            .await
+    }
+
+    #[cfg(feature = "nft")]
+    async fn get_nft_holdings_diff(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        from_block: &db_helpers::Block,
+        to_block: &db_helpers::Block,
+        limit: u32,
+    ) -> crate::Result<nft::data_provider::NftHoldingsDiff> {
+        self.fallback
+            .get_nft_holdings_diff(account_id, from_block, to_block, limit)
+            .await
+    }
+
+    #[cfg(feature = "staking")]
+    async fn get_staking_summary(
+        &self,
+        block: &db_helpers::Block,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<staking::schemas::StakingSummaryResponse> {
+        self.fallback.get_staking_summary(block, account_id).await
+    }
+
+    #[cfg(feature = "staking")]
+    async fn get_staking_pools(
+        &self,
+        block: &db_helpers::Block,
+    ) -> crate::Result<staking::schemas::StakingPoolsResponse> {
+        self.fallback.get_staking_pools(block).await
+    }
+
+    #[cfg(feature = "staking")]
+    async fn get_supply_stats(
+        &self,
+        block: &db_helpers::Block,
+    ) -> crate::Result<staking::schemas::SupplyStatsResponse> {
+        self.fallback.get_supply_stats(block).await
+    }
+
+    #[cfg(feature = "defi")]
+    async fn get_ref_finance_positions(
+        &self,
+        block: &db_helpers::Block,
+        account_id: &near_primitives::types::AccountId,
+    ) -> crate::Result<defi::schemas::RefFinancePositionsResponse> {
+        self.fallback
+            .get_ref_finance_positions(block, account_id)
+            .await
+    }
+
+    #[cfg(feature = "accounts")]
+    async fn get_account_counters(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+        is_latest: bool,
+    ) -> crate::Result<accounts::schemas::AccountCountersResponse> {
+        self.fallback
+            .get_account_counters(account_id, block, is_latest)
+            .await
+    }
+
+    #[cfg(feature = "accounts")]
+    async fn get_account_activity_summary(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+    ) -> crate::Result<Option<accounts::schemas::AccountActivitySummaryResponse>> {
+        self.fallback
+            .get_account_activity_summary(account_id, block)
+            .await
+    }
+
+    #[cfg(feature = "accounts")]
+    async fn get_deployments(
+        &self,
+        contract_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+        limit: u32,
+    ) -> crate::Result<Vec<accounts::schemas::Deployment>> {
+        self.fallback
+            .get_deployments(contract_id, block, limit)
+            .await
+    }
+
+    #[cfg(feature = "accounts")]
+    async fn get_receipt(
+        &self,
+        receipt_id: &str,
+        block: &db_helpers::Block,
+    ) -> crate::Result<Option<accounts::schemas::Receipt>> {
+        self.fallback.get_receipt(receipt_id, block).await
+    }
+
+    #[cfg(feature = "accounts")]
+    async fn get_gas_profile(
+        &self,
+        transaction_hash: &str,
+        block: &db_helpers::Block,
+    ) -> crate::Result<Option<accounts::schemas::GasProfile>> {
+        self.fallback.get_gas_profile(transaction_hash, block).await
+    }
+
+    #[cfg(all(feature = "accounts", feature = "history"))]
+    async fn get_counterparties(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        block: &db_helpers::Block,
+        window_nanos: u64,
+        limit: u32,
+    ) -> crate::Result<Vec<accounts::schemas::Counterparty>> {
+        self.fallback
+            .get_counterparties(account_id, block, window_nanos, limit)
+            .await
+    }
+
+    #[cfg(all(feature = "accounts", feature = "history"))]
+    async fn get_access_keys_history(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        pagination: &types::query_params::HistoryPagination,
+    ) -> crate::Result<
+        Vec<(
+            accounts::schemas::AccessKeyChange,
+            types::query_params::EventIndex,
+        )>,
+    > {
+        self.fallback
+            .get_access_keys_history(account_id, pagination)
+            .await
+    }
+
+    #[cfg(all(feature = "accounts", feature = "history"))]
+    async fn get_access_key_usage(
+        &self,
+        account_id: &near_primitives::types::AccountId,
+        public_key: &str,
+        block: &db_helpers::Block,
+        limit: u32,
+    ) -> crate::Result<Option<accounts::schemas::AccessKeyUsage>> {
+        self.fallback
+            .get_access_key_usage(account_id, public_key, block, limit)
+            .await
+    }
+
+    #[cfg(all(feature = "accounts", feature = "history"))]
+    async fn get_block_transfers(
+        &self,
+        block: &db_helpers::Block,
+    ) -> crate::Result<Vec<accounts::schemas::BlockTransfer>> {
+        self.fallback.get_block_transfers(block).await
+    }
+
+    #[cfg(feature = "admin")]
+    async fn get_usage(
+        &self,
+        from_timestamp: u64,
+        to_timestamp: u64,
+        limit: u32,
+    ) -> crate::Result<Vec<admin::schemas::KeyUsage>> {
+        self.fallback
+            .get_usage(from_timestamp, to_timestamp, limit)
+            .await
+    }
+
+    #[cfg(feature = "admin")]
+    async fn get_key_usage(
+        &self,
+        key_id: &str,
+        from_timestamp: u64,
+        to_timestamp: u64,
+    ) -> crate::Result<Vec<admin::schemas::EndpointUsage>> {
+        self.fallback
+            .get_key_usage(key_id, from_timestamp, to_timestamp)
+            .await
+    }
+
+    #[cfg(feature = "admin")]
+    async fn get_quota(&self, key_id: &str) -> crate::Result<Option<admin::schemas::Quota>> {
+        self.fallback.get_quota(key_id).await
+    }
+
+    #[cfg(feature = "admin")]
+    async fn set_quota(&self, key_id: &str, quota: &admin::schemas::Quota) -> crate::Result<()> {
+        self.fallback.set_quota(key_id, quota).await
+    }
+
+    #[cfg(feature = "admin")]
+    async fn delete_quota(&self, key_id: &str) -> crate::Result<()> {
+        self.fallback.delete_quota(key_id).await
+    }
+
+    #[cfg(feature = "admin")]
+    async fn get_audit_records(
+        &self,
+        actor_key_id: Option<&str>,
+        before_id: Option<u64>,
+        limit: u32,
+    ) -> crate::Result<Vec<admin::schemas::AuditRecord>> {
+        self.fallback
+            .get_audit_records(actor_key_id, before_id, limit)
+            .await
+    }
+
+    #[cfg(feature = "alerts")]
+    async fn get_alerts(&self, key_id: &str) -> crate::Result<Vec<alerts::schemas::Alert>> {
+        self.fallback.get_alerts(key_id).await
+    }
+
+    #[cfg(feature = "alerts")]
+    async fn create_alert(
+        &self,
+        key_id: &str,
+        rule: &alerts::schemas::AlertRule,
+    ) -> crate::Result<alerts::schemas::Alert> {
+        self.fallback.create_alert(key_id, rule).await
+    }
+
+    #[cfg(feature = "alerts")]
+    async fn delete_alert(&self, key_id: &str, alert_id: u64) -> crate::Result<bool> {
+        self.fallback.delete_alert(key_id, alert_id).await
+    }
+
+    #[cfg(feature = "watchlists")]
+    async fn get_watchlists(
+        &self,
+        key_id: &str,
+    ) -> crate::Result<Vec<watchlists::schemas::Watchlist>> {
+        self.fallback.get_watchlists(key_id).await
+    }
+
+    #[cfg(feature = "watchlists")]
+    async fn get_watchlist(
+        &self,
+        key_id: &str,
+        watchlist_id: u64,
+    ) -> crate::Result<Option<watchlists::schemas::Watchlist>> {
+        self.fallback.get_watchlist(key_id, watchlist_id).await
+    }
+
+    #[cfg(feature = "watchlists")]
+    async fn create_watchlist(
+        &self,
+        key_id: &str,
+        input: &watchlists::schemas::WatchlistInput,
+    ) -> crate::Result<watchlists::schemas::Watchlist> {
+        self.fallback.create_watchlist(key_id, input).await
+    }
+
+    #[cfg(feature = "watchlists")]
+    async fn update_watchlist(
+        &self,
+        key_id: &str,
+        watchlist_id: u64,
+        input: &watchlists::schemas::WatchlistInput,
+    ) -> crate::Result<Option<watchlists::schemas::Watchlist>> {
+        self.fallback
+            .update_watchlist(key_id, watchlist_id, input)
+            .await
+    }
+
+    #[cfg(feature = "watchlists")]
+    async fn delete_watchlist(&self, key_id: &str, watchlist_id: u64) -> crate::Result<bool> {
+        self.fallback.delete_watchlist(key_id, watchlist_id).await
+    }
+
+    #[cfg(feature = "exports")]
+    async fn create_export_job(
+        &self,
+        key_id: &str,
+        account_id: &near_primitives::types::AccountId,
+        input: &exports::schemas::ExportInput,
+    ) -> crate::Result<exports::schemas::ExportJob> {
+        self.fallback
+            .create_export_job(key_id, account_id, input)
+            .await
+    }
+
+    #[cfg(feature = "exports")]
+    async fn get_export_job(
+        &self,
+        key_id: &str,
+        job_id: u64,
+    ) -> crate::Result<Option<exports::schemas::ExportJob>> {
+        self.fallback.get_export_job(key_id, job_id).await
+    }
+
+    #[cfg(feature = "exports")]
+    async fn count_active_export_jobs(&self, key_id: &str) -> crate::Result<u64> {
+        self.fallback.count_active_export_jobs(key_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_source::DataSource;
+    use crate::modules::tests::*;
+
+    #[test]
+    fn test_to_block() {
+        let block = to_block((103, 1600000000000000103, get_seeded_block_hash(103)));
+        assert_eq!(block.height, 103);
+        assert_eq!(block.timestamp, 1600000000000000103);
+        assert_eq!(block.hash, get_seeded_block_hash(103));
+    }
+
+    #[test]
+    fn test_to_account_state() {
+        // Both balances are above `bigint`, that's why they are the strings
+        let state = to_account_state((
+            "1000000000000000000000000".to_string(),
+            "500000000000000000000000".to_string(),
+            false,
+        ))
+        .unwrap();
+        assert_eq!(state.nonstaked_balance, 10u128.pow(24));
+        assert_eq!(state.staked_balance, 5 * 10u128.pow(23));
+        assert!(!state.is_deleted);
+
+        let state = to_account_state(("0".to_string(), "0".to_string(), true)).unwrap();
+        assert!(state.is_deleted);
+
+        for balance in ["", "-1", "1.5", "abc"] {
+            let error = to_account_state((balance.to_string(), "0".to_string(), false))
+                .err()
+                .unwrap();
+            assert_eq!(error.error_code, "DB_ERROR");
+        }
+    }
+
+    /// Requires ScyllaDB at `SCYLLA_URL`, e.g. `docker run -p 9042:9042 scylladb/scylla`.
+    /// Creates `near_enhanced_api_test` keyspace with `scylla/schema.cql` and the blocks 100..103 of the seed
+    #[tokio::test]
+    #[ignore]
+    async fn test_scylla_data_source() {
+        dotenv::dotenv().ok();
+        let scylla_url = std::env::var("SCYLLA_URL").expect("failed to get ScyllaDB url");
+        let session = scylla::SessionBuilder::new()
+            .known_node(scylla_url)
+            .build()
+            .await
+            .unwrap();
+        session
+            .query(
+                "CREATE KEYSPACE IF NOT EXISTS near_enhanced_api_test \
+                 WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 1}",
+                &[],
+            )
+            .await
+            .unwrap();
+        session
+            .use_keyspace("near_enhanced_api_test", false)
+            .await
+            .unwrap();
+        let schema: String = include_str!("../../scylla/schema.cql")
+            .lines()
+            .filter(|line| !line.starts_with("--"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        for statement in schema.split(';').filter(|s| !s.trim().is_empty()) {
+            session.query(statement, &[]).await.unwrap();
+        }
+        for height in 100..=103 {
+            let block = get_seeded_block(height);
+            session
+                .query(
+                    "INSERT INTO blocks (block_hash, block_height, block_timestamp) VALUES (?, ?, ?)",
+                    (&block.hash, block.height as i64, block.timestamp as i64),
+                )
+                .await
+                .unwrap();
+            session
+                .query(
+                    "INSERT INTO blocks_by_height (block_height, block_timestamp, block_hash) VALUES (?, ?, ?)",
+                    (block.height as i64, block.timestamp as i64, &block.hash),
+                )
+                .await
+                .unwrap();
+        }
+        session
+            .query(
+                "INSERT INTO meta (key, block_height) VALUES ('last_block', ?)",
+                (103_i64,),
+            )
+            .await
+            .unwrap();
+        // bob.near is created at 100 and deleted at 101
+        for (block_timestamp, balance, is_deleted) in [
+            (1600000000000000100_i64, "1000000000000000000000000", false),
+            (1600000000000000101_i64, "0", true),
+        ] {
+            session
+                .query(
+                    r"INSERT INTO account_changes
+                      (account_id, block_timestamp, index_in_block, nonstaked_balance, staked_balance, is_deleted)
+                      VALUES ('bob.near', ?, 0, ?, '0', ?)",
+                    (block_timestamp, balance, is_deleted),
+                )
+                .await
+                .unwrap();
+        }
+
+        let data_source = ScyllaDataSource {
+            session,
+            fallback: std::sync::Arc::new(crate::data_source::FixtureDataSource {
+                fixtures_dir: None,
+            }),
+        };
+        let block = data_source.get_last_block().await.unwrap();
+        assert_eq!(
+            (block.height, block.hash),
+            (103, get_seeded_block_hash(103))
+        );
+        let block = data_source.get_block_by_id("101").await.unwrap();
+        assert_eq!(
+            (block.timestamp, block.hash),
+            (1600000000000000101, get_seeded_block_hash(101))
+        );
+        let block = data_source
+            .get_block_by_id(&get_seeded_block_hash(102))
+            .await
+            .unwrap();
+        assert_eq!(block.height, 102);
+        let error = data_source.get_block_by_id("500").await.err().unwrap();
+        assert_eq!(error.error_code, "BLOCK_NOT_FOUND");
+
+        let bob = near_primitives::types::AccountId::try_from("bob.near".to_string()).unwrap();
+        assert!(data_source
+            .does_account_exist(&bob, 1600000000000000100)
+            .await
+            .unwrap());
+        assert!(!data_source
+            .does_account_exist(&bob, 1600000000000000103)
+            .await
+            .unwrap());
+        assert!(!data_source
+            .does_account_exist(&bob, 1600000000000000099)
+            .await
+            .unwrap());
+        #[cfg(feature = "coin")]
+        {
+            let balance = data_source
+                .get_near_balance(&get_seeded_block(100), &bob)
+                .await
+                .unwrap();
+            assert_eq!(balance.balance.0, 10u128.pow(24));
+        }
+    }
+}
@@ -6,15 +6,22 @@ const DB_RETRY_COUNT: usize = 1;
 const INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
 const MAX_DELAY_TIME: std::time::Duration = std::time::Duration::from_secs(120);
 
-// temp solution to pass 2 different connection pools
-pub struct DBWrapper {
-    pub pool: sqlx::Pool<sqlx::Postgres>,
+/// 0 disables the slow query log. It's process-wide, the helpers are given only the pool
+static SLOW_QUERY_THRESHOLD_MS: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+/// `select_retry_or_panic` warns about the queries slower than `threshold`, with the query and its parameters
+pub(crate) fn set_slow_query_threshold(threshold: std::time::Duration) {
+    SLOW_QUERY_THRESHOLD_MS.store(
+        threshold.as_millis() as u64,
+        std::sync::atomic::Ordering::Relaxed,
+    );
 }
 
 #[derive(sqlx::FromRow)]
 struct BlockView {
     pub block_height: BigDecimal,
     pub block_timestamp: BigDecimal,
+    pub block_hash: String,
 }
 
 #[derive(sqlx::FromRow)]
@@ -22,14 +29,27 @@ struct ActionKindView {
     pub action_kind: String,
 }
 
+#[derive(sqlx::FromRow)]
+struct CodeChangeView {
+    pub code_sha256: String,
+    pub previous_code_sha256: Option<String>,
+    pub deployer_account_id: String,
+    pub receipt_id: String,
+    pub transaction_hash: Option<String>,
+    pub block_timestamp: BigDecimal,
+    pub block_height: BigDecimal,
+    pub block_hash: String,
+}
+
 #[derive(sqlx::FromRow)]
 pub(crate) struct AccountId {
     pub account_id: String,
 }
 
-pub(crate) struct Block {
+pub struct Block {
     pub timestamp: u64,
     pub height: u64,
+    pub hash: String,
 }
 
 impl TryFrom<&BlockView> for Block {
@@ -39,6 +59,7 @@ impl TryFrom<&BlockView> for Block {
         Ok(Self {
             timestamp: types::numeric::to_u64(&block.block_timestamp)?,
             height: types::numeric::to_u64(&block.block_height)?,
+            hash: block.block_hash.clone(),
         })
     }
 }
@@ -79,18 +100,26 @@ pub(crate) async fn get_block_from_params(
     if let Some(block_height) = params.block_height {
         match select_retry_or_panic::<BlockView>(
             pool,
-            "SELECT block_height, block_timestamp FROM blocks WHERE block_height = $1::numeric(20, 0)",
+            "SELECT block_height, block_timestamp, block_hash FROM blocks WHERE block_height = $1::numeric(20, 0)",
             &[block_height.0.to_string()],
                     )
             .await?
             .first() {
-            None => Err(errors::ErrorKind::DBError(format!("block_height {} is not found", block_height.0)).into()),
+            None => {
+                // The block could be not indexed yet, the client should retry then
+                let last_block = get_last_block(pool).await?;
+                if block_height.0 > last_block.height {
+                    Err(errors::ErrorKind::IndexerLag { block_height: block_height.0, last_block_height: last_block.height }.into())
+                } else {
+                    Err(errors::ErrorKind::BlockNotFound { block_id: block_height.0.to_string() }.into())
+                }
+            }
             Some(block) => Ok(Block::try_from(block)?)
         }
     } else if let Some(block_timestamp) = params.block_timestamp_nanos {
         match select_retry_or_panic::<BlockView>(
             pool,
-            r"SELECT block_height, block_timestamp
+            r"SELECT block_height, block_timestamp, block_hash
               FROM blocks
               WHERE block_timestamp <= $1::numeric(20, 0)
               ORDER BY block_timestamp DESC
@@ -108,10 +137,128 @@ pub(crate) async fn get_block_from_params(
     }
 }
 
+/// `block_id` is the block height or the block hash
+pub(crate) async fn get_block_by_id(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    block_id: &str,
+) -> crate::Result<Block> {
+    let query = if !block_id.is_empty() && block_id.chars().all(|c| c.is_ascii_digit()) {
+        "SELECT block_height, block_timestamp, block_hash FROM blocks WHERE block_height = $1::numeric(20, 0)"
+    } else {
+        "SELECT block_height, block_timestamp, block_hash FROM blocks WHERE block_hash = $1"
+    };
+    match select_retry_or_panic::<BlockView>(pool, query, &[block_id.to_string()])
+        .await?
+        .first()
+    {
+        None => Err(errors::ErrorKind::BlockNotFound {
+            block_id: block_id.to_string(),
+        }
+        .into()),
+        Some(block) => Ok(Block::try_from(block)?),
+    }
+}
+
+/// For each height, the block at this height or the closest one before it (some heights are skipped).
+/// The heights before the first block are dropped. Ordered by height, without duplicates
+pub(crate) async fn get_blocks_by_heights(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    block_heights: &[u64],
+) -> crate::Result<Vec<Block>> {
+    let heights = block_heights
+        .iter()
+        .map(|height| height.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    let blocks = select_retry_or_panic::<BlockView>(
+        pool,
+        r"SELECT DISTINCT blocks.block_height, blocks.block_timestamp, blocks.block_hash
+          FROM unnest($1::numeric(20, 0)[]) requested (block_height)
+          CROSS JOIN LATERAL (
+              SELECT block_height, block_timestamp, block_hash
+              FROM blocks
+              WHERE blocks.block_height <= requested.block_height
+              ORDER BY blocks.block_height DESC
+              LIMIT 1
+          ) blocks
+          ORDER BY blocks.block_height",
+        &[format!("{{{}}}", heights)],
+    )
+    .await?;
+    blocks.iter().map(Block::try_from).collect()
+}
+
+/// The successful deployments which changed the code of the contract, up to the given block.
+/// Recent changes go first, the redeployments of the same code are skipped
+pub(crate) async fn get_code_changes(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    contract_id: &near_primitives::types::AccountId,
+    block_timestamp: u64,
+    limit: u32,
+) -> crate::Result<Vec<types::CodeChange>> {
+    let query = r"
+        SELECT *
+        FROM (
+            SELECT
+                args->>'code_sha256' code_sha256,
+                lag(args->>'code_sha256') OVER (ORDER BY action_receipt_actions.receipt_included_in_block_timestamp)
+                    previous_code_sha256,
+                receipt_predecessor_account_id deployer_account_id,
+                action_receipt_actions.receipt_id,
+                action_receipts.originated_from_transaction_hash transaction_hash,
+                blocks.block_timestamp,
+                blocks.block_height,
+                blocks.block_hash
+            FROM action_receipt_actions
+                JOIN execution_outcomes ON action_receipt_actions.receipt_id = execution_outcomes.receipt_id
+                JOIN blocks ON execution_outcomes.executed_in_block_hash = blocks.block_hash
+                LEFT JOIN action_receipts ON action_receipt_actions.receipt_id = action_receipts.receipt_id
+            WHERE receipt_receiver_account_id = $1
+                AND action_kind = 'DEPLOY_CONTRACT'
+                AND execution_outcomes.status IN ('SUCCESS_VALUE', 'SUCCESS_RECEIPT_ID')
+                AND action_receipt_actions.receipt_included_in_block_timestamp <= $2::numeric(20, 0)
+        ) deployments
+        WHERE previous_code_sha256 IS DISTINCT FROM code_sha256
+        ORDER BY block_timestamp DESC
+        LIMIT $3::numeric(20, 0)
+    ";
+    select_retry_or_panic::<CodeChangeView>(
+        pool,
+        query,
+        &[
+            contract_id.to_string(),
+            block_timestamp.to_string(),
+            limit.to_string(),
+        ],
+    )
+    .await?
+    .into_iter()
+    .map(|change| {
+        Ok(types::CodeChange {
+            code_hash: types::code_change::get_code_hash(&change.code_sha256)?,
+            previous_code_hash: change
+                .previous_code_sha256
+                .as_deref()
+                .map(types::code_change::get_code_hash)
+                .transpose()?,
+            deployer_account_id: change
+                .deployer_account_id
+                .parse::<near_primitives::types::AccountId>()?
+                .into(),
+            receipt_id: change.receipt_id,
+            transaction_hash: change.transaction_hash,
+            block_timestamp_nanos: types::numeric::to_u64(&change.block_timestamp)?.into(),
+            block_height: types::numeric::to_u64(&change.block_height)?.into(),
+            block_hash: change.block_hash,
+        })
+    })
+    .collect()
+}
+
 async fn get_first_block(pool: &sqlx::Pool<sqlx::Postgres>) -> crate::Result<Block> {
     match select_retry_or_panic::<BlockView>(
         pool,
-        r"SELECT block_height, block_timestamp
+        r"SELECT block_height, block_timestamp, block_hash
           FROM blocks
           ORDER BY block_timestamp
           LIMIT 1",
@@ -128,7 +275,7 @@ async fn get_first_block(pool: &sqlx::Pool<sqlx::Postgres>) -> crate::Result<Blo
 pub(crate) async fn get_last_block(pool: &sqlx::Pool<sqlx::Postgres>) -> crate::Result<Block> {
     match select_retry_or_panic::<BlockView>(
         pool,
-        r"SELECT block_height, block_timestamp
+        r"SELECT block_height, block_timestamp, block_hash
           FROM blocks
           ORDER BY block_timestamp DESC
           LIMIT 1",
@@ -171,11 +318,15 @@ pub(crate) async fn select_retry_or_panic<T: Send + Unpin + for<'r> sqlx::FromRo
             args.add(item);
         }
 
+        let started = std::time::Instant::now();
         match sqlx::query_as_with::<_, T, _>(query, args)
             .fetch_all(pool)
             .await
         {
-            Ok(res) => return Ok(res),
+            Ok(res) => {
+                log_if_slow(started.elapsed(), query, substitution_items, res.len());
+                return Ok(res);
+            }
             Err(async_error) => {
                 tracing::warn!(
                     target: crate::LOGGER_MSG,
@@ -193,3 +344,145 @@ pub(crate) async fn select_retry_or_panic<T: Send + Unpin + for<'r> sqlx::FromRo
         }
     }
 }
+
+fn log_if_slow(
+    elapsed: std::time::Duration,
+    query: &str,
+    substitution_items: &[String],
+    rows_count: usize,
+) {
+    let threshold_ms = SLOW_QUERY_THRESHOLD_MS.load(std::sync::atomic::Ordering::Relaxed);
+    if threshold_ms == 0 || elapsed.as_millis() < threshold_ms as u128 {
+        return;
+    }
+    tracing::warn!(
+        target: crate::LOGGER_MSG,
+        duration_ms = elapsed.as_millis() as u64,
+        // One line, so the same queries could be grouped
+        query = %query.split_whitespace().collect::<Vec<_>>().join(" "),
+        params = %substitution_items.join(", "),
+        rows_count,
+        "Slow DB query"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_get_block_from_params_seeded() {
+        let db = init_seeded_db().await;
+
+        let by_height = types::query_params::BlockParams {
+            block_height: Some(101.into()),
+            ..Default::default()
+        };
+        let block = get_block_from_params(&db.pool, &by_height).await.unwrap();
+        assert_eq!((block.height, block.timestamp), (101, 1600000000000000101));
+
+        let unknown_height = types::query_params::BlockParams {
+            block_height: Some(500.into()),
+            ..Default::default()
+        };
+        let error = get_block_from_params(&db.pool, &unknown_height)
+            .await
+            .unwrap_err();
+        assert_eq!((error.code, error.error_code.as_str()), (503, "INDEXER_LAG"));
+
+        // We take the last block before the given timestamp, or the first block if there is nothing before
+        let by_timestamp = types::query_params::BlockParams {
+            block_timestamp_nanos: Some(1600000000000000102.into()),
+            ..Default::default()
+        };
+        let block = get_block_from_params(&db.pool, &by_timestamp).await.unwrap();
+        assert_eq!(block.height, 102);
+        let too_early = types::query_params::BlockParams {
+            block_timestamp_nanos: Some(1.into()),
+            ..Default::default()
+        };
+        let block = get_block_from_params(&db.pool, &too_early).await.unwrap();
+        assert_eq!(block.height, 100);
+
+        let block = get_block_from_params(&db.pool, &Default::default()).await.unwrap();
+        assert_eq!(block.height, 103);
+    }
+
+    #[tokio::test]
+    async fn test_get_block_by_id_seeded() {
+        let db = init_seeded_db().await;
+
+        let block = get_block_by_id(&db.pool, "102").await.unwrap();
+        assert_eq!((block.height, block.timestamp), (102, 1600000000000000102));
+        let block = get_block_by_id(&db.pool, &get_seeded_block_hash(101))
+            .await
+            .unwrap();
+        assert_eq!(block.height, 101);
+        assert!(get_block_by_id(&db.pool, "500").await.is_err());
+        let error = get_block_by_id(&db.pool, "unknown_hash").await.unwrap_err();
+        assert_eq!(error.error_code, "BLOCK_NOT_FOUND");
+    }
+
+    #[tokio::test]
+    async fn test_get_blocks_by_heights_seeded() {
+        let db = init_seeded_db().await;
+
+        // 99 is before the first block, 500 is after the last one, 102 is requested twice
+        let blocks = get_blocks_by_heights(&db.pool, &[99, 101, 102, 102, 500])
+            .await
+            .unwrap();
+        let heights: Vec<u64> = blocks.iter().map(|block| block.height).collect();
+        assert_eq!(heights, vec![101, 102, 103]);
+    }
+
+    #[tokio::test]
+    async fn test_get_code_changes_seeded() {
+        let db = init_seeded_db().await;
+        let dao = near_primitives::types::AccountId::from_str("dao.near").unwrap();
+
+        // The redeployment of the same code at 101 is not the change
+        let changes = get_code_changes(&db.pool, &dao, 1600000000000000103, 10)
+            .await
+            .unwrap();
+        let heights: Vec<u64> = changes.iter().map(|change| change.block_height.0).collect();
+        assert_eq!(heights, vec![103, 100]);
+        assert_eq!(changes[0].block_hash, get_seeded_block_hash(103));
+        assert_eq!(
+            changes[0].previous_code_hash.as_deref(),
+            Some("11111111111111111111111111111111")
+        );
+        assert_eq!(
+            changes[0].transaction_hash.as_deref(),
+            Some("tx_dao_upgrade")
+        );
+        assert_eq!(changes[1].previous_code_hash, None);
+
+        let changes = get_code_changes(&db.pool, &dao, 1600000000000000102, 10)
+            .await
+            .unwrap();
+        assert_eq!(changes.len(), 1);
+
+        // The failed redeployment of nft.near did not change the code
+        let nft = near_primitives::types::AccountId::from_str("nft.near").unwrap();
+        let changes = get_code_changes(&db.pool, &nft, 1600000000000000103, 10)
+            .await
+            .unwrap();
+        assert_eq!(changes.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_does_account_exist_seeded() {
+        let db = init_seeded_db().await;
+        let alice = near_primitives::types::AccountId::from_str("alice.near").unwrap();
+        let bob = near_primitives::types::AccountId::from_str("bob.near").unwrap();
+        let carol = near_primitives::types::AccountId::from_str("carol.near").unwrap();
+
+        assert!(does_account_exist(&db.pool, &alice, 1600000000000000103).await.unwrap());
+        assert!(!does_account_exist(&db.pool, &alice, 1600000000000000099).await.unwrap());
+        assert!(does_account_exist(&db.pool, &bob, 1600000000000000100).await.unwrap());
+        assert!(!does_account_exist(&db.pool, &bob, 1600000000000000101).await.unwrap());
+        assert!(!does_account_exist(&db.pool, &carol, 1600000000000000103).await.unwrap());
+    }
+}
@@ -0,0 +1,177 @@
+//! `?debug=true` adds `debug_message` to the error responses: the underlying RPC or DB error behind
+//! the generic message of `RPC_ERROR` and `DB_ERROR`. It shows the internals (the node responses,
+//! the failed queries), so only the trusted clients get it: the requests with the admin token
+//! (`Authorization: Bearer <ADMIN_API_TOKEN>`) and the API keys from `DEBUG_API_KEY_IDS`.
+//! The keys are listed by `key_id` (the hash of the key, the same as in `/admin/usage`).
+//! The others get the usual responses, `debug=true` is ignored for them.
+use std::collections::BTreeSet;
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use futures::future::{ready, LocalBoxFuture, Ready};
+
+use crate::types::query_params::DebugParams;
+use crate::{api_keys, errors};
+
+/// Create it once and clone to all the workers, so they share the list of the debug keys
+#[derive(Clone, Default)]
+pub struct ErrorDebug {
+    /// `None` if the admin endpoints are not enabled
+    #[cfg(feature = "admin")]
+    admin_token: Option<std::sync::Arc<crate::modules::admin::AdminToken>>,
+    debug_key_ids: std::sync::Arc<std::sync::RwLock<BTreeSet<String>>>,
+}
+
+impl ErrorDebug {
+    #[cfg(feature = "admin")]
+    pub(crate) fn new(admin_token: Option<String>) -> Self {
+        Self {
+            admin_token: admin_token.map(|admin_token| {
+                std::sync::Arc::new(crate::modules::admin::AdminToken(admin_token))
+            }),
+            debug_key_ids: Default::default(),
+        }
+    }
+
+    /// Replaces the whole list, the anonymous requests never get the details
+    pub fn set_debug_key_ids(&self, key_ids: Vec<String>) {
+        *self
+            .debug_key_ids
+            .write()
+            .expect("debug key ids lock is poisoned") = key_ids
+            .into_iter()
+            .filter(|key_id| key_id != api_keys::ANONYMOUS_KEY_ID)
+            .collect();
+    }
+
+    fn is_trusted(&self, req: &ServiceRequest) -> bool {
+        #[cfg(feature = "admin")]
+        if let Some(admin_token) = &self.admin_token {
+            if crate::modules::admin::check_admin_token(req.request(), admin_token).is_ok() {
+                return true;
+            }
+        }
+        self.debug_key_ids
+            .read()
+            .expect("debug key ids lock is poisoned")
+            .contains(&api_keys::get_key_id(req.headers()))
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ErrorDebug
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = ErrorDebugMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ErrorDebugMiddleware {
+            service,
+            error_debug: self.clone(),
+        }))
+    }
+}
+
+pub struct ErrorDebugMiddleware<S> {
+    service: S,
+    error_debug: ErrorDebug,
+}
+
+impl<S, B> Service<ServiceRequest> for ErrorDebugMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        // The invalid values are rejected by `ValidatedQuery`
+        let is_debug = actix_web::web::Query::<DebugParams>::from_query(req.query_string())
+            .map_or(false, |params| params.debug == Some(true))
+            && self.error_debug.is_trusted(&req);
+
+        let response = self.service.call(req);
+        Box::pin(async move {
+            let response = response.await?;
+            if !is_debug {
+                return Ok(response.map_into_boxed_body());
+            }
+            let error = response
+                .response()
+                .error()
+                .and_then(|err| err.as_error::<errors::Error>())
+                .filter(|error| error.debug_message.is_some())
+                .cloned();
+            Ok(match error {
+                Some(error) => {
+                    let (req, _) = response.into_parts();
+                    ServiceResponse::new(req, error.build_response(true))
+                }
+                None => response.map_into_boxed_body(),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_error_debug() {
+        let error_debug = ErrorDebug::default();
+        let app =
+            actix_web::test::init_service(actix_web::App::new().wrap(error_debug.clone()).route(
+                "/balance",
+                actix_web::web::get().to(|| async {
+                    Err::<actix_web::HttpResponse, _>(errors::Error::from_error_kind(
+                        errors::ErrorKind::RPCError("UnknownBlock { block_reference }".to_string()),
+                    ))
+                }),
+            ))
+            .await;
+        let app = &app;
+        let debug_message = move |api_key: Option<&'static str>| async move {
+            let mut request = actix_web::test::TestRequest::get().uri("/balance?debug=true");
+            if let Some(api_key) = api_key {
+                request = request.insert_header((api_keys::API_KEY_HEADER, api_key));
+            }
+            let response = actix_web::test::call_service(app, request.to_request()).await;
+            assert_eq!(response.status(), 500);
+            let error: errors::Error = actix_web::test::read_body_json(response).await;
+            assert_eq!(error.error_code, "RPC_ERROR");
+            assert!(!error.message.contains("UnknownBlock"));
+            error.debug_message
+        };
+
+        assert_eq!(debug_message(None).await, None);
+        assert_eq!(debug_message(Some("support-key")).await, None);
+
+        error_debug
+            .set_debug_key_ids(vec![near_primitives::hash::hash(b"support-key").to_string()]);
+        assert_eq!(
+            debug_message(Some("support-key")).await.as_deref(),
+            Some("UnknownBlock { block_reference }")
+        );
+        assert_eq!(debug_message(Some("other-key")).await, None);
+
+        // Not asked
+        let request = actix_web::test::TestRequest::get()
+            .uri("/balance")
+            .insert_header((api_keys::API_KEY_HEADER, "support-key"))
+            .to_request();
+        let error: errors::Error =
+            actix_web::test::read_body_json(actix_web::test::call_service(app, request).await)
+                .await;
+        assert_eq!(error.debug_message, None);
+    }
+}
@@ -1,7 +1,9 @@
 use paperclip::actix::{api_v2_errors, Apiv2Schema};
 
 use near_jsonrpc_client::errors::JsonRpcError;
+use near_jsonrpc_primitives::types::config::RpcProtocolConfigError;
 use near_jsonrpc_primitives::types::query::RpcQueryError;
+use near_jsonrpc_primitives::types::validator::RpcValidatorError;
 
 #[derive(Debug, strum::EnumIter)]
 pub enum ErrorKind {
@@ -10,13 +12,83 @@ pub enum ErrorKind {
     InternalError(String),
     ContractError(String),
     RPCError(String),
+    /// Missing or wrong credentials
+    Unauthorized(String),
+    /// The client sent too many requests, it should wait before the next one
+    TooManyRequests {
+        message: String,
+        retry_after_secs: u64,
+    },
+    /// The server can't serve the requests for now (e.g. the upstream RPC is down)
+    Unavailable {
+        message: String,
+        retry_after_secs: u64,
+    },
+    /// Nothing changed since `If-Modified-Since` given by the client
+    NotModified,
+    AccountNotFound {
+        account_id: String,
+        block_timestamp: u64,
+    },
+    /// `block_id` is the height or the hash
+    BlockNotFound {
+        block_id: String,
+    },
+    /// The account has no contract, or the contract does not implement the standard
+    ContractNotFound {
+        contract_id: String,
+        block_height: u64,
+    },
+    /// RPC does not keep the state of this block anymore
+    BlockPruned {
+        block_height: u64,
+    },
+    /// The block is not indexed yet
+    IndexerLag {
+        block_height: u64,
+        last_block_height: u64,
+    },
+    /// The operator switched off the endpoints, see `route_toggles`
+    RouteDisabled {
+        path_prefix: String,
+    },
+    /// The deployment serves only the curated contracts, see `allow_list`
+    ContractNotAllowed {
+        contract_id: String,
+    },
 }
 
+/// The delay for `IndexerLag`: the indexer is usually a few blocks behind
+const INDEXER_LAG_RETRY_AFTER_SECS: u64 = 5;
+/// The delay for `RouteDisabled`: the incidents are not resolved in seconds
+const ROUTE_DISABLED_RETRY_AFTER_SECS: u64 = 60;
+/// The delay for the RPC node which can't be reached: the restarts and the failovers take a few seconds
+const RPC_UNAVAILABLE_RETRY_AFTER_SECS: u64 = 10;
+
 /// Instead of utilizing HTTP status codes to describe node errors (which often
 /// do not have a good analog), rich errors are returned using this object.
+///
+/// `error_code` is the stable string to branch on, one of
+/// `DB_ERROR`, `INVALID_INPUT`, `INTERNAL_ERROR`, `CONTRACT_ERROR`, `RPC_ERROR`, `UNAUTHORIZED`,
+/// `TOO_MANY_REQUESTS`, `UNAVAILABLE`, `NOT_MODIFIED`, `ACCOUNT_NOT_FOUND`, `BLOCK_NOT_FOUND`,
+/// `CONTRACT_NOT_FOUND`, `BLOCK_PRUNED`, `INDEXER_LAG`, `ROUTE_DISABLED`, `CONTRACT_NOT_ALLOWED`.
+/// New codes could be added later.
 #[api_v2_errors(
+    code = 304,
+    description = "`NOT_MODIFIED`, only for the requests with `If-Modified-Since`, without the body",
+    code = 400,
+    description = "`INVALID_INPUT`: the path or the query parameters could not be parsed",
+    code = 401,
+    description = "`UNAUTHORIZED`: missing or wrong API key, or the request signature",
+    code = 403,
+    description = "`CONTRACT_NOT_ALLOWED`: the contract is not in the allow-list of this deployment",
+    code = 429,
+    description = "`TOO_MANY_REQUESTS`: see `retry_after_secs`",
     code = 500,
-    description = "See the inner `code` value to get more details"
+    description = "See the inner `code` and `error_code` values to get more details",
+    code = 503,
+    description = "`UNAVAILABLE`, `INDEXER_LAG` or `ROUTE_DISABLED`: see `retry_after_secs`",
+    default_schema = Error,
 )]
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
 pub struct Error {
@@ -24,51 +96,266 @@ pub struct Error {
     /// equivalent to an HTTP status code.
     pub code: u32,
 
+    /// Stable machine-readable code, e.g. `ACCOUNT_NOT_FOUND`
+    #[serde(default)]
+    pub error_code: String,
+
     /// Message is a network-specific error message.
     pub message: String,
 
     /// An error is retriable if the same request may succeed if submitted
     /// again.
     pub retriable: bool,
+
+    /// How long to wait before retrying. Given for throttling (code 429)
+    /// and temporary unavailability (code 503), also sent as `Retry-After` header
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
+
+    /// The values the error is about, e.g. `account_id` for `ACCOUNT_NOT_FOUND`.
+    /// The numbers are given as strings, the same as in the responses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<std::collections::BTreeMap<String, String>>,
+
+    /// The underlying RPC or DB error behind the generic `message` of `RPC_ERROR` and `DB_ERROR`.
+    /// Given only with `?debug=true` to the admin and the debug API keys, see `error_debug`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debug_message: Option<String>,
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let retriable = if self.retriable { " (retriable)" } else { "" };
-        write!(f, "Error #{}{}: {}", self.code, retriable, self.message)
+        write!(
+            f,
+            "Error #{}{}: {}",
+            self.code,
+            retriable,
+            self.raw_message()
+        )
     }
 }
 
 impl Error {
+    fn new(code: u32, error_code: &str, message: String, retriable: bool) -> Self {
+        Self {
+            code,
+            error_code: error_code.to_string(),
+            message,
+            retriable,
+            retry_after_secs: None,
+            details: None,
+            debug_message: None,
+        }
+    }
+
+    /// The public message is generic, the details are kept for the logs and for `?debug=true`
+    fn with_debug_message(mut self, debug_message: String) -> Self {
+        self.debug_message = Some(debug_message);
+        self
+    }
+
+    /// The underlying error if the public message hides it, for the logs and the checks of the error text
+    pub(crate) fn raw_message(&self) -> &str {
+        self.debug_message.as_deref().unwrap_or(&self.message)
+    }
+
+    fn with_details(mut self, details: &[(&str, String)]) -> Self {
+        self.details = Some(
+            details
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect(),
+        );
+        self
+    }
+
+    fn with_retry_after(mut self, retry_after_secs: u64) -> Self {
+        self.retry_after_secs = Some(retry_after_secs);
+        self
+    }
+
     pub fn from_error_kind(err: ErrorKind) -> Self {
         match err {
-            ErrorKind::DBError(message) => Self {
-                code: 500,
-                message: format!("DB Error: {}", message),
-                retriable: true,
-            },
-            ErrorKind::InvalidInput(message) => Self {
-                code: 400,
-                message: format!("Invalid Input: {}", message),
-                retriable: false,
-            },
-            ErrorKind::InternalError(message) => Self {
-                code: 500,
-                message: format!("Internal Error: {}", message),
-                retriable: true,
-            },
-            ErrorKind::ContractError(message) => Self {
-                code: 500,
-                message: format!("Contract Error: {}", message),
-                retriable: true,
-            },
-            ErrorKind::RPCError(message) => Self {
-                code: 500,
-                message: format!("RPC error: {}", message),
-                retriable: true,
-            },
+            ErrorKind::DBError(message) => Self::new(
+                500,
+                "DB_ERROR",
+                "DB Error: the query has failed, please try again later".to_string(),
+                true,
+            )
+            .with_debug_message(message),
+            ErrorKind::InvalidInput(message) => Self::new(
+                400,
+                "INVALID_INPUT",
+                format!("Invalid Input: {}", message),
+                false,
+            ),
+            ErrorKind::InternalError(message) => Self::new(
+                500,
+                "INTERNAL_ERROR",
+                format!("Internal Error: {}", message),
+                true,
+            ),
+            ErrorKind::ContractError(message) => Self::new(
+                500,
+                "CONTRACT_ERROR",
+                format!("Contract Error: {}", message),
+                true,
+            ),
+            ErrorKind::RPCError(message) => Self::new(
+                500,
+                "RPC_ERROR",
+                "RPC error: the node could not answer the request, please try again later"
+                    .to_string(),
+                true,
+            )
+            .with_debug_message(message),
+            ErrorKind::Unauthorized(message) => Self::new(
+                401,
+                "UNAUTHORIZED",
+                format!("Unauthorized: {}", message),
+                false,
+            ),
+            ErrorKind::TooManyRequests {
+                message,
+                retry_after_secs,
+            } => Self::new(
+                429,
+                "TOO_MANY_REQUESTS",
+                format!("Too Many Requests: {}", message),
+                true,
+            )
+            .with_retry_after(retry_after_secs),
+            ErrorKind::Unavailable {
+                message,
+                retry_after_secs,
+            } => Self::new(
+                503,
+                "UNAVAILABLE",
+                format!("Service Unavailable: {}", message),
+                true,
+            )
+            .with_retry_after(retry_after_secs),
+            ErrorKind::NotModified => {
+                Self::new(304, "NOT_MODIFIED", "Not Modified".to_string(), false)
+            }
+            ErrorKind::AccountNotFound {
+                account_id,
+                block_timestamp,
+            } => Self::new(
+                400,
+                "ACCOUNT_NOT_FOUND",
+                format!(
+                    "Invalid Input: account_id {} does not exist at block_timestamp {}",
+                    account_id, block_timestamp
+                ),
+                false,
+            )
+            .with_details(&[
+                ("account_id", account_id),
+                ("block_timestamp_nanos", block_timestamp.to_string()),
+            ]),
+            ErrorKind::BlockNotFound { block_id } => Self::new(
+                400,
+                "BLOCK_NOT_FOUND",
+                format!("Invalid Input: block {} is not found", block_id),
+                false,
+            )
+            .with_details(&[("block_id", block_id)]),
+            ErrorKind::ContractNotFound {
+                contract_id,
+                block_height,
+            } => Self::new(
+                400,
+                "CONTRACT_NOT_FOUND",
+                format!(
+                    "Invalid Input: The account `{}` does not implement any suitable contract at block {}",
+                    contract_id, block_height
+                ),
+                false,
+            )
+            .with_details(&[
+                ("contract_account_id", contract_id),
+                ("block_height", block_height.to_string()),
+            ]),
+            ErrorKind::BlockPruned { block_height } => Self::new(
+                400,
+                "BLOCK_PRUNED",
+                format!(
+                    "Invalid Input: the state at block {} is not available anymore, try the later block",
+                    block_height
+                ),
+                false,
+            )
+            .with_details(&[("block_height", block_height.to_string())]),
+            ErrorKind::IndexerLag {
+                block_height,
+                last_block_height,
+            } => Self::new(
+                503,
+                "INDEXER_LAG",
+                format!(
+                    "Service Unavailable: block {} is not indexed yet, the last one is {}",
+                    block_height, last_block_height
+                ),
+                true,
+            )
+            .with_retry_after(INDEXER_LAG_RETRY_AFTER_SECS)
+            .with_details(&[
+                ("block_height", block_height.to_string()),
+                ("last_block_height", last_block_height.to_string()),
+            ]),
+            ErrorKind::RouteDisabled { path_prefix } => Self::new(
+                503,
+                "ROUTE_DISABLED",
+                format!(
+                    "Service Unavailable: {} endpoints are temporarily disabled by the operator",
+                    path_prefix
+                ),
+                true,
+            )
+            .with_retry_after(ROUTE_DISABLED_RETRY_AFTER_SECS)
+            .with_details(&[("path_prefix", path_prefix)]),
+            ErrorKind::ContractNotAllowed { contract_id } => Self::new(
+                403,
+                "CONTRACT_NOT_ALLOWED",
+                format!(
+                    "Forbidden: The contract `{}` is not served by this deployment",
+                    contract_id
+                ),
+                false,
+            )
+            .with_details(&[("contract_account_id", contract_id)]),
         }
     }
+
+    /// `with_debug_message` is for `?debug=true` of the trusted clients only, see `error_debug`
+    pub(crate) fn build_response(&self, with_debug_message: bool) -> actix_web::HttpResponse {
+        let mut error = self.clone();
+        if !with_debug_message {
+            error.debug_message = None;
+        }
+        let data = paperclip::actix::web::Json(error);
+        // The other errors keep 500 status for compatibility, the inner `code` tells the details.
+        // Auth and throttling are different: the proxies and HTTP clients know how to handle 401/403,
+        // and how to back off on 429/503
+        let mut response =
+            actix_web::HttpResponse::build(actix_web::ResponseError::status_code(self));
+        // 304 can't have the body
+        if self.code == 304 {
+            return response.finish();
+        }
+        if let Some(retry_after_secs) = self.retry_after_secs {
+            response.insert_header((
+                actix_web::http::header::RETRY_AFTER,
+                retry_after_secs.to_string(),
+            ));
+        }
+        if self.code == 429 {
+            response.insert_header(("X-RateLimit-Remaining", "0"));
+        }
+        response.json(data)
+    }
 }
 
 impl<T> From<T> for Error
@@ -81,15 +368,82 @@ where
 }
 
 impl actix_web::ResponseError for Error {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        match self.code {
+            304 => actix_web::http::StatusCode::NOT_MODIFIED,
+            401 => actix_web::http::StatusCode::UNAUTHORIZED,
+            403 => actix_web::http::StatusCode::FORBIDDEN,
+            429 => actix_web::http::StatusCode::TOO_MANY_REQUESTS,
+            503 => actix_web::http::StatusCode::SERVICE_UNAVAILABLE,
+            _ => actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
     fn error_response(&self) -> actix_web::HttpResponse {
-        let data = paperclip::actix::web::Json(self);
-        actix_web::HttpResponse::InternalServerError().json(data)
+        self.build_response(false)
+    }
+}
+
+/// Actix answers the malformed query with the plain text, we give `Error` with 400 status instead
+pub fn query_error_handler(
+    err: actix_web::error::QueryPayloadError,
+    _req: &actix_web::HttpRequest,
+) -> actix_web::Error {
+    bad_request(err)
+}
+
+/// The same as `query_error_handler`, for the path segments
+pub fn path_error_handler(
+    err: actix_web::error::PathError,
+    _req: &actix_web::HttpRequest,
+) -> actix_web::Error {
+    bad_request(err)
+}
+
+pub(crate) fn bad_request<E>(err: E) -> actix_web::Error
+where
+    E: std::fmt::Debug + std::fmt::Display + 'static,
+{
+    let error = Error::from_error_kind(ErrorKind::InvalidInput(err.to_string()));
+    actix_web::error::InternalError::from_response(
+        err,
+        actix_web::HttpResponse::BadRequest().json(error),
+    )
+    .into()
+}
+
+/// The node is down or the connection is broken: the request itself is fine, it could be retried later.
+/// The node's own errors stay `RPCError`
+fn rpc_error<E: std::fmt::Debug>(error: JsonRpcError<E>) -> ErrorKind {
+    match error {
+        JsonRpcError::TransportError(_) => ErrorKind::Unavailable {
+            message: "the RPC node can't be reached".to_string(),
+            retry_after_secs: RPC_UNAVAILABLE_RETRY_AFTER_SECS,
+        },
+        error => ErrorKind::RPCError(format!("{:#?}", error)),
     }
 }
 
 impl From<JsonRpcError<RpcQueryError>> for ErrorKind {
     fn from(error: JsonRpcError<RpcQueryError>) -> Self {
-        Self::RPCError(format!("{:#?}", error))
+        match error.handler_error() {
+            Some(RpcQueryError::GarbageCollectedBlock { block_height, .. }) => Self::BlockPruned {
+                block_height: *block_height,
+            },
+            _ => rpc_error(error),
+        }
+    }
+}
+
+impl From<JsonRpcError<RpcValidatorError>> for ErrorKind {
+    fn from(error: JsonRpcError<RpcValidatorError>) -> Self {
+        rpc_error(error)
+    }
+}
+
+impl From<JsonRpcError<RpcProtocolConfigError>> for ErrorKind {
+    fn from(error: JsonRpcError<RpcProtocolConfigError>) -> Self {
+        rpc_error(error)
     }
 }
 
@@ -104,3 +458,132 @@ impl From<near_primitives::account::id::ParseAccountError> for ErrorKind {
         Self::InternalError(format!("Could not parse account: {:#?}", error))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::ResponseError;
+
+    #[test]
+    fn test_throttling_error_response() {
+        let error = Error::from_error_kind(ErrorKind::TooManyRequests {
+            message: "limit is 10 requests per second".to_string(),
+            retry_after_secs: 3,
+        });
+        let response = error.error_response();
+        assert_eq!(response.status(), 429);
+        assert_eq!(
+            response
+                .headers()
+                .get(actix_web::http::header::RETRY_AFTER)
+                .unwrap(),
+            "3"
+        );
+        assert_eq!(
+            response.headers().get("X-RateLimit-Remaining").unwrap(),
+            "0"
+        );
+
+        let error = Error::from_error_kind(ErrorKind::DBError("timeout".to_string()));
+        let response = error.error_response();
+        assert_eq!(response.status(), 500);
+        assert!(!response
+            .headers()
+            .contains_key(actix_web::http::header::RETRY_AFTER));
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "code": 500,
+                "error_code": "DB_ERROR",
+                "message": "DB Error: the query has failed, please try again later",
+                "retriable": true,
+                "debug_message": "timeout",
+            })
+        );
+        assert_eq!(error.raw_message(), "timeout");
+    }
+
+    #[tokio::test]
+    async fn test_rpc_unavailable_error_response() {
+        // Nothing listens there
+        let rpc_client = near_jsonrpc_client::JsonRpcClient::connect("http://127.0.0.1:1");
+        let rpc_error = rpc_client
+            .call(near_jsonrpc_client::methods::query::RpcQueryRequest {
+                block_reference: near_primitives::types::BlockReference::Finality(
+                    near_primitives::types::Finality::Final,
+                ),
+                request: near_primitives::views::QueryRequest::ViewAccount {
+                    account_id: "alice.near".parse().unwrap(),
+                },
+            })
+            .await
+            .unwrap_err();
+        let error = Error::from(rpc_error);
+        assert_eq!(error.error_code, "UNAVAILABLE");
+        assert!(error.retriable);
+        let response = error.error_response();
+        assert_eq!(response.status(), 503);
+        assert_eq!(
+            response
+                .headers()
+                .get(actix_web::http::header::RETRY_AFTER)
+                .unwrap(),
+            "10"
+        );
+    }
+
+    #[test]
+    fn test_error_details() {
+        let error = Error::from_error_kind(ErrorKind::IndexerLag {
+            block_height: 105,
+            last_block_height: 103,
+        });
+        assert_eq!(error.error_response().status(), 503);
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "code": 503,
+                "error_code": "INDEXER_LAG",
+                "message": "Service Unavailable: block 105 is not indexed yet, the last one is 103",
+                "retriable": true,
+                "retry_after_secs": 5,
+                "details": {"block_height": "105", "last_block_height": "103"},
+            })
+        );
+
+        // The old clients' responses without the new fields are still parsed
+        let error: Error = serde_json::from_value(
+            serde_json::json!({"code": 400, "message": "Invalid Input: oops", "retriable": false}),
+        )
+        .unwrap();
+        assert_eq!((error.error_code.as_str(), error.details), ("", None));
+    }
+
+    #[actix_web::test]
+    async fn test_query_error_handler() {
+        let app = actix_web::test::init_service(
+            actix_web::App::new()
+                .app_data(actix_web::web::QueryConfig::default().error_handler(query_error_handler))
+                .route(
+                    "/block",
+                    actix_web::web::get().to(
+                        |_: actix_web::web::Query<crate::types::query_params::BlockParams>| async {
+                            actix_web::HttpResponse::Ok().finish()
+                        },
+                    ),
+                ),
+        )
+        .await;
+
+        let request = actix_web::test::TestRequest::get()
+            .uri("/block?block_height=latest")
+            .to_request();
+        let response = actix_web::test::call_service(&app, request).await;
+        assert_eq!(response.status(), 400);
+        let error: Error = actix_web::test::read_body_json(response).await;
+        assert_eq!(
+            (error.code, error.error_code.as_str()),
+            (400, "INVALID_INPUT")
+        );
+    }
+}
@@ -0,0 +1,343 @@
+//! Runs the export jobs created by `/accounts/{account_id}/exports`: builds the CSV or Parquet file
+//! and uploads it to the object storage, `/exports/{job_id}` gives the signed link to it.
+//! Every instance runs the worker, the jobs are taken from `export_jobs` with `SKIP LOCKED`,
+//! so each job is processed once. If the instance dies, the job is taken again after `STALE_JOB_AFTER`.
+//! The file is built in memory, that's why the number of rows is limited.
+use std::collections::HashMap;
+
+use crate::modules::coin;
+use crate::modules::exports::{data_provider, schemas};
+use crate::tabular::{ColumnType, Table};
+use crate::{data_source, db_helpers, errors, object_storage, types, BigDecimal, LOGGER_MSG};
+
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+/// The running jobs are touched after every page, the silent ones are abandoned
+const STALE_JOB_AFTER: std::time::Duration = std::time::Duration::from_secs(600);
+const EXPORT_PAGE_SIZE: u32 = 1000;
+const MAX_EXPORT_ROWS: usize = 1_000_000;
+const MAX_EXPORT_CONTRACTS: u32 = 1000;
+
+const HISTORY_COLUMNS: &[(&str, ColumnType)] = &[
+    ("block_timestamp_nanos", ColumnType::UInt64),
+    ("contract_account_id", ColumnType::Text),
+    ("symbol", ColumnType::Text),
+    ("decimals", ColumnType::UInt64),
+    ("cause", ColumnType::Text),
+    ("status", ColumnType::Text),
+    ("involved_account_id", ColumnType::Text),
+    ("signer_account_id", ColumnType::Text),
+    ("predecessor_account_id", ColumnType::Text),
+    ("delta_balance", ColumnType::Text),
+    ("balance", ColumnType::Text),
+];
+const TAX_REPORT_COLUMNS: &[(&str, ColumnType)] = &[
+    ("price_usd", ColumnType::Text),
+    ("value_usd", ColumnType::Text),
+];
+
+/// Spawns the worker, requires Tokio runtime
+pub(crate) fn start(
+    data_source: data_source::DataSourceRef,
+    pool_aux: sqlx::Pool<sqlx::Postgres>,
+    storage: object_storage::ObjectStorage,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            // The queue is drained before waiting again
+            loop {
+                match run_next_job(&data_source, &pool_aux, &storage).await {
+                    Ok(true) => continue,
+                    Ok(false) => break,
+                    Err(err) => {
+                        tracing::warn!(target: LOGGER_MSG, "Failed to run the export job: {}", err);
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Returns `false` if there are no jobs to run
+async fn run_next_job(
+    data_source: &data_source::DataSourceRef,
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    storage: &object_storage::ObjectStorage,
+) -> crate::Result<bool> {
+    let now = now_nanos();
+    let stale_before = now.saturating_sub(STALE_JOB_AFTER.as_nanos() as u64);
+    let job = match data_provider::take_next_export_job(pool_aux, now, stale_before).await? {
+        Some(job) => job,
+        None => return Ok(false),
+    };
+    let result = async {
+        let file = export(data_source, pool_aux, &job)
+            .await?
+            .write(job.format)?;
+        storage
+            .put(&job.object_key(), file, job.format.content_type())
+            .await
+    }
+    .await;
+    let error = match result {
+        Ok(()) => None,
+        Err(err) => {
+            tracing::warn!(
+                target: LOGGER_MSG,
+                "Export job {} has failed: {}",
+                job.job_id.0,
+                err
+            );
+            Some(err.message)
+        }
+    };
+    data_provider::finish_export_job(pool_aux, job.job_id.0, error.as_deref(), now_nanos()).await?;
+    Ok(true)
+}
+
+async fn export(
+    data_source: &data_source::DataSourceRef,
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    job: &schemas::ExportJob,
+) -> crate::Result<Table> {
+    let block = data_source
+        .get_block_from_params(&types::query_params::BlockParams {
+            block_timestamp_nanos: job.to_timestamp_nanos,
+            block_height: None,
+        })
+        .await?;
+    match job.kind {
+        schemas::ExportKind::HoldersSnapshot => {
+            export_holders(data_source, pool_aux, job, &block).await
+        }
+        schemas::ExportKind::FullHistory | schemas::ExportKind::TaxReport => {
+            export_history(data_source, pool_aux, job, &block).await
+        }
+    }
+}
+
+async fn export_holders(
+    data_source: &data_source::DataSourceRef,
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    job: &schemas::ExportJob,
+    block: &db_helpers::Block,
+) -> crate::Result<Table> {
+    let mut table = Table::new(
+        vec![
+            ("account_id", ColumnType::Text),
+            ("balance", ColumnType::Text),
+        ],
+        MAX_EXPORT_ROWS,
+    );
+    let mut after_account_id: Option<near_primitives::types::AccountId> = None;
+    loop {
+        let holders = data_source
+            .get_ft_holders(
+                &job.account_id.0,
+                block.timestamp,
+                after_account_id.as_ref(),
+                EXPORT_PAGE_SIZE,
+            )
+            .await?;
+        for holder in &holders {
+            table.push_row(vec![
+                holder.account_id.0.to_string(),
+                holder.balance.0.to_string(),
+            ])?;
+        }
+        data_provider::touch_export_job(pool_aux, job.job_id.0, now_nanos()).await?;
+        if holders.len() < EXPORT_PAGE_SIZE as usize {
+            return Ok(table);
+        }
+        after_account_id = holders.last().map(|holder| holder.account_id.0.clone());
+    }
+}
+
+/// NEAR changes go first, then the FT contracts by their account id.
+/// The changes of each coin are ordered from the recent ones
+async fn export_history(
+    data_source: &data_source::DataSourceRef,
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    job: &schemas::ExportJob,
+    block: &db_helpers::Block,
+) -> crate::Result<Table> {
+    let account_id = &job.account_id.0;
+    let from_timestamp = job.from_timestamp_nanos.map_or(0, |timestamp| timestamp.0);
+    let with_prices = job.kind == schemas::ExportKind::TaxReport;
+    let mut columns = HISTORY_COLUMNS.to_vec();
+    if with_prices {
+        columns.extend_from_slice(TAX_REPORT_COLUMNS);
+    }
+    let mut table = Table::new(columns, MAX_EXPORT_ROWS);
+    let mut prices = DailyPrices::default();
+
+    let from_block = db_helpers::Block {
+        timestamp: from_timestamp,
+        height: 0,
+        hash: String::new(),
+    };
+    let contracts = data_source
+        .get_changed_ft_contracts(account_id, &from_block, block, MAX_EXPORT_CONTRACTS + 1)
+        .await?;
+    if contracts.len() > MAX_EXPORT_CONTRACTS as usize {
+        return Err(errors::ErrorKind::InvalidInput(format!(
+            "the account has the history of more than {} FT contracts",
+            MAX_EXPORT_CONTRACTS
+        ))
+        .into());
+    }
+    let coins = std::iter::once(None).chain(
+        contracts
+            .into_iter()
+            .map(|contract| Some(contract.contract_id)),
+    );
+
+    for contract_id in coins {
+        let mut pagination = types::query_params::HistoryPagination {
+            block_height: block.height,
+            block_timestamp: block.timestamp,
+            before_event: None,
+            limit: EXPORT_PAGE_SIZE,
+        };
+        loop {
+            let page = match &contract_id {
+                None => {
+                    data_source
+                        .get_near_history(account_id, &pagination)
+                        .await?
+                }
+                Some(contract_id) => {
+                    data_source
+                        .get_coin_history(contract_id, account_id, &pagination)
+                        .await?
+                }
+            };
+            let mut is_last_page = page.len() < EXPORT_PAGE_SIZE as usize;
+            pagination.before_event = page.last().map(|(_, index)| *index);
+            for (item, _) in page {
+                if item.block_timestamp_nanos.0 <= from_timestamp {
+                    is_last_page = true;
+                    break;
+                }
+                let mut row = history_row(contract_id.as_ref(), &item);
+                if with_prices {
+                    let price = prices
+                        .get(
+                            data_source,
+                            contract_id.as_ref(),
+                            item.block_timestamp_nanos.0,
+                        )
+                        .await?;
+                    row.extend(price_columns(&item, price.as_ref())?);
+                }
+                table.push_row(row)?;
+            }
+            data_provider::touch_export_job(pool_aux, job.job_id.0, now_nanos()).await?;
+            if is_last_page {
+                break;
+            }
+        }
+    }
+    Ok(table)
+}
+
+fn history_row(
+    contract_id: Option<&near_primitives::types::AccountId>,
+    item: &coin::schemas::HistoryItem,
+) -> Vec<String> {
+    let optional_account = |account_id: &Option<types::AccountId>| {
+        account_id
+            .as_ref()
+            .map(|account_id| account_id.0.to_string())
+            .unwrap_or_default()
+    };
+    vec![
+        item.block_timestamp_nanos.0.to_string(),
+        contract_id
+            .map(|contract_id| contract_id.to_string())
+            .unwrap_or_default(),
+        item.coin_metadata.symbol.clone(),
+        item.coin_metadata.decimals.to_string(),
+        String::from(item.cause.clone()),
+        item.status.clone(),
+        optional_account(&item.involved_account_id),
+        optional_account(&item.signer_account_id),
+        optional_account(&item.predecessor_account_id),
+        item.delta_balance.0.to_string(),
+        item.balance.0.to_string(),
+    ]
+}
+
+/// `price_usd` and the signed `value_usd` of `delta_balance`, empty if there is no price
+fn price_columns(
+    item: &coin::schemas::HistoryItem,
+    price_usd: Option<&BigDecimal>,
+) -> crate::Result<Vec<String>> {
+    let price_usd = match price_usd {
+        Some(price_usd) => price_usd,
+        None => return Ok(vec![String::new(), String::new()]),
+    };
+    let value_usd = coin::schemas::value_usd(
+        item.delta_balance.0.unsigned_abs(),
+        item.coin_metadata.decimals,
+        price_usd,
+    )?;
+    let value_usd = if item.delta_balance.0 < 0 {
+        -value_usd
+    } else {
+        value_usd
+    };
+    Ok(vec![price_usd.to_string(), value_usd.to_string()])
+}
+
+/// The close USD price of the day, looked up once per coin and day
+#[derive(Default)]
+struct DailyPrices {
+    prices: HashMap<(Option<near_primitives::types::AccountId>, u64), Option<BigDecimal>>,
+}
+
+impl DailyPrices {
+    /// `None` if the coin had no price samples that day
+    async fn get(
+        &mut self,
+        data_source: &data_source::DataSourceRef,
+        contract_id: Option<&near_primitives::types::AccountId>,
+        timestamp: u64,
+    ) -> crate::Result<Option<BigDecimal>> {
+        let day_nanos = coin::schemas::PriceBucket::Day.duration_nanos();
+        let day_start = timestamp / day_nanos * day_nanos;
+        let key = (contract_id.cloned(), day_start);
+        if let Some(price) = self.prices.get(&key) {
+            return Ok(price.clone());
+        }
+        let price_contract_id = match contract_id {
+            Some(contract_id) => contract_id.clone(),
+            None => coin::NEAR_PRICE_CONTRACT_ID
+                .parse()
+                .expect("wrap.near is a valid account id"),
+        };
+        let price = data_source
+            .get_token_price_history(
+                &price_contract_id,
+                &coin::schemas::PriceBucket::Day,
+                day_start + day_nanos - 1,
+                1,
+            )
+            .await?
+            .into_iter()
+            .next()
+            .filter(|point| point.timestamp_nanos.0 == day_start)
+            .and_then(|point| point.close.parse::<BigDecimal>().ok());
+        self.prices.insert(key, price.clone());
+        Ok(price)
+    }
+}
+
+fn now_nanos() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or_default()
+}
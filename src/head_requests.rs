@@ -0,0 +1,161 @@
+//! HEAD requests to all the GET routes, for the monitoring and the CDNs which validate their copies.
+//! HEAD is served as GET, the HTTP codec drops the body but keeps `Content-Length`.
+//! `ETag` is the hash of the body, it's given both for GET and HEAD, so they could be compared;
+//! `If-None-Match` with the same tag gives 304.
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::{header, Method, StatusCode};
+use futures::future::{ready, LocalBoxFuture, Ready};
+use sha2::Digest;
+
+use crate::errors;
+
+fn etag(body: &[u8]) -> String {
+    format!("\"{}\"", hex::encode(&sha2::Sha256::digest(body)[..16]))
+}
+
+/// `If-None-Match: "a", W/"b"` or `*`
+fn matches_if_none_match(if_none_match: &str, etag: &str) -> bool {
+    if_none_match.split(',').any(|tag| {
+        let tag = tag.trim();
+        tag == "*" || tag.trim_start_matches("W/") == etag
+    })
+}
+
+#[derive(Clone, Default)]
+pub struct HeadRequests;
+
+impl<S, B> Transform<S, ServiceRequest> for HeadRequests
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = HeadRequestsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(HeadRequestsMiddleware { service }))
+    }
+}
+
+pub struct HeadRequestsMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for HeadRequestsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        if req.method() == Method::HEAD {
+            req.head_mut().method = Method::GET;
+        } else if req.method() != Method::GET {
+            let response = self.service.call(req);
+            return Box::pin(async move { Ok(response.await?.map_into_boxed_body()) });
+        }
+        let if_none_match = req
+            .headers()
+            .get(header::IF_NONE_MATCH)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+
+        let response = self.service.call(req);
+        Box::pin(async move {
+            let response = response.await?;
+            // The streamed exports are not buffered to get the hash
+            if response.status() != StatusCode::OK
+                || matches!(
+                    response.response().body().size(),
+                    actix_web::body::BodySize::Stream
+                )
+            {
+                return Ok(response.map_into_boxed_body());
+            }
+            let (req, response) = response.into_parts();
+            let (mut response, body) = response.into_parts();
+            let body = actix_web::body::to_bytes(body).await.map_err(|err| {
+                let err: Box<dyn std::error::Error> = err.into();
+                errors::Error::from_error_kind(errors::ErrorKind::InternalError(format!(
+                    "Could not read the response body: {}",
+                    err
+                )))
+            })?;
+            let etag = etag(&body);
+            if let Ok(value) = header::HeaderValue::from_str(&etag) {
+                response.headers_mut().insert(header::ETAG, value);
+            }
+            if let Some(if_none_match) = if_none_match {
+                if matches_if_none_match(&if_none_match, &etag) {
+                    let mut not_modified = actix_web::HttpResponse::NotModified();
+                    for name in [header::ETAG, header::LAST_MODIFIED, header::CACHE_CONTROL] {
+                        if let Some(value) = response.headers().get(&name) {
+                            not_modified.insert_header((name, value.clone()));
+                        }
+                    }
+                    return Ok(ServiceResponse::new(req, not_modified.finish()));
+                }
+            }
+            Ok(ServiceResponse::new(
+                req,
+                response.set_body(body).map_into_boxed_body(),
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_head_requests() {
+        let app = actix_web::test::init_service(actix_web::App::new().wrap(HeadRequests).route(
+            "/block",
+            actix_web::web::get().to(|| async {
+                actix_web::HttpResponse::Ok().json(serde_json::json!({"block_height": "100"}))
+            }),
+        ))
+        .await;
+
+        let request = actix_web::test::TestRequest::get()
+            .uri("/block")
+            .to_request();
+        let response = actix_web::test::call_service(&app, request).await;
+        assert_eq!(response.status(), 200);
+        let etag = response.headers().get(header::ETAG).unwrap().clone();
+
+        let request = actix_web::test::TestRequest::default()
+            .method(Method::HEAD)
+            .uri("/block")
+            .to_request();
+        let response = actix_web::test::call_service(&app, request).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers().get(header::ETAG), Some(&etag));
+
+        let request = actix_web::test::TestRequest::default()
+            .method(Method::HEAD)
+            .uri("/block")
+            .insert_header((header::IF_NONE_MATCH, etag.clone()))
+            .to_request();
+        let response = actix_web::test::call_service(&app, request).await;
+        assert_eq!(response.status(), 304);
+        assert_eq!(response.headers().get(header::ETAG), Some(&etag));
+
+        let request = actix_web::test::TestRequest::get()
+            .uri("/block")
+            .insert_header((header::IF_NONE_MATCH, "\"other\""))
+            .to_request();
+        let response = actix_web::test::call_service(&app, request).await;
+        assert_eq!(response.status(), 200);
+    }
+}
@@ -0,0 +1,78 @@
+//! `action_receipt_actions.action_kind` and `args` in NEAR Indexer for Explorer format
+use near_lake_framework::near_indexer_primitives::views;
+
+/// `FunctionCall` becomes `FUNCTION_CALL`
+pub(crate) fn action_kind(variant: &str) -> String {
+    let mut kind = String::with_capacity(variant.len() + 4);
+    for (i, char) in variant.chars().enumerate() {
+        if char.is_uppercase() && i > 0 {
+            kind.push('_');
+        }
+        kind.push(char.to_ascii_uppercase());
+    }
+    kind
+}
+
+/// `(action_kind, args)`. The view is externally tagged: `"CreateAccount"` or `{"Transfer": {...}}`.
+/// Function call args are stored both as given (`args_base64`) and parsed (`args_json`),
+/// if they are JSON; the sales and the staking queries look into `args_json`
+pub(crate) fn action_kind_and_args(action: &views::ActionView) -> (String, serde_json::Value) {
+    match serde_json::to_value(action).unwrap_or_default() {
+        serde_json::Value::String(variant) => (action_kind(&variant), serde_json::json!({})),
+        serde_json::Value::Object(object) => match object.into_iter().next() {
+            Some((variant, args)) => {
+                let args = if variant == "FunctionCall" {
+                    function_call_args(args)
+                } else {
+                    args
+                };
+                (action_kind(&variant), args)
+            }
+            None => (String::new(), serde_json::json!({})),
+        },
+        _ => (String::new(), serde_json::json!({})),
+    }
+}
+
+fn function_call_args(mut args: serde_json::Value) -> serde_json::Value {
+    if let Some(object) = args.as_object_mut() {
+        if let Some(args_base64) = object.remove("args") {
+            let args_json = args_base64
+                .as_str()
+                .and_then(|args_base64| base64::decode(args_base64).ok())
+                .and_then(|bytes| serde_json::from_slice::<serde_json::Value>(&bytes).ok());
+            if let Some(args_json) = args_json {
+                object.insert("args_json".to_string(), args_json);
+            }
+            object.insert("args_base64".to_string(), args_base64);
+        }
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_function_call_args() {
+        assert_eq!(action_kind("FunctionCall"), "FUNCTION_CALL");
+        assert_eq!(action_kind("CreateAccount"), "CREATE_ACCOUNT");
+
+        let args = function_call_args(serde_json::json!({
+            "method_name": "buy",
+            "args": base64::encode(r#"{"nft_contract_id":"nft.near","token_id":"1"}"#),
+            "gas": 100,
+            "deposit": "5",
+        }));
+        assert_eq!(args["method_name"], "buy");
+        assert_eq!(args["args_json"]["token_id"], "1");
+        assert!(args["args_base64"].is_string());
+
+        let args = function_call_args(serde_json::json!({
+            "method_name": "binary",
+            "args": base64::encode([0u8, 1, 2]),
+        }));
+        assert!(args.get("args_json").is_none());
+    }
+}
@@ -0,0 +1,215 @@
+//! NEP-297 events of NEP-141 and NEP-171 contracts: the logs `EVENT_JSON:{...}`.
+//! Each event could contain several entries, each entry becomes the separate row
+//! (and NFT entries become the row per token), the same as in NEAR Indexer for Explorer.
+
+const EVENT_LOG_PREFIX: &str = "EVENT_JSON:";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EventKind {
+    Mint,
+    Transfer,
+    Burn,
+}
+
+impl EventKind {
+    /// `ft_event_kind`, `nft_event_kind` DB enums
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Mint => "MINT",
+            EventKind::Transfer => "TRANSFER",
+            EventKind::Burn => "BURN",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FtEvent {
+    pub kind: EventKind,
+    /// Empty for `MINT`
+    pub old_owner_id: String,
+    /// Empty for `BURN`
+    pub new_owner_id: String,
+    pub amount: String,
+    pub memo: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct NftEvent {
+    pub kind: EventKind,
+    pub token_id: String,
+    /// Empty for `MINT`
+    pub old_owner_id: String,
+    /// Empty for `BURN`
+    pub new_owner_id: String,
+    pub authorized_id: String,
+    pub memo: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Event {
+    Ft(Vec<FtEvent>),
+    Nft(Vec<NftEvent>),
+}
+
+#[derive(serde::Deserialize)]
+struct EventLog {
+    standard: String,
+    event: String,
+    data: serde_json::Value,
+}
+
+#[derive(serde::Deserialize)]
+struct FtEventData {
+    #[serde(default)]
+    owner_id: String,
+    #[serde(default)]
+    old_owner_id: String,
+    #[serde(default)]
+    new_owner_id: String,
+    amount: String,
+    memo: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct NftEventData {
+    #[serde(default)]
+    owner_id: String,
+    #[serde(default)]
+    old_owner_id: String,
+    #[serde(default)]
+    new_owner_id: String,
+    token_ids: Vec<String>,
+    authorized_id: Option<String>,
+    memo: Option<String>,
+}
+
+/// `None` if the log is not the event, or it's the event of the other standard,
+/// or it doesn't follow the standard
+pub(crate) fn parse_event(log: &str) -> Option<Event> {
+    let event_log: EventLog =
+        serde_json::from_str(log.trim().strip_prefix(EVENT_LOG_PREFIX)?).ok()?;
+    match (event_log.standard.as_str(), event_log.event.as_str()) {
+        ("nep141", event) => {
+            let kind = event_kind(event.strip_prefix("ft_")?)?;
+            let entries: Vec<FtEventData> = serde_json::from_value(event_log.data).ok()?;
+            Some(Event::Ft(
+                entries
+                    .into_iter()
+                    .map(|entry| {
+                        let (old_owner_id, new_owner_id) =
+                            owners(kind, entry.owner_id, entry.old_owner_id, entry.new_owner_id);
+                        FtEvent {
+                            kind,
+                            old_owner_id,
+                            new_owner_id,
+                            amount: entry.amount,
+                            memo: entry.memo.unwrap_or_default(),
+                        }
+                    })
+                    .collect(),
+            ))
+        }
+        ("nep171", event) => {
+            let kind = event_kind(event.strip_prefix("nft_")?)?;
+            let entries: Vec<NftEventData> = serde_json::from_value(event_log.data).ok()?;
+            let mut events = vec![];
+            for entry in entries {
+                let (old_owner_id, new_owner_id) =
+                    owners(kind, entry.owner_id, entry.old_owner_id, entry.new_owner_id);
+                for token_id in entry.token_ids {
+                    events.push(NftEvent {
+                        kind,
+                        token_id,
+                        old_owner_id: old_owner_id.clone(),
+                        new_owner_id: new_owner_id.clone(),
+                        authorized_id: entry.authorized_id.clone().unwrap_or_default(),
+                        memo: entry.memo.clone().unwrap_or_default(),
+                    });
+                }
+            }
+            Some(Event::Nft(events))
+        }
+        _ => None,
+    }
+}
+
+fn event_kind(event: &str) -> Option<EventKind> {
+    match event {
+        "mint" => Some(EventKind::Mint),
+        "transfer" => Some(EventKind::Transfer),
+        "burn" => Some(EventKind::Burn),
+        _ => None,
+    }
+}
+
+/// Mint and burn have only `owner_id`
+fn owners(
+    kind: EventKind,
+    owner_id: String,
+    old_owner_id: String,
+    new_owner_id: String,
+) -> (String, String) {
+    match kind {
+        EventKind::Mint => (String::new(), owner_id),
+        EventKind::Transfer => (old_owner_id, new_owner_id),
+        EventKind::Burn => (owner_id, String::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_event() {
+        let log = r#"EVENT_JSON:{"standard":"nep141","version":"1.0.0","event":"ft_transfer","data":[{"old_owner_id":"alice.near","new_owner_id":"bob.near","amount":"300","memo":"thanks"}]}"#;
+        assert_eq!(
+            parse_event(log),
+            Some(Event::Ft(vec![FtEvent {
+                kind: EventKind::Transfer,
+                old_owner_id: "alice.near".to_string(),
+                new_owner_id: "bob.near".to_string(),
+                amount: "300".to_string(),
+                memo: "thanks".to_string(),
+            }]))
+        );
+
+        let log = r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_mint","data":[{"owner_id":"alice.near","token_ids":["1","2"]}]}"#;
+        let events = match parse_event(log) {
+            Some(Event::Nft(events)) => events,
+            event => panic!("unexpected event {:?}", event),
+        };
+        let events: Vec<_> = events
+            .iter()
+            .map(|event| {
+                (
+                    event.kind,
+                    event.token_id.as_str(),
+                    event.old_owner_id.as_str(),
+                    event.new_owner_id.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (EventKind::Mint, "1", "", "alice.near"),
+                (EventKind::Mint, "2", "", "alice.near")
+            ]
+        );
+
+        assert_eq!(
+            parse_event("Transfer 300 from alice.near to bob.near"),
+            None
+        );
+        // The other standards and the broken events are skipped
+        assert_eq!(
+            parse_event(r#"EVENT_JSON:{"standard":"nep245","event":"mt_mint","data":[]}"#),
+            None
+        );
+        assert_eq!(
+            parse_event(r#"EVENT_JSON:{"standard":"nep141","event":"ft_mint","data":[{}]}"#),
+            None
+        );
+    }
+}
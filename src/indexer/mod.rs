@@ -0,0 +1,441 @@
+//! The built-in mini-indexer: reads the blocks from NEAR Lake (S3) and writes the subset of
+//! NEAR Indexer for Explorer tables this API reads: `blocks`, `transactions`, `execution_outcomes`,
+//! `action_receipt_actions`, `account_changes` and NEP-141/NEP-171 events.
+//! Our own `nft_owner_counts` are kept up to date as well, they speed up the NFT overview.
+//! The balances DB (`balance_changes`) is not filled, the endpoints which need it still require
+//! `DATABASE_URL_BALANCES` pointing to the real one.
+//!
+//! The whole block is written in one DB transaction, so after the restart
+//! we continue from the next block after the last written one.
+//! AWS credentials are taken from the usual `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` env variables.
+//! With `kafka` or `nats` feature, the events of each block could be published to the broker too, see `sink`.
+use near_lake_framework::near_indexer_primitives::{views, IndexerShard, StreamerMessage};
+
+use crate::{config, db_helpers, errors};
+
+mod actions;
+mod events;
+mod nft_counts;
+#[cfg(any(feature = "kafka", feature = "nats"))]
+mod sink;
+
+/// If the streamer stops or the block could not be written, we start again after the pause
+const RESTART_DELAY: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Never returns, unless there is nothing to start from. Run it in the separate task, e.g. with `tokio::spawn`.
+/// `pool` is the indexer DB, the missing tables are created.
+/// Run it on the one instance only
+pub async fn run(pool: sqlx::Pool<sqlx::Postgres>, config: config::IndexerConfig) {
+    // The same subset of the schema the tests use
+    if let Err(err) = sqlx::Executor::execute(
+        &pool,
+        include_str!("../../tests/fixtures/indexer_schema.sql"),
+    )
+    .await
+    {
+        tracing::error!(
+            target: crate::LOGGER_MSG,
+            "Indexer is stopped, failed to create the tables: {}",
+            err
+        );
+        return;
+    }
+    #[cfg(any(feature = "kafka", feature = "nats"))]
+    let sink = match &config.event_sink {
+        Some(sink_config) => match sink::EventSink::connect(sink_config).await {
+            Ok(sink) => Some(sink),
+            Err(err) => {
+                tracing::error!(
+                    target: crate::LOGGER_MSG,
+                    "Indexer is stopped, failed to connect to the event sink: {}",
+                    err
+                );
+                return;
+            }
+        },
+        None => None,
+    };
+    #[cfg(not(any(feature = "kafka", feature = "nats")))]
+    if config.event_sink.is_some() {
+        tracing::warn!(
+            target: crate::LOGGER_MSG,
+            "EVENT_SINK_URL is ignored, the server is built without `kafka` and `nats` features"
+        );
+    }
+
+    loop {
+        let start_block_height = match get_start_block_height(&pool, &config).await {
+            Ok(Some(block_height)) => block_height,
+            Ok(None) => {
+                tracing::error!(
+                    target: crate::LOGGER_MSG,
+                    "Indexer is stopped: the DB is empty and INDEXER_START_BLOCK_HEIGHT is not set"
+                );
+                return;
+            }
+            Err(err) => {
+                tracing::warn!(
+                    target: crate::LOGGER_MSG,
+                    "Indexer failed to get the last block: {}",
+                    err
+                );
+                tokio::time::sleep(RESTART_DELAY).await;
+                continue;
+            }
+        };
+        let lake_config = match lake_config(&config.network, start_block_height) {
+            Ok(lake_config) => lake_config,
+            Err(err) => {
+                tracing::error!(target: crate::LOGGER_MSG, "Indexer is stopped: {}", err);
+                return;
+            }
+        };
+        tracing::info!(
+            target: crate::LOGGER_MSG,
+            "Indexer starts from block {} on {}",
+            start_block_height,
+            config.network
+        );
+
+        let (streamer_handle, mut messages) = near_lake_framework::streamer(lake_config);
+        while let Some(message) = messages.recv().await {
+            let block_height = message.block.header.height;
+            #[cfg(any(feature = "kafka", feature = "nats"))]
+            let stored = store_block(&pool, sink.as_ref(), &message).await;
+            #[cfg(not(any(feature = "kafka", feature = "nats")))]
+            let stored = store_block(&pool, &message).await;
+            if let Err(err) = stored {
+                tracing::warn!(
+                    target: crate::LOGGER_MSG,
+                    "Indexer failed to write block {}: {}",
+                    block_height,
+                    err
+                );
+                break;
+            }
+        }
+        drop(messages);
+        streamer_handle.abort();
+        tokio::time::sleep(RESTART_DELAY).await;
+    }
+}
+
+fn lake_config(
+    network: &str,
+    start_block_height: u64,
+) -> crate::Result<near_lake_framework::LakeConfig> {
+    let builder = near_lake_framework::LakeConfigBuilder::default();
+    let builder = match network {
+        "mainnet" => builder.mainnet(),
+        "testnet" => builder.testnet(),
+        _ => {
+            return Err(errors::ErrorKind::InvalidInput(format!(
+                "unknown NEAR Lake network {}, expected mainnet or testnet",
+                network
+            ))
+            .into())
+        }
+    };
+    builder
+        .start_block_height(start_block_height)
+        .build()
+        .map_err(|err| errors::ErrorKind::InternalError(err.to_string()).into())
+}
+
+/// The next block after the last written one, or the configured one for the empty DB
+async fn get_start_block_height(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    config: &config::IndexerConfig,
+) -> crate::Result<Option<u64>> {
+    let has_blocks: (bool,) = sqlx::query_as("SELECT EXISTS (SELECT 1 FROM blocks)")
+        .fetch_one(pool)
+        .await
+        .map_err(|err| errors::ErrorKind::DBError(err.to_string()))?;
+    if !has_blocks.0 {
+        return Ok(config.start_block_height);
+    }
+    let block = db_helpers::get_last_block(pool).await?;
+    Ok(Some(block.height + 1))
+}
+
+async fn store_block(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    #[cfg(any(feature = "kafka", feature = "nats"))] sink: Option<&sink::EventSink>,
+    message: &StreamerMessage,
+) -> crate::Result<()> {
+    let mut tx = pool.begin().await.map_err(db_error)?;
+    let header = &message.block.header;
+    sqlx::query(
+        r"INSERT INTO blocks (block_height, block_hash, prev_block_hash, block_timestamp, total_supply, gas_price, author_account_id)
+          VALUES ($1::numeric(20, 0), $2, $3, $4::numeric(20, 0), $5::numeric(45, 0), $6::numeric(45, 0), $7)
+          ON CONFLICT DO NOTHING",
+    )
+    .bind(header.height.to_string())
+    .bind(header.hash.to_string())
+    .bind(header.prev_hash.to_string())
+    .bind(header.timestamp_nanosec.to_string())
+    .bind(header.total_supply.to_string())
+    .bind(header.gas_price.to_string())
+    .bind(message.block.author.to_string())
+    .execute(&mut tx)
+    .await
+    .map_err(db_error)?;
+
+    let mut account_changes_index = 0;
+    for shard in &message.shards {
+        store_chunk(&mut tx, message, shard).await?;
+        store_execution_outcomes(&mut tx, message, shard).await?;
+        account_changes_index =
+            store_account_changes(&mut tx, message, shard, account_changes_index).await?;
+    }
+    // Before the commit: if the publishing fails, the block is written and published again
+    #[cfg(any(feature = "kafka", feature = "nats"))]
+    if let Some(sink) = sink {
+        sink.publish(&sink::collect_events(message)).await?;
+    }
+    tx.commit().await.map_err(db_error)
+}
+
+async fn store_chunk(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    message: &StreamerMessage,
+    shard: &IndexerShard,
+) -> crate::Result<()> {
+    let chunk = match &shard.chunk {
+        Some(chunk) => chunk,
+        None => return Ok(()),
+    };
+    let block_hash = message.block.header.hash.to_string();
+    let block_timestamp = message.block.header.timestamp_nanosec.to_string();
+
+    for transaction in &chunk.transactions {
+        let outcome = &transaction.outcome.execution_outcome.outcome;
+        let converted_into_receipt_id = outcome
+            .receipt_ids
+            .first()
+            .map(|receipt_id| receipt_id.to_string())
+            .unwrap_or_default();
+        sqlx::query(
+            r"INSERT INTO transactions (transaction_hash, included_in_block_hash, block_timestamp, signer_account_id,
+                                        receiver_account_id, status, converted_into_receipt_id, signer_public_key,
+                                        receipt_conversion_tokens_burnt)
+              VALUES ($1, $2, $3::numeric(20, 0), $4, $5, $6::execution_outcome_status, $7, $8, $9::numeric(45, 0))
+              ON CONFLICT DO NOTHING",
+        )
+        .bind(transaction.transaction.hash.to_string())
+        .bind(&block_hash)
+        .bind(&block_timestamp)
+        .bind(transaction.transaction.signer_id.to_string())
+        .bind(transaction.transaction.receiver_id.to_string())
+        .bind(execution_status(&outcome.status))
+        .bind(converted_into_receipt_id)
+        .bind(transaction.transaction.public_key.to_string())
+        .bind(outcome.tokens_burnt.to_string())
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+    }
+
+    for receipt in &chunk.receipts {
+        let actions = match &receipt.receipt {
+            views::ReceiptEnumView::Action { actions, .. } => actions,
+            views::ReceiptEnumView::Data { .. } => continue,
+        };
+        for (index, action) in actions.iter().enumerate() {
+            let (action_kind, args) = actions::action_kind_and_args(action);
+            sqlx::query(
+                r"INSERT INTO action_receipt_actions (receipt_id, index_in_action_receipt, action_kind, args,
+                                                      receipt_predecessor_account_id, receipt_receiver_account_id,
+                                                      receipt_included_in_block_timestamp)
+                  VALUES ($1, $2, $3::action_kind, $4, $5, $6, $7::numeric(20, 0))
+                  ON CONFLICT DO NOTHING",
+            )
+            .bind(receipt.receipt_id.to_string())
+            .bind(index as i32)
+            .bind(action_kind)
+            .bind(args)
+            .bind(receipt.predecessor_id.to_string())
+            .bind(receipt.receiver_id.to_string())
+            .bind(&block_timestamp)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        }
+    }
+    Ok(())
+}
+
+/// The outcomes and the events from their logs
+async fn store_execution_outcomes(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    message: &StreamerMessage,
+    shard: &IndexerShard,
+) -> crate::Result<()> {
+    let block_hash = message.block.header.hash.to_string();
+    let block_timestamp = message.block.header.timestamp_nanosec.to_string();
+    let shard_id = shard.shard_id.to_string();
+    let mut ft_events_index = 0;
+    let mut nft_events_index = 0;
+
+    for (index, outcome) in shard.receipt_execution_outcomes.iter().enumerate() {
+        let receipt_id = outcome.receipt.receipt_id.to_string();
+        let execution_outcome = &outcome.execution_outcome.outcome;
+        sqlx::query(
+            r"INSERT INTO execution_outcomes (receipt_id, executed_in_block_hash, executed_in_block_timestamp, index_in_chunk,
+                                              gas_burnt, tokens_burnt, executor_account_id, status, shard_id)
+              VALUES ($1, $2, $3::numeric(20, 0), $4, $5::numeric(20, 0), $6::numeric(45, 0), $7,
+                      $8::execution_outcome_status, $9::numeric(20, 0))
+              ON CONFLICT DO NOTHING",
+        )
+        .bind(&receipt_id)
+        .bind(&block_hash)
+        .bind(&block_timestamp)
+        .bind(index as i32)
+        .bind(execution_outcome.gas_burnt.to_string())
+        .bind(execution_outcome.tokens_burnt.to_string())
+        .bind(execution_outcome.executor_id.to_string())
+        .bind(execution_status(&execution_outcome.status))
+        .bind(&shard_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+        let contract_account_id = execution_outcome.executor_id.to_string();
+        let is_successful = matches!(
+            execution_outcome.status,
+            views::ExecutionStatusView::SuccessValue(_)
+                | views::ExecutionStatusView::SuccessReceiptId(_)
+        );
+        for event in execution_outcome
+            .logs
+            .iter()
+            .filter_map(|log| events::parse_event(log))
+        {
+            match event {
+                events::Event::Ft(ft_events) => {
+                    for event in ft_events {
+                        sqlx::query(
+                            r"INSERT INTO assets__fungible_token_events VALUES
+                              ($1, $2::numeric(20, 0), $3::numeric(20, 0), $4, $5, $6, $7::ft_event_kind, $8, $9, $10)
+                              ON CONFLICT DO NOTHING",
+                        )
+                        .bind(&receipt_id)
+                        .bind(&block_timestamp)
+                        .bind(&shard_id)
+                        .bind(ft_events_index)
+                        .bind(&contract_account_id)
+                        .bind(&event.amount)
+                        .bind(event.kind.as_str())
+                        .bind(&event.old_owner_id)
+                        .bind(&event.new_owner_id)
+                        .bind(&event.memo)
+                        .execute(&mut *tx)
+                        .await
+                        .map_err(db_error)?;
+                        ft_events_index += 1;
+                    }
+                }
+                events::Event::Nft(nft_events) => {
+                    for event in nft_events {
+                        sqlx::query(
+                            r"INSERT INTO assets__non_fungible_token_events VALUES
+                              ($1, $2::numeric(20, 0), $3::numeric(20, 0), $4, $5, $6, $7::nft_event_kind, $8, $9, $10, $11)
+                              ON CONFLICT DO NOTHING",
+                        )
+                        .bind(&receipt_id)
+                        .bind(&block_timestamp)
+                        .bind(&shard_id)
+                        .bind(nft_events_index)
+                        .bind(&contract_account_id)
+                        .bind(&event.token_id)
+                        .bind(event.kind.as_str())
+                        .bind(&event.old_owner_id)
+                        .bind(&event.new_owner_id)
+                        .bind(&event.authorized_id)
+                        .bind(&event.memo)
+                        .execute(&mut *tx)
+                        .await
+                        .map_err(db_error)?;
+                        nft_events_index += 1;
+                        if is_successful {
+                            nft_counts::update_nft_owner_counts(
+                                tx,
+                                &contract_account_id,
+                                &event,
+                                &block_timestamp,
+                            )
+                            .await?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Only the balance changes, `index_in_block` continues from the previous shards.
+/// Returns the next index
+async fn store_account_changes(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    message: &StreamerMessage,
+    shard: &IndexerShard,
+    mut index_in_block: i32,
+) -> crate::Result<i32> {
+    for state_change in &shard.state_changes {
+        let (account_id, nonstaked_balance, staked_balance, storage_usage) = match &state_change
+            .value
+        {
+            views::StateChangeValueView::AccountUpdate {
+                account_id,
+                account,
+            } => (
+                account_id,
+                account.amount,
+                account.locked,
+                account.storage_usage,
+            ),
+            views::StateChangeValueView::AccountDeletion { account_id } => (account_id, 0, 0, 0),
+            _ => continue,
+        };
+        // `{"type": "transaction_processing", "tx_hash": "..."}`
+        let cause = serde_json::to_value(&state_change.cause).unwrap_or_default();
+        sqlx::query(
+            r"INSERT INTO account_changes (affected_account_id, changed_in_block_timestamp, changed_in_block_hash,
+                                           caused_by_transaction_hash, caused_by_receipt_id, update_reason,
+                                           affected_account_nonstaked_balance, affected_account_staked_balance,
+                                           affected_account_storage_usage, index_in_block)
+              VALUES ($1, $2::numeric(20, 0), $3, $4, $5, $6, $7::numeric(45, 0), $8::numeric(45, 0),
+                      $9::numeric(20, 0), $10)",
+        )
+        .bind(account_id.to_string())
+        .bind(message.block.header.timestamp_nanosec.to_string())
+        .bind(message.block.header.hash.to_string())
+        .bind(cause["tx_hash"].as_str())
+        .bind(cause["receipt_hash"].as_str())
+        .bind(cause["type"].as_str().unwrap_or_default().to_uppercase())
+        .bind(nonstaked_balance.to_string())
+        .bind(staked_balance.to_string())
+        .bind(storage_usage.to_string())
+        .bind(index_in_block)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        index_in_block += 1;
+    }
+    Ok(index_in_block)
+}
+
+/// `execution_outcome_status` DB enum
+fn execution_status(status: &views::ExecutionStatusView) -> &'static str {
+    match status {
+        views::ExecutionStatusView::Unknown => "UNKNOWN",
+        views::ExecutionStatusView::Failure(_) => "FAILURE",
+        views::ExecutionStatusView::SuccessValue(_) => "SUCCESS_VALUE",
+        views::ExecutionStatusView::SuccessReceiptId(_) => "SUCCESS_RECEIPT_ID",
+    }
+}
+
+fn db_error(err: sqlx::Error) -> errors::Error {
+    errors::ErrorKind::DBError(err.to_string()).into()
+}
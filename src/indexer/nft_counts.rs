@@ -0,0 +1,179 @@
+//! The current NFT counts per owner in `nft_owner_counts`, so the NFT overview does not replay
+//! all the events of the account. The first time we see the contract, its counts are computed
+//! from the events already in the DB, then each successful event updates them.
+use super::events;
+
+/// Call it only for the events of the successful receipts
+pub(crate) async fn update_nft_owner_counts(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    contract_account_id: &str,
+    event: &events::NftEvent,
+    block_timestamp: &str,
+) -> crate::Result<()> {
+    let is_new_contract = sqlx::query(
+        r"INSERT INTO nft_indexed_contracts (contract_account_id, indexed_at_block_timestamp)
+          VALUES ($1, $2::numeric(20, 0))
+          ON CONFLICT DO NOTHING",
+    )
+    .bind(contract_account_id)
+    .bind(block_timestamp)
+    .execute(&mut *tx)
+    .await
+    .map_err(super::db_error)?
+    .rows_affected()
+        > 0;
+    if is_new_contract {
+        backfill_nft_owner_counts(tx, contract_account_id, block_timestamp).await?;
+    }
+
+    if !event.old_owner_id.is_empty() {
+        add_nft_count(
+            tx,
+            contract_account_id,
+            &event.old_owner_id,
+            -1,
+            block_timestamp,
+        )
+        .await?;
+    }
+    if !event.new_owner_id.is_empty() {
+        add_nft_count(
+            tx,
+            contract_account_id,
+            &event.new_owner_id,
+            1,
+            block_timestamp,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// The counts from the successful events before the given block.
+/// The events of the current block are applied one by one
+async fn backfill_nft_owner_counts(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    contract_account_id: &str,
+    before_block_timestamp: &str,
+) -> crate::Result<()> {
+    sqlx::query(
+        r"
+        WITH successful_events AS (
+            SELECT emitted_at_block_timestamp, token_old_owner_account_id, token_new_owner_account_id
+            FROM assets__non_fungible_token_events
+                JOIN execution_outcomes ON assets__non_fungible_token_events.emitted_for_receipt_id = execution_outcomes.receipt_id
+            WHERE emitted_by_contract_account_id = $1
+                AND emitted_at_block_timestamp < $2::numeric(20, 0)
+                AND execution_outcomes.status IN ('SUCCESS_VALUE', 'SUCCESS_RECEIPT_ID')
+        ),
+        deltas AS (
+            SELECT token_new_owner_account_id owner_account_id, 1 delta, emitted_at_block_timestamp
+            FROM successful_events
+            WHERE token_new_owner_account_id != ''
+            UNION ALL
+            SELECT token_old_owner_account_id owner_account_id, -1 delta, emitted_at_block_timestamp
+            FROM successful_events
+            WHERE token_old_owner_account_id != ''
+        )
+        INSERT INTO nft_owner_counts (emitted_by_contract_account_id, owner_account_id, nft_count, last_updated_at_block_timestamp)
+        SELECT $1, owner_account_id, sum(delta), max(emitted_at_block_timestamp)
+        FROM deltas
+        GROUP BY owner_account_id
+        ON CONFLICT DO NOTHING
+    ",
+    )
+    .bind(contract_account_id)
+    .bind(before_block_timestamp)
+    .execute(&mut *tx)
+    .await
+    .map_err(super::db_error)?;
+    Ok(())
+}
+
+async fn add_nft_count(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    contract_account_id: &str,
+    owner_account_id: &str,
+    delta: i64,
+    block_timestamp: &str,
+) -> crate::Result<()> {
+    sqlx::query(
+        r"INSERT INTO nft_owner_counts (emitted_by_contract_account_id, owner_account_id, nft_count, last_updated_at_block_timestamp)
+          VALUES ($1, $2, $3, $4::numeric(20, 0))
+          ON CONFLICT (emitted_by_contract_account_id, owner_account_id) DO UPDATE
+          SET nft_count = nft_owner_counts.nft_count + EXCLUDED.nft_count,
+              last_updated_at_block_timestamp = EXCLUDED.last_updated_at_block_timestamp",
+    )
+    .bind(contract_account_id)
+    .bind(owner_account_id)
+    .bind(delta)
+    .bind(block_timestamp)
+    .execute(&mut *tx)
+    .await
+    .map_err(super::db_error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+
+    async fn get_counts(pool: &sqlx::Pool<sqlx::Postgres>) -> Vec<(String, i64)> {
+        sqlx::query_as(
+            r"SELECT owner_account_id, nft_count FROM nft_owner_counts
+              WHERE emitted_by_contract_account_id = 'nft.near'
+              ORDER BY owner_account_id",
+        )
+        .fetch_all(pool)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_update_nft_owner_counts_seeded() {
+        let db = init_seeded_db().await;
+        // The failed transfer back to alice at block 103 is not counted
+        let transfer = events::NftEvent {
+            kind: events::EventKind::Transfer,
+            token_id: "1".to_string(),
+            old_owner_id: "bob.near".to_string(),
+            new_owner_id: "carol.near".to_string(),
+            authorized_id: String::new(),
+            memo: String::new(),
+        };
+        let mut tx = db.pool.begin().await.unwrap();
+        update_nft_owner_counts(&mut tx, "nft.near", &transfer, "1600000000000000104")
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+        assert_eq!(
+            get_counts(&db.pool).await,
+            vec![
+                ("alice.near".to_string(), 0),
+                ("bob.near".to_string(), 0),
+                ("carol.near".to_string(), 1)
+            ]
+        );
+
+        // The contract is not backfilled twice
+        let transfer_back = events::NftEvent {
+            old_owner_id: "carol.near".to_string(),
+            new_owner_id: "alice.near".to_string(),
+            ..transfer
+        };
+        let mut tx = db.pool.begin().await.unwrap();
+        update_nft_owner_counts(&mut tx, "nft.near", &transfer_back, "1600000000000000105")
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+        assert_eq!(
+            get_counts(&db.pool).await,
+            vec![
+                ("alice.near".to_string(), 1),
+                ("bob.near".to_string(), 0),
+                ("carol.near".to_string(), 0)
+            ]
+        );
+    }
+}
@@ -0,0 +1,327 @@
+//! Publishes the normalized FT/NFT events and NEAR balance changes of each indexed block to Kafka or NATS,
+//! so the data platforms could consume the stream without polling the DB.
+//! The events are published before the block is committed: if the publishing fails, the block is indexed again.
+//! The consumers could get the duplicates after the restart, but never miss the events.
+//! Kafka is used for `kafka://broker1:9092,broker2:9092` URL (requires `kafka` feature),
+//! NATS for `nats://host:4222` (requires `nats` feature).
+use near_lake_framework::near_indexer_primitives::{views, StreamerMessage};
+
+use super::events;
+use crate::{config, errors};
+
+#[cfg(feature = "kafka")]
+const KAFKA_QUEUE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+enum Publisher {
+    #[cfg(feature = "kafka")]
+    Kafka(rdkafka::producer::FutureProducer),
+    #[cfg(feature = "nats")]
+    Nats(async_nats::Client),
+}
+
+pub(crate) struct EventSink {
+    publisher: Publisher,
+    topic_prefix: String,
+}
+
+impl EventSink {
+    pub async fn connect(config: &config::EventSinkConfig) -> crate::Result<Self> {
+        let publisher = match config.url.split_once("://") {
+            #[cfg(feature = "kafka")]
+            Some(("kafka", brokers)) => Publisher::Kafka(
+                rdkafka::ClientConfig::new()
+                    .set("bootstrap.servers", brokers)
+                    .create()
+                    .map_err(sink_error)?,
+            ),
+            #[cfg(feature = "nats")]
+            Some(("nats", _)) => {
+                Publisher::Nats(async_nats::connect(config.url.as_str()).await.map_err(sink_error)?)
+            }
+            _ => {
+                return Err(errors::ErrorKind::InvalidInput(format!(
+                    "unsupported event sink {}, expected kafka://... (`kafka` feature) or nats://... (`nats` feature)",
+                    config.url
+                ))
+                .into())
+            }
+        };
+        Ok(Self {
+            publisher,
+            topic_prefix: config.topic_prefix.clone(),
+        })
+    }
+
+    /// Returns when all the events are accepted by the broker
+    pub async fn publish(&self, events: &[SinkEvent]) -> crate::Result<()> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        let messages = events
+            .iter()
+            .map(|event| {
+                Ok((
+                    format!("{}.{}", self.topic_prefix, event.topic()),
+                    event.key().to_string(),
+                    serde_json::to_vec(event)?,
+                ))
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+        match &self.publisher {
+            #[cfg(feature = "kafka")]
+            Publisher::Kafka(producer) => {
+                futures::future::try_join_all(messages.iter().map(|(topic, key, payload)| {
+                    producer.send(
+                        rdkafka::producer::FutureRecord::to(topic)
+                            .key(key)
+                            .payload(payload),
+                        KAFKA_QUEUE_TIMEOUT,
+                    )
+                }))
+                .await
+                .map_err(|(err, _)| sink_error(err))?;
+            }
+            #[cfg(feature = "nats")]
+            Publisher::Nats(client) => {
+                for (subject, _, payload) in messages {
+                    client
+                        .publish(subject, payload.into())
+                        .await
+                        .map_err(sink_error)?;
+                }
+                client.flush().await.map_err(sink_error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The message of the topic `{topic_prefix}.{topic}`, the key is the affected account or the contract,
+/// so the events of one account keep their order in Kafka
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum SinkEvent {
+    FtEvent {
+        contract_account_id: String,
+        /// `mint`, `transfer` or `burn`
+        kind: String,
+        /// Null for `mint`
+        old_owner_id: Option<String>,
+        /// Null for `burn`
+        new_owner_id: Option<String>,
+        amount: String,
+        memo: Option<String>,
+        receipt_id: String,
+        block_height: u64,
+        block_timestamp_nanos: String,
+    },
+    NftEvent {
+        contract_account_id: String,
+        /// `mint`, `transfer` or `burn`
+        kind: String,
+        token_id: String,
+        /// Null for `mint`
+        old_owner_id: Option<String>,
+        /// Null for `burn`
+        new_owner_id: Option<String>,
+        memo: Option<String>,
+        receipt_id: String,
+        block_height: u64,
+        block_timestamp_nanos: String,
+    },
+    BalanceChange {
+        account_id: String,
+        /// The balances after the change, yoctoNEAR
+        nonstaked_balance: String,
+        staked_balance: String,
+        /// E.g. `transaction_processing`, `receipt_processing`, `validator_accounts_update`
+        cause: String,
+        transaction_hash: Option<String>,
+        receipt_id: Option<String>,
+        block_height: u64,
+        block_timestamp_nanos: String,
+    },
+}
+
+impl SinkEvent {
+    fn topic(&self) -> &'static str {
+        match self {
+            SinkEvent::FtEvent { .. } => "ft_events",
+            SinkEvent::NftEvent { .. } => "nft_events",
+            SinkEvent::BalanceChange { .. } => "balance_changes",
+        }
+    }
+
+    fn key(&self) -> &str {
+        match self {
+            SinkEvent::FtEvent {
+                contract_account_id,
+                ..
+            }
+            | SinkEvent::NftEvent {
+                contract_account_id,
+                ..
+            } => contract_account_id,
+            SinkEvent::BalanceChange { account_id, .. } => account_id,
+        }
+    }
+}
+
+/// The events of the successful receipts and all the balance changes of the block, in the order of the shards
+pub(crate) fn collect_events(message: &StreamerMessage) -> Vec<SinkEvent> {
+    let block_height = message.block.header.height;
+    let block_timestamp_nanos = message.block.header.timestamp_nanosec.to_string();
+    let mut sink_events = vec![];
+    for shard in &message.shards {
+        for outcome in &shard.receipt_execution_outcomes {
+            let execution_outcome = &outcome.execution_outcome.outcome;
+            if !matches!(
+                execution_outcome.status,
+                views::ExecutionStatusView::SuccessValue(_)
+                    | views::ExecutionStatusView::SuccessReceiptId(_)
+            ) {
+                continue;
+            }
+            let receipt_id = outcome.receipt.receipt_id.to_string();
+            let contract_account_id = execution_outcome.executor_id.to_string();
+            for event in execution_outcome
+                .logs
+                .iter()
+                .filter_map(|log| events::parse_event(log))
+            {
+                sink_events.extend(normalize_event(
+                    event,
+                    &contract_account_id,
+                    &receipt_id,
+                    block_height,
+                    &block_timestamp_nanos,
+                ));
+            }
+        }
+        for state_change in &shard.state_changes {
+            let (account_id, nonstaked_balance, staked_balance) = match &state_change.value {
+                views::StateChangeValueView::AccountUpdate {
+                    account_id,
+                    account,
+                } => (account_id, account.amount, account.locked),
+                views::StateChangeValueView::AccountDeletion { account_id } => (account_id, 0, 0),
+                _ => continue,
+            };
+            // `{"type": "transaction_processing", "tx_hash": "..."}`
+            let cause = serde_json::to_value(&state_change.cause).unwrap_or_default();
+            sink_events.push(SinkEvent::BalanceChange {
+                account_id: account_id.to_string(),
+                nonstaked_balance: nonstaked_balance.to_string(),
+                staked_balance: staked_balance.to_string(),
+                cause: cause["type"].as_str().unwrap_or_default().to_string(),
+                transaction_hash: cause["tx_hash"].as_str().map(str::to_string),
+                receipt_id: cause["receipt_hash"].as_str().map(str::to_string),
+                block_height,
+                block_timestamp_nanos: block_timestamp_nanos.clone(),
+            });
+        }
+    }
+    sink_events
+}
+
+fn normalize_event(
+    event: events::Event,
+    contract_account_id: &str,
+    receipt_id: &str,
+    block_height: u64,
+    block_timestamp_nanos: &str,
+) -> Vec<SinkEvent> {
+    match event {
+        events::Event::Ft(ft_events) => ft_events
+            .into_iter()
+            .map(|event| SinkEvent::FtEvent {
+                contract_account_id: contract_account_id.to_string(),
+                kind: event.kind.as_str().to_lowercase(),
+                old_owner_id: non_empty(event.old_owner_id),
+                new_owner_id: non_empty(event.new_owner_id),
+                amount: event.amount,
+                memo: non_empty(event.memo),
+                receipt_id: receipt_id.to_string(),
+                block_height,
+                block_timestamp_nanos: block_timestamp_nanos.to_string(),
+            })
+            .collect(),
+        events::Event::Nft(nft_events) => nft_events
+            .into_iter()
+            .map(|event| SinkEvent::NftEvent {
+                contract_account_id: contract_account_id.to_string(),
+                kind: event.kind.as_str().to_lowercase(),
+                token_id: event.token_id,
+                old_owner_id: non_empty(event.old_owner_id),
+                new_owner_id: non_empty(event.new_owner_id),
+                memo: non_empty(event.memo),
+                receipt_id: receipt_id.to_string(),
+                block_height,
+                block_timestamp_nanos: block_timestamp_nanos.to_string(),
+            })
+            .collect(),
+    }
+}
+
+/// The parsed events keep the missing values as the empty strings, the same as the DB
+fn non_empty(value: String) -> Option<String> {
+    if value.is_empty() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+fn sink_error(err: impl std::fmt::Display) -> errors::Error {
+    errors::ErrorKind::InternalError(format!("event sink: {}", err)).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_event() {
+        let log = r#"EVENT_JSON:{"standard":"nep141","version":"1.0.0","event":"ft_mint","data":[{"owner_id":"alice.near","amount":"300"}]}"#;
+        let sink_events = normalize_event(
+            events::parse_event(log).unwrap(),
+            "usn",
+            "receipt_1",
+            103,
+            "1600000000000000103",
+        );
+        assert_eq!(
+            sink_events,
+            vec![SinkEvent::FtEvent {
+                contract_account_id: "usn".to_string(),
+                kind: "mint".to_string(),
+                old_owner_id: None,
+                new_owner_id: Some("alice.near".to_string()),
+                amount: "300".to_string(),
+                memo: None,
+                receipt_id: "receipt_1".to_string(),
+                block_height: 103,
+                block_timestamp_nanos: "1600000000000000103".to_string(),
+            }]
+        );
+        assert_eq!(sink_events[0].topic(), "ft_events");
+        assert_eq!(sink_events[0].key(), "usn");
+        assert_eq!(
+            serde_json::to_value(&sink_events[0]).unwrap()["type"],
+            "ft_event"
+        );
+
+        let log = r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_transfer","data":[{"old_owner_id":"alice.near","new_owner_id":"bob.near","token_ids":["1","2"]}]}"#;
+        let sink_events = normalize_event(
+            events::parse_event(log).unwrap(),
+            "nft.near",
+            "receipt_2",
+            103,
+            "1600000000000000103",
+        );
+        assert_eq!(sink_events.len(), 2);
+        assert!(sink_events
+            .iter()
+            .all(|event| event.topic() == "nft_events"));
+    }
+}
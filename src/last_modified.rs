@@ -0,0 +1,146 @@
+//! Conditional requests for the pollers. The handler finds the last event relevant to the response
+//! and calls `check_if_modified_since`: if the client already has the response newer than this event,
+//! the handler stops with 304. The middleware puts the same moment to `Last-Modified` header.
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header;
+use actix_web::HttpMessage;
+use futures::future::{ready, LocalBoxFuture, Ready};
+
+use crate::errors;
+
+/// Put to the request extensions by the handler
+#[derive(Clone, Copy)]
+struct LastModified(header::HttpDate);
+
+/// `last_modified_nanos` is the timestamp of the last event relevant to the response,
+/// `None` if there were no events: we can't tell when the response changed then.
+/// HTTP dates have second precision, we round down, so the events of the same second
+/// given after `If-Modified-Since` are not missed
+pub(crate) fn check_if_modified_since(
+    request: &actix_web::HttpRequest,
+    last_modified_nanos: Option<u64>,
+) -> crate::Result<()> {
+    let last_modified_nanos = match last_modified_nanos {
+        Some(timestamp) => timestamp,
+        None => return Ok(()),
+    };
+    let last_modified = header::HttpDate::from(
+        std::time::UNIX_EPOCH + std::time::Duration::from_secs(last_modified_nanos / 1_000_000_000),
+    );
+    request.extensions_mut().insert(LastModified(last_modified));
+
+    let if_modified_since = request
+        .headers()
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<header::HttpDate>().ok());
+    match if_modified_since {
+        Some(if_modified_since) if last_modified <= if_modified_since => {
+            Err(errors::ErrorKind::NotModified.into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Sets `Last-Modified` header remembered by `check_if_modified_since`
+#[derive(Clone, Default)]
+pub struct LastModifiedHeader;
+
+impl<S, B> Transform<S, ServiceRequest> for LastModifiedHeader
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = LastModifiedHeaderMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(LastModifiedHeaderMiddleware { service }))
+    }
+}
+
+pub struct LastModifiedHeaderMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for LastModifiedHeaderMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let response = self.service.call(req);
+        Box::pin(async move {
+            let mut response = response.await?;
+            let last_modified = response
+                .request()
+                .extensions()
+                .get::<LastModified>()
+                .copied();
+            if let Some(LastModified(last_modified)) = last_modified {
+                if let Ok(value) = header::HeaderValue::from_str(&last_modified.to_string()) {
+                    response.headers_mut().insert(header::LAST_MODIFIED, value);
+                }
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn handler(
+        request: actix_web::HttpRequest,
+    ) -> Result<actix_web::HttpResponse, errors::Error> {
+        // 2020-09-13T12:26:40.5Z
+        check_if_modified_since(&request, Some(1600000000500000000))?;
+        Ok(actix_web::HttpResponse::Ok().body("history"))
+    }
+
+    #[actix_web::test]
+    async fn test_last_modified() {
+        let app = actix_web::test::init_service(
+            actix_web::App::new()
+                .wrap(LastModifiedHeader)
+                .default_service(actix_web::web::to(handler)),
+        )
+        .await;
+
+        let request = actix_web::test::TestRequest::get()
+            .uri("/history")
+            .to_request();
+        let response = actix_web::test::call_service(&app, request).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.headers().get(header::LAST_MODIFIED).unwrap(),
+            "Sun, 13 Sep 2020 12:26:40 GMT"
+        );
+
+        let request = actix_web::test::TestRequest::get()
+            .uri("/history")
+            .insert_header((header::IF_MODIFIED_SINCE, "Sun, 13 Sep 2020 12:26:40 GMT"))
+            .to_request();
+        let response = actix_web::test::call_service(&app, request).await;
+        assert_eq!(response.status(), 304);
+        assert!(response.headers().contains_key(header::LAST_MODIFIED));
+
+        let request = actix_web::test::TestRequest::get()
+            .uri("/history")
+            .insert_header((header::IF_MODIFIED_SINCE, "Sun, 13 Sep 2020 12:26:39 GMT"))
+            .to_request();
+        let response = actix_web::test::call_service(&app, request).await;
+        assert_eq!(response.status(), 200);
+    }
+}
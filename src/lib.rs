@@ -0,0 +1,155 @@
+//! NEAR Enhanced API as a library.
+//!
+//! The binary in `main.rs` is the reference usage, but the endpoints could be mounted
+//! inside any other Actix (paperclip) application:
+//! ```ignore
+//! let ctx = near_enhanced_api::ServerContext::builder()
+//!     .pool(pool)
+//!     .balances_pool(pool_balances)
+//!     .rpc_client(rpc_client)
+//!     .build()?;
+//! app.service(web::scope("/enhanced").configure(|cfg| near_enhanced_api::configure(cfg, &ctx)));
+//! ```
+//! The other backends (e.g. NEAR Lake) implement `data_source::DataSource` and are given to
+//! `ServerContextBuilder::data_source` instead of the pool and the RPC client.
+use paperclip::actix::web;
+pub(crate) use sqlx::types::BigDecimal;
+
+#[cfg(feature = "alerts")]
+pub mod alerts;
+pub mod allow_list;
+mod api_keys;
+pub mod audit;
+pub mod balances_schema;
+mod cache;
+mod cache_warmer;
+pub mod client_ip;
+pub mod config;
+mod consistency_check;
+mod context;
+pub mod data_source;
+mod db_helpers;
+pub mod error_debug;
+pub mod errors;
+#[cfg(feature = "exports")]
+mod export_worker;
+pub mod head_requests;
+#[cfg(feature = "indexer")]
+pub mod indexer;
+pub mod last_modified;
+mod modules;
+#[cfg(feature = "exports")]
+pub mod object_storage;
+#[cfg(feature = "coin")]
+pub mod price_sampler;
+pub mod quotas;
+pub mod request_log;
+pub mod response_cache;
+pub mod response_format;
+pub mod response_meta;
+pub mod route_toggles;
+#[cfg(test)]
+mod rpc_cassette;
+mod rpc_helpers;
+pub mod signing;
+pub mod spec;
+#[cfg(feature = "coin")]
+mod tabular;
+pub mod types;
+pub mod usage;
+
+/// Request and response types of all the endpoints.
+/// `near-enhanced-api-client` reuses them, so the client and the server are always in sync
+pub mod api_models {
+    pub use crate::types::response_meta::ResponseMeta;
+
+    #[cfg(feature = "accounts")]
+    pub mod accounts {
+        pub use crate::modules::accounts::schemas::*;
+    }
+    #[cfg(feature = "admin")]
+    pub mod admin {
+        pub use crate::modules::admin::schemas::*;
+    }
+    #[cfg(feature = "alerts")]
+    pub mod alerts {
+        pub use crate::modules::alerts::schemas::*;
+    }
+    #[cfg(feature = "coin")]
+    pub mod coin {
+        pub use crate::modules::coin::schemas::*;
+    }
+    #[cfg(feature = "defi")]
+    pub mod defi {
+        pub use crate::modules::defi::schemas::*;
+    }
+    #[cfg(feature = "exports")]
+    pub mod exports {
+        pub use crate::modules::exports::schemas::*;
+    }
+    #[cfg(feature = "nft")]
+    pub mod nft {
+        pub use crate::modules::nft::schemas::*;
+    }
+    #[cfg(feature = "staking")]
+    pub mod staking {
+        pub use crate::modules::staking::schemas::*;
+    }
+    #[cfg(feature = "watchlists")]
+    pub mod watchlists {
+        pub use crate::modules::watchlists::schemas::*;
+    }
+}
+
+pub use context::{ServerContext, ServerContextBuilder};
+
+pub(crate) const LOGGER_MSG: &str = "near_enhanced_api";
+
+pub type Result<T> = std::result::Result<T, errors::Error>;
+
+/// Registers the data source from `ctx` and all the API endpoints.
+/// Could be applied to the whole app or to any `web::scope`.
+pub fn configure(app: &mut web::ServiceConfig, ctx: &ServerContext) {
+    app.app_data(web::Data::new(ctx.data_source.clone()))
+        .app_data(web::Data::new(ctx.contract_allow_list.clone()));
+
+    #[cfg(feature = "coin")]
+    modules::coin::register_services(app, &ctx.contract_allow_list);
+    #[cfg(feature = "nft")]
+    modules::nft::register_services(app, &ctx.contract_allow_list);
+    #[cfg(feature = "staking")]
+    modules::staking::register_services(app);
+    #[cfg(feature = "defi")]
+    modules::defi::register_services(app);
+    #[cfg(feature = "accounts")]
+    modules::accounts::register_services(app);
+    #[cfg(feature = "alerts")]
+    modules::alerts::register_services(app);
+    #[cfg(feature = "watchlists")]
+    modules::watchlists::register_services(app);
+    #[cfg(feature = "exports")]
+    if let Some(storage) = &ctx.export_storage {
+        modules::exports::register_services(app, storage);
+    }
+    #[cfg(feature = "admin")]
+    if let Some(admin_token) = &ctx.admin_token {
+        modules::admin::register_services(
+            app,
+            admin_token,
+            &ctx.signature_verifier,
+            &ctx.route_toggles,
+            &ctx.contract_allow_list,
+            &ctx.consistency_checker,
+        );
+    }
+}
+
+/// Applies `migrations/` (our own tables: prices, usage, quotas, audit log, alerts, watchlists, export jobs) to the aux DB.
+/// The migrations are embedded into the binary, the applied ones are tracked in `_sqlx_migrations`.
+/// All of them are idempotent, so it's safe to run it on the DB where they were applied by hand
+pub async fn run_migrations(pool_aux: &sqlx::Pool<sqlx::Postgres>) -> Result<()> {
+    sqlx::migrate!("./migrations")
+        .run(pool_aux)
+        .await
+        .map_err(|err| errors::ErrorKind::DBError(err.to_string()).into())
+}
@@ -1,33 +1,69 @@
 use actix_cors::Cors;
-use actix_web::{App, HttpServer, ResponseError};
+use actix_web::dev::Service;
+use actix_web::{App, HttpMessage, HttpServer, ResponseError};
+use futures::StreamExt;
 use paperclip::actix::{web, OpenApiExt};
-pub(crate) use sqlx::types::BigDecimal;
 
-mod config;
-mod db_helpers;
-mod errors;
-mod modules;
-mod rpc_helpers;
-mod types;
+use near_enhanced_api::{config, errors};
 
-pub(crate) const LOGGER_MSG: &str = "near_enhanced_api";
+const LOGGER_MSG: &str = "near_enhanced_api";
+/// The delay for the requests which hit the route timeout, the load is usually gone by then
+const REQUEST_TIMEOUT_RETRY_AFTER_SECS: u64 = 5;
 
-pub(crate) type Result<T> = std::result::Result<T, errors::Error>;
-
-fn get_cors(cors_allowed_origins: &[String]) -> Cors {
-    let mut cors = Cors::permissive();
-    if cors_allowed_origins != ["*".to_string()] {
-        for origin in cors_allowed_origins {
-            cors = cors.allowed_origin(origin);
-        }
-    }
-    cors.allowed_methods(vec!["GET"])
+/// `origin_patterns` are compiled from `config` once, not per worker
+fn get_cors(config: &config::CorsConfig, origin_patterns: Vec<regex::Regex>) -> Cors {
+    let mut cors = Cors::default()
+        .allowed_methods(config.allowed_methods.iter().map(String::as_str))
         .allowed_headers(vec![
             actix_web::http::header::AUTHORIZATION,
             actix_web::http::header::ACCEPT,
+            actix_web::http::header::HeaderName::from_static("x-api-key"),
+            actix_web::http::header::HeaderName::from_static("x-signature"),
+            actix_web::http::header::HeaderName::from_static("x-signature-timestamp"),
         ])
         .allowed_header(actix_web::http::header::CONTENT_TYPE)
-        .max_age(3600)
+        .expose_any_header()
+        .max_age(config.max_age_secs);
+    for header in &config.extra_allowed_headers {
+        cors = cors.allowed_header(header.as_str());
+    }
+    if config.supports_credentials {
+        cors = cors.supports_credentials();
+    }
+    if config.allows_any_origin() {
+        return cors.allow_any_origin();
+    }
+    for origin in &config.allowed_origins {
+        cors = cors.allowed_origin(origin);
+    }
+    if !origin_patterns.is_empty() {
+        cors = cors.allowed_origin_fn(move |origin, _| {
+            origin.to_str().map_or(false, |origin| {
+                origin_patterns
+                    .iter()
+                    .any(|pattern| pattern.is_match(origin))
+            })
+        });
+    }
+    cors
+}
+
+/// Fails the body reading with `PayloadError::Overflow` after `max_size` bytes,
+/// the extractors answer it with `INVALID_INPUT`
+fn limit_payload(req: &mut actix_web::dev::ServiceRequest, max_size: usize) {
+    let mut size = 0;
+    let payload = req.take_payload().map(move |chunk| {
+        let chunk = chunk?;
+        size += chunk.len();
+        if size > max_size {
+            Err(actix_web::error::PayloadError::Overflow)
+        } else {
+            Ok(chunk)
+        }
+    });
+    req.set_payload(actix_web::dev::Payload::Stream {
+        payload: Box::pin(payload),
+    });
 }
 
 async fn playground_ui() -> impl actix_web::Responder {
@@ -57,6 +93,280 @@ async fn playground_ui() -> impl actix_web::Responder {
         )
 }
 
+/// The socket file stays after the previous run, binding fails if we don't remove it
+#[cfg(unix)]
+fn remove_stale_socket(path: &std::path::Path) -> std::io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    if let Ok(metadata) = std::fs::metadata(path) {
+        if metadata.file_type().is_socket() {
+            std::fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn init_context(
+    max_concurrent_rpc_calls: usize,
+    usage_flush_interval_secs: Option<u64>,
+    quota_refresh_interval_secs: Option<u64>,
+    cache_warm_interval_secs: Option<u64>,
+    consistency_check_interval_secs: Option<u64>,
+    request_signing: config::RequestSigningConfig,
+    alerts_check_interval_secs: Option<u64>,
+    run_migrations: bool,
+    indexer: Option<config::IndexerConfig>,
+    slow_query_threshold_ms: Option<u64>,
+    slow_rpc_call_threshold_ms: Option<u64>,
+    export_storage: Option<config::ExportStorageConfig>,
+) -> near_enhanced_api::ServerContext {
+    let db_url = &std::env::var("DATABASE_URL").expect("failed to get database url");
+    let pool = sqlx::PgPool::connect(db_url)
+        .await
+        .expect("failed to connect to the database");
+
+    let rpc_url = &std::env::var("RPC_URL").expect("failed to get RPC url");
+    let rpc_client = near_jsonrpc_client::JsonRpcClient::connect(rpc_url);
+
+    // Our own tables could live in the separate DB, the indexer DB is usually read-only
+    let pool_aux = match std::env::var("DATABASE_URL_AUX") {
+        Ok(url_aux) => sqlx::PgPool::connect(&url_aux)
+            .await
+            .expect("failed to connect to the aux database"),
+        Err(_) => pool.clone(),
+    };
+    if run_migrations {
+        near_enhanced_api::run_migrations(&pool_aux)
+            .await
+            .expect("failed to apply the migrations");
+    }
+
+    #[cfg(feature = "coin")]
+    if let Ok(sampler_config_path) = std::env::var("PRICE_SAMPLER_CONFIG") {
+        let sampler_config: config::PriceSamplerConfig = serde_json::from_str(
+            &std::fs::read_to_string(sampler_config_path)
+                .expect("failed to read the price sampler config"),
+        )
+        .expect("failed to parse the price sampler config");
+        tokio::spawn(near_enhanced_api::price_sampler::run(
+            pool_aux.clone(),
+            rpc_client.clone(),
+            sampler_config,
+        ));
+    }
+
+    #[cfg(feature = "alerts")]
+    if let Some(check_interval_secs) = alerts_check_interval_secs {
+        tokio::spawn(near_enhanced_api::alerts::run(
+            pool.clone(),
+            pool_aux.clone(),
+            rpc_client.clone(),
+            std::time::Duration::from_secs(check_interval_secs),
+        ));
+    }
+    #[cfg(not(feature = "alerts"))]
+    let _ = alerts_check_interval_secs;
+
+    #[cfg(all(feature = "indexer", feature = "nft"))]
+    let nft_owner_counts = indexer.is_some();
+    #[cfg(feature = "indexer")]
+    if let Some(indexer_config) = indexer {
+        tokio::spawn(near_enhanced_api::indexer::run(
+            pool.clone(),
+            indexer_config,
+        ));
+    }
+    #[cfg(not(feature = "indexer"))]
+    if indexer.is_some() {
+        tracing::warn!(
+            target: LOGGER_MSG,
+            "INDEXER_NETWORK is ignored, the server is built without `indexer` feature"
+        );
+    }
+
+    let ctx_builder = near_enhanced_api::ServerContext::builder()
+        .pool(pool)
+        .aux_pool(pool_aux)
+        .rpc_client(rpc_client)
+        .max_concurrent_rpc_calls(max_concurrent_rpc_calls);
+    // `RPC_URL` is then the regular node, it serves the recent blocks
+    let ctx_builder = match std::env::var("ARCHIVAL_RPC_URL") {
+        Ok(archival_rpc_url) => ctx_builder.archival_rpc_client(
+            near_jsonrpc_client::JsonRpcClient::connect(archival_rpc_url),
+        ),
+        Err(_) => ctx_builder,
+    };
+    #[cfg(all(
+        feature = "history",
+        any(feature = "coin", feature = "staking", feature = "accounts")
+    ))]
+    let ctx_builder = {
+        let url_balances =
+            &std::env::var("DATABASE_URL_BALANCES").expect("failed to get database url");
+        let pool_balances = sqlx::PgPool::connect(url_balances)
+            .await
+            .expect("failed to connect to the balances database");
+        let balances_schema = near_enhanced_api::balances_schema::detect(&pool_balances)
+            .await
+            .expect("failed to detect the balances database schema");
+        tracing::info!(
+            target: LOGGER_MSG,
+            "Balances database schema: {:?}",
+            balances_schema
+        );
+        ctx_builder
+            .balances_pool(pool_balances)
+            .balances_schema(balances_schema)
+    };
+    #[cfg(feature = "admin")]
+    let ctx_builder = match std::env::var("ADMIN_API_TOKEN") {
+        Ok(admin_token) => ctx_builder.admin_token(admin_token),
+        Err(_) => ctx_builder,
+    };
+    #[cfg(feature = "scylla")]
+    let ctx_builder = match std::env::var("SCYLLA_URL") {
+        Ok(scylla_url) => {
+            let session = scylla::SessionBuilder::new()
+                .known_node(scylla_url)
+                .build()
+                .await
+                .expect("failed to connect to ScyllaDB");
+            let keyspace =
+                std::env::var("SCYLLA_KEYSPACE").expect("failed to get ScyllaDB keyspace");
+            session
+                .use_keyspace(keyspace, false)
+                .await
+                .expect("failed to select ScyllaDB keyspace");
+            ctx_builder.scylla_session(session)
+        }
+        Err(_) => ctx_builder,
+    };
+    #[cfg(all(feature = "indexer", feature = "nft"))]
+    let ctx_builder = ctx_builder.nft_owner_counts(nft_owner_counts);
+    #[cfg(feature = "nft")]
+    let ctx_builder = match std::env::var("NFT_SPAM_DENYLIST") {
+        Ok(denylist) => ctx_builder.nft_spam_denylist(
+            denylist
+                .split(',')
+                .map(|contract_id| contract_id.trim().to_string())
+                .filter(|contract_id| !contract_id.is_empty())
+                .collect(),
+        ),
+        Err(_) => ctx_builder,
+    };
+    // Nothing to audit without the admin endpoints and the signed requests
+    let ctx_builder = if std::env::var("ADMIN_API_TOKEN").is_ok()
+        || std::env::var("REQUEST_SIGNING_SECRET").is_ok()
+    {
+        ctx_builder.audit_log()
+    } else {
+        ctx_builder
+    };
+    let ctx_builder = match usage_flush_interval_secs {
+        Some(secs) => ctx_builder.usage_flush_interval(std::time::Duration::from_secs(secs)),
+        None => ctx_builder,
+    };
+    let ctx_builder = match quota_refresh_interval_secs {
+        Some(secs) => ctx_builder.quota_refresh_interval(std::time::Duration::from_secs(secs)),
+        None => ctx_builder,
+    };
+    let ctx_builder = match cache_warm_interval_secs {
+        Some(secs) => ctx_builder.cache_warm_interval(std::time::Duration::from_secs(secs)),
+        None => ctx_builder,
+    };
+    #[cfg(feature = "coin")]
+    let ctx_builder = match consistency_check_interval_secs {
+        Some(secs) => ctx_builder.consistency_check_interval(std::time::Duration::from_secs(secs)),
+        None => ctx_builder,
+    };
+    #[cfg(not(feature = "coin"))]
+    if consistency_check_interval_secs.is_some() {
+        tracing::warn!(
+            target: LOGGER_MSG,
+            "CONSISTENCY_CHECK_INTERVAL_SECS is ignored, the server is built without `coin` feature"
+        );
+    }
+    let ctx_builder = match std::env::var("REQUEST_SIGNING_SECRET") {
+        Ok(master_secret) => ctx_builder.request_signing(master_secret, request_signing),
+        Err(_) => ctx_builder,
+    };
+    let ctx_builder = match slow_query_threshold_ms {
+        Some(ms) => ctx_builder.slow_query_threshold(std::time::Duration::from_millis(ms)),
+        None => ctx_builder,
+    };
+    let ctx_builder = match slow_rpc_call_threshold_ms {
+        Some(ms) => ctx_builder.slow_rpc_call_threshold(std::time::Duration::from_millis(ms)),
+        None => ctx_builder,
+    };
+    #[cfg(feature = "exports")]
+    let ctx_builder = match export_storage {
+        Some(storage_config) => {
+            ctx_builder.export_storage(near_enhanced_api::object_storage::ObjectStorage::new(
+                storage_config,
+                std::env::var("EXPORT_STORAGE_ACCESS_KEY_ID")
+                    .expect("failed to get export storage access key id"),
+                std::env::var("EXPORT_STORAGE_SECRET_ACCESS_KEY")
+                    .expect("failed to get export storage secret access key"),
+            ))
+        }
+        None => ctx_builder,
+    };
+    #[cfg(not(feature = "exports"))]
+    if export_storage.is_some() {
+        tracing::warn!(
+            target: LOGGER_MSG,
+            "EXPORT_STORAGE_BUCKET is ignored, the server is built without `exports` feature"
+        );
+    }
+    ctx_builder
+        .build()
+        .expect("failed to build the server context")
+}
+
+/// The base of the spec, paperclip adds the endpoints to it
+fn get_spec(api_server_public_host: &str) -> paperclip::v2::models::DefaultApiRaw {
+    let mut spec = paperclip::v2::models::DefaultApiRaw::default();
+    spec.schemes
+        .insert(paperclip::v2::models::OperationProtocol::Https);
+    spec.schemes
+        .insert(paperclip::v2::models::OperationProtocol::Http);
+    spec.host = Some(api_server_public_host.to_string());
+    spec.base_path = Some("/".to_string());
+    spec.tags = vec![
+        paperclip::v2::models::Tag {
+            name: "Accounts".to_string(),
+            description: Some("Most common actions with accounts in NEAR".to_string()),
+            external_docs: None,
+        },
+        paperclip::v2::models::Tag {
+            name: "Alerts".to_string(),
+            description: Some(
+                "Webhook notifications about the balances and the NFT transfers".to_string(),
+            ),
+            external_docs: None,
+        },
+        paperclip::v2::models::Tag {
+            name: "Exports".to_string(),
+            description: Some("Background CSV exports of the history and the holders".to_string()),
+            external_docs: None,
+        },
+        paperclip::v2::models::Tag {
+            name: "Standards".to_string(),
+            description: Some(
+                "Manipulate with NEAR Enhancement Proposal (NEP) Standards".to_string(),
+            ),
+            external_docs: None,
+        },
+    ];
+    spec.info = paperclip::v2::models::Info {
+        version: "0.1".into(),
+        title: "NEAR Enhanced API powered by Pagoda".into(),
+        ..Default::default()
+    };
+    spec
+}
+
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
     dotenv::dotenv().ok();
@@ -69,34 +379,118 @@ async fn main() -> std::io::Result<()> {
         .with_writer(std::io::stderr)
         .init();
     tracing::debug!(
-        target: crate::LOGGER_MSG,
+        target: LOGGER_MSG,
         "NEAR Enhanced API Server is initializing..."
     );
 
-    let db_url = &std::env::var("DATABASE_URL").expect("failed to get database url");
-    let pool = sqlx::PgPool::connect(db_url)
-        .await
-        .expect("failed to connect to the database");
-
-    let url_balances = &std::env::var("DATABASE_URL_BALANCES").expect("failed to get database url");
-    let pool_balances = sqlx::PgPool::connect(url_balances)
-        .await
-        .expect("failed to connect to the balances database");
-
-    let rpc_url = &std::env::var("RPC_URL").expect("failed to get RPC url");
-    let rpc_client = near_jsonrpc_client::JsonRpcClient::connect(rpc_url);
-
     let config::Config {
         addr,
-        cors_allowed_origins,
+        cors,
+        trusted_proxies,
         limits,
-    } = config::Config::default();
-    let api_server_public_host =
-        std::env::var("API_SERVER_PUBLIC_HOST").unwrap_or_else(|_| addr.clone());
+        http,
+        max_concurrent_rpc_calls,
+        response_cache,
+        usage_flush_interval_secs,
+        quota_refresh_interval_secs,
+        cache_warm_interval_secs,
+        consistency_check_interval_secs,
+        request_signing,
+        alerts_check_interval_secs,
+        run_migrations,
+        indexer,
+        disabled_routes,
+        allowed_contracts,
+        debug_api_key_ids,
+        log_sampling,
+        slow_query_threshold_ms,
+        slow_rpc_call_threshold_ms,
+        export_storage,
+    } = config::Config::from_env();
+
+    let unix_socket_path = addr.strip_prefix("unix:").map(std::path::PathBuf::from);
+    let api_server_public_host = std::env::var("API_SERVER_PUBLIC_HOST").unwrap_or_else(|_| {
+        if unix_socket_path.is_some() {
+            "localhost".to_string()
+        } else {
+            addr.clone()
+        }
+    });
+
+    // `--export-spec <dir>`: write the specs for the client generators and exit, DB and RPC are not used
+    if std::env::args().any(|arg| arg == "--export-spec") {
+        let dir = std::env::args()
+            .skip_while(|arg| arg != "--export-spec")
+            .nth(1)
+            .expect("--export-spec needs the output directory");
+        near_enhanced_api::spec::export_spec(
+            get_spec(&api_server_public_host),
+            &limits,
+            std::path::Path::new(&dir),
+        )
+        .await?;
+        tracing::info!(target: LOGGER_MSG, "The spec is written to {}", dir);
+        return Ok(());
+    }
+
+    let ctx = if std::env::args().any(|arg| arg == "--mock") {
+        let fixtures_dir = std::env::var("MOCK_FIXTURES_DIR")
+            .ok()
+            .map(std::path::PathBuf::from);
+        tracing::info!(
+            target: LOGGER_MSG,
+            "Mock mode: DB and RPC are not used, fixtures dir: {:?}",
+            fixtures_dir
+        );
+        near_enhanced_api::ServerContext::mock(fixtures_dir)
+    } else {
+        init_context(
+            max_concurrent_rpc_calls,
+            usage_flush_interval_secs,
+            quota_refresh_interval_secs,
+            cache_warm_interval_secs,
+            consistency_check_interval_secs,
+            request_signing,
+            alerts_check_interval_secs,
+            run_migrations,
+            indexer,
+            slow_query_threshold_ms,
+            slow_rpc_call_threshold_ms,
+            export_storage,
+        )
+        .await
+    };
 
+    let response_cache = near_enhanced_api::response_cache::ResponseCache::new(response_cache);
+    let usage_recorder = ctx.usage_recorder();
+    let quota_enforcer = ctx.quota_enforcer();
+    let signature_verifier = ctx.signature_verifier();
+    let audit_logger = ctx.audit_logger();
+    let request_logger = near_enhanced_api::request_log::RequestLogger::new(log_sampling);
+    let route_toggles = ctx.route_toggles();
+    let response_meta_filler = ctx.response_meta_filler();
+    let error_debug = ctx.error_debug();
+    error_debug.set_debug_key_ids(debug_api_key_ids);
+    route_toggles.set_disabled_path_prefixes(disabled_routes);
+    ctx.contract_allow_list()
+        .set_allowed_contracts(allowed_contracts.map(|contract_ids| {
+            contract_ids
+                .iter()
+                .map(|contract_id| {
+                    contract_id
+                        .parse()
+                        .expect("ALLOWED_CONTRACTS should contain valid account ids")
+                })
+                .collect()
+        }));
+    let client_ip_resolver = near_enhanced_api::client_ip::ClientIpResolver::new(&trusted_proxies);
+
+    let cors_origin_patterns = cors
+        .origin_patterns()
+        .expect("CORS_CONFIG has an invalid origin pattern");
     let server = HttpServer::new(move || {
         let json_config = web::JsonConfig::default()
-            .limit(limits.input_payload_max_size)
+            .limit(limits.max_input_payload_size())
             .error_handler(|err, _req| {
                 let error_message = err.to_string();
                 actix_web::error::InternalError::from_response(
@@ -107,59 +501,114 @@ async fn main() -> std::io::Result<()> {
                 .into()
             });
 
-        let mut spec = paperclip::v2::models::DefaultApiRaw::default();
-        spec.schemes
-            .insert(paperclip::v2::models::OperationProtocol::Https);
-        spec.schemes
-            .insert(paperclip::v2::models::OperationProtocol::Http);
-        spec.host = Some(api_server_public_host.clone());
-        spec.base_path = Some("/".to_string());
-        spec.tags = vec![
-            paperclip::v2::models::Tag {
-                name: "Accounts".to_string(),
-                description: Some("Most common actions with accounts in NEAR".to_string()),
-                external_docs: None,
-            },
-            paperclip::v2::models::Tag {
-                name: "Standards".to_string(),
-                description: Some(
-                    "Manipulate with NEAR Enhancement Proposal (NEP) Standards".to_string(),
-                ),
-                external_docs: None,
-            },
-        ];
-        spec.info = paperclip::v2::models::Info {
-            version: "0.1".into(),
-            title: "NEAR Enhanced API powered by Pagoda".into(),
-            ..Default::default()
-        };
-
-        let mut app = App::new()
+        let spec = get_spec(&api_server_public_host);
+
+        App::new()
             .app_data(json_config)
-            .wrap(actix_web::middleware::Logger::default())
-            .app_data(web::Data::new(pool.clone()))
-            .app_data(web::Data::new(db_helpers::DBWrapper {
-                pool: pool_balances.clone(),
-            }))
-            .app_data(web::Data::new(rpc_client.clone()))
-            .wrap(get_cors(&cors_allowed_origins))
+            .app_data(
+                actix_web::web::QueryConfig::default().error_handler(errors::query_error_handler),
+            )
+            .app_data(
+                actix_web::web::PathConfig::default().error_handler(errors::path_error_handler),
+            )
+            .wrap(near_enhanced_api::last_modified::LastModifiedHeader)
+            .wrap(near_enhanced_api::spec::SpecRewriter::new(
+                "/api/spec/",
+                limits.clone(),
+            ))
+            .wrap(near_enhanced_api::response_format::ResponseFormatter)
+            .wrap(response_cache.clone())
+            .wrap(near_enhanced_api::head_requests::HeadRequests)
+            .wrap(error_debug.clone())
+            .wrap(response_meta_filler.clone())
+            .wrap(quota_enforcer.clone())
+            .wrap(route_toggles.clone())
+            .wrap(signature_verifier.clone())
+            .wrap(usage_recorder.clone())
+            .wrap(audit_logger.clone())
+            .wrap(request_logger.clone())
+            .wrap(get_cors(&cors, cors_origin_patterns.clone()))
+            .wrap_fn({
+                let limits = limits.clone();
+                move |mut req, srv| {
+                    let route_limits = limits.for_path(req.path());
+                    req.extensions_mut().insert(route_limits.page_limits);
+                    // Content-Length rejects early, the chunked bodies are counted while they are read
+                    limit_payload(&mut req, route_limits.input_payload_max_size);
+                    let content_length = req
+                        .headers()
+                        .get(actix_web::http::header::CONTENT_LENGTH)
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(|value| value.parse::<usize>().ok())
+                        .unwrap_or_default();
+                    let response = if content_length > route_limits.input_payload_max_size {
+                        Err(req)
+                    } else {
+                        Ok(srv.call(req))
+                    };
+                    async move {
+                        let response = match response {
+                            Err(req) => {
+                                return Ok(req.error_response(errors::Error::from_error_kind(
+                                    errors::ErrorKind::InvalidInput(format!(
+                                        "Payload is bigger than {} bytes",
+                                        route_limits.input_payload_max_size
+                                    )),
+                                )))
+                            }
+                            Ok(response) => response,
+                        };
+                        let response = match route_limits.request_timeout {
+                            Some(timeout) => match tokio::time::timeout(timeout, response).await {
+                                Ok(response) => response,
+                                Err(_) => Err(errors::Error::from_error_kind(
+                                    errors::ErrorKind::Unavailable {
+                                        message: "the request took too long".to_string(),
+                                        retry_after_secs: REQUEST_TIMEOUT_RETRY_AFTER_SECS,
+                                    },
+                                )
+                                .into()),
+                            },
+                            None => response.await,
+                        };
+                        response.map(|response| response.map_into_boxed_body())
+                    }
+                }
+            })
+            .wrap(client_ip_resolver.clone())
             .route("/", actix_web::web::get().to(playground_ui))
-            .wrap_api_with_spec(spec);
-
-        app = app.configure(modules::coin::register_services);
-        app = app.configure(modules::nft::register_services);
-
-        app.with_json_spec_at("/api/spec/v2.json")
+            .wrap_api_with_spec(spec)
+            .configure(|app| near_enhanced_api::configure(app, &ctx))
+            .with_json_spec_at("/api/spec/v2.json")
             .with_json_spec_v3_at("/api/spec/v3.json")
             .build()
     })
-    .bind(addr)
-    .unwrap()
-    .shutdown_timeout(5)
-    .run();
+    .keep_alive(match http.keep_alive_secs {
+        0 => actix_web::http::KeepAlive::Disabled,
+        secs => actix_web::http::KeepAlive::Timeout(std::time::Duration::from_secs(secs)),
+    })
+    .client_request_timeout(std::time::Duration::from_millis(
+        http.client_request_timeout_ms,
+    ))
+    .max_connections(http.max_connections);
+    let server = match http.workers {
+        Some(workers) => server.workers(workers),
+        None => server,
+    };
+    let server = match unix_socket_path {
+        #[cfg(unix)]
+        Some(path) => {
+            remove_stale_socket(&path)?;
+            server.bind_uds(path)?
+        }
+        #[cfg(not(unix))]
+        Some(_) => panic!("Unix domain sockets are supported only on Unix"),
+        None => server.bind(addr)?,
+    };
+    let server = server.shutdown_timeout(5).run();
 
     tracing::debug!(
-        target: crate::LOGGER_MSG,
+        target: LOGGER_MSG,
         "NEAR Enhanced API Server is starting..."
     );
 
@@ -0,0 +1,332 @@
+use std::str::FromStr;
+
+use crate::modules::accounts;
+use crate::{db_helpers, errors, types};
+
+/// The receipts made by the transactions directly are found by `converted_into_receipt_id`,
+/// `action_receipts` covers the rest if the indexer fills it
+pub(crate) async fn get_access_keys_history(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    account_id: &near_primitives::types::AccountId,
+    pagination: &types::query_params::HistoryPagination,
+) -> crate::Result<
+    Vec<(
+        accounts::schemas::AccessKeyChange,
+        types::query_params::EventIndex,
+    )>,
+> {
+    let query = r"
+        SELECT
+            action_receipt_actions.receipt_id,
+            action_kind::text cause,
+            args,
+            coalesce(action_receipts.originated_from_transaction_hash, transactions.transaction_hash) transaction_hash,
+            CASE WHEN execution_outcomes.status IN ('SUCCESS_VALUE', 'SUCCESS_RECEIPT_ID') THEN 'SUCCESS'
+                ELSE 'FAILURE'
+            END status,
+            receipt_included_in_block_timestamp block_timestamp_nanos,
+            execution_outcomes.shard_id,
+            execution_outcomes.index_in_chunk::numeric(20, 0)
+        FROM action_receipt_actions
+            JOIN execution_outcomes ON action_receipt_actions.receipt_id = execution_outcomes.receipt_id
+            LEFT JOIN action_receipts ON action_receipt_actions.receipt_id = action_receipts.receipt_id
+            LEFT JOIN transactions ON action_receipt_actions.receipt_id = transactions.converted_into_receipt_id
+        WHERE receipt_receiver_account_id = $1
+            AND action_kind IN ('ADD_KEY', 'DELETE_KEY')
+            AND receipt_included_in_block_timestamp < $2::numeric(20, 0)
+            AND (receipt_included_in_block_timestamp, execution_outcomes.shard_id, execution_outcomes.index_in_chunk)
+                < ($3::numeric(20, 0), $4::numeric(20, 0), $5::numeric(20, 0))
+            AND receipt_included_in_block_timestamp <= $3::numeric(20, 0)
+        ORDER BY receipt_included_in_block_timestamp DESC, execution_outcomes.shard_id DESC,
+            execution_outcomes.index_in_chunk DESC, index_in_action_receipt DESC
+        LIMIT $6::numeric(20, 0)
+    ";
+    let bound = pagination.event_bound();
+    let changes = db_helpers::select_retry_or_panic::<super::models::AccessKeyChangeInfo>(
+        pool,
+        query,
+        &[
+            account_id.to_string(),
+            pagination.block_timestamp.to_string(),
+            bound.block_timestamp.to_string(),
+            bound.shard_id.to_string(),
+            bound.index_in_chunk.to_string(),
+            pagination.limit.to_string(),
+        ],
+    )
+    .await?;
+
+    let mut result = vec![];
+    for change in changes {
+        let event_index = types::query_params::EventIndex::from_db(
+            &change.block_timestamp_nanos,
+            &change.shard_id,
+            &change.index_in_chunk,
+        )?;
+        result.push((change.try_into()?, event_index));
+    }
+    Ok(result)
+}
+
+/// `None` if the key does not exist at the given block.
+/// The transaction takes the prepaid gas and the gas of its conversion into the receipt from the allowance,
+/// the refunds go to the account balance and never come back to the allowance
+pub(crate) async fn get_access_key_usage(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    account_id: &near_primitives::types::AccountId,
+    public_key: &str,
+    block: &db_helpers::Block,
+    limit: u32,
+) -> crate::Result<Option<accounts::schemas::AccessKeyUsage>> {
+    let last_change_query = r"
+        SELECT
+            action_receipt_actions.receipt_id,
+            action_kind::text cause,
+            args,
+            NULL transaction_hash,
+            'SUCCESS' status,
+            receipt_included_in_block_timestamp block_timestamp_nanos,
+            execution_outcomes.shard_id,
+            execution_outcomes.index_in_chunk::numeric(20, 0)
+        FROM action_receipt_actions
+            JOIN execution_outcomes ON action_receipt_actions.receipt_id = execution_outcomes.receipt_id
+        WHERE receipt_receiver_account_id = $1
+            AND action_kind IN ('ADD_KEY', 'DELETE_KEY')
+            AND args->>'public_key' = $2
+            AND execution_outcomes.status IN ('SUCCESS_VALUE', 'SUCCESS_RECEIPT_ID')
+            AND receipt_included_in_block_timestamp <= $3::numeric(20, 0)
+        ORDER BY receipt_included_in_block_timestamp DESC, execution_outcomes.shard_id DESC,
+            execution_outcomes.index_in_chunk DESC, index_in_action_receipt DESC
+        LIMIT 1
+    ";
+    let changes = db_helpers::select_retry_or_panic::<super::models::AccessKeyChangeInfo>(
+        pool,
+        last_change_query,
+        &[
+            account_id.to_string(),
+            public_key.to_string(),
+            block.timestamp.to_string(),
+        ],
+    )
+    .await?;
+    let added: accounts::schemas::AccessKeyChange = match changes.into_iter().next() {
+        Some(change) if change.cause == "ADD_KEY" => change.try_into()?,
+        _ => return Ok(None),
+    };
+    let allowance = match &added.permission {
+        Some(accounts::schemas::AccessKeyPermission::FunctionCall { allowance, .. }) => *allowance,
+        _ => {
+            return Err(errors::ErrorKind::InvalidInput(format!(
+                "{} is the full access key of {}, it has no allowance",
+                public_key, account_id
+            ))
+            .into())
+        }
+    };
+
+    // `total_spent` is counted over all the transactions, only the recent ones are given
+    let transactions_query = r"
+        SELECT transaction_hash, receiver_account_id, block_timestamp, spent,
+            sum(spent) OVER (ORDER BY block_timestamp, transaction_hash) total_spent
+        FROM (
+            SELECT
+                transactions.transaction_hash,
+                transactions.receiver_account_id,
+                transactions.block_timestamp,
+                transactions.receipt_conversion_tokens_burnt + coalesce(prepaid.gas, 0) * blocks.gas_price spent
+            FROM transactions
+                JOIN blocks ON transactions.included_in_block_hash = blocks.block_hash
+                LEFT JOIN LATERAL (
+                    SELECT sum((args->>'gas')::numeric) gas
+                    FROM action_receipt_actions
+                    WHERE receipt_id = transactions.converted_into_receipt_id
+                        AND action_kind = 'FUNCTION_CALL'
+                ) prepaid ON true
+            WHERE transactions.signer_account_id = $1
+                AND transactions.signer_public_key = $2
+                AND transactions.block_timestamp > $3::numeric(20, 0)
+                AND transactions.block_timestamp <= $4::numeric(20, 0)
+        ) key_transactions
+        ORDER BY block_timestamp DESC, transaction_hash DESC
+        LIMIT $5::numeric(20, 0)
+    ";
+    let transactions =
+        db_helpers::select_retry_or_panic::<super::models::AccessKeyTransactionInfo>(
+            pool,
+            transactions_query,
+            &[
+                account_id.to_string(),
+                public_key.to_string(),
+                added.block_timestamp_nanos.0.to_string(),
+                block.timestamp.to_string(),
+                limit.to_string(),
+            ],
+        )
+        .await?;
+
+    let remaining = |total_spent: u128| {
+        allowance.map(|allowance| types::U128(allowance.0.saturating_sub(total_spent)))
+    };
+    let mut result = vec![];
+    for transaction in transactions {
+        let total_spent = types::numeric::to_u128(&transaction.total_spent)?;
+        result.push(accounts::schemas::AccessKeyTransaction {
+            transaction_hash: transaction.transaction_hash,
+            receiver_account_id: near_primitives::types::AccountId::from_str(
+                &transaction.receiver_account_id,
+            )?
+            .into(),
+            spent: types::numeric::to_u128(&transaction.spent)?.into(),
+            total_spent: total_spent.into(),
+            remaining_allowance: remaining(total_spent),
+            block_timestamp_nanos: types::numeric::to_u64(&transaction.block_timestamp)?.into(),
+        });
+    }
+    let spent = result
+        .first()
+        .map_or(0, |transaction| transaction.total_spent.0);
+    Ok(Some(accounts::schemas::AccessKeyUsage {
+        public_key: added.public_key,
+        permission: added.permission.expect("checked above"),
+        added_at_block_timestamp_nanos: added.block_timestamp_nanos,
+        allowance,
+        spent: spent.into(),
+        remaining_allowance: remaining(spent),
+        transactions: result,
+    }))
+}
+
+impl TryFrom<super::models::AccessKeyChangeInfo> for accounts::schemas::AccessKeyChange {
+    type Error = crate::errors::Error;
+
+    fn try_from(info: super::models::AccessKeyChangeInfo) -> crate::Result<Self> {
+        let permission = if info.cause == "ADD_KEY" {
+            Some(super::receipts::parse_permission(
+                &info.args["access_key"]["permission"],
+            )?)
+        } else {
+            None
+        };
+        Ok(Self {
+            public_key: info.args["public_key"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            cause: info.cause,
+            permission,
+            transaction_hash: info.transaction_hash,
+            receipt_id: info.receipt_id,
+            status: info.status,
+            block_timestamp_nanos: types::numeric::to_u64(&info.block_timestamp_nanos)?.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+
+    #[tokio::test]
+    async fn test_access_keys_history_seeded() {
+        let db = init_seeded_db().await;
+        let account = near_primitives::types::AccountId::from_str("alice.near").unwrap();
+        let pagination = types::query_params::HistoryPagination {
+            block_height: 103,
+            block_timestamp: 1600000000000000104,
+            before_event: None,
+            limit: 10,
+        };
+
+        let history = get_access_keys_history(&db.pool, &account, &pagination)
+            .await
+            .unwrap();
+        let history = crate::modules::without_event_indexes(history);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].cause, "DELETE_KEY");
+        assert_eq!(history[0].public_key, "ed25519:alice");
+        assert_eq!(history[0].permission, None);
+        assert_eq!(
+            history[0].transaction_hash,
+            Some("tx_alice_delete_key".to_string())
+        );
+        assert_eq!(history[1].cause, "ADD_KEY");
+        assert_eq!(
+            history[1].permission,
+            Some(accounts::schemas::AccessKeyPermission::FullAccess)
+        );
+        assert_eq!(
+            history[1].transaction_hash,
+            Some("tx_create_alice".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_access_key_usage_seeded() {
+        let db = init_seeded_db().await;
+        let account = near_primitives::types::AccountId::from_str("dave.near").unwrap();
+        let block = get_seeded_block(103);
+
+        let usage = get_access_key_usage(&db.pool, &account, "ed25519:dave_app", &block, 10)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            usage.allowance,
+            Some(types::U128(250_000_000_000_000_000_000_000))
+        );
+        assert_eq!(usage.spent.0, 7_400_000_000_000_000_000_000);
+        assert_eq!(
+            usage.remaining_allowance,
+            Some(types::U128(242_600_000_000_000_000_000_000))
+        );
+        let spent: Vec<(&str, u128, u128)> = usage
+            .transactions
+            .iter()
+            .map(|transaction| {
+                (
+                    transaction.transaction_hash.as_str(),
+                    transaction.spent.0,
+                    transaction.total_spent.0,
+                )
+            })
+            .collect();
+        assert_eq!(
+            spent,
+            vec![
+                (
+                    "tx_dave_vote",
+                    4_200_000_000_000_000_000_000,
+                    7_400_000_000_000_000_000_000
+                ),
+                (
+                    "tx_dave_early_vote",
+                    3_200_000_000_000_000_000_000,
+                    3_200_000_000_000_000_000_000
+                ),
+            ]
+        );
+
+        // The total is counted over all the transactions
+        let usage = get_access_key_usage(&db.pool, &account, "ed25519:dave_app", &block, 1)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(usage.transactions.len(), 1);
+        assert_eq!(usage.spent.0, 7_400_000_000_000_000_000_000);
+
+        assert!(
+            get_access_key_usage(&db.pool, &account, "ed25519:unknown", &block, 10)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let alice = near_primitives::types::AccountId::from_str("alice.near").unwrap();
+        let block = get_seeded_block(102);
+        assert!(
+            get_access_key_usage(&db.pool, &alice, "ed25519:alice", &block, 10)
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use crate::modules::accounts;
+use crate::{db_helpers, types};
+
+/// Each subquery is the single lookup by `action_receipt_actions_predecessor_idx` or `action_receipt_actions_receiver_idx`.
+/// `None` if the account has no activity before the block
+pub(crate) async fn get_account_activity_summary(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    account_id: &near_primitives::types::AccountId,
+    block: &db_helpers::Block,
+) -> crate::Result<Option<accounts::schemas::AccountActivitySummaryResponse>> {
+    let query = r"
+        SELECT
+            activity.first_seen,
+            first_block.block_height first_seen_block_height,
+            first_block.block_hash first_seen_block_hash,
+            activity.last_active,
+            last_block.block_height last_active_block_height,
+            last_block.block_hash last_active_block_hash,
+            coalesce(activity.is_deleted, false) is_deleted
+        FROM (
+            SELECT
+                (SELECT receipt_included_in_block_timestamp
+                 FROM action_receipt_actions
+                 WHERE receipt_receiver_account_id = $1
+                    AND receipt_included_in_block_timestamp <= $2::numeric(20, 0)
+                 ORDER BY receipt_included_in_block_timestamp
+                 LIMIT 1
+                ) first_seen,
+                greatest(
+                    (SELECT receipt_included_in_block_timestamp
+                     FROM action_receipt_actions
+                     WHERE receipt_predecessor_account_id = $1
+                        AND receipt_included_in_block_timestamp <= $2::numeric(20, 0)
+                     ORDER BY receipt_included_in_block_timestamp DESC
+                     LIMIT 1),
+                    (SELECT receipt_included_in_block_timestamp
+                     FROM action_receipt_actions
+                     WHERE receipt_receiver_account_id = $1
+                        AND receipt_included_in_block_timestamp <= $2::numeric(20, 0)
+                     ORDER BY receipt_included_in_block_timestamp DESC
+                     LIMIT 1)
+                ) last_active,
+                (SELECT action_kind = 'DELETE_ACCOUNT'
+                 FROM action_receipt_actions
+                    JOIN execution_outcomes ON action_receipt_actions.receipt_id = execution_outcomes.receipt_id
+                 WHERE receipt_receiver_account_id = $1
+                    AND action_kind IN ('CREATE_ACCOUNT', 'DELETE_ACCOUNT')
+                    AND execution_outcomes.status IN ('SUCCESS_VALUE', 'SUCCESS_RECEIPT_ID')
+                    AND receipt_included_in_block_timestamp <= $2::numeric(20, 0)
+                 ORDER BY receipt_included_in_block_timestamp DESC, index_in_action_receipt DESC
+                 LIMIT 1
+                ) is_deleted
+        ) activity
+            LEFT JOIN blocks first_block ON first_block.block_timestamp = activity.first_seen
+            LEFT JOIN blocks last_block ON last_block.block_timestamp = activity.last_active
+    ";
+    let summary = db_helpers::select_retry_or_panic::<super::models::ActivitySummaryInfo>(
+        pool,
+        query,
+        &[account_id.to_string(), block.timestamp.to_string()],
+    )
+    .await?;
+    let summary = match summary.first() {
+        Some(summary) => summary,
+        None => return Ok(None),
+    };
+    let (first_seen, first_seen_block_height, first_seen_block_hash) = match (
+        &summary.first_seen,
+        &summary.first_seen_block_height,
+        &summary.first_seen_block_hash,
+    ) {
+        (Some(a), Some(b), Some(c)) => (a, b, c),
+        _ => return Ok(None),
+    };
+    let (last_active, last_active_block_height, last_active_block_hash) = match (
+        &summary.last_active,
+        &summary.last_active_block_height,
+        &summary.last_active_block_hash,
+    ) {
+        (Some(a), Some(b), Some(c)) => (a, b, c),
+        _ => return Ok(None),
+    };
+
+    Ok(Some(accounts::schemas::AccountActivitySummaryResponse {
+        first_seen_timestamp_nanos: types::numeric::to_u64(first_seen)?.into(),
+        first_seen_block_height: types::numeric::to_u64(first_seen_block_height)?.into(),
+        first_seen_block_hash: first_seen_block_hash.clone(),
+        last_active_timestamp_nanos: types::numeric::to_u64(last_active)?.into(),
+        last_active_block_height: types::numeric::to_u64(last_active_block_height)?.into(),
+        last_active_block_hash: last_active_block_hash.clone(),
+        is_deleted: summary.is_deleted,
+        meta: types::ResponseMeta::new(&block),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_account_activity_summary_seeded() {
+        let db = init_seeded_db().await;
+        let block = get_seeded_block(103);
+
+        let alice = near_primitives::types::AccountId::from_str("alice.near").unwrap();
+        let summary = get_account_activity_summary(&db.pool, &alice, &block)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(summary.first_seen_block_height.0, 100);
+        assert_eq!(summary.first_seen_block_hash, get_seeded_block_hash(100));
+        assert_eq!(summary.last_active_block_height.0, 103);
+        assert_eq!(summary.last_active_block_hash, get_seeded_block_hash(103));
+        assert_eq!(summary.last_active_timestamp_nanos.0, 1600000000000000103);
+        assert!(!summary.is_deleted);
+
+        let bob = near_primitives::types::AccountId::from_str("bob.near").unwrap();
+        let summary = get_account_activity_summary(&db.pool, &bob, &block)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(summary.is_deleted);
+        let before_deletion = get_seeded_block(100);
+        let summary = get_account_activity_summary(&db.pool, &bob, &before_deletion)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!summary.is_deleted);
+        assert_eq!(summary.last_active_block_height.0, 100);
+
+        let nobody = near_primitives::types::AccountId::from_str("nobody.near").unwrap();
+        assert!(get_account_activity_summary(&db.pool, &nobody, &block)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
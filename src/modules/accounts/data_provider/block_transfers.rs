@@ -0,0 +1,164 @@
+use crate::modules::accounts;
+use crate::{balances_schema, db_helpers, errors, types};
+
+/// Per each kind of transfers. Even the busiest blocks do not have so many
+const MAX_TRANSFERS_PER_KIND: u32 = 1000;
+
+pub(crate) async fn get_block_transfers(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    balances_db: &balances_schema::BalancesDb,
+    block: &db_helpers::Block,
+) -> crate::Result<Vec<accounts::schemas::BlockTransfer>> {
+    // One NEAR transfer gives 2 balance changes, we take the sender's one
+    let near_query = format!(
+        r"
+        SELECT
+            'nearnative' standard,
+            NULL::text contract_account_id,
+            NULL::text token_id,
+            affected_account_id old_account_id,
+            involved_account_id new_account_id,
+            -(delta_nonstaked_amount + delta_staked_amount) amount,
+            cause,
+            status,
+            receipt_id
+        FROM {}
+        WHERE block_timestamp = $1::numeric(20, 0)
+            AND cause = 'TRANSFER'
+            AND direction = 'OUTBOUND'
+        ORDER BY shard_id, index_in_chunk
+        LIMIT $2::numeric(20, 0)
+    ",
+        balances_db.balance_changes()
+    );
+    let ft_query = r"
+        SELECT
+            'nep141' standard,
+            emitted_by_contract_account_id contract_account_id,
+            NULL::text token_id,
+            token_old_owner_account_id old_account_id,
+            token_new_owner_account_id new_account_id,
+            amount::numeric(45, 0) amount,
+            event_kind::text cause,
+            CASE WHEN execution_outcomes.status IN ('SUCCESS_VALUE', 'SUCCESS_RECEIPT_ID') THEN 'SUCCESS'
+                ELSE 'FAILURE'
+            END status,
+            emitted_for_receipt_id receipt_id
+        FROM assets__fungible_token_events
+            JOIN execution_outcomes ON assets__fungible_token_events.emitted_for_receipt_id = execution_outcomes.receipt_id
+        WHERE emitted_at_block_timestamp = $1::numeric(20, 0)
+        ORDER BY emitted_in_shard_id, emitted_index_of_event_entry_in_shard
+        LIMIT $2::numeric(20, 0)
+    ";
+    let nft_query = r"
+        SELECT
+            'nep171' standard,
+            emitted_by_contract_account_id contract_account_id,
+            token_id,
+            token_old_owner_account_id old_account_id,
+            token_new_owner_account_id new_account_id,
+            NULL::numeric(45, 0) amount,
+            event_kind::text cause,
+            CASE WHEN execution_outcomes.status IN ('SUCCESS_VALUE', 'SUCCESS_RECEIPT_ID') THEN 'SUCCESS'
+                ELSE 'FAILURE'
+            END status,
+            emitted_for_receipt_id receipt_id
+        FROM assets__non_fungible_token_events
+            JOIN execution_outcomes ON assets__non_fungible_token_events.emitted_for_receipt_id = execution_outcomes.receipt_id
+        WHERE emitted_at_block_timestamp = $1::numeric(20, 0)
+        ORDER BY emitted_in_shard_id, emitted_index_of_event_entry_in_shard
+        LIMIT $2::numeric(20, 0)
+    ";
+
+    let params = [
+        block.timestamp.to_string(),
+        MAX_TRANSFERS_PER_KIND.to_string(),
+    ];
+    let mut transfers = db_helpers::select_retry_or_panic::<super::models::BlockTransferInfo>(
+        &balances_db.pool,
+        &near_query,
+        &params,
+    )
+    .await?;
+    for query in [ft_query, nft_query] {
+        transfers.append(
+            &mut db_helpers::select_retry_or_panic::<super::models::BlockTransferInfo>(
+                pool, query, &params,
+            )
+            .await?,
+        );
+    }
+
+    let mut result: Vec<accounts::schemas::BlockTransfer> = vec![];
+    for transfer in transfers {
+        result.push(transfer.try_into()?);
+    }
+    Ok(result)
+}
+
+impl TryFrom<super::models::BlockTransferInfo> for accounts::schemas::BlockTransfer {
+    type Error = errors::Error;
+
+    fn try_from(info: super::models::BlockTransferInfo) -> crate::Result<Self> {
+        let extract = |account_id: Option<String>| -> crate::Result<Option<types::AccountId>> {
+            Ok(
+                types::account_id::extract_account_id(&account_id.unwrap_or_default())?
+                    .map(|id| id.into()),
+            )
+        };
+        Ok(Self {
+            standard: info.standard,
+            contract_account_id: extract(info.contract_account_id)?,
+            token_id: info.token_id,
+            old_account_id: extract(info.old_account_id)?,
+            new_account_id: extract(info.new_account_id)?,
+            amount: match info.amount {
+                Some(amount) => Some(types::numeric::to_u128(&amount)?.into()),
+                None => None,
+            },
+            cause: info.cause,
+            status: info.status,
+            receipt_id: info.receipt_id,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+
+    #[tokio::test]
+    async fn test_block_transfers_seeded() {
+        let db = init_seeded_db().await;
+        let block = get_seeded_block(101);
+
+        let transfers = get_block_transfers(&db.pool, &db.balances_db(), &block)
+            .await
+            .unwrap();
+        let kinds: Vec<(&str, &str, Option<u128>)> = transfers
+            .iter()
+            .map(|item| {
+                (
+                    item.standard.as_str(),
+                    item.cause.as_str(),
+                    item.amount.as_ref().map(|amount| amount.0),
+                )
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("nearnative", "TRANSFER", Some(300)),
+                ("nep141", "TRANSFER", Some(300)),
+                ("nep171", "MINT", None),
+            ]
+        );
+        assert_eq!(
+            transfers[0].new_account_id.as_ref().unwrap().to_string(),
+            "bob.near"
+        );
+        assert_eq!(transfers[2].token_id.as_deref(), Some("1"));
+        assert_eq!(transfers[2].old_account_id, None);
+    }
+}
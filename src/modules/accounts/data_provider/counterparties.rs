@@ -0,0 +1,104 @@
+use crate::modules::accounts;
+use crate::{balances_schema, db_helpers, errors, types};
+
+pub(crate) async fn get_counterparties(
+    balances_db: &balances_schema::BalancesDb,
+    account_id: &near_primitives::types::AccountId,
+    block: &db_helpers::Block,
+    window_nanos: u64,
+    limit: u32,
+) -> crate::Result<Vec<accounts::schemas::Counterparty>> {
+    // Gas is paid to the protocol, not to the counterparty
+    let query = format!(
+        r"
+        SELECT
+            involved_account_id account_id,
+            sum(greatest(-delta, 0)) sent,
+            sum(greatest(delta, 0)) received,
+            count(*) transfers_count
+        FROM (
+            SELECT involved_account_id, delta_nonstaked_amount + delta_staked_amount delta
+            FROM {}
+            WHERE affected_account_id = $1
+                AND involved_account_id IS NOT NULL
+                AND involved_account_id != $1
+                AND status = 'SUCCESS'
+                AND cause NOT IN ('GAS', 'GAS_REFUND')
+                AND block_timestamp > $2::numeric(20, 0)
+                AND block_timestamp <= $3::numeric(20, 0)
+        ) changes
+        GROUP BY involved_account_id
+        ORDER BY sum(abs(delta)) DESC, involved_account_id
+        LIMIT $4::numeric(20, 0)
+    ",
+        balances_db.balance_changes()
+    );
+    let counterparties = db_helpers::select_retry_or_panic::<super::models::CounterpartyInfo>(
+        &balances_db.pool,
+        &query,
+        &[
+            account_id.to_string(),
+            block.timestamp.saturating_sub(window_nanos).to_string(),
+            block.timestamp.to_string(),
+            limit.to_string(),
+        ],
+    )
+    .await?;
+
+    let mut result: Vec<accounts::schemas::Counterparty> = vec![];
+    for counterparty in counterparties {
+        result.push(counterparty.try_into()?);
+    }
+    Ok(result)
+}
+
+impl TryFrom<super::models::CounterpartyInfo> for accounts::schemas::Counterparty {
+    type Error = errors::Error;
+
+    fn try_from(info: super::models::CounterpartyInfo) -> crate::Result<Self> {
+        let sent = types::numeric::to_u128(&info.sent)?;
+        let received = types::numeric::to_u128(&info.received)?;
+        Ok(Self {
+            account_id: info
+                .account_id
+                .parse::<near_primitives::types::AccountId>()?
+                .into(),
+            sent: sent.into(),
+            received: received.into(),
+            total: sent.saturating_add(received).into(),
+            transfers_count: info.transfers_count as u64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_counterparties_seeded() {
+        let db = init_seeded_db().await;
+        let account = near_primitives::types::AccountId::from_str("alice.near").unwrap();
+        let block = get_seeded_block(103);
+
+        let counterparties = get_counterparties(&db.balances_db(), &account, &block, 1_000_000, 10)
+            .await
+            .unwrap();
+        assert_eq!(counterparties.len(), 2);
+        assert_eq!(counterparties[0].account_id.to_string(), "bob.near");
+        assert_eq!(counterparties[0].sent.0, 300);
+        assert_eq!(counterparties[0].received.0, 0);
+        assert_eq!(counterparties[1].account_id.to_string(), "pool.near");
+        assert_eq!(counterparties[1].received.0, 50);
+        assert_eq!(counterparties[1].total.0, 50);
+
+        // The transfer to bob.near is at block 101, outside of the window
+        let counterparties = get_counterparties(&db.balances_db(), &account, &block, 2, 10)
+            .await
+            .unwrap();
+        assert_eq!(counterparties.len(), 1);
+        assert_eq!(counterparties[0].account_id.to_string(), "pool.near");
+    }
+}
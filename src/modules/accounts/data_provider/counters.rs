@@ -0,0 +1,143 @@
+use crate::modules::accounts;
+use crate::{cache, db_helpers, errors, types};
+
+// The latest counters are not exact anyway, the account could send something right now
+const LATEST_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+// The counters for the given block never change, TTL only limits the memory usage
+const HISTORICAL_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// The key is the account and the block height, `None` means the latest block
+pub(crate) type CountersCache = cache::TtlCache<
+    (near_primitives::types::AccountId, Option<u64>),
+    accounts::schemas::AccountCountersResponse,
+>;
+
+/// If `is_latest` is true, the counters computed for the recent block could be returned
+/// instead of the given one. `block_height` in the response tells which block is used
+pub(crate) async fn get_account_counters(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    cache: &CountersCache,
+    account_id: &near_primitives::types::AccountId,
+    block: &db_helpers::Block,
+    is_latest: bool,
+) -> crate::Result<accounts::schemas::AccountCountersResponse> {
+    let (key, ttl) = if is_latest {
+        ((account_id.clone(), None), LATEST_CACHE_TTL)
+    } else {
+        (
+            (account_id.clone(), Some(block.height)),
+            HISTORICAL_CACHE_TTL,
+        )
+    };
+    if let Some(counters) = cache.get(&key, ttl) {
+        return Ok(counters);
+    }
+
+    let counters = compute_account_counters(pool, account_id, block).await?;
+    cache.insert(key, counters.clone());
+    Ok(counters)
+}
+
+async fn compute_account_counters(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    account_id: &near_primitives::types::AccountId,
+    block: &db_helpers::Block,
+) -> crate::Result<accounts::schemas::AccountCountersResponse> {
+    let query = r"
+        SELECT
+            (SELECT count(*)
+             FROM transactions
+             WHERE signer_account_id = $1 AND block_timestamp <= $2::numeric(20, 0)
+            ) transactions_count,
+            (SELECT count(DISTINCT receipt_receiver_account_id)
+             FROM action_receipt_actions
+             WHERE receipt_predecessor_account_id = $1
+                AND receipt_receiver_account_id != $1
+                AND action_kind = 'FUNCTION_CALL'
+                AND receipt_included_in_block_timestamp <= $2::numeric(20, 0)
+            ) contracts_count,
+            min(receipt_included_in_block_timestamp) first_activity,
+            max(receipt_included_in_block_timestamp) last_activity
+        FROM action_receipt_actions
+        WHERE (receipt_predecessor_account_id = $1 OR receipt_receiver_account_id = $1)
+            AND receipt_included_in_block_timestamp <= $2::numeric(20, 0)
+    ";
+    let counters = db_helpers::select_retry_or_panic::<super::models::CountersInfo>(
+        pool,
+        query,
+        &[account_id.to_string(), block.timestamp.to_string()],
+    )
+    .await?;
+    let counters = counters.first().ok_or_else(|| {
+        errors::ErrorKind::DBError("Aggregation query returned nothing".to_string())
+    })?;
+
+    Ok(accounts::schemas::AccountCountersResponse {
+        transactions_count: counters.transactions_count as u64,
+        contracts_count: counters.contracts_count as u64,
+        first_activity_timestamp_nanos: match &counters.first_activity {
+            Some(timestamp) => Some(types::numeric::to_u64(timestamp)?.into()),
+            None => None,
+        },
+        last_activity_timestamp_nanos: match &counters.last_activity {
+            Some(timestamp) => Some(types::numeric::to_u64(timestamp)?.into()),
+            None => None,
+        },
+        meta: types::ResponseMeta::new(&block),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_account_counters_seeded() {
+        let db = init_seeded_db().await;
+        let cache = CountersCache::default();
+        let account = near_primitives::types::AccountId::from_str("alice.near").unwrap();
+        let block = get_seeded_block(103);
+
+        let counters = get_account_counters(&db.pool, &cache, &account, &block, false)
+            .await
+            .unwrap();
+        assert_eq!(counters.transactions_count, 2);
+        // pool.poolv1.near, marketplace.paras.near
+        assert_eq!(counters.contracts_count, 2);
+        assert_eq!(
+            counters.first_activity_timestamp_nanos,
+            Some(types::U64(1600000000000000100))
+        );
+        assert_eq!(
+            counters.last_activity_timestamp_nanos,
+            Some(types::U64(1600000000000000103))
+        );
+
+        let earlier_block = get_seeded_block(101);
+        let counters = get_account_counters(&db.pool, &cache, &account, &earlier_block, false)
+            .await
+            .unwrap();
+        assert_eq!(counters.transactions_count, 1);
+        assert_eq!(counters.contracts_count, 0);
+        assert_eq!(counters.meta.block_height.0, 101);
+    }
+
+    #[tokio::test]
+    async fn test_latest_account_counters_are_cached() {
+        let db = init_seeded_db().await;
+        let cache = CountersCache::default();
+        let account = near_primitives::types::AccountId::from_str("alice.near").unwrap();
+        let block = get_seeded_block(102);
+        let newer_block = get_seeded_block(103);
+
+        get_account_counters(&db.pool, &cache, &account, &block, true)
+            .await
+            .unwrap();
+        let counters = get_account_counters(&db.pool, &cache, &account, &newer_block, true)
+            .await
+            .unwrap();
+        assert_eq!(counters.meta.block_height.0, 102);
+    }
+}
@@ -0,0 +1,97 @@
+use std::str::FromStr;
+
+use crate::modules::accounts;
+use crate::types::code_change::get_code_hash;
+use crate::{db_helpers, errors, types};
+
+pub(crate) async fn get_deployments(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    contract_id: &near_primitives::types::AccountId,
+    block: &db_helpers::Block,
+    limit: u32,
+) -> crate::Result<Vec<accounts::schemas::Deployment>> {
+    let query = r"
+        SELECT
+            action_receipt_actions.receipt_id,
+            args->>'code_sha256' code_sha256,
+            receipt_predecessor_account_id deployer_account_id,
+            CASE WHEN execution_outcomes.status IN ('SUCCESS_VALUE', 'SUCCESS_RECEIPT_ID') THEN 'SUCCESS'
+                ELSE 'FAILURE'
+            END status,
+            blocks.block_timestamp,
+            blocks.block_height,
+            blocks.block_hash
+        FROM action_receipt_actions
+            JOIN execution_outcomes ON action_receipt_actions.receipt_id = execution_outcomes.receipt_id
+            JOIN blocks ON execution_outcomes.executed_in_block_hash = blocks.block_hash
+        WHERE receipt_receiver_account_id = $1
+            AND action_kind = 'DEPLOY_CONTRACT'
+            AND receipt_included_in_block_timestamp <= $2::numeric(20, 0)
+        ORDER BY receipt_included_in_block_timestamp DESC
+        LIMIT $3::numeric(20, 0)
+    ";
+    let deployments = db_helpers::select_retry_or_panic::<super::models::DeploymentInfo>(
+        pool,
+        query,
+        &[
+            contract_id.to_string(),
+            block.timestamp.to_string(),
+            limit.to_string(),
+        ],
+    )
+    .await?;
+
+    let mut result: Vec<accounts::schemas::Deployment> = vec![];
+    for deployment in deployments {
+        result.push(deployment.try_into()?);
+    }
+    Ok(result)
+}
+
+impl TryFrom<super::models::DeploymentInfo> for accounts::schemas::Deployment {
+    type Error = errors::Error;
+
+    fn try_from(info: super::models::DeploymentInfo) -> crate::Result<Self> {
+        Ok(Self {
+            code_hash: get_code_hash(&info.code_sha256)?,
+            deployer_account_id: near_primitives::types::AccountId::from_str(
+                &info.deployer_account_id,
+            )?
+            .into(),
+            receipt_id: info.receipt_id,
+            status: info.status,
+            block_timestamp_nanos: types::numeric::to_u64(&info.block_timestamp)?.into(),
+            block_height: types::numeric::to_u64(&info.block_height)?.into(),
+            block_hash: info.block_hash,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+
+    #[tokio::test]
+    async fn test_deployments_seeded() {
+        let db = init_seeded_db().await;
+        let contract = near_primitives::types::AccountId::from_str("nft.near").unwrap();
+        let block = get_seeded_block(103);
+
+        let deployments = get_deployments(&db.pool, &contract, &block, 10)
+            .await
+            .unwrap();
+        assert_eq!(deployments.len(), 2);
+        assert_eq!(deployments[0].block_height.0, 102);
+        assert_eq!(deployments[0].block_hash, get_seeded_block_hash(102));
+        assert_eq!(deployments[0].status, "FAILURE");
+        assert_eq!(deployments[1].block_height.0, 100);
+        assert_eq!(deployments[1].status, "SUCCESS");
+        assert_eq!(deployments[1].deployer_account_id.to_string(), "nft.near");
+
+        let deployments = get_deployments(&db.pool, &contract, &block, 1)
+            .await
+            .unwrap();
+        assert_eq!(deployments.len(), 1);
+    }
+}
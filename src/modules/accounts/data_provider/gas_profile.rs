@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::modules::accounts;
+use crate::{db_helpers, types};
+
+/// `None` if the transaction is not included at the given block
+pub(crate) async fn get_gas_profile(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    transaction_hash: &str,
+    block: &db_helpers::Block,
+) -> crate::Result<Option<accounts::schemas::GasProfile>> {
+    let transactions = db_helpers::select_retry_or_panic::<super::models::TransactionInfo>(
+        pool,
+        r"
+            SELECT transaction_hash, signer_account_id, receiver_account_id
+            FROM transactions
+            WHERE transaction_hash = $1 AND block_timestamp <= $2::numeric(20, 0)
+        ",
+        &[transaction_hash.to_string(), block.timestamp.to_string()],
+    )
+    .await?;
+    let transaction = match transactions.first() {
+        Some(transaction) => transaction,
+        None => return Ok(None),
+    };
+
+    let query = r"
+        SELECT
+            action_receipts.receipt_id,
+            action_kind::text,
+            args,
+            action_receipts.predecessor_account_id,
+            action_receipts.receiver_account_id,
+            CASE WHEN execution_outcomes.status IN ('SUCCESS_VALUE', 'SUCCESS_RECEIPT_ID') THEN 'SUCCESS'
+                ELSE 'FAILURE'
+            END status,
+            execution_outcomes.gas_burnt,
+            execution_outcomes.tokens_burnt,
+            blocks.block_timestamp,
+            blocks.block_height,
+            blocks.block_hash
+        FROM action_receipts
+            JOIN action_receipt_actions ON action_receipts.receipt_id = action_receipt_actions.receipt_id
+            JOIN execution_outcomes ON action_receipts.receipt_id = execution_outcomes.receipt_id
+            JOIN blocks ON execution_outcomes.executed_in_block_hash = blocks.block_hash
+        WHERE action_receipts.originated_from_transaction_hash = $1
+            AND blocks.block_timestamp <= $2::numeric(20, 0)
+        ORDER BY blocks.block_timestamp, execution_outcomes.index_in_chunk, index_in_action_receipt
+    ";
+    let rows = db_helpers::select_retry_or_panic::<super::models::ReceiptGasInfo>(
+        pool,
+        query,
+        &[transaction_hash.to_string(), block.timestamp.to_string()],
+    )
+    .await?;
+
+    let mut receipts: Vec<accounts::schemas::ReceiptGas> = vec![];
+    // The kind and the method of the main action of each receipt
+    let mut main_actions: Vec<(String, Option<String>)> = vec![];
+    for row in &rows {
+        let action = super::receipts::parse_action(&row.action_kind, &row.args)?;
+        let method_name = match &action {
+            accounts::schemas::Action::FunctionCall { method_name, .. } => {
+                Some(method_name.clone())
+            }
+            _ => None,
+        };
+        match (receipts.last_mut(), main_actions.last_mut()) {
+            (Some(receipt), Some(main_action)) if receipt.receipt_id == row.receipt_id => {
+                // The first function call is the main one, the receipt may start with e.g. a transfer
+                if main_action.1.is_none() && method_name.is_some() {
+                    *main_action = (row.action_kind.clone(), method_name);
+                }
+                receipt.actions.push(action);
+            }
+            _ => {
+                main_actions.push((row.action_kind.clone(), method_name));
+                receipts.push(accounts::schemas::ReceiptGas {
+                    receipt_id: row.receipt_id.clone(),
+                    predecessor_account_id: near_primitives::types::AccountId::from_str(
+                        &row.predecessor_account_id,
+                    )?
+                    .into(),
+                    receiver_account_id: near_primitives::types::AccountId::from_str(
+                        &row.receiver_account_id,
+                    )?
+                    .into(),
+                    status: row.status.clone(),
+                    gas_burnt: types::numeric::to_u64(&row.gas_burnt)?.into(),
+                    tokens_burnt: types::numeric::to_u128(&row.tokens_burnt)?.into(),
+                    actions: vec![action],
+                    block_timestamp_nanos: types::numeric::to_u64(&row.block_timestamp)?.into(),
+                    block_height: types::numeric::to_u64(&row.block_height)?.into(),
+                    block_hash: row.block_hash.clone(),
+                });
+            }
+        }
+    }
+
+    Ok(Some(accounts::schemas::GasProfile {
+        transaction_hash: transaction.transaction_hash.clone(),
+        signer_account_id: near_primitives::types::AccountId::from_str(
+            &transaction.signer_account_id,
+        )?
+        .into(),
+        receiver_account_id: near_primitives::types::AccountId::from_str(
+            &transaction.receiver_account_id,
+        )?
+        .into(),
+        gas_burnt: receipts
+            .iter()
+            .map(|receipt| receipt.gas_burnt.0)
+            .sum::<u64>()
+            .into(),
+        tokens_burnt: receipts
+            .iter()
+            .map(|receipt| receipt.tokens_burnt.0)
+            .sum::<u128>()
+            .into(),
+        actions: sum_by_action(&receipts, main_actions),
+        receipts,
+    }))
+}
+
+fn sum_by_action(
+    receipts: &[accounts::schemas::ReceiptGas],
+    main_actions: Vec<(String, Option<String>)>,
+) -> Vec<accounts::schemas::ActionGas> {
+    let mut actions: Vec<accounts::schemas::ActionGas> = vec![];
+    let mut positions: HashMap<(String, types::AccountId, Option<String>), usize> = HashMap::new();
+    for (receipt, (kind, method_name)) in receipts.iter().zip(main_actions) {
+        let key = (kind, receipt.receiver_account_id.clone(), method_name);
+        let position = *positions.entry(key.clone()).or_insert_with(|| {
+            actions.push(accounts::schemas::ActionGas {
+                kind: key.0,
+                receiver_account_id: key.1,
+                method_name: key.2,
+                receipts_count: 0,
+                gas_burnt: types::U64(0),
+                tokens_burnt: types::U128(0),
+            });
+            actions.len() - 1
+        });
+        let action = &mut actions[position];
+        action.receipts_count += 1;
+        action.gas_burnt.0 += receipt.gas_burnt.0;
+        action.tokens_burnt.0 += receipt.tokens_burnt.0;
+    }
+    // Stable, so the equal ones stay in the order of execution
+    actions.sort_by(|a, b| b.gas_burnt.0.cmp(&a.gas_burnt.0));
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+
+    #[tokio::test]
+    async fn test_gas_profile_seeded() {
+        let db = init_seeded_db().await;
+        let block = get_seeded_block(103);
+
+        let profile = get_gas_profile(&db.pool, "tx_dave_vote", &block)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(profile.signer_account_id.to_string(), "dave.near");
+        assert_eq!(profile.gas_burnt.0, 3_000_000_000_000);
+        assert_eq!(profile.tokens_burnt.0, 300_000_000_000_000_000_000);
+        let receipt_ids: Vec<&str> = profile
+            .receipts
+            .iter()
+            .map(|receipt| receipt.receipt_id.as_str())
+            .collect();
+        assert_eq!(
+            receipt_ids,
+            vec!["receipt_dave_vote", "receipt_app_callback"]
+        );
+        assert_eq!(profile.receipts[0].actions.len(), 2);
+        assert_eq!(profile.receipts[0].block_hash, get_seeded_block_hash(103));
+
+        let methods: Vec<(Option<&str>, u64)> = profile
+            .actions
+            .iter()
+            .map(|action| (action.method_name.as_deref(), action.gas_burnt.0))
+            .collect();
+        assert_eq!(
+            methods,
+            vec![
+                (Some("vote"), 2_400_000_000_000),
+                (Some("on_vote"), 600_000_000_000)
+            ]
+        );
+
+        // Not included yet at the given block
+        let block = get_seeded_block(102);
+        assert!(get_gas_profile(&db.pool, "tx_dave_vote", &block)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,24 @@
+#[cfg(feature = "history")]
+mod access_keys;
+mod activity;
+#[cfg(feature = "history")]
+mod block_transfers;
+#[cfg(feature = "history")]
+mod counterparties;
+mod counters;
+mod deployments;
+mod gas_profile;
+mod models;
+mod receipts;
+
+#[cfg(feature = "history")]
+pub(crate) use access_keys::{get_access_key_usage, get_access_keys_history};
+pub(crate) use activity::get_account_activity_summary;
+#[cfg(feature = "history")]
+pub(crate) use block_transfers::get_block_transfers;
+#[cfg(feature = "history")]
+pub(crate) use counterparties::get_counterparties;
+pub(crate) use counters::{get_account_counters, CountersCache};
+pub(crate) use deployments::get_deployments;
+pub(crate) use gas_profile::get_gas_profile;
+pub(crate) use receipts::get_receipt;
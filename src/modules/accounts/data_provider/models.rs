@@ -0,0 +1,113 @@
+use crate::BigDecimal;
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct CountersInfo {
+    pub transactions_count: i64,
+    pub contracts_count: i64,
+    pub first_activity: Option<BigDecimal>,
+    pub last_activity: Option<BigDecimal>,
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct ActivitySummaryInfo {
+    pub first_seen: Option<BigDecimal>,
+    pub first_seen_block_height: Option<BigDecimal>,
+    pub first_seen_block_hash: Option<String>,
+    pub last_active: Option<BigDecimal>,
+    pub last_active_block_height: Option<BigDecimal>,
+    pub last_active_block_hash: Option<String>,
+    pub is_deleted: bool,
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct DeploymentInfo {
+    pub receipt_id: String,
+    pub code_sha256: String,
+    pub deployer_account_id: String,
+    pub status: String,
+    pub block_timestamp: BigDecimal,
+    pub block_height: BigDecimal,
+    pub block_hash: String,
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct ReceiptActionInfo {
+    pub receipt_id: String,
+    pub action_kind: String,
+    pub args: serde_json::Value,
+    pub predecessor_account_id: String,
+    pub receiver_account_id: String,
+    pub status: String,
+    pub block_timestamp: BigDecimal,
+    pub block_height: BigDecimal,
+    pub block_hash: String,
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct TransactionInfo {
+    pub transaction_hash: String,
+    pub signer_account_id: String,
+    pub receiver_account_id: String,
+}
+
+/// One row per action, the receipt fields are repeated
+#[derive(sqlx::FromRow)]
+pub(crate) struct ReceiptGasInfo {
+    pub receipt_id: String,
+    pub action_kind: String,
+    pub args: serde_json::Value,
+    pub predecessor_account_id: String,
+    pub receiver_account_id: String,
+    pub status: String,
+    pub gas_burnt: BigDecimal,
+    pub tokens_burnt: BigDecimal,
+    pub block_timestamp: BigDecimal,
+    pub block_height: BigDecimal,
+    pub block_hash: String,
+}
+
+#[cfg(feature = "history")]
+#[derive(sqlx::FromRow)]
+pub(crate) struct AccessKeyChangeInfo {
+    pub receipt_id: String,
+    pub cause: String,
+    pub args: serde_json::Value,
+    pub transaction_hash: Option<String>,
+    pub status: String,
+    pub block_timestamp_nanos: BigDecimal,
+    pub shard_id: BigDecimal,
+    pub index_in_chunk: BigDecimal,
+}
+
+#[cfg(feature = "history")]
+#[derive(sqlx::FromRow)]
+pub(crate) struct AccessKeyTransactionInfo {
+    pub transaction_hash: String,
+    pub receiver_account_id: String,
+    pub block_timestamp: BigDecimal,
+    pub spent: BigDecimal,
+    pub total_spent: BigDecimal,
+}
+
+#[cfg(feature = "history")]
+#[derive(sqlx::FromRow)]
+pub(crate) struct BlockTransferInfo {
+    pub standard: String,
+    pub contract_account_id: Option<String>,
+    pub token_id: Option<String>,
+    pub old_account_id: Option<String>,
+    pub new_account_id: Option<String>,
+    pub amount: Option<BigDecimal>,
+    pub cause: String,
+    pub status: String,
+    pub receipt_id: Option<String>,
+}
+
+#[cfg(feature = "history")]
+#[derive(sqlx::FromRow)]
+pub(crate) struct CounterpartyInfo {
+    pub account_id: String,
+    pub sent: BigDecimal,
+    pub received: BigDecimal,
+    pub transfers_count: i64,
+}
@@ -0,0 +1,318 @@
+use std::str::FromStr;
+
+use crate::modules::accounts;
+use crate::{db_helpers, errors, types};
+
+/// `None` if the receipt is not executed at the given block, or it has no actions (data receipts)
+pub(crate) async fn get_receipt(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    receipt_id: &str,
+    block: &db_helpers::Block,
+) -> crate::Result<Option<accounts::schemas::Receipt>> {
+    let query = r"
+        SELECT
+            action_receipt_actions.receipt_id,
+            action_kind::text,
+            args,
+            receipt_predecessor_account_id predecessor_account_id,
+            receipt_receiver_account_id receiver_account_id,
+            CASE WHEN execution_outcomes.status IN ('SUCCESS_VALUE', 'SUCCESS_RECEIPT_ID') THEN 'SUCCESS'
+                ELSE 'FAILURE'
+            END status,
+            blocks.block_timestamp,
+            blocks.block_height,
+            blocks.block_hash
+        FROM action_receipt_actions
+            JOIN execution_outcomes ON action_receipt_actions.receipt_id = execution_outcomes.receipt_id
+            JOIN blocks ON execution_outcomes.executed_in_block_hash = blocks.block_hash
+        WHERE action_receipt_actions.receipt_id = $1
+            AND blocks.block_timestamp <= $2::numeric(20, 0)
+        ORDER BY index_in_action_receipt
+    ";
+    let actions = db_helpers::select_retry_or_panic::<super::models::ReceiptActionInfo>(
+        pool,
+        query,
+        &[receipt_id.to_string(), block.timestamp.to_string()],
+    )
+    .await?;
+    let first = match actions.first() {
+        Some(first) => first,
+        None => return Ok(None),
+    };
+
+    Ok(Some(accounts::schemas::Receipt {
+        receipt_id: first.receipt_id.clone(),
+        predecessor_account_id: near_primitives::types::AccountId::from_str(
+            &first.predecessor_account_id,
+        )?
+        .into(),
+        receiver_account_id: near_primitives::types::AccountId::from_str(
+            &first.receiver_account_id,
+        )?
+        .into(),
+        status: first.status.clone(),
+        block_timestamp_nanos: types::numeric::to_u64(&first.block_timestamp)?.into(),
+        block_height: types::numeric::to_u64(&first.block_height)?.into(),
+        block_hash: first.block_hash.clone(),
+        actions: actions
+            .iter()
+            .map(|action| parse_action(&action.action_kind, &action.args))
+            .collect::<crate::Result<_>>()?,
+    }))
+}
+
+/// `args` of NEAR Indexer for Explorer, or the ones written by `crate::indexer`: they differ in the deployments
+/// and in the access key permissions
+pub(super) fn parse_action(
+    action_kind: &str,
+    args: &serde_json::Value,
+) -> crate::Result<accounts::schemas::Action> {
+    Ok(match action_kind {
+        "CREATE_ACCOUNT" => accounts::schemas::Action::CreateAccount,
+        "DEPLOY_CONTRACT" => accounts::schemas::Action::DeployContract {
+            code_hash: match args.get("code_sha256").and_then(|hash| hash.as_str()) {
+                Some(code_sha256) => types::code_change::get_code_hash(code_sha256)?,
+                None => {
+                    let code = base64::decode(get_str(args, "code")?).map_err(|err| {
+                        errors::ErrorKind::InternalError(format!(
+                            "Malformed contract code: {}",
+                            err
+                        ))
+                    })?;
+                    near_primitives::hash::hash(&code).to_string()
+                }
+            },
+        },
+        "FUNCTION_CALL" => accounts::schemas::Action::FunctionCall {
+            method_name: get_str(args, "method_name")?.to_string(),
+            args_base64: get_str(args, "args_base64")?.to_string(),
+            args_json: match args.get("args_json") {
+                Some(args_json) => Some(args_json.clone()),
+                None => decode_args(get_str(args, "args_base64")?),
+            },
+            gas: types::U64(get_u128(args, "gas")? as u64),
+            deposit: get_u128(args, "deposit")?.into(),
+        },
+        "TRANSFER" => accounts::schemas::Action::Transfer {
+            deposit: get_u128(args, "deposit")?.into(),
+        },
+        "STAKE" => accounts::schemas::Action::Stake {
+            stake: get_u128(args, "stake")?.into(),
+            public_key: get_str(args, "public_key")?.to_string(),
+        },
+        "ADD_KEY" => accounts::schemas::Action::AddKey {
+            public_key: get_str(args, "public_key")?.to_string(),
+            permission: parse_permission(&args["access_key"]["permission"])?,
+        },
+        "DELETE_KEY" => accounts::schemas::Action::DeleteKey {
+            public_key: get_str(args, "public_key")?.to_string(),
+        },
+        "DELETE_ACCOUNT" => accounts::schemas::Action::DeleteAccount {
+            beneficiary_id: near_primitives::types::AccountId::from_str(get_str(
+                args,
+                "beneficiary_id",
+            )?)?
+            .into(),
+        },
+        _ => {
+            return Err(errors::ErrorKind::InternalError(format!(
+                "Unknown action kind {}",
+                action_kind
+            ))
+            .into())
+        }
+    })
+}
+
+/// `{"permission_kind": "FUNCTION_CALL", "permission_details": {...}}` from Explorer,
+/// `"FullAccess"` or `{"FunctionCall": {...}}` from `crate::indexer`
+pub(super) fn parse_permission(
+    permission: &serde_json::Value,
+) -> crate::Result<accounts::schemas::AccessKeyPermission> {
+    let details = match permission {
+        serde_json::Value::String(kind) if kind == "FullAccess" => None,
+        serde_json::Value::Object(object) => match object.get("permission_kind") {
+            Some(kind) if kind == "FULL_ACCESS" => None,
+            Some(_) => object.get("permission_details"),
+            None => object.get("FunctionCall"),
+        }
+        .map(Some)
+        .ok_or_else(|| unknown_permission(permission))?,
+        _ => return Err(unknown_permission(permission)),
+    };
+    let details = match details {
+        Some(details) => details,
+        None => return Ok(accounts::schemas::AccessKeyPermission::FullAccess),
+    };
+    Ok(accounts::schemas::AccessKeyPermission::FunctionCall {
+        allowance: match details.get("allowance") {
+            Some(allowance) if !allowance.is_null() => Some(get_u128(details, "allowance")?.into()),
+            _ => None,
+        },
+        receiver_id: near_primitives::types::AccountId::from_str(get_str(details, "receiver_id")?)?
+            .into(),
+        method_names: details["method_names"]
+            .as_array()
+            .map(|names| {
+                names
+                    .iter()
+                    .filter_map(|name| name.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    })
+}
+
+/// The indexers keep `args_json` only for the new rows, the old ones have only `args_base64`
+fn decode_args(args_base64: &str) -> Option<serde_json::Value> {
+    let bytes = base64::decode(args_base64).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+fn unknown_permission(permission: &serde_json::Value) -> errors::Error {
+    errors::ErrorKind::InternalError(format!("Unknown access key permission {}", permission)).into()
+}
+
+fn get_str<'a>(args: &'a serde_json::Value, name: &str) -> crate::Result<&'a str> {
+    args.get(name)
+        .and_then(|value| value.as_str())
+        .ok_or_else(|| {
+            errors::ErrorKind::InternalError(format!("Action args have no {}: {}", name, args))
+                .into()
+        })
+}
+
+/// The amounts are given as strings, gas is given as the number
+fn get_u128(args: &serde_json::Value, name: &str) -> crate::Result<u128> {
+    let value = match args.get(name) {
+        Some(serde_json::Value::String(value)) => value.parse::<u128>().ok(),
+        Some(serde_json::Value::Number(value)) => value.as_u64().map(u128::from),
+        _ => None,
+    };
+    value.ok_or_else(|| {
+        errors::ErrorKind::InternalError(format!("Action args have no {}: {}", name, args)).into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+
+    #[test]
+    fn test_parse_action() {
+        assert_eq!(
+            parse_action(
+                "FUNCTION_CALL",
+                &serde_json::json!({"method_name": "ft_transfer", "args_base64": "e30=", "gas": 30000000000000u64, "deposit": "1"})
+            )
+            .unwrap(),
+            accounts::schemas::Action::FunctionCall {
+                method_name: "ft_transfer".to_string(),
+                args_base64: "e30=".to_string(),
+                args_json: Some(serde_json::json!({})),
+                gas: types::U64(30000000000000),
+                deposit: types::U128(1),
+            }
+        );
+        assert_eq!(
+            parse_action(
+                "DEPLOY_CONTRACT",
+                &serde_json::json!({"code_sha256": "0000000000000000000000000000000000000000000000000000000000000000"})
+            )
+            .unwrap(),
+            accounts::schemas::Action::DeployContract {
+                code_hash: "11111111111111111111111111111111".to_string()
+            }
+        );
+        assert_eq!(
+            decode_args(&base64::encode(r#"{"receiver_id":"bob.near"}"#)),
+            Some(serde_json::json!({"receiver_id": "bob.near"}))
+        );
+        assert_eq!(decode_args(&base64::encode([0u8, 159, 146, 150])), None);
+        assert_eq!(decode_args("not base64"), None);
+        assert!(parse_action("TRANSFER", &serde_json::json!({})).is_err());
+        assert!(parse_action("DELEGATE", &serde_json::json!({})).is_err());
+
+        // Explorer format
+        let explorer = parse_action(
+            "ADD_KEY",
+            &serde_json::json!({
+                "public_key": "ed25519:key",
+                "access_key": {"nonce": 0, "permission": {
+                    "permission_kind": "FUNCTION_CALL",
+                    "permission_details": {"allowance": null, "receiver_id": "app.near", "method_names": ["vote"]}
+                }}
+            }),
+        )
+        .unwrap();
+        // `crate::indexer` format
+        let indexer = parse_action(
+            "ADD_KEY",
+            &serde_json::json!({
+                "public_key": "ed25519:key",
+                "access_key": {"nonce": 0, "permission": {
+                    "FunctionCall": {"allowance": null, "receiver_id": "app.near", "method_names": ["vote"]}
+                }}
+            }),
+        )
+        .unwrap();
+        assert_eq!(explorer, indexer);
+        assert_eq!(
+            parse_permission(&serde_json::json!("FullAccess")).unwrap(),
+            accounts::schemas::AccessKeyPermission::FullAccess
+        );
+        assert_eq!(
+            parse_permission(&serde_json::json!({"permission_kind": "FULL_ACCESS"})).unwrap(),
+            accounts::schemas::AccessKeyPermission::FullAccess
+        );
+    }
+
+    #[tokio::test]
+    async fn test_receipt_seeded() {
+        let db = init_seeded_db().await;
+        let block = get_seeded_block(103);
+
+        let receipt = get_receipt(&db.pool, "receipt_create_alice", &block)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(receipt.status, "SUCCESS");
+        assert_eq!(receipt.block_height.0, 100);
+        assert_eq!(receipt.block_hash, get_seeded_block_hash(100));
+        assert_eq!(receipt.actions.len(), 3);
+        assert_eq!(receipt.actions[0], accounts::schemas::Action::CreateAccount);
+        assert_eq!(
+            receipt.actions[1],
+            accounts::schemas::Action::Transfer {
+                deposit: types::U128(1000)
+            }
+        );
+
+        let receipt = get_receipt(&db.pool, "receipt_alice_stake", &block)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(receipt.receiver_account_id.to_string(), "pool.poolv1.near");
+        match &receipt.actions[0] {
+            accounts::schemas::Action::FunctionCall {
+                method_name,
+                args_json,
+                gas,
+                ..
+            } => {
+                assert_eq!(method_name, "deposit_and_stake");
+                assert_eq!(args_json, &Some(serde_json::json!({})));
+                assert_eq!(gas.0, 100000000000000);
+            }
+            action => panic!("unexpected action {:?}", action),
+        }
+
+        // Not executed yet at the given block
+        let block = get_seeded_block(101);
+        assert!(get_receipt(&db.pool, "receipt_alice_stake", &block)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
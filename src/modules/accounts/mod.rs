@@ -0,0 +1,51 @@
+use paperclip::actix::web;
+
+pub(crate) mod data_provider;
+mod resources;
+pub(crate) mod schemas;
+
+pub(crate) fn register_services(app: &mut web::ServiceConfig) {
+    app.service(
+        web::resource("/accounts/{account_id}/counters")
+            .route(web::get().to(resources::get_account_counters)),
+    )
+    .service(
+        web::resource("/accounts/{account_id}/activity/summary")
+            .route(web::get().to(resources::get_account_activity_summary)),
+    )
+    .service(
+        web::resource("/contracts/{contract_account_id}/deployments")
+            .route(web::get().to(resources::get_deployments)),
+    )
+    .service(
+        web::resource("/contracts/{contract_account_id}/code-history")
+            .route(web::get().to(resources::get_code_history)),
+    )
+    .service(web::resource("/receipts/{receipt_id}").route(web::get().to(resources::get_receipt)))
+    .service(
+        web::resource("/transactions/{tx_hash}/gas-profile")
+            .route(web::get().to(resources::get_gas_profile)),
+    );
+
+    #[cfg(feature = "history")]
+    app.service(
+        web::resource("/accounts/{account_id}/counterparties")
+            .route(web::get().to(resources::get_counterparties)),
+    )
+    .service(
+        web::resource("/accounts/{account_id}/graph")
+            .route(web::get().to(resources::get_account_graph)),
+    )
+    .service(
+        web::resource("/accounts/{account_id}/keys/history")
+            .route(web::get().to(resources::get_access_keys_history)),
+    )
+    .service(
+        web::resource("/accounts/{account_id}/keys/{public_key}/usage")
+            .route(web::get().to(resources::get_access_key_usage)),
+    )
+    .service(
+        web::resource("/blocks/{block_id}/transfers")
+            .route(web::get().to(resources::get_block_transfers)),
+    );
+}
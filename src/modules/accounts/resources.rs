@@ -0,0 +1,427 @@
+use paperclip::actix::{
+    api_v2_operation,
+    web::{self, Json},
+};
+
+use crate::{data_source, errors, modules, types};
+
+use super::schemas;
+
+#[api_v2_operation(tags(Accounts))]
+/// Get account activity counters
+///
+/// This endpoint returns the number of transactions, the number of contracts the account interacted with,
+/// and the first/last activity moments of the given account_id, for the given timestamp/block_height.
+///
+/// **Limitations**
+/// * The counters are cached. If you don't provide the block, the response could be up to 5 minutes old,
+///   check `block_height` in the response.
+pub async fn get_account_counters(
+    _query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: web::Path<schemas::AccountRequest>,
+    block_params: web::Query<types::query_params::BlockParams>,
+) -> crate::Result<Json<schemas::AccountCountersResponse>> {
+    let is_latest =
+        block_params.block_height.is_none() && block_params.block_timestamp_nanos.is_none();
+    let block = data_source.get_block_from_params(&block_params).await?;
+    modules::check_account_exists(&data_source, &request.account_id.0, block.timestamp).await?;
+
+    Ok(Json(
+        data_source
+            .get_account_counters(&request.account_id.0, &block, is_latest)
+            .await?,
+    ))
+}
+
+#[api_v2_operation(tags(Accounts))]
+/// Get account activity summary
+///
+/// This endpoint returns when the given account_id was seen first and active last,
+/// and whether it is deleted, for the given timestamp/block_height.
+/// It's much cheaper than the counters, use it for dormancy checks and scoring.
+///
+/// **Limitations**
+/// * Implicit accounts are created by the transfer, they are never shown as deleted
+///   until the explicit deletion.
+pub async fn get_account_activity_summary(
+    _query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: web::Path<schemas::AccountRequest>,
+    block_params: web::Query<types::query_params::BlockParams>,
+) -> crate::Result<Json<schemas::AccountActivitySummaryResponse>> {
+    let block = data_source.get_block_from_params(&block_params).await?;
+
+    // The deleted accounts are also welcome here, so we don't check the existence
+    let summary = data_source
+        .get_account_activity_summary(&request.account_id.0, &block)
+        .await?
+        .ok_or_else(|| errors::ErrorKind::AccountNotFound {
+            account_id: request.account_id.0.to_string(),
+            block_timestamp: block.timestamp,
+        })?;
+    Ok(Json(summary))
+}
+
+#[api_v2_operation(tags(Accounts))]
+/// Get contract deployments
+///
+/// This endpoint returns the code deployments to the given contract_account_id
+/// up to the given timestamp/block_height, recent deployments go first.
+/// Check it to see when the contract was upgraded last time.
+///
+/// **Limitations**
+/// * We provide only up to `limit` items.
+///   Full-featured pagination will be provided later.
+pub async fn get_deployments(
+    query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: web::Path<schemas::ContractRequest>,
+    block_params: web::Query<types::query_params::BlockParams>,
+    mut pagination_params: web::Query<types::query_params::PaginationParams>,
+) -> crate::Result<Json<schemas::DeploymentsResponse>> {
+    pagination_params.limit = Some(query.limit);
+    let pagination = types::query_params::Pagination::from(pagination_params.0);
+    let block = data_source.get_block_from_params(&block_params).await?;
+    modules::check_account_exists(
+        &data_source,
+        &request.contract_account_id.0,
+        block.timestamp,
+    )
+    .await?;
+
+    Ok(Json(schemas::DeploymentsResponse {
+        deployments: data_source
+            .get_deployments(&request.contract_account_id.0, &block, pagination.limit)
+            .await?,
+        meta: types::ResponseMeta::new(&block),
+    }))
+}
+
+#[api_v2_operation(tags(Accounts))]
+/// Get contract code history
+///
+/// This endpoint returns the code changes of the given contract_account_id up to the given timestamp/block_height,
+/// recent changes go first. Each change has the new and the previous code hash and the receipt and transaction
+/// which deployed it. The failed deployments and the redeployments of the same code are skipped.
+/// Check it to see if the contract changed after you reviewed it.
+///
+/// **Limitations**
+/// * We provide only up to `limit` items.
+///   Full-featured pagination will be provided later.
+pub async fn get_code_history(
+    query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: web::Path<schemas::ContractRequest>,
+    block_params: web::Query<types::query_params::BlockParams>,
+    mut pagination_params: web::Query<types::query_params::PaginationParams>,
+) -> crate::Result<Json<schemas::CodeHistoryResponse>> {
+    pagination_params.limit = Some(query.limit);
+    let pagination = types::query_params::Pagination::from(pagination_params.0);
+    let block = data_source.get_block_from_params(&block_params).await?;
+    modules::check_account_exists(
+        &data_source,
+        &request.contract_account_id.0,
+        block.timestamp,
+    )
+    .await?;
+
+    Ok(Json(schemas::CodeHistoryResponse {
+        code_changes: data_source
+            .get_code_changes(&request.contract_account_id.0, &block, pagination.limit)
+            .await?,
+        meta: types::ResponseMeta::new(&block),
+    }))
+}
+
+#[api_v2_operation(tags(Accounts))]
+/// Get receipt
+///
+/// This endpoint returns the receipt with its actions decoded: the called method with its args (if they are JSON),
+/// the attached gas and deposit, the transferred amount, the added key with its permission, etc.
+/// The receipt is given only if it was executed at or before the given timestamp/block_height.
+///
+/// **Limitations**
+/// * Data receipts (the results of the cross-contract calls) are not supported.
+pub async fn get_receipt(
+    _query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: web::Path<schemas::ReceiptRequest>,
+    block_params: web::Query<types::query_params::BlockParams>,
+) -> crate::Result<Json<schemas::ReceiptResponse>> {
+    let block = data_source.get_block_from_params(&block_params).await?;
+
+    let receipt = data_source
+        .get_receipt(&request.receipt_id, &block)
+        .await?
+        .ok_or_else(|| {
+            errors::ErrorKind::InvalidInput(format!(
+                "receipt {} is not found at block {}",
+                request.receipt_id, block.height
+            ))
+        })?;
+    Ok(Json(schemas::ReceiptResponse {
+        receipt,
+        meta: types::ResponseMeta::new(&block),
+    }))
+}
+
+#[api_v2_operation(tags(Accounts))]
+/// Get gas profile of the transaction
+///
+/// This endpoint returns the gas burnt by each receipt of the given transaction and its NEAR cost,
+/// and the same summed up by the called methods, so the most expensive calls are easy to find.
+/// Only the receipts executed at or before the given timestamp/block_height are counted.
+///
+/// **Limitations**
+/// * The gas burnt for converting the transaction into the first receipt is not counted.
+/// * The gas is known per receipt, not per action. The receipt is counted in its first function call
+///   (or its first action if there are no calls), see `actions`.
+/// * `tokens_burnt` is by the gas price of the block where the receipt was executed,
+///   the refunds of the unused prepaid gas are not subtracted.
+pub async fn get_gas_profile(
+    _query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: web::Path<schemas::TransactionRequest>,
+    block_params: web::Query<types::query_params::BlockParams>,
+) -> crate::Result<Json<schemas::GasProfileResponse>> {
+    let block = data_source.get_block_from_params(&block_params).await?;
+
+    let gas_profile = data_source
+        .get_gas_profile(&request.tx_hash, &block)
+        .await?
+        .ok_or_else(|| {
+            errors::ErrorKind::InvalidInput(format!(
+                "transaction {} is not found at block {}",
+                request.tx_hash, block.height
+            ))
+        })?;
+    Ok(Json(schemas::GasProfileResponse {
+        gas_profile,
+        meta: types::ResponseMeta::new(&block),
+    }))
+}
+
+#[cfg(feature = "history")]
+#[api_v2_operation(tags(Accounts))]
+/// Get access keys history
+///
+/// This endpoint returns the keys added to and deleted from the given account_id
+/// with the transactions which did it, for the given timestamp/block_height.
+/// Check it if you suspect the key was compromised.
+///
+/// **Limitations**
+/// * We provide only up to `limit` items, where recent changes go first.
+///   Pass `meta.next_cursor` as `cursor` to get the next page.
+pub async fn get_access_keys_history(
+    http_request: actix_web::HttpRequest,
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: web::Path<schemas::AccountRequest>,
+    pagination_params: web::Query<types::query_params::HistoryPaginationParams>,
+) -> crate::Result<Json<schemas::AccessKeysHistoryResponse>> {
+    let (block, pagination) = modules::check_and_get_history_pagination_params(
+        &data_source,
+        &http_request,
+        pagination_params.0,
+    )
+    .await?;
+    modules::check_account_exists(&data_source, &request.account_id.0, block.timestamp).await?;
+    let history = data_source
+        .get_access_keys_history(&request.account_id.0, &pagination)
+        .await?;
+    let next_cursor = modules::next_history_cursor(&history, &block, &pagination);
+
+    Ok(Json(schemas::AccessKeysHistoryResponse {
+        history: modules::without_event_indexes(history),
+        meta: types::ResponseMeta::new(&block).with_next_cursor(next_cursor),
+    }))
+}
+
+#[cfg(feature = "history")]
+#[api_v2_operation(tags(Accounts))]
+/// Get function call key usage
+///
+/// This endpoint returns how much of the allowance the given function call key of account_id spent
+/// since it was added, with the transactions signed by it, for the given timestamp/block_height.
+/// Warn the users before the allowance runs out, they will have to sign in again.
+///
+/// **Limitations**
+/// * The prepaid gas is priced by the gas price of the block with the transaction.
+///   The protocol takes the higher (pessimistic) price for the cross-contract calls,
+///   so the actual `remaining_allowance` could be a bit lower.
+/// * Full access keys have no allowance, they are not supported.
+/// * We provide only up to `limit` recent transactions, `spent` is counted over all of them.
+pub async fn get_access_key_usage(
+    query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: web::Path<schemas::AccessKeyRequest>,
+    block_params: web::Query<types::query_params::BlockParams>,
+    mut pagination_params: web::Query<types::query_params::PaginationParams>,
+) -> crate::Result<Json<schemas::AccessKeyUsageResponse>> {
+    pagination_params.limit = Some(query.limit);
+    let pagination = types::query_params::Pagination::from(pagination_params.0);
+    let block = data_source.get_block_from_params(&block_params).await?;
+    modules::check_account_exists(&data_source, &request.account_id.0, block.timestamp).await?;
+
+    let key_usage = data_source
+        .get_access_key_usage(
+            &request.account_id.0,
+            &request.public_key,
+            &block,
+            pagination.limit,
+        )
+        .await?
+        .ok_or_else(|| {
+            errors::ErrorKind::InvalidInput(format!(
+                "access key {} of {} is not found at block {}",
+                request.public_key, request.account_id.0, block.height
+            ))
+        })?;
+    Ok(Json(schemas::AccessKeyUsageResponse {
+        key_usage,
+        meta: types::ResponseMeta::new(&block),
+    }))
+}
+
+#[cfg(feature = "history")]
+#[api_v2_operation(tags(Accounts))]
+/// Get block transfers
+///
+/// This endpoint returns all NEAR, FT and NFT transfers (including FT/NFT mints and burns)
+/// indexed in the given block. `block_id` is the block height or the block hash.
+/// Walk the chain block by block with it instead of reading the raw chunks from RPC.
+///
+/// **Limitations**
+/// * NEAR transfers are shown in the block where the sender was charged,
+///   the receiver could get the money in one of the next blocks.
+/// * For now, we support only FT and NFT contracts which implement Events NEP.
+/// * We provide only up to 1000 items of each kind.
+pub async fn get_block_transfers(
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: web::Path<schemas::BlockRequest>,
+) -> crate::Result<Json<schemas::BlockTransfersResponse>> {
+    let block = data_source.get_block_by_id(&request.block_id).await?;
+
+    Ok(Json(schemas::BlockTransfersResponse {
+        transfers: data_source.get_block_transfers(&block).await?,
+        meta: types::ResponseMeta::new(&block),
+    }))
+}
+
+#[cfg(feature = "history")]
+#[api_v2_operation(tags(Accounts))]
+/// Get top counterparties
+///
+/// This endpoint returns the accounts and contracts the given account_id moved the most NEAR with,
+/// for the window which ends at the given timestamp/block_height.
+/// `window` is `90d` by default.
+///
+/// **Limitations**
+/// * Only NEAR transfers are taken into account, FT amounts are not comparable between each other.
+/// * We provide only up to `limit` items.
+pub async fn get_counterparties(
+    query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: web::Path<schemas::AccountRequest>,
+    block_params: web::Query<types::query_params::BlockParams>,
+    window_params: web::Query<types::query_params::WindowParams>,
+    mut pagination_params: web::Query<types::query_params::PaginationParams>,
+) -> crate::Result<Json<schemas::CounterpartiesResponse>> {
+    pagination_params.limit = Some(query.limit);
+    let window_nanos = types::query_params::get_window_nanos(&window_params, 90)?;
+    let pagination = types::query_params::Pagination::from(pagination_params.0);
+    let block = data_source.get_block_from_params(&block_params).await?;
+    modules::check_account_exists(&data_source, &request.account_id.0, block.timestamp).await?;
+
+    Ok(Json(schemas::CounterpartiesResponse {
+        counterparties: data_source
+            .get_counterparties(
+                &request.account_id.0,
+                &block,
+                window_nanos,
+                pagination.limit,
+            )
+            .await?,
+        window_start_timestamp_nanos: types::U64::from(
+            block.timestamp.saturating_sub(window_nanos),
+        ),
+        meta: types::ResponseMeta::new(&block),
+    }))
+}
+
+/// The top counterparties of the given account which get their own counterparties on `depth=2`
+#[cfg(feature = "history")]
+const GRAPH_EXPANDED_NODES: usize = 10;
+#[cfg(feature = "history")]
+const GRAPH_MAX_DEPTH: u32 = 2;
+
+#[cfg(feature = "history")]
+#[api_v2_operation(tags(Accounts))]
+/// Get account relationship graph
+///
+/// This endpoint returns the counterparties of the given account_id as the graph ready for rendering:
+/// the accounts are the nodes, the edges have NEAR moved between the accounts and the number of transfers.
+/// With `depth=2`, the top counterparties are expanded with their own counterparties.
+/// The window ends at the given timestamp/block_height, `window` is `90d` by default.
+///
+/// **Limitations**
+/// * Only NEAR transfers are taken into account, FT amounts are not comparable between each other.
+/// * We provide up to `limit` counterparties for each expanded account,
+///   only 10 top counterparties are expanded on `depth=2`.
+pub async fn get_account_graph(
+    query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: web::Path<schemas::AccountRequest>,
+    block_params: web::Query<types::query_params::BlockParams>,
+    window_params: web::Query<types::query_params::WindowParams>,
+    graph_params: web::Query<schemas::GraphParams>,
+    mut pagination_params: web::Query<types::query_params::PaginationParams>,
+) -> crate::Result<Json<schemas::AccountGraphResponse>> {
+    pagination_params.limit = Some(query.limit);
+    let depth = graph_params.depth.unwrap_or(1);
+    if depth == 0 || depth > GRAPH_MAX_DEPTH {
+        return Err(errors::ErrorKind::InvalidInput(format!(
+            "depth should be in range [1, {}]",
+            GRAPH_MAX_DEPTH
+        ))
+        .into());
+    }
+    let window_nanos = types::query_params::get_window_nanos(&window_params, 90)?;
+    let pagination = types::query_params::Pagination::from(pagination_params.0);
+    let block = data_source.get_block_from_params(&block_params).await?;
+    modules::check_account_exists(&data_source, &request.account_id.0, block.timestamp).await?;
+
+    let counterparties = data_source
+        .get_counterparties(
+            &request.account_id.0,
+            &block,
+            window_nanos,
+            pagination.limit,
+        )
+        .await?;
+    let expanded_ids: Vec<types::AccountId> = if depth > 1 {
+        counterparties
+            .iter()
+            .take(GRAPH_EXPANDED_NODES)
+            .map(|counterparty| counterparty.account_id.clone())
+            .collect()
+    } else {
+        vec![]
+    };
+    let second_level = futures::future::try_join_all(expanded_ids.iter().map(|account_id| {
+        data_source.get_counterparties(&account_id.0, &block, window_nanos, pagination.limit)
+    }))
+    .await?;
+    let mut expanded = vec![(request.account_id.clone(), counterparties)];
+    expanded.extend(expanded_ids.into_iter().zip(second_level));
+    let (nodes, edges) = schemas::build_graph(expanded);
+
+    Ok(Json(schemas::AccountGraphResponse {
+        nodes,
+        edges,
+        window_start_timestamp_nanos: types::U64::from(
+            block.timestamp.saturating_sub(window_nanos),
+        ),
+        meta: types::ResponseMeta::new(&block),
+    }))
+}
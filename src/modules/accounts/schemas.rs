@@ -0,0 +1,466 @@
+use paperclip::actix::Apiv2Schema;
+
+use crate::types;
+
+// *** Requests ***
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct AccountRequest {
+    pub account_id: types::AccountId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct ContractRequest {
+    pub contract_account_id: types::AccountId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct BlockRequest {
+    /// Block height or block hash
+    pub block_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct ReceiptRequest {
+    pub receipt_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct TransactionRequest {
+    pub tx_hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct AccessKeyRequest {
+    pub account_id: types::AccountId,
+    /// E.g. `ed25519:...`
+    pub public_key: String,
+}
+
+#[derive(
+    Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema,
+)]
+pub struct GraphParams {
+    /// 1 by default, 2 at most. The second level is built only for the top counterparties
+    pub depth: Option<u32>,
+}
+
+// *** Responses ***
+
+/// Activity of the account up to the given block.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct AccountCountersResponse {
+    /// Transactions signed by the account
+    pub transactions_count: u64,
+    /// Distinct accounts the account called the functions on
+    pub contracts_count: u64,
+    /// The first and the last receipts sent or received by the account.
+    /// Null if the account has no activity
+    pub first_activity_timestamp_nanos: Option<types::U64>,
+    pub last_activity_timestamp_nanos: Option<types::U64>,
+    pub meta: types::ResponseMeta,
+}
+
+/// The first receipt the account received (usually its creation) and the last receipt
+/// it sent or received up to the given block.
+/// `is_deleted` is true if the last creation/deletion of the account was the deletion
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct AccountActivitySummaryResponse {
+    pub first_seen_timestamp_nanos: types::U64,
+    pub first_seen_block_height: types::U64,
+    pub first_seen_block_hash: String,
+    pub last_active_timestamp_nanos: types::U64,
+    pub last_active_block_height: types::U64,
+    pub last_active_block_hash: String,
+    pub is_deleted: bool,
+    pub meta: types::ResponseMeta,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct DeploymentsResponse {
+    pub deployments: Vec<Deployment>,
+    pub meta: types::ResponseMeta,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct CodeHistoryResponse {
+    pub code_changes: Vec<types::CodeChange>,
+    pub meta: types::ResponseMeta,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct ReceiptResponse {
+    pub receipt: Receipt,
+    pub meta: types::ResponseMeta,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct GasProfileResponse {
+    pub gas_profile: GasProfile,
+    pub meta: types::ResponseMeta,
+}
+
+/// The counterparties of the account (and of its top counterparties if `depth` is 2) as the graph.
+/// The given account has depth 0. Each pair of the accounts has at most one edge
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct AccountGraphResponse {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+    pub window_start_timestamp_nanos: types::U64,
+    pub meta: types::ResponseMeta,
+}
+
+/// The accounts with the biggest NEAR turnover with the given account in the window
+/// `[window_start_timestamp_nanos, block_timestamp_nanos]`, the biggest `total` goes first.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct CounterpartiesResponse {
+    pub counterparties: Vec<Counterparty>,
+    pub window_start_timestamp_nanos: types::U64,
+    pub meta: types::ResponseMeta,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct AccessKeysHistoryResponse {
+    pub history: Vec<AccessKeyChange>,
+    pub meta: types::ResponseMeta,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct AccessKeyUsageResponse {
+    pub key_usage: AccessKeyUsage,
+    pub meta: types::ResponseMeta,
+}
+
+/// All the transfers of the block: NEAR first, then FT, then NFT.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct BlockTransfersResponse {
+    pub transfers: Vec<BlockTransfer>,
+    pub meta: types::ResponseMeta,
+}
+
+// ---
+
+/// `standard` is one of ["nearnative", "nep141", "nep171"].
+/// `contract_account_id` is empty for NEAR, `token_id` is given only for NFT, `amount` is empty for NFT.
+/// `amount` is in yoctoNEAR for NEAR and in the smallest units of the token for FT.
+/// `old_account_id` is empty for mints, `new_account_id` is empty for burns.
+/// `cause` is the event kind for FT/NFT (["MINT", "TRANSFER", "BURN"]) and "TRANSFER" for NEAR
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct BlockTransfer {
+    pub standard: String,
+    pub contract_account_id: Option<types::AccountId>,
+    pub token_id: Option<String>,
+    pub old_account_id: Option<types::AccountId>,
+    pub new_account_id: Option<types::AccountId>,
+    pub amount: Option<types::U128>,
+    pub cause: String,
+    pub status: String,
+    pub receipt_id: Option<String>,
+}
+
+/// `cause` is one of ["ADD_KEY", "DELETE_KEY"], `permission` is given only for `ADD_KEY`.
+/// `transaction_hash` is the transaction the change originated from, null if the indexer does not know it.
+/// `status` is one of ["SUCCESS", "FAILURE"], the failed changes did not touch the keys
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct AccessKeyChange {
+    pub cause: String,
+    pub public_key: String,
+    pub permission: Option<AccessKeyPermission>,
+    pub transaction_hash: Option<String>,
+    pub receipt_id: String,
+    pub status: String,
+    pub block_timestamp_nanos: types::U64,
+}
+
+/// The allowance spent by the function call key since it was added, up to the given block.
+/// The amounts are in yoctoNEAR. `allowance` is the one the key was added with,
+/// `allowance` and `remaining_allowance` are null for the keys with unlimited allowance
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct AccessKeyUsage {
+    pub public_key: String,
+    pub permission: AccessKeyPermission,
+    pub added_at_block_timestamp_nanos: types::U64,
+    pub allowance: Option<types::U128>,
+    pub spent: types::U128,
+    pub remaining_allowance: Option<types::U128>,
+    /// The transactions signed by the key, recent first
+    pub transactions: Vec<AccessKeyTransaction>,
+}
+
+/// `spent` is the part of the allowance taken by the transaction (the failed ones pay too),
+/// `total_spent` and `remaining_allowance` are right after it
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct AccessKeyTransaction {
+    pub transaction_hash: String,
+    pub receiver_account_id: types::AccountId,
+    pub spent: types::U128,
+    pub total_spent: types::U128,
+    pub remaining_allowance: Option<types::U128>,
+    pub block_timestamp_nanos: types::U64,
+}
+
+/// NEAR amounts in yoctoNEAR, `total` is `sent + received`
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct Counterparty {
+    pub account_id: types::AccountId,
+    pub sent: types::U128,
+    pub received: types::U128,
+    pub total: types::U128,
+    pub transfers_count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct GraphNode {
+    pub account_id: types::AccountId,
+    /// Number of the edges from the given account
+    pub depth: u32,
+}
+
+/// NEAR amounts in yoctoNEAR, `sent` goes from `from_account_id` to `to_account_id`
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct GraphEdge {
+    pub from_account_id: types::AccountId,
+    pub to_account_id: types::AccountId,
+    pub sent: types::U128,
+    pub received: types::U128,
+    pub total: types::U128,
+    pub transfers_count: u64,
+}
+
+/// `DeployContract` action. `code_hash` is base58, the same as RPC `view_account` gives.
+/// `status` is one of ["SUCCESS", "FAILURE"], the failed deployments did not change the code
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct Deployment {
+    pub code_hash: String,
+    pub deployer_account_id: types::AccountId,
+    pub receipt_id: String,
+    pub status: String,
+    pub block_timestamp_nanos: types::U64,
+    pub block_height: types::U64,
+    pub block_hash: String,
+}
+
+/// `status` is one of ["SUCCESS", "FAILURE"], the actions of the failed receipt did not change the state.
+/// `block_timestamp_nanos`, `block_height`, `block_hash` point at the block where the receipt was executed
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct Receipt {
+    pub receipt_id: String,
+    pub predecessor_account_id: types::AccountId,
+    pub receiver_account_id: types::AccountId,
+    pub status: String,
+    /// In the order of execution
+    pub actions: Vec<Action>,
+    pub block_timestamp_nanos: types::U64,
+    pub block_height: types::U64,
+    pub block_hash: String,
+}
+
+/// The action of the receipt. `kind` is one of ["CREATE_ACCOUNT", "DEPLOY_CONTRACT", "FUNCTION_CALL",
+/// "TRANSFER", "STAKE", "ADD_KEY", "DELETE_KEY", "DELETE_ACCOUNT"], the other fields depend on it.
+/// The amounts are in yoctoNEAR, `code_hash` is base58, the same as RPC `view_account` gives.
+/// New kinds could be added later, so please always have a fallback branch
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Action {
+    CreateAccount,
+    DeployContract {
+        code_hash: String,
+    },
+    FunctionCall {
+        method_name: String,
+        /// The args as they were given, always present
+        args_base64: String,
+        /// The decoded args, null if they are not JSON (e.g. borsh)
+        args_json: Option<serde_json::Value>,
+        gas: types::U64,
+        deposit: types::U128,
+    },
+    Transfer {
+        deposit: types::U128,
+    },
+    Stake {
+        stake: types::U128,
+        public_key: String,
+    },
+    AddKey {
+        public_key: String,
+        permission: AccessKeyPermission,
+    },
+    DeleteKey {
+        public_key: String,
+    },
+    DeleteAccount {
+        beneficiary_id: types::AccountId,
+    },
+}
+
+impl paperclip::v2::schema::TypedData for Action {
+    fn data_type() -> paperclip::v2::models::DataType {
+        paperclip::v2::models::DataType::Object
+    }
+}
+
+/// The gas burnt by the receipts of the transaction executed up to the given block.
+/// `tokens_burnt` is the NEAR cost in yoctoNEAR, by the gas price of the blocks where the receipts were executed
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct GasProfile {
+    pub transaction_hash: String,
+    pub signer_account_id: types::AccountId,
+    pub receiver_account_id: types::AccountId,
+    /// The sum of all the receipts
+    pub gas_burnt: types::U64,
+    pub tokens_burnt: types::U128,
+    /// In the order of execution
+    pub receipts: Vec<ReceiptGas>,
+    /// The receipts summed up by their main action, the most expensive go first
+    pub actions: Vec<ActionGas>,
+}
+
+/// `status` is one of ["SUCCESS", "FAILURE"], the failed receipts also burn the gas
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct ReceiptGas {
+    pub receipt_id: String,
+    pub predecessor_account_id: types::AccountId,
+    pub receiver_account_id: types::AccountId,
+    pub status: String,
+    pub gas_burnt: types::U64,
+    pub tokens_burnt: types::U128,
+    pub actions: Vec<Action>,
+    pub block_timestamp_nanos: types::U64,
+    pub block_height: types::U64,
+    pub block_hash: String,
+}
+
+/// The main action of the receipt is its first function call, or its first action if there are no calls.
+/// `kind` is the same as in `Action`, `method_name` is null for the other kinds
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct ActionGas {
+    pub kind: String,
+    pub receiver_account_id: types::AccountId,
+    pub method_name: Option<String>,
+    pub receipts_count: u32,
+    pub gas_burnt: types::U64,
+    pub tokens_burnt: types::U128,
+}
+
+/// `permission_kind` is one of ["FULL_ACCESS", "FUNCTION_CALL"].
+/// The function call keys could call only `method_names` (any method if empty) of `receiver_id`,
+/// spending up to `allowance` for gas (unlimited if null)
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "permission_kind", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AccessKeyPermission {
+    FullAccess,
+    FunctionCall {
+        allowance: Option<types::U128>,
+        receiver_id: types::AccountId,
+        method_names: Vec<String>,
+    },
+}
+
+impl paperclip::v2::schema::TypedData for AccessKeyPermission {
+    fn data_type() -> paperclip::v2::models::DataType {
+        paperclip::v2::models::DataType::Object
+    }
+}
+
+/// `expanded` are the accounts with their counterparties, in the order of the traversal: the given account goes first.
+/// The accounts are added as the nodes when they are met first, the edges are deduplicated
+pub(crate) fn build_graph(
+    expanded: Vec<(types::AccountId, Vec<Counterparty>)>,
+) -> (Vec<GraphNode>, Vec<GraphEdge>) {
+    let mut nodes: Vec<GraphNode> = vec![];
+    let mut edges: Vec<GraphEdge> = vec![];
+    let mut depths: std::collections::HashMap<types::AccountId, u32> = Default::default();
+    let mut pairs: std::collections::HashSet<(types::AccountId, types::AccountId)> =
+        Default::default();
+
+    for (account_id, counterparties) in expanded {
+        let depth = *depths.entry(account_id.clone()).or_insert_with(|| {
+            nodes.push(GraphNode {
+                account_id: account_id.clone(),
+                depth: 0,
+            });
+            0
+        });
+        for counterparty in counterparties {
+            if !depths.contains_key(&counterparty.account_id) {
+                depths.insert(counterparty.account_id.clone(), depth + 1);
+                nodes.push(GraphNode {
+                    account_id: counterparty.account_id.clone(),
+                    depth: depth + 1,
+                });
+            }
+            let pair = if account_id < counterparty.account_id {
+                (account_id.clone(), counterparty.account_id.clone())
+            } else {
+                (counterparty.account_id.clone(), account_id.clone())
+            };
+            if !pairs.insert(pair) {
+                continue;
+            }
+            edges.push(GraphEdge {
+                from_account_id: account_id.clone(),
+                to_account_id: counterparty.account_id,
+                sent: counterparty.sent,
+                received: counterparty.received,
+                total: counterparty.total,
+                transfers_count: counterparty.transfers_count,
+            });
+        }
+    }
+    (nodes, edges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_build_graph() {
+        let account = |account_id: &str| types::AccountId::from_str(account_id).unwrap();
+        let counterparty = |account_id: &str, sent: u128, received: u128| Counterparty {
+            account_id: account(account_id),
+            sent: types::U128(sent),
+            received: types::U128(received),
+            total: types::U128(sent + received),
+            transfers_count: 1,
+        };
+        let (nodes, edges) = build_graph(vec![
+            (
+                account("alice.near"),
+                vec![
+                    counterparty("bob.near", 300, 0),
+                    counterparty("pool.near", 0, 50),
+                ],
+            ),
+            (
+                account("bob.near"),
+                vec![
+                    counterparty("alice.near", 0, 300),
+                    counterparty("carol.near", 10, 0),
+                ],
+            ),
+        ]);
+
+        let depths: Vec<(String, u32)> = nodes
+            .iter()
+            .map(|node| (node.account_id.to_string(), node.depth))
+            .collect();
+        assert_eq!(
+            depths,
+            vec![
+                ("alice.near".to_string(), 0),
+                ("bob.near".to_string(), 1),
+                ("pool.near".to_string(), 1),
+                ("carol.near".to_string(), 2),
+            ]
+        );
+        // alice.near - bob.near is given twice, we keep the first one
+        assert_eq!(edges.len(), 3);
+        assert_eq!(edges[0].from_account_id.to_string(), "alice.near");
+        assert_eq!(edges[0].sent.0, 300);
+        assert_eq!(edges[2].from_account_id.to_string(), "bob.near");
+        assert_eq!(edges[2].to_account_id.to_string(), "carol.near");
+    }
+}
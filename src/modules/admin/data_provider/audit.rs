@@ -0,0 +1,97 @@
+use crate::modules::admin;
+use crate::{db_helpers, types};
+
+/// The newest records go first. `before_id` is the pagination cursor, exclusive
+pub(crate) async fn get_audit_records(
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    actor_key_id: Option<&str>,
+    before_id: Option<u64>,
+    limit: u32,
+) -> crate::Result<Vec<admin::schemas::AuditRecord>> {
+    let query = r"
+        SELECT id, timestamp, actor_key_id, method, endpoint, path, query, body, status, client_ip
+        FROM audit_log
+        WHERE ($1 = '' OR actor_key_id = $1)
+            AND id < $2::numeric(20, 0)
+        ORDER BY id DESC
+        LIMIT $3::numeric(20, 0)
+    ";
+    let records = db_helpers::select_retry_or_panic::<super::models::AuditRecordInfo>(
+        pool_aux,
+        query,
+        &[
+            actor_key_id.unwrap_or_default().to_string(),
+            before_id.unwrap_or(i64::MAX as u64).to_string(),
+            limit.to_string(),
+        ],
+    )
+    .await?;
+
+    let mut result: Vec<admin::schemas::AuditRecord> = vec![];
+    for record in records {
+        result.push(record.try_into()?);
+    }
+    Ok(result)
+}
+
+impl TryFrom<super::models::AuditRecordInfo> for admin::schemas::AuditRecord {
+    type Error = crate::errors::Error;
+
+    fn try_from(info: super::models::AuditRecordInfo) -> crate::Result<Self> {
+        Ok(Self {
+            id: types::U64(info.id as u64),
+            timestamp_nanos: types::numeric::to_u64(&info.timestamp)?.into(),
+            actor_key_id: info.actor_key_id,
+            method: info.method,
+            endpoint: info.endpoint,
+            path: info.path,
+            query: info.query,
+            body: info.body,
+            status: info.status as u16,
+            client_ip: info.client_ip,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+
+    #[tokio::test]
+    async fn test_get_audit_records_seeded() {
+        let db = init_seeded_db().await;
+        sqlx::query(
+            r"INSERT INTO audit_log (timestamp, actor_key_id, method, endpoint, path, query, body, status, client_ip)
+              VALUES (1, 'admin_a', 'GET', '/admin/usage', '/admin/usage', 'window=7d', NULL, 200, '10.0.0.1'),
+                     (2, 'admin_b', 'PUT', '/admin/keys/{key_id}/quota', '/admin/keys/k/quota', '', '{}', 200, NULL),
+                     (3, 'admin_a', 'DELETE', '/admin/keys/{key_id}/quota', '/admin/keys/k/quota', '', NULL, 401, NULL)",
+        )
+        .execute(&db.pool)
+        .await
+        .unwrap();
+
+        let records = get_audit_records(&db.pool, None, None, 10).await.unwrap();
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| record.timestamp_nanos.0)
+                .collect::<Vec<_>>(),
+            vec![3, 2, 1]
+        );
+
+        let records = get_audit_records(&db.pool, Some("admin_a"), None, 1)
+            .await
+            .unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(
+            (records[0].method.as_str(), records[0].status),
+            ("DELETE", 401)
+        );
+        let records = get_audit_records(&db.pool, Some("admin_a"), Some(records[0].id.0), 10)
+            .await
+            .unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].client_ip.as_deref(), Some("10.0.0.1"));
+    }
+}
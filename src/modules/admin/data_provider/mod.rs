@@ -0,0 +1,8 @@
+mod audit;
+mod models;
+mod quotas;
+mod usage;
+
+pub(crate) use audit::get_audit_records;
+pub(crate) use quotas::{delete_quota, get_quota, set_quota};
+pub(crate) use usage::{get_key_usage, get_usage};
@@ -0,0 +1,29 @@
+use crate::BigDecimal;
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct UsageInfo {
+    /// `key_id` or `endpoint`, depending on the grouping
+    pub group_key: String,
+    pub requests_count: i64,
+    pub response_bytes: i64,
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct QuotaInfo {
+    pub daily_requests: Option<i64>,
+    pub monthly_requests: Option<i64>,
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct AuditRecordInfo {
+    pub id: i64,
+    pub timestamp: BigDecimal,
+    pub actor_key_id: String,
+    pub method: String,
+    pub endpoint: String,
+    pub path: String,
+    pub query: String,
+    pub body: Option<String>,
+    pub status: i32,
+    pub client_ip: Option<String>,
+}
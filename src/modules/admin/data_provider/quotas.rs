@@ -0,0 +1,95 @@
+use crate::modules::admin;
+use crate::{db_helpers, errors};
+
+/// `None` if the key has no quota
+pub(crate) async fn get_quota(
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    key_id: &str,
+) -> crate::Result<Option<admin::schemas::Quota>> {
+    let query = "SELECT daily_requests, monthly_requests FROM api_quotas WHERE key_id = $1";
+    let quotas = db_helpers::select_retry_or_panic::<super::models::QuotaInfo>(
+        pool_aux,
+        query,
+        &[key_id.to_string()],
+    )
+    .await?;
+    Ok(quotas.first().map(|quota| admin::schemas::Quota {
+        daily_requests: quota.daily_requests.map(|limit| (limit as u64).into()),
+        monthly_requests: quota.monthly_requests.map(|limit| (limit as u64).into()),
+    }))
+}
+
+pub(crate) async fn set_quota(
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    key_id: &str,
+    quota: &admin::schemas::Quota,
+    timestamp: u64,
+) -> crate::Result<()> {
+    sqlx::query(
+        r"INSERT INTO api_quotas (key_id, daily_requests, monthly_requests, updated_at_timestamp)
+          VALUES ($1, $2, $3, $4::numeric(20, 0))
+          ON CONFLICT (key_id) DO UPDATE
+          SET daily_requests = EXCLUDED.daily_requests,
+              monthly_requests = EXCLUDED.monthly_requests,
+              updated_at_timestamp = EXCLUDED.updated_at_timestamp",
+    )
+    .bind(key_id)
+    .bind(to_db_limit(quota.daily_requests)?)
+    .bind(to_db_limit(quota.monthly_requests)?)
+    .bind(timestamp.to_string())
+    .execute(pool_aux)
+    .await
+    .map_err(|err| errors::ErrorKind::DBError(err.to_string()))?;
+    Ok(())
+}
+
+pub(crate) async fn delete_quota(
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    key_id: &str,
+) -> crate::Result<()> {
+    sqlx::query("DELETE FROM api_quotas WHERE key_id = $1")
+        .bind(key_id)
+        .execute(pool_aux)
+        .await
+        .map_err(|err| errors::ErrorKind::DBError(err.to_string()))?;
+    Ok(())
+}
+
+fn to_db_limit(limit: Option<crate::types::U64>) -> crate::Result<Option<i64>> {
+    limit
+        .map(|limit| {
+            i64::try_from(limit.0).map_err(|_| {
+                errors::ErrorKind::InvalidInput(format!("quota {} is too big", limit.0)).into()
+            })
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+
+    #[tokio::test]
+    async fn test_quotas_seeded() {
+        let db = init_seeded_db().await;
+        assert_eq!(get_quota(&db.pool, "key_a").await.unwrap(), None);
+
+        let quota = admin::schemas::Quota {
+            daily_requests: Some(1000.into()),
+            monthly_requests: None,
+        };
+        set_quota(&db.pool, "key_a", &quota, 1).await.unwrap();
+        assert_eq!(get_quota(&db.pool, "key_a").await.unwrap(), Some(quota));
+
+        let quota = admin::schemas::Quota {
+            daily_requests: None,
+            monthly_requests: Some(20000.into()),
+        };
+        set_quota(&db.pool, "key_a", &quota, 2).await.unwrap();
+        assert_eq!(get_quota(&db.pool, "key_a").await.unwrap(), Some(quota));
+
+        delete_quota(&db.pool, "key_a").await.unwrap();
+        assert_eq!(get_quota(&db.pool, "key_a").await.unwrap(), None);
+    }
+}
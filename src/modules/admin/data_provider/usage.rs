@@ -0,0 +1,129 @@
+use crate::db_helpers;
+use crate::modules::admin;
+
+/// Top API keys by the number of requests in `[from_timestamp, to_timestamp]`
+pub(crate) async fn get_usage(
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    from_timestamp: u64,
+    to_timestamp: u64,
+    limit: u32,
+) -> crate::Result<Vec<admin::schemas::KeyUsage>> {
+    let query = r"
+        SELECT
+            key_id group_key,
+            sum(requests_count)::bigint requests_count,
+            sum(response_bytes)::bigint response_bytes
+        FROM api_usage
+        WHERE day_timestamp >= $1::numeric(20, 0)
+            AND day_timestamp <= $2::numeric(20, 0)
+        GROUP BY key_id
+        ORDER BY requests_count DESC, key_id
+        LIMIT $3::numeric(20, 0)
+    ";
+    let usage = db_helpers::select_retry_or_panic::<super::models::UsageInfo>(
+        pool_aux,
+        query,
+        &[
+            from_timestamp.to_string(),
+            to_timestamp.to_string(),
+            limit.to_string(),
+        ],
+    )
+    .await?;
+    Ok(usage
+        .into_iter()
+        .map(|info| admin::schemas::KeyUsage {
+            key_id: info.group_key,
+            requests_count: (info.requests_count as u64).into(),
+            response_bytes: (info.response_bytes as u64).into(),
+        })
+        .collect())
+}
+
+/// Per endpoint breakdown of the given API key usage in `[from_timestamp, to_timestamp]`
+pub(crate) async fn get_key_usage(
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    key_id: &str,
+    from_timestamp: u64,
+    to_timestamp: u64,
+) -> crate::Result<Vec<admin::schemas::EndpointUsage>> {
+    let query = r"
+        SELECT
+            endpoint group_key,
+            sum(requests_count)::bigint requests_count,
+            sum(response_bytes)::bigint response_bytes
+        FROM api_usage
+        WHERE key_id = $1
+            AND day_timestamp >= $2::numeric(20, 0)
+            AND day_timestamp <= $3::numeric(20, 0)
+        GROUP BY endpoint
+        ORDER BY requests_count DESC, endpoint
+    ";
+    let usage = db_helpers::select_retry_or_panic::<super::models::UsageInfo>(
+        pool_aux,
+        query,
+        &[
+            key_id.to_string(),
+            from_timestamp.to_string(),
+            to_timestamp.to_string(),
+        ],
+    )
+    .await?;
+    Ok(usage
+        .into_iter()
+        .map(|info| admin::schemas::EndpointUsage {
+            endpoint: info.group_key,
+            requests_count: (info.requests_count as u64).into(),
+            response_bytes: (info.response_bytes as u64).into(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+
+    #[tokio::test]
+    async fn test_usage_seeded() {
+        let db = init_seeded_db().await;
+        sqlx::query(
+            r"INSERT INTO api_usage (key_id, day_timestamp, endpoint, requests_count, response_bytes)
+              VALUES ('key_a', 86400000000000, '/accounts/{account_id}/coins/NEAR', 10, 1000),
+                     ('key_a', 172800000000000, '/accounts/{account_id}/coins/NEAR', 5, 500),
+                     ('key_a', 172800000000000, '/nep141/{contract_account_id}/metadata', 1, 50),
+                     ('key_b', 172800000000000, '/accounts/{account_id}/coins/NEAR', 3, 300)",
+        )
+        .execute(&db.pool)
+        .await
+        .unwrap();
+
+        let usage = get_usage(&db.pool, 0, 172800000000000, 10).await.unwrap();
+        let usage: Vec<_> = usage
+            .iter()
+            .map(|key| {
+                (
+                    key.key_id.as_str(),
+                    key.requests_count.0,
+                    key.response_bytes.0,
+                )
+            })
+            .collect();
+        assert_eq!(usage, vec![("key_a", 16, 1550), ("key_b", 3, 300)]);
+
+        let usage = get_key_usage(&db.pool, "key_a", 172800000000000, 172800000000000)
+            .await
+            .unwrap();
+        let usage: Vec<_> = usage
+            .iter()
+            .map(|endpoint| (endpoint.endpoint.as_str(), endpoint.requests_count.0))
+            .collect();
+        assert_eq!(
+            usage,
+            vec![
+                ("/accounts/{account_id}/coins/NEAR", 5),
+                ("/nep141/{contract_account_id}/metadata", 1)
+            ]
+        );
+    }
+}
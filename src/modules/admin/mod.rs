@@ -0,0 +1,101 @@
+use paperclip::actix::web;
+
+use crate::{allow_list, api_keys, consistency_check, errors, route_toggles, signing};
+
+pub(crate) mod data_provider;
+mod resources;
+pub(crate) mod schemas;
+
+/// `ADMIN_API_TOKEN`, the admin endpoints are not registered without it
+pub(crate) struct AdminToken(pub String);
+
+pub(crate) fn register_services(
+    app: &mut web::ServiceConfig,
+    admin_token: &str,
+    signature_verifier: &signing::SignatureVerifier,
+    route_toggles: &route_toggles::RouteToggles,
+    allow_list: &allow_list::ContractAllowList,
+    consistency_checker: &consistency_check::ConsistencyChecker,
+) {
+    app.app_data(web::Data::new(AdminToken(admin_token.to_string())))
+        .app_data(web::Data::new(route_toggles.clone()))
+        .app_data(web::Data::new(allow_list.clone()))
+        .service(web::resource("/admin/usage").route(web::get().to(resources::get_usage)))
+        .service(
+            web::resource("/admin/usage/{key_id}").route(web::get().to(resources::get_key_usage)),
+        )
+        .service(web::resource("/admin/audit").route(web::get().to(resources::get_audit_records)))
+        .service(
+            web::resource("/admin/keys/{key_id}/quota")
+                .route(web::get().to(resources::get_quota))
+                .route(web::put().to(resources::set_quota))
+                .route(web::delete().to(resources::delete_quota)),
+        )
+        .service(
+            web::resource("/admin/routes/disabled")
+                .route(web::get().to(resources::get_disabled_routes))
+                .route(web::put().to(resources::set_disabled_routes)),
+        )
+        .service(
+            web::resource("/admin/contracts/allowed")
+                .route(web::get().to(resources::get_allowed_contracts))
+                .route(web::put().to(resources::set_allowed_contracts)),
+        );
+
+    if signature_verifier.is_enabled() {
+        app.app_data(web::Data::new(signature_verifier.clone()))
+            .service(
+                web::resource("/admin/keys/{key_id}/signing-secret")
+                    .route(web::get().to(resources::get_signing_secret)),
+            );
+    }
+    if consistency_checker.is_enabled() {
+        app.app_data(web::Data::new(consistency_checker.clone()))
+            .service(
+                web::resource("/admin/consistency")
+                    .route(web::get().to(resources::get_consistency_report)),
+            );
+    }
+}
+
+pub(crate) fn check_admin_token(
+    request: &actix_web::HttpRequest,
+    admin_token: &AdminToken,
+) -> crate::Result<()> {
+    let token = api_keys::get_bearer_token(request.headers()).unwrap_or_default();
+    // Constant time comparison, the token should not leak through the response timings
+    let is_valid = token.len() == admin_token.0.len()
+        && token
+            .bytes()
+            .zip(admin_token.0.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0;
+    if is_valid {
+        Ok(())
+    } else {
+        Err(errors::ErrorKind::Unauthorized("invalid admin token".to_string()).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_admin_token() {
+        let admin_token = AdminToken("admin_secret".to_string());
+        for (header, is_valid) in [
+            (Some("Bearer admin_secret"), true),
+            (Some("Bearer admin_secre"), false),
+            (Some("admin_secret"), false),
+            (None, false),
+        ] {
+            let mut request = actix_web::test::TestRequest::get();
+            if let Some(header) = header {
+                request = request.insert_header((actix_web::http::header::AUTHORIZATION, header));
+            }
+            let result = check_admin_token(&request.to_http_request(), &admin_token);
+            assert_eq!(result.is_ok(), is_valid, "{:?}", header);
+        }
+    }
+}
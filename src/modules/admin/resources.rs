@@ -0,0 +1,353 @@
+use paperclip::actix::{
+    api_v2_operation,
+    web::{self, Json},
+};
+
+use crate::{allow_list, consistency_check, data_source, errors, route_toggles, signing, types};
+
+use super::schemas;
+
+const DEFAULT_WINDOW_DAYS: u64 = 30;
+const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+#[api_v2_operation(skip)]
+/// Get usage per API key
+///
+/// This endpoint returns the number of requests and the response bytes of the most active
+/// API keys in the given window, the last 30 days by default.
+/// Requires `Authorization: Bearer <ADMIN_API_TOKEN>` header.
+///
+/// **Limitations**
+/// * The counters are written in batches, the last few seconds could be missing.
+pub async fn get_usage(
+    request: actix_web::HttpRequest,
+    admin_token: web::Data<super::AdminToken>,
+    data_source: web::Data<data_source::DataSourceRef>,
+    window_params: web::Query<types::query_params::WindowParams>,
+    mut pagination_params: web::Query<types::query_params::PaginationParams>,
+) -> crate::Result<Json<schemas::UsageResponse>> {
+    super::check_admin_token(&request, &admin_token)?;
+    types::query_params::check_limit(&request, &mut pagination_params.limit)?;
+    let (from_timestamp, to_timestamp) = get_usage_window(&window_params)?;
+    let pagination = types::query_params::Pagination::from(pagination_params.0);
+
+    Ok(Json(schemas::UsageResponse {
+        keys: data_source
+            .get_usage(from_timestamp, to_timestamp, pagination.limit)
+            .await?,
+        from_timestamp_nanos: from_timestamp.into(),
+        to_timestamp_nanos: to_timestamp.into(),
+    }))
+}
+
+#[api_v2_operation(skip)]
+/// Get usage of the API key per endpoint
+///
+/// This endpoint returns the number of requests and the response bytes of the given API key
+/// for each endpoint in the given window, the last 30 days by default.
+/// Requires `Authorization: Bearer <ADMIN_API_TOKEN>` header.
+///
+/// **Limitations**
+/// * The counters are written in batches, the last few seconds could be missing.
+pub async fn get_key_usage(
+    request: actix_web::HttpRequest,
+    admin_token: web::Data<super::AdminToken>,
+    data_source: web::Data<data_source::DataSourceRef>,
+    key_request: web::Path<schemas::KeyRequest>,
+    window_params: web::Query<types::query_params::WindowParams>,
+) -> crate::Result<Json<schemas::KeyUsageResponse>> {
+    super::check_admin_token(&request, &admin_token)?;
+    let (from_timestamp, to_timestamp) = get_usage_window(&window_params)?;
+
+    Ok(Json(schemas::KeyUsageResponse {
+        endpoints: data_source
+            .get_key_usage(&key_request.key_id, from_timestamp, to_timestamp)
+            .await?,
+        key_id: key_request.into_inner().key_id,
+        from_timestamp_nanos: from_timestamp.into(),
+        to_timestamp_nanos: to_timestamp.into(),
+    }))
+}
+
+#[api_v2_operation(skip)]
+/// Get the audit trail
+///
+/// This endpoint returns the admin requests and the signed requests: who sent them, when,
+/// to which endpoint, with which params and body, and the response status. The newest records go first,
+/// pass `id` of the last record as `before_id` to get the next page.
+/// Requires `Authorization: Bearer <ADMIN_API_TOKEN>` header.
+pub async fn get_audit_records(
+    request: actix_web::HttpRequest,
+    admin_token: web::Data<super::AdminToken>,
+    data_source: web::Data<data_source::DataSourceRef>,
+    audit_params: web::Query<schemas::AuditParams>,
+    mut pagination_params: web::Query<types::query_params::PaginationParams>,
+) -> crate::Result<Json<schemas::AuditResponse>> {
+    super::check_admin_token(&request, &admin_token)?;
+    types::query_params::check_limit(&request, &mut pagination_params.limit)?;
+    let pagination = types::query_params::Pagination::from(pagination_params.0);
+
+    Ok(Json(schemas::AuditResponse {
+        records: data_source
+            .get_audit_records(
+                audit_params.actor_key_id.as_deref(),
+                audit_params.before_id.map(|id| id.0),
+                pagination.limit,
+            )
+            .await?,
+    }))
+}
+
+#[api_v2_operation(skip)]
+/// Get the quota of the API key
+///
+/// This endpoint returns the daily and monthly request quotas of the given API key,
+/// null means unlimited. Use `*` key_id for the quota of the keys without their own quota.
+/// Requires `Authorization: Bearer <ADMIN_API_TOKEN>` header.
+pub async fn get_quota(
+    request: actix_web::HttpRequest,
+    admin_token: web::Data<super::AdminToken>,
+    data_source: web::Data<data_source::DataSourceRef>,
+    key_request: web::Path<schemas::KeyRequest>,
+) -> crate::Result<Json<schemas::QuotaResponse>> {
+    super::check_admin_token(&request, &admin_token)?;
+
+    Ok(Json(schemas::QuotaResponse {
+        quota: data_source
+            .get_quota(&key_request.key_id)
+            .await?
+            .unwrap_or_default(),
+        key_id: key_request.into_inner().key_id,
+    }))
+}
+
+#[api_v2_operation(skip)]
+/// Set the quota of the API key
+///
+/// This endpoint replaces the daily and monthly request quotas of the given API key,
+/// null means unlimited. The requests over the quota get 429 error until the end of the UTC day/month.
+/// Requires `Authorization: Bearer <ADMIN_API_TOKEN>` header.
+///
+/// **Limitations**
+/// * The server applies the new quota within `QUOTA_REFRESH_INTERVAL_SECS`.
+pub async fn set_quota(
+    request: actix_web::HttpRequest,
+    admin_token: web::Data<super::AdminToken>,
+    data_source: web::Data<data_source::DataSourceRef>,
+    key_request: web::Path<schemas::KeyRequest>,
+    quota: web::Json<schemas::Quota>,
+) -> crate::Result<Json<schemas::QuotaResponse>> {
+    super::check_admin_token(&request, &admin_token)?;
+    data_source.set_quota(&key_request.key_id, &quota).await?;
+
+    Ok(Json(schemas::QuotaResponse {
+        key_id: key_request.into_inner().key_id,
+        quota: quota.into_inner(),
+    }))
+}
+
+#[api_v2_operation(skip)]
+/// Remove the quota of the API key
+///
+/// This endpoint makes the given API key unlimited, or limited by `*` quota if it's set.
+/// Requires `Authorization: Bearer <ADMIN_API_TOKEN>` header.
+///
+/// **Limitations**
+/// * The server applies the change within `QUOTA_REFRESH_INTERVAL_SECS`.
+pub async fn delete_quota(
+    request: actix_web::HttpRequest,
+    admin_token: web::Data<super::AdminToken>,
+    data_source: web::Data<data_source::DataSourceRef>,
+    key_request: web::Path<schemas::KeyRequest>,
+) -> crate::Result<Json<schemas::QuotaResponse>> {
+    super::check_admin_token(&request, &admin_token)?;
+    data_source.delete_quota(&key_request.key_id).await?;
+
+    Ok(Json(schemas::QuotaResponse {
+        key_id: key_request.into_inner().key_id,
+        quota: Default::default(),
+    }))
+}
+
+#[api_v2_operation(skip)]
+/// Get the signing secret of the API key
+///
+/// This endpoint returns the secret the owner of the given API key signs the requests with.
+/// The secret is derived from `REQUEST_SIGNING_SECRET`, it changes only if the master secret is changed.
+/// Requires `Authorization: Bearer <ADMIN_API_TOKEN>` header.
+pub async fn get_signing_secret(
+    request: actix_web::HttpRequest,
+    admin_token: web::Data<super::AdminToken>,
+    signature_verifier: web::Data<signing::SignatureVerifier>,
+    key_request: web::Path<schemas::KeyRequest>,
+) -> crate::Result<Json<schemas::SigningSecretResponse>> {
+    super::check_admin_token(&request, &admin_token)?;
+    let secret = signature_verifier
+        .key_secret(&key_request.key_id)
+        .ok_or_else(|| {
+            errors::ErrorKind::InvalidInput("request signing is disabled".to_string())
+        })?;
+
+    Ok(Json(schemas::SigningSecretResponse {
+        key_id: key_request.into_inner().key_id,
+        secret,
+    }))
+}
+
+#[api_v2_operation(skip)]
+/// Get the disabled route groups
+///
+/// This endpoint returns the path prefixes which answer 503 `ROUTE_DISABLED` on this instance.
+/// Requires `Authorization: Bearer <ADMIN_API_TOKEN>` header.
+pub async fn get_disabled_routes(
+    request: actix_web::HttpRequest,
+    admin_token: web::Data<super::AdminToken>,
+    route_toggles: web::Data<route_toggles::RouteToggles>,
+) -> crate::Result<Json<schemas::DisabledRoutesResponse>> {
+    super::check_admin_token(&request, &admin_token)?;
+
+    Ok(Json(schemas::DisabledRoutesResponse {
+        path_prefixes: route_toggles.disabled_path_prefixes(),
+    }))
+}
+
+#[api_v2_operation(skip)]
+/// Set the disabled route groups
+///
+/// This endpoint replaces the list of the disabled path prefixes, the empty list enables everything.
+/// `*` matches any path segment, e.g. `/accounts/*/coins/NEAR/history`.
+/// `/admin` endpoints are never disabled.
+/// Requires `Authorization: Bearer <ADMIN_API_TOKEN>` header.
+///
+/// **Limitations**
+/// * The list is kept in memory, it's applied only to this instance and is reset to `DISABLED_ROUTES` on restart.
+pub async fn set_disabled_routes(
+    request: actix_web::HttpRequest,
+    admin_token: web::Data<super::AdminToken>,
+    route_toggles: web::Data<route_toggles::RouteToggles>,
+    disabled_routes: web::Json<schemas::DisabledRoutesRequest>,
+) -> crate::Result<Json<schemas::DisabledRoutesResponse>> {
+    super::check_admin_token(&request, &admin_token)?;
+    let path_prefixes = disabled_routes.into_inner().path_prefixes;
+    if let Some(path_prefix) = path_prefixes
+        .iter()
+        .find(|path_prefix| !path_prefix.starts_with('/'))
+    {
+        return Err(errors::ErrorKind::InvalidInput(format!(
+            "path prefix should start with '/': {}",
+            path_prefix
+        ))
+        .into());
+    }
+    route_toggles.set_disabled_path_prefixes(path_prefixes.clone());
+
+    Ok(Json(schemas::DisabledRoutesResponse { path_prefixes }))
+}
+
+#[api_v2_operation(skip)]
+/// Get the allowed contracts
+///
+/// This endpoint returns the FT and NFT contracts which are served on this instance, null if all of them are served.
+/// Requires `Authorization: Bearer <ADMIN_API_TOKEN>` header.
+pub async fn get_allowed_contracts(
+    request: actix_web::HttpRequest,
+    admin_token: web::Data<super::AdminToken>,
+    allow_list: web::Data<allow_list::ContractAllowList>,
+) -> crate::Result<Json<schemas::AllowedContractsResponse>> {
+    super::check_admin_token(&request, &admin_token)?;
+
+    Ok(Json(allowed_contracts_response(&allow_list)))
+}
+
+#[api_v2_operation(skip)]
+/// Set the allowed contracts
+///
+/// This endpoint replaces the list of the served FT and NFT contracts. The endpoints about the other contract
+/// answer 403 `CONTRACT_NOT_ALLOWED`, the lists (balances, NFT overview, bulk metadata) omit the other contracts.
+/// Null serves all the contracts, the empty list serves only NEAR.
+/// Requires `Authorization: Bearer <ADMIN_API_TOKEN>` header.
+///
+/// **Limitations**
+/// * The list is kept in memory, it's applied only to this instance and is reset to `ALLOWED_CONTRACTS` on restart.
+/// * The responses already in the response cache are served until they expire.
+pub async fn set_allowed_contracts(
+    request: actix_web::HttpRequest,
+    admin_token: web::Data<super::AdminToken>,
+    allow_list: web::Data<allow_list::ContractAllowList>,
+    allowed_contracts: web::Json<schemas::AllowedContractsRequest>,
+) -> crate::Result<Json<schemas::AllowedContractsResponse>> {
+    super::check_admin_token(&request, &admin_token)?;
+    let contract_ids = allowed_contracts.into_inner().contract_account_ids;
+    allow_list.set_allowed_contracts(
+        contract_ids.map(|contract_ids| contract_ids.into_iter().map(Into::into).collect()),
+    );
+
+    Ok(Json(allowed_contracts_response(&allow_list)))
+}
+
+fn allowed_contracts_response(
+    allow_list: &allow_list::ContractAllowList,
+) -> schemas::AllowedContractsResponse {
+    schemas::AllowedContractsResponse {
+        contract_account_ids: allow_list.allowed_contracts().map(|contract_ids| {
+            contract_ids
+                .into_iter()
+                .map(types::AccountId::from)
+                .collect()
+        }),
+    }
+}
+
+#[api_v2_operation(skip)]
+/// Get the consistency report
+///
+/// This endpoint returns how many random FT balances were compared with `ft_balance_of` on this instance,
+/// how many of them did not match, and the last mismatches to investigate.
+/// Requires `Authorization: Bearer <ADMIN_API_TOKEN>` header.
+///
+/// **Limitations**
+/// * The counters are kept in memory, they are reset on restart.
+pub async fn get_consistency_report(
+    request: actix_web::HttpRequest,
+    admin_token: web::Data<super::AdminToken>,
+    consistency_checker: web::Data<consistency_check::ConsistencyChecker>,
+) -> crate::Result<Json<schemas::ConsistencyReportResponse>> {
+    super::check_admin_token(&request, &admin_token)?;
+    let report = consistency_checker.report().ok_or_else(|| {
+        errors::ErrorKind::InvalidInput("consistency checks are disabled".to_string())
+    })?;
+
+    Ok(Json(schemas::ConsistencyReportResponse {
+        started_at_timestamp_nanos: report.started_at_timestamp_nanos.into(),
+        checked: report.checked.into(),
+        mismatches: report.mismatches.into(),
+        failed: report.failed.into(),
+        recent_mismatches: report
+            .recent_mismatches
+            .into_iter()
+            .map(|mismatch| schemas::BalanceMismatch {
+                contract_account_id: mismatch.contract_id.into(),
+                account_id: mismatch.account_id.into(),
+                block_height: mismatch.block_height.into(),
+                block_hash: mismatch.block_hash,
+                block_timestamp_nanos: mismatch.block_timestamp.into(),
+                indexed_balance: mismatch.indexed_balance.into(),
+                rpc_balance: mismatch.rpc_balance.into(),
+                checked_at_timestamp_nanos: mismatch.checked_at_timestamp_nanos.into(),
+            })
+            .collect(),
+    }))
+}
+
+/// The counters are stored per day, so the window starts at the beginning of the day
+fn get_usage_window(
+    window_params: &types::query_params::WindowParams,
+) -> crate::Result<(u64, u64)> {
+    let window_nanos = types::query_params::get_window_nanos(window_params, DEFAULT_WINDOW_DAYS)?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or_default();
+    let from_timestamp = now.saturating_sub(window_nanos) / DAY_NANOS * DAY_NANOS;
+    Ok((from_timestamp, now))
+}
@@ -0,0 +1,158 @@
+use paperclip::actix::Apiv2Schema;
+
+use crate::types;
+
+// *** Requests ***
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct KeyRequest {
+    /// Hash of the API key, see `key_id` in `/admin/usage`
+    pub key_id: String,
+}
+
+#[derive(
+    Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema,
+)]
+pub struct AuditParams {
+    /// Only the requests of the given API key (or the admin token), see `key_id` in `/admin/usage`
+    pub actor_key_id: Option<String>,
+    /// Pagination cursor: `id` of the last record from the previous page
+    pub before_id: Option<types::U64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct DisabledRoutesRequest {
+    /// Path prefixes, `*` matches any segment, e.g. `/accounts/*/coins/NEAR/history`
+    pub path_prefixes: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct AllowedContractsRequest {
+    /// FT and NFT contracts which are served, null serves all of them
+    pub contract_account_ids: Option<Vec<types::AccountId>>,
+}
+
+// *** Responses ***
+
+/// Usage of all the API keys in the window which ends now.
+/// The counters are stored per UTC day, the window is rounded to whole days
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct UsageResponse {
+    pub keys: Vec<KeyUsage>,
+    pub from_timestamp_nanos: types::U64,
+    pub to_timestamp_nanos: types::U64,
+}
+
+/// Per endpoint usage of the given API key
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct KeyUsageResponse {
+    pub key_id: String,
+    pub endpoints: Vec<EndpointUsage>,
+    pub from_timestamp_nanos: types::U64,
+    pub to_timestamp_nanos: types::U64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct KeyUsage {
+    /// Hash of the API key, `anonymous` for the requests without the key
+    pub key_id: String,
+    pub requests_count: types::U64,
+    pub response_bytes: types::U64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct EndpointUsage {
+    /// Route pattern, e.g. `/accounts/{account_id}/coins/NEAR`
+    pub endpoint: String,
+    pub requests_count: types::U64,
+    pub response_bytes: types::U64,
+}
+
+/// Request quota of the API key. Null means unlimited.
+/// The days and the months are in UTC
+#[derive(
+    Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema,
+)]
+pub struct Quota {
+    pub daily_requests: Option<types::U64>,
+    pub monthly_requests: Option<types::U64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct QuotaResponse {
+    /// `*` is the quota for the keys without their own quota
+    pub key_id: String,
+    pub quota: Quota,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct SigningSecretResponse {
+    pub key_id: String,
+    /// Give it to the owner of the API key, they sign the requests with it
+    pub secret: String,
+}
+
+/// The route groups which answer 503 `ROUTE_DISABLED` on this instance
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct DisabledRoutesResponse {
+    pub path_prefixes: Vec<String>,
+}
+
+/// The FT and NFT contracts which are served on this instance, NEAR is always served
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct AllowedContractsResponse {
+    /// Ordered by account id, null if all the contracts are served
+    pub contract_account_ids: Option<Vec<types::AccountId>>,
+}
+
+/// Audit trail of the admin and signed requests, the newest records go first
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct AuditResponse {
+    pub records: Vec<AuditRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct AuditRecord {
+    pub id: types::U64,
+    pub timestamp_nanos: types::U64,
+    /// Hash of the API key or of the admin token
+    pub actor_key_id: String,
+    pub method: String,
+    /// Route pattern, e.g. `/admin/keys/{key_id}/quota`
+    pub endpoint: String,
+    pub path: String,
+    pub query: String,
+    /// Request body, the first 64 KB
+    pub body: Option<String>,
+    /// Response status
+    pub status: u16,
+    pub client_ip: Option<String>,
+}
+
+/// The background comparison of the indexed FT balances with `ft_balance_of` on this instance
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct ConsistencyReportResponse {
+    /// The counters start from zero on restart
+    pub started_at_timestamp_nanos: types::U64,
+    /// The balances which were compared
+    pub checked: types::U64,
+    pub mismatches: types::U64,
+    /// The checks which could not get one of the balances, e.g. RPC was not available
+    pub failed: types::U64,
+    /// Up to 100, the newest go first
+    pub recent_mismatches: Vec<BalanceMismatch>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct BalanceMismatch {
+    pub contract_account_id: types::AccountId,
+    pub account_id: types::AccountId,
+    pub block_height: types::U64,
+    pub block_hash: String,
+    pub block_timestamp_nanos: types::U64,
+    /// The sum of the successful FT events of the account
+    pub indexed_balance: types::U128,
+    /// `ft_balance_of` called through RPC
+    pub rpc_balance: types::U128,
+    pub checked_at_timestamp_nanos: types::U64,
+}
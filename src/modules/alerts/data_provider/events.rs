@@ -0,0 +1,172 @@
+use crate::{db_helpers, types};
+
+/// Successful NFT events of the given contracts in `(from_timestamp, to_timestamp]`, the oldest go first
+pub(crate) async fn get_nft_events(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    contract_ids: &[String],
+    from_timestamp: u64,
+    to_timestamp: u64,
+    limit: u32,
+) -> crate::Result<Vec<NftEvent>> {
+    let query = r"
+        SELECT
+            assets__non_fungible_token_events.emitted_for_receipt_id receipt_id,
+            assets__non_fungible_token_events.emitted_by_contract_account_id contract_account_id,
+            assets__non_fungible_token_events.token_id,
+            assets__non_fungible_token_events.token_new_owner_account_id new_owner_id,
+            blocks.block_timestamp,
+            blocks.block_height,
+            blocks.block_hash
+        FROM assets__non_fungible_token_events
+            JOIN blocks ON assets__non_fungible_token_events.emitted_at_block_timestamp = blocks.block_timestamp
+            JOIN execution_outcomes ON assets__non_fungible_token_events.emitted_for_receipt_id = execution_outcomes.receipt_id
+        WHERE emitted_by_contract_account_id = ANY($1::text[])
+            AND emitted_at_block_timestamp > $2::numeric(20, 0)
+            AND emitted_at_block_timestamp <= $3::numeric(20, 0)
+            AND execution_outcomes.status IN ('SUCCESS_VALUE', 'SUCCESS_RECEIPT_ID')
+        ORDER BY emitted_at_block_timestamp, emitted_in_shard_id, emitted_index_of_event_entry_in_shard
+        LIMIT $4::numeric(20, 0)
+    ";
+    let events = db_helpers::select_retry_or_panic::<super::models::NftEventInfo>(
+        pool,
+        query,
+        &[
+            to_sql_array(contract_ids),
+            from_timestamp.to_string(),
+            to_timestamp.to_string(),
+            limit.to_string(),
+        ],
+    )
+    .await?;
+
+    let mut result: Vec<NftEvent> = vec![];
+    for event in events {
+        result.push(NftEvent {
+            receipt_id: event.receipt_id,
+            contract_account_id: event.contract_account_id,
+            token_id: event.token_id,
+            new_owner_id: types::account_id::extract_account_id(&event.new_owner_id)?,
+            block_timestamp: types::numeric::to_u64(&event.block_timestamp)?,
+            block_height: types::numeric::to_u64(&event.block_height)?,
+            block_hash: event.block_hash,
+        });
+    }
+    Ok(result)
+}
+
+/// `(contract_account_id, account_id)` pairs which had FT events in `(from_timestamp, to_timestamp]`.
+/// The failed events are also here: the balances are checked anyway
+pub(crate) async fn get_ft_event_accounts(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    contract_ids: &[String],
+    from_timestamp: u64,
+    to_timestamp: u64,
+    limit: u32,
+) -> crate::Result<Vec<(String, String)>> {
+    let query = r"
+        SELECT DISTINCT
+            assets__fungible_token_events.emitted_by_contract_account_id contract_account_id,
+            accounts.account_id
+        FROM assets__fungible_token_events
+            CROSS JOIN LATERAL (
+                VALUES (token_old_owner_account_id), (token_new_owner_account_id)
+            ) accounts (account_id)
+        WHERE emitted_by_contract_account_id = ANY($1::text[])
+            AND emitted_at_block_timestamp > $2::numeric(20, 0)
+            AND emitted_at_block_timestamp <= $3::numeric(20, 0)
+            AND accounts.account_id != ''
+        LIMIT $4::numeric(20, 0)
+    ";
+    let accounts = db_helpers::select_retry_or_panic::<super::models::FtEventAccountInfo>(
+        pool,
+        query,
+        &[
+            to_sql_array(contract_ids),
+            from_timestamp.to_string(),
+            to_timestamp.to_string(),
+            limit.to_string(),
+        ],
+    )
+    .await?;
+    Ok(accounts
+        .into_iter()
+        .map(|info| (info.contract_account_id, info.account_id))
+        .collect())
+}
+
+pub(crate) struct NftEvent {
+    pub receipt_id: String,
+    pub contract_account_id: String,
+    pub token_id: String,
+    /// `None` for the burns
+    pub new_owner_id: Option<near_primitives::types::AccountId>,
+    pub block_timestamp: u64,
+    pub block_height: u64,
+    pub block_hash: String,
+}
+
+/// Postgres array literal, e.g. `{a.near,b.near}`.
+/// The account ids never have commas, quotes or braces, nothing to escape
+fn to_sql_array(account_ids: &[String]) -> String {
+    format!("{{{}}}", account_ids.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+
+    #[tokio::test]
+    async fn test_alert_events_seeded() {
+        let db = init_seeded_db().await;
+        let contracts = vec!["nft.near".to_string(), "token.near".to_string()];
+
+        // The failed transfer at block 103 is not here
+        let events = get_nft_events(
+            &db.pool,
+            &contracts,
+            1600000000000000100,
+            1600000000000000103,
+            10,
+        )
+        .await
+        .unwrap();
+        assert_eq!(events[0].block_hash, get_seeded_block_hash(101));
+        let events: Vec<(&str, Option<String>, u64)> = events
+            .iter()
+            .map(|event| {
+                (
+                    event.receipt_id.as_str(),
+                    event.new_owner_id.as_ref().map(|id| id.to_string()),
+                    event.block_height,
+                )
+            })
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                ("receipt_nft_mint", Some("alice.near".to_string()), 101),
+                ("receipt_nft_transfer", Some("bob.near".to_string()), 102)
+            ]
+        );
+
+        // The mint at block 100 is not included, the start of the window is exclusive
+        let mut accounts = get_ft_event_accounts(
+            &db.pool,
+            &contracts,
+            1600000000000000100,
+            1600000000000000101,
+            10,
+        )
+        .await
+        .unwrap();
+        accounts.sort();
+        assert_eq!(
+            accounts,
+            vec![
+                ("token.near".to_string(), "alice.near".to_string()),
+                ("token.near".to_string(), "bob.near".to_string())
+            ]
+        );
+    }
+}
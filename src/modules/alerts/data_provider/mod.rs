@@ -0,0 +1,8 @@
+mod events;
+mod models;
+mod rules;
+
+pub(crate) use events::{get_ft_event_accounts, get_nft_events, NftEvent};
+pub(crate) use rules::{
+    create_alert, delete_alert, get_alerts, get_all_alerts, set_alert_triggered,
+};
@@ -0,0 +1,31 @@
+use crate::BigDecimal;
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct AlertRuleInfo {
+    pub id: i64,
+    pub kind: String,
+    pub contract_account_id: String,
+    pub account_id: Option<String>,
+    pub token_id: Option<String>,
+    pub threshold: Option<BigDecimal>,
+    pub webhook_url: String,
+    pub created_at_timestamp: BigDecimal,
+    pub is_triggered: bool,
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct NftEventInfo {
+    pub receipt_id: String,
+    pub contract_account_id: String,
+    pub token_id: String,
+    pub new_owner_id: String,
+    pub block_timestamp: BigDecimal,
+    pub block_height: BigDecimal,
+    pub block_hash: String,
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct FtEventAccountInfo {
+    pub contract_account_id: String,
+    pub account_id: String,
+}
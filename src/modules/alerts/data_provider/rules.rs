@@ -0,0 +1,189 @@
+use std::str::FromStr;
+
+use crate::modules::alerts;
+use crate::{db_helpers, errors, types};
+
+const ALERT_COLUMNS: &str = r"
+    id, kind, contract_account_id, account_id, token_id, threshold::numeric(45, 0),
+    webhook_url, created_at_timestamp, is_triggered
+";
+
+pub(crate) async fn create_alert(
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    key_id: &str,
+    rule: &alerts::schemas::AlertRule,
+    timestamp: u64,
+) -> crate::Result<alerts::schemas::Alert> {
+    let alert_id: i64 = sqlx::query_scalar(
+        r"INSERT INTO alert_rules
+              (key_id, kind, contract_account_id, account_id, token_id, threshold, webhook_url, created_at_timestamp)
+          VALUES ($1, $2, $3, $4, $5, $6::numeric(45, 0), $7, $8::numeric(20, 0))
+          RETURNING id",
+    )
+    .bind(key_id)
+    .bind(rule.kind.as_str())
+    .bind(rule.contract_account_id.0.as_str())
+    .bind(rule.account_id.as_ref().map(|account_id| account_id.0.as_str()))
+    .bind(&rule.token_id)
+    .bind(rule.threshold.map(|threshold| threshold.0.to_string()))
+    .bind(&rule.webhook_url)
+    .bind(timestamp.to_string())
+    .fetch_one(pool_aux)
+    .await
+    .map_err(|err| errors::ErrorKind::DBError(err.to_string()))?;
+
+    Ok(alerts::schemas::Alert {
+        alert_id: types::U64(alert_id as u64),
+        rule: rule.clone(),
+        is_triggered: false,
+        created_at_timestamp_nanos: timestamp.into(),
+    })
+}
+
+/// The alerts of the given API key, the oldest go first
+pub(crate) async fn get_alerts(
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    key_id: &str,
+) -> crate::Result<Vec<alerts::schemas::Alert>> {
+    let query = format!(
+        "SELECT {} FROM alert_rules WHERE key_id = $1 ORDER BY id",
+        ALERT_COLUMNS
+    );
+    let rules = db_helpers::select_retry_or_panic::<super::models::AlertRuleInfo>(
+        pool_aux,
+        &query,
+        &[key_id.to_string()],
+    )
+    .await?;
+    to_alerts(rules)
+}
+
+/// The alerts of all the API keys, for the evaluation
+pub(crate) async fn get_all_alerts(
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+) -> crate::Result<Vec<alerts::schemas::Alert>> {
+    let query = format!("SELECT {} FROM alert_rules ORDER BY id", ALERT_COLUMNS);
+    let rules =
+        db_helpers::select_retry_or_panic::<super::models::AlertRuleInfo>(pool_aux, &query, &[])
+            .await?;
+    to_alerts(rules)
+}
+
+/// Returns `false` if the API key has no such alert
+pub(crate) async fn delete_alert(
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    key_id: &str,
+    alert_id: u64,
+) -> crate::Result<bool> {
+    let result = sqlx::query("DELETE FROM alert_rules WHERE key_id = $1 AND id = $2")
+        .bind(key_id)
+        .bind(alert_id as i64)
+        .execute(pool_aux)
+        .await
+        .map_err(|err| errors::ErrorKind::DBError(err.to_string()))?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub(crate) async fn set_alert_triggered(
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    alert_id: u64,
+    is_triggered: bool,
+) -> crate::Result<()> {
+    sqlx::query("UPDATE alert_rules SET is_triggered = $2 WHERE id = $1")
+        .bind(alert_id as i64)
+        .bind(is_triggered)
+        .execute(pool_aux)
+        .await
+        .map_err(|err| errors::ErrorKind::DBError(err.to_string()))?;
+    Ok(())
+}
+
+fn to_alerts(
+    rules: Vec<super::models::AlertRuleInfo>,
+) -> crate::Result<Vec<alerts::schemas::Alert>> {
+    let mut result: Vec<alerts::schemas::Alert> = vec![];
+    for rule in rules {
+        result.push(rule.try_into()?);
+    }
+    Ok(result)
+}
+
+impl TryFrom<super::models::AlertRuleInfo> for alerts::schemas::Alert {
+    type Error = errors::Error;
+
+    fn try_from(info: super::models::AlertRuleInfo) -> crate::Result<Self> {
+        Ok(Self {
+            alert_id: types::U64(info.id as u64),
+            rule: alerts::schemas::AlertRule {
+                kind: alerts::schemas::AlertKind::from_str(&info.kind)
+                    .map_err(errors::ErrorKind::InternalError)?,
+                contract_account_id: near_primitives::types::AccountId::from_str(
+                    &info.contract_account_id,
+                )?
+                .into(),
+                account_id: info
+                    .account_id
+                    .map(|account_id| near_primitives::types::AccountId::from_str(&account_id))
+                    .transpose()?
+                    .map(|account_id| account_id.into()),
+                token_id: info.token_id,
+                threshold: info
+                    .threshold
+                    .map(|threshold| types::numeric::to_u128(&threshold))
+                    .transpose()?
+                    .map(|threshold| threshold.into()),
+                webhook_url: info.webhook_url,
+            },
+            is_triggered: info.is_triggered,
+            created_at_timestamp_nanos: types::numeric::to_u64(&info.created_at_timestamp)?.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+
+    #[tokio::test]
+    async fn test_alerts_seeded() {
+        let db = init_seeded_db().await;
+        let rule = alerts::schemas::AlertRule {
+            kind: alerts::schemas::AlertKind::FtBalanceBelow,
+            contract_account_id: near_primitives::types::AccountId::from_str("token.near")
+                .unwrap()
+                .into(),
+            account_id: Some(
+                near_primitives::types::AccountId::from_str("alice.near")
+                    .unwrap()
+                    .into(),
+            ),
+            token_id: None,
+            threshold: Some(types::U128(500)),
+            webhook_url: "https://example.com/hook".to_string(),
+        };
+        let alert = create_alert(&db.pool, "key_a", &rule, 1).await.unwrap();
+        assert_eq!(
+            get_alerts(&db.pool, "key_a").await.unwrap(),
+            vec![alert.clone()]
+        );
+        assert!(get_alerts(&db.pool, "key_b").await.unwrap().is_empty());
+
+        set_alert_triggered(&db.pool, alert.alert_id.0, true)
+            .await
+            .unwrap();
+        let alerts = get_all_alerts(&db.pool).await.unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert!(alerts[0].is_triggered);
+        assert_eq!(alerts[0].rule, rule);
+
+        // Only the owner can delete it
+        assert!(!delete_alert(&db.pool, "key_b", alert.alert_id.0)
+            .await
+            .unwrap());
+        assert!(delete_alert(&db.pool, "key_a", alert.alert_id.0)
+            .await
+            .unwrap());
+        assert!(get_all_alerts(&db.pool).await.unwrap().is_empty());
+    }
+}
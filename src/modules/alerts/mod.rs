@@ -0,0 +1,88 @@
+use paperclip::actix::web;
+
+use crate::errors;
+
+pub(crate) mod data_provider;
+mod resources;
+pub(crate) mod schemas;
+
+pub(crate) fn register_services(app: &mut web::ServiceConfig) {
+    app.service(
+        web::resource("/alerts")
+            .route(web::get().to(resources::get_alerts))
+            .route(web::post().to(resources::create_alert)),
+    )
+    .service(web::resource("/alerts/{alert_id}").route(web::delete().to(resources::delete_alert)));
+}
+
+pub(crate) fn check_alert_rule(rule: &schemas::AlertRule) -> crate::Result<()> {
+    let url = reqwest::Url::parse(&rule.webhook_url).map_err(|err| {
+        errors::ErrorKind::InvalidInput(format!("webhook_url is not a valid URL: {}", err))
+    })?;
+    if url.scheme() != "https" {
+        return Err(
+            errors::ErrorKind::InvalidInput("webhook_url should be https".to_string()).into(),
+        );
+    }
+    match rule.kind {
+        schemas::AlertKind::FtBalanceBelow => {
+            if rule.account_id.is_none() || rule.threshold.is_none() {
+                return Err(errors::ErrorKind::InvalidInput(
+                    "FT_BALANCE_BELOW requires account_id and threshold".to_string(),
+                )
+                .into());
+            }
+            if rule.token_id.is_some() {
+                return Err(errors::ErrorKind::InvalidInput(
+                    "FT_BALANCE_BELOW does not have token_id".to_string(),
+                )
+                .into());
+            }
+        }
+        schemas::AlertKind::NftTransfer => {
+            if rule.account_id.is_some() || rule.threshold.is_some() {
+                return Err(errors::ErrorKind::InvalidInput(
+                    "NFT_TRANSFER does not have account_id and threshold".to_string(),
+                )
+                .into());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_check_alert_rule() {
+        let mut rule = schemas::AlertRule {
+            kind: schemas::AlertKind::NftTransfer,
+            contract_account_id: near_primitives::types::AccountId::from_str("nft.near")
+                .unwrap()
+                .into(),
+            account_id: None,
+            token_id: Some("1".to_string()),
+            threshold: None,
+            webhook_url: "https://example.com/hook".to_string(),
+        };
+        assert!(check_alert_rule(&rule).is_ok());
+
+        rule.webhook_url = "http://169.254.169.254/latest".to_string();
+        assert!(check_alert_rule(&rule).is_err());
+        rule.webhook_url = "https://example.com/hook".to_string();
+
+        rule.kind = schemas::AlertKind::FtBalanceBelow;
+        assert!(check_alert_rule(&rule).is_err());
+        rule.token_id = None;
+        rule.account_id = Some(
+            near_primitives::types::AccountId::from_str("alice.near")
+                .unwrap()
+                .into(),
+        );
+        rule.threshold = Some(crate::types::U128(100));
+        assert!(check_alert_rule(&rule).is_ok());
+    }
+}
@@ -0,0 +1,90 @@
+use paperclip::actix::{
+    api_v2_operation,
+    web::{self, Json},
+};
+
+use crate::{api_keys, data_source, errors};
+
+use super::schemas;
+
+/// Every rule costs the evaluation, so the keys can't have too many of them
+const MAX_ALERTS_PER_KEY: usize = 100;
+
+#[api_v2_operation(tags(Alerts))]
+/// Get the alerts
+///
+/// This endpoint returns the alert rules of the API key, the oldest go first.
+/// Requires the API key in `X-API-Key` header.
+pub async fn get_alerts(
+    request: actix_web::HttpRequest,
+    data_source: web::Data<data_source::DataSourceRef>,
+) -> crate::Result<Json<schemas::AlertsResponse>> {
+    let key_id = api_keys::require_key_id(request.headers(), "the alerts")?;
+
+    Ok(Json(schemas::AlertsResponse {
+        alerts: data_source.get_alerts(&key_id).await?,
+    }))
+}
+
+#[api_v2_operation(tags(Alerts))]
+/// Create the alert
+///
+/// This endpoint registers the rule which is checked against the new events,
+/// the notifications are sent to `webhook_url` with POST as `AlertNotification` JSON.
+/// * `FT_BALANCE_BELOW`: the FT balance of `account_id` drops below `threshold`.
+///   The next notification is sent only after the balance goes back above the threshold and drops again.
+/// * `NFT_TRANSFER`: `token_id` (or any token of the contract) is minted, transferred or burnt.
+///
+/// Requires the API key in `X-API-Key` header.
+///
+/// **Limitations**
+/// * For now, we support only the contracts which implement Events NEP.
+/// * The events are checked every `ALERTS_CHECK_INTERVAL_SECS`, the notifications are not instant.
+/// * The webhook call is retried 3 times, then the notification is dropped.
+/// * Up to 100 alerts per API key.
+pub async fn create_alert(
+    request: actix_web::HttpRequest,
+    data_source: web::Data<data_source::DataSourceRef>,
+    rule: web::Json<schemas::AlertRule>,
+) -> crate::Result<Json<schemas::AlertResponse>> {
+    let key_id = api_keys::require_key_id(request.headers(), "the alerts")?;
+    super::check_alert_rule(&rule)?;
+    if data_source.get_alerts(&key_id).await?.len() >= MAX_ALERTS_PER_KEY {
+        return Err(errors::ErrorKind::InvalidInput(format!(
+            "the API key can't have more than {} alerts",
+            MAX_ALERTS_PER_KEY
+        ))
+        .into());
+    }
+
+    Ok(Json(schemas::AlertResponse {
+        alert: data_source.create_alert(&key_id, &rule).await?,
+    }))
+}
+
+#[api_v2_operation(tags(Alerts))]
+/// Delete the alert
+///
+/// This endpoint removes the alert rule of the API key and returns the remaining ones.
+/// Requires the API key in `X-API-Key` header.
+pub async fn delete_alert(
+    request: actix_web::HttpRequest,
+    data_source: web::Data<data_source::DataSourceRef>,
+    alert_request: web::Path<schemas::AlertRequest>,
+) -> crate::Result<Json<schemas::AlertsResponse>> {
+    let key_id = api_keys::require_key_id(request.headers(), "the alerts")?;
+    if !data_source
+        .delete_alert(&key_id, alert_request.alert_id.0)
+        .await?
+    {
+        return Err(errors::ErrorKind::InvalidInput(format!(
+            "alert {} is not found",
+            alert_request.alert_id.0
+        ))
+        .into());
+    }
+
+    Ok(Json(schemas::AlertsResponse {
+        alerts: data_source.get_alerts(&key_id).await?,
+    }))
+}
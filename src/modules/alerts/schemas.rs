@@ -0,0 +1,95 @@
+use paperclip::actix::Apiv2Schema;
+
+use crate::types;
+
+// *** Requests ***
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct AlertRequest {
+    pub alert_id: types::U64,
+}
+
+/// What to watch and where to send the notifications
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct AlertRule {
+    pub kind: AlertKind,
+    pub contract_account_id: types::AccountId,
+    /// The account which balance is watched. Required for `FT_BALANCE_BELOW`
+    pub account_id: Option<types::AccountId>,
+    /// The watched NFT for `NFT_TRANSFER`, all the tokens of the contract if not set
+    pub token_id: Option<String>,
+    /// Required for `FT_BALANCE_BELOW`, in the smallest units of the token
+    pub threshold: Option<types::U128>,
+    /// https URL, the notifications are sent there with POST as `AlertNotification` JSON
+    pub webhook_url: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AlertKind {
+    /// The FT balance of the account drops below the threshold
+    FtBalanceBelow,
+    /// The NFT (or any NFT of the contract) is minted, transferred or burnt
+    NftTransfer,
+}
+
+impl AlertKind {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Self::FtBalanceBelow => "FT_BALANCE_BELOW",
+            Self::NftTransfer => "NFT_TRANSFER",
+        }
+    }
+}
+
+impl std::str::FromStr for AlertKind {
+    type Err = String;
+
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        match kind {
+            "FT_BALANCE_BELOW" => Ok(Self::FtBalanceBelow),
+            "NFT_TRANSFER" => Ok(Self::NftTransfer),
+            _ => Err(format!("unknown alert kind {}", kind)),
+        }
+    }
+}
+
+// *** Responses ***
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct AlertsResponse {
+    pub alerts: Vec<Alert>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct AlertResponse {
+    pub alert: Alert,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct Alert {
+    pub alert_id: types::U64,
+    pub rule: AlertRule,
+    /// `FT_BALANCE_BELOW` rule is triggered while the balance is below the threshold
+    pub is_triggered: bool,
+    pub created_at_timestamp_nanos: types::U64,
+}
+
+/// The body of the webhook call
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct AlertNotification {
+    pub alert_id: types::U64,
+    pub kind: AlertKind,
+    pub contract_account_id: types::AccountId,
+    /// The watched account for `FT_BALANCE_BELOW`, the new owner for `NFT_TRANSFER` (`None` for burns)
+    pub account_id: Option<types::AccountId>,
+    pub token_id: Option<String>,
+    /// The balance for `FT_BALANCE_BELOW`
+    pub balance: Option<types::U128>,
+    pub threshold: Option<types::U128>,
+    /// The event receipt for `NFT_TRANSFER`
+    pub receipt_id: Option<String>,
+    pub block_timestamp_nanos: types::U64,
+    pub block_height: types::U64,
+    pub block_hash: String,
+}
@@ -26,8 +26,7 @@ pub(crate) async fn get_near_balance(
         Some(balance) => Ok(coin::schemas::NearBalanceResponse {
             balance: types::numeric::to_u128(&balance.balance)?.into(),
             metadata: super::metadata::get_near_metadata(),
-            block_timestamp_nanos: block.timestamp.into(),
-            block_height: block.height.into(),
+            meta: types::ResponseMeta::new(&block),
         }),
         None => Err(errors::ErrorKind::DBError(format!(
             "Could not find the data in account_changes table for account_id {}",
@@ -37,14 +36,76 @@ pub(crate) async fn get_near_balance(
     }
 }
 
+pub(crate) async fn get_near_balance_breakdown(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    rpc_client: &dyn rpc_helpers::RpcApi,
+    storage_price_cache: &super::StoragePriceCache,
+    block: &db_helpers::Block,
+    account_id: &near_primitives::types::AccountId,
+) -> crate::Result<coin::schemas::NearBalanceBreakdownResponse> {
+    let states =
+        db_helpers::select_retry_or_panic::<super::models::AccountChangesState>(
+            pool,
+            r"
+                SELECT affected_account_nonstaked_balance nonstaked_balance,
+                    affected_account_staked_balance staked_balance,
+                    affected_account_storage_usage storage_usage
+                FROM account_changes
+                WHERE affected_account_id = $1 AND changed_in_block_timestamp <= $2::numeric(20, 0)
+                ORDER BY changed_in_block_timestamp DESC
+                LIMIT 1
+            ",
+            &[account_id.to_string(), block.timestamp.to_string()],
+        ).await?;
+    let state = states.first().ok_or_else(|| {
+        errors::ErrorKind::DBError(format!(
+            "Could not find the data in account_changes table for account_id {}",
+            account_id
+        ))
+    })?;
+
+    let storage_amount_per_byte =
+        super::get_storage_amount_per_byte(rpc_client, storage_price_cache, block.height).await?;
+    Ok(near_balance_breakdown(
+        types::numeric::to_u128(&state.nonstaked_balance)?,
+        types::numeric::to_u128(&state.staked_balance)?,
+        types::numeric::to_u64(&state.storage_usage)?,
+        storage_amount_per_byte,
+        block,
+    ))
+}
+
+/// The runtime counts the staked balance towards the storage first, the rest of the cost locks the nonstaked one
+fn near_balance_breakdown(
+    nonstaked_balance: u128,
+    staked_balance: u128,
+    storage_usage: u64,
+    storage_amount_per_byte: u128,
+    block: &db_helpers::Block,
+) -> coin::schemas::NearBalanceBreakdownResponse {
+    let storage_cost = (storage_usage as u128).saturating_mul(storage_amount_per_byte);
+    let storage_locked_balance = storage_cost
+        .saturating_sub(staked_balance)
+        .min(nonstaked_balance);
+    coin::schemas::NearBalanceBreakdownResponse {
+        total_balance: nonstaked_balance.saturating_add(staked_balance).into(),
+        staked_balance: staked_balance.into(),
+        storage_usage: storage_usage.into(),
+        storage_amount_per_byte: storage_amount_per_byte.into(),
+        storage_locked_balance: storage_locked_balance.into(),
+        available_balance: (nonstaked_balance - storage_locked_balance).into(),
+        meta: types::ResponseMeta::new(block),
+    }
+}
+
 // TODO PHASE 2 pagination (recently updated go first), by artificial index added to assets__fungible_token_events
 pub(crate) async fn get_coin_balances(
     pool: &sqlx::Pool<sqlx::Postgres>,
-    rpc_client: &near_jsonrpc_client::JsonRpcClient,
+    rpc_client: &dyn rpc_helpers::RpcApi,
     block: &db_helpers::Block,
     account_id: &near_primitives::types::AccountId,
     pagination: &types::query_params::Pagination,
-) -> crate::Result<Vec<coin::schemas::Coin>> {
+) -> crate::Result<(Vec<coin::schemas::Coin>, Vec<types::FailedContract>)> {
     let query = r"
         SELECT DISTINCT emitted_by_contract_account_id account_id
         FROM assets__fungible_token_events
@@ -65,21 +126,25 @@ pub(crate) async fn get_coin_balances(
     .await?;
 
     let mut balances: Vec<coin::schemas::Coin> = vec![];
+    let mut failed_contracts: Vec<types::FailedContract> = vec![];
     for contract in contracts {
         if let Ok(contract_id) = near_primitives::types::AccountId::from_str(&contract.account_id) {
-            balances.append(
-                &mut get_coin_balances_by_contract(rpc_client, block, &contract_id, account_id)
-                    .await?,
-            );
+            // One broken contract should not hide the whole portfolio
+            match get_coin_balances_by_contract(rpc_client, block, &contract_id, account_id).await {
+                Ok(mut contract_balances) => balances.append(&mut contract_balances),
+                Err(error) => {
+                    failed_contracts.push(types::FailedContract::new(&contract_id, error))
+                }
+            }
         }
     }
-    Ok(balances)
+    Ok((balances, failed_contracts))
 }
 
 // TODO PHASE 2 change RPC call to DB call by adding absolute amount values to assets__fungible_token_events
 // TODO PHASE 2 add metadata tables to the DB, with periodic autoupdate
 pub(crate) async fn get_coin_balances_by_contract(
-    rpc_client: &near_jsonrpc_client::JsonRpcClient,
+    rpc_client: &dyn rpc_helpers::RpcApi,
     block: &db_helpers::Block,
     contract_id: &near_primitives::types::AccountId,
     account_id: &near_primitives::types::AccountId,
@@ -96,21 +161,21 @@ pub(crate) async fn get_coin_balances_by_contract(
             .await?,
     );
 
+    let liquid_staking =
+        super::liquid_staking::get_liquid_staking(rpc_client, contract_id, balance, block.height)
+            .await?;
+
     Ok(vec![coin::schemas::Coin {
         standard: "nep141".to_string(),
         contract_account_id: Some(contract_id.clone().into()),
         balance: balance.into(),
-        metadata: coin::schemas::CoinMetadata {
-            name: metadata.name,
-            symbol: metadata.symbol,
-            icon: metadata.icon,
-            decimals: metadata.decimals,
-        },
+        metadata: coin::schemas::CoinMetadata::from_ft_metadata(contract_id, metadata),
+        liquid_staking,
     }])
 }
 
 pub(crate) async fn get_ft_balance_by_contract(
-    rpc_client: &near_jsonrpc_client::JsonRpcClient,
+    rpc_client: &dyn rpc_helpers::RpcApi,
     contract_id: near_primitives::types::AccountId,
     account_id: near_primitives::types::AccountId,
     block_height: u64,
@@ -133,6 +198,7 @@ impl From<coin::schemas::NearBalanceResponse> for coin::schemas::Coin {
             balance: near_coin.balance,
             contract_account_id: None,
             metadata: near_coin.metadata,
+            liquid_staking: None,
         }
     }
 }
@@ -152,6 +218,25 @@ mod tests {
         insta::assert_debug_snapshot!(balance);
     }
 
+    #[test]
+    fn test_near_balance_breakdown() {
+        let block = get_block();
+        let near = 10u128.pow(24);
+        // 100 KB at the old price of 1 NEAR per 10 KB, and at the current one of 1 NEAR per 100 KB
+        let old_price = near_balance_breakdown(5 * near, 0, 100_000, 10u128.pow(20), &block);
+        assert_eq!(old_price.storage_locked_balance.0, 5 * near);
+        assert_eq!(old_price.available_balance.0, 0);
+        let new_price = near_balance_breakdown(5 * near, 0, 100_000, 10u128.pow(19), &block);
+        assert_eq!(new_price.storage_locked_balance.0, near);
+        assert_eq!(new_price.available_balance.0, 4 * near);
+
+        // The validator's stake covers the storage
+        let validator = near_balance_breakdown(near, 10 * near, 100_000, 10u128.pow(19), &block);
+        assert_eq!(validator.total_balance.0, 11 * near);
+        assert_eq!(validator.storage_locked_balance.0, 0);
+        assert_eq!(validator.available_balance.0, near);
+    }
+
     #[tokio::test]
     async fn test_coin_balances() {
         let pool = init_db().await;
@@ -159,7 +244,9 @@ mod tests {
         let block = get_block();
         let account = near_primitives::types::AccountId::from_str("patagonita.near").unwrap();
         let pagination = types::query_params::Pagination { limit: 10 };
-        let balance = get_coin_balances(&pool, &rpc_client, &block, &account, &pagination).await;
+        let balance = get_coin_balances(&pool, &rpc_client, &block, &account, &pagination)
+            .await
+            .map(|(balances, _)| balances);
         insta::assert_debug_snapshot!(balance);
     }
 
@@ -170,10 +257,34 @@ mod tests {
         let block = get_block();
         let account = near_primitives::types::AccountId::from_str("olga.near").unwrap();
         let pagination = types::query_params::Pagination { limit: 10 };
-        let balance = get_coin_balances(&pool, &rpc_client, &block, &account, &pagination)
-            .await
-            .unwrap();
+        let (balance, failed_contracts) =
+            get_coin_balances(&pool, &rpc_client, &block, &account, &pagination)
+                .await
+                .unwrap();
         assert!(balance.is_empty());
+        assert!(failed_contracts.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_coin_balances_failed_contract_seeded() {
+        let db = init_seeded_db().await;
+        // Nothing is mocked, so the call to token.near fails
+        let (_server, rpc_client) = init_mock_rpc().await;
+        let block = get_seeded_block(103);
+        let account = near_primitives::types::AccountId::from_str("alice.near").unwrap();
+        let pagination = types::query_params::Pagination { limit: 10 };
+
+        let (balances, failed_contracts) =
+            get_coin_balances(&db.pool, &rpc_client, &block, &account, &pagination)
+                .await
+                .unwrap();
+        assert!(balances.is_empty());
+        assert_eq!(failed_contracts.len(), 1);
+        assert_eq!(
+            failed_contracts[0].contract_account_id.to_string(),
+            "token.near"
+        );
+        assert_eq!(failed_contracts[0].error_code, "RPC_ERROR");
     }
 
     #[tokio::test]
@@ -187,6 +298,37 @@ mod tests {
         insta::assert_debug_snapshot!(balance);
     }
 
+    #[tokio::test]
+    async fn test_coin_balances_by_contract_mock_rpc() {
+        let (server, rpc_client) = init_mock_rpc().await;
+        mock_function_call(&server, "usn", "ft_balance_of", serde_json::json!("1000")).await;
+        mock_function_call(
+            &server,
+            "usn",
+            "ft_metadata",
+            serde_json::json!({
+                "spec": "ft-1.0.0",
+                "name": "USN",
+                "symbol": "USN",
+                "icon": null,
+                "reference": null,
+                "reference_hash": null,
+                "decimals": 18
+            }),
+        )
+        .await;
+        let block = get_block();
+        let contract = near_primitives::types::AccountId::from_str("usn").unwrap();
+        let account = near_primitives::types::AccountId::from_str("patagonita.near").unwrap();
+
+        let balance = get_coin_balances_by_contract(&rpc_client, &block, &contract, &account)
+            .await
+            .unwrap();
+        assert_eq!(balance.len(), 1);
+        assert_eq!(balance[0].balance, types::U128(1000));
+        assert_eq!(balance[0].metadata.symbol, "USN");
+    }
+
     #[tokio::test]
     async fn test_coin_balances_by_contract_no_contract_deployed() {
         let rpc_client = init_rpc();
@@ -0,0 +1,110 @@
+use std::str::FromStr;
+
+use crate::{db_helpers, errors};
+
+/// FT contract where the account had the events in the given range
+pub struct ChangedFtContract {
+    pub contract_id: near_primitives::types::AccountId,
+    /// `false` if the account had no events of this contract before the range:
+    /// the balance was 0, and the contract could be not deployed yet
+    pub held_before: bool,
+}
+
+/// FT contracts with the events of the account in `(from_block_timestamp, to_block_timestamp]`,
+/// ordered by contract account_id.
+/// The failed events are also counted, it only costs the extra balance call
+pub(crate) async fn get_changed_ft_contracts(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    account_id: &near_primitives::types::AccountId,
+    from_block_timestamp: u64,
+    to_block_timestamp: u64,
+    limit: u32,
+) -> crate::Result<Vec<ChangedFtContract>> {
+    let query = r"
+        SELECT
+            emitted_by_contract_account_id account_id,
+            bool_or(emitted_at_block_timestamp <= $2::numeric(20, 0)) held_before
+        FROM assets__fungible_token_events
+        WHERE (token_old_owner_account_id = $1 OR token_new_owner_account_id = $1)
+            AND emitted_at_block_timestamp <= $3::numeric(20, 0)
+        GROUP BY emitted_by_contract_account_id
+        HAVING bool_or(emitted_at_block_timestamp > $2::numeric(20, 0))
+        ORDER BY emitted_by_contract_account_id
+        LIMIT $4::numeric(20, 0)
+    ";
+    let contracts = db_helpers::select_retry_or_panic::<super::models::ChangedFtContractInfo>(
+        pool,
+        query,
+        &[
+            account_id.to_string(),
+            from_block_timestamp.to_string(),
+            to_block_timestamp.to_string(),
+            limit.to_string(),
+        ],
+    )
+    .await?;
+
+    let mut result: Vec<ChangedFtContract> = vec![];
+    for contract in contracts {
+        result.push(contract.try_into()?);
+    }
+    Ok(result)
+}
+
+impl TryFrom<super::models::ChangedFtContractInfo> for ChangedFtContract {
+    type Error = errors::Error;
+
+    fn try_from(info: super::models::ChangedFtContractInfo) -> crate::Result<Self> {
+        Ok(Self {
+            contract_id: near_primitives::types::AccountId::from_str(&info.account_id)?,
+            held_before: info.held_before,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+
+    #[tokio::test]
+    async fn test_changed_ft_contracts_seeded() {
+        let db = init_seeded_db().await;
+        let alice = near_primitives::types::AccountId::from_str("alice.near").unwrap();
+        let bob = near_primitives::types::AccountId::from_str("bob.near").unwrap();
+
+        // alice.near got the tokens at block 100 and sent some at 101
+        let contracts = get_changed_ft_contracts(
+            &db.pool,
+            &alice,
+            1600000000000000100,
+            1600000000000000101,
+            10,
+        )
+        .await
+        .unwrap();
+        assert_eq!(contracts.len(), 1);
+        assert_eq!(contracts[0].contract_id.as_str(), "token.near");
+        assert!(contracts[0].held_before);
+
+        // bob.near had nothing before block 101
+        let contracts =
+            get_changed_ft_contracts(&db.pool, &bob, 1600000000000000100, 1600000000000000103, 10)
+                .await
+                .unwrap();
+        assert_eq!(contracts.len(), 1);
+        assert!(!contracts[0].held_before);
+
+        // Nothing happened after block 102
+        let contracts = get_changed_ft_contracts(
+            &db.pool,
+            &alice,
+            1600000000000000102,
+            1600000000000000103,
+            10,
+        )
+        .await
+        .unwrap();
+        assert!(contracts.is_empty());
+    }
+}
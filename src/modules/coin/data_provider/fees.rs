@@ -0,0 +1,96 @@
+use crate::modules::coin;
+use crate::{balances_schema, db_helpers, types};
+
+const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// Gas refunds come as the separate balance changes, so we sum them up with the payments
+pub(crate) async fn get_gas_fees(
+    balances_db: &balances_schema::BalancesDb,
+    account_id: &near_primitives::types::AccountId,
+    block: &db_helpers::Block,
+    window_nanos: u64,
+) -> crate::Result<coin::schemas::GasFeesResponse> {
+    let window_start = block.timestamp.saturating_sub(window_nanos);
+    let query = format!(
+        r"
+        SELECT
+            div(block_timestamp, $4::numeric(20, 0)) * $4::numeric(20, 0) bucket_timestamp,
+            -sum(delta_nonstaked_amount) burnt,
+            count(*) FILTER (WHERE delta_nonstaked_amount < 0) payments_count
+        FROM {}
+        WHERE affected_account_id = $1
+            AND cause IN ('GAS', 'GAS_REFUND')
+            AND block_timestamp > $2::numeric(20, 0)
+            AND block_timestamp <= $3::numeric(20, 0)
+        GROUP BY bucket_timestamp
+        ORDER BY bucket_timestamp DESC
+    ",
+        balances_db.balance_changes()
+    );
+    let buckets = db_helpers::select_retry_or_panic::<super::models::GasFeesBucketInfo>(
+        &balances_db.pool,
+        &query,
+        &[
+            account_id.to_string(),
+            window_start.to_string(),
+            block.timestamp.to_string(),
+            DAY_NANOS.to_string(),
+        ],
+    )
+    .await?;
+
+    let mut daily: Vec<coin::schemas::GasFeesBucket> = vec![];
+    let mut payments_count = 0;
+    for bucket in buckets {
+        // Refund may land in the next day, or even outside of the window
+        let burnt = types::numeric::to_i128(&bucket.burnt)?.max(0) as u128;
+        payments_count += bucket.payments_count as u64;
+        daily.push(coin::schemas::GasFeesBucket {
+            timestamp_nanos: types::numeric::to_u64(&bucket.bucket_timestamp)?.into(),
+            burnt: burnt.into(),
+        });
+    }
+
+    Ok(coin::schemas::GasFeesResponse {
+        total_burnt: daily
+            .iter()
+            .map(|bucket| bucket.burnt.0)
+            .sum::<u128>()
+            .into(),
+        payments_count,
+        daily,
+        window_start_timestamp_nanos: window_start.into(),
+        meta: types::ResponseMeta::new(&block),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_gas_fees_seeded() {
+        let db = init_seeded_db().await;
+        let account = near_primitives::types::AccountId::from_str("carol.near").unwrap();
+        let block = get_seeded_block(103);
+
+        let fees = get_gas_fees(&db.balances_db(), &account, &block, 1_000_000)
+            .await
+            .unwrap();
+        assert_eq!(fees.total_burnt.0, 25);
+        assert_eq!(fees.payments_count, 2);
+        assert_eq!(fees.daily.len(), 1);
+        assert_eq!(
+            fees.daily[0].timestamp_nanos.0,
+            1600000000000000103 / DAY_NANOS * DAY_NANOS
+        );
+
+        let fees = get_gas_fees(&db.balances_db(), &account, &block, 1)
+            .await
+            .unwrap();
+        assert_eq!(fees.total_burnt.0, 0);
+        assert!(fees.daily.is_empty());
+    }
+}
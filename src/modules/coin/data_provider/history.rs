@@ -1,56 +1,172 @@
 use std::str::FromStr;
 
 use crate::modules::coin;
-use crate::{db_helpers, errors, types};
+use crate::{balances_schema, db_helpers, errors, rpc_helpers, types};
 
-// TODO PHASE 2 pagination by artificial index added to balance_changes
+/// `pool` is the indexer DB, the signers and the predecessors are taken from there
 pub(crate) async fn get_near_history(
-    balances_pool: &sqlx::Pool<sqlx::Postgres>,
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    balances_db: &balances_schema::BalancesDb,
     account_id: &near_primitives::types::AccountId,
     pagination: &types::query_params::HistoryPagination,
-) -> crate::Result<Vec<coin::schemas::HistoryItem>> {
-    let query = r"
+) -> crate::Result<Vec<(coin::schemas::HistoryItem, types::query_params::EventIndex)>> {
+    let query = format!(
+        r"
         SELECT
+            receipt_id,
+            transaction_hash,
             involved_account_id,
             delta_nonstaked_amount + delta_staked_amount delta_balance,
             absolute_nonstaked_amount + absolute_staked_amount balance,
             cause,
             status,
-            block_timestamp block_timestamp_nanos
-        FROM balance_changes
-        WHERE affected_account_id = $1 AND block_timestamp < $2::numeric(20, 0)
-        ORDER BY block_timestamp DESC
-        LIMIT $3::numeric(20, 0)
-    ";
+            block_timestamp block_timestamp_nanos,
+            shard_id::numeric(20, 0),
+            index_in_chunk::numeric(20, 0)
+        FROM {}
+        WHERE affected_account_id = $1
+            AND block_timestamp < $2::numeric(20, 0)
+            AND (block_timestamp, shard_id, index_in_chunk) < ($3::numeric(20, 0), $4::numeric(20, 0), $5::numeric(20, 0))
+            AND block_timestamp > $6::numeric(20, 0)
+            AND block_timestamp <= $7::numeric(20, 0)
+        ORDER BY block_timestamp DESC, shard_id DESC, index_in_chunk DESC
+        LIMIT $8::numeric(20, 0)
+    ",
+        balances_db.balance_changes()
+    );
 
-    let history_info = db_helpers::select_retry_or_panic::<super::models::NearHistoryInfo>(
-        balances_pool,
-        query,
-        &[
-            account_id.to_string(),
-            pagination.block_timestamp.to_string(),
-            pagination.limit.to_string(),
-        ],
+    let bound = pagination.event_bound();
+    let mut history_info: Vec<super::models::NearHistoryInfo> = vec![];
+    // The plain bounds let Postgres skip the partitions outside of the window
+    for (after_timestamp, until_timestamp) in
+        balances_schema::history_search_ranges(bound.block_timestamp)
+    {
+        let limit = pagination.limit as usize - history_info.len();
+        history_info.extend(
+            db_helpers::select_retry_or_panic::<super::models::NearHistoryInfo>(
+                &balances_db.pool,
+                &query,
+                &[
+                    account_id.to_string(),
+                    pagination.block_timestamp.to_string(),
+                    bound.block_timestamp.to_string(),
+                    bound.shard_id.to_string(),
+                    bound.index_in_chunk.to_string(),
+                    after_timestamp.to_string(),
+                    until_timestamp.to_string(),
+                    limit.to_string(),
+                ],
+            )
+            .await?,
+        );
+        if history_info.len() >= pagination.limit as usize {
+            break;
+        }
+    }
+
+    let originators = get_originators(
+        pool,
+        history_info
+            .iter()
+            .filter_map(|history| history.receipt_id.clone()),
+        history_info
+            .iter()
+            .filter(|history| history.receipt_id.is_none())
+            .filter_map(|history| history.transaction_hash.clone()),
     )
     .await?;
 
-    let mut result: Vec<coin::schemas::HistoryItem> = vec![];
+    let mut result = vec![];
     for history in history_info {
-        result.push(history.try_into()?);
+        let event_index = types::query_params::EventIndex::from_db(
+            &history.block_timestamp_nanos,
+            &history.shard_id,
+            &history.index_in_chunk,
+        )?;
+        let originator = history
+            .receipt_id
+            .as_ref()
+            .or(history.transaction_hash.as_ref())
+            .and_then(|id| originators.get(id));
+        let mut item: coin::schemas::HistoryItem = history.try_into()?;
+        if let Some(originator) = originator {
+            item.signer_account_id =
+                types::account_id::extract_account_id(&originator.signer_account_id)?
+                    .map(|id| id.into());
+            item.predecessor_account_id =
+                types::account_id::extract_account_id(&originator.predecessor_account_id)?
+                    .map(|id| id.into());
+        }
+        result.push((item, event_index));
     }
     Ok(result)
 }
 
-// TODO PHASE 2 pagination by artificial index added to assets__fungible_token_events
+/// The signers and the predecessors of the receipts, and the signers of the transactions (e.g. for the gas payments).
+/// balance_changes lives in the other DB, so we can't join it
+async fn get_originators(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    receipt_ids: impl Iterator<Item = String>,
+    transaction_hashes: impl Iterator<Item = String>,
+) -> crate::Result<std::collections::HashMap<String, super::models::OriginatorInfo>> {
+    let receipt_ids: Vec<String> = receipt_ids.collect();
+    let transaction_hashes: Vec<String> = transaction_hashes.collect();
+    if receipt_ids.is_empty() && transaction_hashes.is_empty() {
+        return Ok(std::collections::HashMap::new());
+    }
+    let originators = db_helpers::select_retry_or_panic::<super::models::OriginatorInfo>(
+        pool,
+        r"
+        SELECT receipt_id id, signer_account_id, predecessor_account_id
+        FROM action_receipts
+        WHERE receipt_id = ANY($1::text[])
+        UNION ALL
+        SELECT transaction_hash id, signer_account_id, signer_account_id predecessor_account_id
+        FROM transactions
+        WHERE transaction_hash = ANY($2::text[])
+    ",
+        &[
+            format!("{{{}}}", receipt_ids.join(",")),
+            format!("{{{}}}", transaction_hashes.join(",")),
+        ],
+    )
+    .await?;
+    Ok(originators
+        .into_iter()
+        .map(|originator| (originator.id.clone(), originator))
+        .collect())
+}
+
+const COIN_HISTORY_COLUMNS: &str = r"
+            blocks.block_timestamp,
+            assets__fungible_token_events.amount::numeric(45, 0),
+            assets__fungible_token_events.event_kind::text cause,
+            CASE WHEN execution_outcomes.status IN ('SUCCESS_VALUE', 'SUCCESS_RECEIPT_ID') THEN 'SUCCESS'
+                ELSE 'FAILURE'
+            END status,
+            assets__fungible_token_events.token_old_owner_account_id old_owner_id,
+            assets__fungible_token_events.token_new_owner_account_id new_owner_id,
+            action_receipts.signer_account_id,
+            action_receipts.predecessor_account_id,
+            assets__fungible_token_events.emitted_in_shard_id shard_id,
+            assets__fungible_token_events.emitted_index_of_event_entry_in_shard::numeric(20, 0) index_in_chunk
+        FROM assets__fungible_token_events
+            JOIN blocks ON assets__fungible_token_events.emitted_at_block_timestamp = blocks.block_timestamp
+            JOIN execution_outcomes ON assets__fungible_token_events.emitted_for_receipt_id = execution_outcomes.receipt_id
+            LEFT JOIN action_receipts ON assets__fungible_token_events.emitted_for_receipt_id = action_receipts.receipt_id
+        WHERE emitted_by_contract_account_id = $1
+            AND (token_old_owner_account_id = $2 OR token_new_owner_account_id = $2)";
+
 // TODO PHASE 2 change RPC call to DB call by adding absolute amount values to assets__fungible_token_events
 // TODO PHASE 2 make the decision about separate FT/MT tables or one table. Pagination implementation depends on this
+/// `pagination.block_height` is the block to take the balance at, the last one before `pagination.block_timestamp`
 pub(crate) async fn get_coin_history(
     pool: &sqlx::Pool<sqlx::Postgres>,
-    rpc_client: &near_jsonrpc_client::JsonRpcClient,
+    rpc_client: &dyn rpc_helpers::RpcApi,
     contract_id: &near_primitives::types::AccountId,
     account_id: &near_primitives::types::AccountId,
     pagination: &types::query_params::HistoryPagination,
-) -> crate::Result<Vec<coin::schemas::HistoryItem>> {
+) -> crate::Result<Vec<(coin::schemas::HistoryItem, types::query_params::EventIndex)>> {
     // this is temp solution before we make changes to the DB
     let mut last_balance = super::balance::get_ft_balance_by_contract(
         rpc_client,
@@ -59,7 +175,8 @@ pub(crate) async fn get_coin_history(
         pagination.block_height,
     )
     .await?;
-    let metadata = coin::schemas::CoinMetadata::from(
+    let metadata = coin::schemas::CoinMetadata::from_ft_metadata(
+        contract_id,
         super::metadata::get_ft_contract_metadata(
             rpc_client,
             contract_id.clone(),
@@ -69,82 +186,152 @@ pub(crate) async fn get_coin_history(
     );
 
     let account_id = account_id.to_string();
-    let query = r"
-        SELECT
-            -- blocks.block_height,
-            blocks.block_timestamp,
-            assets__fungible_token_events.amount::numeric(45, 0),
-            assets__fungible_token_events.event_kind::text cause,
-            CASE WHEN execution_outcomes.status IN ('SUCCESS_VALUE', 'SUCCESS_RECEIPT_ID') THEN 'SUCCESS'
-                ELSE 'FAILURE'
-            END status,
-            assets__fungible_token_events.token_old_owner_account_id old_owner_id,
-            assets__fungible_token_events.token_new_owner_account_id new_owner_id
-        FROM assets__fungible_token_events
-            JOIN blocks ON assets__fungible_token_events.emitted_at_block_timestamp = blocks.block_timestamp
-            JOIN execution_outcomes ON assets__fungible_token_events.emitted_for_receipt_id = execution_outcomes.receipt_id
-        WHERE emitted_by_contract_account_id = $1
-            AND (token_old_owner_account_id = $2 OR token_new_owner_account_id = $2)
-            AND emitted_at_block_timestamp <= $3::numeric(20, 0)
-        ORDER BY emitted_at_block_timestamp desc
-        LIMIT $4::numeric(20, 0)
-    ";
+    let bound = pagination.event_bound();
+    // The balance is taken at the end of the block, but the page could start in the middle of it.
+    // The events of this block which were on the previous page are rolled back first
+    if let Some(before_event) = pagination.before_event {
+        let query = format!(
+            r"
+            SELECT {}
+                AND emitted_at_block_timestamp = $3::numeric(20, 0)
+                AND (emitted_in_shard_id, emitted_index_of_event_entry_in_shard) >= ($4::numeric(20, 0), $5::numeric(20, 0))
+        ",
+            COIN_HISTORY_COLUMNS
+        );
+        let rolled_back = db_helpers::select_retry_or_panic::<super::models::CoinHistoryInfo>(
+            pool,
+            &query,
+            &[
+                contract_id.to_string(),
+                account_id.clone(),
+                before_event.block_timestamp.to_string(),
+                before_event.shard_id.to_string(),
+                before_event.index_in_chunk.to_string(),
+            ],
+        )
+        .await?;
+        for db_info in rolled_back {
+            let (delta, _) = get_delta(&account_id, &db_info)?;
+            last_balance = roll_back(last_balance, delta, &db_info, &account_id, contract_id)?;
+        }
+    }
+
+    let query = format!(
+        r"
+        SELECT {}
+            AND emitted_at_block_timestamp < $3::numeric(20, 0)
+            AND (emitted_at_block_timestamp, emitted_in_shard_id, emitted_index_of_event_entry_in_shard)
+                < ($4::numeric(20, 0), $5::numeric(20, 0), $6::numeric(20, 0))
+            AND emitted_at_block_timestamp <= $4::numeric(20, 0)
+        ORDER BY emitted_at_block_timestamp DESC, emitted_in_shard_id DESC, emitted_index_of_event_entry_in_shard DESC
+        LIMIT $7::numeric(20, 0)
+    ",
+        COIN_HISTORY_COLUMNS
+    );
     let history_info = db_helpers::select_retry_or_panic::<super::models::CoinHistoryInfo>(
         pool,
-        query,
+        &query,
         &[
             contract_id.to_string(),
             account_id.clone(),
             pagination.block_timestamp.to_string(),
+            bound.block_timestamp.to_string(),
+            bound.shard_id.to_string(),
+            bound.index_in_chunk.to_string(),
             pagination.limit.to_string(),
         ],
     )
     .await?;
 
-    let mut result: Vec<coin::schemas::HistoryItem> = vec![];
+    let mut result = vec![];
     for db_info in history_info {
-        let mut delta: i128 = types::numeric::to_i128(&db_info.amount)?;
+        let (delta, involved_account_id) = get_delta(&account_id, &db_info)?;
         let balance = last_balance;
-        // TODO PHASE 2 maybe we want to change assets__fungible_token_events also to affected/involved?
-        let involved_account_id = if account_id == db_info.old_owner_id {
-            delta = -delta;
-            types::account_id::extract_account_id(&db_info.new_owner_id)?
-        } else if account_id == db_info.new_owner_id {
-            types::account_id::extract_account_id(&db_info.old_owner_id)?
-        } else {
-            return Err(
-                errors::ErrorKind::InternalError(
-                    format!("The account {} should be sender or receiver ({}, {}). If you see this, please create the issue",
-                            account_id, db_info.old_owner_id, db_info.new_owner_id)).into(),
-            );
-        };
-
-        if db_info.status == "SUCCESS" {
-            // TODO PHASE 2 this strange error will go away after we add absolute amounts to the DB
-            if (last_balance as i128) - delta < 0 {
-                return Err(errors::ErrorKind::InternalError(format!(
-                    "Balance could not be negative: account {}, contract {}",
-                    account_id, contract_id
-                ))
-                .into());
-            }
-            last_balance = ((last_balance as i128) - delta) as u128;
-        }
+        last_balance = roll_back(last_balance, delta, &db_info, &account_id, contract_id)?;
 
-        result.push(coin::schemas::HistoryItem {
-            cause: db_info.cause.clone(),
-            involved_account_id: involved_account_id.map(|id| id.into()),
-            delta_balance: delta.into(),
-            balance: balance.into(),
-            coin_metadata: metadata.clone(),
-            block_timestamp_nanos: types::numeric::to_u64(&db_info.block_timestamp)?.into(),
-            // block_height: types::numeric::to_u64(&db_info.block_height)?.into(),
-            status: db_info.status,
-        });
+        let event_index = types::query_params::EventIndex::from_db(
+            &db_info.block_timestamp,
+            &db_info.shard_id,
+            &db_info.index_in_chunk,
+        )?;
+        result.push((
+            coin::schemas::HistoryItem {
+                cause: db_info.cause.clone().into(),
+                involved_account_id: involved_account_id.map(|id| id.into()),
+                signer_account_id: extract_optional_account_id(&db_info.signer_account_id)?,
+                predecessor_account_id: extract_optional_account_id(
+                    &db_info.predecessor_account_id,
+                )?,
+                delta_balance: delta.into(),
+                balance: balance.into(),
+                coin_metadata: metadata.clone(),
+                block_timestamp_nanos: types::numeric::to_u64(&db_info.block_timestamp)?.into(),
+                // block_height: types::numeric::to_u64(&db_info.block_height)?.into(),
+                status: db_info.status,
+            },
+            event_index,
+        ));
     }
     Ok(result)
 }
 
+/// The change of the balance of the account, and the other side of the transfer
+fn get_delta(
+    account_id: &str,
+    db_info: &super::models::CoinHistoryInfo,
+) -> crate::Result<(i128, Option<near_primitives::types::AccountId>)> {
+    let delta: i128 = types::numeric::to_i128(&db_info.amount)?;
+    // TODO PHASE 2 maybe we want to change assets__fungible_token_events also to affected/involved?
+    if account_id == db_info.old_owner_id {
+        Ok((
+            -delta,
+            types::account_id::extract_account_id(&db_info.new_owner_id)?,
+        ))
+    } else if account_id == db_info.new_owner_id {
+        Ok((
+            delta,
+            types::account_id::extract_account_id(&db_info.old_owner_id)?,
+        ))
+    } else {
+        Err(
+            errors::ErrorKind::InternalError(
+                format!("The account {} should be sender or receiver ({}, {}). If you see this, please create the issue",
+                        account_id, db_info.old_owner_id, db_info.new_owner_id)).into(),
+        )
+    }
+}
+
+fn extract_optional_account_id(
+    account_id: &Option<String>,
+) -> crate::Result<Option<types::AccountId>> {
+    Ok(match account_id {
+        Some(account_id) => types::account_id::extract_account_id(account_id)?.map(|id| id.into()),
+        None => None,
+    })
+}
+
+/// The balance before the event, failed events did not change it
+fn roll_back(
+    balance: u128,
+    delta: i128,
+    db_info: &super::models::CoinHistoryInfo,
+    account_id: &str,
+    contract_id: &near_primitives::types::AccountId,
+) -> crate::Result<u128> {
+    if db_info.status != "SUCCESS" {
+        return Ok(balance);
+    }
+    // TODO PHASE 2 this strange error will go away after we add absolute amounts to the DB
+    if (balance as i128) - delta < 0 {
+        return Err(errors::ErrorKind::InternalError(format!(
+            "Balance could not be negative: account {}, contract {}",
+            account_id, contract_id
+        ))
+        .into());
+    }
+    Ok(((balance as i128) - delta) as u128)
+}
+
 impl TryFrom<super::models::NearHistoryInfo> for coin::schemas::HistoryItem {
     type Error = errors::Error;
 
@@ -157,9 +344,12 @@ impl TryFrom<super::models::NearHistoryInfo> for coin::schemas::HistoryItem {
             };
         Ok(Self {
             involved_account_id,
+            // Filled in by get_near_history, the data lives in the other DB
+            signer_account_id: None,
+            predecessor_account_id: None,
             delta_balance: types::numeric::to_i128(&info.delta_balance)?.into(),
             balance: types::numeric::to_u128(&info.balance)?.into(),
-            cause: info.cause,
+            cause: info.cause.into(),
             status: info.status,
             coin_metadata: super::get_near_metadata(),
             block_timestamp_nanos: types::numeric::to_u64(&info.block_timestamp_nanos)?.into(),
@@ -172,6 +362,81 @@ mod tests {
     use super::*;
     use crate::modules::tests::*;
 
+    #[test]
+    fn test_history_cause_normalization() {
+        assert_eq!(
+            coin::schemas::HistoryCause::from("VALIDATORS_REWARD".to_string()),
+            coin::schemas::HistoryCause::ValidatorReward
+        );
+        assert_eq!(
+            coin::schemas::HistoryCause::from("transfer".to_string()),
+            coin::schemas::HistoryCause::Transfer
+        );
+        assert_eq!(
+            coin::schemas::HistoryCause::from("SOMETHING_NEW".to_string()),
+            coin::schemas::HistoryCause::Unknown("SOMETHING_NEW".to_string())
+        );
+        assert_eq!(
+            String::from(coin::schemas::HistoryCause::ContractReward),
+            "CONTRACT_REWARD"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_near_history_seeded() {
+        let db = init_seeded_db().await;
+        let account = near_primitives::types::AccountId::from_str("alice.near").unwrap();
+        let pagination = types::query_params::HistoryPagination {
+            block_height: 104,
+            block_timestamp: 1600000000000000104,
+            before_event: None,
+            limit: 2,
+        };
+
+        let history = get_near_history(&db.pool, &db.balances_db(), &account, &pagination)
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].1.block_timestamp, 1600000000000000101);
+        let history = crate::modules::without_event_indexes(history);
+        // Staked and nonstaked amounts are summed up
+        assert_eq!(history[0].delta_balance.0, 50);
+        assert_eq!(history[0].balance.0, 750);
+        assert_eq!(history[0].cause, coin::schemas::HistoryCause::ValidatorReward);
+        assert_eq!(history[1].delta_balance.0, -300);
+        assert_eq!(history[1].balance.0, 700);
+        assert_eq!(
+            history[1].involved_account_id,
+            Some(near_primitives::types::AccountId::from_str("bob.near").unwrap().into())
+        );
+        assert_eq!(history[1].block_timestamp_nanos.0, 1600000000000000101);
+        assert_eq!(
+            history[1].signer_account_id,
+            Some(near_primitives::types::AccountId::from_str("alice.near").unwrap().into())
+        );
+        assert_eq!(history[1].predecessor_account_id, history[1].signer_account_id);
+
+        // The page goes strictly before the event of the cursor
+        let pagination = types::query_params::HistoryPagination {
+            block_height: 104,
+            block_timestamp: 1600000000000000103,
+            before_event: Some(types::query_params::EventIndex {
+                block_timestamp: 1600000000000000102,
+                shard_id: 0,
+                index_in_chunk: 0,
+            }),
+            limit: 10,
+        };
+        let history = get_near_history(&db.pool, &db.balances_db(), &account, &pagination)
+            .await
+            .unwrap();
+        let timestamps: Vec<u64> = history
+            .iter()
+            .map(|(item, _)| item.block_timestamp_nanos.0)
+            .collect();
+        assert_eq!(timestamps, vec![1600000000000000101, 1600000000000000100]);
+    }
+
     #[tokio::test]
     async fn test_near_history() {
         let block = get_block();
@@ -186,10 +451,18 @@ mod tests {
         let pagination = types::query_params::HistoryPagination {
             block_height: block.height,
             block_timestamp: block.timestamp,
+            before_event: None,
             limit: 10,
         };
 
-        let balance = get_near_history(&pool, &account, &pagination).await;
+        let balance = get_near_history(
+            &init_db().await,
+            &balances_schema::BalancesDb::new(pool, Default::default()),
+            &account,
+            &pagination,
+        )
+        .await
+        .map(crate::modules::without_event_indexes);
         insta::assert_debug_snapshot!(balance);
     }
 
@@ -197,7 +470,8 @@ mod tests {
     async fn test_near_history_with_failed_receipts() {
         let block = db_helpers::Block {
             timestamp: 1618591017607373869,
-            height: 34943083
+            height: 34943083,
+            hash: String::new(),
         };
         // Using the other pool because we have this table at the other DB
         dotenv::dotenv().ok();
@@ -210,10 +484,18 @@ mod tests {
         let pagination = types::query_params::HistoryPagination {
             block_height: block.height,
             block_timestamp: block.timestamp,
+            before_event: None,
             limit: 10,
         };
 
-        let balance = get_near_history(&pool, &account, &pagination).await;
+        let balance = get_near_history(
+            &init_db().await,
+            &balances_schema::BalancesDb::new(pool, Default::default()),
+            &account,
+            &pagination,
+        )
+        .await
+        .map(crate::modules::without_event_indexes);
         insta::assert_debug_snapshot!(balance);
     }
 
@@ -227,10 +509,13 @@ mod tests {
         let pagination = types::query_params::HistoryPagination {
             block_height: block.height,
             block_timestamp: block.timestamp,
+            before_event: None,
             limit: 10,
         };
 
-        let balance = get_coin_history(&pool, &rpc_client, &contract, &account, &pagination).await;
+        let balance = get_coin_history(&pool, &rpc_client, &contract, &account, &pagination)
+            .await
+            .map(crate::modules::without_event_indexes);
         insta::assert_debug_snapshot!(balance);
     }
 
@@ -240,17 +525,21 @@ mod tests {
         let rpc_client = init_rpc();
         let block = db_helpers::Block {
             timestamp: 1651062637353692535,
-            height: 64408633
+            height: 64408633,
+            hash: String::new(),
         };
         let contract = near_primitives::types::AccountId::from_str("sweat_token_testing.near").unwrap();
         let account = near_primitives::types::AccountId::from_str("intmainreturn0.near").unwrap();
         let pagination = types::query_params::HistoryPagination {
             block_height: block.height,
             block_timestamp: block.timestamp,
+            before_event: None,
             limit: 10,
         };
 
-        let balance = get_coin_history(&pool, &rpc_client, &contract, &account, &pagination).await;
+        let balance = get_coin_history(&pool, &rpc_client, &contract, &account, &pagination)
+            .await
+            .map(crate::modules::without_event_indexes);
         insta::assert_debug_snapshot!(balance);
     }
 }
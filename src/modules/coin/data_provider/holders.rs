@@ -0,0 +1,240 @@
+use std::str::FromStr;
+
+use crate::modules::coin;
+use crate::{db_helpers, errors, types};
+
+/// Accounts with the positive balance at the given moment, ordered by account_id.
+/// The balances are the sums of the successful events, so the page boundaries don't move
+/// while the new events arrive: the same `block_timestamp` always gives the same snapshot
+pub(crate) async fn get_ft_holders(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    contract_id: &near_primitives::types::AccountId,
+    block_timestamp: u64,
+    after_account_id: Option<&near_primitives::types::AccountId>,
+    limit: u32,
+) -> crate::Result<Vec<coin::schemas::FtHolder>> {
+    let query = r"
+        WITH successful_events AS (
+            SELECT
+                assets__fungible_token_events.token_old_owner_account_id old_owner_id,
+                assets__fungible_token_events.token_new_owner_account_id new_owner_id,
+                assets__fungible_token_events.amount::numeric(45, 0) amount
+            FROM assets__fungible_token_events
+                JOIN execution_outcomes ON assets__fungible_token_events.emitted_for_receipt_id = execution_outcomes.receipt_id
+            WHERE emitted_by_contract_account_id = $1
+                AND emitted_at_block_timestamp <= $2::numeric(20, 0)
+                AND execution_outcomes.status IN ('SUCCESS_VALUE', 'SUCCESS_RECEIPT_ID')
+        ), deltas AS (
+            SELECT new_owner_id account_id, amount delta FROM successful_events WHERE new_owner_id != ''
+            UNION ALL
+            SELECT old_owner_id account_id, -amount delta FROM successful_events WHERE old_owner_id != ''
+        )
+        SELECT account_id, sum(delta)::numeric(45, 0) balance
+        FROM deltas
+        WHERE account_id > $3
+        GROUP BY account_id
+        HAVING sum(delta) > 0
+        ORDER BY account_id
+        LIMIT $4::numeric(20, 0)
+    ";
+    let holders = db_helpers::select_retry_or_panic::<super::models::FtHolderInfo>(
+        pool,
+        query,
+        &[
+            contract_id.to_string(),
+            block_timestamp.to_string(),
+            after_account_id
+                .map(|account_id| account_id.to_string())
+                .unwrap_or_default(),
+            limit.to_string(),
+        ],
+    )
+    .await?;
+
+    let mut result: Vec<coin::schemas::FtHolder> = vec![];
+    for holder in holders {
+        result.push(holder.try_into()?);
+    }
+    Ok(result)
+}
+
+/// The balance of one account computed the same way as for the holders,
+/// `ft_balance_of` gives the contract's opinion, see `/reconcile`
+pub(crate) async fn get_ft_balance_from_events(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    contract_id: &near_primitives::types::AccountId,
+    account_id: &near_primitives::types::AccountId,
+    block_timestamp: u64,
+) -> crate::Result<u128> {
+    let query = r"
+        WITH successful_events AS (
+            SELECT
+                assets__fungible_token_events.token_old_owner_account_id old_owner_id,
+                assets__fungible_token_events.token_new_owner_account_id new_owner_id,
+                assets__fungible_token_events.amount::numeric(45, 0) amount
+            FROM assets__fungible_token_events
+                JOIN execution_outcomes ON assets__fungible_token_events.emitted_for_receipt_id = execution_outcomes.receipt_id
+            WHERE emitted_by_contract_account_id = $1
+                AND (token_old_owner_account_id = $2 OR token_new_owner_account_id = $2)
+                AND emitted_at_block_timestamp <= $3::numeric(20, 0)
+                AND execution_outcomes.status IN ('SUCCESS_VALUE', 'SUCCESS_RECEIPT_ID')
+        ), deltas AS (
+            SELECT new_owner_id account_id, amount delta FROM successful_events WHERE new_owner_id = $2
+            UNION ALL
+            SELECT old_owner_id account_id, -amount delta FROM successful_events WHERE old_owner_id = $2
+        )
+        SELECT account_id, sum(delta)::numeric(45, 0) balance
+        FROM deltas
+        GROUP BY account_id
+    ";
+    let balances = db_helpers::select_retry_or_panic::<super::models::FtHolderInfo>(
+        pool,
+        query,
+        &[
+            contract_id.to_string(),
+            account_id.to_string(),
+            block_timestamp.to_string(),
+        ],
+    )
+    .await?;
+
+    match balances.first() {
+        // The negative sum means the events are broken, it should not look like the zero balance
+        Some(balance) => types::numeric::to_u128(&balance.balance),
+        None => Ok(0),
+    }
+}
+
+/// The receiver of the FT event at the random moment of the indexed history, with the moment itself.
+/// `None` if there are no FT events yet
+pub(crate) async fn get_random_ft_balance_sample(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+) -> crate::Result<Option<FtBalanceSample>> {
+    // The random timestamp goes by the index, `ORDER BY random()` would read the whole table.
+    // The timestamp is taken between the first and the last event, so there is always the event after it
+    let query = r"
+        SELECT
+            emitted_by_contract_account_id contract_account_id,
+            token_new_owner_account_id account_id,
+            emitted_at_block_timestamp block_timestamp
+        FROM assets__fungible_token_events
+        WHERE emitted_at_block_timestamp >= (
+                SELECT min(emitted_at_block_timestamp)
+                    + floor((max(emitted_at_block_timestamp) - min(emitted_at_block_timestamp)) * random()::numeric)
+                FROM assets__fungible_token_events
+            )
+            AND token_new_owner_account_id != ''
+        ORDER BY emitted_at_block_timestamp
+        LIMIT 1
+    ";
+    let samples =
+        db_helpers::select_retry_or_panic::<super::models::FtBalanceSampleInfo>(pool, query, &[])
+            .await?;
+
+    match samples.into_iter().next() {
+        Some(sample) => Ok(Some(FtBalanceSample {
+            contract_id: near_primitives::types::AccountId::from_str(&sample.contract_account_id)?,
+            account_id: near_primitives::types::AccountId::from_str(&sample.account_id)?,
+            block_timestamp: types::numeric::to_u64(&sample.block_timestamp)?,
+        })),
+        None => Ok(None),
+    }
+}
+
+pub(crate) struct FtBalanceSample {
+    pub contract_id: near_primitives::types::AccountId,
+    pub account_id: near_primitives::types::AccountId,
+    pub block_timestamp: u64,
+}
+
+impl TryFrom<super::models::FtHolderInfo> for coin::schemas::FtHolder {
+    type Error = errors::Error;
+
+    fn try_from(info: super::models::FtHolderInfo) -> crate::Result<Self> {
+        Ok(Self {
+            account_id: near_primitives::types::AccountId::from_str(&info.account_id)?.into(),
+            balance: types::numeric::to_u128(&info.balance)?.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+
+    #[tokio::test]
+    async fn test_ft_holders_seeded() {
+        let db = init_seeded_db().await;
+        let contract = near_primitives::types::AccountId::from_str("token.near").unwrap();
+
+        // The failed transfer at block 102 does not change anything
+        let holders = get_ft_holders(&db.pool, &contract, 1600000000000000103, None, 10)
+            .await
+            .unwrap();
+        let balances: Vec<(String, u128)> = holders
+            .iter()
+            .map(|holder| (holder.account_id.to_string(), holder.balance.0))
+            .collect();
+        assert_eq!(
+            balances,
+            vec![
+                ("alice.near".to_string(), 700),
+                ("bob.near".to_string(), 300)
+            ]
+        );
+
+        // Snapshot before the transfer to bob
+        let holders = get_ft_holders(&db.pool, &contract, 1600000000000000100, None, 10)
+            .await
+            .unwrap();
+        assert_eq!(holders.len(), 1);
+        assert_eq!(holders[0].balance.0, 1000);
+
+        // The next page starts after the cursor
+        let alice = near_primitives::types::AccountId::from_str("alice.near").unwrap();
+        let holders = get_ft_holders(&db.pool, &contract, 1600000000000000103, Some(&alice), 1)
+            .await
+            .unwrap();
+        assert_eq!(holders.len(), 1);
+        assert_eq!(holders[0].account_id.to_string(), "bob.near");
+    }
+
+    #[tokio::test]
+    async fn test_ft_balance_from_events_seeded() {
+        let db = init_seeded_db().await;
+        let contract = near_primitives::types::AccountId::from_str("token.near").unwrap();
+        let bob = near_primitives::types::AccountId::from_str("bob.near").unwrap();
+        let carol = near_primitives::types::AccountId::from_str("carol.near").unwrap();
+
+        let balance = get_ft_balance_from_events(&db.pool, &contract, &bob, 1600000000000000103)
+            .await
+            .unwrap();
+        assert_eq!(balance, 300);
+        let balance = get_ft_balance_from_events(&db.pool, &contract, &bob, 1600000000000000100)
+            .await
+            .unwrap();
+        assert_eq!(balance, 0);
+        let balance = get_ft_balance_from_events(&db.pool, &contract, &carol, 1600000000000000103)
+            .await
+            .unwrap();
+        assert_eq!(balance, 0);
+    }
+
+    #[tokio::test]
+    async fn test_random_ft_balance_sample_seeded() {
+        let db = init_seeded_db().await;
+
+        let sample = get_random_ft_balance_sample(&db.pool)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(sample.contract_id.as_str(), "token.near");
+        assert!(["alice.near", "bob.near"].contains(&sample.account_id.as_str()));
+        assert!(
+            (1600000000000000100..=1600000000000000102).contains(&sample.block_timestamp),
+            "{}",
+            sample.block_timestamp
+        );
+    }
+}
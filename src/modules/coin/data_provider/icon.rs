@@ -0,0 +1,344 @@
+use crate::modules::coin;
+use crate::{cache, errors, rpc_helpers};
+
+/// Bigger icons are not served, the wallets do not need more than 64 KB to draw 32x32 picture
+const MAX_ICON_SIZE: usize = 64 * 1024;
+const CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+const RASTER_CONTENT_TYPES: &[&str] = &["image/png", "image/jpeg", "image/gif", "image/webp"];
+const SVG_CONTENT_TYPE: &str = "image/svg+xml";
+// Everything that could run the code or load something from the outside
+const SVG_FORBIDDEN_PATTERNS: &[&str] = &[
+    "<script",
+    "<foreignobject",
+    "<iframe",
+    "<embed",
+    "<object",
+    "<!entity",
+    "javascript:",
+    "href=\"http",
+    "href='http",
+    "href=\"//",
+    "href='//",
+    "url(http",
+    "url(//",
+    "@import",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Icon {
+    /// Decoded data URI which passed all the checks
+    Inline {
+        content_type: String,
+        bytes: Vec<u8>,
+    },
+    /// https link from the metadata. We redirect to it instead of downloading, so the server
+    /// could not be used to reach the internal network
+    Link(String),
+}
+
+/// The icons of the latest FT metadata. `None` is cached as well: the contract has no icon,
+/// or the icon did not pass the checks
+pub(crate) type IconCache = cache::TtlCache<near_primitives::types::AccountId, Option<Icon>>;
+
+pub(crate) async fn get_ft_icon(
+    rpc_client: &dyn rpc_helpers::RpcApi,
+    cache: &IconCache,
+    contract_id: &near_primitives::types::AccountId,
+    block_height: u64,
+) -> crate::Result<Option<Icon>> {
+    if let Some(icon) = cache.get(contract_id, CACHE_TTL) {
+        return Ok(icon);
+    }
+    let metadata =
+        super::metadata::get_ft_contract_metadata(rpc_client, contract_id.clone(), block_height)
+            .await?;
+    Ok(update_cached_ft_icon(cache, contract_id, &metadata))
+}
+
+/// Replaces the cached icon with the one from the given metadata, e.g. after the forced refresh
+pub(crate) fn update_cached_ft_icon(
+    cache: &IconCache,
+    contract_id: &near_primitives::types::AccountId,
+    metadata: &coin::schemas::FtContractMetadata,
+) -> Option<Icon> {
+    let icon = match &metadata.icon {
+        Some(icon) => match sanitize_icon(icon) {
+            Ok(icon) => Some(icon),
+            Err(err) => {
+                tracing::debug!(
+                    target: crate::LOGGER_MSG,
+                    "Icon of {} is rejected: {:?}",
+                    contract_id,
+                    err
+                );
+                None
+            }
+        },
+        None => None,
+    };
+    cache.insert(contract_id.clone(), icon.clone());
+    icon
+}
+
+/// Path of `/icons/{contract_account_id}` endpoint, relative to the API root
+pub(crate) fn get_icon_url(contract_id: &near_primitives::types::AccountId) -> String {
+    format!("icons/{}", contract_id)
+}
+
+pub(crate) fn sanitize_icon(icon: &str) -> crate::Result<Icon> {
+    let icon = icon.trim();
+    if icon.starts_with("https://") {
+        if icon.len() > 2048 || icon.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return Err(errors::ErrorKind::ContractError("Malformed icon URL".to_string()).into());
+        }
+        return Ok(Icon::Link(icon.to_string()));
+    }
+
+    let data = icon.strip_prefix("data:").ok_or_else(|| {
+        errors::ErrorKind::ContractError("Icon should be data URI or https URL".to_string())
+    })?;
+    let (header, payload) = data
+        .split_once(',')
+        .ok_or_else(|| errors::ErrorKind::ContractError("Malformed data URI".to_string()))?;
+    let mut header_parts = header.split(';');
+    let content_type = header_parts
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    let is_base64 = header_parts.any(|part| part.trim().eq_ignore_ascii_case("base64"));
+
+    // The encoded payload is never smaller than the decoded one
+    if payload.len() > 2 * MAX_ICON_SIZE {
+        return Err(too_big_error());
+    }
+    let bytes = if is_base64 {
+        base64::decode(payload.trim()).map_err(|err| {
+            errors::ErrorKind::ContractError(format!("Icon is not valid base64: {}", err))
+        })?
+    } else {
+        percent_decode(payload)?
+    };
+    if bytes.len() > MAX_ICON_SIZE {
+        return Err(too_big_error());
+    }
+
+    if content_type == SVG_CONTENT_TYPE {
+        check_svg(&bytes)?;
+    } else if RASTER_CONTENT_TYPES.contains(&content_type.as_str()) {
+        if !has_expected_signature(&content_type, &bytes) {
+            return Err(errors::ErrorKind::ContractError(format!(
+                "Icon content does not match {}",
+                content_type
+            ))
+            .into());
+        }
+    } else {
+        return Err(errors::ErrorKind::ContractError(format!(
+            "Unsupported icon type {}",
+            content_type
+        ))
+        .into());
+    }
+
+    Ok(Icon::Inline {
+        content_type,
+        bytes,
+    })
+}
+
+fn too_big_error() -> errors::Error {
+    errors::ErrorKind::ContractError(format!("Icon is bigger than {} bytes", MAX_ICON_SIZE)).into()
+}
+
+fn check_svg(bytes: &[u8]) -> crate::Result<()> {
+    let svg = std::str::from_utf8(bytes)
+        .map_err(|_| errors::ErrorKind::ContractError("SVG icon is not UTF-8".to_string()))?
+        .to_lowercase();
+    if !svg.contains("<svg") {
+        return Err(errors::ErrorKind::ContractError("Icon is not SVG".to_string()).into());
+    }
+    if let Some(pattern) = SVG_FORBIDDEN_PATTERNS.iter().find(|p| svg.contains(*p)) {
+        return Err(errors::ErrorKind::ContractError(format!(
+            "SVG icon contains forbidden {}",
+            pattern
+        ))
+        .into());
+    }
+    if has_event_handler(&svg) {
+        return Err(errors::ErrorKind::ContractError(
+            "SVG icon contains event handlers".to_string(),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Looks for the attributes like `onload=`, `onclick =`
+fn has_event_handler(svg: &str) -> bool {
+    let bytes = svg.as_bytes();
+    svg.match_indices("on").any(|(i, _)| {
+        let preceded_by_space =
+            i > 0 && (bytes[i - 1].is_ascii_whitespace() || bytes[i - 1] == b'/');
+        if !preceded_by_space {
+            return false;
+        }
+        let rest = &svg[i + 2..];
+        let name_len = rest.bytes().take_while(|c| c.is_ascii_alphabetic()).count();
+        name_len > 0 && rest[name_len..].trim_start().starts_with('=')
+    })
+}
+
+fn has_expected_signature(content_type: &str, bytes: &[u8]) -> bool {
+    match content_type {
+        "image/png" => bytes.starts_with(b"\x89PNG\r\n\x1a\n"),
+        "image/jpeg" => bytes.starts_with(b"\xff\xd8\xff"),
+        "image/gif" => bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a"),
+        "image/webp" => bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP",
+        _ => false,
+    }
+}
+
+fn percent_decode(payload: &str) -> crate::Result<Vec<u8>> {
+    let bytes = payload.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes.get(i + 1..i + 3).ok_or_else(|| {
+                errors::ErrorKind::ContractError("Malformed percent-encoding".to_string())
+            })?;
+            let hex = std::str::from_utf8(hex).unwrap_or_default();
+            let byte = u8::from_str_radix(hex, 16).map_err(|_| {
+                errors::ErrorKind::ContractError("Malformed percent-encoding".to_string())
+            })?;
+            result.push(byte);
+            i += 3;
+        } else {
+            result.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Ok(result)
+}
+
+impl coin::schemas::CoinMetadata {
+    pub(crate) fn from_ft_metadata(
+        contract_id: &near_primitives::types::AccountId,
+        metadata: coin::schemas::FtContractMetadata,
+    ) -> Self {
+        Self {
+            name: metadata.name,
+            symbol: metadata.symbol,
+            icon_url: metadata.icon.map(|_| get_icon_url(contract_id)),
+            decimals: metadata.decimals,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_sanitize_raster_icon() {
+        let png = format!(
+            "data:image/png;base64,{}",
+            base64::encode(b"\x89PNG\r\n\x1a\n1234")
+        );
+        assert!(matches!(
+            sanitize_icon(&png),
+            Ok(Icon::Inline { content_type, .. }) if content_type == "image/png"
+        ));
+
+        let fake_png = format!("data:image/png;base64,{}", base64::encode(b"<svg></svg>"));
+        assert!(sanitize_icon(&fake_png).is_err());
+
+        let huge = format!(
+            "data:image/png;base64,{}",
+            base64::encode(vec![0u8; MAX_ICON_SIZE + 1])
+        );
+        assert!(sanitize_icon(&huge).is_err());
+        assert!(sanitize_icon("data:text/html,<b>hi</b>").is_err());
+        assert!(sanitize_icon("http://example.com/icon.png").is_err());
+        assert_eq!(
+            sanitize_icon("https://example.com/icon.png").unwrap(),
+            Icon::Link("https://example.com/icon.png".to_string())
+        );
+    }
+
+    #[test]
+    fn test_sanitize_svg_icon() {
+        let svg = "data:image/svg+xml,%3Csvg xmlns='http://www.w3.org/2000/svg'%3E%3Ccircle r='1'/%3E%3C/svg%3E";
+        assert_eq!(
+            sanitize_icon(svg).unwrap(),
+            Icon::Inline {
+                content_type: SVG_CONTENT_TYPE.to_string(),
+                bytes: b"<svg xmlns='http://www.w3.org/2000/svg'><circle r='1'/></svg>".to_vec(),
+            }
+        );
+
+        for hostile in [
+            "data:image/svg+xml,<svg><script>alert(1)</script></svg>",
+            "data:image/svg+xml,<svg onload=\"alert(1)\"></svg>",
+            "data:image/svg+xml,<svg><a xlink:href=\"javascript:alert(1)\"/></svg>",
+            "data:image/svg+xml,<svg><image href=\"https://tracker.example/x.png\"/></svg>",
+            "data:image/svg+xml,<b>not svg</b>",
+        ] {
+            assert!(sanitize_icon(hostile).is_err(), "{}", hostile);
+        }
+        // `on` inside the other words is fine
+        assert!(sanitize_icon("data:image/svg+xml,<svg><polygon points=\"0\"/></svg>").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_ft_icon_is_cached() {
+        let (server, rpc_client) = init_mock_rpc().await;
+        let contract = near_primitives::types::AccountId::from_str("token.near").unwrap();
+        mock_function_call(
+            &server,
+            "token.near",
+            "ft_metadata",
+            serde_json::json!({
+                "spec": "ft-1.0.0",
+                "name": "Token",
+                "symbol": "TKN",
+                "icon": "data:image/svg+xml,<svg><script/></svg>",
+                "reference": null,
+                "reference_hash": null,
+                "decimals": 18
+            }),
+        )
+        .await;
+        let cache = IconCache::default();
+
+        let icon = get_ft_icon(&rpc_client, &cache, &contract, 100)
+            .await
+            .unwrap();
+        assert_eq!(icon, None);
+        assert_eq!(cache.get(&contract, CACHE_TTL), Some(None));
+    }
+
+    #[test]
+    fn test_update_cached_ft_icon() {
+        let contract = near_primitives::types::AccountId::from_str("token.near").unwrap();
+        let cache = IconCache::default();
+        cache.insert(contract.clone(), None);
+        let metadata = coin::schemas::FtContractMetadata {
+            spec: "ft-1.0.0".to_string(),
+            name: "Token".to_string(),
+            symbol: "TKN".to_string(),
+            icon: Some("https://example.com/icon.png".to_string()),
+            reference: None,
+            reference_hash: None,
+            decimals: 18,
+        };
+
+        let icon = Some(Icon::Link("https://example.com/icon.png".to_string()));
+        assert_eq!(update_cached_ft_icon(&cache, &contract, &metadata), icon);
+        assert_eq!(cache.get(&contract, CACHE_TTL), Some(icon));
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{balances_schema, db_helpers, types};
+
+/// The timestamp of the last NEAR balance change of the account, not later than the given block.
+/// `None` if the account has no changes at all
+pub(crate) async fn get_near_last_modified(
+    balances_db: &balances_schema::BalancesDb,
+    account_id: &near_primitives::types::AccountId,
+    block_timestamp: u64,
+) -> crate::Result<Option<u64>> {
+    let query = format!(
+        r"
+        SELECT max(block_timestamp) block_timestamp
+        FROM {}
+        WHERE affected_account_id = $1 AND block_timestamp <= $2::numeric(20, 0)
+    ",
+        balances_db.balance_changes()
+    );
+    let last_modified = db_helpers::select_retry_or_panic::<super::models::LastModifiedInfo>(
+        &balances_db.pool,
+        &query,
+        &[account_id.to_string(), block_timestamp.to_string()],
+    )
+    .await?;
+    to_timestamp(last_modified)
+}
+
+/// The timestamp of the last FT event of the account, not later than the given block.
+/// Only the events of `contract_id` are taken if it's given.
+/// The failed events are also counted: they are shown in the history
+pub(crate) async fn get_ft_last_modified(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    account_id: &near_primitives::types::AccountId,
+    contract_id: Option<&near_primitives::types::AccountId>,
+    block_timestamp: u64,
+) -> crate::Result<Option<u64>> {
+    let query = r"
+        SELECT max(emitted_at_block_timestamp) block_timestamp
+        FROM assets__fungible_token_events
+        WHERE (token_old_owner_account_id = $1 OR token_new_owner_account_id = $1)
+            AND emitted_at_block_timestamp <= $2::numeric(20, 0)
+            AND ($3 = '' OR emitted_by_contract_account_id = $3)
+    ";
+    let last_modified = db_helpers::select_retry_or_panic::<super::models::LastModifiedInfo>(
+        pool,
+        query,
+        &[
+            account_id.to_string(),
+            block_timestamp.to_string(),
+            contract_id.map(|id| id.to_string()).unwrap_or_default(),
+        ],
+    )
+    .await?;
+    to_timestamp(last_modified)
+}
+
+fn to_timestamp(last_modified: Vec<super::models::LastModifiedInfo>) -> crate::Result<Option<u64>> {
+    match last_modified
+        .first()
+        .and_then(|info| info.block_timestamp.as_ref())
+    {
+        Some(timestamp) => Ok(Some(types::numeric::to_u64(timestamp)?)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_near_last_modified_seeded() {
+        let db = init_seeded_db().await;
+        let alice = near_primitives::types::AccountId::from_str("alice.near").unwrap();
+        let carol = near_primitives::types::AccountId::from_str("carol.near").unwrap();
+
+        let last_modified = get_near_last_modified(&db.balances_db(), &alice, 1600000000000000103)
+            .await
+            .unwrap();
+        assert_eq!(last_modified, Some(1600000000000000102));
+        let last_modified = get_near_last_modified(&db.balances_db(), &alice, 1600000000000000101)
+            .await
+            .unwrap();
+        assert_eq!(last_modified, Some(1600000000000000101));
+        let last_modified = get_near_last_modified(&db.balances_db(), &carol, 1600000000000000103)
+            .await
+            .unwrap();
+        assert_eq!(last_modified, None);
+    }
+
+    #[tokio::test]
+    async fn test_ft_last_modified_seeded() {
+        let db = init_seeded_db().await;
+        let alice = near_primitives::types::AccountId::from_str("alice.near").unwrap();
+        let token = near_primitives::types::AccountId::from_str("token.near").unwrap();
+        let other_token = near_primitives::types::AccountId::from_str("other.near").unwrap();
+
+        // The failed transfer at block 102 is counted
+        let last_modified = get_ft_last_modified(&db.pool, &alice, None, 1600000000000000103)
+            .await
+            .unwrap();
+        assert_eq!(last_modified, Some(1600000000000000102));
+        let last_modified =
+            get_ft_last_modified(&db.pool, &alice, Some(&token), 1600000000000000101)
+                .await
+                .unwrap();
+        assert_eq!(last_modified, Some(1600000000000000101));
+        let last_modified =
+            get_ft_last_modified(&db.pool, &alice, Some(&other_token), 1600000000000000103)
+                .await
+                .unwrap();
+        assert_eq!(last_modified, None);
+    }
+}
@@ -0,0 +1,77 @@
+use crate::modules::coin;
+use crate::{rpc_helpers, types};
+
+/// Liquid staking contracts and their view methods which give yoctoNEAR for 1 whole token.
+/// All of them have 24 decimals, as NEAR
+const LIQUID_STAKING_CONTRACTS: &[(&str, &str)] = &[
+    ("meta-pool.near", "get_st_near_price"),
+    ("linear-protocol.near", "ft_price"),
+];
+const ONE_TOKEN: u128 = 10u128.pow(24);
+
+/// `None` if the contract is not the known liquid staking contract
+pub(crate) async fn get_liquid_staking(
+    rpc_client: &dyn rpc_helpers::RpcApi,
+    contract_id: &near_primitives::types::AccountId,
+    balance: u128,
+    block_height: u64,
+) -> crate::Result<Option<coin::schemas::LiquidStaking>> {
+    let method_name = match LIQUID_STAKING_CONTRACTS
+        .iter()
+        .find(|(liquid_staking_id, _)| *liquid_staking_id == contract_id.as_str())
+    {
+        Some((_, method_name)) => method_name,
+        None => return Ok(None),
+    };
+    let request = rpc_helpers::get_function_call_request(
+        block_height,
+        contract_id.clone(),
+        method_name,
+        serde_json::json!({}),
+    );
+    let response =
+        rpc_helpers::wrapped_call(rpc_client, request, block_height, contract_id).await?;
+    let near_price = serde_json::from_slice::<types::U128>(&response.result)?;
+
+    Ok(Some(coin::schemas::LiquidStaking {
+        near_price,
+        near_equivalent_balance: types::numeric::mul_div(balance, near_price.0, ONE_TOKEN)?.into(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_liquid_staking_mock_rpc() {
+        let (server, rpc_client) = init_mock_rpc().await;
+        mock_function_call(
+            &server,
+            "meta-pool.near",
+            "get_st_near_price",
+            serde_json::json!("1200000000000000000000000"),
+        )
+        .await;
+        let contract = near_primitives::types::AccountId::from_str("meta-pool.near").unwrap();
+
+        let liquid_staking =
+            get_liquid_staking(&rpc_client, &contract, 5 * ONE_TOKEN, get_block().height)
+                .await
+                .unwrap()
+                .unwrap();
+        assert_eq!(liquid_staking.near_price.0, 12 * ONE_TOKEN / 10);
+        assert_eq!(liquid_staking.near_equivalent_balance.0, 6 * ONE_TOKEN);
+
+        // Nothing is mocked for usn, we don't go to RPC
+        let contract = near_primitives::types::AccountId::from_str("usn").unwrap();
+        assert!(
+            get_liquid_staking(&rpc_client, &contract, ONE_TOKEN, get_block().height)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
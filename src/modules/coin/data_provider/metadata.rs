@@ -1,11 +1,20 @@
 use borsh::{BorshDeserialize, BorshSerialize};
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 
 use crate::modules::coin;
-use crate::{rpc_helpers, types};
+use crate::{cache, rpc_helpers, types};
+
+const CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+/// The contracts of the bulk request are asked in parallel, but not all at once
+const MAX_CONCURRENT_METADATA_QUERIES: usize = 10;
+
+/// The latest metadata of FT contracts, for the bulk requests
+pub(crate) type FtMetadataCache =
+    cache::TtlCache<near_primitives::types::AccountId, coin::schemas::FtContractMetadata>;
 
 pub(crate) async fn get_ft_contract_metadata(
-    rpc_client: &near_jsonrpc_client::JsonRpcClient,
+    rpc_client: &dyn rpc_helpers::RpcApi,
     contract_id: near_primitives::types::AccountId,
     block_height: u64,
 ) -> crate::Result<coin::schemas::FtContractMetadata> {
@@ -30,12 +39,53 @@ pub(crate) async fn get_ft_contract_metadata(
     })
 }
 
+/// The latest metadata of the contracts, in the given order. The cached values are up to 1 hour old,
+/// the rest is asked at `block_height`. One broken contract does not fail the others
+pub(crate) async fn get_ft_contracts_metadata(
+    rpc_client: &dyn rpc_helpers::RpcApi,
+    cache: &FtMetadataCache,
+    contract_ids: &[near_primitives::types::AccountId],
+    block_height: u64,
+) -> (
+    Vec<coin::schemas::FtContractMetadataItem>,
+    Vec<types::FailedContract>,
+) {
+    let results: Vec<_> = futures::stream::iter(contract_ids)
+        .map(|contract_id| async move {
+            if let Some(metadata) = cache.get(contract_id, CACHE_TTL) {
+                return (contract_id, Ok(metadata));
+            }
+            let metadata =
+                get_ft_contract_metadata(rpc_client, contract_id.clone(), block_height).await;
+            if let Ok(metadata) = &metadata {
+                cache.insert(contract_id.clone(), metadata.clone());
+            }
+            (contract_id, metadata)
+        })
+        .buffered(MAX_CONCURRENT_METADATA_QUERIES)
+        .collect()
+        .await;
+
+    let mut items: Vec<coin::schemas::FtContractMetadataItem> = vec![];
+    let mut failed_contracts: Vec<types::FailedContract> = vec![];
+    for (contract_id, metadata) in results {
+        match metadata {
+            Ok(metadata) => items.push(coin::schemas::FtContractMetadataItem {
+                contract_account_id: contract_id.clone().into(),
+                metadata,
+            }),
+            Err(error) => failed_contracts.push(types::FailedContract::new(contract_id, error)),
+        }
+    }
+    (items, failed_contracts)
+}
+
 pub(crate) fn get_near_metadata() -> coin::schemas::CoinMetadata {
     coin::schemas::CoinMetadata {
         name: "NEAR blockchain native token".to_string(),
         symbol: "NEAR".to_string(),
         // TODO PHASE 2 re-check the icon. It's the best I can find
-        icon: Some("https://raw.githubusercontent.com/near/near-wallet/7ef3c824404282b76b36da2dff4f3e593e7f928d/packages/frontend/src/images/near.svg".to_string()),
+        icon_url: Some("https://raw.githubusercontent.com/near/near-wallet/7ef3c824404282b76b36da2dff4f3e593e7f928d/packages/frontend/src/images/near.svg".to_string()),
         decimals: 24,
     }
 }
@@ -52,17 +102,6 @@ pub struct FtMetadata {
     pub decimals: u8,
 }
 
-impl From<coin::schemas::FtContractMetadata> for coin::schemas::CoinMetadata {
-    fn from(metadata: coin::schemas::FtContractMetadata) -> Self {
-        coin::schemas::CoinMetadata {
-            name: metadata.name,
-            symbol: metadata.symbol,
-            icon: metadata.icon,
-            decimals: metadata.decimals,
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -79,6 +118,69 @@ mod tests {
         insta::assert_debug_snapshot!(metadata);
     }
 
+    #[tokio::test]
+    async fn test_ft_contract_metadata_mock_rpc() {
+        let (server, rpc_client) = init_mock_rpc().await;
+        mock_function_call(
+            &server,
+            "usn",
+            "ft_metadata",
+            serde_json::json!({
+                "spec": "ft-1.0.0",
+                "name": "USN",
+                "symbol": "USN",
+                "icon": null,
+                "reference": null,
+                "reference_hash": null,
+                "decimals": 18
+            }),
+        )
+        .await;
+        let block = get_block();
+        let contract = near_primitives::types::AccountId::from_str("usn").unwrap();
+
+        let metadata = get_ft_contract_metadata(&rpc_client, contract, block.height)
+            .await
+            .unwrap();
+        assert_eq!(metadata.symbol, "USN");
+        assert_eq!(metadata.decimals, 18);
+    }
+
+    #[tokio::test]
+    async fn test_ft_contracts_metadata_mock_rpc() {
+        let (server, rpc_client) = init_mock_rpc().await;
+        mock_function_call(
+            &server,
+            "usn",
+            "ft_metadata",
+            serde_json::json!({
+                "spec": "ft-1.0.0",
+                "name": "USN",
+                "symbol": "USN",
+                "icon": null,
+                "reference": null,
+                "reference_hash": null,
+                "decimals": 18
+            }),
+        )
+        .await;
+        let block = get_block();
+        let cache = FtMetadataCache::default();
+        let broken = near_primitives::types::AccountId::from_str("broken.near").unwrap();
+        let contracts = vec![
+            broken.clone(),
+            near_primitives::types::AccountId::from_str("usn").unwrap(),
+        ];
+
+        let (items, failed_contracts) =
+            get_ft_contracts_metadata(&rpc_client, &cache, &contracts, block.height).await;
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].metadata.symbol, "USN");
+        assert_eq!(failed_contracts.len(), 1);
+        assert_eq!(failed_contracts[0].contract_account_id, broken.into());
+        assert!(cache.get(&contracts[1], CACHE_TTL).is_some());
+    }
+
     #[tokio::test]
     async fn test_ft_contract_metadata_no_contract_deployed() {
         let rpc_client = init_rpc();
@@ -1,8 +1,40 @@
 mod balance;
+mod diff;
+#[cfg(feature = "history")]
+mod fees;
+#[cfg(feature = "history")]
 mod history;
+mod holders;
+mod icon;
+mod last_modified;
+mod liquid_staking;
 mod metadata;
 mod models;
+mod price;
+mod storage_price;
+#[cfg(feature = "history")]
+mod transfers;
 
-pub(crate) use balance::{get_coin_balances, get_coin_balances_by_contract, get_near_balance};
+pub(crate) use balance::{
+    get_coin_balances, get_coin_balances_by_contract, get_ft_balance_by_contract, get_near_balance,
+    get_near_balance_breakdown,
+};
+pub(crate) use diff::get_changed_ft_contracts;
+pub use diff::ChangedFtContract;
+#[cfg(feature = "history")]
+pub(crate) use fees::get_gas_fees;
+#[cfg(feature = "history")]
 pub(crate) use history::{get_coin_history, get_near_history};
-pub(crate) use metadata::{get_ft_contract_metadata, get_near_metadata};
+pub(crate) use holders::{
+    get_ft_balance_from_events, get_ft_holders, get_random_ft_balance_sample, FtBalanceSample,
+};
+pub use icon::Icon;
+pub(crate) use icon::{get_ft_icon, update_cached_ft_icon, IconCache};
+pub(crate) use last_modified::{get_ft_last_modified, get_near_last_modified};
+pub(crate) use metadata::{
+    get_ft_contract_metadata, get_ft_contracts_metadata, get_near_metadata, FtMetadataCache,
+};
+pub(crate) use price::get_price_history;
+pub(crate) use storage_price::{get_storage_amount_per_byte, StoragePriceCache};
+#[cfg(feature = "history")]
+pub(crate) use transfers::get_ft_transfers;
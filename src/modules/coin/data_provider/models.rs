@@ -5,18 +5,30 @@ pub(crate) struct AccountChangesBalance {
     pub balance: BigDecimal,
 }
 
+#[derive(sqlx::FromRow)]
+pub(crate) struct AccountChangesState {
+    pub nonstaked_balance: BigDecimal,
+    pub staked_balance: BigDecimal,
+    pub storage_usage: BigDecimal,
+}
+
+#[cfg(feature = "history")]
 #[derive(sqlx::FromRow)]
 pub(crate) struct NearHistoryInfo {
+    pub receipt_id: Option<String>,
+    pub transaction_hash: Option<String>,
     pub involved_account_id: Option<String>,
     pub delta_balance: BigDecimal,
     pub balance: BigDecimal,
     pub cause: String,
     pub status: String,
-    // pub index: super::types::U128,
     pub block_timestamp_nanos: BigDecimal,
+    pub shard_id: BigDecimal,
+    pub index_in_chunk: BigDecimal,
     // pub block_height: super::types::U64,
 }
 
+#[cfg(feature = "history")]
 #[derive(sqlx::FromRow)]
 pub(crate) struct CoinHistoryInfo {
     // TODO PHASE 2 add symbol
@@ -27,4 +39,74 @@ pub(crate) struct CoinHistoryInfo {
     pub status: String,
     pub old_owner_id: String,
     pub new_owner_id: String,
+    pub signer_account_id: Option<String>,
+    pub predecessor_account_id: Option<String>,
+    pub shard_id: BigDecimal,
+    pub index_in_chunk: BigDecimal,
+}
+
+/// The receipt or the transaction which caused the balance change
+#[cfg(feature = "history")]
+#[derive(sqlx::FromRow)]
+pub(crate) struct OriginatorInfo {
+    pub id: String,
+    pub signer_account_id: String,
+    pub predecessor_account_id: String,
+}
+
+#[cfg(feature = "history")]
+#[derive(sqlx::FromRow)]
+pub(crate) struct FtTransferInfo {
+    pub receipt_id: String,
+    pub amount: BigDecimal,
+    pub cause: String,
+    pub status: String,
+    pub old_owner_id: String,
+    pub new_owner_id: String,
+    pub block_timestamp: BigDecimal,
+    pub block_height: BigDecimal,
+    pub block_hash: String,
+    pub shard_id: BigDecimal,
+    pub index_in_chunk: BigDecimal,
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct FtHolderInfo {
+    pub account_id: String,
+    pub balance: BigDecimal,
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct FtBalanceSampleInfo {
+    pub contract_account_id: String,
+    pub account_id: String,
+    pub block_timestamp: BigDecimal,
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct PriceBucketInfo {
+    pub bucket_start: BigDecimal,
+    pub open: BigDecimal,
+    pub high: BigDecimal,
+    pub low: BigDecimal,
+    pub close: BigDecimal,
+}
+
+#[cfg(feature = "history")]
+#[derive(sqlx::FromRow)]
+pub(crate) struct GasFeesBucketInfo {
+    pub bucket_timestamp: BigDecimal,
+    pub burnt: BigDecimal,
+    pub payments_count: i64,
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct ChangedFtContractInfo {
+    pub account_id: String,
+    pub held_before: bool,
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct LastModifiedInfo {
+    pub block_timestamp: Option<BigDecimal>,
 }
@@ -0,0 +1,108 @@
+use crate::modules::coin;
+use crate::{db_helpers, types};
+
+pub(crate) async fn get_price_history(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    contract_id: &near_primitives::types::AccountId,
+    bucket: &coin::schemas::PriceBucket,
+    block_timestamp: u64,
+    limit: u32,
+) -> crate::Result<Vec<coin::schemas::PricePoint>> {
+    let query = r"
+        SELECT
+            bucket_start,
+            (array_agg(price_usd ORDER BY sampled_at_timestamp))[1] open,
+            max(price_usd) high,
+            min(price_usd) low,
+            (array_agg(price_usd ORDER BY sampled_at_timestamp DESC))[1] close
+        FROM (
+            SELECT
+                floor(sampled_at_timestamp / $2::numeric(20, 0)) * $2::numeric(20, 0) bucket_start,
+                price_usd,
+                sampled_at_timestamp
+            FROM token_prices
+            WHERE contract_account_id = $1 AND sampled_at_timestamp <= $3::numeric(20, 0)
+        ) samples
+        GROUP BY bucket_start
+        ORDER BY bucket_start DESC
+        LIMIT $4::numeric(20, 0)
+    ";
+    let buckets = db_helpers::select_retry_or_panic::<super::models::PriceBucketInfo>(
+        pool,
+        query,
+        &[
+            contract_id.to_string(),
+            bucket.duration_nanos().to_string(),
+            block_timestamp.to_string(),
+            limit.to_string(),
+        ],
+    )
+    .await?;
+
+    let mut result: Vec<coin::schemas::PricePoint> = vec![];
+    for bucket in buckets {
+        result.push(coin::schemas::PricePoint {
+            timestamp_nanos: types::numeric::to_u64(&bucket.bucket_start)?.into(),
+            open: bucket.open.to_string(),
+            high: bucket.high.to_string(),
+            low: bucket.low.to_string(),
+            close: bucket.close.to_string(),
+        });
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_price_history_seeded() {
+        let db = init_seeded_db().await;
+        let contract = near_primitives::types::AccountId::from_str("token.near").unwrap();
+
+        let prices = get_price_history(
+            &db.pool,
+            &contract,
+            &coin::schemas::PriceBucket::Hour,
+            u64::MAX,
+            10,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            prices,
+            vec![
+                coin::schemas::PricePoint {
+                    timestamp_nanos: 1600002000000000000.into(),
+                    open: "1.30".to_string(),
+                    high: "1.30".to_string(),
+                    low: "1.30".to_string(),
+                    close: "1.30".to_string(),
+                },
+                coin::schemas::PricePoint {
+                    timestamp_nanos: 1599998400000000000.into(),
+                    open: "1.10".to_string(),
+                    high: "1.25".to_string(),
+                    low: "1.05".to_string(),
+                    close: "1.20".to_string(),
+                },
+            ]
+        );
+
+        let prices = get_price_history(
+            &db.pool,
+            &contract,
+            &coin::schemas::PriceBucket::Week,
+            u64::MAX,
+            10,
+        )
+        .await
+        .unwrap();
+        assert_eq!(prices.len(), 1);
+        assert_eq!(prices[0].open, "1.10");
+        assert_eq!(prices[0].close, "1.30");
+    }
+}
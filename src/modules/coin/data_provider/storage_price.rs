@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+
+use crate::rpc_helpers;
+
+// Mainnet has about 2 epochs per day, it's years of the history
+const MAX_CACHED_EPOCHS: usize = 10_000;
+
+/// The storage price of the known epochs by their start height.
+/// The protocol config does not change inside the epoch, so the entries never expire
+#[derive(Default)]
+pub(crate) struct StoragePriceCache {
+    epochs: std::sync::Mutex<BTreeMap<u64, EpochStoragePrice>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct EpochStoragePrice {
+    /// The epoch has at least that many heights, the later ones are asked again
+    epoch_length: u64,
+    storage_amount_per_byte: u128,
+}
+
+impl StoragePriceCache {
+    fn get(&self, block_height: u64) -> Option<u128> {
+        let epochs = self.epochs.lock().unwrap_or_else(|err| err.into_inner());
+        epochs
+            .range(..=block_height)
+            .next_back()
+            .filter(|(start_height, epoch)| {
+                block_height < start_height.saturating_add(epoch.epoch_length)
+            })
+            .map(|(_, epoch)| epoch.storage_amount_per_byte)
+    }
+
+    fn insert(&self, epoch_start_height: u64, epoch: EpochStoragePrice) {
+        let mut epochs = self.epochs.lock().unwrap_or_else(|err| err.into_inner());
+        if epochs.len() >= MAX_CACHED_EPOCHS {
+            epochs.clear();
+        }
+        epochs.insert(epoch_start_height, epoch);
+    }
+}
+
+/// yoctoNEAR per byte of the storage in the epoch of the given block.
+/// The price was changed by the protocol upgrades, today's value is wrong for the old blocks
+pub(crate) async fn get_storage_amount_per_byte(
+    rpc_client: &dyn rpc_helpers::RpcApi,
+    cache: &StoragePriceCache,
+    block_height: u64,
+) -> crate::Result<u128> {
+    if let Some(storage_amount_per_byte) = cache.get(block_height) {
+        return Ok(storage_amount_per_byte);
+    }
+    let epoch_info = rpc_helpers::get_epoch_info(rpc_client, block_height).await?;
+    let protocol_config = rpc_helpers::get_protocol_config(rpc_client, block_height).await?;
+    let epoch = EpochStoragePrice {
+        epoch_length: protocol_config.epoch_length,
+        storage_amount_per_byte: protocol_config.runtime_config.storage_amount_per_byte,
+    };
+    cache.insert(epoch_info.epoch_start_height, epoch);
+    Ok(epoch.storage_amount_per_byte)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storage_price_cache() {
+        let cache = StoragePriceCache::default();
+        cache.insert(
+            1000,
+            EpochStoragePrice {
+                epoch_length: 100,
+                storage_amount_per_byte: 10u128.pow(20),
+            },
+        );
+        cache.insert(
+            1100,
+            EpochStoragePrice {
+                epoch_length: 100,
+                storage_amount_per_byte: 10u128.pow(19),
+            },
+        );
+
+        assert_eq!(cache.get(999), None);
+        assert_eq!(cache.get(1000), Some(10u128.pow(20)));
+        assert_eq!(cache.get(1099), Some(10u128.pow(20)));
+        assert_eq!(cache.get(1150), Some(10u128.pow(19)));
+        // The next epoch is not known yet
+        assert_eq!(cache.get(1200), None);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::modules::coin;
+use crate::{db_helpers, errors, types};
+
+pub(crate) async fn get_ft_transfers(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    contract_id: &near_primitives::types::AccountId,
+    pagination: &types::query_params::HistoryPagination,
+) -> crate::Result<Vec<(coin::schemas::FtTransfer, types::query_params::EventIndex)>> {
+    let query = r"
+        SELECT
+            assets__fungible_token_events.emitted_for_receipt_id receipt_id,
+            assets__fungible_token_events.amount::numeric(45, 0),
+            assets__fungible_token_events.event_kind::text cause,
+            CASE WHEN execution_outcomes.status IN ('SUCCESS_VALUE', 'SUCCESS_RECEIPT_ID') THEN 'SUCCESS'
+                ELSE 'FAILURE'
+            END status,
+            assets__fungible_token_events.token_old_owner_account_id old_owner_id,
+            assets__fungible_token_events.token_new_owner_account_id new_owner_id,
+            blocks.block_timestamp,
+            blocks.block_height,
+            blocks.block_hash,
+            assets__fungible_token_events.emitted_in_shard_id shard_id,
+            assets__fungible_token_events.emitted_index_of_event_entry_in_shard::numeric(20, 0) index_in_chunk
+        FROM assets__fungible_token_events
+            JOIN blocks ON assets__fungible_token_events.emitted_at_block_timestamp = blocks.block_timestamp
+            JOIN execution_outcomes ON assets__fungible_token_events.emitted_for_receipt_id = execution_outcomes.receipt_id
+        WHERE emitted_by_contract_account_id = $1
+            AND emitted_at_block_timestamp < $2::numeric(20, 0)
+            AND (emitted_at_block_timestamp, emitted_in_shard_id, emitted_index_of_event_entry_in_shard)
+                < ($3::numeric(20, 0), $4::numeric(20, 0), $5::numeric(20, 0))
+            AND emitted_at_block_timestamp <= $3::numeric(20, 0)
+        ORDER BY emitted_at_block_timestamp DESC, emitted_in_shard_id DESC, emitted_index_of_event_entry_in_shard DESC
+        LIMIT $6::numeric(20, 0)
+    ";
+    let bound = pagination.event_bound();
+    let transfers = db_helpers::select_retry_or_panic::<super::models::FtTransferInfo>(
+        pool,
+        query,
+        &[
+            contract_id.to_string(),
+            pagination.block_timestamp.to_string(),
+            bound.block_timestamp.to_string(),
+            bound.shard_id.to_string(),
+            bound.index_in_chunk.to_string(),
+            pagination.limit.to_string(),
+        ],
+    )
+    .await?;
+
+    let mut result = vec![];
+    for transfer in transfers {
+        let event_index = types::query_params::EventIndex::from_db(
+            &transfer.block_timestamp,
+            &transfer.shard_id,
+            &transfer.index_in_chunk,
+        )?;
+        result.push((transfer.try_into()?, event_index));
+    }
+    Ok(result)
+}
+
+impl TryFrom<super::models::FtTransferInfo> for coin::schemas::FtTransfer {
+    type Error = errors::Error;
+
+    fn try_from(info: super::models::FtTransferInfo) -> crate::Result<Self> {
+        Ok(Self {
+            cause: info.cause.into(),
+            old_account_id: types::account_id::extract_account_id(&info.old_owner_id)?
+                .map(|id| id.into()),
+            new_account_id: types::account_id::extract_account_id(&info.new_owner_id)?
+                .map(|id| id.into()),
+            amount: types::numeric::to_u128(&info.amount)?.into(),
+            status: info.status,
+            receipt_id: info.receipt_id,
+            block_timestamp_nanos: types::numeric::to_u64(&info.block_timestamp)?.into(),
+            block_height: types::numeric::to_u64(&info.block_height)?.into(),
+            block_hash: info.block_hash,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_ft_transfers_seeded() {
+        let db = init_seeded_db().await;
+        let contract = near_primitives::types::AccountId::from_str("token.near").unwrap();
+        let pagination = types::query_params::HistoryPagination {
+            block_height: 104,
+            block_timestamp: 1600000000000000104,
+            before_event: None,
+            limit: 10,
+        };
+
+        let transfers = get_ft_transfers(&db.pool, &contract, &pagination)
+            .await
+            .map(crate::modules::without_event_indexes)
+            .unwrap();
+        let events: Vec<(&str, u128, u64)> = transfers
+            .iter()
+            .map(|item| (item.status.as_str(), item.amount.0, item.block_height.0))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                ("FAILURE", 50, 102),
+                ("SUCCESS", 300, 101),
+                ("SUCCESS", 1000, 100)
+            ]
+        );
+        assert_eq!(transfers[2].cause, coin::schemas::HistoryCause::Mint);
+        assert_eq!(transfers[2].block_hash, get_seeded_block_hash(100));
+        assert_eq!(transfers[2].old_account_id, None);
+        assert_eq!(
+            transfers[1].new_account_id.as_ref().unwrap().to_string(),
+            "bob.near"
+        );
+
+        // The given timestamp is not included
+        let pagination = types::query_params::HistoryPagination {
+            block_height: 101,
+            block_timestamp: 1600000000000000101,
+            before_event: None,
+            limit: 10,
+        };
+        let transfers = get_ft_transfers(&db.pool, &contract, &pagination)
+            .await
+            .unwrap();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].0.receipt_id, "receipt_ft_mint");
+    }
+}
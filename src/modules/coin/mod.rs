@@ -2,9 +2,14 @@ use actix_web::{HttpResponse, error};
 use actix_web_validator::{Error, PathConfig};
 use paperclip::actix::web;
 
-mod data_provider;
+use crate::{allow_list, data_source, db_helpers, modules, types};
+
+pub(crate) mod data_provider;
 mod resources;
-mod schemas;
+pub(crate) mod schemas;
+
+/// We sample the price of wrapped NEAR, it's the same as NEAR price
+pub(crate) const NEAR_PRICE_CONTRACT_ID: &str = "wrap.near";
 
 #[derive(serde::Serialize)]
 pub struct ValidationErrorJsonPayload {
@@ -22,7 +27,10 @@ impl From<&validator::ValidationErrors> for ValidationErrorJsonPayload {
     }
 }
 
-pub(crate) fn register_services(app: &mut web::ServiceConfig) {
+pub(crate) fn register_services(
+    app: &mut web::ServiceConfig,
+    allow_list: &allow_list::ContractAllowList,
+) {
     app
         .app_data(PathConfig::default().error_handler(|err, _| {
             let json_error = match &err {
@@ -35,24 +43,207 @@ pub(crate) fn register_services(app: &mut web::ServiceConfig) {
         web::resource("/accounts/{account_id}/coins/NEAR")
             .route(web::get().to(resources::get_near_balance)),
     )
+    .service(
+        web::resource("/accounts/{account_id}/coins/NEAR/breakdown")
+            .route(web::get().to(resources::get_near_balance_breakdown)),
+    )
     .service(
         web::resource("/accounts/{account_id}/coins")
             .route(web::get().to(resources::get_coin_balances)),
     )
+    // Should go before `{contract_account_id}`, `diff` is the valid account_id
+    .service(
+        web::resource("/accounts/{account_id}/coins/diff")
+            .route(web::get().to(resources::get_balances_diff)),
+    )
     .service(
         web::resource("/accounts/{account_id}/coins/{contract_account_id}")
-            .route(web::get().to(resources::get_coin_balances_by_contract)),
+            .route(web::get().to(resources::get_coin_balances_by_contract))
+            .wrap(allow_list.clone()),
+    )
+    .service(
+        web::resource("/accounts/{account_id}/coins/{contract_account_id}/reconcile")
+            .route(web::get().to(resources::reconcile_coin_balance))
+            .wrap(allow_list.clone()),
+    )
+    .service(
+        web::resource("/accounts/{account_id}/coins/NEAR/at-blocks")
+            .route(web::get().to(resources::get_near_balances_at_blocks)),
+    )
+    .service(
+        web::resource("/accounts/{account_id}/coins/{contract_account_id}/at-blocks")
+            .route(web::get().to(resources::get_coin_balances_at_blocks))
+            .wrap(allow_list.clone()),
+    )
+    .service(
+        web::resource("/nep141/metadata/{contract_account_id}")
+            .route(web::get().to(resources::get_ft_contract_metadata))
+            .wrap(allow_list.clone()),
+    )
+    .service(
+        web::resource("/nep141/metadata")
+            .route(web::post().to(resources::get_ft_contracts_metadata)),
+    )
+    .service(
+        web::resource("/nep141/metadata/{contract_account_id}/refresh")
+            .route(web::post().to(resources::refresh_ft_contract_metadata))
+            .wrap(allow_list.clone()),
+    )
+    .service(
+        web::resource("/nep141/{contract_account_id}/price/history")
+            .route(web::get().to(resources::get_price_history))
+            .wrap(allow_list.clone()),
+    )
+    .service(
+        web::resource("/nep141/{contract_account_id}/holders")
+            .route(web::get().to(resources::get_ft_holders))
+            .wrap(allow_list.clone()),
+    )
+    .service(
+        web::resource("/nep141/{contract_account_id}/holders/export")
+            .route(web::get().to(resources::export_ft_holders))
+            .wrap(allow_list.clone()),
     )
     .service(
+        web::resource("/icons/{contract_account_id}")
+            .route(web::get().to(resources::get_ft_icon))
+            .wrap(allow_list.clone()),
+    )
+    .service(
+        web::resource("/portfolio")
+            .route(web::post().to(resources::get_portfolio)),
+    );
+
+    #[cfg(feature = "history")]
+    app.service(
         web::resource("/accounts/{account_id}/coins/NEAR/history")
             .route(web::get().to(resources::get_near_history)),
     )
     .service(
         web::resource("/accounts/{account_id}/coins/{contract_account_id}/history")
-            .route(web::get().to(resources::get_coin_history)),
+            .route(web::get().to(resources::get_coin_history))
+            .wrap(allow_list.clone()),
     )
     .service(
-        web::resource("/nep141/metadata/{contract_account_id}")
-            .route(web::get().to(resources::get_ft_contract_metadata)),
+        web::resource("/accounts/{account_id}/fees")
+            .route(web::get().to(resources::get_gas_fees)),
+    )
+    .service(
+        web::resource("/nep141/{contract_account_id}/transfers")
+            .route(web::get().to(resources::get_ft_transfers))
+            .wrap(allow_list.clone()),
     );
 }
+
+/// The balances of the accounts at the block valued in USD, see `/portfolio`.
+/// The accounts should be already deduplicated
+pub(crate) async fn get_portfolio(
+    data_source: &data_source::DataSourceRef,
+    allow_list: &allow_list::ContractAllowList,
+    account_ids: Vec<types::AccountId>,
+    block: &db_helpers::Block,
+) -> crate::Result<schemas::PortfolioResponse> {
+    let pagination = types::query_params::Pagination {
+        limit: types::query_params::PageLimits::default().max_limit,
+    };
+
+    let mut balances: Vec<(types::AccountId, Vec<schemas::Coin>)> = vec![];
+    let mut failed_contracts: Vec<types::FailedContract> = vec![];
+    for account_id in account_ids {
+        modules::check_account_exists(data_source, &account_id.0, block.timestamp).await?;
+        let mut coins: Vec<schemas::Coin> = vec![data_source.get_near_balance(block, &account_id.0).await?.into()];
+        let (mut ft_balances, mut ft_failed_contracts) =
+            data_source.get_coin_balances(block, &account_id.0, &pagination).await?;
+        allow_list.retain(&mut ft_balances, |coin| coin.contract_account_id.as_ref().map(|id| &id.0));
+        allow_list.retain(&mut ft_failed_contracts, |failed| Some(&failed.contract_account_id.0));
+        coins.append(&mut ft_balances);
+        for failed in ft_failed_contracts {
+            if !failed_contracts.iter().any(|known| known.contract_account_id == failed.contract_account_id) {
+                failed_contracts.push(failed);
+            }
+        }
+        balances.push((account_id, coins));
+    }
+
+    let mut prices: std::collections::HashMap<Option<types::AccountId>, Option<crate::BigDecimal>> =
+        std::collections::HashMap::new();
+    for (_, coins) in &balances {
+        for coin in coins {
+            if prices.contains_key(&coin.contract_account_id) {
+                continue;
+            }
+            let price_contract_id = match &coin.contract_account_id {
+                Some(contract_account_id) => contract_account_id.0.clone(),
+                None => NEAR_PRICE_CONTRACT_ID.parse().expect("wrap.near is a valid account id"),
+            };
+            let price = data_source
+                .get_token_price_history(&price_contract_id, &schemas::PriceBucket::Hour, block.timestamp, 1)
+                .await?
+                .into_iter()
+                .next()
+                .and_then(|point| point.close.parse::<crate::BigDecimal>().ok());
+            prices.insert(coin.contract_account_id.clone(), price);
+        }
+    }
+    let value_coins = |coins: Vec<schemas::Coin>| -> crate::Result<Vec<schemas::PortfolioCoin>> {
+        coins
+            .into_iter()
+            .map(|coin| {
+                let price = prices.get(&coin.contract_account_id).cloned().flatten();
+                schemas::PortfolioCoin::new(coin, price.as_ref())
+            })
+            .collect()
+    };
+
+    let coins = value_coins(schemas::merge_coins(
+        balances.iter().flat_map(|(_, coins)| coins.iter().cloned()),
+    ))?;
+    let mut accounts: Vec<schemas::AccountPortfolio> = vec![];
+    for (account_id, account_coins) in balances {
+        let coins = value_coins(account_coins)?;
+        accounts.push(schemas::AccountPortfolio {
+            account_id,
+            value_usd: schemas::total_value_usd(&coins)?,
+            coins,
+        });
+    }
+
+    Ok(schemas::PortfolioResponse {
+        total_value_usd: schemas::total_value_usd(&coins)?,
+        coins,
+        accounts,
+        meta: types::ResponseMeta::new(&block),
+        failed_contracts,
+    })
+}
+
+/// Replaces the metadata of FT balances with the current one, see `MetadataMode::Latest`.
+/// The contracts which fail to give it keep the metadata at the requested block
+pub(crate) async fn use_latest_metadata(
+    data_source: &data_source::DataSourceRef,
+    balances: &mut [schemas::Coin],
+) -> crate::Result<()> {
+    let contract_ids: Vec<near_primitives::types::AccountId> = balances
+        .iter()
+        .filter_map(|coin| coin.contract_account_id.as_ref().map(|id| id.0.clone()))
+        .collect();
+    if contract_ids.is_empty() {
+        return Ok(());
+    }
+    let last_block = data_source.get_last_block().await?;
+    let (items, _) = data_source
+        .get_ft_contracts_metadata(&contract_ids, last_block.height)
+        .await?;
+    for item in items {
+        for coin in balances
+            .iter_mut()
+            .filter(|coin| coin.contract_account_id.as_ref() == Some(&item.contract_account_id))
+        {
+            coin.metadata = schemas::CoinMetadata::from_ft_metadata(
+                &item.contract_account_id.0,
+                item.metadata.clone(),
+            );
+        }
+    }
+    Ok(())
+}
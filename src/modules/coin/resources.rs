@@ -1,3 +1,4 @@
+use futures::{StreamExt, TryStreamExt};
 use paperclip::actix::{
     api_v2_operation,
     web::{self, Json},
@@ -5,25 +6,68 @@ use paperclip::actix::{
 use validator::{HasLen};
 
 use super::{data_provider, schemas};
-use crate::{db_helpers, errors, modules, types};
+use crate::{allow_list, data_source, errors, last_modified, modules, tabular, types};
 use actix_web_validator::{Path as ValidatedPath};
 
+/// The holders are loaded from DB by the pages of this size while the export is streamed
+const EXPORT_PAGE_SIZE: u32 = 10_000;
+/// Parquet file is built in memory before it's sent
+const MAX_PARQUET_EXPORT_ROWS: usize = 1_000_000;
+/// The balances at the different blocks (or of the different contracts) are queried in parallel, but not all at once
+const MAX_CONCURRENT_BALANCE_QUERIES: usize = 10;
+/// The limit of FT contracts in the balances diff
+const MAX_DIFF_CONTRACTS: u32 = 100;
+
 #[api_v2_operation(tags(Coins))]
 /// Get user's NEAR balance
 ///
 /// This endpoint returns the NEAR balance of the given account_id
 /// for the given timestamp/block_height.
+/// `Last-Modified` is the time of the last balance change, use `If-Modified-Since` to poll the updates.
 pub async fn get_near_balance(
-    pool: web::Data<sqlx::Pool<sqlx::Postgres>>,
+    _query: types::query_params::ValidatedQuery,
+    http_request: actix_web::HttpRequest,
+    data_source: web::Data<data_source::DataSourceRef>,
     request: ValidatedPath<schemas::BalanceRequest>,
     block_params: web::Query<types::query_params::BlockParams>,
 ) -> crate::Result<Json<schemas::NearBalanceResponse>> {
-    types::query_params::check_block_params(&block_params)?;
-    let block = db_helpers::get_block_from_params(&pool, &block_params).await?;
-    modules::check_account_exists(&pool, &request.account_id.0, block.timestamp).await?;
+    let block = data_source.get_block_from_params(&block_params).await?;
+    last_modified::check_if_modified_since(
+        &http_request,
+        data_source.get_near_last_modified(&request.account_id.0, &block).await?,
+    )?;
+    modules::check_account_exists(&data_source, &request.account_id.0, block.timestamp).await?;
 
     Ok(Json(
-        data_provider::get_near_balance(&pool, &block, &request.account_id.0).await?,
+        data_source
+            .get_near_balance(&block, &request.account_id.0)
+            .await?,
+    ))
+}
+
+#[api_v2_operation(tags(Coins))]
+/// Get user's NEAR balance breakdown
+///
+/// This endpoint returns the NEAR balance of the given account_id for the given timestamp/block_height,
+/// split into the staked balance, the balance locked by the storage and the available one.
+///
+/// **Limitations**
+/// * The storage is priced by the protocol config of the block's epoch, it was changed by the protocol upgrades.
+///   The first request for each epoch asks RPC for it, usually the archival one.
+/// * The NEAR delegated to the staking pools is not in `staked_balance`, see `/accounts/{account_id}/staking/summary`.
+pub async fn get_near_balance_breakdown(
+    _query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: ValidatedPath<schemas::BalanceRequest>,
+    block_params: web::Query<types::query_params::BlockParams>,
+) -> crate::Result<Json<schemas::NearBalanceBreakdownResponse>> {
+    let block = data_source.get_block_from_params(&block_params).await?;
+    modules::check_account_exists(&data_source, &request.account_id.0, block.timestamp).await?;
+
+    Ok(Json(
+        data_source
+            .get_near_balance_breakdown(&block, &request.account_id.0)
+            .await?,
     ))
 }
 
@@ -32,51 +76,70 @@ pub async fn get_near_balance(
 ///
 /// This endpoint returns all the countable coin balances (including NEAR, FTs, later will add MTs)
 /// of the given account_id, for the given timestamp/block_height.
+/// Liquid staking tokens (stNEAR, LiNEAR) also have their value in NEAR by the current exchange rate.
+/// `Last-Modified` is the time of the last balance change, use `If-Modified-Since` to poll the updates.
 ///
 /// **Limitations**
 /// * For now, we support only the balance for NEAR and FT contracts which implement Events NEP.
 ///   We work on the solution to support the other FT contracts, including `wrap.near` and bridged tokens.
 /// * We are in the process of supporting Multi Token balances.
-/// * We provide only up to 100 items, where recently updated data goes first.
+/// * We provide only up to `limit` items, where recently updated data goes first.
 ///   Full-featured pagination will be provided later.
+/// * If the RPC call to some FT contract fails, its balance is skipped, the contract is listed in `failed_contracts`.
+///   Pass `skip_errors=true` to omit such contracts completely.
+/// * The metadata is taken at the same block as the balances.
+///   Pass `metadata=latest` to get the current names and icons instead, it could be up to 1 hour old.
 pub async fn get_coin_balances(
-    pool: web::Data<sqlx::Pool<sqlx::Postgres>>,
-    rpc_client: web::Data<near_jsonrpc_client::JsonRpcClient>,
+    query: types::query_params::ValidatedQuery,
+    http_request: actix_web::HttpRequest,
+    data_source: web::Data<data_source::DataSourceRef>,
+    allow_list: web::Data<allow_list::ContractAllowList>,
     request: ValidatedPath<schemas::BalanceRequest>,
     block_params: web::Query<types::query_params::BlockParams>,
     // TODO PHASE 2 pagination by index (recently updated go first)
-    pagination_params: web::Query<types::query_params::PaginationParams>,
+    mut pagination_params: web::Query<types::query_params::PaginationParams>,
+    skip_errors_params: web::Query<types::query_params::SkipErrorsParams>,
+    metadata_params: web::Query<types::query_params::MetadataParams>,
 ) -> crate::Result<Json<schemas::CoinBalancesResponse>> {
-    types::query_params::check_limit(pagination_params.limit)?;
+    pagination_params.limit = Some(query.limit);
     let mut pagination = types::query_params::Pagination::from(pagination_params.0);
-    let block = db_helpers::get_block_from_params(&pool, &block_params).await?;
-    modules::check_account_exists(&pool, &request.account_id.0, block.timestamp).await?;
+    let block = data_source.get_block_from_params(&block_params).await?;
+    let near_last_modified = data_source.get_near_last_modified(&request.account_id.0, &block).await?;
+    let ft_last_modified = data_source.get_ft_last_modified(&request.account_id.0, None, &block).await?;
+    last_modified::check_if_modified_since(&http_request, near_last_modified.max(ft_last_modified))?;
+    modules::check_account_exists(&data_source, &request.account_id.0, block.timestamp).await?;
 
     let mut balances: Vec<schemas::Coin> = vec![];
+    let mut failed_contracts: Vec<types::FailedContract> = vec![];
     balances.push(
-        data_provider::get_near_balance(&pool, &block, &request.account_id.0)
+        data_source
+            .get_near_balance(&block, &request.account_id.0)
             .await?
             .into(),
     );
     pagination.limit -= 1;
 
     if pagination.limit > 0 {
-        let ft_balances = &mut data_provider::get_coin_balances(
-            &pool,
-            &rpc_client,
-            &block,
-            &request.account_id.0,
-            &pagination,
-        )
-        .await?;
+        let (ft_balances, ft_failed_contracts) = &mut data_source
+            .get_coin_balances(&block, &request.account_id.0, &pagination)
+            .await?;
+        allow_list.retain(ft_balances, |coin| coin.contract_account_id.as_ref().map(|id| &id.0));
+        allow_list.retain(ft_failed_contracts, |failed| Some(&failed.contract_account_id.0));
         balances.append(ft_balances);
+        failed_contracts.append(ft_failed_contracts);
         pagination.limit -= ft_balances.length() as u32;
     }
+    if skip_errors_params.skip_errors.unwrap_or_default() {
+        failed_contracts.clear();
+    }
+    if metadata_params.is_latest() {
+        super::use_latest_metadata(&data_source, &mut balances).await?;
+    }
 
     Ok(Json(schemas::CoinBalancesResponse {
         balances,
-        block_timestamp_nanos: types::U64::from(block.timestamp),
-        block_height: types::U64::from(block.height),
+        meta: types::ResponseMeta::new(&block),
+        failed_contracts,
     }))
 }
 
@@ -87,16 +150,22 @@ pub async fn get_coin_balances(
 /// for the given contract and timestamp/block_height.
 /// For FT contract, the response has only 1 item in the list.
 /// For MT contracts, there could be several balances (MT support is not ready yet).
+/// Liquid staking tokens (stNEAR, LiNEAR) also have their value in NEAR by the current exchange rate.
+/// `Last-Modified` is the time of the last event, use `If-Modified-Since` to poll the updates.
 ///
 /// **Limitations**
 /// * For now, we support only the balance for FT contracts which implement Events NEP.
 ///   We work on the solution to support the other FT contracts, including `wrap.near` and bridged tokens.
 /// * We are in the process of supporting Multi Token balances.
+/// * The metadata is taken at the same block as the balances.
+///   Pass `metadata=latest` to get the current name and icon instead, it could be up to 1 hour old.
 pub async fn get_coin_balances_by_contract(
-    pool: web::Data<sqlx::Pool<sqlx::Postgres>>,
-    rpc_client: web::Data<near_jsonrpc_client::JsonRpcClient>,
+    _query: types::query_params::ValidatedQuery,
+    http_request: actix_web::HttpRequest,
+    data_source: web::Data<data_source::DataSourceRef>,
     request: ValidatedPath<schemas::BalanceByContractRequest>,
     block_params: web::Query<types::query_params::BlockParams>,
+    metadata_params: web::Query<types::query_params::MetadataParams>,
 ) -> crate::Result<Json<schemas::CoinBalancesResponse>> {
     if request.contract_account_id.to_string() == "near" {
         return Err(errors::ErrorKind::InvalidInput(
@@ -104,72 +173,365 @@ pub async fn get_coin_balances_by_contract(
         )
         .into());
     }
-    types::query_params::check_block_params(&block_params)?;
-    let block = db_helpers::get_block_from_params(&pool, &block_params).await?;
-    modules::check_account_exists(&pool, &request.account_id.0, block.timestamp).await?;
-
-    let balances = data_provider::get_coin_balances_by_contract(
-        &rpc_client,
-        &block,
-        &request.contract_account_id.0,
-        &request.account_id.0,
-    )
-    .await?;
+    let block = data_source.get_block_from_params(&block_params).await?;
+    last_modified::check_if_modified_since(
+        &http_request,
+        data_source
+            .get_ft_last_modified(&request.account_id.0, Some(&request.contract_account_id.0), &block)
+            .await?,
+    )?;
+    modules::check_account_exists(&data_source, &request.account_id.0, block.timestamp).await?;
+
+    let mut balances = data_source
+        .get_coin_balances_by_contract(
+            &block,
+            &request.contract_account_id.0,
+            &request.account_id.0,
+        )
+        .await?;
+    if metadata_params.is_latest() {
+        super::use_latest_metadata(&data_source, &mut balances).await?;
+    }
 
     Ok(Json(schemas::CoinBalancesResponse {
         balances,
-        block_timestamp_nanos: types::U64::from(block.timestamp),
-        block_height: types::U64::from(block.height),
+        meta: types::ResponseMeta::new(&block),
+        failed_contracts: vec![],
     }))
 }
 
+#[api_v2_operation(tags(Coins))]
+/// Reconcile user's FT balance
+///
+/// This endpoint returns the FT balance for the given account_id, contract_id, timestamp/block_height
+/// computed from the indexed events, together with `ft_balance_of` of the contract at the same block.
+/// `matches` is `false` if they differ, e.g. if the indexer has missed the events.
+///
+/// **Limitations**
+/// * The contracts which do not implement Events NEP (or change the balances without the events,
+///   e.g. the rebasing tokens) never match.
+/// * `ft_balance_of` goes to the archival RPC for the old blocks, it's much slower than the DB.
+pub async fn reconcile_coin_balance(
+    _query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: ValidatedPath<schemas::BalanceByContractRequest>,
+    block_params: web::Query<types::query_params::BlockParams>,
+) -> crate::Result<Json<schemas::BalanceReconciliationResponse>> {
+    if request.contract_account_id.to_string() == "near" {
+        return Err(errors::ErrorKind::InvalidInput(
+            "NEAR balance is always taken from the indexer, only FT contracts could be reconciled".to_string(),
+        )
+        .into());
+    }
+    let block = data_source.get_block_from_params(&block_params).await?;
+    modules::check_account_exists(&data_source, &request.account_id.0, block.timestamp).await?;
+
+    let indexed_balance = data_source
+        .get_ft_balance_from_events(&block, &request.contract_account_id.0, &request.account_id.0)
+        .await?;
+    let rpc_balance = data_source
+        .get_ft_balance(&block, &request.contract_account_id.0, &request.account_id.0)
+        .await?;
+
+    Ok(Json(schemas::BalanceReconciliationResponse {
+        contract_account_id: request.contract_account_id.clone(),
+        indexed_balance: indexed_balance.into(),
+        rpc_balance: rpc_balance.into(),
+        matches: indexed_balance == rpc_balance,
+        meta: types::ResponseMeta::new(&block),
+    }))
+}
+
+#[api_v2_operation(tags(Coins))]
+/// Get user's NEAR balance at several blocks
+///
+/// This endpoint returns the NEAR balance of the given account_id at each of the given block heights
+/// (or at the evenly spaced heights of the given range), e.g. to draw the chart in one call.
+///
+/// **Limitations**
+/// * We provide up to 100 points.
+pub async fn get_near_balances_at_blocks(
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: ValidatedPath<schemas::BalanceRequest>,
+    params: web::Query<schemas::BalancesAtBlocksParams>,
+) -> crate::Result<Json<schemas::BalancesAtBlocksResponse>> {
+    let blocks = data_source.get_blocks_by_heights(&params.block_heights()?).await?;
+    let last_block = blocks.last().ok_or_else(|| errors::ErrorKind::InvalidInput(
+        "All the requested block heights are before the first indexed block".to_string(),
+    ))?;
+    modules::check_account_exists(&data_source, &request.account_id.0, last_block.timestamp).await?;
+
+    let (data_source, account_id) = (&data_source, &request.account_id.0);
+    let balances = futures::stream::iter(blocks.iter())
+        .map(|block| async move {
+            // The chart could start before the account was created
+            let balance = if data_source.does_account_exist(account_id, block.timestamp).await? {
+                data_source.get_near_balance(block, account_id).await?.balance
+            } else {
+                types::U128(0)
+            };
+            Ok::<_, errors::Error>(schemas::BalanceAtBlock {
+                balance,
+                block_timestamp_nanos: types::U64::from(block.timestamp),
+                block_height: types::U64::from(block.height),
+                block_hash: block.hash.clone(),
+            })
+        })
+        .buffered(MAX_CONCURRENT_BALANCE_QUERIES)
+        .try_collect()
+        .await?;
+
+    Ok(Json(schemas::BalancesAtBlocksResponse {
+        balances,
+        metadata: data_provider::get_near_metadata(),
+    }))
+}
+
+#[api_v2_operation(tags(Coins))]
+/// Get user's coin balance by contract at several blocks
+///
+/// This endpoint returns the FT balance of the given account_id for the given contract
+/// at each of the given block heights (or at the evenly spaced heights of the given range),
+/// e.g. to draw the chart in one call.
+///
+/// **Limitations**
+/// * We provide up to 100 points.
+/// * The contract should exist at all the requested heights.
+pub async fn get_coin_balances_at_blocks(
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: ValidatedPath<schemas::BalanceByContractRequest>,
+    params: web::Query<schemas::BalancesAtBlocksParams>,
+) -> crate::Result<Json<schemas::BalancesAtBlocksResponse>> {
+    if request.contract_account_id.to_string() == "near" {
+        return Err(errors::ErrorKind::InvalidInput(
+            "For native balance, please use NEAR (uppercase)".to_string(),
+        )
+        .into());
+    }
+    let blocks = data_source.get_blocks_by_heights(&params.block_heights()?).await?;
+    let last_block = blocks.last().ok_or_else(|| errors::ErrorKind::InvalidInput(
+        "All the requested block heights are before the first indexed block".to_string(),
+    ))?;
+    modules::check_account_exists(&data_source, &request.account_id.0, last_block.timestamp).await?;
+
+    let (data_source, contract_id, account_id) =
+        (&data_source, &request.contract_account_id.0, &request.account_id.0);
+    let balances = futures::stream::iter(blocks.iter())
+        .map(|block| async move {
+            let balance = data_source.get_ft_balance(block, contract_id, account_id).await?;
+            Ok::<_, errors::Error>(schemas::BalanceAtBlock {
+                balance: balance.into(),
+                block_timestamp_nanos: types::U64::from(block.timestamp),
+                block_height: types::U64::from(block.height),
+                block_hash: block.hash.clone(),
+            })
+        })
+        .buffered(MAX_CONCURRENT_BALANCE_QUERIES)
+        .try_collect()
+        .await?;
+    let metadata = data_source
+        .get_ft_contract_metadata(&request.contract_account_id.0, last_block.height)
+        .await?;
+
+    Ok(Json(schemas::BalancesAtBlocksResponse {
+        balances,
+        metadata: schemas::CoinMetadata::from_ft_metadata(&request.contract_account_id.0, metadata),
+    }))
+}
+
+#[api_v2_operation(tags(Coins))]
+/// Get user's balance changes between two blocks
+///
+/// This endpoint returns NEAR and FT balances of the given account_id at `from_block` and `to_block`
+/// and the difference between them, for each coin changed in this range.
+///
+/// **Limitations**
+/// * For now, we support only FT contracts which implement Events NEP.
+/// * We provide only up to 100 FT contracts, ordered by contract_account_id.
+pub async fn get_balances_diff(
+    data_source: web::Data<data_source::DataSourceRef>,
+    allow_list: web::Data<allow_list::ContractAllowList>,
+    request: ValidatedPath<schemas::BalanceRequest>,
+    params: web::Query<schemas::BalancesDiffParams>,
+) -> crate::Result<Json<schemas::BalancesDiffResponse>> {
+    if params.from_block.0 > params.to_block.0 {
+        return Err(errors::ErrorKind::InvalidInput(
+            "from_block should not be greater than to_block".to_string(),
+        )
+        .into());
+    }
+    let from_block = data_source
+        .get_block_from_params(&types::query_params::BlockParams {
+            block_height: Some(params.from_block),
+            ..Default::default()
+        })
+        .await?;
+    let to_block = data_source
+        .get_block_from_params(&types::query_params::BlockParams {
+            block_height: Some(params.to_block),
+            ..Default::default()
+        })
+        .await?;
+    let account_id = &request.account_id.0;
+    modules::check_account_exists(&data_source, account_id, to_block.timestamp).await?;
+
+    let to_near_balance = data_source.get_near_balance(&to_block, account_id).await?;
+    let from_near_balance = if data_source.does_account_exist(account_id, from_block.timestamp).await? {
+        data_source.get_near_balance(&from_block, account_id).await?.balance.0
+    } else {
+        0
+    };
+    let mut diffs = vec![coin_diff(
+        "nearprotocol",
+        None,
+        from_near_balance,
+        to_near_balance.balance.0,
+        to_near_balance.metadata,
+    )?];
+
+    let mut contracts = data_source
+        .get_changed_ft_contracts(account_id, &from_block, &to_block, MAX_DIFF_CONTRACTS)
+        .await?;
+    allow_list.retain(&mut contracts, |contract| Some(&contract.contract_id));
+    let (data_source, from_block, to_block) = (&data_source, &from_block, &to_block);
+    let mut ft_diffs: Vec<schemas::CoinDiff> = futures::stream::iter(contracts.iter())
+        .map(|contract| async move {
+            let from_balance = if contract.held_before {
+                data_source.get_ft_balance(from_block, &contract.contract_id, account_id).await?
+            } else {
+                0
+            };
+            let to_balance = data_source.get_ft_balance(to_block, &contract.contract_id, account_id).await?;
+            let metadata = data_source
+                .get_ft_contract_metadata(&contract.contract_id, to_block.height)
+                .await?;
+            coin_diff(
+                "nep141",
+                Some(&contract.contract_id),
+                from_balance,
+                to_balance,
+                schemas::CoinMetadata::from_ft_metadata(&contract.contract_id, metadata),
+            )
+        })
+        .buffered(MAX_CONCURRENT_BALANCE_QUERIES)
+        .try_collect()
+        .await?;
+    diffs.append(&mut ft_diffs);
+
+    Ok(Json(schemas::BalancesDiffResponse {
+        diffs,
+        from_block_timestamp_nanos: types::U64::from(from_block.timestamp),
+        from_block_height: types::U64::from(from_block.height),
+        from_block_hash: from_block.hash.clone(),
+        meta: types::ResponseMeta::new(&to_block),
+    }))
+}
+
+fn coin_diff(
+    standard: &str,
+    contract_id: Option<&near_primitives::types::AccountId>,
+    from_balance: u128,
+    to_balance: u128,
+    metadata: schemas::CoinMetadata,
+) -> crate::Result<schemas::CoinDiff> {
+    let delta = i128::try_from(to_balance)
+        .ok()
+        .zip(i128::try_from(from_balance).ok())
+        .and_then(|(to_balance, from_balance)| to_balance.checked_sub(from_balance))
+        .ok_or_else(|| errors::ErrorKind::InternalError(format!(
+            "The balance change from {} to {} does not fit into i128", from_balance, to_balance
+        )))?;
+    Ok(schemas::CoinDiff {
+        standard: standard.to_string(),
+        contract_account_id: contract_id.map(|contract_id| contract_id.clone().into()),
+        from_balance: from_balance.into(),
+        to_balance: to_balance.into(),
+        delta: delta.into(),
+        metadata,
+    })
+}
+
+#[cfg(feature = "history")]
 #[api_v2_operation(tags(Coins))]
 /// Get user's NEAR history
 ///
 /// This endpoint returns the history of operations with NEAR coin
 /// for the given account_id, timestamp/block_height.
+/// `Last-Modified` is the time of the last operation, use `If-Modified-Since` to poll the updates.
 ///
 /// **Limitations**
-/// * We provide only up to 100 items, where recent updates go first.
-///   Full-featured pagination will be provided later.
+/// * We provide only up to `limit` items, where recent updates go first.
+///   Pass `meta.next_cursor` as `cursor` to get the next page.
 pub async fn get_near_history(
-    pool: web::Data<sqlx::Pool<sqlx::Postgres>>,
-    pool_balances: web::Data<db_helpers::DBWrapper>,
+    http_request: actix_web::HttpRequest,
+    data_source: web::Data<data_source::DataSourceRef>,
     request: ValidatedPath<schemas::BalanceRequest>,
     pagination_params: web::Query<types::query_params::HistoryPaginationParams>,
 ) -> crate::Result<Json<schemas::HistoryResponse>> {
-    let block = db_helpers::get_last_block(&pool).await?;
-    modules::check_account_exists(&pool, &request.account_id.0, block.timestamp).await?;
-    let pagination =
-        modules::check_and_get_history_pagination_params(&pool, pagination_params.0).await?;
+    let (block, pagination) =
+        modules::check_and_get_history_pagination_params(&data_source, &http_request, pagination_params.0).await?;
+    last_modified::check_if_modified_since(
+        &http_request,
+        data_source.get_near_last_modified(&request.account_id.0, &block).await?,
+    )?;
+    modules::check_account_exists(&data_source, &request.account_id.0, block.timestamp).await?;
+    let history = data_source
+        .get_near_history(&request.account_id, &pagination)
+        .await?;
+    let next_cursor = modules::next_history_cursor(&history, &block, &pagination);
 
     Ok(Json(schemas::HistoryResponse {
-        history: data_provider::get_near_history(
-            &pool_balances.pool,
-            &request.account_id,
-            &pagination,
-        )
-        .await?,
-        block_timestamp_nanos: types::U64::from(block.timestamp),
-        block_height: types::U64::from(block.height),
+        history: modules::without_event_indexes(history),
+        meta: types::ResponseMeta::new(&block).with_next_cursor(next_cursor),
     }))
 }
 
+#[cfg(feature = "history")]
+#[api_v2_operation(tags(Coins))]
+/// Get user's gas fees
+///
+/// This endpoint returns NEAR burnt on gas by the given account_id, with the daily breakdown,
+/// for the window which ends at the given timestamp/block_height.
+/// `window` is `30d` by default.
+///
+/// **Limitations**
+/// * Only the gas paid by the account itself is counted. Gas prepaid for the receipts
+///   is attributed to the signer of the transaction.
+pub async fn get_gas_fees(
+    _query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: ValidatedPath<schemas::BalanceRequest>,
+    block_params: web::Query<types::query_params::BlockParams>,
+    window_params: web::Query<types::query_params::WindowParams>,
+) -> crate::Result<Json<schemas::GasFeesResponse>> {
+    let window_nanos = types::query_params::get_window_nanos(&window_params, 30)?;
+    let block = data_source.get_block_from_params(&block_params).await?;
+    modules::check_account_exists(&data_source, &request.account_id.0, block.timestamp).await?;
+
+    Ok(Json(
+        data_source
+            .get_gas_fees(&request.account_id.0, &block, window_nanos)
+            .await?,
+    ))
+}
+
+#[cfg(feature = "history")]
 #[api_v2_operation(tags(Coins))]
 /// Get user's coin history by contract
 ///
 /// This endpoint returns the history of coin operations (FT, other standards)
 /// for the given account_id, contract_id, timestamp/block_height.
+/// `Last-Modified` is the time of the last operation, use `If-Modified-Since` to poll the updates.
 ///
 /// **Limitations**
 /// * For now, we support only FT contracts which implement Events NEP.
 ///   We work on the solution to support the other FT contracts, including `wrap.near` and bridged tokens.
 /// * We are in the process of supporting Multi Token history.
-/// * We provide only up to 100 items, where recent updates go first.
-///   Full-featured pagination will be provided later.
+/// * We provide only up to `limit` items, where recent updates go first.
+///   Pass `meta.next_cursor` as `cursor` to get the next page.
 pub async fn get_coin_history(
-    pool: web::Data<sqlx::Pool<sqlx::Postgres>>,
-    rpc_client: web::Data<near_jsonrpc_client::JsonRpcClient>,
+    http_request: actix_web::HttpRequest,
+    data_source: web::Data<data_source::DataSourceRef>,
     request: ValidatedPath<schemas::HistoryRequest>,
     pagination_params: web::Query<types::query_params::HistoryPaginationParams>,
 ) -> crate::Result<Json<schemas::HistoryResponse>> {
@@ -179,49 +541,410 @@ pub async fn get_coin_history(
         )
         .into());
     }
-    let pagination =
-        modules::check_and_get_history_pagination_params(&pool, pagination_params.0).await?;
-    modules::check_account_exists(&pool, &request.account_id.0, pagination.block_timestamp).await?;
-
-    Ok(Json(schemas::HistoryResponse {
-        history: data_provider::get_coin_history(
-            &pool,
-            &rpc_client,
+    let (block, pagination) =
+        modules::check_and_get_history_pagination_params(&data_source, &http_request, pagination_params.0).await?;
+    last_modified::check_if_modified_since(
+        &http_request,
+        data_source
+            .get_ft_last_modified(&request.account_id.0, Some(&request.contract_account_id.0), &block)
+            .await?,
+    )?;
+    modules::check_account_exists(&data_source, &request.account_id.0, block.timestamp).await?;
+    // The balance is taken at the last block strictly before the position of the page
+    let balance_block = data_source
+        .get_block_from_params(&types::query_params::BlockParams {
+            block_timestamp_nanos: Some((pagination.block_timestamp - 1).into()),
+            ..Default::default()
+        })
+        .await?;
+    let history = data_source
+        .get_coin_history(
             &request.contract_account_id.0,
             &request.account_id.0,
-            &pagination,
+            &types::query_params::HistoryPagination {
+                block_height: balance_block.height,
+                ..pagination
+            },
         )
-        .await?,
-        block_timestamp_nanos: types::U64::from(pagination.block_timestamp),
-        block_height: types::U64::from(pagination.block_height),
+        .await?;
+    let next_cursor = modules::next_history_cursor(&history, &block, &pagination);
+
+    Ok(Json(schemas::HistoryResponse {
+        history: modules::without_event_indexes(history),
+        meta: types::ResponseMeta::new(&block).with_next_cursor(next_cursor),
+    }))
+}
+
+#[cfg(feature = "history")]
+#[api_v2_operation(tags(Coins))]
+/// Get FT contract transfers
+///
+/// This endpoint returns all the transfer events (including mints and burns) of the given FT contract,
+/// not only the ones of the specific account. Use it to monitor the activity of your token.
+/// Pass `meta.next_cursor` as `cursor` to get the next page.
+///
+/// **Limitations**
+/// * For now, we support only FT contracts which implement Events NEP.
+///   We work on the solution to support the other FT contracts, including `wrap.near` and bridged tokens.
+/// * We provide only up to `limit` items, where recent events go first.
+pub async fn get_ft_transfers(
+    http_request: actix_web::HttpRequest,
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: ValidatedPath<schemas::FtTransfersRequest>,
+    pagination_params: web::Query<types::query_params::HistoryPaginationParams>,
+) -> crate::Result<Json<schemas::FtTransfersResponse>> {
+    let (block, pagination) =
+        modules::check_and_get_history_pagination_params(&data_source, &http_request, pagination_params.0).await?;
+    let transfers = data_source
+        .get_ft_transfers(&request.contract_account_id.0, &pagination)
+        .await?;
+    let next_cursor = modules::next_history_cursor(&transfers, &block, &pagination);
+
+    Ok(Json(schemas::FtTransfersResponse {
+        transfers: modules::without_event_indexes(transfers),
+        meta: types::ResponseMeta::new(&block).with_next_cursor(next_cursor),
+    }))
+}
+
+#[api_v2_operation(tags(Coins))]
+/// Get FT holders
+///
+/// This endpoint returns the accounts with the positive balance of the given FT contract
+/// at the given timestamp/block_height, ordered by account_id.
+/// Pass `meta.block_height` of the response and the last account_id as `after_account_id` to get the next page:
+/// the pages of the same block_height always make up the same snapshot.
+///
+/// **Limitations**
+/// * For now, we support only FT contracts which implement Events NEP.
+///   The balances are the sums of the events, they differ from `ft_balance_of` if the contract
+///   missed the events (e.g. the storage unregister with the force flag).
+/// * We provide only up to `limit` items, use `/nep141/{contract_account_id}/holders/export` to get all of them.
+pub async fn get_ft_holders(
+    query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: ValidatedPath<schemas::FtHoldersRequest>,
+    holders_params: web::Query<schemas::FtHoldersParams>,
+    block_params: web::Query<types::query_params::BlockParams>,
+    mut pagination_params: web::Query<types::query_params::PaginationParams>,
+) -> crate::Result<Json<schemas::FtHoldersResponse>> {
+    pagination_params.limit = Some(query.limit);
+    let block = data_source.get_block_from_params(&block_params).await?;
+    let pagination = types::query_params::Pagination::from(pagination_params.0);
+
+    Ok(Json(schemas::FtHoldersResponse {
+        holders: data_source
+            .get_ft_holders(
+                &request.contract_account_id.0,
+                block.timestamp,
+                holders_params
+                    .after_account_id
+                    .as_ref()
+                    .map(|account_id| &account_id.0),
+                pagination.limit,
+            )
+            .await?,
+        meta: types::ResponseMeta::new(&block),
     }))
 }
 
+#[api_v2_operation(tags(Coins))]
+/// Export FT holders to CSV or Parquet
+///
+/// This endpoint returns all the holders of the given FT contract at the given timestamp/block_height
+/// as the file with `account_id,balance` columns, e.g. for the governance snapshots.
+/// The block is also given in `X-Block-Height` and `X-Block-Timestamp-Nanos` headers.
+///
+/// **Limitations**
+/// * The same as for `/nep141/{contract_account_id}/holders`.
+/// * CSV is streamed, if the DB fails in the middle, the connection is closed
+///   and the file is incomplete. Check that the last line ends with the newline.
+/// * Parquet is available if the server is built with `parquet` feature, up to 1000000 holders.
+pub async fn export_ft_holders(
+    _query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: ValidatedPath<schemas::FtHoldersRequest>,
+    block_params: web::Query<types::query_params::BlockParams>,
+    format_params: web::Query<types::query_params::FileFormatParams>,
+) -> crate::Result<actix_web::HttpResponse> {
+    let format = format_params.format.unwrap_or_default();
+    format.check_supported()?;
+    let block = data_source.get_block_from_params(&block_params).await?;
+    let contract_id = request.contract_account_id.0.clone();
+    let block_timestamp = block.timestamp;
+    let filename = format!(
+        "{}_holders_{}.{}",
+        contract_id,
+        block.height,
+        format.as_str()
+    );
+    if format == types::query_params::FileFormat::Parquet {
+        let mut table = tabular::Table::new(
+            vec![
+                ("account_id", tabular::ColumnType::Text),
+                ("balance", tabular::ColumnType::Text),
+            ],
+            MAX_PARQUET_EXPORT_ROWS,
+        );
+        let mut after_account_id: Option<near_primitives::types::AccountId> = None;
+        loop {
+            let holders = data_source
+                .get_ft_holders(
+                    &contract_id,
+                    block_timestamp,
+                    after_account_id.as_ref(),
+                    EXPORT_PAGE_SIZE,
+                )
+                .await?;
+            for holder in &holders {
+                table.push_row(vec![
+                    holder.account_id.0.to_string(),
+                    holder.balance.0.to_string(),
+                ])?;
+            }
+            if holders.len() < EXPORT_PAGE_SIZE as usize {
+                break;
+            }
+            after_account_id = holders.last().map(|holder| holder.account_id.0.clone());
+        }
+        return Ok(actix_web::HttpResponse::Ok()
+            .content_type(format.content_type())
+            .insert_header((
+                "Content-Disposition",
+                format!("attachment; filename=\"{}\"", filename),
+            ))
+            .insert_header(("X-Block-Height", block.height.to_string()))
+            .insert_header(("X-Block-Timestamp-Nanos", block.timestamp.to_string()))
+            .body(table.write(format)?));
+    }
+
+    let header = futures::stream::once(futures::future::ready(Ok::<_, std::io::Error>(
+        actix_web::web::Bytes::from_static(b"account_id,balance\n"),
+    )));
+    // `None` means we are done, `Some(None)` is the first page
+    let pages = futures::stream::try_unfold(Some(None), move |cursor| {
+        let data_source = data_source.clone();
+        let contract_id = contract_id.clone();
+        async move {
+            let after_account_id: Option<near_primitives::types::AccountId> = match cursor {
+                Some(after_account_id) => after_account_id,
+                None => return Ok(None),
+            };
+            let holders = data_source
+                .get_ft_holders(
+                    &contract_id,
+                    block_timestamp,
+                    after_account_id.as_ref(),
+                    EXPORT_PAGE_SIZE,
+                )
+                .await
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err.to_string()))?;
+            let next_cursor = if holders.len() < EXPORT_PAGE_SIZE as usize {
+                None
+            } else {
+                holders.last().map(|holder| Some(holder.account_id.0.clone()))
+            };
+            // Account ids never have commas, quotes or newlines, nothing to escape
+            let csv: String = holders
+                .iter()
+                .map(|holder| format!("{},{}\n", holder.account_id.0, holder.balance.0))
+                .collect();
+            Ok(Some((actix_web::web::Bytes::from(csv), next_cursor)))
+        }
+    });
+
+    Ok(actix_web::HttpResponse::Ok()
+        .content_type("text/csv")
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"{}\"", filename),
+        ))
+        .insert_header(("X-Block-Height", block.height.to_string()))
+        .insert_header(("X-Block-Timestamp-Nanos", block.timestamp.to_string()))
+        .streaming(futures::StreamExt::chain(header, pages)))
+}
+
 #[api_v2_operation(tags(Coins))]
 /// Get FT contract metadata
 ///
 /// This endpoint returns the metadata for given FT contract and timestamp/block_height.
+/// `upgraded_since` warns if the contract code changed after this block.
 ///
 /// **Limitations**
 /// * For now, we support only FT contracts which implement Events NEP.
 ///   We work on the solution to support the other FT contracts, including `wrap.near` and bridged tokens.
 pub async fn get_ft_contract_metadata(
-    pool: web::Data<sqlx::Pool<sqlx::Postgres>>,
-    rpc_client: web::Data<near_jsonrpc_client::JsonRpcClient>,
+    _query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
     request: ValidatedPath<schemas::ContractMetadataRequest>,
     block_params: web::Query<types::query_params::BlockParams>,
 ) -> crate::Result<Json<schemas::FtContractMetadataResponse>> {
-    types::query_params::check_block_params(&block_params)?;
-    let block = db_helpers::get_block_from_params(&pool, &block_params).await?;
+    let block = data_source.get_block_from_params(&block_params).await?;
 
     Ok(Json(schemas::FtContractMetadataResponse {
-        metadata: data_provider::get_ft_contract_metadata(
-            &rpc_client,
-            request.contract_account_id.0.clone(),
-            block.height,
-        )
-        .await?,
-        block_timestamp_nanos: types::U64::from(block.timestamp),
-        block_height: types::U64::from(block.height),
+        metadata: data_source
+            .get_ft_contract_metadata(&request.contract_account_id.0, block.height)
+            .await?,
+        meta: types::ResponseMeta::new(&block),
+        upgraded_since: modules::get_upgraded_since(&data_source, &request.contract_account_id.0, &block).await?,
+    }))
+}
+
+#[api_v2_operation(tags(Coins))]
+/// Get the metadata of several FT contracts
+///
+/// This endpoint returns the latest metadata of all the given FT contracts in the order of the request,
+/// e.g. for the token list of the wallet. It's POST because the list could be too long for the query.
+///
+/// **Limitations**
+/// * Up to 100 contracts.
+/// * The metadata could be up to 1 hour old, use `/nep141/metadata/{contract_account_id}/refresh` to update it.
+/// * If the RPC call to some FT contract fails, it's listed in `failed_contracts`.
+pub async fn get_ft_contracts_metadata(
+    data_source: web::Data<data_source::DataSourceRef>,
+    allow_list: web::Data<allow_list::ContractAllowList>,
+    request: web::Json<schemas::FtContractsMetadataRequest>,
+) -> crate::Result<Json<schemas::FtContractsMetadataResponse>> {
+    let mut contract_ids: Vec<near_primitives::types::AccountId> =
+        request.contract_account_ids()?.into_iter().map(|contract_id| contract_id.0).collect();
+    allow_list.retain(&mut contract_ids, |contract_id| Some(contract_id));
+    let block = data_source.get_last_block().await?;
+    let (metadata, failed_contracts) =
+        data_source.get_ft_contracts_metadata(&contract_ids, block.height).await?;
+
+    Ok(Json(schemas::FtContractsMetadataResponse {
+        metadata,
+        meta: types::ResponseMeta::new(&block),
+        failed_contracts,
+    }))
+}
+
+#[api_v2_operation(tags(Coins))]
+/// Refresh FT contract metadata
+///
+/// This endpoint asks the contract for the metadata at the last block bypassing our caches,
+/// puts the fresh value (and the icon of `/icons/{contract_account_id}`) into them and returns it.
+/// Use it after the token changed its name, symbol or icon.
+///
+/// **Limitations**
+/// * The icons already cached by the browsers and CDNs are updated within `Cache-Control` max-age (1 hour).
+pub async fn refresh_ft_contract_metadata(
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: ValidatedPath<schemas::ContractMetadataRequest>,
+) -> crate::Result<Json<schemas::FtContractMetadataResponse>> {
+    let block = data_source.get_last_block().await?;
+
+    Ok(Json(schemas::FtContractMetadataResponse {
+        metadata: data_source
+            .refresh_ft_contract_metadata(&request.contract_account_id.0, block.height)
+            .await?,
+        meta: types::ResponseMeta::new(&block),
+        // It's the last block
+        upgraded_since: None,
+    }))
+}
+
+#[api_v2_operation(tags(Coins))]
+/// Get FT price history
+///
+/// This endpoint returns the USD price of the given FT contract aggregated by hour/day/week buckets
+/// (open/high/low/close), recent buckets go first.
+/// Only the samples taken not later than the given timestamp/block_height are used.
+///
+/// **Limitations**
+/// * We sample the prices only for the tokens configured by the server operator,
+///   from the DEX pools paired with USD stablecoin.
+/// * We provide only up to `limit` buckets. Full-featured pagination will be provided later.
+pub async fn get_price_history(
+    query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: ValidatedPath<schemas::PriceHistoryRequest>,
+    price_params: web::Query<schemas::PriceHistoryParams>,
+    block_params: web::Query<types::query_params::BlockParams>,
+    mut pagination_params: web::Query<types::query_params::PaginationParams>,
+) -> crate::Result<Json<schemas::PriceHistoryResponse>> {
+    pagination_params.limit = Some(query.limit);
+    let pagination = types::query_params::Pagination::from(pagination_params.0);
+    let block = data_source.get_block_from_params(&block_params).await?;
+
+    Ok(Json(schemas::PriceHistoryResponse {
+        prices: data_source
+            .get_token_price_history(
+                &request.contract_account_id.0,
+                &price_params.bucket.unwrap_or_default(),
+                block.timestamp,
+                pagination.limit,
+            )
+            .await?,
+        meta: types::ResponseMeta::new(&block),
     }))
 }
+
+#[api_v2_operation(tags(Coins))]
+/// Get the portfolio of several accounts
+///
+/// This endpoint returns the coin balances of all the given accounts (e.g. the main account,
+/// its sub-accounts and the implicit accounts) for the given timestamp/block_height,
+/// valued in USD with the last sampled prices: summed up by coin, and per account.
+/// It's POST because the list of the accounts could be too long for the query.
+///
+/// **Limitations**
+/// * Up to 25 accounts, up to 100 coins of each account.
+/// * We sample the prices only for the tokens configured by the server operator, NEAR is valued by `wrap.near` price.
+///   The coins without the price have null `value_usd` and are not counted in the totals.
+/// * If the RPC call to some FT contract fails, it's listed in `failed_contracts`.
+pub async fn get_portfolio(
+    _query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
+    allow_list: web::Data<allow_list::ContractAllowList>,
+    block_params: web::Query<types::query_params::BlockParams>,
+    request: web::Json<schemas::PortfolioRequest>,
+) -> crate::Result<Json<schemas::PortfolioResponse>> {
+    let account_ids = request.account_ids()?;
+    let block = data_source.get_block_from_params(&block_params).await?;
+    Ok(Json(super::get_portfolio(&data_source, &allow_list, account_ids, &block).await?))
+}
+
+#[api_v2_operation(tags(Coins))]
+/// Get FT icon
+///
+/// This endpoint returns the icon from the latest metadata of the given FT contract.
+/// Use it instead of `icon` field of the metadata: `/coins` endpoints give `icon_url` pointing here.
+///
+/// **Limitations**
+/// * The icons bigger than 64 KB, SVGs with scripts/external links, and the unsupported formats are not served.
+/// * If the contract has https link in the metadata, we redirect there.
+pub async fn get_ft_icon(
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: ValidatedPath<schemas::IconRequest>,
+) -> crate::Result<actix_web::HttpResponse> {
+    let block = data_source.get_last_block().await?;
+    let icon = data_source
+        .get_ft_icon(&request.contract_account_id.0, block.height)
+        .await?
+        .ok_or_else(|| {
+            errors::ErrorKind::InvalidInput(format!(
+                "FT contract {} does not have the icon we could serve",
+                request.contract_account_id.0
+            ))
+        })?;
+
+    Ok(match icon {
+        data_provider::Icon::Inline {
+            content_type,
+            bytes,
+        } => actix_web::HttpResponse::Ok()
+            .content_type(content_type)
+            .insert_header(("Cache-Control", "public, max-age=3600"))
+            .insert_header(("X-Content-Type-Options", "nosniff"))
+            // SVG opened directly should not be able to do anything even if we missed something
+            .insert_header((
+                "Content-Security-Policy",
+                "default-src 'none'; style-src 'unsafe-inline'",
+            ))
+            .body(bytes),
+        data_provider::Icon::Link(url) => actix_web::HttpResponse::Found()
+            .insert_header(("Location", url))
+            .insert_header(("Cache-Control", "public, max-age=3600"))
+            .finish(),
+    })
+}
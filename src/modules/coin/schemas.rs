@@ -1,7 +1,7 @@
 use paperclip::actix::Apiv2Schema;
 use validator::{Validate, ValidationError};
 
-use crate::types;
+use crate::{types, BigDecimal};
 
 // *** Requests ***
 
@@ -34,15 +34,101 @@ pub struct ContractMetadataRequest {
     pub contract_account_id: types::AccountId,
 }
 
+#[derive(Validate, Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct PriceHistoryRequest {
+    #[validate(custom = "near_primitives::types::AccountId::validate")]
+    pub contract_account_id: types::AccountId,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct PriceHistoryParams {
+    /// `hour` by default
+    pub bucket: Option<PriceBucket>,
+}
+
+#[derive(Validate, Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct FtTransfersRequest {
+    #[validate(custom = "near_primitives::types::AccountId::validate")]
+    pub contract_account_id: types::AccountId,
+}
+
+#[derive(Validate, Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct FtHoldersRequest {
+    #[validate(custom = "near_primitives::types::AccountId::validate")]
+    pub contract_account_id: types::AccountId,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct FtHoldersParams {
+    /// Pagination cursor: `account_id` of the last holder from the previous page
+    pub after_account_id: Option<types::AccountId>,
+}
+
+/// Either `block_heights`, or the range `from_block_height`..`to_block_height` split into `points` evenly spaced heights.
+/// Up to 100 heights are served
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct BalancesAtBlocksParams {
+    /// Comma-separated, e.g. `80000000,80100000,80200000`
+    pub block_heights: Option<String>,
+    pub from_block_height: Option<types::U64>,
+    pub to_block_height: Option<types::U64>,
+    /// 10 by default, the ends of the range are included
+    pub points: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct BalancesDiffParams {
+    /// Block height, the balances are taken at the end of this block
+    pub from_block: types::U64,
+    /// Block height, should not be less than `from_block`
+    pub to_block: types::U64,
+}
+
+#[derive(Validate, Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct IconRequest {
+    #[validate(custom = "near_primitives::types::AccountId::validate")]
+    pub contract_account_id: types::AccountId,
+}
+
+/// E.g. all the tokens of the wallet token list
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct FtContractsMetadataRequest {
+    pub contract_account_ids: Vec<types::AccountId>,
+}
+
+/// E.g. the main account, its sub-accounts and the implicit accounts of the same user
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct PortfolioRequest {
+    pub account_ids: Vec<types::AccountId>,
+}
+
 // *** Responses ***
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
 pub struct NearBalanceResponse {
-    /// Sum of staked and nonstaked balances
+    /// Sum of staked and nonstaked balances, in yoctoNEAR
     pub balance: types::U128,
     pub metadata: CoinMetadata,
-    pub block_timestamp_nanos: types::U64,
-    pub block_height: types::U64,
+    pub meta: types::ResponseMeta,
+}
+
+/// NEAR balance split by what the account could spend at the given block.
+/// The storage is paid by the staked balance first, the rest of its cost locks the nonstaked balance
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct NearBalanceBreakdownResponse {
+    /// Sum of staked and nonstaked balances, in yoctoNEAR
+    pub total_balance: types::U128,
+    /// Locked by the validator, in yoctoNEAR. The delegations to the staking pools are not here
+    pub staked_balance: types::U128,
+    /// Bytes taken by the account, its access keys, contract code and data
+    pub storage_usage: types::U64,
+    /// yoctoNEAR per byte in the epoch of the block, it was changed by the protocol upgrades
+    pub storage_amount_per_byte: types::U128,
+    /// The part of the nonstaked balance which pays for the storage, in yoctoNEAR
+    pub storage_locked_balance: types::U128,
+    /// The balance which could be transferred or spent on gas, in yoctoNEAR
+    pub available_balance: types::U128,
+    pub meta: types::ResponseMeta,
 }
 
 /// This response gives the information about all the available balances for the user.
@@ -51,23 +137,120 @@ pub struct NearBalanceResponse {
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
 pub struct CoinBalancesResponse {
     pub balances: Vec<Coin>,
-    pub block_timestamp_nanos: types::U64,
-    pub block_height: types::U64,
+    pub meta: types::ResponseMeta,
+    /// The contracts which failed to give the balance, they are not in `balances`
+    #[serde(default)]
+    pub failed_contracts: Vec<types::FailedContract>,
+}
+
+/// The FT balance computed from the indexed events, compared with the contract's answer at the same block
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct BalanceReconciliationResponse {
+    pub contract_account_id: types::AccountId,
+    /// The sum of the successful FT events of the account
+    pub indexed_balance: types::U128,
+    /// `ft_balance_of` called through RPC
+    pub rpc_balance: types::U128,
+    /// `false` means the indexer missed the events, or the contract changes the balances without the events
+    pub matches: bool,
+    pub meta: types::ResponseMeta,
 }
 
 /// This response provides the coin history (NEAR or by contract).
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
 pub struct HistoryResponse {
     pub history: Vec<HistoryItem>,
-    pub block_timestamp_nanos: types::U64,
-    pub block_height: types::U64,
+    pub meta: types::ResponseMeta,
+}
+
+/// All the transfer events of the FT contract, recent events go first.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct FtTransfersResponse {
+    pub transfers: Vec<FtTransfer>,
+    pub meta: types::ResponseMeta,
+}
+
+/// FT holders at the given block, ordered by account_id.
+/// Pass the same block_height and the last account_id to get the next page of the same snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct FtHoldersResponse {
+    pub holders: Vec<FtHolder>,
+    pub meta: types::ResponseMeta,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
 pub struct FtContractMetadataResponse {
     pub metadata: FtContractMetadata,
-    pub block_timestamp_nanos: types::U64,
-    pub block_height: types::U64,
+    pub meta: types::ResponseMeta,
+    /// The last code change after the requested block, `null` if the code is the same since then.
+    /// The full list is given by `/contracts/{contract_account_id}/code-history`
+    #[serde(default)]
+    pub upgraded_since: Option<types::CodeChange>,
+}
+
+/// The metadata of the requested FT contracts, in the order of the request
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct FtContractsMetadataResponse {
+    pub metadata: Vec<FtContractMetadataItem>,
+    pub meta: types::ResponseMeta,
+    /// The contracts which failed to give the metadata, they are not in `metadata`
+    #[serde(default)]
+    pub failed_contracts: Vec<types::FailedContract>,
+}
+
+/// Token prices aggregated by buckets, recent buckets go first.
+/// NEAR burnt on gas by the account in the window `[window_start_timestamp_nanos, block_timestamp_nanos]`,
+/// gas refunds are already subtracted.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct GasFeesResponse {
+    /// yoctoNEAR
+    pub total_burnt: types::U128,
+    pub payments_count: u64,
+    /// Days with the gas spendings, recent days go first
+    pub daily: Vec<GasFeesBucket>,
+    pub window_start_timestamp_nanos: types::U64,
+    pub meta: types::ResponseMeta,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct PriceHistoryResponse {
+    pub prices: Vec<PricePoint>,
+    pub meta: types::ResponseMeta,
+}
+
+/// The balances at the requested heights, ascending.
+/// If the requested height was skipped, the balance is given at the closest block before it
+/// (so there could be less items than the requested heights).
+/// The heights before the first indexed block are dropped
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct BalancesAtBlocksResponse {
+    pub balances: Vec<BalanceAtBlock>,
+    pub metadata: CoinMetadata,
+}
+
+/// The balance changes of the account between the blocks: NEAR goes first, then FTs ordered by contract_account_id.
+/// Only FTs with the events of the account in the range are listed, the other balances did not change
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct BalancesDiffResponse {
+    pub diffs: Vec<CoinDiff>,
+    pub from_block_timestamp_nanos: types::U64,
+    pub from_block_height: types::U64,
+    pub from_block_hash: String,
+    pub meta: types::ResponseMeta,
+}
+
+/// The coins of all the accounts valued in USD: summed up by coin and per account.
+/// NEAR goes first, then the coins in the order of the first appearance
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct PortfolioResponse {
+    /// The sum of the coins which have the price, decimal string
+    pub total_value_usd: String,
+    pub coins: Vec<PortfolioCoin>,
+    pub accounts: Vec<AccountPortfolio>,
+    pub meta: types::ResponseMeta,
+    /// The contracts which failed to give the balance for any of the accounts, they are not in the valuation
+    #[serde(default)]
+    pub failed_contracts: Vec<types::FailedContract>,
 }
 
 // ---
@@ -81,14 +264,28 @@ pub struct FtContractMetadataResponse {
 pub struct Coin {
     /// "nearprotocol" for NEAR, "nep141" for FT
     pub standard: String,
+    /// In the smallest units, apply `metadata.decimals` to show it
     pub balance: types::U128,
     /// null for NEAR, not null otherwise
     pub contract_account_id: Option<types::AccountId>,
     pub metadata: CoinMetadata,
+    /// Not null for the liquid staking tokens (stNEAR, LiNEAR)
+    #[serde(default)]
+    pub liquid_staking: Option<LiquidStaking>,
     // TODO PHASE 1 (idea) I think it would be great to add here the info about last update moment. Timestamp, later also index
     // I'm already doing it at NftCount
 }
 
+/// The staked NEAR behind the liquid staking token, by the exchange rate of its contract.
+/// The rate grows with the staking rewards
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct LiquidStaking {
+    /// yoctoNEAR for 1 whole token
+    pub near_price: types::U128,
+    /// `balance` in yoctoNEAR
+    pub near_equivalent_balance: types::U128,
+}
+
 /// This type describes the history of coin movements for the given user.
 /// Coins could be NEAR, FT, it could be also later used for Multi Tokens.
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
@@ -97,9 +294,17 @@ pub struct HistoryItem {
     // pub index: types::U128,
     // TODO PHASE 1 (idea) do we want to add here tx_hash/receipt_id? We may want to add it at many places
     pub involved_account_id: Option<types::AccountId>,
+    /// The signer of the transaction the change originated from.
+    /// null if the change was not caused by a receipt, e.g. validator rewards
+    pub signer_account_id: Option<types::AccountId>,
+    /// The account which sent the receipt. Differs from `signer_account_id`
+    /// if the change was made through the contract
+    pub predecessor_account_id: Option<types::AccountId>,
+    /// In the smallest units, apply `coin_metadata.decimals` to show it
     pub delta_balance: types::I128,
+    /// The balance after the change, in the smallest units
     pub balance: types::U128,
-    pub cause: String,
+    pub cause: HistoryCause,
     pub status: String,
     pub coin_metadata: CoinMetadata,
     pub block_timestamp_nanos: types::U64,
@@ -107,13 +312,103 @@ pub struct HistoryItem {
     // pub block_height: types::U64,
 }
 
+/// FT event emitted by the contract. `old_account_id` is empty for mints,
+/// `new_account_id` is empty for burns. `amount` is in the smallest units of the token
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct FtTransfer {
+    pub cause: HistoryCause,
+    pub old_account_id: Option<types::AccountId>,
+    pub new_account_id: Option<types::AccountId>,
+    pub amount: types::U128,
+    pub status: String,
+    pub receipt_id: String,
+    pub block_timestamp_nanos: types::U64,
+    pub block_height: types::U64,
+    pub block_hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct FtHolder {
+    pub account_id: types::AccountId,
+    /// In the smallest units of the token
+    pub balance: types::U128,
+}
+
+/// The normalized reason of the balance change.
+/// Serialized as one of
+/// ["TRANSACTION", "RECEIPT", "TRANSFER", "MINT", "BURN", "GAS", "CONTRACT_REWARD", "VALIDATOR_REWARD"].
+/// Values we do not recognize are passed through as is, so please always have a fallback branch.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum HistoryCause {
+    Transaction,
+    Receipt,
+    Transfer,
+    Mint,
+    Burn,
+    Gas,
+    ContractReward,
+    ValidatorReward,
+    Unknown(String),
+}
+
+// Different versions of the balances DB (and the events tables) name the same things differently,
+// so we accept all the spellings we know about
+impl From<String> for HistoryCause {
+    fn from(raw: String) -> Self {
+        match raw.to_uppercase().as_str() {
+            "TRANSACTION" => Self::Transaction,
+            "RECEIPT" => Self::Receipt,
+            "TRANSFER" => Self::Transfer,
+            "MINT" => Self::Mint,
+            "BURN" => Self::Burn,
+            "GAS" | "GAS_REFUND" => Self::Gas,
+            "CONTRACT_REWARD" => Self::ContractReward,
+            "VALIDATOR_REWARD" | "VALIDATORS_REWARD" => Self::ValidatorReward,
+            _ => Self::Unknown(raw),
+        }
+    }
+}
+
+impl From<HistoryCause> for String {
+    fn from(cause: HistoryCause) -> Self {
+        match cause {
+            HistoryCause::Transaction => "TRANSACTION".to_string(),
+            HistoryCause::Receipt => "RECEIPT".to_string(),
+            HistoryCause::Transfer => "TRANSFER".to_string(),
+            HistoryCause::Mint => "MINT".to_string(),
+            HistoryCause::Burn => "BURN".to_string(),
+            HistoryCause::Gas => "GAS".to_string(),
+            HistoryCause::ContractReward => "CONTRACT_REWARD".to_string(),
+            HistoryCause::ValidatorReward => "VALIDATOR_REWARD".to_string(),
+            HistoryCause::Unknown(raw) => raw,
+        }
+    }
+}
+
+impl paperclip::v2::schema::TypedData for HistoryCause {
+    fn data_type() -> paperclip::v2::models::DataType {
+        paperclip::v2::models::DataType::String
+    }
+}
+
+/// `timestamp_nanos` is the start of the day (UTC)
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct GasFeesBucket {
+    pub timestamp_nanos: types::U64,
+    /// yoctoNEAR
+    pub burnt: types::U128,
+}
+
 /// This type describes general Metadata info, collecting the most important fields from different standards in the one format.
 /// `decimals` may contain `0` if it's not applicable (e.g. if it's general MT metadata)
+/// `icon_url` is absolute for NEAR, for FTs it's the path of `/icons/{contract_account_id}` relative to the API root.
+/// We do not inline the icons here, some of them are hundreds of KB
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
 pub struct CoinMetadata {
     pub name: String,
     pub symbol: String,
-    pub icon: Option<String>,
+    pub icon_url: Option<String>,
     pub decimals: u8,
 }
 
@@ -130,6 +425,326 @@ pub struct FtContractMetadata {
     pub decimals: u8,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct FtContractMetadataItem {
+    pub contract_account_id: types::AccountId,
+    pub metadata: FtContractMetadata,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+#[serde(rename_all = "lowercase")]
+pub enum PriceBucket {
+    Hour,
+    Day,
+    Week,
+}
+
+impl Default for PriceBucket {
+    fn default() -> Self {
+        Self::Hour
+    }
+}
+
+impl PriceBucket {
+    pub(crate) fn duration_nanos(&self) -> u64 {
+        let seconds = match self {
+            Self::Hour => 60 * 60,
+            Self::Day => 24 * 60 * 60,
+            Self::Week => 7 * 24 * 60 * 60,
+        };
+        seconds * 1_000_000_000
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct BalanceAtBlock {
+    /// In the smallest units, apply `metadata.decimals` of the response to show it
+    pub balance: types::U128,
+    pub block_timestamp_nanos: types::U64,
+    pub block_height: types::U64,
+    pub block_hash: String,
+}
+
+/// `delta` is `to_balance - from_balance`, it could be 0 if the tokens came back during the range.
+/// The amounts are in the smallest units, apply `metadata.decimals` to show them
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct CoinDiff {
+    /// "nearprotocol" for NEAR, "nep141" for FT
+    pub standard: String,
+    /// null for NEAR
+    pub contract_account_id: Option<types::AccountId>,
+    pub from_balance: types::U128,
+    pub to_balance: types::U128,
+    pub delta: types::I128,
+    pub metadata: CoinMetadata,
+}
+
+/// Token price in USD during the bucket (we measure it against USD stablecoin at DEX).
+/// Prices are decimal strings, token decimals are already applied.
+/// Buckets without the samples are skipped.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct PricePoint {
+    /// The beginning of the bucket
+    pub timestamp_nanos: types::U64,
+    pub open: String,
+    pub high: String,
+    pub low: String,
+    pub close: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct AccountPortfolio {
+    pub account_id: types::AccountId,
+    /// The sum of the coins of the account which have the price, decimal string
+    pub value_usd: String,
+    pub coins: Vec<PortfolioCoin>,
+}
+
+/// `Coin` with its valuation
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct PortfolioCoin {
+    /// "nearprotocol" for NEAR, "nep141" for FT
+    pub standard: String,
+    /// In the smallest units, apply `metadata.decimals` to show it
+    pub balance: types::U128,
+    /// null for NEAR, not null otherwise
+    pub contract_account_id: Option<types::AccountId>,
+    pub metadata: CoinMetadata,
+    /// The last sampled price of the whole token, decimal string. null if we don't sample the price of the token
+    pub price_usd: Option<String>,
+    /// `balance` with the decimals applied, multiplied by `price_usd` and rounded down to cents
+    pub value_usd: Option<String>,
+}
+
+impl BalancesAtBlocksParams {
+    const MAX_POINTS: u32 = 100;
+    const DEFAULT_POINTS: u32 = 10;
+
+    /// The requested heights, ascending, without duplicates
+    pub(crate) fn block_heights(&self) -> crate::Result<Vec<u64>> {
+        let mut heights = match (&self.block_heights, self.from_block_height, self.to_block_height) {
+            (Some(block_heights), None, None) => {
+                if self.points.is_some() {
+                    return Err(crate::errors::ErrorKind::InvalidInput(
+                        "points could be used only with from_block_height and to_block_height".to_string(),
+                    ).into());
+                }
+                block_heights
+                    .split(',')
+                    .map(|height| height.trim().parse::<u64>().map_err(|_| {
+                        crate::errors::ErrorKind::InvalidInput(format!("Invalid block height {}", height)).into()
+                    }))
+                    .collect::<crate::Result<Vec<u64>>>()?
+            }
+            (None, Some(from), Some(to)) => {
+                if from.0 > to.0 {
+                    return Err(crate::errors::ErrorKind::InvalidInput(
+                        "from_block_height should not be greater than to_block_height".to_string(),
+                    ).into());
+                }
+                let points = self.points.unwrap_or(Self::DEFAULT_POINTS);
+                if points < 2 || points > Self::MAX_POINTS {
+                    return Err(crate::errors::ErrorKind::InvalidInput(format!(
+                        "points should be in range [2, {}]", Self::MAX_POINTS
+                    )).into());
+                }
+                let step = (to.0 - from.0) as u128;
+                (0..points as u128)
+                    .map(|i| from.0 + (step * i / (points as u128 - 1)) as u64)
+                    .collect()
+            }
+            _ => {
+                return Err(crate::errors::ErrorKind::InvalidInput(
+                    "Please provide either block_heights, or both from_block_height and to_block_height".to_string(),
+                ).into())
+            }
+        };
+        heights.sort_unstable();
+        heights.dedup();
+        if heights.len() > Self::MAX_POINTS as usize {
+            return Err(crate::errors::ErrorKind::InvalidInput(format!(
+                "Up to {} block heights are supported", Self::MAX_POINTS
+            )).into());
+        }
+        Ok(heights)
+    }
+}
+
+impl FtContractsMetadataRequest {
+    pub(crate) const MAX_CONTRACTS: usize = 100;
+
+    /// The requested contracts without duplicates, in the order of the request
+    pub(crate) fn contract_account_ids(&self) -> crate::Result<Vec<types::AccountId>> {
+        if self.contract_account_ids.is_empty() || self.contract_account_ids.len() > Self::MAX_CONTRACTS {
+            return Err(crate::errors::ErrorKind::InvalidInput(format!(
+                "contract_account_ids should have from 1 to {} contracts", Self::MAX_CONTRACTS
+            )).into());
+        }
+        let mut contract_account_ids: Vec<types::AccountId> = vec![];
+        for contract_account_id in &self.contract_account_ids {
+            if !contract_account_ids.contains(contract_account_id) {
+                contract_account_ids.push(contract_account_id.clone());
+            }
+        }
+        Ok(contract_account_ids)
+    }
+}
+
+impl PortfolioRequest {
+    pub(crate) const MAX_ACCOUNTS: usize = 25;
+
+    /// The requested accounts without duplicates, in the order of the request
+    pub(crate) fn account_ids(&self) -> crate::Result<Vec<types::AccountId>> {
+        if self.account_ids.is_empty() || self.account_ids.len() > Self::MAX_ACCOUNTS {
+            return Err(crate::errors::ErrorKind::InvalidInput(format!(
+                "account_ids should have from 1 to {} accounts", Self::MAX_ACCOUNTS
+            )).into());
+        }
+        let mut account_ids: Vec<types::AccountId> = vec![];
+        for account_id in &self.account_ids {
+            if !account_ids.contains(account_id) {
+                account_ids.push(account_id.clone());
+            }
+        }
+        Ok(account_ids)
+    }
+}
+
+impl PortfolioCoin {
+    /// `price_usd` is the price of the whole token, `None` if it's unknown
+    pub(crate) fn new(coin: Coin, price_usd: Option<&BigDecimal>) -> crate::Result<Self> {
+        let value_usd = match price_usd {
+            Some(price_usd) => Some(value_usd(coin.balance.0, coin.metadata.decimals, price_usd)?.to_string()),
+            None => None,
+        };
+        Ok(Self {
+            standard: coin.standard,
+            balance: coin.balance,
+            contract_account_id: coin.contract_account_id,
+            metadata: coin.metadata,
+            price_usd: price_usd.map(|price_usd| price_usd.to_string()),
+            value_usd,
+        })
+    }
+}
+
+pub(crate) fn value_usd(balance: u128, decimals: u8, price_usd: &BigDecimal) -> crate::Result<BigDecimal> {
+    use std::str::FromStr;
+
+    let amount = BigDecimal::from_str(&format!("{}E-{}", balance, decimals)).map_err(|err| {
+        crate::errors::ErrorKind::InternalError(format!("Failed to apply the decimals to {}: {}", balance, err))
+    })?;
+    Ok((amount * price_usd).with_scale(2))
+}
+
+/// The sum of `value_usd` of the coins which have it
+pub(crate) fn total_value_usd(coins: &[PortfolioCoin]) -> crate::Result<String> {
+    use std::str::FromStr;
+
+    let mut total = BigDecimal::from(0).with_scale(2);
+    for value_usd in coins.iter().filter_map(|coin| coin.value_usd.as_ref()) {
+        total += BigDecimal::from_str(value_usd).map_err(|err| {
+            crate::errors::ErrorKind::InternalError(format!("Failed to parse the value {}: {}", value_usd, err))
+        })?;
+    }
+    Ok(total.to_string())
+}
+
+/// The same coins of the different accounts are summed up, in the order of the first appearance
+pub(crate) fn merge_coins(coins: impl IntoIterator<Item = Coin>) -> Vec<Coin> {
+    let mut merged: Vec<Coin> = vec![];
+    for coin in coins {
+        match merged.iter_mut().find(|merged| merged.contract_account_id == coin.contract_account_id) {
+            Some(merged) => merged.balance.0 = merged.balance.0.saturating_add(coin.balance.0),
+            None => merged.push(coin),
+        }
+    }
+    merged
+}
+
 pub fn validate(account_id: &str) -> Result<(), ValidationError> {
     Err(ValidationError::new("something"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_balances_at_blocks_heights() {
+        let params = BalancesAtBlocksParams {
+            block_heights: Some("300, 100,200,100".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(params.block_heights().unwrap(), vec![100, 200, 300]);
+
+        let params = BalancesAtBlocksParams {
+            from_block_height: Some(types::U64(1000)),
+            to_block_height: Some(types::U64(2000)),
+            points: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(params.block_heights().unwrap(), vec![1000, 1250, 1500, 1750, 2000]);
+
+        for params in [
+            BalancesAtBlocksParams::default(),
+            BalancesAtBlocksParams { block_heights: Some("1,a".to_string()), ..Default::default() },
+            BalancesAtBlocksParams {
+                from_block_height: Some(types::U64(2000)),
+                to_block_height: Some(types::U64(1000)),
+                ..Default::default()
+            },
+            BalancesAtBlocksParams {
+                from_block_height: Some(types::U64(1000)),
+                to_block_height: Some(types::U64(2000)),
+                points: Some(101),
+                ..Default::default()
+            },
+        ] {
+            assert!(params.block_heights().is_err());
+        }
+    }
+
+    #[test]
+    fn test_portfolio_valuation() {
+        use std::str::FromStr;
+
+        let coin = |contract_account_id: Option<&str>, balance: u128, decimals: u8| Coin {
+            standard: if contract_account_id.is_some() { "nep141" } else { "nearprotocol" }.to_string(),
+            balance: types::U128(balance),
+            contract_account_id: contract_account_id.map(|id| types::AccountId::from_str(id).unwrap()),
+            metadata: CoinMetadata {
+                name: "Token".to_string(),
+                symbol: "TKN".to_string(),
+                icon_url: None,
+                decimals,
+            },
+            liquid_staking: None,
+        };
+        let merged = merge_coins(vec![
+            coin(None, 10u128.pow(24), 24),
+            coin(Some("usn"), 1_500_000, 6),
+            coin(None, 5 * 10u128.pow(23), 24),
+        ]);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].balance.0, 15 * 10u128.pow(23));
+
+        let near = PortfolioCoin::new(merged[0].clone(), Some(&BigDecimal::from_str("2.345").unwrap())).unwrap();
+        assert_eq!(near.value_usd.as_deref(), Some("3.51"));
+        let usn = PortfolioCoin::new(merged[1].clone(), Some(&BigDecimal::from_str("1.00").unwrap())).unwrap();
+        assert_eq!(usn.value_usd.as_deref(), Some("1.50"));
+        let unpriced = PortfolioCoin::new(coin(Some("spam.near"), 100, 0), None).unwrap();
+        assert_eq!(unpriced.value_usd, None);
+        assert_eq!(total_value_usd(&[near, usn, unpriced]).unwrap(), "5.01");
+
+        let request = PortfolioRequest {
+            account_ids: vec![
+                types::AccountId::from_str("alice.near").unwrap(),
+                types::AccountId::from_str("bob.near").unwrap(),
+                types::AccountId::from_str("alice.near").unwrap(),
+            ],
+        };
+        assert_eq!(request.account_ids().unwrap().len(), 2);
+        assert!(PortfolioRequest { account_ids: vec![] }.account_ids().is_err());
+    }
+}
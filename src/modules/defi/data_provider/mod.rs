@@ -0,0 +1,4 @@
+mod models;
+mod ref_finance;
+
+pub(crate) use ref_finance::get_ref_finance_positions;
@@ -0,0 +1,4 @@
+#[derive(sqlx::FromRow)]
+pub(crate) struct PoolId {
+    pub pool_id: Option<String>,
+}
@@ -0,0 +1,236 @@
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::modules::defi;
+use crate::{db_helpers, rpc_helpers, types};
+
+const EXCHANGE_CONTRACT_ID: &str = "v2.ref-finance.near";
+const FARM_CONTRACT_ID: &str = "boostfarm.ref-labs.near";
+
+#[derive(Deserialize, Debug)]
+struct PoolInfo {
+    pool_kind: String,
+    token_account_ids: Vec<near_primitives::types::AccountId>,
+    amounts: Vec<types::U128>,
+    shares_total_supply: types::U128,
+}
+
+#[derive(Deserialize, Debug)]
+struct FarmerSeed {
+    free_amount: types::U128,
+    locked_amount: types::U128,
+}
+
+pub(crate) async fn get_ref_finance_positions(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    rpc_client: &dyn rpc_helpers::RpcApi,
+    block: &db_helpers::Block,
+    account_id: &near_primitives::types::AccountId,
+) -> crate::Result<defi::schemas::RefFinancePositionsResponse> {
+    let farming_shares = get_farming_shares(rpc_client, block, account_id).await?;
+    let pool_ids: Vec<u64> = get_liquidity_pool_ids(pool, account_id, block.timestamp)
+        .await?
+        .into_iter()
+        .chain(farming_shares.keys().copied())
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    // Each pool is the separate view call, so we ask them all at once
+    let positions = futures::future::try_join_all(pool_ids.iter().map(|pool_id| {
+        get_position(
+            rpc_client,
+            block,
+            account_id,
+            *pool_id,
+            farming_shares.get(pool_id).copied().unwrap_or_default(),
+        )
+    }))
+    .await?;
+
+    Ok(defi::schemas::RefFinancePositionsResponse {
+        positions: positions.into_iter().flatten().collect(),
+        meta: types::ResponseMeta::new(&block),
+    })
+}
+
+/// All the pools the account ever added the liquidity to. Some of them could be already withdrawn
+async fn get_liquidity_pool_ids(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    account_id: &near_primitives::types::AccountId,
+    block_timestamp: u64,
+) -> crate::Result<Vec<u64>> {
+    let query = r"
+        SELECT DISTINCT args->'args_json'->>'pool_id' pool_id
+        FROM action_receipt_actions
+        WHERE receipt_predecessor_account_id = $1
+            AND receipt_receiver_account_id = $2
+            AND action_kind = 'FUNCTION_CALL'
+            AND args->>'method_name' IN ('add_liquidity', 'add_stable_liquidity')
+            AND receipt_included_in_block_timestamp <= $3::numeric(20, 0)
+    ";
+    Ok(db_helpers::select_retry_or_panic::<super::models::PoolId>(
+        pool,
+        query,
+        &[
+            account_id.to_string(),
+            EXCHANGE_CONTRACT_ID.to_string(),
+            block_timestamp.to_string(),
+        ],
+    )
+    .await?
+    .into_iter()
+    .filter_map(|pool| pool.pool_id?.parse().ok())
+    .collect())
+}
+
+/// LP shares in the farm by the pool id. The seeds are named `v2.ref-finance.near@<pool_id>`,
+/// the other seeds (e.g. the single tokens) are skipped
+async fn get_farming_shares(
+    rpc_client: &dyn rpc_helpers::RpcApi,
+    block: &db_helpers::Block,
+    account_id: &near_primitives::types::AccountId,
+) -> crate::Result<BTreeMap<u64, u128>> {
+    let farm_id = near_primitives::types::AccountId::from_str(FARM_CONTRACT_ID)?;
+    let request = rpc_helpers::get_function_call_request(
+        block.height,
+        farm_id.clone(),
+        "list_farmer_seeds",
+        serde_json::json!({ "farmer_id": account_id }),
+    );
+    let response = rpc_helpers::wrapped_call(rpc_client, request, block.height, &farm_id).await?;
+    let seeds = serde_json::from_slice::<HashMap<String, FarmerSeed>>(&response.result)?;
+
+    let prefix = format!("{}@", EXCHANGE_CONTRACT_ID);
+    Ok(seeds
+        .into_iter()
+        .filter_map(|(seed_id, seed)| {
+            let pool_id = seed_id.strip_prefix(&prefix)?.parse::<u64>().ok()?;
+            Some((pool_id, seed.free_amount.0 + seed.locked_amount.0))
+        })
+        .filter(|(_, shares)| *shares > 0)
+        .collect())
+}
+
+/// `None` if the account has nothing in the pool
+async fn get_position(
+    rpc_client: &dyn rpc_helpers::RpcApi,
+    block: &db_helpers::Block,
+    account_id: &near_primitives::types::AccountId,
+    pool_id: u64,
+    farming_shares: u128,
+) -> crate::Result<Option<defi::schemas::LiquidityPosition>> {
+    let exchange_id = near_primitives::types::AccountId::from_str(EXCHANGE_CONTRACT_ID)?;
+    let request = rpc_helpers::get_function_call_request(
+        block.height,
+        exchange_id.clone(),
+        "get_pool_shares",
+        serde_json::json!({ "pool_id": pool_id, "account_id": account_id }),
+    );
+    let response =
+        rpc_helpers::wrapped_call(rpc_client, request, block.height, &exchange_id).await?;
+    let shares = serde_json::from_slice::<types::U128>(&response.result)?.0;
+    if shares == 0 && farming_shares == 0 {
+        return Ok(None);
+    }
+
+    let request = rpc_helpers::get_function_call_request(
+        block.height,
+        exchange_id.clone(),
+        "get_pool",
+        serde_json::json!({ "pool_id": pool_id }),
+    );
+    let response =
+        rpc_helpers::wrapped_call(rpc_client, request, block.height, &exchange_id).await?;
+    let pool = serde_json::from_slice::<PoolInfo>(&response.result)?;
+
+    let tokens = pool
+        .token_account_ids
+        .into_iter()
+        .zip(pool.amounts)
+        .map(|(contract_account_id, amount)| {
+            Ok(defi::schemas::UnderlyingToken {
+                contract_account_id: contract_account_id.into(),
+                amount: types::numeric::mul_div(
+                    amount.0,
+                    shares + farming_shares,
+                    pool.shares_total_supply.0,
+                )?
+                .into(),
+            })
+        })
+        .collect::<crate::Result<_>>()?;
+    Ok(Some(defi::schemas::LiquidityPosition {
+        pool_id: pool_id.into(),
+        pool_kind: pool.pool_kind,
+        shares: shares.into(),
+        farming_shares: farming_shares.into(),
+        shares_total_supply: pool.shares_total_supply,
+        tokens,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+
+    #[tokio::test]
+    async fn test_position_mock_rpc() {
+        let (server, rpc_client) = init_mock_rpc().await;
+        mock_function_call(
+            &server,
+            FARM_CONTRACT_ID,
+            "list_farmer_seeds",
+            serde_json::json!({
+                "v2.ref-finance.near@79": {"free_amount": "100", "locked_amount": "50", "x_locked_amount": "0"},
+                "v2.ref-finance.near@80": {"free_amount": "0", "locked_amount": "0", "x_locked_amount": "0"},
+                "meta-pool.near": {"free_amount": "7", "locked_amount": "0", "x_locked_amount": "0"},
+            }),
+        )
+        .await;
+        mock_function_call(
+            &server,
+            EXCHANGE_CONTRACT_ID,
+            "get_pool_shares",
+            serde_json::json!("250"),
+        )
+        .await;
+        mock_function_call(
+            &server,
+            EXCHANGE_CONTRACT_ID,
+            "get_pool",
+            serde_json::json!({
+                "pool_kind": "SIMPLE_POOL",
+                "token_account_ids": ["wrap.near", "usn"],
+                "amounts": ["2000", "30"],
+                "total_fee": 30,
+                "shares_total_supply": "1000",
+                "amp": 0,
+            }),
+        )
+        .await;
+        let account = near_primitives::types::AccountId::from_str("alice.near").unwrap();
+
+        let farming_shares = get_farming_shares(&rpc_client, &get_block(), &account)
+            .await
+            .unwrap();
+        assert_eq!(farming_shares, BTreeMap::from([(79, 150)]));
+
+        let position = get_position(&rpc_client, &get_block(), &account, 79, 150)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(position.shares.0, 250);
+        assert_eq!(position.farming_shares.0, 150);
+        assert_eq!(position.tokens.len(), 2);
+        assert_eq!(
+            position.tokens[0].contract_account_id.to_string(),
+            "wrap.near"
+        );
+        assert_eq!(position.tokens[0].amount.0, 800);
+        assert_eq!(position.tokens[1].amount.0, 12);
+    }
+}
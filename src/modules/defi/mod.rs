@@ -0,0 +1,12 @@
+use paperclip::actix::web;
+
+pub(crate) mod data_provider;
+mod resources;
+pub(crate) mod schemas;
+
+pub(crate) fn register_services(app: &mut web::ServiceConfig) {
+    app.service(
+        web::resource("/accounts/{account_id}/defi/ref")
+            .route(web::get().to(resources::get_ref_finance_positions)),
+    );
+}
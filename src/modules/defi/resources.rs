@@ -0,0 +1,36 @@
+use paperclip::actix::{
+    api_v2_operation,
+    web::{self, Json},
+};
+
+use crate::{data_source, modules, types};
+
+use super::schemas;
+
+#[api_v2_operation(tags(DeFi))]
+/// Get user's Ref Finance liquidity positions
+///
+/// This endpoint returns the Ref Finance pools where the given account_id has the shares,
+/// both held directly and deposited to the farms, for the given timestamp/block_height.
+/// The shares are resolved into the amounts of the underlying tokens, these tokens are not shown in `/coins`.
+///
+/// **Limitations**
+/// * We find the pools by `add_liquidity`, `add_stable_liquidity` calls made by the account itself
+///   and by the seeds of Ref boost farm. The shares got by `mft_transfer` from the others are not included.
+/// * Only the mainnet contracts `v2.ref-finance.near` and `boostfarm.ref-labs.near` are supported.
+/// * The tokens deposited to Ref Finance but not added to the pools are not shown.
+pub async fn get_ref_finance_positions(
+    _query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: web::Path<schemas::RefFinancePositionsRequest>,
+    block_params: web::Query<types::query_params::BlockParams>,
+) -> crate::Result<Json<schemas::RefFinancePositionsResponse>> {
+    let block = data_source.get_block_from_params(&block_params).await?;
+    modules::check_account_exists(&data_source, &request.account_id.0, block.timestamp).await?;
+
+    Ok(Json(
+        data_source
+            .get_ref_finance_positions(&block, &request.account_id.0)
+            .await?,
+    ))
+}
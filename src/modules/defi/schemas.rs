@@ -0,0 +1,42 @@
+use paperclip::actix::Apiv2Schema;
+
+use crate::types;
+
+// *** Requests ***
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct RefFinancePositionsRequest {
+    pub account_id: types::AccountId,
+}
+
+// *** Responses ***
+
+/// Liquidity positions of the account in Ref Finance pools, the smallest pool id goes first
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct RefFinancePositionsResponse {
+    pub positions: Vec<LiquidityPosition>,
+    pub meta: types::ResponseMeta,
+}
+
+// ---
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct LiquidityPosition {
+    pub pool_id: types::U64,
+    /// `SIMPLE_POOL`, `STABLE_SWAP`, `RATED_SWAP`
+    pub pool_kind: String,
+    /// LP shares held by the account
+    pub shares: types::U128,
+    /// LP shares deposited to the farm
+    pub farming_shares: types::U128,
+    pub shares_total_supply: types::U128,
+    /// The part of the pool reserves which belongs to `shares` and `farming_shares` together
+    pub tokens: Vec<UnderlyingToken>,
+}
+
+/// The amount is without the decimals, see `/nep141/metadata/{contract_account_id}`
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct UnderlyingToken {
+    pub contract_account_id: types::AccountId,
+    pub amount: types::U128,
+}
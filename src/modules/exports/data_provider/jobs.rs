@@ -0,0 +1,258 @@
+use std::str::FromStr;
+
+use crate::modules::exports;
+use crate::{db_helpers, errors, types};
+
+const EXPORT_JOB_COLUMNS: &str = r"
+    id, account_id, kind, from_timestamp, to_timestamp, format, status, error,
+    created_at_timestamp, updated_at_timestamp
+";
+
+pub(crate) async fn create_export_job(
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    key_id: &str,
+    account_id: &near_primitives::types::AccountId,
+    input: &exports::schemas::ExportInput,
+    timestamp: u64,
+) -> crate::Result<exports::schemas::ExportJob> {
+    let job_id: i64 = sqlx::query_scalar(
+        r"INSERT INTO export_jobs (key_id, account_id, kind, from_timestamp, to_timestamp, format, status, created_at_timestamp, updated_at_timestamp)
+          VALUES ($1, $2, $3, $4::numeric(20, 0), $5::numeric(20, 0), $6, $7, $8::numeric(20, 0), $8::numeric(20, 0))
+          RETURNING id",
+    )
+    .bind(key_id)
+    .bind(account_id.to_string())
+    .bind(input.kind.as_str())
+    .bind(input.from_timestamp_nanos.map(|timestamp| timestamp.0.to_string()))
+    .bind(input.to_timestamp_nanos.map(|timestamp| timestamp.0.to_string()))
+    .bind(input.format.unwrap_or_default().as_str())
+    .bind(exports::schemas::ExportStatus::Pending.as_str())
+    .bind(timestamp.to_string())
+    .fetch_one(pool_aux)
+    .await
+    .map_err(|err| errors::ErrorKind::DBError(err.to_string()))?;
+
+    Ok(exports::schemas::ExportJob {
+        job_id: types::U64(job_id as u64),
+        account_id: account_id.clone().into(),
+        kind: input.kind,
+        from_timestamp_nanos: input.from_timestamp_nanos,
+        to_timestamp_nanos: input.to_timestamp_nanos,
+        format: input.format.unwrap_or_default(),
+        status: exports::schemas::ExportStatus::Pending,
+        error: None,
+        download_url: None,
+        download_url_expires_at_timestamp_nanos: None,
+        created_at_timestamp_nanos: timestamp.into(),
+        updated_at_timestamp_nanos: timestamp.into(),
+    })
+}
+
+/// `None` if the API key has no such job
+pub(crate) async fn get_export_job(
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    key_id: &str,
+    job_id: u64,
+) -> crate::Result<Option<exports::schemas::ExportJob>> {
+    let query = format!(
+        "SELECT {} FROM export_jobs WHERE key_id = $1 AND id = $2::bigint",
+        EXPORT_JOB_COLUMNS
+    );
+    let jobs = db_helpers::select_retry_or_panic::<super::models::ExportJobInfo>(
+        pool_aux,
+        &query,
+        &[key_id.to_string(), job_id.to_string()],
+    )
+    .await?;
+    jobs.into_iter()
+        .next()
+        .map(|job| job.try_into())
+        .transpose()
+}
+
+/// The jobs of the API key which are not finished yet
+pub(crate) async fn count_active_export_jobs(
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    key_id: &str,
+) -> crate::Result<u64> {
+    let count: i64 = sqlx::query_scalar(
+        "SELECT count(*) FROM export_jobs WHERE key_id = $1 AND status IN ('PENDING', 'RUNNING')",
+    )
+    .bind(key_id)
+    .fetch_one(pool_aux)
+    .await
+    .map_err(|err| errors::ErrorKind::DBError(err.to_string()))?;
+    Ok(count as u64)
+}
+
+/// Marks the oldest pending job as running and returns it.
+/// The running jobs not updated since `stale_before` are taken again, their worker has died.
+/// `SKIP LOCKED` lets several instances take the jobs without the duplicates
+pub(crate) async fn take_next_export_job(
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    timestamp: u64,
+    stale_before: u64,
+) -> crate::Result<Option<exports::schemas::ExportJob>> {
+    let query = format!(
+        r"UPDATE export_jobs SET status = 'RUNNING', updated_at_timestamp = $1::numeric(20, 0)
+          WHERE id = (
+              SELECT id FROM export_jobs
+              WHERE status = 'PENDING' OR (status = 'RUNNING' AND updated_at_timestamp < $2::numeric(20, 0))
+              ORDER BY id
+              LIMIT 1
+              FOR UPDATE SKIP LOCKED
+          )
+          RETURNING {}",
+        EXPORT_JOB_COLUMNS
+    );
+    let job = sqlx::query_as::<_, super::models::ExportJobInfo>(&query)
+        .bind(timestamp.to_string())
+        .bind(stale_before.to_string())
+        .fetch_optional(pool_aux)
+        .await
+        .map_err(|err| errors::ErrorKind::DBError(err.to_string()))?;
+    job.map(|job| job.try_into()).transpose()
+}
+
+/// Shows that the worker of the running job is alive
+pub(crate) async fn touch_export_job(
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    job_id: u64,
+    timestamp: u64,
+) -> crate::Result<()> {
+    sqlx::query(
+        "UPDATE export_jobs SET updated_at_timestamp = $2::numeric(20, 0) WHERE id = $1 AND status = 'RUNNING'",
+    )
+    .bind(job_id as i64)
+    .bind(timestamp.to_string())
+    .execute(pool_aux)
+    .await
+    .map_err(|err| errors::ErrorKind::DBError(err.to_string()))?;
+    Ok(())
+}
+
+/// `SUCCEEDED` if there is no error, `FAILED` otherwise
+pub(crate) async fn finish_export_job(
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    job_id: u64,
+    error: Option<&str>,
+    timestamp: u64,
+) -> crate::Result<()> {
+    let status = match error {
+        Some(_) => exports::schemas::ExportStatus::Failed,
+        None => exports::schemas::ExportStatus::Succeeded,
+    };
+    sqlx::query(
+        r"UPDATE export_jobs SET status = $2, error = $3, updated_at_timestamp = $4::numeric(20, 0)
+          WHERE id = $1",
+    )
+    .bind(job_id as i64)
+    .bind(status.as_str())
+    .bind(error)
+    .bind(timestamp.to_string())
+    .execute(pool_aux)
+    .await
+    .map_err(|err| errors::ErrorKind::DBError(err.to_string()))?;
+    Ok(())
+}
+
+impl TryFrom<super::models::ExportJobInfo> for exports::schemas::ExportJob {
+    type Error = errors::Error;
+
+    fn try_from(info: super::models::ExportJobInfo) -> crate::Result<Self> {
+        Ok(Self {
+            job_id: types::U64(info.id as u64),
+            account_id: near_primitives::types::AccountId::from_str(&info.account_id)?.into(),
+            kind: exports::schemas::ExportKind::from_str(&info.kind)
+                .map_err(errors::ErrorKind::InternalError)?,
+            from_timestamp_nanos: info
+                .from_timestamp
+                .map(|timestamp| types::numeric::to_u64(&timestamp))
+                .transpose()?
+                .map(types::U64),
+            to_timestamp_nanos: info
+                .to_timestamp
+                .map(|timestamp| types::numeric::to_u64(&timestamp))
+                .transpose()?
+                .map(types::U64),
+            format: types::query_params::FileFormat::from_str(&info.format)
+                .map_err(errors::ErrorKind::InternalError)?,
+            status: exports::schemas::ExportStatus::from_str(&info.status)
+                .map_err(errors::ErrorKind::InternalError)?,
+            error: info.error,
+            download_url: None,
+            download_url_expires_at_timestamp_nanos: None,
+            created_at_timestamp_nanos: types::numeric::to_u64(&info.created_at_timestamp)?.into(),
+            updated_at_timestamp_nanos: types::numeric::to_u64(&info.updated_at_timestamp)?.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+
+    #[tokio::test]
+    async fn test_export_jobs_seeded() {
+        let db = init_seeded_db().await;
+        let account_id = near_primitives::types::AccountId::from_str("alice.near").unwrap();
+        let input = exports::schemas::ExportInput {
+            kind: exports::schemas::ExportKind::FullHistory,
+            from_timestamp_nanos: None,
+            to_timestamp_nanos: Some(types::U64(100)),
+            format: Some(types::query_params::FileFormat::Parquet),
+        };
+        let job = create_export_job(&db.pool, "key_a", &account_id, &input, 1)
+            .await
+            .unwrap();
+        assert_eq!(
+            get_export_job(&db.pool, "key_a", job.job_id.0)
+                .await
+                .unwrap(),
+            Some(job.clone())
+        );
+        assert!(get_export_job(&db.pool, "key_b", job.job_id.0)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            count_active_export_jobs(&db.pool, "key_a").await.unwrap(),
+            1
+        );
+
+        let taken = take_next_export_job(&db.pool, 2, 0).await.unwrap().unwrap();
+        assert_eq!(taken.job_id, job.job_id);
+        assert_eq!(taken.status, exports::schemas::ExportStatus::Running);
+        // The running job is not stale yet
+        assert!(take_next_export_job(&db.pool, 3, 1)
+            .await
+            .unwrap()
+            .is_none());
+        // The worker has died
+        let retaken = take_next_export_job(&db.pool, 10, 5)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(retaken.job_id, job.job_id);
+
+        finish_export_job(&db.pool, job.job_id.0, Some("boom"), 11)
+            .await
+            .unwrap();
+        let failed = get_export_job(&db.pool, "key_a", job.job_id.0)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(failed.status, exports::schemas::ExportStatus::Failed);
+        assert_eq!(failed.error.as_deref(), Some("boom"));
+        assert_eq!(failed.updated_at_timestamp_nanos, types::U64(11));
+        assert_eq!(
+            count_active_export_jobs(&db.pool, "key_a").await.unwrap(),
+            0
+        );
+        assert!(take_next_export_job(&db.pool, 100, 100)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
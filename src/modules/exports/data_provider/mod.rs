@@ -0,0 +1,7 @@
+mod jobs;
+mod models;
+
+pub(crate) use jobs::{
+    count_active_export_jobs, create_export_job, finish_export_job, get_export_job,
+    take_next_export_job, touch_export_job,
+};
@@ -0,0 +1,15 @@
+use crate::BigDecimal;
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct ExportJobInfo {
+    pub id: i64,
+    pub account_id: String,
+    pub kind: String,
+    pub from_timestamp: Option<BigDecimal>,
+    pub to_timestamp: Option<BigDecimal>,
+    pub format: String,
+    pub status: String,
+    pub error: Option<String>,
+    pub created_at_timestamp: BigDecimal,
+    pub updated_at_timestamp: BigDecimal,
+}
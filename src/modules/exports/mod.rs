@@ -0,0 +1,55 @@
+use paperclip::actix::web;
+
+use crate::object_storage;
+
+pub(crate) mod data_provider;
+mod resources;
+pub(crate) mod schemas;
+
+pub(crate) fn register_services(
+    app: &mut web::ServiceConfig,
+    storage: &object_storage::ObjectStorage,
+) {
+    app.app_data(web::Data::new(storage.clone()))
+        .service(
+            web::resource("/accounts/{account_id}/exports")
+                .route(web::post().to(resources::create_export)),
+        )
+        .service(web::resource("/exports/{job_id}").route(web::get().to(resources::get_export)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_export_input() {
+        let input = schemas::ExportInput {
+            kind: schemas::ExportKind::TaxReport,
+            from_timestamp_nanos: Some(crate::types::U64(1)),
+            to_timestamp_nanos: Some(crate::types::U64(2)),
+            format: None,
+        };
+        assert!(input.validate().is_ok());
+
+        let mut invalid = input.clone();
+        invalid.to_timestamp_nanos = None;
+        assert!(invalid.validate().is_err());
+        invalid.to_timestamp_nanos = Some(crate::types::U64(1));
+        assert!(invalid.validate().is_err());
+
+        let mut holders = input.clone();
+        holders.kind = schemas::ExportKind::HoldersSnapshot;
+        assert!(holders.validate().is_err());
+        holders.from_timestamp_nanos = None;
+        assert!(holders.validate().is_ok());
+
+        let history = schemas::ExportInput {
+            kind: schemas::ExportKind::FullHistory,
+            from_timestamp_nanos: None,
+            to_timestamp_nanos: None,
+            format: Some(crate::types::query_params::FileFormat::Parquet),
+        };
+        assert_eq!(history.validate().is_ok(), cfg!(feature = "parquet"));
+    }
+}
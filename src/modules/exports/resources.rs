@@ -0,0 +1,90 @@
+use paperclip::actix::{
+    api_v2_operation,
+    web::{self, Json},
+};
+
+use crate::{api_keys, data_source, errors, modules, object_storage, types};
+
+use super::schemas;
+
+/// Every job reads the whole history of the account, the keys should not queue too many of them
+const MAX_ACTIVE_EXPORTS_PER_KEY: u64 = 3;
+
+#[api_v2_operation(tags(Exports))]
+/// Create the export job
+///
+/// This endpoint starts the background export of the account data to CSV,
+/// poll `/exports/{job_id}` until the job has `SUCCEEDED` and download the file by `download_url`.
+/// * `FULL_HISTORY`: NEAR and FT balance changes of the account.
+/// * `HOLDERS_SNAPSHOT`: FT holders of the contract `account_id` at `to_timestamp_nanos`.
+/// * `TAX_REPORT`: NEAR and FT balance changes in the window with their USD value at the daily close price.
+///
+/// Requires the API key in `X-API-Key` header.
+///
+/// **Limitations**
+/// * Up to 3 unfinished jobs per API key.
+/// * Up to 1000000 rows per file, and the history of up to 1000 FT contracts. The bigger exports fail.
+/// * We sample the prices only for the tokens configured by the server operator, the others have empty `value_usd`.
+pub async fn create_export(
+    request: actix_web::HttpRequest,
+    data_source: web::Data<data_source::DataSourceRef>,
+    exports_request: web::Path<schemas::ExportsRequest>,
+    input: web::Json<schemas::ExportInput>,
+) -> crate::Result<Json<schemas::ExportJobResponse>> {
+    let key_id = api_keys::require_key_id(request.headers(), "the exports")?;
+    input.validate()?;
+    let block = data_source
+        .get_block_from_params(&types::query_params::BlockParams {
+            block_timestamp_nanos: input.to_timestamp_nanos,
+            block_height: None,
+        })
+        .await?;
+    modules::check_account_exists(&data_source, &exports_request.account_id.0, block.timestamp)
+        .await?;
+    if data_source.count_active_export_jobs(&key_id).await? >= MAX_ACTIVE_EXPORTS_PER_KEY {
+        return Err(errors::ErrorKind::InvalidInput(format!(
+            "the API key can't have more than {} unfinished exports",
+            MAX_ACTIVE_EXPORTS_PER_KEY
+        ))
+        .into());
+    }
+
+    Ok(Json(schemas::ExportJobResponse {
+        job: data_source
+            .create_export_job(&key_id, &exports_request.account_id.0, &input)
+            .await?,
+    }))
+}
+
+#[api_v2_operation(tags(Exports))]
+/// Get the export job
+///
+/// This endpoint returns the status of the export job of the API key.
+/// When the job has `SUCCEEDED`, `download_url` is the signed link to the CSV file,
+/// every request gives the fresh link.
+/// Requires the API key in `X-API-Key` header.
+pub async fn get_export(
+    request: actix_web::HttpRequest,
+    data_source: web::Data<data_source::DataSourceRef>,
+    storage: web::Data<object_storage::ObjectStorage>,
+    job_request: web::Path<schemas::ExportJobRequest>,
+) -> crate::Result<Json<schemas::ExportJobResponse>> {
+    let key_id = api_keys::require_key_id(request.headers(), "the exports")?;
+    let job_id = job_request.job_id.0;
+    let mut job = data_source
+        .get_export_job(&key_id, job_id)
+        .await?
+        .ok_or_else(|| {
+            errors::Error::from(errors::ErrorKind::InvalidInput(format!(
+                "export {} is not found",
+                job_id
+            )))
+        })?;
+    if job.status == schemas::ExportStatus::Succeeded {
+        let (download_url, expires_at) = storage.download_url(&job.object_key())?;
+        job.download_url = Some(download_url);
+        job.download_url_expires_at_timestamp_nanos = Some(expires_at.into());
+    }
+
+    Ok(Json(schemas::ExportJobResponse { job }))
+}
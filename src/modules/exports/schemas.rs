@@ -0,0 +1,164 @@
+use paperclip::actix::Apiv2Schema;
+
+use crate::{errors, types};
+
+// *** Requests ***
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct ExportsRequest {
+    pub account_id: types::AccountId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct ExportJobRequest {
+    pub job_id: types::U64,
+}
+
+/// The body of create request
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct ExportInput {
+    pub kind: ExportKind,
+    /// Only the changes after this moment are exported. Required for `TAX_REPORT`
+    pub from_timestamp_nanos: Option<types::U64>,
+    /// The snapshot moment, the last block by default. Required for `TAX_REPORT`
+    pub to_timestamp_nanos: Option<types::U64>,
+    /// `csv` by default
+    pub format: Option<types::query_params::FileFormat>,
+}
+
+impl ExportInput {
+    pub(crate) fn validate(&self) -> crate::Result<()> {
+        self.format.unwrap_or_default().check_supported()?;
+        if self.kind == ExportKind::TaxReport
+            && (self.from_timestamp_nanos.is_none() || self.to_timestamp_nanos.is_none())
+        {
+            return Err(errors::ErrorKind::InvalidInput(
+                "TAX_REPORT requires from_timestamp_nanos and to_timestamp_nanos".to_string(),
+            )
+            .into());
+        }
+        if self.kind == ExportKind::HoldersSnapshot && self.from_timestamp_nanos.is_some() {
+            return Err(errors::ErrorKind::InvalidInput(
+                "HOLDERS_SNAPSHOT is taken at to_timestamp_nanos, from_timestamp_nanos is not supported"
+                    .to_string(),
+            )
+            .into());
+        }
+        if let (Some(from), Some(to)) = (self.from_timestamp_nanos, self.to_timestamp_nanos) {
+            if from.0 >= to.0 {
+                return Err(errors::ErrorKind::InvalidInput(
+                    "from_timestamp_nanos should be less than to_timestamp_nanos".to_string(),
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ExportKind {
+    /// NEAR and FT balance changes of the account
+    FullHistory,
+    /// FT holders of the contract `account_id`
+    HoldersSnapshot,
+    /// NEAR and FT balance changes of the account with the USD value at the daily close price
+    TaxReport,
+}
+
+impl ExportKind {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Self::FullHistory => "FULL_HISTORY",
+            Self::HoldersSnapshot => "HOLDERS_SNAPSHOT",
+            Self::TaxReport => "TAX_REPORT",
+        }
+    }
+}
+
+impl std::str::FromStr for ExportKind {
+    type Err = String;
+
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        match kind {
+            "FULL_HISTORY" => Ok(Self::FullHistory),
+            "HOLDERS_SNAPSHOT" => Ok(Self::HoldersSnapshot),
+            "TAX_REPORT" => Ok(Self::TaxReport),
+            _ => Err(format!("unknown export kind {}", kind)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ExportStatus {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl ExportStatus {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "PENDING",
+            Self::Running => "RUNNING",
+            Self::Succeeded => "SUCCEEDED",
+            Self::Failed => "FAILED",
+        }
+    }
+}
+
+impl std::str::FromStr for ExportStatus {
+    type Err = String;
+
+    fn from_str(status: &str) -> Result<Self, Self::Err> {
+        match status {
+            "PENDING" => Ok(Self::Pending),
+            "RUNNING" => Ok(Self::Running),
+            "SUCCEEDED" => Ok(Self::Succeeded),
+            "FAILED" => Ok(Self::Failed),
+            _ => Err(format!("unknown export status {}", status)),
+        }
+    }
+}
+
+// *** Responses ***
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct ExportJobResponse {
+    pub job: ExportJob,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct ExportJob {
+    pub job_id: types::U64,
+    pub account_id: types::AccountId,
+    pub kind: ExportKind,
+    pub from_timestamp_nanos: Option<types::U64>,
+    pub to_timestamp_nanos: Option<types::U64>,
+    pub format: types::query_params::FileFormat,
+    pub status: ExportStatus,
+    /// Why the job has failed
+    pub error: Option<String>,
+    /// The file, set when the job has succeeded. The link is signed and expires,
+    /// get the job again for the fresh one
+    pub download_url: Option<String>,
+    pub download_url_expires_at_timestamp_nanos: Option<types::U64>,
+    pub created_at_timestamp_nanos: types::U64,
+    pub updated_at_timestamp_nanos: types::U64,
+}
+
+impl ExportJob {
+    /// The file name is given to the browsers which download it
+    pub(crate) fn object_key(&self) -> String {
+        format!(
+            "exports/{}/{}_{}.{}",
+            self.job_id.0,
+            self.account_id.0,
+            self.kind.as_str().to_lowercase(),
+            self.format.as_str()
+        )
+    }
+}
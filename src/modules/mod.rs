@@ -1,29 +1,73 @@
-use crate::{db_helpers, errors, types};
+use crate::{data_source, db_helpers, errors, types};
 
+#[cfg(feature = "accounts")]
+pub(crate) mod accounts;
+#[cfg(feature = "admin")]
+pub(crate) mod admin;
+#[cfg(feature = "alerts")]
+pub(crate) mod alerts;
+#[cfg(feature = "coin")]
 pub(crate) mod coin;
+#[cfg(feature = "defi")]
+pub(crate) mod defi;
+#[cfg(feature = "exports")]
+pub(crate) mod exports;
+#[cfg(feature = "nft")]
 pub(crate) mod nft;
+#[cfg(feature = "staking")]
+pub(crate) mod staking;
+#[cfg(feature = "watchlists")]
+pub(crate) mod watchlists;
 
 pub(crate) async fn check_account_exists(
-    pool: &sqlx::Pool<sqlx::Postgres>,
+    data_source: &data_source::DataSourceRef,
     account_id: &near_primitives::types::AccountId,
     block_timestamp: u64,
 ) -> crate::Result<()> {
-    if !db_helpers::does_account_exist(pool, account_id, block_timestamp).await? {
-        Err(errors::ErrorKind::InvalidInput(format!(
-            "account_id {} does not exist at block_timestamp {}",
-            account_id, block_timestamp
-        ))
+    if !data_source
+        .does_account_exist(account_id, block_timestamp)
+        .await?
+    {
+        Err(errors::ErrorKind::AccountNotFound {
+            account_id: account_id.to_string(),
+            block_timestamp,
+        }
         .into())
     } else {
         Ok(())
     }
 }
 
+/// The last code change of the contract after the given block, to warn the users who reviewed
+/// the contract at that block. `None` if the code is still the same
+#[cfg(any(feature = "coin", feature = "nft"))]
+pub(crate) async fn get_upgraded_since(
+    data_source: &data_source::DataSourceRef,
+    contract_id: &near_primitives::types::AccountId,
+    block: &db_helpers::Block,
+) -> crate::Result<Option<types::CodeChange>> {
+    let last_block = data_source.get_last_block().await?;
+    Ok(data_source
+        .get_code_changes(contract_id, &last_block, 1)
+        .await?
+        .into_iter()
+        .next()
+        .filter(|change| change.block_height.0 > block.height))
+}
+
+/// The snapshot block and the pagination of the page. The first page is taken at the last block,
+/// the next pages are taken from the same snapshot, see `HistoryCursor`
+#[cfg(feature = "history")]
 pub(crate) async fn check_and_get_history_pagination_params(
-    pool: &sqlx::Pool<sqlx::Postgres>,
-    pagination_params: types::query_params::HistoryPaginationParams,
-) -> crate::Result<types::query_params::HistoryPagination> {
-    types::query_params::check_limit(pagination_params.limit)?;
+    data_source: &data_source::DataSourceRef,
+    http_request: &actix_web::HttpRequest,
+    mut pagination_params: types::query_params::HistoryPaginationParams,
+) -> crate::Result<(db_helpers::Block, types::query_params::HistoryPagination)> {
+    types::query_params::check_limit(http_request, &mut pagination_params.limit)?;
+    let cursor = match &pagination_params.cursor {
+        Some(cursor) => Some(types::query_params::HistoryCursor::decode(cursor)?),
+        None => None,
+    };
     let pagination = types::query_params::Pagination::from(pagination_params);
     // if pagination_params.after_block_height.is_some() && pagination_params.after_timestamp_nanos.is_some() {
     //     return Err(errors::ErrorKind::InvalidInput(
@@ -32,17 +76,75 @@ pub(crate) async fn check_and_get_history_pagination_params(
     //     )
     //         .into());
     // }
-    // TODO PHASE 2 take the block from pagination_params
-    let block = db_helpers::get_last_block(pool).await?;
-    Ok(types::query_params::HistoryPagination {
-        block_height: block.height,
-        block_timestamp: block.timestamp,
-        limit: pagination.limit,
+    Ok(match cursor {
+        Some(cursor) => (
+            // The cursor keeps the snapshot block of the first page
+            match cursor.block_hash {
+                Some(block_hash) => db_helpers::Block {
+                    timestamp: cursor.block_timestamp,
+                    height: cursor.block_height,
+                    hash: block_hash,
+                },
+                // The old cursors don't have the hash
+                None => {
+                    data_source
+                        .get_block_by_id(&cursor.block_height.to_string())
+                        .await?
+                }
+            },
+            types::query_params::HistoryPagination {
+                block_height: cursor.block_height,
+                block_timestamp: cursor.before_event.block_timestamp + 1,
+                before_event: Some(cursor.before_event),
+                limit: pagination.limit,
+            },
+        ),
+        None => {
+            let block = data_source.get_last_block().await?;
+            let pagination = types::query_params::HistoryPagination {
+                block_height: block.height,
+                block_timestamp: block.timestamp,
+                before_event: None,
+                limit: pagination.limit,
+            };
+            (block, pagination)
+        }
     })
 }
 
+/// `next_cursor` for the page of the history, recent items go first. `None` if it's the last page.
+/// The next page starts strictly before the last item, so the events of one block could be split between the pages
+#[cfg(feature = "history")]
+pub(crate) fn next_history_cursor<T>(
+    items: &[(T, types::query_params::EventIndex)],
+    snapshot: &db_helpers::Block,
+    pagination: &types::query_params::HistoryPagination,
+) -> Option<String> {
+    if items.len() < pagination.limit as usize {
+        return None;
+    }
+    let (_, before_event) = items.last()?;
+    Some(
+        types::query_params::HistoryCursor {
+            block_height: snapshot.height,
+            block_timestamp: snapshot.timestamp,
+            block_hash: Some(snapshot.hash.clone()),
+            before_event: *before_event,
+        }
+        .encode(),
+    )
+}
+
+/// Drops the event indexes after `next_history_cursor`
+#[cfg(feature = "history")]
+pub(crate) fn without_event_indexes<T>(
+    items: Vec<(T, types::query_params::EventIndex)>,
+) -> Vec<T> {
+    items.into_iter().map(|(item, _)| item).collect()
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use crate::db_helpers;
 
     pub(crate) async fn init_db() -> sqlx::Pool<sqlx::Postgres> {
@@ -54,17 +156,206 @@ mod tests {
             .expect("failed to connect to the database")
     }
 
-    pub(crate) fn init_rpc() -> near_jsonrpc_client::JsonRpcClient {
+    /// Disposable Postgres with the indexer schema, `migrations/` and `tests/fixtures/seed.sql` loaded.
+    /// Requires Docker. Keep the value alive until the end of the test, the container is removed on drop
+    pub(crate) struct SeededDb {
+        pub pool: sqlx::Pool<sqlx::Postgres>,
+        _container: testcontainers::Container<'static, testcontainers::images::postgres::Postgres>,
+    }
+
+    impl SeededDb {
+        /// `balance_changes` lives in the same DB
+        pub(crate) fn balances_db(&self) -> crate::balances_schema::BalancesDb {
+            crate::balances_schema::BalancesDb::new(self.pool.clone(), Default::default())
+        }
+    }
+
+    pub(crate) async fn init_seeded_db() -> SeededDb {
+        // The container borrows the client, so the client has to outlive the test
+        let docker: &'static testcontainers::clients::Cli =
+            Box::leak(Box::new(testcontainers::clients::Cli::default()));
+        let container = docker.run(testcontainers::images::postgres::Postgres::default());
+        let db_url = format!(
+            "postgres://postgres@127.0.0.1:{}/postgres",
+            container.get_host_port_ipv4(5432)
+        );
+        let pool = sqlx::PgPool::connect(&db_url)
+            .await
+            .expect("failed to connect to the seeded database");
+        sqlx::Executor::execute(&pool, include_str!("../../tests/fixtures/indexer_schema.sql"))
+            .await
+            .expect("failed to apply the indexer schema");
+        // Our own tables
+        crate::run_migrations(&pool)
+            .await
+            .expect("failed to apply the migrations");
+        sqlx::Executor::execute(&pool, include_str!("../../tests/fixtures/seed.sql"))
+            .await
+            .expect("failed to load the seed data");
+        SeededDb {
+            pool,
+            _container: container,
+        }
+    }
+
+    /// Replays the recorded mainnet responses from `tests/cassettes/rpc.json`.
+    /// `RPC_CASSETTES=record` calls `RPC_URL` instead and records the responses
+    pub(crate) fn init_rpc() -> crate::rpc_cassette::CassetteRpcClient {
         dotenv::dotenv().ok();
-        let rpc_url = &std::env::var("RPC_URL").expect("failed to get RPC url");
-        let connector = near_jsonrpc_client::JsonRpcClient::new_client();
-        connector.connect(rpc_url)
+        let cassette_path = std::path::Path::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/cassettes/rpc.json"
+        ));
+        if std::env::var("RPC_CASSETTES").as_deref() == Ok("record") {
+            let rpc_url = &std::env::var("RPC_URL").expect("failed to get RPC url");
+            let connector = near_jsonrpc_client::JsonRpcClient::new_client();
+            crate::rpc_cassette::CassetteRpcClient::record(
+                connector.connect(rpc_url),
+                cassette_path,
+            )
+        } else {
+            crate::rpc_cassette::CassetteRpcClient::replay(cassette_path)
+        }
+    }
+
+    /// RPC which answers only what you mocked with `mock_function_call`.
+    /// Keep the server alive until the end of the test
+    pub(crate) async fn init_mock_rpc() -> (
+        wiremock::MockServer,
+        near_jsonrpc_client::JsonRpcClient,
+    ) {
+        let server = wiremock::MockServer::start().await;
+        let rpc_client = near_jsonrpc_client::JsonRpcClient::connect(&server.uri());
+        (server, rpc_client)
+    }
+
+    pub(crate) async fn mock_function_call(
+        server: &wiremock::MockServer,
+        contract_id: &str,
+        method_name: &str,
+        result: serde_json::Value,
+    ) {
+        wiremock::Mock::given(wiremock::matchers::method("POST"))
+            .and(wiremock::matchers::body_partial_json(serde_json::json!({
+                "method": "query",
+                "params": {
+                    "request_type": "call_function",
+                    "account_id": contract_id,
+                    "method_name": method_name,
+                }
+            })))
+            .respond_with(
+                wiremock::ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": "dontcare",
+                    "result": {
+                        "result": result.to_string().into_bytes(),
+                        "logs": [],
+                        "block_height": get_block().height,
+                        "block_hash": "11111111111111111111111111111111",
+                    }
+                })),
+            )
+            .mount(server)
+            .await;
+    }
+
+    /// The contract call fails with `vm_error`, e.g. `CodeDoesNotExist` for the account without the contract
+    pub(crate) async fn mock_function_call_error(
+        server: &wiremock::MockServer,
+        contract_id: &str,
+        method_name: &str,
+        vm_error: &str,
+    ) {
+        wiremock::Mock::given(wiremock::matchers::method("POST"))
+            .and(wiremock::matchers::body_partial_json(serde_json::json!({
+                "method": "query",
+                "params": {
+                    "request_type": "call_function",
+                    "account_id": contract_id,
+                    "method_name": method_name,
+                }
+            })))
+            .respond_with(
+                // RPC gives the contract errors in `result`, the client turns them into `ContractExecutionError`
+                wiremock::ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": "dontcare",
+                    "result": {
+                        "error": vm_error,
+                        "logs": [],
+                        "block_height": get_block().height,
+                        "block_hash": "11111111111111111111111111111111",
+                    }
+                })),
+            )
+            .mount(server)
+            .await;
     }
 
     pub(crate) fn get_block() -> db_helpers::Block {
         db_helpers::Block {
             timestamp: 1655571176644255779,
             height: 68000000,
+            hash: "11111111111111111111111111111111".to_string(),
+        }
+    }
+
+    /// The hashes of blocks 100..103 from `tests/fixtures/seed.sql`
+    pub(crate) const SEEDED_BLOCK_HASHES: [&str; 4] = [
+        "9N8SLwByDi25YTLNgsdCzWaV5AsWJEpp4UFD2x8VuMzU",
+        "EpH8jp6BVS6MMeVSjNjV6zX4iRjKGUG7GEoBVR9xafTq",
+        "6mGTj16KMeMHmBiVBgvNQcbzWGoAb2seoXPUyCVMfhBH",
+        "22wm1M3iXDuaiadUgZcysR9pvo6nh46jbtrLThJZCBBi",
+    ];
+
+    pub(crate) fn get_seeded_block_hash(height: u64) -> String {
+        SEEDED_BLOCK_HASHES[(height - 100) as usize].to_string()
+    }
+
+    /// One of the blocks 100..103 from `tests/fixtures/seed.sql`
+    pub(crate) fn get_seeded_block(height: u64) -> db_helpers::Block {
+        db_helpers::Block {
+            timestamp: 1600000000000000000 + height,
+            height,
+            hash: get_seeded_block_hash(height),
         }
     }
+
+    #[cfg(feature = "history")]
+    #[test]
+    fn test_next_history_cursor() {
+        use crate::types::query_params::{EventIndex, HistoryCursor, HistoryPagination};
+
+        let snapshot = get_block();
+        let pagination = HistoryPagination {
+            block_height: snapshot.height,
+            block_timestamp: snapshot.timestamp,
+            before_event: None,
+            limit: 3,
+        };
+        let event = |block_timestamp, index_in_chunk| {
+            (
+                (),
+                EventIndex {
+                    block_timestamp,
+                    shard_id: 0,
+                    index_in_chunk,
+                },
+            )
+        };
+        let next_cursor = |items: &[((), EventIndex)]| {
+            super::next_history_cursor(items, &snapshot, &pagination)
+                .map(|cursor| HistoryCursor::decode(&cursor).unwrap())
+        };
+
+        // Not full page is the last one
+        assert!(next_cursor(&[event(30, 0), event(20, 0)]).is_none());
+
+        // The block could be split between the pages, the next one continues from the last event
+        let cursor = next_cursor(&[event(30, 0), event(10, 5), event(10, 4)]).unwrap();
+        assert_eq!(cursor.before_event, event(10, 4).1);
+        assert_eq!(cursor.block_height, snapshot.height);
+        assert_eq!(cursor.block_hash, Some(snapshot.hash.clone()));
+    }
 }
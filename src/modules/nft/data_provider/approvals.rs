@@ -0,0 +1,140 @@
+use crate::modules::nft;
+use crate::{db_helpers, errors, types};
+
+/// NEP-178 does not define the events, so we look at the calls themselves.
+/// `nft_approve` is usually called by the owner, the other two could be also called by the contract itself
+const APPROVAL_METHODS: &[(&str, &str)] = &[
+    ("nft_approve", "APPROVE"),
+    ("nft_revoke", "REVOKE"),
+    ("nft_revoke_all", "REVOKE_ALL"),
+];
+
+fn get_cause(method_name: &str) -> String {
+    APPROVAL_METHODS
+        .iter()
+        .find(|(method, _)| *method == method_name)
+        .map(|(_, cause)| cause.to_string())
+        .unwrap_or_else(|| method_name.to_uppercase())
+}
+
+pub(crate) async fn get_nft_approvals_history(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    account_id: &near_primitives::types::AccountId,
+    pagination: &types::query_params::HistoryPagination,
+) -> crate::Result<Vec<(nft::schemas::NftApproval, types::query_params::EventIndex)>> {
+    let query = r"
+        SELECT
+            action_receipt_actions.receipt_id,
+            receipt_receiver_account_id contract_account_id,
+            args->>'method_name' method_name,
+            args->'args_json'->>'token_id' token_id,
+            args->'args_json'->>'account_id' approved_account_id,
+            CASE WHEN execution_outcomes.status IN ('SUCCESS_VALUE', 'SUCCESS_RECEIPT_ID') THEN 'SUCCESS'
+                ELSE 'FAILURE'
+            END status,
+            receipt_included_in_block_timestamp block_timestamp_nanos,
+            execution_outcomes.shard_id,
+            execution_outcomes.index_in_chunk::numeric(20, 0)
+        FROM action_receipt_actions
+            JOIN execution_outcomes ON action_receipt_actions.receipt_id = execution_outcomes.receipt_id
+        WHERE receipt_predecessor_account_id = $1
+            AND action_kind = 'FUNCTION_CALL'
+            AND args->>'method_name' = ANY($2::text[])
+            AND receipt_included_in_block_timestamp < $3::numeric(20, 0)
+            AND (receipt_included_in_block_timestamp, execution_outcomes.shard_id, execution_outcomes.index_in_chunk)
+                < ($4::numeric(20, 0), $5::numeric(20, 0), $6::numeric(20, 0))
+            AND receipt_included_in_block_timestamp <= $4::numeric(20, 0)
+        ORDER BY receipt_included_in_block_timestamp DESC, execution_outcomes.shard_id DESC, execution_outcomes.index_in_chunk DESC
+        LIMIT $7::numeric(20, 0)
+    ";
+    let methods: Vec<&str> = APPROVAL_METHODS.iter().map(|(method, _)| *method).collect();
+    let bound = pagination.event_bound();
+    let approvals = db_helpers::select_retry_or_panic::<super::models::NftApprovalInfo>(
+        pool,
+        query,
+        &[
+            account_id.to_string(),
+            format!("{{{}}}", methods.join(",")),
+            pagination.block_timestamp.to_string(),
+            bound.block_timestamp.to_string(),
+            bound.shard_id.to_string(),
+            bound.index_in_chunk.to_string(),
+            pagination.limit.to_string(),
+        ],
+    )
+    .await?;
+
+    let mut result = vec![];
+    for approval in approvals {
+        let event_index = types::query_params::EventIndex::from_db(
+            &approval.block_timestamp_nanos,
+            &approval.shard_id,
+            &approval.index_in_chunk,
+        )?;
+        result.push((approval.try_into()?, event_index));
+    }
+    Ok(result)
+}
+
+impl TryFrom<super::models::NftApprovalInfo> for nft::schemas::NftApproval {
+    type Error = errors::Error;
+
+    fn try_from(info: super::models::NftApprovalInfo) -> crate::Result<Self> {
+        Ok(Self {
+            cause: get_cause(&info.method_name),
+            contract_account_id: info.contract_account_id.parse()?,
+            token_id: info.token_id.unwrap_or_default(),
+            approved_account_id: match info.approved_account_id {
+                Some(account_id) => types::account_id::extract_account_id(&account_id)?
+                    .map(|account_id| account_id.into()),
+                None => None,
+            },
+            status: info.status,
+            receipt_id: info.receipt_id,
+            block_timestamp_nanos: types::numeric::to_u64(&info.block_timestamp_nanos)?.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_cause() {
+        assert_eq!(get_cause("nft_approve"), "APPROVE");
+        assert_eq!(get_cause("nft_revoke_all"), "REVOKE_ALL");
+    }
+
+    #[tokio::test]
+    async fn test_nft_approvals_history_seeded() {
+        let db = init_seeded_db().await;
+        let account = near_primitives::types::AccountId::from_str("bob.near").unwrap();
+        let pagination = types::query_params::HistoryPagination {
+            block_height: 103,
+            block_timestamp: 1600000000000000104,
+            before_event: None,
+            limit: 10,
+        };
+
+        let history = get_nft_approvals_history(&db.pool, &account, &pagination)
+            .await
+            .unwrap();
+        let history = crate::modules::without_event_indexes(history);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].cause, "REVOKE");
+        assert_eq!(history[0].status, "SUCCESS");
+        assert_eq!(history[1].cause, "APPROVE");
+        assert_eq!(history[1].token_id, "1");
+        assert_eq!(
+            history[1].approved_account_id,
+            Some(
+                near_primitives::types::AccountId::from_str("marketplace.paras.near")
+                    .unwrap()
+                    .into()
+            )
+        );
+    }
+}
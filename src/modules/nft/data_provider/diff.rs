@@ -0,0 +1,160 @@
+use std::str::FromStr;
+
+use crate::modules::nft;
+use crate::{db_helpers, errors};
+
+pub struct NftHoldingsDiff {
+    pub gained: Vec<nft::schemas::NftHoldingChange>,
+    pub lost: Vec<nft::schemas::NftHoldingChange>,
+}
+
+/// The tokens the account owned at one of the moments but not at the other one, ordered by contract and token_id.
+/// The owner at the moment is the new owner of the last successful event of the token,
+/// so the token which left and came back in the range is not listed
+pub(crate) async fn get_nft_holdings_diff(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    account_id: &near_primitives::types::AccountId,
+    from_block_timestamp: u64,
+    to_block_timestamp: u64,
+    limit: u32,
+) -> crate::Result<NftHoldingsDiff> {
+    let query = r"
+        WITH successful_events AS (
+            SELECT
+                assets__non_fungible_token_events.*
+            FROM assets__non_fungible_token_events
+                JOIN execution_outcomes ON assets__non_fungible_token_events.emitted_for_receipt_id = execution_outcomes.receipt_id
+            WHERE execution_outcomes.status IN ('SUCCESS_VALUE', 'SUCCESS_RECEIPT_ID')
+        ), tokens AS (
+            SELECT DISTINCT emitted_by_contract_account_id contract_id, token_id
+            FROM successful_events
+            WHERE (token_old_owner_account_id = $1 OR token_new_owner_account_id = $1)
+                AND emitted_at_block_timestamp > $2::numeric(20, 0)
+                AND emitted_at_block_timestamp <= $3::numeric(20, 0)
+        ), owners AS (
+            SELECT
+                tokens.contract_id,
+                tokens.token_id,
+                coalesce((
+                    SELECT token_new_owner_account_id
+                    FROM successful_events
+                    WHERE emitted_by_contract_account_id = tokens.contract_id
+                        AND successful_events.token_id = tokens.token_id
+                        AND emitted_at_block_timestamp <= $2::numeric(20, 0)
+                    ORDER BY emitted_at_block_timestamp DESC, emitted_in_shard_id DESC, emitted_index_of_event_entry_in_shard DESC
+                    LIMIT 1
+                ), '') from_owner_id,
+                coalesce((
+                    SELECT token_new_owner_account_id
+                    FROM successful_events
+                    WHERE emitted_by_contract_account_id = tokens.contract_id
+                        AND successful_events.token_id = tokens.token_id
+                        AND emitted_at_block_timestamp <= $3::numeric(20, 0)
+                    ORDER BY emitted_at_block_timestamp DESC, emitted_in_shard_id DESC, emitted_index_of_event_entry_in_shard DESC
+                    LIMIT 1
+                ), '') to_owner_id
+            FROM tokens
+        )
+        SELECT contract_id, token_id, to_owner_id = $1 gained
+        FROM owners
+        WHERE (from_owner_id = $1) != (to_owner_id = $1)
+        ORDER BY contract_id, token_id
+        LIMIT $4::numeric(20, 0)
+    ";
+    let changes = db_helpers::select_retry_or_panic::<super::models::NftHoldingChangeInfo>(
+        pool,
+        query,
+        &[
+            account_id.to_string(),
+            from_block_timestamp.to_string(),
+            to_block_timestamp.to_string(),
+            limit.to_string(),
+        ],
+    )
+    .await?;
+
+    let mut diff = NftHoldingsDiff {
+        gained: vec![],
+        lost: vec![],
+    };
+    for change in changes {
+        let gained = change.gained;
+        let change: nft::schemas::NftHoldingChange = change.try_into()?;
+        if gained {
+            diff.gained.push(change);
+        } else {
+            diff.lost.push(change);
+        }
+    }
+    Ok(diff)
+}
+
+impl TryFrom<super::models::NftHoldingChangeInfo> for nft::schemas::NftHoldingChange {
+    type Error = errors::Error;
+
+    fn try_from(info: super::models::NftHoldingChangeInfo) -> crate::Result<Self> {
+        Ok(Self {
+            contract_account_id: near_primitives::types::AccountId::from_str(&info.contract_id)?
+                .into(),
+            token_id: info.token_id,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+
+    #[tokio::test]
+    async fn test_nft_holdings_diff_seeded() {
+        let db = init_seeded_db().await;
+        let alice = near_primitives::types::AccountId::from_str("alice.near").unwrap();
+        let bob = near_primitives::types::AccountId::from_str("bob.near").unwrap();
+
+        // Minted to alice.near at 101 and transferred to bob.near at 102
+        let diff = get_nft_holdings_diff(
+            &db.pool,
+            &alice,
+            1600000000000000100,
+            1600000000000000101,
+            10,
+        )
+        .await
+        .unwrap();
+        assert_eq!(diff.gained.len(), 1);
+        assert_eq!(diff.gained[0].token_id, "1");
+        assert!(diff.lost.is_empty());
+
+        // The token came and left during the range
+        let diff = get_nft_holdings_diff(
+            &db.pool,
+            &alice,
+            1600000000000000100,
+            1600000000000000103,
+            10,
+        )
+        .await
+        .unwrap();
+        assert!(diff.gained.is_empty() && diff.lost.is_empty());
+
+        let diff = get_nft_holdings_diff(
+            &db.pool,
+            &alice,
+            1600000000000000101,
+            1600000000000000103,
+            10,
+        )
+        .await
+        .unwrap();
+        assert_eq!(diff.lost.len(), 1);
+
+        // The failed transfer back at 103 does not count
+        let diff =
+            get_nft_holdings_diff(&db.pool, &bob, 1600000000000000100, 1600000000000000103, 10)
+                .await
+                .unwrap();
+        assert_eq!(diff.gained.len(), 1);
+        assert!(diff.lost.is_empty());
+    }
+}
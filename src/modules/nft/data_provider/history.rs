@@ -1,13 +1,12 @@
 use crate::modules::nft;
 use crate::{db_helpers, errors, types};
 
-// TODO PHASE 2 pagination by artificial index added to assets__non_fungible_token_events
 pub(crate) async fn get_nft_history(
     pool: &sqlx::Pool<sqlx::Postgres>,
     contract_id: &near_primitives::types::AccountId,
     token_id: &str,
     pagination: &types::query_params::HistoryPagination,
-) -> crate::Result<Vec<nft::schemas::HistoryItem>> {
+) -> crate::Result<Vec<(nft::schemas::HistoryItem, types::query_params::EventIndex)>> {
     let query = r"
         SELECT
             event_kind::text cause,
@@ -17,16 +16,23 @@ pub(crate) async fn get_nft_history(
             token_old_owner_account_id old_account_id,
             token_new_owner_account_id new_account_id,
             emitted_at_block_timestamp block_timestamp_nanos,
-            block_height
+            block_height,
+            block_hash,
+            emitted_in_shard_id shard_id,
+            emitted_index_of_event_entry_in_shard::numeric(20, 0) index_in_chunk
         FROM assets__non_fungible_token_events
             JOIN blocks ON assets__non_fungible_token_events.emitted_at_block_timestamp = blocks.block_timestamp
             JOIN execution_outcomes ON assets__non_fungible_token_events.emitted_for_receipt_id = execution_outcomes.receipt_id
         WHERE token_id = $1
             AND emitted_by_contract_account_id = $2
             AND emitted_at_block_timestamp < $3::numeric(20, 0)
-        ORDER BY emitted_at_block_timestamp DESC
-        LIMIT $4::numeric(20, 0)
+            AND (emitted_at_block_timestamp, emitted_in_shard_id, emitted_index_of_event_entry_in_shard)
+                < ($4::numeric(20, 0), $5::numeric(20, 0), $6::numeric(20, 0))
+            AND emitted_at_block_timestamp <= $4::numeric(20, 0)
+        ORDER BY emitted_at_block_timestamp DESC, emitted_in_shard_id DESC, emitted_index_of_event_entry_in_shard DESC
+        LIMIT $7::numeric(20, 0)
     ";
+    let bound = pagination.event_bound();
     let history_items = db_helpers::select_retry_or_panic::<super::models::NftHistoryInfo>(
         pool,
         query,
@@ -34,14 +40,22 @@ pub(crate) async fn get_nft_history(
             token_id.to_string(),
             contract_id.to_string(),
             pagination.block_timestamp.to_string(),
+            bound.block_timestamp.to_string(),
+            bound.shard_id.to_string(),
+            bound.index_in_chunk.to_string(),
             pagination.limit.to_string(),
         ],
     )
     .await?;
 
-    let mut result: Vec<nft::schemas::HistoryItem> = vec![];
+    let mut result = vec![];
     for history in history_items {
-        result.push(history.try_into()?);
+        let event_index = types::query_params::EventIndex::from_db(
+            &history.block_timestamp_nanos,
+            &history.shard_id,
+            &history.index_in_chunk,
+        )?;
+        result.push((history.try_into()?, event_index));
     }
     Ok(result)
 }
@@ -59,6 +73,7 @@ impl TryFrom<super::models::NftHistoryInfo> for nft::schemas::HistoryItem {
                 .map(|account| account.into()),
             block_timestamp_nanos: types::numeric::to_u64(&info.block_timestamp_nanos)?.into(),
             block_height: types::numeric::to_u64(&info.block_height)?.into(),
+            block_hash: info.block_hash,
         })
     }
 }
@@ -69,6 +84,17 @@ mod tests {
     use crate::modules::tests::*;
     use std::str::FromStr;
 
+    /// The live DB snapshots keep the heights only, the hashes are checked in `test_nft_history_seeded`
+    fn without_block_hashes(
+        mut history: Vec<nft::schemas::HistoryItem>,
+    ) -> Vec<nft::schemas::HistoryItem> {
+        for item in &mut history {
+            assert!(!item.block_hash.is_empty());
+            item.block_hash.clear();
+        }
+        history
+    }
+
     #[tokio::test]
     async fn test_nft_history() {
         let pool = init_db().await;
@@ -78,10 +104,14 @@ mod tests {
         let pagination = types::query_params::HistoryPagination {
             block_height: block.height,
             block_timestamp: block.timestamp,
+            before_event: None,
             limit: 10,
         };
 
-        let history = get_nft_history(&pool, &contract, token, &pagination).await;
+        let history = get_nft_history(&pool, &contract, token, &pagination)
+            .await
+            .map(crate::modules::without_event_indexes)
+            .map(without_block_hashes);
         insta::assert_debug_snapshot!(history);
     }
 
@@ -94,10 +124,14 @@ mod tests {
         let pagination = types::query_params::HistoryPagination {
             block_height: block.height,
             block_timestamp: block.timestamp,
+            before_event: None,
             limit: 10,
         };
 
-        let history = get_nft_history(&pool, &contract, token, &pagination).await;
+        let history = get_nft_history(&pool, &contract, token, &pagination)
+            .await
+            .map(crate::modules::without_event_indexes)
+            .map(without_block_hashes);
         insta::assert_debug_snapshot!(history);
     }
 
@@ -110,6 +144,7 @@ mod tests {
         let pagination = types::query_params::HistoryPagination {
             block_height: block.height,
             block_timestamp: block.timestamp,
+            before_event: None,
             limit: 10,
         };
 
@@ -118,4 +153,52 @@ mod tests {
             .unwrap();
         assert!(history.is_empty());
     }
+
+    #[tokio::test]
+    async fn test_nft_history_seeded() {
+        let db = init_seeded_db().await;
+        let contract = near_primitives::types::AccountId::from_str("nft.near").unwrap();
+        let pagination = types::query_params::HistoryPagination {
+            block_height: 104,
+            block_timestamp: 1600000000000000104,
+            before_event: None,
+            limit: 10,
+        };
+
+        let history = get_nft_history(&db.pool, &contract, "1", &pagination)
+            .await
+            .map(crate::modules::without_event_indexes)
+            .unwrap();
+        let events: Vec<(&str, &str, u64)> = history
+            .iter()
+            .map(|item| (item.cause.as_str(), item.status.as_str(), item.block_height.0))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                ("TRANSFER", "FAILURE", 103),
+                ("TRANSFER", "SUCCESS", 102),
+                ("MINT", "SUCCESS", 101),
+            ]
+        );
+        assert_eq!(history[0].block_hash, get_seeded_block_hash(103));
+        assert_eq!(history[2].block_hash, get_seeded_block_hash(101));
+        assert_eq!(history[2].old_account_id, None);
+        assert_eq!(
+            history[1].new_account_id,
+            Some(near_primitives::types::AccountId::from_str("bob.near").unwrap().into())
+        );
+
+        // The given timestamp is not included
+        let pagination = types::query_params::HistoryPagination {
+            block_height: 102,
+            block_timestamp: 1600000000000000102,
+            before_event: None,
+            limit: 10,
+        };
+        let history = get_nft_history(&db.pool, &contract, "1", &pagination)
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 1);
+    }
 }
@@ -1,50 +1,142 @@
 use crate::modules::nft;
-use crate::{errors, rpc_helpers, types};
+use crate::{cache, errors, rpc_helpers, types};
 use borsh::{BorshDeserialize, BorshSerialize};
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 
+// The metadata rarely changes, the old one is better than nothing when the contract is slow
+const STALE_METADATA_TTL: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
+/// The bulk requests take the cached metadata if it's not older than this
+const BULK_METADATA_TTL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+/// The contracts of the bulk request are asked in parallel, but not all at once
+const MAX_CONCURRENT_METADATA_QUERIES: usize = 10;
+
+/// The last known metadata of NFT contracts
+pub(crate) type NftMetadataCache =
+    cache::TtlCache<near_primitives::types::AccountId, nft::schemas::NftContractMetadata>;
+
+/// `None` if the contract implements NEP-171 without the metadata extension (NEP-177):
+/// it does not have `nft_metadata`, or the method panics (e.g. `nft.nearapps.near`).
+/// The contracts without both are not NFT contracts, it's `ContractNotFound`
 pub(crate) async fn get_nft_contract_metadata(
-    rpc_client: &near_jsonrpc_client::JsonRpcClient,
+    rpc_client: &dyn rpc_helpers::RpcApi,
     contract_id: near_primitives::types::AccountId,
     block_height: u64,
-) -> crate::Result<nft::schemas::NftContractMetadata> {
+) -> crate::Result<Option<nft::schemas::NftContractMetadata>> {
     let request = rpc_helpers::get_function_call_request(
         block_height,
         contract_id.clone(),
         "nft_metadata",
         serde_json::json!({}),
     );
-    let response = match rpc_helpers::wrapped_call(rpc_client, request, block_height, &contract_id)
-        .await
-    {
-        Ok(response) => response,
-        Err(err) => {
-            println!("{}", err.message);
-            if err
-                .message
-                .contains("called `Option::unwrap()` on a `None` value")
+    let response =
+        match rpc_helpers::wrapped_optional_call(rpc_client, request, block_height, &contract_id)
+            .await
+        {
+            Ok(Some(response)) => response,
+            Ok(None) => {
+                check_nft_core(rpc_client, &contract_id, block_height).await?;
+                return Ok(None);
+            }
+            Err(err)
+                if err
+                    .raw_message()
+                    .contains("called `Option::unwrap()` on a `None` value") =>
             {
-                return Err(errors::ErrorKind::ContractError(
-                    "The contract did not provide NFT Metadata which is a required part of NFT NEP 171".to_string(),
-                )
-                    .into());
+                return Ok(None)
             }
-            return Err(err);
-        }
-    };
+            Err(err) => return Err(err),
+        };
 
     nft::schemas::NftContractMetadata::try_from(serde_json::from_slice::<NFTContractMetadata>(
         &response.result,
     )?)
+    .map(Some)
+}
+
+/// `nft_token` is the required method of NEP-171, we only check that the contract has it
+async fn check_nft_core(
+    rpc_client: &dyn rpc_helpers::RpcApi,
+    contract_id: &near_primitives::types::AccountId,
+    block_height: u64,
+) -> crate::Result<()> {
+    let request = rpc_helpers::get_function_call_request(
+        block_height,
+        contract_id.clone(),
+        "nft_token",
+        serde_json::json!({ "token_id": "" }),
+    );
+    match rpc_helpers::wrapped_optional_call(rpc_client, request, block_height, contract_id).await {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(errors::ErrorKind::ContractNotFound {
+            contract_id: contract_id.to_string(),
+            block_height,
+        }
+        .into()),
+        // Some contracts panic on the unknown token_id, the method is there anyway
+        Err(err) if err.raw_message().contains("ContractExecutionError") => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+/// The latest metadata of the contracts, in the given order. The cached values are up to 1 hour old,
+/// the rest is asked at `block_height`. One broken contract does not fail the others.
+/// The contracts without the metadata extension are asked every time, the RPC client remembers them
+pub(crate) async fn get_nft_contracts_metadata(
+    rpc_client: &dyn rpc_helpers::RpcApi,
+    cache: &NftMetadataCache,
+    contract_ids: &[near_primitives::types::AccountId],
+    block_height: u64,
+) -> (
+    Vec<nft::schemas::NftContractMetadataItem>,
+    Vec<types::FailedContract>,
+) {
+    let results: Vec<_> = futures::stream::iter(contract_ids)
+        .map(|contract_id| async move {
+            if let Some(metadata) = cache.get(contract_id, BULK_METADATA_TTL) {
+                return (contract_id, Ok(Some(metadata)));
+            }
+            let metadata =
+                get_nft_contract_metadata(rpc_client, contract_id.clone(), block_height).await;
+            if let Ok(Some(metadata)) = &metadata {
+                cache.insert(contract_id.clone(), metadata.clone());
+            }
+            (contract_id, metadata)
+        })
+        .buffered(MAX_CONCURRENT_METADATA_QUERIES)
+        .collect()
+        .await;
+
+    let mut items: Vec<nft::schemas::NftContractMetadataItem> = vec![];
+    let mut failed_contracts: Vec<types::FailedContract> = vec![];
+    for (contract_id, metadata) in results {
+        match metadata {
+            Ok(metadata) => items.push(nft::schemas::NftContractMetadataItem {
+                contract_account_id: contract_id.clone().into(),
+                standards_missing: nft::schemas::standards_missing(metadata.as_ref()),
+                metadata,
+            }),
+            Err(error) => failed_contracts.push(types::FailedContract::new(contract_id, error)),
+        }
+    }
+    (items, failed_contracts)
+}
+
+pub(crate) fn get_cached_nft_contract_metadata(
+    cache: &NftMetadataCache,
+    contract_id: &near_primitives::types::AccountId,
+) -> Option<nft::schemas::NftContractMetadata> {
+    cache.get(contract_id, STALE_METADATA_TTL)
 }
 
-// Metadata is the required part of the standard.
-// Unfortunately, some contracts (e.g. `nft.nearapps.near`) do not implement it.
+// Metadata is the optional extension of the standard, some contracts (e.g. `nft.nearapps.near`) do not implement it.
 // We should give at least anything for such contracts when we serve the overview information.
-pub(crate) fn get_default_nft_contract_metadata() -> nft::schemas::NftContractMetadata {
+pub(crate) fn get_default_nft_contract_metadata(
+    contract_id: &near_primitives::types::AccountId,
+) -> nft::schemas::NftContractMetadata {
     nft::schemas::NftContractMetadata {
         spec: "nft-1.0.0".to_string(),
-        name: "The contract did not provide the metadata".to_string(),
+        name: contract_id.to_string(),
         symbol: "The contract did not provide the symbol".to_string(),
         icon: None,
         base_uri: None,
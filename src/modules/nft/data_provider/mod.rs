@@ -1,8 +1,32 @@
+#[cfg(feature = "history")]
+mod approvals;
+mod diff;
+#[cfg(feature = "history")]
 mod history;
 mod metadata;
 mod models;
 mod nft_info;
+mod owner_counts;
+#[cfg(feature = "history")]
+mod provenance;
+mod sales;
+mod spam;
 
+#[cfg(feature = "history")]
+pub(crate) use approvals::get_nft_approvals_history;
+pub(crate) use diff::get_nft_holdings_diff;
+pub use diff::NftHoldingsDiff;
+#[cfg(feature = "history")]
 pub(crate) use history::get_nft_history;
-pub(crate) use metadata::get_nft_contract_metadata;
+pub(crate) use metadata::{
+    get_default_nft_contract_metadata, get_nft_contract_metadata, get_nft_contracts_metadata,
+    NftMetadataCache,
+};
 pub(crate) use nft_info::{get_nft, get_nfts_by_contract, get_nfts_count};
+pub(crate) use owner_counts::get_nfts_count_from_owner_counts;
+#[cfg(feature = "history")]
+pub(crate) use provenance::get_nft_provenance;
+#[cfg(feature = "history")]
+pub(crate) use sales::get_nft_sales;
+pub(crate) use sales::get_nft_market_stats;
+pub(crate) use spam::mark_spam_collections;
@@ -1,14 +1,17 @@
 use crate::BigDecimal;
 
+#[cfg(feature = "history")]
 #[derive(sqlx::FromRow)]
 pub(crate) struct NftHistoryInfo {
-    // pub index: super::types::U128,
     pub cause: String,
     pub status: String,
     pub old_account_id: String,
     pub new_account_id: String,
     pub block_timestamp_nanos: BigDecimal,
     pub block_height: BigDecimal,
+    pub block_hash: String,
+    pub shard_id: BigDecimal,
+    pub index_in_chunk: BigDecimal,
 }
 
 #[derive(sqlx::FromRow)]
@@ -17,3 +20,44 @@ pub(crate) struct NftCount {
     pub count: i64,
     pub last_updated_at_timestamp: BigDecimal,
 }
+
+#[cfg(feature = "history")]
+#[derive(sqlx::FromRow)]
+pub(crate) struct NftSaleInfo {
+    pub receipt_id: String,
+    pub marketplace_account_id: String,
+    pub buyer_account_id: String,
+    pub price: BigDecimal,
+    pub block_timestamp_nanos: BigDecimal,
+    pub shard_id: BigDecimal,
+    pub index_in_chunk: BigDecimal,
+}
+
+#[cfg(feature = "history")]
+#[derive(sqlx::FromRow)]
+pub(crate) struct NftApprovalInfo {
+    pub receipt_id: String,
+    pub contract_account_id: String,
+    pub method_name: String,
+    pub token_id: Option<String>,
+    pub approved_account_id: Option<String>,
+    pub status: String,
+    pub block_timestamp_nanos: BigDecimal,
+    pub shard_id: BigDecimal,
+    pub index_in_chunk: BigDecimal,
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct NftMarketStatsInfo {
+    pub sales_count: i64,
+    pub volume: BigDecimal,
+    pub floor_price: Option<BigDecimal>,
+    pub average_price: Option<BigDecimal>,
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct NftHoldingChangeInfo {
+    pub contract_id: String,
+    pub token_id: String,
+    pub gained: bool,
+}
@@ -1,18 +1,25 @@
 use std::str::FromStr;
 
+use futures::{StreamExt, TryStreamExt};
+
 use crate::modules::nft;
 use crate::{db_helpers, errors, rpc_helpers, types};
 use borsh::{BorshDeserialize, BorshSerialize};
 use serde::{Deserialize, Serialize};
 
 // TODO PHASE 2 pagination by artificial index added to assets__non_fungible_token_events
+// One slow contract should not hold the whole overview
+const METADATA_REQUESTS_CONCURRENCY: usize = 16;
+const METADATA_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
 pub(crate) async fn get_nfts_count(
     pool: &sqlx::Pool<sqlx::Postgres>,
-    rpc_client: &near_jsonrpc_client::JsonRpcClient,
+    rpc_client: &dyn rpc_helpers::RpcApi,
+    metadata_cache: &super::NftMetadataCache,
     block: &db_helpers::Block,
     account_id: &near_primitives::types::AccountId,
     pagination_params: types::query_params::PaginationParams,
-) -> crate::Result<Vec<nft::schemas::NftCount>> {
+) -> crate::Result<(Vec<nft::schemas::NftCount>, Vec<types::FailedContract>)> {
     let query = r"
         WITH relevant_events AS (
             SELECT emitted_at_block_timestamp, token_id, emitted_by_contract_account_id, token_old_owner_account_id, token_new_owner_account_id
@@ -67,33 +74,122 @@ pub(crate) async fn get_nfts_count(
         ],
     )
     .await?;
+    add_nft_contract_metadata(rpc_client, metadata_cache, block, info_by_contract).await
+}
+
+/// The metadata of all the contracts, asked in parallel
+pub(super) async fn add_nft_contract_metadata(
+    rpc_client: &dyn rpc_helpers::RpcApi,
+    metadata_cache: &super::NftMetadataCache,
+    block: &db_helpers::Block,
+    info_by_contract: Vec<super::models::NftCount>,
+) -> crate::Result<(Vec<nft::schemas::NftCount>, Vec<types::FailedContract>)> {
+    let counts: Vec<(nft::schemas::NftCount, Option<types::FailedContract>)> =
+        futures::stream::iter(info_by_contract.into_iter().filter_map(|info| {
+            near_primitives::types::AccountId::from_str(&info.contract_id)
+                .ok()
+                .map(|contract_id| {
+                    get_nft_count_with_metadata(
+                        rpc_client,
+                        metadata_cache,
+                        block,
+                        contract_id,
+                        info,
+                        METADATA_TIMEOUT,
+                    )
+                })
+        }))
+        .buffered(METADATA_REQUESTS_CONCURRENCY)
+        .try_collect()
+        .await?;
 
     let mut result: Vec<nft::schemas::NftCount> = vec![];
-    for info in info_by_contract {
-        if let Ok(contract_id) = near_primitives::types::AccountId::from_str(&info.contract_id) {
-            let metadata = super::metadata::get_nft_contract_metadata(
-                rpc_client,
-                contract_id.clone(),
-                block.height,
+    let mut failed_contracts: Vec<types::FailedContract> = vec![];
+    for (count, failed_contract) in counts {
+        result.push(count);
+        failed_contracts.extend(failed_contract);
+    }
+    Ok((result, failed_contracts))
+}
+
+/// The count is known from the DB, so the entry is kept with the default metadata if the contract fails
+/// or does not implement the metadata extension.
+/// If the contract does not answer in `timeout`, the last known metadata is given with `stale: true`
+async fn get_nft_count_with_metadata(
+    rpc_client: &dyn rpc_helpers::RpcApi,
+    metadata_cache: &super::NftMetadataCache,
+    block: &db_helpers::Block,
+    contract_id: near_primitives::types::AccountId,
+    info: super::models::NftCount,
+    timeout: std::time::Duration,
+) -> crate::Result<(nft::schemas::NftCount, Option<types::FailedContract>)> {
+    let metadata = tokio::time::timeout(
+        timeout,
+        super::metadata::get_nft_contract_metadata(rpc_client, contract_id.clone(), block.height),
+    )
+    .await;
+    let mut standards_missing = vec![];
+    let (metadata, stale, error) = match metadata {
+        Ok(Ok(Some(metadata))) => {
+            metadata_cache.insert(contract_id.clone(), metadata.clone());
+            (metadata, false, None)
+        }
+        Ok(Ok(None)) => {
+            standards_missing = nft::schemas::standards_missing(None);
+            (
+                super::metadata::get_default_nft_contract_metadata(&contract_id),
+                false,
+                None,
             )
-            .await
-            .unwrap_or_else(|_| super::metadata::get_default_nft_contract_metadata());
-            result.push(nft::schemas::NftCount {
-                contract_account_id: contract_id.into(),
-                nft_count: info.count as u32,
-                last_updated_at_timestamp_nanos: types::numeric::to_u128(
-                    &info.last_updated_at_timestamp,
-                )?
-                .into(),
-                contract_metadata: metadata,
-            });
         }
-    }
-    Ok(result)
+        // The contracts without the metadata are not the failures
+        Ok(Err(error)) if error.error_code == "CONTRACT_NOT_FOUND" => (
+            super::metadata::get_default_nft_contract_metadata(&contract_id),
+            false,
+            None,
+        ),
+        Ok(Err(error)) => (
+            super::metadata::get_default_nft_contract_metadata(&contract_id),
+            false,
+            Some(error),
+        ),
+        Err(_) => {
+            match super::metadata::get_cached_nft_contract_metadata(metadata_cache, &contract_id) {
+                Some(metadata) => (metadata, true, None),
+                None => (
+                    super::metadata::get_default_nft_contract_metadata(&contract_id),
+                    true,
+                    Some(
+                        errors::ErrorKind::RPCError(format!(
+                            "NFT metadata was not given in {:?}",
+                            timeout
+                        ))
+                        .into(),
+                    ),
+                ),
+            }
+        }
+    };
+
+    Ok((
+        nft::schemas::NftCount {
+            contract_account_id: contract_id.clone().into(),
+            nft_count: info.count as u32,
+            last_updated_at_timestamp_nanos: types::numeric::to_u64(
+                &info.last_updated_at_timestamp,
+            )?
+            .into(),
+            contract_metadata: metadata,
+            standards_missing,
+            stale,
+            is_spam: false,
+        },
+        error.map(|error| types::FailedContract::new(&contract_id, error)),
+    ))
 }
 
 pub(crate) async fn get_nfts_by_contract(
-    rpc_client: &near_jsonrpc_client::JsonRpcClient,
+    rpc_client: &dyn rpc_helpers::RpcApi,
     contract_id: near_primitives::types::AccountId,
     account_id: near_primitives::types::AccountId,
     block_height: u64,
@@ -124,7 +220,7 @@ pub(crate) async fn get_nfts_by_contract(
 }
 
 pub(crate) async fn get_nft(
-    rpc_client: &near_jsonrpc_client::JsonRpcClient,
+    rpc_client: &dyn rpc_helpers::RpcApi,
     contract_id: near_primitives::types::AccountId,
     token_id: String,
     block_height: u64,
@@ -223,7 +319,16 @@ mod tests {
         let account = near_primitives::types::AccountId::from_str("blondjesus.near").unwrap();
         let pagination = types::query_params::PaginationParams { limit: Some(10) };
 
-        let nft_count = get_nfts_count(&pool, &rpc_client, &block, &account, pagination).await;
+        let nft_count = get_nfts_count(
+            &pool,
+            &rpc_client,
+            &Default::default(),
+            &block,
+            &account,
+            pagination,
+        )
+        .await
+        .map(|(nft_count, _)| nft_count);
         insta::assert_debug_snapshot!(nft_count);
     }
 
@@ -235,10 +340,168 @@ mod tests {
         let account = near_primitives::types::AccountId::from_str("frol.near").unwrap();
         let pagination = types::query_params::PaginationParams { limit: None };
 
-        let nft_count = get_nfts_count(&pool, &rpc_client, &block, &account, pagination)
-            .await
-            .unwrap();
+        let (nft_count, failed_contracts) = get_nfts_count(
+            &pool,
+            &rpc_client,
+            &Default::default(),
+            &block,
+            &account,
+            pagination,
+        )
+        .await
+        .unwrap();
         assert!(nft_count.is_empty());
+        assert!(failed_contracts.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_nfts_count_failed_contract_seeded() {
+        let db = init_seeded_db().await;
+        // Nothing is mocked, so the metadata call to nft.near fails
+        let (_server, rpc_client) = init_mock_rpc().await;
+        let block = get_seeded_block(101);
+        let account = near_primitives::types::AccountId::from_str("alice.near").unwrap();
+        let pagination = types::query_params::PaginationParams { limit: None };
+
+        let (nft_count, failed_contracts) = get_nfts_count(
+            &db.pool,
+            &rpc_client,
+            &Default::default(),
+            &block,
+            &account,
+            pagination,
+        )
+        .await
+        .unwrap();
+        assert_eq!(nft_count.len(), 1);
+        assert_eq!(nft_count[0].nft_count, 1);
+        assert_eq!(failed_contracts.len(), 1);
+        assert_eq!(
+            failed_contracts[0].contract_account_id.to_string(),
+            "nft.near"
+        );
+        assert_eq!(failed_contracts[0].error_code, "RPC_ERROR");
+    }
+
+    /// `nft.near` answers, `gone.near` has no contract anymore, `broken.near` fails
+    async fn get_mixed_nft_counts() -> (Vec<nft::schemas::NftCount>, Vec<types::FailedContract>) {
+        let (server, rpc_client) = init_mock_rpc().await;
+        mock_function_call(
+            &server,
+            "nft.near",
+            "nft_metadata",
+            serde_json::json!({"spec": "nft-1.0.0", "name": "NFT", "symbol": "NFT"}),
+        )
+        .await;
+        mock_function_call_error(
+            &server,
+            "gone.near",
+            "nft_metadata",
+            "wasm execution failed with error: CompilationError(CodeDoesNotExist)",
+        )
+        .await;
+        let block = get_block();
+        let info_by_contract = ["nft.near", "gone.near", "broken.near"]
+            .into_iter()
+            .map(|contract_id| super::super::models::NftCount {
+                contract_id: contract_id.to_string(),
+                count: 1,
+                last_updated_at_timestamp: block.timestamp.into(),
+            })
+            .collect();
+        add_nft_contract_metadata(&rpc_client, &Default::default(), &block, info_by_contract)
+            .await
+            .unwrap()
+    }
+
+    fn contract_ids<T>(items: &[T], contract_id: impl Fn(&T) -> String) -> Vec<String> {
+        items.iter().map(contract_id).collect()
+    }
+
+    #[tokio::test]
+    async fn test_nfts_count_failed_contracts_by_default() {
+        let (nft_counts, failed_contracts) = get_mixed_nft_counts().await;
+        // The contract without the code is not the failure, all the counts are kept
+        assert_eq!(
+            contract_ids(&nft_counts, |count| count.contract_account_id.to_string()),
+            ["nft.near", "gone.near", "broken.near"]
+        );
+        assert_eq!(nft_counts[0].contract_metadata.name, "NFT");
+        assert_eq!(
+            contract_ids(&failed_contracts, |failed| failed
+                .contract_account_id
+                .to_string()),
+            ["broken.near"]
+        );
+        assert_eq!(failed_contracts[0].error_code, "RPC_ERROR");
+    }
+
+    #[tokio::test]
+    async fn test_nfts_count_skip_errors() {
+        let (mut nft_counts, mut failed_contracts) = get_mixed_nft_counts().await;
+        nft::skip_failed_contracts(&mut nft_counts, &mut failed_contracts);
+        assert_eq!(
+            contract_ids(&nft_counts, |count| count.contract_account_id.to_string()),
+            ["nft.near", "gone.near"]
+        );
+        assert!(failed_contracts.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_nft_count_metadata_timeout() {
+        let (server, rpc_client) = init_mock_rpc().await;
+        wiremock::Mock::given(wiremock::matchers::method("POST"))
+            .respond_with(
+                wiremock::ResponseTemplate::new(500).set_delay(std::time::Duration::from_secs(5)),
+            )
+            .mount(&server)
+            .await;
+        let block = get_block();
+        let contract = near_primitives::types::AccountId::from_str("nft.near").unwrap();
+        let info = || super::super::models::NftCount {
+            contract_id: contract.to_string(),
+            count: 3,
+            last_updated_at_timestamp: block.timestamp.into(),
+        };
+        let timeout = std::time::Duration::from_millis(100);
+        let metadata_cache = super::super::NftMetadataCache::default();
+
+        // Nothing is cached, the default metadata is given
+        let (count, failed_contract) = get_nft_count_with_metadata(
+            &rpc_client,
+            &metadata_cache,
+            &block,
+            contract.clone(),
+            info(),
+            timeout,
+        )
+        .await
+        .unwrap();
+        assert!(count.stale);
+        assert_eq!(count.nft_count, 3);
+        assert_eq!(
+            count.contract_metadata,
+            super::super::metadata::get_default_nft_contract_metadata(&contract)
+        );
+        assert_eq!(failed_contract.unwrap().error_code, "RPC_ERROR");
+
+        let mut cached_metadata =
+            super::super::metadata::get_default_nft_contract_metadata(&contract);
+        cached_metadata.name = "Cached".to_string();
+        metadata_cache.insert(contract.clone(), cached_metadata.clone());
+        let (count, failed_contract) = get_nft_count_with_metadata(
+            &rpc_client,
+            &metadata_cache,
+            &block,
+            contract,
+            info(),
+            timeout,
+        )
+        .await
+        .unwrap();
+        assert!(count.stale);
+        assert_eq!(count.contract_metadata, cached_metadata);
+        assert!(failed_contract.is_none());
     }
 
     #[tokio::test]
@@ -249,7 +512,16 @@ mod tests {
         let account = near_primitives::types::AccountId::from_str("vlad.near").unwrap();
         let pagination = types::query_params::PaginationParams { limit: Some(10) };
 
-        let nft_count = get_nfts_count(&pool, &rpc_client, &block, &account, pagination).await;
+        let nft_count = get_nfts_count(
+            &pool,
+            &rpc_client,
+            &Default::default(),
+            &block,
+            &account,
+            pagination,
+        )
+        .await
+        .map(|(nft_count, _)| nft_count);
         insta::assert_debug_snapshot!(nft_count);
     }
 
@@ -261,7 +533,16 @@ mod tests {
         let account = near_primitives::types::AccountId::from_str("kbneoburner3.near").unwrap();
         let pagination = types::query_params::PaginationParams { limit: None };
 
-        let nft_count = get_nfts_count(&pool, &rpc_client, &block, &account, pagination).await;
+        let nft_count = get_nfts_count(
+            &pool,
+            &rpc_client,
+            &Default::default(),
+            &block,
+            &account,
+            pagination,
+        )
+        .await
+        .map(|(nft_count, _)| nft_count);
         insta::assert_debug_snapshot!(nft_count);
     }
 
@@ -0,0 +1,173 @@
+use std::str::FromStr;
+
+use futures::{StreamExt, TryStreamExt};
+
+use crate::modules::nft;
+use crate::{db_helpers, rpc_helpers, types};
+
+const SUPPLY_REQUESTS_CONCURRENCY: usize = 16;
+
+/// The fast path of `get_nfts_count` for the last block: the counts kept by the built-in indexer
+/// in `nft_owner_counts`. The contracts the indexer has not seen yet are asked with `nft_supply_for_owner`
+pub(crate) async fn get_nfts_count_from_owner_counts(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    rpc_client: &dyn rpc_helpers::RpcApi,
+    metadata_cache: &super::NftMetadataCache,
+    block: &db_helpers::Block,
+    account_id: &near_primitives::types::AccountId,
+    pagination_params: types::query_params::PaginationParams,
+) -> crate::Result<(Vec<nft::schemas::NftCount>, Vec<types::FailedContract>)> {
+    let pagination = types::query_params::Pagination::from(pagination_params);
+    let mut info_by_contract = db_helpers::select_retry_or_panic::<super::models::NftCount>(
+        pool,
+        r"
+        SELECT emitted_by_contract_account_id contract_id,
+            nft_count count,
+            last_updated_at_block_timestamp last_updated_at_timestamp
+        FROM nft_owner_counts
+        WHERE owner_account_id = $1 AND nft_count > 0
+        ORDER BY last_updated_at_block_timestamp DESC
+        LIMIT $2::numeric(20, 0)
+    ",
+        &[account_id.to_string(), pagination.limit.to_string()],
+    )
+    .await?;
+
+    let not_indexed_contracts = db_helpers::select_retry_or_panic::<super::models::NftCount>(
+        pool,
+        r"
+        SELECT emitted_by_contract_account_id contract_id,
+            0::bigint count,
+            max(emitted_at_block_timestamp) last_updated_at_timestamp
+        FROM assets__non_fungible_token_events
+        WHERE token_new_owner_account_id = $1
+            AND emitted_at_block_timestamp <= $2::numeric(20, 0)
+            AND emitted_by_contract_account_id NOT IN (SELECT contract_account_id FROM nft_indexed_contracts)
+        GROUP BY emitted_by_contract_account_id
+    ",
+        &[account_id.to_string(), block.timestamp.to_string()],
+    )
+    .await?;
+    let supplies: Vec<(super::models::NftCount, crate::Result<u128>)> =
+        futures::stream::iter(not_indexed_contracts.into_iter().map(|info| async move {
+            let supply = match near_primitives::types::AccountId::from_str(&info.contract_id) {
+                Ok(contract_id) => {
+                    get_nft_supply_for_owner(rpc_client, contract_id, account_id, block.height)
+                        .await
+                }
+                Err(err) => Err(err.into()),
+            };
+            Ok::<_, crate::errors::Error>((info, supply))
+        }))
+        .buffered(SUPPLY_REQUESTS_CONCURRENCY)
+        .try_collect()
+        .await?;
+
+    let mut failed_contracts: Vec<types::FailedContract> = vec![];
+    for (mut info, supply) in supplies {
+        match supply {
+            Ok(supply) if supply > 0 => {
+                info.count = i64::try_from(supply).unwrap_or(i64::MAX);
+                info_by_contract.push(info);
+            }
+            Ok(_) => {}
+            Err(error) => {
+                if let Ok(contract_id) =
+                    near_primitives::types::AccountId::from_str(&info.contract_id)
+                {
+                    failed_contracts.push(types::FailedContract::new(&contract_id, error));
+                }
+            }
+        }
+    }
+    info_by_contract.sort_by(|a, b| {
+        b.last_updated_at_timestamp
+            .cmp(&a.last_updated_at_timestamp)
+    });
+    info_by_contract.truncate(pagination.limit as usize);
+
+    let (counts, mut metadata_failed_contracts) = super::nft_info::add_nft_contract_metadata(
+        rpc_client,
+        metadata_cache,
+        block,
+        info_by_contract,
+    )
+    .await?;
+    failed_contracts.append(&mut metadata_failed_contracts);
+    Ok((counts, failed_contracts))
+}
+
+async fn get_nft_supply_for_owner(
+    rpc_client: &dyn rpc_helpers::RpcApi,
+    contract_id: near_primitives::types::AccountId,
+    account_id: &near_primitives::types::AccountId,
+    block_height: u64,
+) -> crate::Result<u128> {
+    let request = rpc_helpers::get_function_call_request(
+        block_height,
+        contract_id.clone(),
+        "nft_supply_for_owner",
+        serde_json::json!({ "account_id": account_id }),
+    );
+    let response =
+        rpc_helpers::wrapped_call(rpc_client, request, block_height, &contract_id).await?;
+    Ok(serde_json::from_slice::<types::U128>(&response.result)?.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+
+    #[tokio::test]
+    async fn test_nfts_count_from_owner_counts_seeded() {
+        let db = init_seeded_db().await;
+        // What the built-in indexer would write for nft.near.
+        // legacy.near has the events written before the indexer started
+        sqlx::Executor::execute(
+            &db.pool,
+            r"
+            INSERT INTO nft_indexed_contracts VALUES ('nft.near', 1600000000000000101);
+            INSERT INTO nft_owner_counts VALUES
+                ('nft.near', 'alice.near', 0, 1600000000000000102),
+                ('nft.near', 'bob.near', 1, 1600000000000000102);
+            INSERT INTO assets__non_fungible_token_events VALUES
+                ('receipt_legacy_mint', 1600000000000000100, 0, 0, 'legacy.near', 'a', 'MINT', '', 'bob.near', '', '');
+        ",
+        )
+        .await
+        .unwrap();
+        let (server, rpc_client) = init_mock_rpc().await;
+        mock_function_call(
+            &server,
+            "legacy.near",
+            "nft_supply_for_owner",
+            serde_json::json!("2"),
+        )
+        .await;
+        let block = get_seeded_block(103);
+        let bob = near_primitives::types::AccountId::from_str("bob.near").unwrap();
+        let pagination = types::query_params::PaginationParams { limit: None };
+
+        let (counts, failed_contracts) = get_nfts_count_from_owner_counts(
+            &db.pool,
+            &rpc_client,
+            &Default::default(),
+            &block,
+            &bob,
+            pagination,
+        )
+        .await
+        .unwrap();
+        let counts: Vec<_> = counts
+            .iter()
+            .map(|count| (count.contract_account_id.to_string(), count.nft_count))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![("nft.near".to_string(), 1), ("legacy.near".to_string(), 2)]
+        );
+        // The metadata is not mocked
+        assert_eq!(failed_contracts.len(), 2);
+    }
+}
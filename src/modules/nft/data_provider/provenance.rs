@@ -0,0 +1,149 @@
+use crate::modules::nft;
+use crate::{db_helpers, types};
+
+// Should be enough for any real token. If not, the oldest owners are shown
+const MAX_PROVENANCE_ITEMS: u32 = 1000;
+
+pub(crate) async fn get_nft_provenance(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    contract_id: &near_primitives::types::AccountId,
+    token_id: &str,
+    block: &db_helpers::Block,
+) -> crate::Result<Vec<nft::schemas::ProvenanceItem>> {
+    let query = r"
+        SELECT
+            event_kind::text cause,
+            'SUCCESS' status,
+            token_old_owner_account_id old_account_id,
+            token_new_owner_account_id new_account_id,
+            emitted_at_block_timestamp block_timestamp_nanos,
+            block_height,
+            block_hash,
+            emitted_in_shard_id shard_id,
+            emitted_index_of_event_entry_in_shard::numeric(20, 0) index_in_chunk
+        FROM assets__non_fungible_token_events
+            JOIN blocks ON assets__non_fungible_token_events.emitted_at_block_timestamp = blocks.block_timestamp
+            JOIN execution_outcomes ON assets__non_fungible_token_events.emitted_for_receipt_id = execution_outcomes.receipt_id
+        WHERE token_id = $1
+            AND emitted_by_contract_account_id = $2
+            AND emitted_at_block_timestamp <= $3::numeric(20, 0)
+            AND execution_outcomes.status IN ('SUCCESS_VALUE', 'SUCCESS_RECEIPT_ID')
+        ORDER BY emitted_at_block_timestamp, emitted_in_shard_id, emitted_index_of_event_entry_in_shard
+        LIMIT $4::numeric(20, 0)
+    ";
+    let events = db_helpers::select_retry_or_panic::<super::models::NftHistoryInfo>(
+        pool,
+        query,
+        &[
+            token_id.to_string(),
+            contract_id.to_string(),
+            block.timestamp.to_string(),
+            MAX_PROVENANCE_ITEMS.to_string(),
+        ],
+    )
+    .await?;
+    let mut history: Vec<nft::schemas::HistoryItem> = vec![];
+    for event in events {
+        history.push(event.try_into()?);
+    }
+
+    let sales = super::sales::get_nft_sales(
+        pool,
+        contract_id,
+        token_id,
+        &types::query_params::HistoryPagination {
+            block_height: block.height,
+            // `get_nft_sales` excludes the given moment
+            block_timestamp: block.timestamp + 1,
+            before_event: None,
+            limit: MAX_PROVENANCE_ITEMS,
+        },
+    )
+    .await
+    .map(crate::modules::without_event_indexes)?;
+
+    Ok(attach_sales(history, &sales))
+}
+
+/// The marketplace `buy` receipt goes before the transfer event, so the sale belongs to the transfer
+/// to the buyer which happened after the sale, but not later than the next ownership change
+fn attach_sales(
+    history: Vec<nft::schemas::HistoryItem>,
+    sales: &[nft::schemas::NftSale],
+) -> Vec<nft::schemas::ProvenanceItem> {
+    let mut result: Vec<nft::schemas::ProvenanceItem> = vec![];
+    let mut previous_timestamp = 0;
+    for item in history {
+        let timestamp = item.block_timestamp_nanos.0;
+        let sale = item.new_account_id.as_ref().and_then(|owner| {
+            sales.iter().find(|sale| {
+                &sale.buyer_account_id == owner
+                    && sale.block_timestamp_nanos.0 >= previous_timestamp
+                    && sale.block_timestamp_nanos.0 <= timestamp
+            })
+        });
+        previous_timestamp = timestamp;
+        result.push(nft::schemas::ProvenanceItem {
+            cause: item.cause,
+            from_account_id: item.old_account_id,
+            owner_account_id: item.new_account_id,
+            price: sale.map(|sale| sale.price.clone()),
+            marketplace: sale.map(|sale| sale.marketplace.clone()),
+            block_timestamp_nanos: item.block_timestamp_nanos,
+            block_height: item.block_height,
+            block_hash: item.block_hash,
+        });
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_nft_provenance_seeded() {
+        let db = init_seeded_db().await;
+        let contract = near_primitives::types::AccountId::from_str("nft.near").unwrap();
+        let block = get_seeded_block(103);
+
+        let provenance = get_nft_provenance(&db.pool, &contract, "1", &block)
+            .await
+            .unwrap();
+        // The failed transfer at 103 is not the part of the ownership chain
+        assert_eq!(provenance.len(), 2);
+        assert_eq!(provenance[0].cause, "MINT");
+        assert_eq!(
+            provenance[0].owner_account_id.as_ref().unwrap().to_string(),
+            "alice.near"
+        );
+        assert_eq!(provenance[0].price, None);
+        assert_eq!(provenance[1].cause, "TRANSFER");
+        assert_eq!(
+            provenance[1].from_account_id.as_ref().unwrap().to_string(),
+            "alice.near"
+        );
+        assert_eq!(
+            provenance[1].owner_account_id.as_ref().unwrap().to_string(),
+            "bob.near"
+        );
+        assert_eq!(provenance[1].price, Some(types::U128(5 * 10u128.pow(24))));
+        assert_eq!(provenance[1].marketplace.as_deref(), Some("paras"));
+        assert_eq!(provenance[1].block_hash, get_seeded_block_hash(102));
+    }
+
+    #[tokio::test]
+    async fn test_nft_provenance_at_past_block() {
+        let db = init_seeded_db().await;
+        let contract = near_primitives::types::AccountId::from_str("nft.near").unwrap();
+        let block = get_seeded_block(101);
+
+        let provenance = get_nft_provenance(&db.pool, &contract, "1", &block)
+            .await
+            .unwrap();
+        assert_eq!(provenance.len(), 1);
+        assert_eq!(provenance[0].cause, "MINT");
+    }
+}
@@ -0,0 +1,221 @@
+use crate::modules::nft;
+use crate::{db_helpers, errors, types};
+
+/// Marketplaces we know how to parse. All of them sell via `buy` call with
+/// `nft_contract_id`, `token_id` in the args, the attached deposit is the price in yoctoNEAR
+const MARKETPLACES: &[(&str, &str)] = &[
+    ("paras", "marketplace.paras.near"),
+    ("mintbase", "simple.market.mintbase1.near"),
+    ("fewandfar", "market.fewandfar.near"),
+];
+
+fn marketplace_accounts_sql() -> String {
+    MARKETPLACES
+        .iter()
+        .map(|(_, account_id)| format!("'{}'", account_id))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn get_marketplace_name(account_id: &str) -> String {
+    MARKETPLACES
+        .iter()
+        .find(|(_, marketplace_account_id)| *marketplace_account_id == account_id)
+        .map(|(name, _)| name.to_string())
+        .unwrap_or_else(|| account_id.to_string())
+}
+
+#[cfg(feature = "history")]
+pub(crate) async fn get_nft_sales(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    contract_id: &near_primitives::types::AccountId,
+    token_id: &str,
+    pagination: &types::query_params::HistoryPagination,
+) -> crate::Result<Vec<(nft::schemas::NftSale, types::query_params::EventIndex)>> {
+    let query = format!(
+        r"
+        SELECT
+            action_receipt_actions.receipt_id,
+            receipt_receiver_account_id marketplace_account_id,
+            receipt_predecessor_account_id buyer_account_id,
+            (args->>'deposit')::numeric(45, 0) price,
+            receipt_included_in_block_timestamp block_timestamp_nanos,
+            execution_outcomes.shard_id,
+            execution_outcomes.index_in_chunk::numeric(20, 0)
+        FROM action_receipt_actions
+            JOIN execution_outcomes ON action_receipt_actions.receipt_id = execution_outcomes.receipt_id
+        WHERE receipt_receiver_account_id IN ({})
+            AND action_kind = 'FUNCTION_CALL'
+            AND args->>'method_name' = 'buy'
+            AND args->'args_json'->>'nft_contract_id' = $1
+            AND args->'args_json'->>'token_id' = $2
+            AND execution_outcomes.status IN ('SUCCESS_VALUE', 'SUCCESS_RECEIPT_ID')
+            AND receipt_included_in_block_timestamp < $3::numeric(20, 0)
+            AND (receipt_included_in_block_timestamp, execution_outcomes.shard_id, execution_outcomes.index_in_chunk)
+                < ($4::numeric(20, 0), $5::numeric(20, 0), $6::numeric(20, 0))
+            AND receipt_included_in_block_timestamp <= $4::numeric(20, 0)
+        ORDER BY receipt_included_in_block_timestamp DESC, execution_outcomes.shard_id DESC, execution_outcomes.index_in_chunk DESC
+        LIMIT $7::numeric(20, 0)
+    ",
+        marketplace_accounts_sql()
+    );
+    let bound = pagination.event_bound();
+    let sales = db_helpers::select_retry_or_panic::<super::models::NftSaleInfo>(
+        pool,
+        &query,
+        &[
+            contract_id.to_string(),
+            token_id.to_string(),
+            pagination.block_timestamp.to_string(),
+            bound.block_timestamp.to_string(),
+            bound.shard_id.to_string(),
+            bound.index_in_chunk.to_string(),
+            pagination.limit.to_string(),
+        ],
+    )
+    .await?;
+
+    let mut result = vec![];
+    for sale in sales {
+        let event_index = types::query_params::EventIndex::from_db(
+            &sale.block_timestamp_nanos,
+            &sale.shard_id,
+            &sale.index_in_chunk,
+        )?;
+        result.push((sale.try_into()?, event_index));
+    }
+    Ok(result)
+}
+
+pub(crate) async fn get_nft_market_stats(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    contract_id: &near_primitives::types::AccountId,
+    block: &db_helpers::Block,
+    window_nanos: u64,
+) -> crate::Result<nft::schemas::NftMarketStatsResponse> {
+    let window_start = block.timestamp.saturating_sub(window_nanos);
+    let query = format!(
+        r"
+        SELECT
+            count(*) sales_count,
+            coalesce(sum(price), 0) volume,
+            min(price) floor_price,
+            avg(price) average_price
+        FROM (
+            SELECT (args->>'deposit')::numeric(45, 0) price
+            FROM action_receipt_actions
+                JOIN execution_outcomes ON action_receipt_actions.receipt_id = execution_outcomes.receipt_id
+            WHERE receipt_receiver_account_id IN ({})
+                AND action_kind = 'FUNCTION_CALL'
+                AND args->>'method_name' = 'buy'
+                AND args->'args_json'->>'nft_contract_id' = $1
+                AND execution_outcomes.status IN ('SUCCESS_VALUE', 'SUCCESS_RECEIPT_ID')
+                AND receipt_included_in_block_timestamp > $2::numeric(20, 0)
+                AND receipt_included_in_block_timestamp <= $3::numeric(20, 0)
+        ) sales
+    ",
+        marketplace_accounts_sql()
+    );
+    let stats = db_helpers::select_retry_or_panic::<super::models::NftMarketStatsInfo>(
+        pool,
+        &query,
+        &[
+            contract_id.to_string(),
+            window_start.to_string(),
+            block.timestamp.to_string(),
+        ],
+    )
+    .await?;
+    let stats = stats.first().ok_or_else(|| {
+        errors::ErrorKind::DBError("Aggregation query returned nothing".to_string())
+    })?;
+
+    Ok(nft::schemas::NftMarketStatsResponse {
+        sales_count: stats.sales_count as u64,
+        volume: types::numeric::to_u128(&stats.volume)?.into(),
+        floor_price: match &stats.floor_price {
+            Some(price) => Some(types::numeric::to_u128(price)?.into()),
+            None => None,
+        },
+        // avg gives the fraction, we don't need it for yoctoNEAR
+        average_price: match &stats.average_price {
+            Some(price) => Some(types::numeric::to_u128(&price.with_scale(0))?.into()),
+            None => None,
+        },
+        window_start_timestamp_nanos: window_start.into(),
+        meta: types::ResponseMeta::new(&block),
+    })
+}
+
+#[cfg(feature = "history")]
+impl TryFrom<super::models::NftSaleInfo> for nft::schemas::NftSale {
+    type Error = errors::Error;
+
+    fn try_from(info: super::models::NftSaleInfo) -> crate::Result<Self> {
+        Ok(Self {
+            marketplace: get_marketplace_name(&info.marketplace_account_id),
+            marketplace_account_id: info.marketplace_account_id.parse()?,
+            buyer_account_id: info.buyer_account_id.parse()?,
+            price: types::numeric::to_u128(&info.price)?.into(),
+            receipt_id: info.receipt_id,
+            block_timestamp_nanos: types::numeric::to_u64(&info.block_timestamp_nanos)?.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_marketplace_name() {
+        assert_eq!(get_marketplace_name("marketplace.paras.near"), "paras");
+        assert_eq!(get_marketplace_name("unknown.near"), "unknown.near");
+    }
+
+    #[cfg(feature = "history")]
+    #[tokio::test]
+    async fn test_nft_sales_seeded() {
+        let db = init_seeded_db().await;
+        let contract = near_primitives::types::AccountId::from_str("nft.near").unwrap();
+        let pagination = types::query_params::HistoryPagination {
+            block_height: 104,
+            block_timestamp: 1600000000000000104,
+            before_event: None,
+            limit: 10,
+        };
+
+        let sales = get_nft_sales(&db.pool, &contract, "1", &pagination)
+            .await
+            .map(crate::modules::without_event_indexes)
+            .unwrap();
+        assert_eq!(sales.len(), 1);
+        assert_eq!(sales[0].marketplace, "paras");
+        assert_eq!(sales[0].buyer_account_id.to_string(), "bob.near");
+        assert_eq!(sales[0].price.0, 5 * 10u128.pow(24));
+    }
+
+    #[tokio::test]
+    async fn test_nft_market_stats_seeded() {
+        let db = init_seeded_db().await;
+        let contract = near_primitives::types::AccountId::from_str("nft.near").unwrap();
+        let block = get_seeded_block(103);
+
+        let stats = get_nft_market_stats(&db.pool, &contract, &block, 1_000_000)
+            .await
+            .unwrap();
+        // The failed purchase is not counted
+        assert_eq!(stats.sales_count, 1);
+        assert_eq!(stats.volume.0, 5 * 10u128.pow(24));
+        assert_eq!(stats.floor_price, Some(types::U128(5 * 10u128.pow(24))));
+
+        let stats = get_nft_market_stats(&db.pool, &contract, &block, 1)
+            .await
+            .unwrap();
+        assert_eq!(stats.sales_count, 0);
+        assert_eq!(stats.volume.0, 0);
+        assert_eq!(stats.floor_price, None);
+    }
+}
@@ -0,0 +1,98 @@
+use crate::db_helpers;
+use crate::modules::nft;
+
+/// Legit collections are minted by the buyers themselves or with the attached deposit
+const AIRDROP_RECEIVERS_THRESHOLD: u32 = 100;
+
+/// Sets `is_spam` for the denylisted collections and for the mass airdrops:
+/// the contracts which minted the tokens for free to many accounts which did not ask for them
+pub(crate) async fn mark_spam_collections(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    denylist: &[String],
+    block: &db_helpers::Block,
+    counts: &mut [nft::schemas::NftCount],
+) -> crate::Result<()> {
+    if counts.is_empty() {
+        return Ok(());
+    }
+    let contract_ids: Vec<String> = counts
+        .iter()
+        .map(|count| count.contract_account_id.0.to_string())
+        .collect();
+    let airdropping_contracts = get_airdropping_contracts(
+        pool,
+        &contract_ids,
+        block.timestamp,
+        AIRDROP_RECEIVERS_THRESHOLD,
+    )
+    .await?;
+    for count in counts.iter_mut() {
+        let contract_id = count.contract_account_id.0.as_str();
+        count.is_spam = denylist.iter().any(|denied| denied == contract_id)
+            || airdropping_contracts
+                .iter()
+                .any(|airdropping| airdropping == contract_id);
+    }
+    Ok(())
+}
+
+/// The contracts which have the free mints made by someone else for at least `min_receivers` accounts
+async fn get_airdropping_contracts(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    contract_ids: &[String],
+    block_timestamp: u64,
+    min_receivers: u32,
+) -> crate::Result<Vec<String>> {
+    let query = r"
+        SELECT emitted_by_contract_account_id account_id
+        FROM assets__non_fungible_token_events
+            JOIN action_receipt_actions ON assets__non_fungible_token_events.emitted_for_receipt_id = action_receipt_actions.receipt_id
+        WHERE emitted_by_contract_account_id = ANY($1::text[])
+            AND event_kind = 'MINT'
+            AND emitted_at_block_timestamp <= $2::numeric(20, 0)
+            AND action_kind = 'FUNCTION_CALL'
+            AND receipt_predecessor_account_id != token_new_owner_account_id
+            AND coalesce(args->>'deposit', '0') = '0'
+        GROUP BY emitted_by_contract_account_id
+        HAVING count(DISTINCT token_new_owner_account_id) >= $3::numeric(20, 0)
+    ";
+    Ok(db_helpers::select_retry_or_panic::<db_helpers::AccountId>(
+        pool,
+        query,
+        &[
+            format!("{{{}}}", contract_ids.join(",")),
+            block_timestamp.to_string(),
+            min_receivers.to_string(),
+        ],
+    )
+    .await?
+    .into_iter()
+    .map(|contract| contract.account_id)
+    .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+
+    #[tokio::test]
+    async fn test_airdropping_contracts_seeded() {
+        let db = init_seeded_db().await;
+        let contract_ids = vec!["nft.near".to_string(), "other.near".to_string()];
+
+        // Token "1" was minted to alice.near by nft.near itself with no deposit
+        let contracts = get_airdropping_contracts(&db.pool, &contract_ids, 1600000000000000103, 1)
+            .await
+            .unwrap();
+        assert_eq!(contracts, vec!["nft.near".to_string()]);
+        let contracts = get_airdropping_contracts(&db.pool, &contract_ids, 1600000000000000103, 2)
+            .await
+            .unwrap();
+        assert!(contracts.is_empty());
+        let contracts = get_airdropping_contracts(&db.pool, &contract_ids, 1600000000000000100, 1)
+            .await
+            .unwrap();
+        assert!(contracts.is_empty());
+    }
+}
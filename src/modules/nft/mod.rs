@@ -1,28 +1,169 @@
 use paperclip::actix::web;
 
-mod data_provider;
+use crate::{allow_list, data_source, types};
+
+pub(crate) mod data_provider;
 mod resources;
-mod schemas;
+pub(crate) mod schemas;
 
-pub(crate) fn register_services(app: &mut web::ServiceConfig) {
+pub(crate) fn register_services(
+    app: &mut web::ServiceConfig,
+    allow_list: &allow_list::ContractAllowList,
+) {
     app.service(
         web::resource("/accounts/{account_id}/NFT")
             .route(web::get().to(resources::get_nft_collection_overview)),
     )
+    // Should go before `{contract_account_id}`, `diff` is the valid account_id
+    .service(
+        web::resource("/accounts/{account_id}/NFT/diff")
+            .route(web::get().to(resources::get_nft_holdings_diff)),
+    )
     .service(
         web::resource("/accounts/{account_id}/NFT/{contract_account_id}")
-            .route(web::get().to(resources::get_nft_collection_by_contract)),
+            .route(web::get().to(resources::get_nft_collection_by_contract))
+            .wrap(allow_list.clone()),
     )
     .service(
         web::resource("/NFT/{contract_account_id}/{token_id}")
-            .route(web::get().to(resources::get_nft)),
+            .route(web::get().to(resources::get_nft))
+            .wrap(allow_list.clone()),
     )
     .service(
-        web::resource("/NFT/{contract_account_id}/{token_id}/history")
-            .route(web::get().to(resources::get_nft_history)),
+        web::resource("/nep171/token/{contract_account_id}")
+            .route(web::get().to(resources::get_nft_by_token_param))
+            .wrap(allow_list.clone()),
     )
     .service(
         web::resource("/nep171/metadata/{contract_account_id}")
-            .route(web::get().to(resources::get_nft_contract_metadata)),
+            .route(web::get().to(resources::get_nft_contract_metadata))
+            .wrap(allow_list.clone()),
+    )
+    .service(
+        web::resource("/nep171/metadata")
+            .route(web::post().to(resources::get_nft_contracts_metadata)),
+    )
+    .service(
+        web::resource("/nep171/metadata/{contract_account_id}/refresh")
+            .route(web::post().to(resources::refresh_nft_contract_metadata))
+            .wrap(allow_list.clone()),
+    )
+    .service(
+        web::resource("/nep171/market/{contract_account_id}")
+            .route(web::get().to(resources::get_nft_market_stats))
+            .wrap(allow_list.clone()),
+    );
+
+    #[cfg(feature = "history")]
+    app.service(
+        web::resource("/accounts/{account_id}/NFT/approvals/history")
+            .route(web::get().to(resources::get_nft_approvals_history)),
+    )
+    .service(
+        web::resource("/NFT/{contract_account_id}/{token_id}/history")
+            .route(web::get().to(resources::get_nft_history))
+            .wrap(allow_list.clone()),
+    )
+    .service(
+        web::resource("/NFT/{contract_account_id}/{token_id}/sales")
+            .route(web::get().to(resources::get_nft_sales))
+            .wrap(allow_list.clone()),
+    )
+    .service(
+        web::resource("/NFT/{contract_account_id}/{token_id}/provenance")
+            .route(web::get().to(resources::get_nft_provenance))
+            .wrap(allow_list.clone()),
+    )
+    .service(
+        web::resource("/nep171/token/{contract_account_id}/history")
+            .route(web::get().to(resources::get_nft_history_by_token_param))
+            .wrap(allow_list.clone()),
+    )
+    .service(
+        web::resource("/nep171/token/{contract_account_id}/sales")
+            .route(web::get().to(resources::get_nft_sales_by_token_param))
+            .wrap(allow_list.clone()),
+    )
+    .service(
+        web::resource("/nep171/token/{contract_account_id}/provenance")
+            .route(web::get().to(resources::get_nft_provenance_by_token_param))
+            .wrap(allow_list.clone()),
     );
 }
+
+/// `skip_errors=true`: the contracts from `failed_contracts` are dropped from the overview as well
+pub(crate) fn skip_failed_contracts(
+    nft_counts: &mut Vec<schemas::NftCount>,
+    failed_contracts: &mut Vec<types::FailedContract>,
+) {
+    nft_counts.retain(|nft_count| {
+        !failed_contracts
+            .iter()
+            .any(|failed| failed.contract_account_id == nft_count.contract_account_id)
+    });
+    failed_contracts.clear();
+}
+
+/// Replaces the collection metadata with the current one, see `MetadataMode::Latest`.
+/// The contracts which fail to give it keep the metadata at the requested block
+pub(crate) async fn use_latest_metadata(
+    data_source: &data_source::DataSourceRef,
+    nft_counts: &mut [schemas::NftCount],
+) -> crate::Result<()> {
+    if nft_counts.is_empty() {
+        return Ok(());
+    }
+    let contract_ids: Vec<near_primitives::types::AccountId> = nft_counts
+        .iter()
+        .map(|nft_count| nft_count.contract_account_id.0.clone())
+        .collect();
+    let last_block = data_source.get_last_block().await?;
+    let (items, _) = data_source
+        .get_nft_contracts_metadata(&contract_ids, last_block.height)
+        .await?;
+    for item in items {
+        if let Some(nft_count) = nft_counts
+            .iter_mut()
+            .find(|nft_count| nft_count.contract_account_id == item.contract_account_id)
+        {
+            nft_count.standards_missing = item.standards_missing;
+            nft_count.contract_metadata = item.metadata.unwrap_or_else(|| {
+                data_provider::get_default_nft_contract_metadata(&nft_count.contract_account_id.0)
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_exotic_token_ids_in_path() {
+        let app = actix_web::test::init_service(actix_web::App::new().route(
+            "/NFT/{contract_account_id}/{token_id}/history",
+            actix_web::web::get().to(
+                |request: actix_web::web::Path<schemas::NftRequest>| async move {
+                    request.into_inner().token_id
+                },
+            ),
+        ))
+        .await;
+
+        for (encoded, token_id) in [
+            ("a%2Fb", "a/b"),
+            ("ipfs%3A%2F%2Fbafy", "ipfs://bafy"),
+            ("my%20token", "my token"),
+            ("%D1%82%D0%BE%D0%BA%D0%B5%D0%BD", "токен"),
+            ("100%25", "100%"),
+            ("a%3Fb%23c", "a?b#c"),
+        ] {
+            let request = actix_web::test::TestRequest::get()
+                .uri(&format!("/NFT/x.paras.near/{}/history", encoded))
+                .to_request();
+            let body = actix_web::test::call_and_read_body(&app, request).await;
+            assert_eq!(body, token_id);
+        }
+    }
+}
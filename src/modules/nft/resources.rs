@@ -3,10 +3,13 @@ use paperclip::actix::{
     web::{self, Json},
 };
 
-use crate::{db_helpers, modules, types};
+use crate::{allow_list, data_source, errors, modules, types};
 
 use super::schemas;
 
+/// The limit of the tokens in the holdings diff, gained and lost together
+const MAX_DIFF_TOKENS: u32 = 1000;
+
 #[api_v2_operation(tags(NFT))]
 /// Get user's NFT collection overview
 ///
@@ -17,32 +20,54 @@ use super::schemas;
 /// `block_timestamp_nanos` helps you to choose the moment of time, we fix the blockchain state at that time.
 ///
 /// **Limitations**
-/// * We provide only up to 100 items, where recently updated data goes first.
+/// * We provide only up to `limit` items, where recently updated data goes first.
 ///   Full-featured pagination will be provided later.
+/// * If the RPC call to some NFT contract fails, it's listed in `failed_contracts`.
+///   Pass `skip_errors=true` to omit such contracts completely.
+/// * The contracts are asked in parallel, the slow ones are given with the last known metadata and `stale: true`.
+/// * `is_spam` is the heuristic: the collection is denylisted by the server,
+///   or its tokens were minted for free to at least 100 accounts which did not ask for them.
+///   Pass `hide_spam=true` to omit such collections, the page could then have less than `limit` items.
+/// * The contract metadata is taken at the same block as the counts.
+///   Pass `metadata=latest` to get the current names and icons instead, it could be up to 1 hour old.
 pub async fn get_nft_collection_overview(
-    pool: web::Data<sqlx::Pool<sqlx::Postgres>>,
-    rpc_client: web::Data<near_jsonrpc_client::JsonRpcClient>,
+    query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
+    allow_list: web::Data<allow_list::ContractAllowList>,
     request: web::Path<schemas::NftCountsRequest>,
     block_params: web::Query<types::query_params::BlockParams>,
-    pagination_params: web::Query<types::query_params::PaginationParams>,
+    mut pagination_params: web::Query<types::query_params::PaginationParams>,
+    skip_errors_params: web::Query<types::query_params::SkipErrorsParams>,
+    hide_spam_params: web::Query<schemas::HideSpamParams>,
+    metadata_params: web::Query<types::query_params::MetadataParams>,
 ) -> crate::Result<Json<schemas::NftCountsResponse>> {
-    types::query_params::check_limit(pagination_params.limit)?;
-    types::query_params::check_block_params(&block_params)?;
-    let block = db_helpers::get_block_from_params(&pool, &block_params).await?;
-    modules::check_account_exists(&pool, &request.account_id.0, block.timestamp).await?;
+    pagination_params.limit = Some(query.limit);
+    let block = data_source.get_block_from_params(&block_params).await?;
+    modules::check_account_exists(&data_source, &request.account_id.0, block.timestamp).await?;
 
+    // TODO PHASE 2 We can data_provider metadata in the DB and update once in 10 minutes
+    let (mut nft_counts, mut failed_contracts) = data_source
+        .get_nfts_count(&block, &request.account_id.0, pagination_params.0)
+        .await?;
+    allow_list.retain(&mut nft_counts, |nft_count| {
+        Some(&nft_count.contract_account_id.0)
+    });
+    allow_list.retain(&mut failed_contracts, |failed| {
+        Some(&failed.contract_account_id.0)
+    });
+    if skip_errors_params.skip_errors.unwrap_or_default() {
+        super::skip_failed_contracts(&mut nft_counts, &mut failed_contracts);
+    }
+    if hide_spam_params.hide_spam.unwrap_or_default() {
+        nft_counts.retain(|nft_count| !nft_count.is_spam);
+    }
+    if metadata_params.is_latest() {
+        super::use_latest_metadata(&data_source, &mut nft_counts).await?;
+    }
     Ok(Json(schemas::NftCountsResponse {
-        // TODO PHASE 2 We can data_provider metadata in the DB and update once in 10 minutes
-        nft_counts: super::data_provider::get_nfts_count(
-            &pool,
-            &rpc_client,
-            &block,
-            &request.account_id.0,
-            pagination_params.0,
-        )
-        .await?,
-        block_timestamp_nanos: types::U64::from(block.timestamp),
-        block_height: types::U64::from(block.height),
+        nft_counts,
+        meta: types::ResponseMeta::new(&block),
+        failed_contracts,
     }))
 }
 
@@ -54,38 +79,101 @@ pub async fn get_nft_collection_overview(
 /// You can copy the token_id from this response and then ask for NFT history.
 ///
 /// **Limitations**
-/// * We provide only up to 100 items.
+/// * We provide only up to `limit` items.
 ///   Full-featured pagination will be provided later.
+/// * The contract metadata is taken at the same block as the tokens.
+///   Pass `metadata=latest` to get the current one, the tokens' own metadata stays at the block.
 pub async fn get_nft_collection_by_contract(
-    pool: web::Data<sqlx::Pool<sqlx::Postgres>>,
-    rpc_client: web::Data<near_jsonrpc_client::JsonRpcClient>,
+    query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
     request: web::Path<schemas::NftCollectionRequest>,
     block_params: web::Query<types::query_params::BlockParams>,
-    pagination_params: web::Query<types::query_params::PaginationParams>,
+    mut pagination_params: web::Query<types::query_params::PaginationParams>,
+    metadata_params: web::Query<types::query_params::MetadataParams>,
 ) -> crate::Result<Json<schemas::NftsResponse>> {
-    types::query_params::check_limit(pagination_params.limit)?;
-    types::query_params::check_block_params(&block_params)?;
-    let block = db_helpers::get_block_from_params(&pool, &block_params).await?;
-    modules::check_account_exists(&pool, &request.account_id.0, block.timestamp).await?;
+    pagination_params.limit = Some(query.limit);
+    let block = data_source.get_block_from_params(&block_params).await?;
+    modules::check_account_exists(&data_source, &request.account_id.0, block.timestamp).await?;
     let pagination = types::query_params::Pagination::from(pagination_params.0);
+    let metadata_block_height = if metadata_params.is_latest() {
+        data_source.get_last_block().await?.height
+    } else {
+        block.height
+    };
+
+    let contract_metadata = data_source
+        .get_nft_contract_metadata(&request.contract_account_id.0, metadata_block_height)
+        .await?;
 
     Ok(Json(schemas::NftsResponse {
-        nfts: super::data_provider::get_nfts_by_contract(
-            &rpc_client,
-            request.contract_account_id.0.clone(),
-            request.account_id.0.clone(),
-            block.height,
-            pagination.limit,
+        nfts: data_source
+            .get_nfts_by_contract(
+                &request.contract_account_id.0,
+                &request.account_id.0,
+                block.height,
+                pagination.limit,
+            )
+            .await?,
+        standards_missing: schemas::standards_missing(contract_metadata.as_ref()),
+        contract_metadata,
+        meta: types::ResponseMeta::new(&block),
+    }))
+}
+
+#[api_v2_operation(tags(NFT))]
+/// Get user's NFT holdings changes between two blocks
+///
+/// This endpoint returns NFTs the given account_id got and gave away between `from_block` and `to_block`,
+/// computed from NFT events.
+///
+/// **Limitations**
+/// * For now, we support only NFT contracts which implement Events NEP.
+/// * We provide only up to 1000 tokens in total, ordered by contract_account_id and token_id.
+pub async fn get_nft_holdings_diff(
+    data_source: web::Data<data_source::DataSourceRef>,
+    allow_list: web::Data<allow_list::ContractAllowList>,
+    request: web::Path<schemas::NftCountsRequest>,
+    params: web::Query<schemas::NftHoldingsDiffParams>,
+) -> crate::Result<Json<schemas::NftHoldingsDiffResponse>> {
+    if params.from_block.0 > params.to_block.0 {
+        return Err(errors::ErrorKind::InvalidInput(
+            "from_block should not be greater than to_block".to_string(),
         )
-        .await?,
-        contract_metadata: super::data_provider::get_nft_contract_metadata(
-            &rpc_client,
-            request.contract_account_id.0.clone(),
-            block.height,
+        .into());
+    }
+    let from_block = data_source
+        .get_block_from_params(&types::query_params::BlockParams {
+            block_height: Some(params.from_block),
+            ..Default::default()
+        })
+        .await?;
+    let to_block = data_source
+        .get_block_from_params(&types::query_params::BlockParams {
+            block_height: Some(params.to_block),
+            ..Default::default()
+        })
+        .await?;
+    modules::check_account_exists(&data_source, &request.account_id.0, to_block.timestamp).await?;
+
+    let mut diff = data_source
+        .get_nft_holdings_diff(
+            &request.account_id.0,
+            &from_block,
+            &to_block,
+            MAX_DIFF_TOKENS,
         )
-        .await?,
-        block_timestamp_nanos: types::U64::from(block.timestamp),
-        block_height: types::U64::from(block.height),
+        .await?;
+    allow_list.retain(&mut diff.gained, |change| {
+        Some(&change.contract_account_id.0)
+    });
+    allow_list.retain(&mut diff.lost, |change| Some(&change.contract_account_id.0));
+    Ok(Json(schemas::NftHoldingsDiffResponse {
+        gained: diff.gained,
+        lost: diff.lost,
+        from_block_timestamp_nanos: types::U64::from(from_block.timestamp),
+        from_block_height: types::U64::from(from_block.height),
+        from_block_hash: from_block.hash.clone(),
+        meta: types::ResponseMeta::new(&to_block),
     }))
 }
 
@@ -94,34 +182,80 @@ pub async fn get_nft_collection_by_contract(
 ///
 /// This endpoint returns the NFT detailed information
 /// for the given token_id, NFT contract_id, timestamp/block_height.
+/// `token_id` should be percent-encoded, e.g. `a%2Fb` for `a/b`;
+/// see also `/nep171/token/{contract_account_id}` which takes `token_id` from the query.
 pub async fn get_nft(
-    pool: web::Data<sqlx::Pool<sqlx::Postgres>>,
-    rpc_client: web::Data<near_jsonrpc_client::JsonRpcClient>,
+    data_source: web::Data<data_source::DataSourceRef>,
     request: web::Path<schemas::NftRequest>,
     block_params: web::Query<types::query_params::BlockParams>,
 ) -> crate::Result<Json<schemas::NftResponse>> {
-    types::query_params::check_block_params(&block_params)?;
-    let block = db_helpers::get_block_from_params(&pool, &block_params).await?;
-
-    Ok(Json(schemas::NftResponse {
-        nft: super::data_provider::get_nft(
-            &rpc_client,
-            request.contract_account_id.0.clone(),
-            request.token_id.clone(),
-            block.height,
+    Ok(Json(
+        nft_response(
+            &data_source,
+            &request.contract_account_id,
+            &request.token_id,
+            &block_params,
         )
         .await?,
-        contract_metadata: super::data_provider::get_nft_contract_metadata(
-            &rpc_client,
-            request.contract_account_id.0.clone(),
-            block.height,
+    ))
+}
+
+#[api_v2_operation(tags(NFT))]
+/// Get NFT by token_id in the query
+///
+/// The same as `/NFT/{contract_account_id}/{token_id}`, for the token_ids which are hard to put to the path:
+/// the ones with `/`, `.` and `..`, spaces, non-ASCII characters.
+pub async fn get_nft_by_token_param(
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: web::Path<schemas::NftContractRequest>,
+    token_params: web::Query<schemas::TokenIdParams>,
+    block_params: web::Query<types::query_params::BlockParams>,
+) -> crate::Result<Json<schemas::NftResponse>> {
+    Ok(Json(
+        nft_response(
+            &data_source,
+            &request.contract_account_id,
+            &token_params.token_id,
+            &block_params,
         )
         .await?,
-        block_timestamp_nanos: types::U64::from(block.timestamp),
-        block_height: types::U64::from(block.height),
-    }))
+    ))
+}
+
+async fn nft_response(
+    data_source: &data_source::DataSourceRef,
+    contract_account_id: &types::AccountId,
+    token_id: &str,
+    block_params: &types::query_params::BlockParams,
+) -> crate::Result<schemas::NftResponse> {
+    check_token_id(token_id)?;
+    types::query_params::check_block_params(block_params)?;
+    let block = data_source.get_block_from_params(block_params).await?;
+    let contract_metadata = data_source
+        .get_nft_contract_metadata(&contract_account_id.0, block.height)
+        .await?;
+
+    Ok(schemas::NftResponse {
+        nft: data_source
+            .get_nft(&contract_account_id.0, token_id, block.height)
+            .await?,
+        standards_missing: schemas::standards_missing(contract_metadata.as_ref()),
+        contract_metadata,
+        meta: types::ResponseMeta::new(&block),
+    })
+}
+
+/// The token_ids are arbitrary strings in NEP-171, but the empty one is surely a broken request
+fn check_token_id(token_id: &str) -> crate::Result<()> {
+    if token_id.is_empty() {
+        return Err(
+            errors::ErrorKind::InvalidInput("token_id should not be empty".to_string()).into(),
+        );
+    }
+    Ok(())
 }
 
+#[cfg(feature = "history")]
 #[api_v2_operation(tags(NFT))]
 /// Get NFT history
 ///
@@ -130,60 +264,379 @@ pub async fn get_nft(
 ///
 /// **Limitations**
 /// * For now, we support only NFT contracts which implement Events NEP.
-/// * We provide only up to 100 items, where recent updates go first.
-///   Full-featured pagination will be provided later.
+/// * We provide only up to `limit` items, where recent updates go first.
+///   Pass `meta.next_cursor` as `cursor` to get the next page.
 pub async fn get_nft_history(
-    pool: web::Data<sqlx::Pool<sqlx::Postgres>>,
-    rpc_client: web::Data<near_jsonrpc_client::JsonRpcClient>,
+    http_request: actix_web::HttpRequest,
+    data_source: web::Data<data_source::DataSourceRef>,
     request: web::Path<schemas::NftRequest>,
     pagination_params: web::Query<types::query_params::HistoryPaginationParams>,
 ) -> crate::Result<Json<schemas::HistoryResponse>> {
-    let block = db_helpers::get_last_block(&pool).await?;
-    let pagination =
-        modules::check_and_get_history_pagination_params(&pool, pagination_params.0).await?;
+    Ok(Json(
+        nft_history_response(
+            &data_source,
+            &http_request,
+            &request.contract_account_id,
+            &request.token_id,
+            pagination_params.0,
+        )
+        .await?,
+    ))
+}
 
-    Ok(Json(schemas::HistoryResponse {
-        history: super::data_provider::get_nft_history(
-            &pool,
-            &request.contract_account_id.0,
+#[cfg(feature = "history")]
+#[api_v2_operation(tags(NFT))]
+/// Get NFT history by token_id in the query
+///
+/// The same as `/NFT/{contract_account_id}/{token_id}/history`, for the token_ids which are hard to put to the path.
+pub async fn get_nft_history_by_token_param(
+    http_request: actix_web::HttpRequest,
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: web::Path<schemas::NftContractRequest>,
+    token_params: web::Query<schemas::TokenIdParams>,
+    pagination_params: web::Query<types::query_params::HistoryPaginationParams>,
+) -> crate::Result<Json<schemas::HistoryResponse>> {
+    Ok(Json(
+        nft_history_response(
+            &data_source,
+            &http_request,
+            &request.contract_account_id,
+            &token_params.token_id,
+            pagination_params.0,
+        )
+        .await?,
+    ))
+}
+
+#[cfg(feature = "history")]
+async fn nft_history_response(
+    data_source: &data_source::DataSourceRef,
+    http_request: &actix_web::HttpRequest,
+    contract_account_id: &types::AccountId,
+    token_id: &str,
+    pagination_params: types::query_params::HistoryPaginationParams,
+) -> crate::Result<schemas::HistoryResponse> {
+    check_token_id(token_id)?;
+    let (block, pagination) = modules::check_and_get_history_pagination_params(
+        data_source,
+        http_request,
+        pagination_params,
+    )
+    .await?;
+    let history = data_source
+        .get_nft_history(&contract_account_id.0, token_id, &pagination)
+        .await?;
+    let next_cursor = modules::next_history_cursor(&history, &block, &pagination);
+
+    Ok(schemas::HistoryResponse {
+        history: modules::without_event_indexes(history),
+        nft: data_source
+            .get_nft(&contract_account_id.0, token_id, block.height)
+            .await?,
+        meta: types::ResponseMeta::new(&block).with_next_cursor(next_cursor),
+    })
+}
+
+#[cfg(feature = "history")]
+#[api_v2_operation(tags(NFT))]
+/// Get NFT sales
+///
+/// This endpoint returns the marketplace purchases of the given NFT with their prices.
+/// The transfer history does not show how much was paid, this one does.
+///
+/// **Limitations**
+/// * For now, we support only Paras, Mintbase and Few and Far marketplaces.
+/// * We provide only up to `limit` items, where recent sales go first.
+///   Pass `meta.next_cursor` as `cursor` to get the next page.
+pub async fn get_nft_sales(
+    http_request: actix_web::HttpRequest,
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: web::Path<schemas::NftRequest>,
+    pagination_params: web::Query<types::query_params::HistoryPaginationParams>,
+) -> crate::Result<Json<schemas::NftSalesResponse>> {
+    Ok(Json(
+        nft_sales_response(
+            &data_source,
+            &http_request,
+            &request.contract_account_id,
             &request.token_id,
-            &pagination,
+            pagination_params.0,
         )
         .await?,
-        nft: super::data_provider::get_nft(
-            &rpc_client,
-            request.contract_account_id.0.clone(),
-            request.token_id.clone(),
-            block.height,
+    ))
+}
+
+#[cfg(feature = "history")]
+#[api_v2_operation(tags(NFT))]
+/// Get NFT sales by token_id in the query
+///
+/// The same as `/NFT/{contract_account_id}/{token_id}/sales`, for the token_ids which are hard to put to the path.
+pub async fn get_nft_sales_by_token_param(
+    http_request: actix_web::HttpRequest,
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: web::Path<schemas::NftContractRequest>,
+    token_params: web::Query<schemas::TokenIdParams>,
+    pagination_params: web::Query<types::query_params::HistoryPaginationParams>,
+) -> crate::Result<Json<schemas::NftSalesResponse>> {
+    Ok(Json(
+        nft_sales_response(
+            &data_source,
+            &http_request,
+            &request.contract_account_id,
+            &token_params.token_id,
+            pagination_params.0,
         )
         .await?,
-        block_timestamp_nanos: types::U64::from(block.timestamp),
-        block_height: types::U64::from(block.height),
+    ))
+}
+
+#[cfg(feature = "history")]
+async fn nft_sales_response(
+    data_source: &data_source::DataSourceRef,
+    http_request: &actix_web::HttpRequest,
+    contract_account_id: &types::AccountId,
+    token_id: &str,
+    pagination_params: types::query_params::HistoryPaginationParams,
+) -> crate::Result<schemas::NftSalesResponse> {
+    check_token_id(token_id)?;
+    let (block, pagination) = modules::check_and_get_history_pagination_params(
+        data_source,
+        http_request,
+        pagination_params,
+    )
+    .await?;
+    let sales = data_source
+        .get_nft_sales(&contract_account_id.0, token_id, &pagination)
+        .await?;
+    let next_cursor = modules::next_history_cursor(&sales, &block, &pagination);
+
+    Ok(schemas::NftSalesResponse {
+        sales: modules::without_event_indexes(sales),
+        meta: types::ResponseMeta::new(&block).with_next_cursor(next_cursor),
+    })
+}
+
+#[cfg(feature = "history")]
+#[api_v2_operation(tags(NFT))]
+/// Get user's NFT approvals history
+///
+/// This endpoint returns NEP-178 `nft_approve`, `nft_revoke` and `nft_revoke_all` calls made by the given account_id,
+/// for the given timestamp/block_height. Check which marketplaces could still transfer your NFTs.
+///
+/// **Limitations**
+/// * NEP-178 does not define the events, so we show only the calls made by the account itself.
+///   The approvals dropped by the contract (e.g. on the transfer) are not shown.
+/// * We provide only up to `limit` items, where recent calls go first.
+///   Pass `meta.next_cursor` as `cursor` to get the next page.
+pub async fn get_nft_approvals_history(
+    http_request: actix_web::HttpRequest,
+    data_source: web::Data<data_source::DataSourceRef>,
+    allow_list: web::Data<allow_list::ContractAllowList>,
+    request: web::Path<schemas::NftCountsRequest>,
+    pagination_params: web::Query<types::query_params::HistoryPaginationParams>,
+) -> crate::Result<Json<schemas::NftApprovalsHistoryResponse>> {
+    let (block, pagination) = modules::check_and_get_history_pagination_params(
+        &data_source,
+        &http_request,
+        pagination_params.0,
+    )
+    .await?;
+    modules::check_account_exists(&data_source, &request.account_id.0, block.timestamp).await?;
+    let mut history = data_source
+        .get_nft_approvals_history(&request.account_id.0, &pagination)
+        .await?;
+    // The cursor is taken before, so the next page starts after the omitted items too
+    let next_cursor = modules::next_history_cursor(&history, &block, &pagination);
+    allow_list.retain(&mut history, |(item, _)| Some(&item.contract_account_id.0));
+
+    Ok(Json(schemas::NftApprovalsHistoryResponse {
+        history: modules::without_event_indexes(history),
+        meta: types::ResponseMeta::new(&block).with_next_cursor(next_cursor),
     }))
 }
 
+#[cfg(feature = "history")]
+#[api_v2_operation(tags(NFT))]
+/// Get NFT provenance
+///
+/// This endpoint returns the full ownership chain of the given NFT at the given timestamp/block_height:
+/// the minter and then each next owner with the moment of the transfer.
+/// If the transfer was a marketplace sale, the price and the marketplace are also provided.
+/// The oldest records go first.
+///
+/// **Limitations**
+/// * For now, we support only Paras, Mintbase and Few and Far marketplaces.
+/// * We provide only up to 1000 ownership changes.
+pub async fn get_nft_provenance(
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: web::Path<schemas::NftRequest>,
+    block_params: web::Query<types::query_params::BlockParams>,
+) -> crate::Result<Json<schemas::NftProvenanceResponse>> {
+    Ok(Json(
+        nft_provenance_response(
+            &data_source,
+            &request.contract_account_id,
+            &request.token_id,
+            &block_params,
+        )
+        .await?,
+    ))
+}
+
+#[cfg(feature = "history")]
+#[api_v2_operation(tags(NFT))]
+/// Get NFT provenance by token_id in the query
+///
+/// The same as `/NFT/{contract_account_id}/{token_id}/provenance`, for the token_ids which are hard to put to the path.
+pub async fn get_nft_provenance_by_token_param(
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: web::Path<schemas::NftContractRequest>,
+    token_params: web::Query<schemas::TokenIdParams>,
+    block_params: web::Query<types::query_params::BlockParams>,
+) -> crate::Result<Json<schemas::NftProvenanceResponse>> {
+    Ok(Json(
+        nft_provenance_response(
+            &data_source,
+            &request.contract_account_id,
+            &token_params.token_id,
+            &block_params,
+        )
+        .await?,
+    ))
+}
+
+#[cfg(feature = "history")]
+async fn nft_provenance_response(
+    data_source: &data_source::DataSourceRef,
+    contract_account_id: &types::AccountId,
+    token_id: &str,
+    block_params: &types::query_params::BlockParams,
+) -> crate::Result<schemas::NftProvenanceResponse> {
+    check_token_id(token_id)?;
+    types::query_params::check_block_params(block_params)?;
+    let block = data_source.get_block_from_params(block_params).await?;
+
+    Ok(schemas::NftProvenanceResponse {
+        provenance: data_source
+            .get_nft_provenance(&contract_account_id.0, token_id, &block)
+            .await?,
+        meta: types::ResponseMeta::new(&block),
+    })
+}
+
+#[api_v2_operation(tags(NFT))]
+/// Get NFT collection market stats
+///
+/// This endpoint returns the number of sales, the volume, the floor and the average price
+/// of the given NFT contract for the window which ends at the given timestamp/block_height.
+/// `window` is `30d` by default.
+///
+/// **Limitations**
+/// * For now, we support only Paras, Mintbase and Few and Far marketplaces.
+pub async fn get_nft_market_stats(
+    _query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: web::Path<schemas::NftMarketStatsRequest>,
+    block_params: web::Query<types::query_params::BlockParams>,
+    window_params: web::Query<types::query_params::WindowParams>,
+) -> crate::Result<Json<schemas::NftMarketStatsResponse>> {
+    let window_nanos = types::query_params::get_window_nanos(&window_params, 30)?;
+    let block = data_source.get_block_from_params(&block_params).await?;
+
+    Ok(Json(
+        data_source
+            .get_nft_market_stats(&request.contract_account_id.0, &block, window_nanos)
+            .await?,
+    ))
+}
+
 #[api_v2_operation(tags(NFT))]
 /// Get NFT contract metadata
 ///
 /// This endpoint returns the metadata for given NFT contract and timestamp/block_height.
 /// Keep in mind, this is contract-wide metadata. Each NFT also has its own metadata.
+/// `upgraded_since` warns if the contract code changed after this block.
+///
+/// **Limitations**
+/// * The metadata is the optional extension of NEP-171. If the contract does not implement it,
+///   `metadata` is `null` and `standards_missing` lists `NEP-177`.
 pub async fn get_nft_contract_metadata(
-    pool: web::Data<sqlx::Pool<sqlx::Postgres>>,
-    rpc_client: web::Data<near_jsonrpc_client::JsonRpcClient>,
+    _query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
     request: web::Path<schemas::MetadataRequest>,
     block_params: web::Query<types::query_params::BlockParams>,
 ) -> crate::Result<Json<schemas::MetadataResponse>> {
-    types::query_params::check_block_params(&block_params)?;
-    let block = db_helpers::get_block_from_params(&pool, &block_params).await?;
+    let block = data_source.get_block_from_params(&block_params).await?;
+    let metadata = data_source
+        .get_nft_contract_metadata(&request.contract_account_id.0, block.height)
+        .await?;
 
     Ok(Json(schemas::MetadataResponse {
-        metadata: super::data_provider::get_nft_contract_metadata(
-            &rpc_client,
-            request.contract_account_id.0.clone(),
-            block.height,
+        standards_missing: schemas::standards_missing(metadata.as_ref()),
+        metadata,
+        meta: types::ResponseMeta::new(&block),
+        upgraded_since: modules::get_upgraded_since(
+            &data_source,
+            &request.contract_account_id.0,
+            &block,
         )
         .await?,
-        block_timestamp_nanos: types::U64::from(block.timestamp),
-        block_height: types::U64::from(block.height),
+    }))
+}
+
+#[api_v2_operation(tags(NFT))]
+/// Get the metadata of several NFT contracts
+///
+/// This endpoint returns the latest contract-wide metadata of all the given NFT contracts in the order of the request,
+/// e.g. for the collections of the marketplace aggregator. It's POST because the list could be too long for the query.
+///
+/// **Limitations**
+/// * Up to 100 contracts.
+/// * The metadata could be up to 1 hour old, use `/nep171/metadata/{contract_account_id}/refresh` to update it.
+/// * If the RPC call to some NFT contract fails, it's listed in `failed_contracts`.
+pub async fn get_nft_contracts_metadata(
+    data_source: web::Data<data_source::DataSourceRef>,
+    allow_list: web::Data<allow_list::ContractAllowList>,
+    request: web::Json<schemas::NftContractsMetadataRequest>,
+) -> crate::Result<Json<schemas::NftContractsMetadataResponse>> {
+    let mut contract_ids: Vec<near_primitives::types::AccountId> = request
+        .contract_account_ids()?
+        .into_iter()
+        .map(|contract_id| contract_id.0)
+        .collect();
+    allow_list.retain(&mut contract_ids, |contract_id| Some(contract_id));
+    let block = data_source.get_last_block().await?;
+    let (metadata, failed_contracts) = data_source
+        .get_nft_contracts_metadata(&contract_ids, block.height)
+        .await?;
+
+    Ok(Json(schemas::NftContractsMetadataResponse {
+        metadata,
+        meta: types::ResponseMeta::new(&block),
+        failed_contracts,
+    }))
+}
+
+#[api_v2_operation(tags(NFT))]
+/// Refresh NFT contract metadata
+///
+/// This endpoint asks the contract for the metadata at the last block bypassing our caches,
+/// puts the fresh value into them and returns it.
+/// Use it after the collection changed its name, symbol or icon.
+pub async fn refresh_nft_contract_metadata(
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: web::Path<schemas::MetadataRequest>,
+) -> crate::Result<Json<schemas::MetadataResponse>> {
+    let block = data_source.get_last_block().await?;
+    let metadata = data_source
+        .refresh_nft_contract_metadata(&request.contract_account_id.0, block.height)
+        .await?;
+
+    Ok(Json(schemas::MetadataResponse {
+        standards_missing: schemas::standards_missing(metadata.as_ref()),
+        metadata,
+        meta: types::ResponseMeta::new(&block),
+        // It's the last block
+        upgraded_since: None,
     }))
 }
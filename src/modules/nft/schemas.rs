@@ -28,44 +28,173 @@ pub struct NftRequest {
     pub token_id: String,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct NftContractRequest {
+    pub contract_account_id: types::AccountId,
+}
+
+/// The alternative to `token_id` in the path, it's not needed to escape anything but the query special characters
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct TokenIdParams {
+    pub token_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct NftMarketStatsRequest {
+    pub contract_account_id: types::AccountId,
+}
+
+#[derive(
+    Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema,
+)]
+pub struct HideSpamParams {
+    /// Omit the collections marked with `is_spam`
+    pub hide_spam: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct NftHoldingsDiffParams {
+    /// Block height, the holdings are taken at the end of this block
+    pub from_block: types::U64,
+    /// Block height, should not be less than `from_block`
+    pub to_block: types::U64,
+}
+
+/// E.g. all the collections listed by the marketplace aggregator
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct NftContractsMetadataRequest {
+    pub contract_account_ids: Vec<types::AccountId>,
+}
+
+impl NftContractsMetadataRequest {
+    pub(crate) const MAX_CONTRACTS: usize = 100;
+
+    /// The requested contracts without duplicates, in the order of the request
+    pub(crate) fn contract_account_ids(&self) -> crate::Result<Vec<types::AccountId>> {
+        if self.contract_account_ids.is_empty()
+            || self.contract_account_ids.len() > Self::MAX_CONTRACTS
+        {
+            return Err(crate::errors::ErrorKind::InvalidInput(format!(
+                "contract_account_ids should have from 1 to {} contracts",
+                Self::MAX_CONTRACTS
+            ))
+            .into());
+        }
+        let mut contract_account_ids: Vec<types::AccountId> = vec![];
+        for contract_account_id in &self.contract_account_ids {
+            if !contract_account_ids.contains(contract_account_id) {
+                contract_account_ids.push(contract_account_id.clone());
+            }
+        }
+        Ok(contract_account_ids)
+    }
+}
+
 // *** Responses ***
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
 pub struct NftCountsResponse {
     pub nft_counts: Vec<NftCount>,
-    pub block_timestamp_nanos: types::U64,
-    pub block_height: types::U64,
+    pub meta: types::ResponseMeta,
+    /// The contracts which failed to give the metadata, their `nft_counts` entries have the default metadata
+    #[serde(default)]
+    pub failed_contracts: Vec<types::FailedContract>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
 pub struct NftsResponse {
     pub nfts: Vec<Nft>,
-    pub contract_metadata: NftContractMetadata,
-    pub block_timestamp_nanos: types::U64,
-    pub block_height: types::U64,
+    /// `null` if the contract does not implement the metadata extension, see `standards_missing`
+    pub contract_metadata: Option<NftContractMetadata>,
+    /// The optional NFT standards the contract does not implement, e.g. `["NEP-177"]`
+    #[serde(default)]
+    pub standards_missing: Vec<String>,
+    pub meta: types::ResponseMeta,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
 pub struct NftResponse {
     pub nft: Nft,
-    pub contract_metadata: NftContractMetadata,
-    pub block_timestamp_nanos: types::U64,
-    pub block_height: types::U64,
+    /// `null` if the contract does not implement the metadata extension, see `standards_missing`
+    pub contract_metadata: Option<NftContractMetadata>,
+    /// The optional NFT standards the contract does not implement, e.g. `["NEP-177"]`
+    #[serde(default)]
+    pub standards_missing: Vec<String>,
+    pub meta: types::ResponseMeta,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
 pub struct HistoryResponse {
     pub history: Vec<HistoryItem>,
     pub nft: Nft,
-    pub block_timestamp_nanos: types::U64,
-    pub block_height: types::U64,
+    pub meta: types::ResponseMeta,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct NftSalesResponse {
+    pub sales: Vec<NftSale>,
+    pub meta: types::ResponseMeta,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct NftApprovalsHistoryResponse {
+    pub history: Vec<NftApproval>,
+    pub meta: types::ResponseMeta,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct NftProvenanceResponse {
+    pub provenance: Vec<ProvenanceItem>,
+    pub meta: types::ResponseMeta,
+}
+
+/// Aggregated marketplace sales of the whole NFT contract for the window
+/// `[window_start_timestamp_nanos, block_timestamp_nanos]`.
+/// The prices are in yoctoNEAR, `floor_price` is the lowest sale price in the window.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct NftMarketStatsResponse {
+    pub sales_count: u64,
+    pub volume: types::U128,
+    pub floor_price: Option<types::U128>,
+    pub average_price: Option<types::U128>,
+    pub window_start_timestamp_nanos: types::U64,
+    pub meta: types::ResponseMeta,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
 pub struct MetadataResponse {
-    pub metadata: NftContractMetadata,
-    pub block_timestamp_nanos: types::U64,
-    pub block_height: types::U64,
+    /// `null` if the contract does not implement the metadata extension, see `standards_missing`
+    pub metadata: Option<NftContractMetadata>,
+    /// The optional NFT standards the contract does not implement, e.g. `["NEP-177"]`
+    #[serde(default)]
+    pub standards_missing: Vec<String>,
+    pub meta: types::ResponseMeta,
+    /// The last code change after the requested block, `null` if the code is the same since then.
+    /// The full list is given by `/contracts/{contract_account_id}/code-history`
+    #[serde(default)]
+    pub upgraded_since: Option<types::CodeChange>,
+}
+
+/// The metadata of the requested NFT contracts, in the order of the request
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct NftContractsMetadataResponse {
+    pub metadata: Vec<NftContractMetadataItem>,
+    pub meta: types::ResponseMeta,
+    /// The contracts which failed to give the metadata, they are not in `metadata`
+    #[serde(default)]
+    pub failed_contracts: Vec<types::FailedContract>,
+}
+
+/// NFTs the account got (`gained`) and gave away (`lost`) between the blocks, ordered by contract_account_id and token_id.
+/// The token which left and came back in the range is not listed
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct NftHoldingsDiffResponse {
+    pub gained: Vec<NftHoldingChange>,
+    pub lost: Vec<NftHoldingChange>,
+    pub from_block_timestamp_nanos: types::U64,
+    pub from_block_height: types::U64,
+    pub from_block_hash: String,
+    pub meta: types::ResponseMeta,
 }
 
 // ---
@@ -83,6 +212,54 @@ pub struct HistoryItem {
     pub status: String,
     pub block_timestamp_nanos: types::U64,
     pub block_height: types::U64,
+    pub block_hash: String,
+}
+
+/// The NFT purchase at one of the supported marketplaces.
+/// `marketplace` is one of ["paras", "mintbase", "fewandfar"], `price` is in yoctoNEAR
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct NftSale {
+    pub marketplace: String,
+    pub marketplace_account_id: types::AccountId,
+    pub buyer_account_id: types::AccountId,
+    pub price: types::U128,
+    pub receipt_id: String,
+    pub block_timestamp_nanos: types::U64,
+}
+
+/// NEP-178 approval call made by the account. `cause` is one of ["APPROVE", "REVOKE", "REVOKE_ALL"].
+/// `approved_account_id` is the marketplace (or any other account) which got or lost the approval,
+/// null for `REVOKE_ALL`. `status` is one of ["SUCCESS", "FAILURE"], the failed calls changed nothing
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct NftApproval {
+    pub cause: String,
+    pub contract_account_id: types::AccountId,
+    pub token_id: String,
+    pub approved_account_id: Option<types::AccountId>,
+    pub status: String,
+    pub receipt_id: String,
+    pub block_timestamp_nanos: types::U64,
+}
+
+/// One step of the NFT ownership chain: the mint, or the transfer to `owner_account_id`.
+/// `price` (in yoctoNEAR) and `marketplace` are filled if the transfer was a sale
+/// at one of the supported marketplaces
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct ProvenanceItem {
+    pub cause: String,
+    pub from_account_id: Option<types::AccountId>,
+    pub owner_account_id: Option<types::AccountId>,
+    pub price: Option<types::U128>,
+    pub marketplace: Option<String>,
+    pub block_timestamp_nanos: types::U64,
+    pub block_height: types::U64,
+    pub block_hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct NftHoldingChange {
+    pub contract_account_id: types::AccountId,
+    pub token_id: String,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
@@ -90,8 +267,18 @@ pub struct NftCount {
     pub contract_account_id: types::AccountId,
     pub nft_count: u32,
     // TODO PHASE 1 naming.
-    pub last_updated_at_timestamp_nanos: types::U128,
+    pub last_updated_at_timestamp_nanos: types::U64,
+    /// The default one named after the contract if it does not implement the metadata extension
     pub contract_metadata: NftContractMetadata,
+    /// The optional NFT standards the contract does not implement, e.g. `["NEP-177"]`
+    #[serde(default)]
+    pub standards_missing: Vec<String>,
+    /// The contract did not answer in time, `contract_metadata` is the last known one (or the default one)
+    #[serde(default)]
+    pub stale: bool,
+    /// The collection is denylisted, or it was mass airdropped: minted for free to many accounts
+    #[serde(default)]
+    pub is_spam: bool,
 }
 
 /// The type for Non Fungible Token Contract Metadata. Inspired by
@@ -107,6 +294,27 @@ pub struct NftContractMetadata {
     pub reference_hash: Option<String>, // Base64-encoded sha256 hash of JSON from reference field. Required if `reference` is included.
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct NftContractMetadataItem {
+    pub contract_account_id: types::AccountId,
+    /// `null` if the contract does not implement the metadata extension, see `standards_missing`
+    pub metadata: Option<NftContractMetadata>,
+    /// The optional NFT standards the contract does not implement, e.g. `["NEP-177"]`
+    #[serde(default)]
+    pub standards_missing: Vec<String>,
+}
+
+/// The contract metadata is NEP-177, the optional extension of NEP-171
+pub(crate) const METADATA_STANDARD: &str = "NEP-177";
+
+/// `standards_missing` of the responses with the contract metadata
+pub(crate) fn standards_missing(contract_metadata: Option<&NftContractMetadata>) -> Vec<String> {
+    match contract_metadata {
+        Some(_) => vec![],
+        None => vec![METADATA_STANDARD.to_string()],
+    }
+}
+
 /// The type for Non Fungible Token. Inspired by
 /// https://nomicon.io/Standards/Tokens/NonFungibleToken/Metadata
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
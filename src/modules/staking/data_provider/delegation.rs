@@ -0,0 +1,239 @@
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::modules::staking;
+use crate::{db_helpers, rpc_helpers, types};
+
+#[derive(Deserialize, Debug)]
+struct PoolAccountView {
+    unstaked_balance: types::U128,
+    staked_balance: types::U128,
+    can_withdraw: bool,
+}
+
+pub(crate) async fn get_staking_summary(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    rpc_client: &dyn rpc_helpers::RpcApi,
+    block: &db_helpers::Block,
+    account_id: &near_primitives::types::AccountId,
+) -> crate::Result<staking::schemas::StakingSummaryResponse> {
+    let epoch_info = rpc_helpers::get_epoch_info(rpc_client, block.height).await?;
+    let total_stake: u128 = epoch_info
+        .current_validators
+        .iter()
+        .map(|validator| validator.stake)
+        .sum();
+    let network_apy = super::network::get_network_apy(
+        super::network::get_total_supply(pool, block.height).await?,
+        total_stake,
+    );
+
+    let pool_ids = get_delegated_pools(pool, account_id, block.timestamp).await?;
+    // Each pool is the separate contract, so we ask them all at once
+    let delegations: Vec<staking::schemas::Delegation> =
+        futures::future::try_join_all(pool_ids.iter().map(|pool_id| {
+            get_delegation(pool, rpc_client, block, account_id, pool_id, network_apy)
+        }))
+        .await?
+        .into_iter()
+        .flatten()
+        .collect();
+
+    let total_staked_balance: u128 = delegations.iter().map(|d| d.staked_balance.0).sum();
+    let total_unstaked_balance: u128 = delegations.iter().map(|d| d.unstaked_balance.0).sum();
+    let apy = if total_staked_balance == 0 {
+        0.0
+    } else {
+        delegations
+            .iter()
+            .map(|d| d.apy * d.staked_balance.0 as f64)
+            .sum::<f64>()
+            / total_staked_balance as f64
+    };
+
+    Ok(staking::schemas::StakingSummaryResponse {
+        total_staked_balance: total_staked_balance.into(),
+        total_unstaked_balance: total_unstaked_balance.into(),
+        apy,
+        delegations,
+        epoch_height: epoch_info.epoch_height.into(),
+        meta: types::ResponseMeta::new(&block),
+    })
+}
+
+/// All the pools the account ever staked to. Some of them could be already empty
+async fn get_delegated_pools(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    account_id: &near_primitives::types::AccountId,
+    block_timestamp: u64,
+) -> crate::Result<Vec<near_primitives::types::AccountId>> {
+    let query = r"
+        SELECT DISTINCT receipt_receiver_account_id account_id
+        FROM action_receipt_actions
+        WHERE receipt_predecessor_account_id = $1
+            AND action_kind = 'FUNCTION_CALL'
+            AND args->>'method_name' IN ('deposit_and_stake', 'stake', 'stake_all')
+            AND receipt_included_in_block_timestamp <= $2::numeric(20, 0)
+        ORDER BY receipt_receiver_account_id
+    ";
+    Ok(db_helpers::select_retry_or_panic::<db_helpers::AccountId>(
+        pool,
+        query,
+        &[account_id.to_string(), block_timestamp.to_string()],
+    )
+    .await?
+    .into_iter()
+    .filter_map(|pool| near_primitives::types::AccountId::from_str(&pool.account_id).ok())
+    .collect())
+}
+
+async fn get_delegation(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    rpc_client: &dyn rpc_helpers::RpcApi,
+    block: &db_helpers::Block,
+    account_id: &near_primitives::types::AccountId,
+    pool_id: &near_primitives::types::AccountId,
+    network_apy: f64,
+) -> crate::Result<Option<staking::schemas::Delegation>> {
+    let request = rpc_helpers::get_function_call_request(
+        block.height,
+        pool_id.clone(),
+        "get_account",
+        serde_json::json!({ "account_id": account_id }),
+    );
+    let response = rpc_helpers::wrapped_call(rpc_client, request, block.height, pool_id).await?;
+    let account = serde_json::from_slice::<PoolAccountView>(&response.result)?;
+    if account.staked_balance.0 == 0 && account.unstaked_balance.0 == 0 {
+        return Ok(None);
+    }
+
+    let unstake_release_epoch_height = if account.unstaked_balance.0 > 0 && !account.can_withdraw {
+        match get_last_unstake_block_height(pool, account_id, pool_id, block.timestamp).await? {
+            Some(unstake_block_height) => Some(
+                (rpc_helpers::get_epoch_info(rpc_client, unstake_block_height)
+                    .await?
+                    .epoch_height
+                    + super::network::NUM_EPOCHS_TO_UNLOCK)
+                    .into(),
+            ),
+            None => None,
+        }
+    } else {
+        None
+    };
+
+    let fee = super::network::get_reward_fee(rpc_client, pool_id, block.height).await?;
+    Ok(Some(staking::schemas::Delegation {
+        pool_account_id: pool_id.clone().into(),
+        staked_balance: account.staked_balance,
+        unstaked_balance: account.unstaked_balance,
+        can_withdraw: account.can_withdraw,
+        unstake_release_epoch_height,
+        fee: fee * 100.0,
+        apy: network_apy * (1.0 - fee),
+    }))
+}
+
+async fn get_last_unstake_block_height(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    account_id: &near_primitives::types::AccountId,
+    pool_id: &near_primitives::types::AccountId,
+    block_timestamp: u64,
+) -> crate::Result<Option<u64>> {
+    let query = r"
+        SELECT blocks.block_height
+        FROM action_receipt_actions
+            JOIN execution_outcomes ON action_receipt_actions.receipt_id = execution_outcomes.receipt_id
+            JOIN blocks ON execution_outcomes.executed_in_block_hash = blocks.block_hash
+        WHERE receipt_predecessor_account_id = $1
+            AND receipt_receiver_account_id = $2
+            AND action_kind = 'FUNCTION_CALL'
+            AND args->>'method_name' IN ('unstake', 'unstake_all')
+            AND execution_outcomes.status IN ('SUCCESS_VALUE', 'SUCCESS_RECEIPT_ID')
+            AND receipt_included_in_block_timestamp <= $3::numeric(20, 0)
+        ORDER BY receipt_included_in_block_timestamp DESC
+        LIMIT 1
+    ";
+    match db_helpers::select_retry_or_panic::<super::models::BlockHeight>(
+        pool,
+        query,
+        &[
+            account_id.to_string(),
+            pool_id.to_string(),
+            block_timestamp.to_string(),
+        ],
+    )
+    .await?
+    .first()
+    {
+        Some(block) => Ok(Some(types::numeric::to_u64(&block.block_height)?)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+
+    #[tokio::test]
+    async fn test_delegated_pools_seeded() {
+        let db = init_seeded_db().await;
+        let alice = near_primitives::types::AccountId::from_str("alice.near").unwrap();
+        let bob = near_primitives::types::AccountId::from_str("bob.near").unwrap();
+
+        let pools = get_delegated_pools(&db.pool, &alice, 1600000000000000103)
+            .await
+            .unwrap();
+        assert_eq!(
+            pools,
+            vec![near_primitives::types::AccountId::from_str("pool.poolv1.near").unwrap()]
+        );
+        let pools = get_delegated_pools(&db.pool, &alice, 1600000000000000101)
+            .await
+            .unwrap();
+        assert!(pools.is_empty());
+        let pools = get_delegated_pools(&db.pool, &bob, 1600000000000000103)
+            .await
+            .unwrap();
+        assert!(pools.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delegation_mock_rpc() {
+        let (server, rpc_client) = init_mock_rpc().await;
+        mock_function_call(
+            &server,
+            "pool.poolv1.near",
+            "get_account",
+            serde_json::json!({
+                "account_id": "alice.near",
+                "unstaked_balance": "0",
+                "staked_balance": "1000",
+                "can_withdraw": true,
+            }),
+        )
+        .await;
+        mock_function_call(
+            &server,
+            "pool.poolv1.near",
+            "get_reward_fee_fraction",
+            serde_json::json!({ "numerator": 10, "denominator": 100 }),
+        )
+        .await;
+        // Nothing is pending, so we don't go to the DB
+        let pool = sqlx::PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+        let account = near_primitives::types::AccountId::from_str("alice.near").unwrap();
+        let pool_id = near_primitives::types::AccountId::from_str("pool.poolv1.near").unwrap();
+
+        let delegation = get_delegation(&pool, &rpc_client, &get_block(), &account, &pool_id, 10.0)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(delegation.staked_balance.0, 1000);
+        assert_eq!(delegation.unstake_release_epoch_height, None);
+        assert!((delegation.fee - 10.0).abs() < 1e-9);
+        assert!((delegation.apy - 9.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,9 @@
+mod delegation;
+mod models;
+mod network;
+mod pools;
+mod supply;
+
+pub(crate) use delegation::get_staking_summary;
+pub(crate) use pools::get_staking_pools;
+pub(crate) use supply::get_supply_stats;
@@ -0,0 +1,22 @@
+use crate::BigDecimal;
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct BlockHeight {
+    pub block_height: BigDecimal,
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct TotalSupply {
+    pub total_supply: BigDecimal,
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct Reward {
+    pub account_id: String,
+    pub reward: BigDecimal,
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct Balance {
+    pub balance: BigDecimal,
+}
@@ -0,0 +1,99 @@
+use serde::Deserialize;
+
+use crate::{db_helpers, errors, rpc_helpers, types};
+
+// See https://nomicon.io/Economics/Economic
+pub(crate) const MAX_INFLATION_RATE: f64 = 0.05;
+const PROTOCOL_TREASURY_SHARE: f64 = 0.1;
+// See `NUM_EPOCHS_TO_UNLOCK` at https://github.com/near/core-contracts/tree/master/staking-pool
+pub(crate) const NUM_EPOCHS_TO_UNLOCK: u64 = 4;
+
+#[derive(Deserialize, Debug)]
+struct RewardFeeFraction {
+    numerator: u32,
+    denominator: u32,
+}
+
+pub(crate) async fn get_total_supply(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    block_height: u64,
+) -> crate::Result<u128> {
+    match db_helpers::select_retry_or_panic::<super::models::TotalSupply>(
+        pool,
+        "SELECT total_supply FROM blocks WHERE block_height = $1::numeric(20, 0)",
+        &[block_height.to_string()],
+    )
+    .await?
+    .first()
+    {
+        None => Err(errors::ErrorKind::BlockNotFound {
+            block_id: block_height.to_string(),
+        }
+        .into()),
+        Some(supply) => types::numeric::to_u128(&supply.total_supply),
+    }
+}
+
+/// The yearly reward of the validators (before the pool fees), in percents.
+/// We assume all the validators are online, so it's the upper bound
+pub(crate) fn get_network_apy(total_supply: u128, total_stake: u128) -> f64 {
+    if total_stake == 0 {
+        return 0.0;
+    }
+    MAX_INFLATION_RATE * (1.0 - PROTOCOL_TREASURY_SHARE) * (total_supply as f64)
+        / (total_stake as f64)
+        * 100.0
+}
+
+/// Part of the rewards the pool owner takes, from 0 to 1
+pub(crate) async fn get_reward_fee(
+    rpc_client: &dyn rpc_helpers::RpcApi,
+    pool_id: &near_primitives::types::AccountId,
+    block_height: u64,
+) -> crate::Result<f64> {
+    let request = rpc_helpers::get_function_call_request(
+        block_height,
+        pool_id.clone(),
+        "get_reward_fee_fraction",
+        serde_json::json!({}),
+    );
+    let response = rpc_helpers::wrapped_call(rpc_client, request, block_height, pool_id).await?;
+    let fee = serde_json::from_slice::<RewardFeeFraction>(&response.result)?;
+    if fee.denominator == 0 {
+        return Ok(0.0);
+    }
+    Ok(fee.numerator as f64 / fee.denominator as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_network_apy() {
+        // 1B supply, 400M staked
+        let apy = get_network_apy(10u128.pow(33), 4 * 10u128.pow(32));
+        assert!((apy - 11.25).abs() < 1e-9);
+        assert_eq!(get_network_apy(10u128.pow(33), 0), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_reward_fee_mock_rpc() {
+        let (server, rpc_client) = init_mock_rpc().await;
+        mock_function_call(
+            &server,
+            "pool.poolv1.near",
+            "get_reward_fee_fraction",
+            serde_json::json!({ "numerator": 7, "denominator": 100 }),
+        )
+        .await;
+        let pool_id = near_primitives::types::AccountId::from_str("pool.poolv1.near").unwrap();
+
+        let fee = get_reward_fee(&rpc_client, &pool_id, get_block().height)
+            .await
+            .unwrap();
+        assert!((fee - 0.07).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use futures::{StreamExt, TryStreamExt};
+
+use crate::modules::staking;
+use crate::{balances_schema, db_helpers, rpc_helpers, types};
+
+// Each pool needs several view calls, we don't want to send hundreds of them at once
+const POOL_REQUESTS_CONCURRENCY: usize = 16;
+const REWARDS_WINDOW_NANOS: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+
+pub(crate) async fn get_staking_pools(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    balances_db: Option<&balances_schema::BalancesDb>,
+    rpc_client: &dyn rpc_helpers::RpcApi,
+    block: &db_helpers::Block,
+) -> crate::Result<staking::schemas::StakingPoolsResponse> {
+    let epoch_info = rpc_helpers::get_epoch_info(rpc_client, block.height).await?;
+    let total_stake: u128 = epoch_info
+        .current_validators
+        .iter()
+        .map(|validator| validator.stake)
+        .sum();
+    let network_apy = super::network::get_network_apy(
+        super::network::get_total_supply(pool, block.height).await?,
+        total_stake,
+    );
+    let rewards = match balances_db {
+        Some(balances_db) => Some(get_rewards(balances_db, block.timestamp).await?),
+        None => None,
+    };
+
+    let mut validators = epoch_info.current_validators;
+    validators.sort_by(|a, b| b.stake.cmp(&a.stake));
+    let pools = futures::stream::iter(validators.into_iter().map(|validator| {
+        let historical_apy = rewards.as_ref().map(|rewards| {
+            get_historical_apy(
+                rewards
+                    .get(validator.account_id.as_str())
+                    .copied()
+                    .unwrap_or_default(),
+                validator.stake,
+            )
+        });
+        let uptime = get_uptime(
+            validator.num_produced_blocks + validator.num_produced_chunks,
+            validator.num_expected_blocks + validator.num_expected_chunks,
+        );
+        async move {
+            // Validator is not always the staking pool contract, so these could be missing
+            let fee =
+                super::network::get_reward_fee(rpc_client, &validator.account_id, block.height)
+                    .await
+                    .ok();
+            let delegators_count =
+                get_delegators_count(rpc_client, &validator.account_id, block.height)
+                    .await
+                    .ok();
+            crate::Result::Ok(staking::schemas::StakingPool {
+                pool_account_id: validator.account_id.into(),
+                total_staked_balance: validator.stake.into(),
+                fee: fee.map(|fee| fee * 100.0),
+                delegators_count,
+                uptime: uptime * 100.0,
+                apy: network_apy * uptime * (1.0 - fee.unwrap_or_default()),
+                historical_apy,
+            })
+        }
+    }))
+    .buffered(POOL_REQUESTS_CONCURRENCY)
+    .try_collect()
+    .await?;
+
+    Ok(staking::schemas::StakingPoolsResponse {
+        pools,
+        epoch_height: epoch_info.epoch_height.into(),
+        meta: types::ResponseMeta::new(&block),
+    })
+}
+
+async fn get_delegators_count(
+    rpc_client: &dyn rpc_helpers::RpcApi,
+    pool_id: &near_primitives::types::AccountId,
+    block_height: u64,
+) -> crate::Result<u64> {
+    let request = rpc_helpers::get_function_call_request(
+        block_height,
+        pool_id.clone(),
+        "get_number_of_accounts",
+        serde_json::json!({}),
+    );
+    let response = rpc_helpers::wrapped_call(rpc_client, request, block_height, pool_id).await?;
+    Ok(serde_json::from_slice::<u64>(&response.result)?)
+}
+
+/// Rewards of all the validators for the last week
+async fn get_rewards(
+    balances_db: &balances_schema::BalancesDb,
+    block_timestamp: u64,
+) -> crate::Result<HashMap<String, u128>> {
+    let query = format!(
+        r"
+        SELECT affected_account_id account_id, sum(delta_nonstaked_amount + delta_staked_amount) reward
+        FROM {}
+        WHERE cause IN ('VALIDATORS_REWARD', 'VALIDATOR_REWARD')
+            AND block_timestamp > $1::numeric(20, 0)
+            AND block_timestamp <= $2::numeric(20, 0)
+        GROUP BY affected_account_id
+    ",
+        balances_db.balance_changes()
+    );
+    let rewards = db_helpers::select_retry_or_panic::<super::models::Reward>(
+        &balances_db.pool,
+        &query,
+        &[
+            block_timestamp
+                .saturating_sub(REWARDS_WINDOW_NANOS)
+                .to_string(),
+            block_timestamp.to_string(),
+        ],
+    )
+    .await?;
+
+    let mut result = HashMap::new();
+    for reward in rewards {
+        if near_primitives::types::AccountId::from_str(&reward.account_id).is_ok() {
+            result.insert(reward.account_id, types::numeric::to_u128(&reward.reward)?);
+        }
+    }
+    Ok(result)
+}
+
+/// Part of the blocks and chunks produced by the validator in the current epoch, from 0 to 1
+fn get_uptime(produced: u64, expected: u64) -> f64 {
+    if expected == 0 {
+        return 1.0;
+    }
+    produced as f64 / expected as f64
+}
+
+/// The last week rewards, extrapolated to the year, in percents
+fn get_historical_apy(weekly_reward: u128, stake: u128) -> f64 {
+    if stake == 0 {
+        return 0.0;
+    }
+    weekly_reward as f64 / stake as f64 * (365.0 / 7.0) * 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+
+    #[test]
+    fn test_uptime_and_historical_apy() {
+        assert_eq!(get_uptime(90, 100), 0.9);
+        assert_eq!(get_uptime(0, 0), 1.0);
+        assert!((get_historical_apy(7, 365) - 100.0).abs() < 1e-9);
+        assert_eq!(get_historical_apy(7, 0), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_rewards_seeded() {
+        let db = init_seeded_db().await;
+
+        let rewards = get_rewards(&db.balances_db(), 1600000000000000103)
+            .await
+            .unwrap();
+        assert_eq!(rewards.get("pool.poolv1.near"), Some(&120));
+        assert_eq!(rewards.get("alice.near"), Some(&50));
+    }
+}
@@ -0,0 +1,104 @@
+use crate::modules::staking;
+use crate::{balances_schema, db_helpers, errors, rpc_helpers, types};
+
+const LOCKUP_ACCOUNTS_PATTERN: &str = "%.lockup.near";
+
+pub(crate) async fn get_supply_stats(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    balances_db: Option<&balances_schema::BalancesDb>,
+    rpc_client: &dyn rpc_helpers::RpcApi,
+    block: &db_helpers::Block,
+) -> crate::Result<staking::schemas::SupplyStatsResponse> {
+    let epoch_info = rpc_helpers::get_epoch_info(rpc_client, block.height).await?;
+    let total_supply = super::network::get_total_supply(pool, block.height).await?;
+    let total_staked: u128 = epoch_info
+        .current_validators
+        .iter()
+        .map(|validator| validator.stake)
+        .sum();
+    // The smallest stake which got the seat. The real seat price is a bit lower,
+    // but this one is enough to get the seat for sure
+    let seat_price = epoch_info
+        .current_validators
+        .iter()
+        .map(|validator| validator.stake)
+        .min()
+        .unwrap_or_default();
+    let circulating_supply = match balances_db {
+        Some(balances_db) => {
+            let locked = get_lockups_balance(balances_db, block.timestamp).await?;
+            Some(total_supply.saturating_sub(locked).into())
+        }
+        None => None,
+    };
+
+    Ok(staking::schemas::SupplyStatsResponse {
+        total_supply: total_supply.into(),
+        circulating_supply,
+        total_staked_balance: total_staked.into(),
+        seat_price: seat_price.into(),
+        max_inflation_rate: super::network::MAX_INFLATION_RATE * 100.0,
+        network_apy: super::network::get_network_apy(total_supply, total_staked),
+        epoch_height: epoch_info.epoch_height.into(),
+        meta: types::ResponseMeta::new(&block),
+    })
+}
+
+/// Sum of the latest balances of all the lockup accounts
+async fn get_lockups_balance(
+    balances_db: &balances_schema::BalancesDb,
+    block_timestamp: u64,
+) -> crate::Result<u128> {
+    let query = format!(
+        r"
+        SELECT coalesce(sum(balance), 0) balance
+        FROM (
+            SELECT DISTINCT ON (affected_account_id)
+                absolute_nonstaked_amount + absolute_staked_amount balance
+            FROM {}
+            WHERE affected_account_id LIKE $1
+                AND block_timestamp <= $2::numeric(20, 0)
+            ORDER BY affected_account_id, block_timestamp DESC
+        ) lockups
+    ",
+        balances_db.balance_changes()
+    );
+    match db_helpers::select_retry_or_panic::<super::models::Balance>(
+        &balances_db.pool,
+        &query,
+        &[
+            LOCKUP_ACCOUNTS_PATTERN.to_string(),
+            block_timestamp.to_string(),
+        ],
+    )
+    .await?
+    .first()
+    {
+        None => {
+            Err(errors::ErrorKind::DBError("Aggregation query returned nothing".to_string()).into())
+        }
+        Some(balance) => types::numeric::to_u128(&balance.balance),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+
+    #[tokio::test]
+    async fn test_lockups_balance_seeded() {
+        let db = init_seeded_db().await;
+
+        // Only the latest balance of each lockup is counted
+        let balance = get_lockups_balance(&db.balances_db(), 1600000000000000103)
+            .await
+            .unwrap();
+        assert_eq!(balance, 700 + 5000);
+
+        let balance = get_lockups_balance(&db.balances_db(), 1600000000000000100)
+            .await
+            .unwrap();
+        assert_eq!(balance, 1000);
+    }
+}
@@ -0,0 +1,16 @@
+use paperclip::actix::web;
+
+pub(crate) mod data_provider;
+mod resources;
+pub(crate) mod schemas;
+
+pub(crate) fn register_services(app: &mut web::ServiceConfig) {
+    app.service(
+        web::resource("/accounts/{account_id}/staking/summary")
+            .route(web::get().to(resources::get_staking_summary)),
+    )
+    .service(
+        web::resource("/staking-pools").route(web::get().to(resources::get_staking_pools)),
+    )
+    .service(web::resource("/stats/supply").route(web::get().to(resources::get_supply_stats)));
+}
@@ -0,0 +1,73 @@
+use paperclip::actix::{
+    api_v2_operation,
+    web::{self, Json},
+};
+
+use crate::{data_source, modules, types};
+
+use super::schemas;
+
+#[api_v2_operation(tags(Staking))]
+/// Get user's staking summary
+///
+/// This endpoint returns the staked and unstaked balances of the given account_id
+/// in all the staking pools, for the given timestamp/block_height.
+/// Pending unstakes have the epoch when the balance becomes available for withdrawal.
+///
+/// **Limitations**
+/// * We find the pools by `deposit_and_stake`, `stake`, `stake_all` calls made by the account itself.
+///   Stake made through lockup contracts is not included.
+/// * APY is an estimation: we assume all the validators are online and take the current pool fee.
+pub async fn get_staking_summary(
+    _query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
+    request: web::Path<schemas::StakingSummaryRequest>,
+    block_params: web::Query<types::query_params::BlockParams>,
+) -> crate::Result<Json<schemas::StakingSummaryResponse>> {
+    let block = data_source.get_block_from_params(&block_params).await?;
+    modules::check_account_exists(&data_source, &request.account_id.0, block.timestamp).await?;
+
+    Ok(Json(
+        data_source
+            .get_staking_summary(&block, &request.account_id.0)
+            .await?,
+    ))
+}
+
+#[api_v2_operation(tags(Staking))]
+/// Get staking pools
+///
+/// This endpoint returns the validators of the epoch for the given timestamp/block_height,
+/// with their stake, fee, number of delegators, uptime and APY estimations.
+///
+/// **Limitations**
+/// * Only the current validators are included, the pools without the seat are not shown.
+pub async fn get_staking_pools(
+    _query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
+    block_params: web::Query<types::query_params::BlockParams>,
+) -> crate::Result<Json<schemas::StakingPoolsResponse>> {
+    let block = data_source.get_block_from_params(&block_params).await?;
+
+    Ok(Json(data_source.get_staking_pools(&block).await?))
+}
+
+#[api_v2_operation(tags(Staking))]
+/// Get NEAR supply stats
+///
+/// This endpoint returns the total and circulating supply, total stake, seat price and inflation
+/// for the given timestamp/block_height.
+///
+/// **Limitations**
+/// * Circulating supply excludes the whole balance of `*.lockup.near` accounts, including the unlocked part.
+///   The tokens staked from the lockups are in the staking pools, so they are counted as circulating.
+/// * Seat price is the smallest stake of the current validators, not the exact protocol value.
+pub async fn get_supply_stats(
+    _query: types::query_params::ValidatedQuery,
+    data_source: web::Data<data_source::DataSourceRef>,
+    block_params: web::Query<types::query_params::BlockParams>,
+) -> crate::Result<Json<schemas::SupplyStatsResponse>> {
+    let block = data_source.get_block_from_params(&block_params).await?;
+
+    Ok(Json(data_source.get_supply_stats(&block).await?))
+}
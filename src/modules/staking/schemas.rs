@@ -0,0 +1,89 @@
+use paperclip::actix::Apiv2Schema;
+
+use crate::types;
+
+// *** Requests ***
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct StakingSummaryRequest {
+    pub account_id: types::AccountId,
+}
+
+// *** Responses ***
+
+/// All the delegations of the account, summed up across the staking pools.
+/// The balances are in yoctoNEAR.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct StakingSummaryResponse {
+    pub total_staked_balance: types::U128,
+    /// Includes the balance which is not yet available for withdrawal
+    pub total_unstaked_balance: types::U128,
+    /// Average of the pools APY, weighted by the staked balance. Estimation, in percents
+    pub apy: f64,
+    pub delegations: Vec<Delegation>,
+    pub epoch_height: types::U64,
+    pub meta: types::ResponseMeta,
+}
+
+/// Validators of the current epoch, the biggest stake goes first.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct StakingPoolsResponse {
+    pub pools: Vec<StakingPool>,
+    pub epoch_height: types::U64,
+    pub meta: types::ResponseMeta,
+}
+
+/// NEAR tokenomics for the given block. All the amounts are in yoctoNEAR.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct SupplyStatsResponse {
+    pub total_supply: types::U128,
+    /// Total supply minus the balances of the lockup accounts.
+    /// Null if the server does not have the balances DB
+    pub circulating_supply: Option<types::U128>,
+    /// Stake of the current epoch validators
+    pub total_staked_balance: types::U128,
+    /// The smallest stake among the current epoch validators
+    pub seat_price: types::U128,
+    /// Yearly, in percents. The real inflation is lower if the validators are offline
+    pub max_inflation_rate: f64,
+    /// The yearly reward of the validators before the pool fees. Estimation, in percents
+    pub network_apy: f64,
+    pub epoch_height: types::U64,
+    pub meta: types::ResponseMeta,
+}
+
+// ---
+
+/// The balances are in yoctoNEAR
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct Delegation {
+    pub pool_account_id: types::AccountId,
+    pub staked_balance: types::U128,
+    pub unstaked_balance: types::U128,
+    pub can_withdraw: bool,
+    /// Not null if there is the unstaked balance which is not yet available for withdrawal.
+    /// Estimated from the last unstake call
+    pub unstake_release_epoch_height: Option<types::U64>,
+    /// Pool fee, in percents
+    pub fee: f64,
+    /// Network APY minus the pool fee. Estimation, in percents
+    pub apy: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct StakingPool {
+    pub pool_account_id: types::AccountId,
+    /// yoctoNEAR
+    pub total_staked_balance: types::U128,
+    /// Pool fee, in percents. Null if the validator is not the staking pool contract
+    pub fee: Option<f64>,
+    /// Null if the validator is not the staking pool contract
+    pub delegators_count: Option<u64>,
+    /// Produced blocks and chunks in the current epoch, in percents
+    pub uptime: f64,
+    /// Network APY, adjusted by the uptime and the pool fee. Estimation, in percents
+    pub apy: f64,
+    /// The rewards of the last 7 days extrapolated to the year, in percents.
+    /// Null if the server does not have the balances DB
+    pub historical_apy: Option<f64>,
+}
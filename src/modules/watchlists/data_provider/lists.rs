@@ -0,0 +1,208 @@
+use std::str::FromStr;
+
+use crate::modules::watchlists;
+use crate::{db_helpers, errors, types};
+
+const WATCHLIST_COLUMNS: &str = r"
+    id, name, account_ids, created_at_timestamp, updated_at_timestamp
+";
+
+fn to_db_account_ids(input: &watchlists::schemas::WatchlistInput) -> Vec<String> {
+    input
+        .account_ids
+        .iter()
+        .map(|account_id| account_id.0.to_string())
+        .collect()
+}
+
+pub(crate) async fn create_watchlist(
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    key_id: &str,
+    input: &watchlists::schemas::WatchlistInput,
+    timestamp: u64,
+) -> crate::Result<watchlists::schemas::Watchlist> {
+    let watchlist_id: i64 = sqlx::query_scalar(
+        r"INSERT INTO watchlists (key_id, name, account_ids, created_at_timestamp, updated_at_timestamp)
+          VALUES ($1, $2, $3, $4::numeric(20, 0), $4::numeric(20, 0))
+          RETURNING id",
+    )
+    .bind(key_id)
+    .bind(&input.name)
+    .bind(to_db_account_ids(input))
+    .bind(timestamp.to_string())
+    .fetch_one(pool_aux)
+    .await
+    .map_err(|err| errors::ErrorKind::DBError(err.to_string()))?;
+
+    Ok(watchlists::schemas::Watchlist {
+        watchlist_id: types::U64(watchlist_id as u64),
+        name: input.name.clone(),
+        account_ids: input.account_ids.clone(),
+        created_at_timestamp_nanos: timestamp.into(),
+        updated_at_timestamp_nanos: timestamp.into(),
+    })
+}
+
+/// The watchlists of the given API key, the oldest go first
+pub(crate) async fn get_watchlists(
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    key_id: &str,
+) -> crate::Result<Vec<watchlists::schemas::Watchlist>> {
+    let query = format!(
+        "SELECT {} FROM watchlists WHERE key_id = $1 ORDER BY id",
+        WATCHLIST_COLUMNS
+    );
+    let lists = db_helpers::select_retry_or_panic::<super::models::WatchlistInfo>(
+        pool_aux,
+        &query,
+        &[key_id.to_string()],
+    )
+    .await?;
+    lists.into_iter().map(|list| list.try_into()).collect()
+}
+
+/// `None` if the API key has no such watchlist
+pub(crate) async fn get_watchlist(
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    key_id: &str,
+    watchlist_id: u64,
+) -> crate::Result<Option<watchlists::schemas::Watchlist>> {
+    let query = format!(
+        "SELECT {} FROM watchlists WHERE key_id = $1 AND id = $2::bigint",
+        WATCHLIST_COLUMNS
+    );
+    let lists = db_helpers::select_retry_or_panic::<super::models::WatchlistInfo>(
+        pool_aux,
+        &query,
+        &[key_id.to_string(), watchlist_id.to_string()],
+    )
+    .await?;
+    lists
+        .into_iter()
+        .next()
+        .map(|list| list.try_into())
+        .transpose()
+}
+
+/// Replaces the name and the accounts, `None` if the API key has no such watchlist
+pub(crate) async fn update_watchlist(
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    key_id: &str,
+    watchlist_id: u64,
+    input: &watchlists::schemas::WatchlistInput,
+    timestamp: u64,
+) -> crate::Result<Option<watchlists::schemas::Watchlist>> {
+    let result = sqlx::query(
+        r"UPDATE watchlists SET name = $3, account_ids = $4, updated_at_timestamp = $5::numeric(20, 0)
+          WHERE key_id = $1 AND id = $2",
+    )
+    .bind(key_id)
+    .bind(watchlist_id as i64)
+    .bind(&input.name)
+    .bind(to_db_account_ids(input))
+    .bind(timestamp.to_string())
+    .execute(pool_aux)
+    .await
+    .map_err(|err| errors::ErrorKind::DBError(err.to_string()))?;
+    if result.rows_affected() == 0 {
+        return Ok(None);
+    }
+    get_watchlist(pool_aux, key_id, watchlist_id).await
+}
+
+/// Returns `false` if the API key has no such watchlist
+pub(crate) async fn delete_watchlist(
+    pool_aux: &sqlx::Pool<sqlx::Postgres>,
+    key_id: &str,
+    watchlist_id: u64,
+) -> crate::Result<bool> {
+    let result = sqlx::query("DELETE FROM watchlists WHERE key_id = $1 AND id = $2")
+        .bind(key_id)
+        .bind(watchlist_id as i64)
+        .execute(pool_aux)
+        .await
+        .map_err(|err| errors::ErrorKind::DBError(err.to_string()))?;
+    Ok(result.rows_affected() > 0)
+}
+
+impl TryFrom<super::models::WatchlistInfo> for watchlists::schemas::Watchlist {
+    type Error = errors::Error;
+
+    fn try_from(info: super::models::WatchlistInfo) -> crate::Result<Self> {
+        Ok(Self {
+            watchlist_id: types::U64(info.id as u64),
+            name: info.name,
+            account_ids: info
+                .account_ids
+                .iter()
+                .map(|account_id| {
+                    near_primitives::types::AccountId::from_str(account_id)
+                        .map(types::AccountId::from)
+                })
+                .collect::<Result<Vec<types::AccountId>, _>>()?,
+            created_at_timestamp_nanos: types::numeric::to_u64(&info.created_at_timestamp)?.into(),
+            updated_at_timestamp_nanos: types::numeric::to_u64(&info.updated_at_timestamp)?.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::tests::*;
+
+    #[tokio::test]
+    async fn test_watchlists_seeded() {
+        let db = init_seeded_db().await;
+        let mut input = watchlists::schemas::WatchlistInput {
+            name: "whales".to_string(),
+            account_ids: vec![near_primitives::types::AccountId::from_str("alice.near")
+                .unwrap()
+                .into()],
+        };
+        let watchlist = create_watchlist(&db.pool, "key_a", &input, 1)
+            .await
+            .unwrap();
+        assert_eq!(
+            get_watchlists(&db.pool, "key_a").await.unwrap(),
+            vec![watchlist.clone()]
+        );
+        assert!(get_watchlists(&db.pool, "key_b").await.unwrap().is_empty());
+
+        // Only the owner can see, change and delete it
+        input.account_ids.push(
+            near_primitives::types::AccountId::from_str("token.near")
+                .unwrap()
+                .into(),
+        );
+        assert!(
+            update_watchlist(&db.pool, "key_b", watchlist.watchlist_id.0, &input, 2)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let updated = update_watchlist(&db.pool, "key_a", watchlist.watchlist_id.0, &input, 2)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(updated.account_ids, input.account_ids);
+        assert_eq!(updated.created_at_timestamp_nanos, types::U64(1));
+        assert_eq!(updated.updated_at_timestamp_nanos, types::U64(2));
+        assert!(get_watchlist(&db.pool, "key_b", watchlist.watchlist_id.0)
+            .await
+            .unwrap()
+            .is_none());
+
+        assert!(
+            !delete_watchlist(&db.pool, "key_b", watchlist.watchlist_id.0)
+                .await
+                .unwrap()
+        );
+        assert!(
+            delete_watchlist(&db.pool, "key_a", watchlist.watchlist_id.0)
+                .await
+                .unwrap()
+        );
+        assert!(get_watchlists(&db.pool, "key_a").await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,6 @@
+mod lists;
+mod models;
+
+pub(crate) use lists::{
+    create_watchlist, delete_watchlist, get_watchlist, get_watchlists, update_watchlist,
+};
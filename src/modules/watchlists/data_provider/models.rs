@@ -0,0 +1,10 @@
+use crate::BigDecimal;
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct WatchlistInfo {
+    pub id: i64,
+    pub name: String,
+    pub account_ids: Vec<String>,
+    pub created_at_timestamp: BigDecimal,
+    pub updated_at_timestamp: BigDecimal,
+}
@@ -0,0 +1,135 @@
+use paperclip::actix::web;
+
+pub(crate) mod data_provider;
+mod resources;
+pub(crate) mod schemas;
+
+pub(crate) fn register_services(app: &mut web::ServiceConfig) {
+    app.service(
+        web::resource("/watchlists")
+            .route(web::get().to(resources::get_watchlists))
+            .route(web::post().to(resources::create_watchlist)),
+    )
+    .service(
+        web::resource("/watchlists/{watchlist_id}")
+            .route(web::get().to(resources::get_watchlist))
+            .route(web::put().to(resources::update_watchlist))
+            .route(web::delete().to(resources::delete_watchlist)),
+    )
+    .service(
+        web::resource("/watchlists/{watchlist_id}/portfolio")
+            .route(web::get().to(resources::get_watchlist_portfolio)),
+    )
+    .service(
+        web::resource("/watchlists/{watchlist_id}/activity")
+            .route(web::get().to(resources::get_watchlist_activity)),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{api_keys, errors};
+    use std::str::FromStr;
+
+    fn account_id(account_id: &str) -> crate::types::AccountId {
+        near_primitives::types::AccountId::from_str(account_id)
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn test_validate_watchlist_input() {
+        let input = schemas::WatchlistInput {
+            name: "  whales ".to_string(),
+            account_ids: vec![
+                account_id("bob.near"),
+                account_id("alice.near"),
+                account_id("bob.near"),
+            ],
+        };
+        assert_eq!(
+            input.validated().unwrap(),
+            schemas::WatchlistInput {
+                name: "whales".to_string(),
+                account_ids: vec![account_id("bob.near"), account_id("alice.near")],
+            }
+        );
+
+        let mut invalid = input.clone();
+        invalid.name = " ".to_string();
+        assert!(invalid.validated().is_err());
+        invalid.name = "x".repeat(schemas::WatchlistInput::MAX_NAME_LEN + 1);
+        assert!(invalid.validated().is_err());
+
+        let mut invalid = input;
+        invalid.account_ids = vec![];
+        assert!(invalid.validated().is_err());
+        invalid.account_ids = vec![account_id("alice.near"); 26];
+        assert!(invalid.validated().is_err());
+    }
+
+    /// The watchlist ids are easy to guess, the cached pages of one key should never reach the other one
+    #[actix_web::test]
+    async fn test_watchlist_pages_are_not_cached_across_keys() {
+        use paperclip::actix::OpenApiExt;
+
+        // Only alice has the watchlist
+        let fixtures_dir =
+            std::env::temp_dir().join(format!("watchlists-cache-{}", std::process::id()));
+        std::fs::create_dir_all(fixtures_dir.join("get_watchlist")).unwrap();
+        let bob_key_id = near_primitives::hash::hash(b"bob-key").to_string();
+        std::fs::write(
+            fixtures_dir
+                .join("get_watchlist")
+                .join(format!("{}_1.json", bob_key_id)),
+            "null",
+        )
+        .unwrap();
+        let ctx = crate::ServerContext::mock(Some(fixtures_dir.clone()));
+        let app = actix_web::test::init_service(
+            actix_web::App::new()
+                .wrap(crate::response_cache::ResponseCache::new(Default::default()))
+                .wrap_api()
+                .configure(|app| crate::configure(app, &ctx))
+                .build(),
+        )
+        .await;
+
+        let cursor = crate::types::query_params::HistoryCursor {
+            block_height: 100,
+            block_timestamp: 1600000000000000000,
+            block_hash: None,
+            before_event: crate::types::query_params::EventIndex {
+                block_timestamp: 1600000000000000000,
+                shard_id: 0,
+                index_in_chunk: 0,
+            },
+        }
+        .encode();
+        for uri in [
+            "/watchlists/1/portfolio?block_height=100".to_string(),
+            format!("/watchlists/1/activity?cursor={}", cursor),
+        ] {
+            let request = actix_web::test::TestRequest::get()
+                .uri(&uri)
+                .insert_header((api_keys::API_KEY_HEADER, "alice-key"))
+                .to_request();
+            let response = actix_web::test::call_service(&app, request).await;
+            if uri.contains("portfolio") {
+                assert!(response.status().is_success());
+            }
+
+            let request = actix_web::test::TestRequest::get()
+                .uri(&uri)
+                .insert_header((api_keys::API_KEY_HEADER, "bob-key"))
+                .to_request();
+            let error: errors::Error =
+                actix_web::test::read_body_json(actix_web::test::call_service(&app, request).await)
+                    .await;
+            assert_eq!(error.error_code, "INVALID_INPUT");
+            assert!(error.message.contains("watchlist 1 is not found"));
+        }
+        std::fs::remove_dir_all(fixtures_dir).unwrap();
+    }
+}
@@ -0,0 +1,218 @@
+use paperclip::actix::{
+    api_v2_operation,
+    web::{self, Json},
+};
+
+use crate::modules::coin;
+use crate::{allow_list, api_keys, data_source, errors, modules, types};
+
+use super::schemas;
+
+/// Every watchlist is the aggregate query of up to 25 accounts, so the keys can't have too many of them
+const MAX_WATCHLISTS_PER_KEY: usize = 100;
+
+fn watchlist_not_found(watchlist_id: u64) -> errors::Error {
+    errors::ErrorKind::InvalidInput(format!("watchlist {} is not found", watchlist_id)).into()
+}
+
+async fn get_own_watchlist(
+    request: &actix_web::HttpRequest,
+    data_source: &data_source::DataSourceRef,
+    watchlist_id: u64,
+) -> crate::Result<schemas::Watchlist> {
+    let key_id = api_keys::require_key_id(request.headers(), "the watchlists")?;
+    data_source
+        .get_watchlist(&key_id, watchlist_id)
+        .await?
+        .ok_or_else(|| watchlist_not_found(watchlist_id))
+}
+
+#[api_v2_operation(tags(Watchlists))]
+/// Get the watchlists
+///
+/// This endpoint returns the watchlists of the API key, the oldest go first.
+/// Requires the API key in `X-API-Key` header.
+pub async fn get_watchlists(
+    request: actix_web::HttpRequest,
+    data_source: web::Data<data_source::DataSourceRef>,
+) -> crate::Result<Json<schemas::WatchlistsResponse>> {
+    let key_id = api_keys::require_key_id(request.headers(), "the watchlists")?;
+
+    Ok(Json(schemas::WatchlistsResponse {
+        watchlists: data_source.get_watchlists(&key_id).await?,
+    }))
+}
+
+#[api_v2_operation(tags(Watchlists))]
+/// Create the watchlist
+///
+/// This endpoint saves the named list of the accounts and the contracts,
+/// use `/watchlists/{watchlist_id}/portfolio` and `/watchlists/{watchlist_id}/activity` to watch them.
+/// Requires the API key in `X-API-Key` header.
+///
+/// **Limitations**
+/// * Up to 25 accounts per watchlist, up to 100 watchlists per API key.
+/// * We do not check that the accounts exist, the aggregate views fail on the missing ones.
+pub async fn create_watchlist(
+    request: actix_web::HttpRequest,
+    data_source: web::Data<data_source::DataSourceRef>,
+    input: web::Json<schemas::WatchlistInput>,
+) -> crate::Result<Json<schemas::WatchlistResponse>> {
+    let key_id = api_keys::require_key_id(request.headers(), "the watchlists")?;
+    let input = input.validated()?;
+    if data_source.get_watchlists(&key_id).await?.len() >= MAX_WATCHLISTS_PER_KEY {
+        return Err(errors::ErrorKind::InvalidInput(format!(
+            "the API key can't have more than {} watchlists",
+            MAX_WATCHLISTS_PER_KEY
+        ))
+        .into());
+    }
+
+    Ok(Json(schemas::WatchlistResponse {
+        watchlist: data_source.create_watchlist(&key_id, &input).await?,
+    }))
+}
+
+#[api_v2_operation(tags(Watchlists))]
+/// Get the watchlist
+///
+/// This endpoint returns the watchlist of the API key.
+/// Requires the API key in `X-API-Key` header.
+pub async fn get_watchlist(
+    request: actix_web::HttpRequest,
+    data_source: web::Data<data_source::DataSourceRef>,
+    watchlist_request: web::Path<schemas::WatchlistRequest>,
+) -> crate::Result<Json<schemas::WatchlistResponse>> {
+    Ok(Json(schemas::WatchlistResponse {
+        watchlist: get_own_watchlist(&request, &data_source, watchlist_request.watchlist_id.0)
+            .await?,
+    }))
+}
+
+#[api_v2_operation(tags(Watchlists))]
+/// Update the watchlist
+///
+/// This endpoint replaces the name and the accounts of the watchlist.
+/// Requires the API key in `X-API-Key` header.
+///
+/// **Limitations**
+/// * Up to 25 accounts per watchlist.
+pub async fn update_watchlist(
+    request: actix_web::HttpRequest,
+    data_source: web::Data<data_source::DataSourceRef>,
+    watchlist_request: web::Path<schemas::WatchlistRequest>,
+    input: web::Json<schemas::WatchlistInput>,
+) -> crate::Result<Json<schemas::WatchlistResponse>> {
+    let key_id = api_keys::require_key_id(request.headers(), "the watchlists")?;
+    let input = input.validated()?;
+    let watchlist_id = watchlist_request.watchlist_id.0;
+
+    Ok(Json(schemas::WatchlistResponse {
+        watchlist: data_source
+            .update_watchlist(&key_id, watchlist_id, &input)
+            .await?
+            .ok_or_else(|| watchlist_not_found(watchlist_id))?,
+    }))
+}
+
+#[api_v2_operation(tags(Watchlists))]
+/// Delete the watchlist
+///
+/// This endpoint removes the watchlist of the API key and returns the remaining ones.
+/// Requires the API key in `X-API-Key` header.
+pub async fn delete_watchlist(
+    request: actix_web::HttpRequest,
+    data_source: web::Data<data_source::DataSourceRef>,
+    watchlist_request: web::Path<schemas::WatchlistRequest>,
+) -> crate::Result<Json<schemas::WatchlistsResponse>> {
+    let key_id = api_keys::require_key_id(request.headers(), "the watchlists")?;
+    let watchlist_id = watchlist_request.watchlist_id.0;
+    if !data_source.delete_watchlist(&key_id, watchlist_id).await? {
+        return Err(watchlist_not_found(watchlist_id));
+    }
+
+    Ok(Json(schemas::WatchlistsResponse {
+        watchlists: data_source.get_watchlists(&key_id).await?,
+    }))
+}
+
+#[api_v2_operation(tags(Watchlists))]
+/// Get the portfolio of the watchlist
+///
+/// This endpoint returns the coin balances of all the accounts of the watchlist
+/// for the given timestamp/block_height, valued in USD, the same way as `/portfolio`.
+/// Requires the API key in `X-API-Key` header.
+///
+/// **Limitations**
+/// * Up to 100 coins of each account.
+/// * We sample the prices only for the tokens configured by the server operator, NEAR is valued by `wrap.near` price.
+///   The coins without the price have null `value_usd` and are not counted in the totals.
+/// * If the RPC call to some FT contract fails, it's listed in `failed_contracts`.
+pub async fn get_watchlist_portfolio(
+    _query: types::query_params::ValidatedQuery,
+    request: actix_web::HttpRequest,
+    data_source: web::Data<data_source::DataSourceRef>,
+    allow_list: web::Data<allow_list::ContractAllowList>,
+    watchlist_request: web::Path<schemas::WatchlistRequest>,
+    block_params: web::Query<types::query_params::BlockParams>,
+) -> crate::Result<Json<coin::schemas::PortfolioResponse>> {
+    let watchlist =
+        get_own_watchlist(&request, &data_source, watchlist_request.watchlist_id.0).await?;
+    let block = data_source.get_block_from_params(&block_params).await?;
+
+    Ok(Json(
+        coin::get_portfolio(&data_source, &allow_list, watchlist.account_ids, &block).await?,
+    ))
+}
+
+#[api_v2_operation(tags(Watchlists))]
+/// Get the activity of the watchlist
+///
+/// This endpoint returns NEAR balance changes of all the accounts of the watchlist merged together,
+/// recent changes go first. Pass `meta.next_cursor` of the response as `cursor` to get the next page.
+/// Requires the API key in `X-API-Key` header.
+///
+/// **Limitations**
+/// * For now, only NEAR balance changes are listed, use `/accounts/{account_id}/coins/{contract_account_id}/history`
+///   for FT history of the accounts.
+/// * We provide only up to `limit` items.
+pub async fn get_watchlist_activity(
+    request: actix_web::HttpRequest,
+    data_source: web::Data<data_source::DataSourceRef>,
+    watchlist_request: web::Path<schemas::WatchlistRequest>,
+    pagination_params: web::Query<types::query_params::HistoryPaginationParams>,
+) -> crate::Result<Json<schemas::WatchlistActivityResponse>> {
+    let watchlist =
+        get_own_watchlist(&request, &data_source, watchlist_request.watchlist_id.0).await?;
+    let (block, pagination) = modules::check_and_get_history_pagination_params(
+        &data_source,
+        &request,
+        pagination_params.0,
+    )
+    .await?;
+
+    // Every account gives up to `limit` items before the same event,
+    // so the first `limit` of the merged items are the right page
+    let mut activity = vec![];
+    for account_id in watchlist.account_ids {
+        modules::check_account_exists(&data_source, &account_id.0, block.timestamp).await?;
+        for (change, event_index) in data_source
+            .get_near_history(&account_id.0, &pagination)
+            .await?
+        {
+            let item = schemas::WatchlistActivityItem {
+                account_id: account_id.clone(),
+                change,
+            };
+            activity.push((item, event_index));
+        }
+    }
+    activity.sort_by(|(_, a), (_, b)| b.cmp(a));
+    activity.truncate(pagination.limit as usize);
+    let next_cursor = modules::next_history_cursor(&activity, &block, &pagination);
+
+    Ok(Json(schemas::WatchlistActivityResponse {
+        activity: modules::without_event_indexes(activity),
+        meta: types::ResponseMeta::new(&block).with_next_cursor(next_cursor),
+    }))
+}
@@ -0,0 +1,79 @@
+use paperclip::actix::Apiv2Schema;
+
+use crate::modules::coin;
+use crate::{errors, types};
+
+// *** Requests ***
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct WatchlistRequest {
+    pub watchlist_id: types::U64,
+}
+
+/// The body of create and update requests
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct WatchlistInput {
+    pub name: String,
+    /// The accounts and the contracts, up to 25. The duplicates are dropped
+    pub account_ids: Vec<types::AccountId>,
+}
+
+impl WatchlistInput {
+    pub(crate) const MAX_NAME_LEN: usize = 100;
+
+    /// Trims the name and drops the duplicated accounts, keeping the order
+    pub(crate) fn validated(&self) -> crate::Result<Self> {
+        let name = self.name.trim();
+        if name.is_empty() || name.chars().count() > Self::MAX_NAME_LEN {
+            return Err(errors::ErrorKind::InvalidInput(format!(
+                "name should have from 1 to {} characters",
+                Self::MAX_NAME_LEN
+            ))
+            .into());
+        }
+        // The lists should be small enough to get the portfolio of all of them
+        let account_ids = coin::schemas::PortfolioRequest {
+            account_ids: self.account_ids.clone(),
+        }
+        .account_ids()?;
+        Ok(Self {
+            name: name.to_string(),
+            account_ids,
+        })
+    }
+}
+
+// *** Responses ***
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct WatchlistsResponse {
+    pub watchlists: Vec<Watchlist>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct WatchlistResponse {
+    pub watchlist: Watchlist,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct Watchlist {
+    pub watchlist_id: types::U64,
+    pub name: String,
+    pub account_ids: Vec<types::AccountId>,
+    pub created_at_timestamp_nanos: types::U64,
+    pub updated_at_timestamp_nanos: types::U64,
+}
+
+/// NEAR balance changes of all the accounts of the watchlist, recent changes go first
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct WatchlistActivityResponse {
+    pub activity: Vec<WatchlistActivityItem>,
+    pub meta: types::ResponseMeta,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct WatchlistActivityItem {
+    /// The account of the watchlist which balance has changed
+    pub account_id: types::AccountId,
+    pub change: coin::schemas::HistoryItem,
+}
@@ -0,0 +1,118 @@
+//! Periodically takes the token prices from the DEX and stores them to `token_prices` table,
+//! so that `/nep141/{contract_account_id}/price/history` has something to show.
+use std::str::FromStr;
+
+use crate::modules::coin;
+use crate::{config, errors, rpc_helpers, types, BigDecimal};
+
+/// Never returns. Run it in the separate task, e.g. with `tokio::spawn`.
+/// `pool` should point to the DB with our own tables (see `migrations/`)
+pub async fn run(
+    pool: sqlx::Pool<sqlx::Postgres>,
+    rpc_client: near_jsonrpc_client::JsonRpcClient,
+    config: config::PriceSamplerConfig,
+) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(config.interval_secs));
+    loop {
+        interval.tick().await;
+        if let Err(err) = sample_prices(&pool, &rpc_client, &config).await {
+            tracing::warn!(
+                target: crate::LOGGER_MSG,
+                "Failed to sample token prices: {}",
+                err
+            );
+        }
+    }
+}
+
+async fn sample_prices(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    rpc_client: &near_jsonrpc_client::JsonRpcClient,
+    config: &config::PriceSamplerConfig,
+) -> crate::Result<()> {
+    let block = rpc_client
+        .call(near_jsonrpc_client::methods::block::RpcBlockRequest {
+            block_reference: near_primitives::types::BlockReference::Finality(
+                near_primitives::types::Finality::Final,
+            ),
+        })
+        .await
+        .map_err(|err| errors::ErrorKind::RPCError(format!("{:#?}", err)))?;
+    let block_height = block.header.height;
+    let dex_id = near_primitives::types::AccountId::from_str(&config.dex_contract_account_id)?;
+    let quote_id = near_primitives::types::AccountId::from_str(&config.quote_contract_account_id)?;
+    let quote_decimals =
+        coin::data_provider::get_ft_contract_metadata(rpc_client, quote_id.clone(), block_height)
+            .await?
+            .decimals;
+
+    // One broken pool should not stop the others
+    for token in &config.tokens {
+        let price = sample_price(
+            rpc_client,
+            &dex_id,
+            &quote_id,
+            quote_decimals,
+            token,
+            block_height,
+        )
+        .await;
+        match price {
+            Ok(price) => {
+                sqlx::query(
+                    r"INSERT INTO token_prices
+                      VALUES ($1, $2::numeric(20, 0), $3::numeric)
+                      ON CONFLICT DO NOTHING",
+                )
+                .bind(&token.contract_account_id)
+                .bind(block.header.timestamp_nanosec.to_string())
+                .bind(price.to_string())
+                .execute(pool)
+                .await
+                .map_err(|err| errors::ErrorKind::DBError(err.to_string()))?;
+            }
+            Err(err) => tracing::warn!(
+                target: crate::LOGGER_MSG,
+                "Failed to sample the price of {}: {}",
+                token.contract_account_id,
+                err
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// How much of the quote token we get for 1 token
+async fn sample_price(
+    rpc_client: &near_jsonrpc_client::JsonRpcClient,
+    dex_id: &near_primitives::types::AccountId,
+    quote_id: &near_primitives::types::AccountId,
+    quote_decimals: u8,
+    token: &config::PricedTokenConfig,
+    block_height: u64,
+) -> crate::Result<BigDecimal> {
+    let token_id = near_primitives::types::AccountId::from_str(&token.contract_account_id)?;
+    let decimals =
+        coin::data_provider::get_ft_contract_metadata(rpc_client, token_id.clone(), block_height)
+            .await?
+            .decimals;
+    let amount_in = 10u128.checked_pow(decimals as u32).ok_or_else(|| {
+        errors::ErrorKind::ContractError(format!("Unexpected decimals value: {}", decimals))
+    })?;
+    let request = rpc_helpers::get_function_call_request(
+        block_height,
+        dex_id.clone(),
+        "get_return",
+        serde_json::json!({
+            "pool_id": token.pool_id,
+            "token_in": token_id,
+            "amount_in": types::U128(amount_in),
+            "token_out": quote_id,
+        }),
+    );
+    let response = rpc_helpers::wrapped_call(rpc_client, request, block_height, dex_id).await?;
+    let amount_out = serde_json::from_slice::<types::U128>(&response.result)?.0;
+    BigDecimal::from_str(&format!("{}e-{}", amount_out, quote_decimals)).map_err(|err| {
+        errors::ErrorKind::InternalError(format!("Failed to compute the price: {}", err)).into()
+    })
+}
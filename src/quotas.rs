@@ -0,0 +1,489 @@
+//! Actix middleware which enforces the daily and monthly request quotas per API key.
+//! The quotas (`api_quotas` table) and the usage (`api_usage` table, see `usage`) are reloaded
+//! from DB periodically, the requests in between are counted in memory.
+//! So the quotas are approximate: a key could go over the limit by the requests which were not
+//! flushed to DB yet.
+//! The requests without the API key share the quota of `anonymous` key, but each client IP
+//! (see `client_ip`) is counted separately. The same goes for the keys without their own quota,
+//! otherwise a random key per request would never hit the `*` quota. These counters live only in memory.
+use std::collections::HashMap;
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header;
+use futures::future::{ready, LocalBoxFuture, Ready};
+
+use crate::{api_keys, client_ip, errors, LOGGER_MSG};
+
+const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+/// The quota for the keys without their own quota
+pub(crate) const DEFAULT_QUOTA_KEY_ID: &str = "*";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Quota {
+    daily_requests: Option<i64>,
+    monthly_requests: Option<i64>,
+}
+
+#[derive(sqlx::FromRow)]
+struct QuotaInfo {
+    key_id: String,
+    daily_requests: Option<i64>,
+    monthly_requests: Option<i64>,
+}
+
+#[derive(sqlx::FromRow)]
+struct UsedRequestsInfo {
+    key_id: String,
+    day_requests: i64,
+    month_requests: i64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct UsedRequests {
+    day: i64,
+    month: i64,
+}
+
+/// `[start, end)` of the UTC day and the UTC month, in nanoseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Periods {
+    day_start: u64,
+    day_end: u64,
+    month_start: u64,
+    month_end: u64,
+}
+
+impl Periods {
+    fn at(timestamp_nanos: u64) -> Self {
+        let days = timestamp_nanos / DAY_NANOS;
+        let (year, month, day) = civil_from_days(days);
+        let month_start_days = days - (day - 1);
+        Self {
+            day_start: days * DAY_NANOS,
+            day_end: (days + 1) * DAY_NANOS,
+            month_start: month_start_days * DAY_NANOS,
+            month_end: (month_start_days + days_in_month(year, month)) * DAY_NANOS,
+        }
+    }
+}
+
+/// `(year, month, day)` of the given number of days since UNIX epoch.
+/// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn now_nanos() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+/// `quota_key_id` is `anonymous` or `*`, the requests under the shared quota are counted per client IP
+fn client_counter_id(quota_key_id: &str, client_ip: &std::net::IpAddr) -> String {
+    format!("{}:{}", quota_key_id, client_ip)
+}
+
+fn is_client_counter_id(counter_id: &str) -> bool {
+    [api_keys::ANONYMOUS_KEY_ID, DEFAULT_QUOTA_KEY_ID]
+        .iter()
+        .any(|quota_key_id| {
+            counter_id
+                .strip_prefix(quota_key_id)
+                .map_or(false, |rest| rest.starts_with(':'))
+        })
+}
+
+#[derive(Default)]
+struct UsageState {
+    periods: Periods,
+    used: HashMap<String, UsedRequests>,
+}
+
+#[derive(Default)]
+struct Inner {
+    quotas: std::sync::RwLock<HashMap<String, Quota>>,
+    usage: std::sync::Mutex<UsageState>,
+}
+
+impl Inner {
+    /// Counts the request. Returns the number of the remaining requests if the key has the quota,
+    /// the error if the quota is exceeded
+    fn check(
+        &self,
+        key_id: &str,
+        client_ip: Option<std::net::IpAddr>,
+        now: u64,
+    ) -> crate::Result<Option<i64>> {
+        let (quota, quota_key_id) = {
+            let quotas = self.quotas.read().expect("quotas lock is poisoned");
+            match quotas.get(key_id) {
+                Some(quota) => (*quota, key_id),
+                None => match quotas.get(DEFAULT_QUOTA_KEY_ID) {
+                    Some(quota) => (*quota, DEFAULT_QUOTA_KEY_ID),
+                    None => return Ok(None),
+                },
+            }
+        };
+
+        let mut usage = self.usage.lock().expect("quota usage lock is poisoned");
+        let periods = Periods::at(now);
+        if usage.periods != periods {
+            if usage.periods.month_start != periods.month_start {
+                usage.used.clear();
+            } else {
+                usage.used.values_mut().for_each(|used| used.day = 0);
+            }
+            usage.periods = periods;
+        }
+        let counter_id = match client_ip {
+            Some(client_ip)
+                if quota_key_id == api_keys::ANONYMOUS_KEY_ID
+                    || quota_key_id == DEFAULT_QUOTA_KEY_ID =>
+            {
+                client_counter_id(quota_key_id, &client_ip)
+            }
+            _ => key_id.to_string(),
+        };
+        let used = usage.used.entry(counter_id).or_default();
+
+        let mut remaining: Option<i64> = None;
+        for (limit, used, period_end, period_name) in [
+            (quota.daily_requests, used.day, periods.day_end, "daily"),
+            (
+                quota.monthly_requests,
+                used.month,
+                periods.month_end,
+                "monthly",
+            ),
+        ] {
+            if let Some(limit) = limit {
+                if used >= limit {
+                    return Err(errors::ErrorKind::TooManyRequests {
+                        message: format!("{} quota of {} requests is exceeded", period_name, limit),
+                        retry_after_secs: (period_end - now) / 1_000_000_000 + 1,
+                    }
+                    .into());
+                }
+                let left = limit - used - 1;
+                remaining = Some(remaining.map_or(left, |remaining| remaining.min(left)));
+            }
+        }
+        used.day += 1;
+        used.month += 1;
+        Ok(remaining)
+    }
+}
+
+/// Create it once and clone to all the workers, so they share the counters
+#[derive(Clone, Default)]
+pub struct QuotaEnforcer {
+    /// `None` if the quotas are disabled
+    inner: Option<std::sync::Arc<Inner>>,
+}
+
+impl QuotaEnforcer {
+    /// Passes all the requests through
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Spawns the task which reloads the quotas and the usage from `pool` every `refresh_interval`.
+    /// Nothing is limited until the first reload. Should be called inside Tokio runtime
+    pub(crate) fn new(
+        pool: sqlx::Pool<sqlx::Postgres>,
+        refresh_interval: std::time::Duration,
+    ) -> Self {
+        let inner = std::sync::Arc::new(Inner::default());
+        tokio::spawn(run_refresher(pool, inner.clone(), refresh_interval));
+        Self { inner: Some(inner) }
+    }
+}
+
+async fn run_refresher(
+    pool: sqlx::Pool<sqlx::Postgres>,
+    inner: std::sync::Arc<Inner>,
+    refresh_interval: std::time::Duration,
+) {
+    let mut interval = tokio::time::interval(refresh_interval);
+    loop {
+        interval.tick().await;
+        if let Err(err) = refresh(&pool, &inner).await {
+            tracing::warn!(
+                target: LOGGER_MSG,
+                "Failed to reload the quotas, the previous ones are used: {:#?}",
+                err
+            );
+        }
+    }
+}
+
+async fn refresh(pool: &sqlx::Pool<sqlx::Postgres>, inner: &Inner) -> Result<(), sqlx::Error> {
+    let quotas = sqlx::query_as::<_, QuotaInfo>(
+        "SELECT key_id, daily_requests, monthly_requests FROM api_quotas",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let periods = Periods::at(now_nanos());
+    let used = sqlx::query_as::<_, UsedRequestsInfo>(
+        r"SELECT
+              key_id,
+              (coalesce(sum(requests_count) FILTER (WHERE day_timestamp >= $1::numeric(20, 0)), 0))::bigint day_requests,
+              sum(requests_count)::bigint month_requests
+          FROM api_usage
+          WHERE day_timestamp >= $2::numeric(20, 0)
+          GROUP BY key_id",
+    )
+    .bind(periods.day_start.to_string())
+    .bind(periods.month_start.to_string())
+    .fetch_all(pool)
+    .await?;
+
+    *inner.quotas.write().expect("quotas lock is poisoned") = quotas
+        .into_iter()
+        .map(|quota| {
+            (
+                quota.key_id,
+                Quota {
+                    daily_requests: quota.daily_requests,
+                    monthly_requests: quota.monthly_requests,
+                },
+            )
+        })
+        .collect();
+    let mut used: HashMap<String, UsedRequests> = used
+        .into_iter()
+        .map(|used| {
+            (
+                used.key_id,
+                UsedRequests {
+                    day: used.day_requests,
+                    month: used.month_requests,
+                },
+            )
+        })
+        .collect();
+    let mut usage = inner.usage.lock().expect("quota usage lock is poisoned");
+    // DB does not know the counters per client IP, we keep them until the end of the period
+    if usage.periods.month_start == periods.month_start {
+        let same_day = usage.periods == periods;
+        for (counter_id, counter) in usage.used.drain() {
+            if is_client_counter_id(&counter_id) {
+                let day = if same_day { counter.day } else { 0 };
+                used.insert(
+                    counter_id,
+                    UsedRequests {
+                        day,
+                        month: counter.month,
+                    },
+                );
+            }
+        }
+    }
+    *usage = UsageState { periods, used };
+    Ok(())
+}
+
+impl<S, B> Transform<S, ServiceRequest> for QuotaEnforcer
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = QuotaEnforcerMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(QuotaEnforcerMiddleware {
+            service,
+            inner: self.inner.clone(),
+        }))
+    }
+}
+
+pub struct QuotaEnforcerMiddleware<S> {
+    service: S,
+    inner: Option<std::sync::Arc<Inner>>,
+}
+
+impl<S, B> Service<ServiceRequest> for QuotaEnforcerMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let remaining = match &self.inner {
+            Some(inner) if !req.path().contains(api_keys::ADMIN_PATH_SEGMENT) => {
+                match inner.check(
+                    &api_keys::get_key_id(req.headers()),
+                    client_ip::get_client_ip(&req),
+                    now_nanos(),
+                ) {
+                    Ok(remaining) => remaining,
+                    Err(err) => return Box::pin(ready(Ok(req.error_response(err)))),
+                }
+            }
+            _ => None,
+        };
+
+        let response = self.service.call(req);
+        Box::pin(async move {
+            let mut response = response.await?.map_into_boxed_body();
+            if let Some(remaining) = remaining {
+                response.headers_mut().insert(
+                    header::HeaderName::from_static("x-ratelimit-remaining"),
+                    header::HeaderValue::from(remaining),
+                );
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND_NANOS: u64 = 1_000_000_000;
+
+    #[test]
+    fn test_periods() {
+        // 2022-03-15 12:00:00 UTC
+        let periods = Periods::at(1647345600 * SECOND_NANOS);
+        assert_eq!(periods.day_start, 1647302400 * SECOND_NANOS);
+        assert_eq!(periods.day_end, 1647388800 * SECOND_NANOS);
+        assert_eq!(periods.month_start, 1646092800 * SECOND_NANOS);
+        assert_eq!(periods.month_end, 1648771200 * SECOND_NANOS);
+
+        // 2024-02-10, leap year
+        let periods = Periods::at(1707523200 * SECOND_NANOS);
+        assert_eq!(periods.month_start, 1706745600 * SECOND_NANOS);
+        assert_eq!(periods.month_end, 1709251200 * SECOND_NANOS);
+
+        // 2022-12-31 23:59:59, the next month is in the next year
+        let periods = Periods::at(1672531199 * SECOND_NANOS);
+        assert_eq!(periods.month_start, 1669852800 * SECOND_NANOS);
+        assert_eq!(periods.month_end, 1672531200 * SECOND_NANOS);
+    }
+
+    #[test]
+    fn test_quota_check() {
+        let inner = Inner::default();
+        *inner.quotas.write().unwrap() = HashMap::from([
+            (
+                "limited".to_string(),
+                Quota {
+                    daily_requests: Some(2),
+                    monthly_requests: Some(3),
+                },
+            ),
+            (
+                DEFAULT_QUOTA_KEY_ID.to_string(),
+                Quota {
+                    daily_requests: None,
+                    monthly_requests: Some(100),
+                },
+            ),
+        ]);
+        // 2022-03-15 12:00:00 UTC
+        let now = 1647345600 * SECOND_NANOS;
+
+        assert_eq!(inner.check("limited", None, now).unwrap(), Some(1));
+        assert_eq!(inner.check("limited", None, now).unwrap(), Some(0));
+        let error = inner.check("limited", None, now).unwrap_err();
+        assert_eq!(error.code, 429);
+        // Until the next UTC day
+        assert_eq!(error.retry_after_secs, Some(12 * 60 * 60 + 1));
+
+        // The next day, only the monthly quota is left
+        let tomorrow = now + DAY_NANOS;
+        assert_eq!(inner.check("limited", None, tomorrow).unwrap(), Some(0));
+        let error = inner.check("limited", None, tomorrow).unwrap_err();
+        assert!(error.message.contains("monthly"));
+
+        assert_eq!(inner.check("other", None, now).unwrap(), Some(99));
+
+        // Each client IP has its own counter of the anonymous requests
+        let client = |ip: &str| Some(ip.parse::<std::net::IpAddr>().unwrap());
+        assert_eq!(
+            inner
+                .check(api_keys::ANONYMOUS_KEY_ID, client("192.0.2.1"), now)
+                .unwrap(),
+            Some(99)
+        );
+        assert_eq!(
+            inner
+                .check(api_keys::ANONYMOUS_KEY_ID, client("192.0.2.1"), now)
+                .unwrap(),
+            Some(98)
+        );
+        assert_eq!(
+            inner
+                .check(api_keys::ANONYMOUS_KEY_ID, client("192.0.2.2"), now)
+                .unwrap(),
+            Some(99)
+        );
+        // The keys without their own quota are counted per client IP as well,
+        // so the random keys from the same IP share one counter
+        assert_eq!(
+            inner.check("random1", client("192.0.2.3"), now).unwrap(),
+            Some(99)
+        );
+        assert_eq!(
+            inner.check("random2", client("192.0.2.3"), now).unwrap(),
+            Some(98)
+        );
+        assert_eq!(
+            inner
+                .check("limited", client("192.0.2.3"), now)
+                .unwrap_err()
+                .code,
+            429
+        );
+        assert!(is_client_counter_id(&client_counter_id(
+            api_keys::ANONYMOUS_KEY_ID,
+            &"::1".parse().unwrap()
+        )));
+        assert!(is_client_counter_id(&client_counter_id(
+            DEFAULT_QUOTA_KEY_ID,
+            &"::1".parse().unwrap()
+        )));
+        assert!(!is_client_counter_id(api_keys::ANONYMOUS_KEY_ID));
+        inner.quotas.write().unwrap().clear();
+        assert_eq!(inner.check("other", None, now).unwrap(), None);
+    }
+}
@@ -0,0 +1,181 @@
+//! Access log with sampling, so the busy instances don't drown in the log lines.
+//! The errors and the successes are sampled separately (e.g. all the errors and 1% of the successes),
+//! the requests slower than `slow_request_threshold_ms` are always logged.
+//! The sampling is deterministic: rate 0.01 logs every 100th request, not the random ones.
+//! The line format is the default actix one, with the client IP behind the trusted proxies.
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::{header, StatusCode};
+use futures::future::{ready, LocalBoxFuture, Ready};
+
+use crate::{client_ip, config, LOGGER_MSG};
+
+#[derive(Default)]
+struct Counters {
+    successes: AtomicU64,
+    errors: AtomicU64,
+}
+
+/// Create it once and clone to all the workers, so they share the sampling counters
+#[derive(Clone)]
+pub struct RequestLogger {
+    config: config::LogSamplingConfig,
+    counters: std::sync::Arc<Counters>,
+}
+
+impl RequestLogger {
+    pub fn new(config: config::LogSamplingConfig) -> Self {
+        Self {
+            config,
+            counters: Default::default(),
+        }
+    }
+
+    fn should_log(&self, status: StatusCode, elapsed: std::time::Duration) -> bool {
+        if let Some(threshold_ms) = self.config.slow_request_threshold_ms {
+            if elapsed.as_millis() >= threshold_ms as u128 {
+                return true;
+            }
+        }
+        if status.is_client_error() || status.is_server_error() {
+            is_sampled(self.config.error_sample_rate, &self.counters.errors)
+        } else {
+            is_sampled(self.config.success_sample_rate, &self.counters.successes)
+        }
+    }
+}
+
+/// Spreads the sampled requests evenly: the request is logged when `count * rate` crosses the next integer
+fn is_sampled(rate: f64, counter: &AtomicU64) -> bool {
+    if rate >= 1.0 {
+        return true;
+    }
+    if rate <= 0.0 {
+        return false;
+    }
+    let count = counter.fetch_add(1, Ordering::Relaxed);
+    ((count + 1) as f64 * rate).floor() > (count as f64 * rate).floor()
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequestLogger
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = RequestLoggerMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestLoggerMiddleware {
+            service,
+            logger: self.clone(),
+        }))
+    }
+}
+
+pub struct RequestLoggerMiddleware<S> {
+    service: S,
+    logger: RequestLogger,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestLoggerMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let started = std::time::Instant::now();
+        let client_ip =
+            client_ip::get_client_ip(&req).map_or_else(|| "-".to_string(), |ip| ip.to_string());
+        let request_line = format!(
+            "{} {} {:?}",
+            req.method(),
+            req.uri()
+                .path_and_query()
+                .map_or_else(|| req.path(), |path_and_query| path_and_query.as_str()),
+            req.version()
+        );
+        let header_value = |name: header::HeaderName| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or("-")
+                .to_string()
+        };
+        let referer = header_value(header::REFERER);
+        let user_agent = header_value(header::USER_AGENT);
+        let logger = self.logger.clone();
+        let response = self.service.call(req);
+
+        Box::pin(async move {
+            let response = response.await;
+            let elapsed = started.elapsed();
+            let (status, size) = match &response {
+                Ok(response) => (response.status(), response.response().body().size()),
+                Err(err) => (err.as_response_error().status_code(), BodySize::None),
+            };
+            if logger.should_log(status, elapsed) {
+                tracing::info!(
+                    target: LOGGER_MSG,
+                    r#"{} "{}" {} {} "{}" "{}" {:.6}"#,
+                    client_ip,
+                    request_line,
+                    status.as_u16(),
+                    match size {
+                        BodySize::Sized(size) => size.to_string(),
+                        _ => "-".to_string(),
+                    },
+                    referer,
+                    user_agent,
+                    elapsed.as_secs_f64()
+                );
+            }
+            response
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_log() {
+        let logger = RequestLogger::new(config::LogSamplingConfig {
+            success_sample_rate: 0.01,
+            error_sample_rate: 1.0,
+            slow_request_threshold_ms: Some(500),
+        });
+        let fast = std::time::Duration::from_millis(10);
+        let slow = std::time::Duration::from_millis(600);
+
+        let logged_successes = (0..1000)
+            .filter(|_| logger.should_log(StatusCode::OK, fast))
+            .count();
+        assert_eq!(logged_successes, 10);
+        assert!((0..100).all(|_| logger.should_log(StatusCode::NOT_FOUND, fast)));
+        assert!((0..100).all(|_| logger.should_log(StatusCode::OK, slow)));
+
+        let logger = RequestLogger::new(config::LogSamplingConfig {
+            success_sample_rate: 0.0,
+            error_sample_rate: 0.5,
+            slow_request_threshold_ms: None,
+        });
+        assert!(!(0..100).any(|_| logger.should_log(StatusCode::OK, slow)));
+        let logged_errors = (0..100)
+            .filter(|_| logger.should_log(StatusCode::INTERNAL_SERVER_ERROR, fast))
+            .count();
+        assert_eq!(logged_errors, 50);
+    }
+}
@@ -0,0 +1,403 @@
+//! Actix middleware which caches the responses for the requests pinned to the past block.
+//! Such responses never change, so the popular accounts and tokens are served from memory.
+//! The history pages taken by the old enough `cursor` never change as well, they are kept longer:
+//! the integrators backfill the same history again and again.
+//! Each API key has its own entries, so the per-key routes never leak to the other keys.
+//! The admin, watchlist and export routes and the requests with `Authorization` are never cached.
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header;
+use futures::future::{ready, LocalBoxFuture, Ready};
+
+use crate::{api_keys, cache, config, errors, types};
+
+/// Finality takes a few seconds, we wait longer to be sure the block under the timestamp won't change
+const FINALITY_MARGIN: std::time::Duration = std::time::Duration::from_secs(60);
+/// Bigger responses are not worth keeping in memory
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+/// The path segments of the routes which belong to the API key or to the admin, they are not cached at all.
+/// The segments are checked instead of the prefixes, so it works when the API is mounted under any scope
+const UNCACHED_PATH_SEGMENTS: [&str; 3] = ["admin", "exports", "watchlists"];
+
+#[derive(Clone)]
+struct CachedResponse {
+    content_type: Option<header::HeaderValue>,
+    body: actix_web::web::Bytes,
+    cached_at: std::time::SystemTime,
+}
+
+struct Inner {
+    items: cache::TtlCache<String, CachedResponse>,
+    config: config::ResponseCacheConfig,
+}
+
+impl Inner {
+    /// The key and how long the response could be kept
+    fn cache_key(&self, req: &ServiceRequest) -> Option<(String, std::time::Duration)> {
+        if self.config.max_items == 0
+            || req.method() != actix_web::http::Method::GET
+            || req.headers().contains_key(header::AUTHORIZATION)
+            || req
+                .path()
+                .split('/')
+                .any(|segment| UNCACHED_PATH_SEGMENTS.contains(&segment))
+        {
+            return None;
+        }
+        if !self.config.path_prefixes.is_empty()
+            && !self
+                .config
+                .path_prefixes
+                .iter()
+                .any(|prefix| config::matches_path_prefix(prefix, req.path()))
+        {
+            return None;
+        }
+        let ttl_secs = if is_pinned_to_past_block(req.query_string()) {
+            self.config.ttl_secs
+        } else if is_immutable_history_page(req.query_string()) {
+            self.config.history_ttl_secs
+        } else {
+            return None;
+        };
+        Some((
+            format!(
+                "{} {}?{}",
+                api_keys::get_key_id(req.headers()),
+                req.path(),
+                req.query_string()
+            ),
+            std::time::Duration::from_secs(ttl_secs),
+        ))
+    }
+}
+
+/// The responses to the API keys could differ per key, the shared proxies should not keep them
+fn cache_control(req: &actix_web::HttpRequest, ttl: std::time::Duration) -> String {
+    let visibility = match api_keys::get_api_key(req.headers()) {
+        Some(_) => "private",
+        None => "public",
+    };
+    format!("{}, max-age={}", visibility, ttl.as_secs())
+}
+
+/// The timestamps before this one are final
+fn finalized_before() -> u128 {
+    std::time::SystemTime::now()
+        .checked_sub(FINALITY_MARGIN)
+        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|duration| duration.as_nanos())
+        .unwrap_or_default()
+}
+
+/// The page goes strictly before the event of the cursor, and the cursor keeps the snapshot block.
+/// If the event is final, nothing on the page could change
+#[cfg(feature = "history")]
+fn is_immutable_history_page(query_string: &str) -> bool {
+    let params =
+        match actix_web::web::Query::<types::query_params::HistoryPaginationParams>::from_query(
+            query_string,
+        ) {
+            Ok(params) => params.into_inner(),
+            Err(_) => return false,
+        };
+    match params
+        .cursor
+        .and_then(|cursor| types::query_params::HistoryCursor::decode(&cursor).ok())
+    {
+        Some(cursor) => (cursor.before_event.block_timestamp as u128) < finalized_before(),
+        None => false,
+    }
+}
+
+#[cfg(not(feature = "history"))]
+fn is_immutable_history_page(_query_string: &str) -> bool {
+    false
+}
+
+fn is_pinned_to_past_block(query_string: &str) -> bool {
+    let params =
+        match actix_web::web::Query::<types::query_params::BlockParams>::from_query(query_string) {
+            Ok(params) => params.into_inner(),
+            Err(_) => return false,
+        };
+    if params.block_height.is_some() {
+        return true;
+    }
+    match params.block_timestamp_nanos {
+        Some(timestamp) => (timestamp.0 as u128) < finalized_before(),
+        None => false,
+    }
+}
+
+#[derive(Clone)]
+pub struct ResponseCache {
+    inner: std::sync::Arc<Inner>,
+}
+
+impl ResponseCache {
+    /// Create it once and clone to all the workers, so they share the cache
+    pub fn new(config: config::ResponseCacheConfig) -> Self {
+        Self {
+            inner: std::sync::Arc::new(Inner {
+                items: cache::TtlCache::new(config.max_items),
+                config,
+            }),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ResponseCache
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = ResponseCacheMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ResponseCacheMiddleware {
+            service: std::rc::Rc::new(service),
+            inner: self.inner.clone(),
+        }))
+    }
+}
+
+pub struct ResponseCacheMiddleware<S> {
+    service: std::rc::Rc<S>,
+    inner: std::sync::Arc<Inner>,
+}
+
+impl<S, B> Service<ServiceRequest> for ResponseCacheMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let (key, ttl) = match self.inner.cache_key(&req) {
+            Some(key) => key,
+            None => {
+                let response = self.service.call(req);
+                return Box::pin(async move { Ok(response.await?.map_into_boxed_body()) });
+            }
+        };
+
+        if let Some(cached) = self.inner.items.get(&key, ttl) {
+            let age = cached.cached_at.elapsed().unwrap_or_default().as_secs();
+            let mut response = actix_web::HttpResponse::Ok();
+            if let Some(content_type) = cached.content_type {
+                response.insert_header((header::CONTENT_TYPE, content_type));
+            }
+            let response = response
+                .insert_header((header::CACHE_CONTROL, cache_control(req.request(), ttl)))
+                .insert_header((header::AGE, age.to_string()))
+                .body(cached.body);
+            return Box::pin(ready(Ok(req.into_response(response))));
+        }
+
+        let response = self.service.call(req);
+        let inner = self.inner.clone();
+        Box::pin(async move {
+            let response = response.await?;
+            if response.status() != actix_web::http::StatusCode::OK {
+                return Ok(response.map_into_boxed_body());
+            }
+            // The streamed exports could be huge, we don't buffer them
+            if let actix_web::body::BodySize::Stream = response.response().body().size() {
+                return Ok(response.map_into_boxed_body());
+            }
+            let (req, response) = response.into_parts();
+            let (mut response, body) = response.into_parts();
+            let body = actix_web::body::to_bytes(body).await.map_err(|err| {
+                let err: Box<dyn std::error::Error> = err.into();
+                errors::Error::from_error_kind(errors::ErrorKind::InternalError(format!(
+                    "Could not read the response body: {}",
+                    err
+                )))
+            })?;
+            if body.len() <= MAX_BODY_SIZE {
+                inner.items.insert(
+                    key,
+                    CachedResponse {
+                        content_type: response.headers().get(header::CONTENT_TYPE).cloned(),
+                        body: body.clone(),
+                        cached_at: std::time::SystemTime::now(),
+                    },
+                );
+            }
+            let headers = response.headers_mut();
+            headers.insert(
+                header::CACHE_CONTROL,
+                header::HeaderValue::from_str(&cache_control(&req, ttl))
+                    .unwrap_or_else(|_| header::HeaderValue::from_static("no-cache")),
+            );
+            headers.insert(header::AGE, header::HeaderValue::from_static("0"));
+            Ok(ServiceResponse::new(
+                req,
+                response.set_body(body).map_into_boxed_body(),
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_is_pinned_to_past_block() {
+        assert!(is_pinned_to_past_block("block_height=100"));
+        assert!(is_pinned_to_past_block(
+            "limit=10&block_timestamp_nanos=1600000000000000000"
+        ));
+        assert!(!is_pinned_to_past_block("limit=10"));
+        assert!(!is_pinned_to_past_block(&format!(
+            "block_timestamp_nanos={}",
+            u64::MAX
+        )));
+        assert!(!is_pinned_to_past_block("block_height=latest"));
+    }
+
+    #[cfg(feature = "history")]
+    #[test]
+    fn test_is_immutable_history_page() {
+        let cursor = |before_timestamp| {
+            types::query_params::HistoryCursor {
+                block_height: 100,
+                block_timestamp: u64::MAX,
+                block_hash: None,
+                before_event: types::query_params::EventIndex {
+                    block_timestamp: before_timestamp,
+                    shard_id: 0,
+                    index_in_chunk: 0,
+                },
+            }
+            .encode()
+        };
+        assert!(is_immutable_history_page(&format!(
+            "limit=10&cursor={}",
+            cursor(1600000000000000000)
+        )));
+        // The events right before the cursor could be not final yet
+        assert!(!is_immutable_history_page(&format!(
+            "cursor={}",
+            cursor(u64::MAX)
+        )));
+        assert!(!is_immutable_history_page("limit=10"));
+        assert!(!is_immutable_history_page("cursor=invalid"));
+    }
+
+    #[actix_web::test]
+    async fn test_response_cache() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        let app = actix_web::test::init_service(
+            actix_web::App::new()
+                .wrap(ResponseCache::new(config::ResponseCacheConfig {
+                    path_prefixes: vec!["/cached".to_string()],
+                    ..Default::default()
+                }))
+                .default_service(actix_web::web::to(|| async {
+                    CALLS.fetch_add(1, Ordering::SeqCst);
+                    actix_web::HttpResponse::Ok().json(serde_json::json!({"ok": true}))
+                })),
+        )
+        .await;
+
+        for _ in 0..2 {
+            let request = actix_web::test::TestRequest::get()
+                .uri("/cached/alice.near?block_height=100")
+                .to_request();
+            let response = actix_web::test::call_service(&app, request).await;
+            assert!(response.status().is_success());
+            assert_eq!(
+                response.headers().get(header::CACHE_CONTROL).unwrap(),
+                "public, max-age=600"
+            );
+            assert!(response.headers().contains_key(header::AGE));
+            let body = actix_web::test::read_body(response).await;
+            assert_eq!(body, r#"{"ok":true}"#);
+        }
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+
+        // Latest block and the other routes are never cached
+        for uri in ["/cached/alice.near", "/other?block_height=100"] {
+            let request = actix_web::test::TestRequest::get().uri(uri).to_request();
+            let response = actix_web::test::call_service(&app, request).await;
+            assert!(!response.headers().contains_key(header::AGE));
+        }
+        assert_eq!(CALLS.load(Ordering::SeqCst), 3);
+    }
+
+    #[actix_web::test]
+    async fn test_response_cache_per_key() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        let app = actix_web::test::init_service(
+            actix_web::App::new()
+                .wrap(ResponseCache::new(config::ResponseCacheConfig::default()))
+                .default_service(actix_web::web::to(|| async {
+                    CALLS.fetch_add(1, Ordering::SeqCst);
+                    actix_web::HttpResponse::Ok().json(serde_json::json!({"ok": true}))
+                })),
+        )
+        .await;
+        let app = &app;
+        // `Cache-Control` is set only for the cached responses
+        let call = move |uri: &'static str, auth: Option<(&'static str, &'static str)>| async move {
+            let mut request = actix_web::test::TestRequest::get().uri(uri);
+            if let Some(auth) = auth {
+                request = request.insert_header(auth);
+            }
+            let response = actix_web::test::call_service(app, request.to_request()).await;
+            assert!(response.status().is_success());
+            response
+                .headers()
+                .get(header::CACHE_CONTROL)
+                .map(|value| value.to_str().unwrap().to_string())
+        };
+        let calls = || CALLS.load(Ordering::SeqCst);
+        let alice = Some((api_keys::API_KEY_HEADER, "alice-key"));
+        let bob = Some((api_keys::API_KEY_HEADER, "bob-key"));
+
+        let uri = "/accounts/alice.near/coins?block_height=100";
+        assert_eq!(
+            call(uri, alice).await.as_deref(),
+            Some("private, max-age=600")
+        );
+        call(uri, alice).await;
+        assert_eq!(calls(), 1);
+        // The other key and the anonymous request don't get the cached response of the key
+        call(uri, bob).await;
+        assert_eq!(calls(), 2);
+        assert_eq!(
+            call(uri, None).await.as_deref(),
+            Some("public, max-age=600")
+        );
+        assert_eq!(calls(), 3);
+        call(uri, bob).await;
+        call(uri, None).await;
+        assert_eq!(calls(), 3);
+
+        // The routes of the key and of the admin are never cached
+        for (uri, auth) in [
+            ("/watchlists/1/portfolio?block_height=100", alice),
+            ("/exports/1?block_height=100", alice),
+            ("/admin/usage?block_height=100", None),
+            (uri, Some(("Authorization", "Bearer secret"))),
+        ] {
+            for _ in 0..2 {
+                assert_eq!(call(uri, auth).await, None);
+            }
+        }
+        assert_eq!(calls(), 11);
+    }
+}
@@ -0,0 +1,354 @@
+//! The response format options of all the endpoints, the middleware rewrites the JSON responses.
+//!
+//! `?timestamp_format=iso8601|epoch_ms|nanos`: the handlers always give `..._timestamp_nanos` fields,
+//! `iso8601` replaces them with `..._timestamp_iso8601` strings, `epoch_ms` with `..._timestamp_ms` numbers,
+//! `nanos` leaves them as is. By default, both `..._timestamp_nanos` and `..._timestamp_iso8601` are given.
+//!
+//! `?amount_float=true`: the amounts are always given as the integer strings in the smallest units,
+//! `..._float` numbers with the decimals applied are added next to them. They lose the precision,
+//! use them only for the display. The decimals are taken from `metadata`/`coin_metadata`
+//! of the object or of its parents, the amounts without the metadata nearby are left as is.
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header;
+use futures::future::{ready, LocalBoxFuture, Ready};
+
+use crate::errors;
+use crate::types::query_params::{ResponseFormatParams, TimestampFormat};
+
+const NANOS_SUFFIX: &str = "timestamp_nanos";
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+const SECONDS_PER_DAY: u64 = 86400;
+/// The amount fields which get `..._float` pair
+const AMOUNT_KEYS: &[&str] = &[
+    "amount",
+    "balance",
+    "delta",
+    "delta_balance",
+    "from_balance",
+    "to_balance",
+];
+const METADATA_KEYS: &[&str] = &["metadata", "coin_metadata"];
+
+/// `None` is the default format
+pub(crate) fn rewrite_timestamps(value: &mut serde_json::Value, format: Option<TimestampFormat>) {
+    match value {
+        serde_json::Value::Array(items) => {
+            for item in items {
+                rewrite_timestamps(item, format);
+            }
+        }
+        serde_json::Value::Object(fields) => {
+            let timestamp_keys: Vec<String> = fields
+                .keys()
+                .filter(|key| key.ends_with(NANOS_SUFFIX))
+                .cloned()
+                .collect();
+            for (_, field) in fields.iter_mut() {
+                rewrite_timestamps(field, format);
+            }
+            for key in timestamp_keys {
+                let nanos = match fields.get(&key).and_then(parse_nanos) {
+                    Some(nanos) => nanos,
+                    None => continue,
+                };
+                let base = key.trim_end_matches("_nanos");
+                match format {
+                    None => {
+                        fields.insert(format!("{}_iso8601", base), to_iso8601(nanos).into());
+                    }
+                    Some(TimestampFormat::Iso8601) => {
+                        fields.remove(&key);
+                        fields.insert(format!("{}_iso8601", base), to_iso8601(nanos).into());
+                    }
+                    Some(TimestampFormat::EpochMs) => {
+                        fields.remove(&key);
+                        fields.insert(format!("{}_ms", base), (nanos / 1_000_000).into());
+                    }
+                    Some(TimestampFormat::Nanos) => {}
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `decimals` are inherited from the parent object if the object does not have its own metadata
+fn add_amount_floats(value: &mut serde_json::Value, decimals: Option<u32>) {
+    match value {
+        serde_json::Value::Array(items) => {
+            for item in items {
+                add_amount_floats(item, decimals);
+            }
+        }
+        serde_json::Value::Object(fields) => {
+            let decimals = METADATA_KEYS
+                .iter()
+                .filter_map(|key| fields.get(*key)?.get("decimals")?.as_u64())
+                .next()
+                .map(|decimals| decimals as u32)
+                .or(decimals);
+            for (_, field) in fields.iter_mut() {
+                add_amount_floats(field, decimals);
+            }
+            let decimals = match decimals {
+                Some(decimals) => decimals,
+                None => return,
+            };
+            for key in AMOUNT_KEYS {
+                let amount = match fields.get(*key).and_then(|amount| amount.as_str()) {
+                    Some(amount) => amount,
+                    None => continue,
+                };
+                if let Ok(amount) = amount.parse::<i128>() {
+                    let amount_float = amount as f64 / 10f64.powi(decimals as i32);
+                    fields.insert(format!("{}_float", key), amount_float.into());
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `U64` is given as string, but let's be ready for the numbers as well
+fn parse_nanos(value: &serde_json::Value) -> Option<u64> {
+    match value {
+        serde_json::Value::String(nanos) => nanos.parse().ok(),
+        serde_json::Value::Number(nanos) => nanos.as_u64(),
+        _ => None,
+    }
+}
+
+/// RFC 3339 in UTC with all 9 digits of the nanoseconds
+pub(crate) fn to_iso8601(nanos: u64) -> String {
+    let seconds = nanos / NANOS_PER_SECOND;
+    let (year, month, day) = civil_from_days(seconds / SECONDS_PER_DAY);
+    let seconds_of_day = seconds % SECONDS_PER_DAY;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:09}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60,
+        nanos % NANOS_PER_SECOND
+    )
+}
+
+/// Days since 1970-01-01 to (year, month, day), see http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+#[derive(Clone, Default)]
+pub struct ResponseFormatter;
+
+impl<S, B> Transform<S, ServiceRequest> for ResponseFormatter
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = ResponseFormatterMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ResponseFormatterMiddleware { service }))
+    }
+}
+
+pub struct ResponseFormatterMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for ResponseFormatterMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let params =
+            match actix_web::web::Query::<ResponseFormatParams>::from_query(req.query_string()) {
+                Ok(params) => params.into_inner(),
+                Err(err) => {
+                    let error = errors::Error::from_error_kind(errors::ErrorKind::InvalidInput(
+                        err.to_string(),
+                    ));
+                    return Box::pin(ready(Ok(req.error_response(error))));
+                }
+            };
+        let format = params.timestamp_format;
+        let amount_float = params.amount_float.unwrap_or_default();
+
+        let response = self.service.call(req);
+        Box::pin(async move {
+            let response = response.await?;
+            let is_json = response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .map_or(false, |content_type| {
+                    content_type.as_bytes().starts_with(b"application/json")
+                });
+            if (format == Some(TimestampFormat::Nanos) && !amount_float)
+                || response.status() != actix_web::http::StatusCode::OK
+                || !is_json
+            {
+                return Ok(response.map_into_boxed_body());
+            }
+            // The streamed exports could be huge, they are not JSON anyway
+            if let actix_web::body::BodySize::Stream = response.response().body().size() {
+                return Ok(response.map_into_boxed_body());
+            }
+            let (req, response) = response.into_parts();
+            let (response, body) = response.into_parts();
+            let body = actix_web::body::to_bytes(body).await.map_err(|err| {
+                let err: Box<dyn std::error::Error> = err.into();
+                errors::Error::from_error_kind(errors::ErrorKind::InternalError(format!(
+                    "Could not read the response body: {}",
+                    err
+                )))
+            })?;
+            let body = match serde_json::from_slice::<serde_json::Value>(&body) {
+                Ok(mut value) => {
+                    rewrite_timestamps(&mut value, format);
+                    if amount_float {
+                        add_amount_floats(&mut value, None);
+                    }
+                    actix_web::web::Bytes::from(
+                        serde_json::to_vec(&value).map_err(errors::Error::from)?,
+                    )
+                }
+                Err(_) => body,
+            };
+            Ok(ServiceResponse::new(
+                req,
+                response.set_body(body).map_into_boxed_body(),
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_iso8601() {
+        assert_eq!(to_iso8601(0), "1970-01-01T00:00:00.000000000Z");
+        assert_eq!(
+            to_iso8601(1655571176644255779),
+            "2022-06-18T16:52:56.644255779Z"
+        );
+        // Leap day
+        assert_eq!(
+            to_iso8601(1709210096000000001),
+            "2024-02-29T12:34:56.000000001Z"
+        );
+    }
+
+    #[test]
+    fn test_rewrite_timestamps() {
+        let response = serde_json::json!({
+            "history": [{"block_timestamp_nanos": "1655571176644255779", "balance": "10"}],
+            "block_timestamp_nanos": "1655571176644255779",
+            "block_height": "68000000",
+        });
+
+        let mut value = response.clone();
+        rewrite_timestamps(&mut value, None);
+        assert_eq!(
+            value["history"][0]["block_timestamp_iso8601"],
+            "2022-06-18T16:52:56.644255779Z"
+        );
+        assert_eq!(value["block_timestamp_nanos"], "1655571176644255779");
+        assert_eq!(value["block_height"], "68000000");
+
+        let mut value = response.clone();
+        rewrite_timestamps(&mut value, Some(TimestampFormat::EpochMs));
+        assert_eq!(value["block_timestamp_ms"], 1655571176644_u64);
+        assert!(value.get("block_timestamp_nanos").is_none());
+        assert!(value["history"][0].get("block_timestamp_nanos").is_none());
+
+        let mut value = response.clone();
+        rewrite_timestamps(&mut value, Some(TimestampFormat::Iso8601));
+        assert_eq!(
+            value["block_timestamp_iso8601"],
+            "2022-06-18T16:52:56.644255779Z"
+        );
+        assert!(value.get("block_timestamp_nanos").is_none());
+    }
+
+    #[test]
+    fn test_add_amount_floats() {
+        let mut value = serde_json::json!({
+            "balances": [
+                {"balance": "1500000", "metadata": {"symbol": "USN", "decimals": 6}},
+                {"balance": "2500000000000000000000000", "metadata": {"symbol": "NEAR", "decimals": 24}},
+            ],
+            "transfers": [{"amount": "1000"}],
+        });
+        add_amount_floats(&mut value, None);
+        assert_eq!(value["balances"][0]["balance_float"], 1.5);
+        assert_eq!(value["balances"][1]["balance_float"], 2.5);
+        assert_eq!(value["balances"][1]["balance"], "2500000000000000000000000");
+        // We don't know the decimals
+        assert!(value["transfers"][0].get("amount_float").is_none());
+
+        // The metadata of the parent is used
+        let mut value = serde_json::json!({
+            "balances": [{"balance": "-25"}],
+            "metadata": {"decimals": 1},
+        });
+        add_amount_floats(&mut value, None);
+        assert_eq!(value["balances"][0]["balance_float"], -2.5);
+    }
+
+    #[actix_web::test]
+    async fn test_response_formatter() {
+        let app = actix_web::test::init_service(
+            actix_web::App::new()
+                .wrap(ResponseFormatter)
+                .default_service(actix_web::web::to(|| async {
+                    actix_web::HttpResponse::Ok()
+                        .json(serde_json::json!({"block_timestamp_nanos": "1000000000"}))
+                })),
+        )
+        .await;
+
+        let request = actix_web::test::TestRequest::get()
+            .uri("/block?timestamp_format=epoch_ms")
+            .to_request();
+        let response: serde_json::Value =
+            actix_web::test::call_and_read_body_json(&app, request).await;
+        assert_eq!(response, serde_json::json!({"block_timestamp_ms": 1000}));
+
+        let request = actix_web::test::TestRequest::get()
+            .uri("/block?timestamp_format=seconds")
+            .to_request();
+        let response: errors::Error = actix_web::test::call_and_read_body_json(&app, request).await;
+        assert_eq!(response.code, 400);
+    }
+}
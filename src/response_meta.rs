@@ -0,0 +1,326 @@
+//! Fills `meta` of the JSON responses on the way out, see `api_models::ResponseMeta`.
+//! The handlers give the block and the pagination, the middleware adds `request_id`
+//! (`X-Request-Id` of the request, or the generated one, also given back in the header)
+//! and `last_indexed_block_*`, the head of the indexer polled in the background.
+//! It should wrap `response_cache` and `head_requests`, so the cached bodies and `ETag`
+//! are the same for all the requests.
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header;
+use futures::future::{ready, LocalBoxFuture, Ready};
+
+use crate::types::query_params::ResponseFormatParams;
+use crate::{data_source, errors, response_format, LOGGER_MSG};
+
+const REQUEST_ID_HEADER: &str = "x-request-id";
+/// The longer ids given by the clients are replaced with the generated ones
+const MAX_REQUEST_ID_LEN: usize = 128;
+const HEAD_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+#[derive(Clone, Copy)]
+struct IndexerHead {
+    block_height: u64,
+    block_timestamp: u64,
+}
+
+/// Create it once and clone to all the workers, so they share the indexer head
+#[derive(Clone)]
+pub struct ResponseMetaFiller {
+    /// `None` until the first poll
+    head: std::sync::Arc<std::sync::RwLock<Option<IndexerHead>>>,
+    /// The generated ids are `{instance}-{number}`, the instance is the start time
+    instance_id: u64,
+    next_request_number: std::sync::Arc<AtomicU64>,
+}
+
+impl Default for ResponseMetaFiller {
+    fn default() -> Self {
+        Self {
+            head: Default::default(),
+            instance_id: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|duration| duration.as_nanos() as u64)
+                .unwrap_or_default(),
+            next_request_number: Default::default(),
+        }
+    }
+}
+
+impl ResponseMetaFiller {
+    /// `last_indexed_block_*` are not given until `start` is called
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spawns the task which polls the indexer head, requires Tokio runtime
+    pub(crate) fn start(&self, data_source: data_source::DataSourceRef) {
+        let head = self.head.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(HEAD_POLL_INTERVAL);
+            loop {
+                interval.tick().await;
+                match data_source.get_last_block().await {
+                    Ok(block) => {
+                        let mut head = head.write().unwrap_or_else(|err| err.into_inner());
+                        *head = Some(IndexerHead {
+                            block_height: block.height,
+                            block_timestamp: block.timestamp,
+                        });
+                    }
+                    Err(err) => {
+                        tracing::debug!(
+                            target: LOGGER_MSG,
+                            "Failed to poll the indexer head: {}",
+                            err
+                        );
+                    }
+                }
+            }
+        });
+    }
+
+    fn head(&self) -> Option<IndexerHead> {
+        *self.head.read().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn request_id(&self, req: &ServiceRequest) -> String {
+        req.headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|request_id| is_valid_request_id(request_id))
+            .map(str::to_string)
+            .unwrap_or_else(|| {
+                format!(
+                    "{:x}-{:x}",
+                    self.instance_id,
+                    self.next_request_number.fetch_add(1, Ordering::Relaxed)
+                )
+            })
+    }
+}
+
+/// The ids go to the logs and back to the headers, we don't take anything unusual
+fn is_valid_request_id(request_id: &str) -> bool {
+    !request_id.is_empty()
+        && request_id.len() <= MAX_REQUEST_ID_LEN
+        && request_id.bytes().all(|byte| byte.is_ascii_graphic())
+}
+
+/// Does nothing with the responses without top-level `meta` object
+fn fill_meta(
+    value: &mut serde_json::Value,
+    request_id: &str,
+    head: Option<IndexerHead>,
+    params: &ResponseFormatParams,
+) {
+    let meta = match value.get_mut("meta").and_then(|meta| meta.as_object_mut()) {
+        Some(meta) => meta,
+        None => return,
+    };
+    let mut fields = serde_json::json!({ "request_id": request_id });
+    if let Some(head) = head {
+        fields["last_indexed_block_height"] = head.block_height.to_string().into();
+        fields["last_indexed_block_timestamp_nanos"] = head.block_timestamp.to_string().into();
+    }
+    // The rest of the response is already formatted
+    response_format::rewrite_timestamps(&mut fields, params.timestamp_format);
+    if let serde_json::Value::Object(fields) = fields {
+        meta.extend(fields);
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ResponseMetaFiller
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = ResponseMetaFillerMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ResponseMetaFillerMiddleware {
+            service,
+            filler: self.clone(),
+        }))
+    }
+}
+
+pub struct ResponseMetaFillerMiddleware<S> {
+    service: S,
+    filler: ResponseMetaFiller,
+}
+
+impl<S, B> Service<ServiceRequest> for ResponseMetaFillerMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let request_id = self.filler.request_id(&req);
+        let head = self.filler.head();
+        // The invalid params are rejected by `response_format`
+        let params = actix_web::web::Query::<ResponseFormatParams>::from_query(req.query_string())
+            .map(|params| params.into_inner())
+            .unwrap_or_default();
+
+        let response = self.service.call(req);
+        Box::pin(async move {
+            let mut response = response.await?;
+            if let Ok(value) = header::HeaderValue::from_str(&request_id) {
+                response
+                    .headers_mut()
+                    .insert(header::HeaderName::from_static(REQUEST_ID_HEADER), value);
+            }
+            let is_json = response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .map_or(false, |content_type| {
+                    content_type.as_bytes().starts_with(b"application/json")
+                });
+            if response.status() != actix_web::http::StatusCode::OK || !is_json {
+                return Ok(response.map_into_boxed_body());
+            }
+            if let actix_web::body::BodySize::Stream = response.response().body().size() {
+                return Ok(response.map_into_boxed_body());
+            }
+            let (req, response) = response.into_parts();
+            let (response, body) = response.into_parts();
+            let body = actix_web::body::to_bytes(body).await.map_err(|err| {
+                let err: Box<dyn std::error::Error> = err.into();
+                errors::Error::from_error_kind(errors::ErrorKind::InternalError(format!(
+                    "Could not read the response body: {}",
+                    err
+                )))
+            })?;
+            let body = match serde_json::from_slice::<serde_json::Value>(&body) {
+                Ok(mut value) if value.get("meta").is_some() => {
+                    fill_meta(&mut value, &request_id, head, &params);
+                    actix_web::web::Bytes::from(
+                        serde_json::to_vec(&value).map_err(errors::Error::from)?,
+                    )
+                }
+                _ => body,
+            };
+            Ok(ServiceResponse::new(
+                req,
+                response.set_body(body).map_into_boxed_body(),
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::query_params::TimestampFormat;
+
+    #[test]
+    fn test_fill_meta() {
+        let head = IndexerHead {
+            block_height: 105,
+            block_timestamp: 1655571176644255779,
+        };
+        let mut value = serde_json::json!({
+            "balance": "10",
+            "meta": {"block_height": "100", "block_hash": "11111111111111111111111111111111"},
+        });
+        fill_meta(
+            &mut value,
+            "req-1",
+            Some(head),
+            &ResponseFormatParams::default(),
+        );
+        assert_eq!(value["meta"]["request_id"], "req-1");
+        assert_eq!(value["meta"]["block_height"], "100");
+        assert_eq!(value["meta"]["last_indexed_block_height"], "105");
+        assert_eq!(
+            value["meta"]["last_indexed_block_timestamp_iso8601"],
+            "2022-06-18T16:52:56.644255779Z"
+        );
+
+        let mut value = serde_json::json!({"meta": {}});
+        fill_meta(
+            &mut value,
+            "req-2",
+            Some(head),
+            &ResponseFormatParams {
+                timestamp_format: Some(TimestampFormat::EpochMs),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            value["meta"]["last_indexed_block_timestamp_ms"],
+            1655571176644u64
+        );
+        assert!(value["meta"]
+            .get("last_indexed_block_timestamp_nanos")
+            .is_none());
+
+        // Not known yet
+        let mut value = serde_json::json!({"meta": {}});
+        fill_meta(&mut value, "req-3", None, &ResponseFormatParams::default());
+        assert_eq!(value["meta"], serde_json::json!({"request_id": "req-3"}));
+
+        let mut value = serde_json::json!({"accounts": []});
+        fill_meta(
+            &mut value,
+            "req-4",
+            Some(head),
+            &ResponseFormatParams::default(),
+        );
+        assert_eq!(value, serde_json::json!({"accounts": []}));
+    }
+
+    #[actix_web::test]
+    async fn test_response_meta_filler() {
+        let filler = ResponseMetaFiller::new();
+        let app = actix_web::test::init_service(actix_web::App::new().wrap(filler).route(
+            "/balance",
+            actix_web::web::get().to(|| async {
+                actix_web::HttpResponse::Ok()
+                    .json(serde_json::json!({"balance": "10", "meta": {"block_height": "100"}}))
+            }),
+        ))
+        .await;
+
+        let request = actix_web::test::TestRequest::get()
+            .uri("/balance")
+            .insert_header((REQUEST_ID_HEADER, "trace-42"))
+            .to_request();
+        let response = actix_web::test::call_service(&app, request).await;
+        assert_eq!(
+            response.headers().get(REQUEST_ID_HEADER).unwrap(),
+            "trace-42"
+        );
+        let body: serde_json::Value = actix_web::test::read_body_json(response).await;
+        assert_eq!(body["meta"]["request_id"], "trace-42");
+
+        // The unusual ids are replaced
+        let request = actix_web::test::TestRequest::get()
+            .uri("/balance")
+            .insert_header((REQUEST_ID_HEADER, "two words"))
+            .to_request();
+        let response = actix_web::test::call_service(&app, request).await;
+        let request_id = response
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert_ne!(request_id, "two words");
+        let body: serde_json::Value = actix_web::test::read_body_json(response).await;
+        assert_eq!(body["meta"]["request_id"], request_id.as_str());
+    }
+}
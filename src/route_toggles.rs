@@ -0,0 +1,146 @@
+//! Actix middleware which switches off the route groups at runtime, so the operators could shed the load
+//! during the incidents (e.g. the heavy history exports) without the redeploy.
+//! The disabled routes answer 503 `ROUTE_DISABLED` before reaching the handlers.
+//! The initial list is taken from `DISABLED_ROUTES`, `/admin/routes/disabled` changes it.
+//! The list lives in memory, each instance behind the load balancer should be switched separately.
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use futures::future::{ready, LocalBoxFuture, Ready};
+
+use crate::{config, errors};
+
+/// The admin endpoints are never disabled, otherwise there is no way to switch the routes back on
+const ALWAYS_ENABLED_PATH_PREFIX: &str = "/admin";
+
+type DisabledPathPrefixes = std::sync::Arc<std::sync::RwLock<Vec<String>>>;
+
+/// Create it once and clone to all the workers and to the admin endpoints, so they share the list
+#[derive(Clone, Default)]
+pub struct RouteToggles {
+    disabled: DisabledPathPrefixes,
+}
+
+impl RouteToggles {
+    /// The path prefixes in the format of `config::RouteLimitsConfig`, `*` matches any segment
+    pub fn disabled_path_prefixes(&self) -> Vec<String> {
+        self.disabled
+            .read()
+            .expect("route toggles lock is poisoned")
+            .clone()
+    }
+
+    /// Replaces the whole list, the empty list enables everything
+    pub fn set_disabled_path_prefixes(&self, path_prefixes: Vec<String>) {
+        *self
+            .disabled
+            .write()
+            .expect("route toggles lock is poisoned") = path_prefixes;
+    }
+
+    /// The prefix which disables the path, `None` if the path is enabled
+    fn disabled_by(&self, path: &str) -> Option<String> {
+        if config::matches_path_prefix(ALWAYS_ENABLED_PATH_PREFIX, path) {
+            return None;
+        }
+        self.disabled
+            .read()
+            .expect("route toggles lock is poisoned")
+            .iter()
+            .find(|prefix| config::matches_path_prefix(prefix, path))
+            .cloned()
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RouteToggles
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = RouteTogglesMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RouteTogglesMiddleware {
+            service,
+            toggles: self.clone(),
+        }))
+    }
+}
+
+pub struct RouteTogglesMiddleware<S> {
+    service: S,
+    toggles: RouteToggles,
+}
+
+impl<S, B> Service<ServiceRequest> for RouteTogglesMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if let Some(path_prefix) = self.toggles.disabled_by(req.path()) {
+            let err =
+                errors::Error::from_error_kind(errors::ErrorKind::RouteDisabled { path_prefix });
+            return Box::pin(ready(Ok(req.error_response(err))));
+        }
+        let response = self.service.call(req);
+        Box::pin(async move { Ok(response.await?.map_into_boxed_body()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_route_toggles() {
+        let toggles = RouteToggles::default();
+        let app = actix_web::test::init_service(
+            actix_web::App::new()
+                .wrap(toggles.clone())
+                .default_service(actix_web::web::to(|| async {
+                    actix_web::HttpResponse::Ok().finish()
+                })),
+        )
+        .await;
+        let status = |path: &'static str| {
+            let app = &app;
+            async move {
+                let request = actix_web::test::TestRequest::get().uri(path).to_request();
+                actix_web::test::call_service(app, request).await.status()
+            }
+        };
+
+        assert_eq!(status("/accounts/alice.near/coins/NEAR/history").await, 200);
+
+        toggles.set_disabled_path_prefixes(vec!["/accounts/*/coins/NEAR/history".to_string()]);
+        let response = actix_web::test::call_service(
+            &app,
+            actix_web::test::TestRequest::get()
+                .uri("/accounts/alice.near/coins/NEAR/history")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), 503);
+        let error: errors::Error = actix_web::test::read_body_json(response).await;
+        assert_eq!(error.error_code, "ROUTE_DISABLED");
+        assert_eq!(status("/accounts/alice.near/coins").await, 200);
+
+        // Everything but the admin endpoints
+        toggles.set_disabled_path_prefixes(vec!["/".to_string()]);
+        assert_eq!(status("/accounts/alice.near/coins").await, 503);
+        assert_eq!(status("/admin/routes/disabled").await, 200);
+
+        toggles.set_disabled_path_prefixes(vec![]);
+        assert_eq!(status("/accounts/alice.near/coins/NEAR/history").await, 200);
+    }
+}
@@ -0,0 +1,185 @@
+//! Record/replay of RPC responses for the tests, so the snapshots don't drift when mainnet state changes.
+//! By default the responses are replayed from the cassette file and the real RPC is never called.
+//! `RPC_CASSETTES=record` sends the calls to `RPC_URL` and writes the responses to the cassette.
+//! The handler errors (e.g. the method is not found) are recorded as well, the transport errors are not.
+use std::collections::BTreeMap;
+
+use near_jsonrpc_client::errors::{JsonRpcError, JsonRpcServerError};
+use near_jsonrpc_client::methods::EXPERIMENTAL_protocol_config::RpcProtocolConfigRequest;
+use near_jsonrpc_primitives::types::config::RpcProtocolConfigError;
+use near_jsonrpc_primitives::types::query::{RpcQueryError, RpcQueryResponse};
+use near_jsonrpc_primitives::types::validator::RpcValidatorError;
+
+use crate::rpc_helpers::{ProtocolConfigView, RpcApi};
+
+/// The tests record in parallel, the file is rewritten under the lock
+static CASSETTE_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Recording {
+    Ok(serde_json::Value),
+    HandlerError(serde_json::Value),
+}
+
+pub(crate) struct CassetteRpcClient {
+    path: std::path::PathBuf,
+    /// `None` in the replay mode
+    inner: Option<near_jsonrpc_client::JsonRpcClient>,
+    recordings: BTreeMap<String, Recording>,
+}
+
+impl CassetteRpcClient {
+    pub fn record(inner: near_jsonrpc_client::JsonRpcClient, path: &std::path::Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            inner: Some(inner),
+            recordings: BTreeMap::new(),
+        }
+    }
+
+    pub fn replay(path: &std::path::Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            inner: None,
+            recordings: load(path),
+        }
+    }
+
+    fn save<R: serde::Serialize, E: serde::Serialize>(
+        &self,
+        key: String,
+        result: &Result<R, JsonRpcError<E>>,
+    ) {
+        let recording = match result {
+            Ok(response) => Recording::Ok(serde_json::to_value(response).unwrap()),
+            Err(err) => match err.handler_error() {
+                Some(handler_error) => {
+                    Recording::HandlerError(serde_json::to_value(handler_error).unwrap())
+                }
+                None => return,
+            },
+        };
+        let _lock = CASSETTE_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let mut recordings = load(&self.path);
+        recordings.insert(key, recording);
+        std::fs::write(
+            &self.path,
+            serde_json::to_string_pretty(&recordings).unwrap() + "\n",
+        )
+        .expect("failed to write the RPC cassette");
+    }
+
+    fn replay_call<R: serde::de::DeserializeOwned, E: serde::de::DeserializeOwned>(
+        &self,
+        key: &str,
+    ) -> Result<R, JsonRpcError<E>> {
+        match self.recordings.get(key) {
+            Some(Recording::Ok(response)) => {
+                Ok(serde_json::from_value(response.clone()).expect("broken RPC cassette"))
+            }
+            Some(Recording::HandlerError(handler_error)) => {
+                Err(JsonRpcError::ServerError(JsonRpcServerError::HandlerError(
+                    serde_json::from_value(handler_error.clone()).expect("broken RPC cassette"),
+                )))
+            }
+            None => panic!(
+                "RPC call is not recorded in {:?}, run the test with RPC_CASSETTES=record: {}",
+                self.path, key
+            ),
+        }
+    }
+}
+
+fn load(path: &std::path::Path) -> BTreeMap<String, Recording> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content).expect("broken RPC cassette"),
+        Err(_) => BTreeMap::new(),
+    }
+}
+
+fn cassette_key(method: &str, request: &impl serde::Serialize) -> String {
+    format!("{} {}", method, serde_json::to_string(request).unwrap())
+}
+
+#[async_trait::async_trait]
+impl RpcApi for CassetteRpcClient {
+    async fn query(
+        &self,
+        request: near_jsonrpc_client::methods::query::RpcQueryRequest,
+    ) -> Result<RpcQueryResponse, JsonRpcError<RpcQueryError>> {
+        let key = cassette_key("query", &request);
+        match &self.inner {
+            Some(inner) => {
+                let result = inner.call(request).await;
+                self.save(key, &result);
+                result
+            }
+            None => self.replay_call(&key),
+        }
+    }
+
+    async fn validators(
+        &self,
+        request: near_jsonrpc_client::methods::validators::RpcValidatorRequest,
+    ) -> Result<near_primitives::views::EpochValidatorInfo, JsonRpcError<RpcValidatorError>> {
+        let key = cassette_key("validators", &request);
+        match &self.inner {
+            Some(inner) => {
+                let result = inner.call(request).await;
+                self.save(key, &result);
+                result
+            }
+            None => self.replay_call(&key),
+        }
+    }
+
+    async fn protocol_config(
+        &self,
+        request: RpcProtocolConfigRequest,
+    ) -> Result<ProtocolConfigView, JsonRpcError<RpcProtocolConfigError>> {
+        let key = cassette_key("EXPERIMENTAL_protocol_config", &request);
+        match &self.inner {
+            Some(inner) => {
+                let result = inner.call(request).await;
+                self.save(key, &result);
+                result
+            }
+            None => self.replay_call(&key),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc_helpers;
+
+    #[tokio::test]
+    async fn test_replay_recorded_call() {
+        let path = std::env::temp_dir().join(format!("rpc_cassette_{}.json", std::process::id()));
+        let request = rpc_helpers::get_function_call_request(
+            1,
+            "token.near".parse().unwrap(),
+            "ft_metadata",
+            serde_json::json!({}),
+        );
+        let response = RpcQueryResponse {
+            kind: near_jsonrpc_primitives::types::query::QueryResponseKind::CallResult(
+                near_primitives::views::CallResult {
+                    result: b"{}".to_vec(),
+                    logs: vec![],
+                },
+            ),
+            block_height: 1,
+            block_hash: Default::default(),
+        };
+        CassetteRpcClient::replay(&path)
+            .save::<_, RpcQueryError>(cassette_key("query", &request), &Ok(response));
+
+        let rpc_client = CassetteRpcClient::replay(&path);
+        let replayed = rpc_client.query(request).await.unwrap();
+        assert_eq!(replayed.block_height, 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
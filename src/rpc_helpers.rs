@@ -1,6 +1,567 @@
-use near_jsonrpc_primitives::types::query::{QueryResponseKind, RpcQueryError};
+use std::collections::HashMap;
 
-use crate::errors;
+use near_jsonrpc_client::errors::{JsonRpcError, JsonRpcServerError};
+use near_jsonrpc_client::methods::EXPERIMENTAL_protocol_config::RpcProtocolConfigRequest;
+use near_jsonrpc_primitives::types::config::RpcProtocolConfigError;
+use near_jsonrpc_primitives::types::query::{QueryResponseKind, RpcQueryError, RpcQueryResponse};
+use near_jsonrpc_primitives::types::validator::RpcValidatorError;
+
+use crate::{cache, errors};
+
+// Short enough to see the contract deployed right after the failed lookup
+const NEGATIVE_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(60);
+// The contracts rarely get the new standard methods, the redeploy is noticed after that time
+const CAPABILITY_RECHECK_AFTER: std::time::Duration = std::time::Duration::from_secs(3600);
+const MAX_CAPABILITY_ITEMS: usize = 100_000;
+// Regular nodes keep 5 epochs (43200 blocks each) by default, we leave the margin for the smaller GC settings
+const REGULAR_RPC_BLOCKS: u64 = 3 * 43_200;
+
+/// `near-chain-configs` is not our direct dependency, so the type is taken from the method
+pub(crate) type ProtocolConfigView =
+    <RpcProtocolConfigRequest as near_jsonrpc_client::methods::RpcMethod>::Response;
+
+/// All the RPC calls go through this trait.
+/// It makes possible to substitute RPC in the tests, or to add the logic around the calls.
+#[async_trait::async_trait]
+pub(crate) trait RpcApi: Send + Sync {
+    async fn query(
+        &self,
+        request: near_jsonrpc_client::methods::query::RpcQueryRequest,
+    ) -> Result<RpcQueryResponse, JsonRpcError<RpcQueryError>>;
+
+    async fn validators(
+        &self,
+        request: near_jsonrpc_client::methods::validators::RpcValidatorRequest,
+    ) -> Result<near_primitives::views::EpochValidatorInfo, JsonRpcError<RpcValidatorError>>;
+
+    async fn protocol_config(
+        &self,
+        request: RpcProtocolConfigRequest,
+    ) -> Result<ProtocolConfigView, JsonRpcError<RpcProtocolConfigError>>;
+}
+
+#[async_trait::async_trait]
+impl RpcApi for near_jsonrpc_client::JsonRpcClient {
+    async fn query(
+        &self,
+        request: near_jsonrpc_client::methods::query::RpcQueryRequest,
+    ) -> Result<RpcQueryResponse, JsonRpcError<RpcQueryError>> {
+        self.call(request).await
+    }
+
+    async fn validators(
+        &self,
+        request: near_jsonrpc_client::methods::validators::RpcValidatorRequest,
+    ) -> Result<near_primitives::views::EpochValidatorInfo, JsonRpcError<RpcValidatorError>> {
+        self.call(request).await
+    }
+
+    async fn protocol_config(
+        &self,
+        request: RpcProtocolConfigRequest,
+    ) -> Result<ProtocolConfigView, JsonRpcError<RpcProtocolConfigError>> {
+        self.call(request).await
+    }
+}
+
+/// Queues the calls when `max_concurrent_calls` are already in flight.
+/// Traffic spikes become slower responses instead of thousands of simultaneous connections
+/// to the archival RPC, which then rate limits us
+pub(crate) struct LimitedRpcClient<T> {
+    inner: T,
+    semaphore: tokio::sync::Semaphore,
+}
+
+impl<T> LimitedRpcClient<T> {
+    pub fn new(inner: T, max_concurrent_calls: usize) -> Self {
+        Self {
+            inner,
+            semaphore: tokio::sync::Semaphore::new(max_concurrent_calls),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: RpcApi> RpcApi for LimitedRpcClient<T> {
+    async fn query(
+        &self,
+        request: near_jsonrpc_client::methods::query::RpcQueryRequest,
+    ) -> Result<RpcQueryResponse, JsonRpcError<RpcQueryError>> {
+        // The semaphore is never closed
+        let _permit = self.semaphore.acquire().await.ok();
+        self.inner.query(request).await
+    }
+
+    async fn validators(
+        &self,
+        request: near_jsonrpc_client::methods::validators::RpcValidatorRequest,
+    ) -> Result<near_primitives::views::EpochValidatorInfo, JsonRpcError<RpcValidatorError>> {
+        let _permit = self.semaphore.acquire().await.ok();
+        self.inner.validators(request).await
+    }
+
+    async fn protocol_config(
+        &self,
+        request: RpcProtocolConfigRequest,
+    ) -> Result<ProtocolConfigView, JsonRpcError<RpcProtocolConfigError>> {
+        let _permit = self.semaphore.acquire().await.ok();
+        self.inner.protocol_config(request).await
+    }
+}
+
+/// Remembers for a short time that the account does not exist or has no contract.
+/// Wallets often retry the lookups of the plain accounts, each of them used to go to the archival node.
+/// The key is the account, the method and the block reference: the contract could be deployed
+/// at the later block, and `MethodNotFound` is left to `CapabilityCachingRpcClient` which tracks the blocks
+pub(crate) struct NegativeCachingRpcClient<T> {
+    inner: T,
+    // The errors are not `Clone`, so we keep them serialized
+    not_found: cache::TtlCache<NegativeCacheKey, serde_json::Value>,
+}
+
+/// The account, the method and `Debug` of the block reference, it does not implement `Hash`
+type NegativeCacheKey = (near_primitives::types::AccountId, String, String);
+
+impl<T> NegativeCachingRpcClient<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            not_found: Default::default(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: RpcApi> RpcApi for NegativeCachingRpcClient<T> {
+    async fn query(
+        &self,
+        request: near_jsonrpc_client::methods::query::RpcQueryRequest,
+    ) -> Result<RpcQueryResponse, JsonRpcError<RpcQueryError>> {
+        let key = match &request.request {
+            near_primitives::views::QueryRequest::CallFunction {
+                account_id,
+                method_name,
+                ..
+            } => Some((
+                account_id.clone(),
+                method_name.clone(),
+                format!("{:?}", request.block_reference),
+            )),
+            _ => None,
+        };
+        if let Some(key) = &key {
+            if let Some(error) = self
+                .not_found
+                .get(key, NEGATIVE_CACHE_TTL)
+                .and_then(|error| serde_json::from_value::<RpcQueryError>(error).ok())
+            {
+                return Err(JsonRpcError::ServerError(JsonRpcServerError::HandlerError(
+                    error,
+                )));
+            }
+        }
+
+        let response = self.inner.query(request).await;
+        if let (Some(key), Err(err)) = (key, &response) {
+            if let Some(error) = err.handler_error().filter(|error| is_not_found(error)) {
+                if let Ok(error) = serde_json::to_value(error) {
+                    self.not_found.insert(key, error);
+                }
+            }
+        }
+        response
+    }
+
+    async fn validators(
+        &self,
+        request: near_jsonrpc_client::methods::validators::RpcValidatorRequest,
+    ) -> Result<near_primitives::views::EpochValidatorInfo, JsonRpcError<RpcValidatorError>> {
+        self.inner.validators(request).await
+    }
+
+    async fn protocol_config(
+        &self,
+        request: RpcProtocolConfigRequest,
+    ) -> Result<ProtocolConfigView, JsonRpcError<RpcProtocolConfigError>> {
+        self.inner.protocol_config(request).await
+    }
+}
+
+/// Remembers which contracts lack which methods (`MethodNotFound`), so the overview endpoints
+/// stop calling `ft_metadata`, `nft_metadata` etc. of the contracts which will never have them.
+/// The method is known to be missing between the lowest and the highest blocks where RPC said so,
+/// these calls are answered right away. The newer blocks are answered the same way
+/// for `CAPABILITY_RECHECK_AFTER`, then RPC is asked again in case the contract was redeployed.
+/// The successful call forgets the method. The table lives in memory, each instance fills its own
+pub(crate) struct CapabilityCachingRpcClient<T> {
+    inner: T,
+    missing_methods: std::sync::Mutex<HashMap<MethodKey, MissingMethod>>,
+    recheck_after: std::time::Duration,
+}
+
+/// The contract and the method
+type MethodKey = (near_primitives::types::AccountId, String);
+
+#[derive(Debug, Clone)]
+struct MissingMethod {
+    vm_error: String,
+    from_block_height: u64,
+    to_block_height: u64,
+    checked_at: std::time::Instant,
+}
+
+impl MissingMethod {
+    fn is_known_at(&self, block_height: u64, recheck_after: std::time::Duration) -> bool {
+        (self.from_block_height..=self.to_block_height).contains(&block_height)
+            || (block_height > self.to_block_height && self.checked_at.elapsed() < recheck_after)
+    }
+}
+
+impl<T> CapabilityCachingRpcClient<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            missing_methods: Default::default(),
+            recheck_after: CAPABILITY_RECHECK_AFTER,
+        }
+    }
+
+    fn missing_methods(&self) -> std::sync::MutexGuard<HashMap<MethodKey, MissingMethod>> {
+        self.missing_methods
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn remember_missing(&self, key: MethodKey, block_height: u64, vm_error: &str) {
+        let mut missing_methods = self.missing_methods();
+        if missing_methods.len() >= MAX_CAPABILITY_ITEMS && !missing_methods.contains_key(&key) {
+            // Same as `cache::TtlCache`, it only costs a few calls
+            missing_methods.clear();
+        }
+        let missing = missing_methods.entry(key).or_insert_with(|| MissingMethod {
+            vm_error: vm_error.to_string(),
+            from_block_height: block_height,
+            to_block_height: block_height,
+            checked_at: std::time::Instant::now(),
+        });
+        missing.from_block_height = missing.from_block_height.min(block_height);
+        if block_height >= missing.to_block_height {
+            missing.to_block_height = block_height;
+            missing.checked_at = std::time::Instant::now();
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: RpcApi> RpcApi for CapabilityCachingRpcClient<T> {
+    async fn query(
+        &self,
+        request: near_jsonrpc_client::methods::query::RpcQueryRequest,
+    ) -> Result<RpcQueryResponse, JsonRpcError<RpcQueryError>> {
+        let call = match (&request.block_reference, &request.request) {
+            (
+                near_primitives::types::BlockReference::BlockId(
+                    near_primitives::types::BlockId::Height(block_height),
+                ),
+                near_primitives::views::QueryRequest::CallFunction {
+                    account_id,
+                    method_name,
+                    ..
+                },
+            ) => Some(((account_id.clone(), method_name.clone()), *block_height)),
+            _ => None,
+        };
+        let (key, block_height) = match call {
+            Some(call) => call,
+            None => return self.inner.query(request).await,
+        };
+        let known_missing = self
+            .missing_methods()
+            .get(&key)
+            .filter(|missing| missing.is_known_at(block_height, self.recheck_after))
+            .map(|missing| missing.vm_error.clone());
+        if let Some(vm_error) = known_missing {
+            return Err(JsonRpcError::ServerError(JsonRpcServerError::HandlerError(
+                RpcQueryError::ContractExecutionError {
+                    vm_error,
+                    block_height,
+                    block_hash: Default::default(),
+                },
+            )));
+        }
+
+        let response = self.inner.query(request).await;
+        match &response {
+            Ok(_) => {
+                self.missing_methods().remove(&key);
+            }
+            Err(err) => {
+                if let Some(RpcQueryError::ContractExecutionError { vm_error, .. }) =
+                    err.handler_error()
+                {
+                    if is_method_not_found(vm_error) {
+                        self.remember_missing(key, block_height, vm_error);
+                    }
+                }
+            }
+        }
+        response
+    }
+
+    async fn validators(
+        &self,
+        request: near_jsonrpc_client::methods::validators::RpcValidatorRequest,
+    ) -> Result<near_primitives::views::EpochValidatorInfo, JsonRpcError<RpcValidatorError>> {
+        self.inner.validators(request).await
+    }
+
+    async fn protocol_config(
+        &self,
+        request: RpcProtocolConfigRequest,
+    ) -> Result<ProtocolConfigView, JsonRpcError<RpcProtocolConfigError>> {
+        self.inner.protocol_config(request).await
+    }
+}
+
+/// Warns about the calls slower than `threshold` with the contract, the method and the block,
+/// so the operators could find the pathological contracts. The time in the queue is not counted.
+/// Logs nothing if `threshold` is not set
+pub(crate) struct SlowCallLoggingRpcClient<T> {
+    inner: T,
+    threshold: Option<std::time::Duration>,
+}
+
+impl<T> SlowCallLoggingRpcClient<T> {
+    pub fn new(inner: T, threshold: Option<std::time::Duration>) -> Self {
+        Self { inner, threshold }
+    }
+
+    fn is_slow(&self, elapsed: std::time::Duration) -> bool {
+        self.threshold
+            .map_or(false, |threshold| elapsed >= threshold)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: RpcApi> RpcApi for SlowCallLoggingRpcClient<T> {
+    async fn query(
+        &self,
+        request: near_jsonrpc_client::methods::query::RpcQueryRequest,
+    ) -> Result<RpcQueryResponse, JsonRpcError<RpcQueryError>> {
+        let (account_id, method_name) = match &request.request {
+            near_primitives::views::QueryRequest::CallFunction {
+                account_id,
+                method_name,
+                ..
+            } => (account_id.to_string(), method_name.clone()),
+            other => ("-".to_string(), format!("{:?}", other)),
+        };
+        let block_reference = request.block_reference.clone();
+        let started = std::time::Instant::now();
+        let response = self.inner.query(request).await;
+        let elapsed = started.elapsed();
+        if self.is_slow(elapsed) {
+            tracing::warn!(
+                target: crate::LOGGER_MSG,
+                duration_ms = elapsed.as_millis() as u64,
+                contract_id = %account_id,
+                method_name = %method_name,
+                block = ?block_reference,
+                is_ok = response.is_ok(),
+                "Slow RPC call"
+            );
+        }
+        response
+    }
+
+    async fn validators(
+        &self,
+        request: near_jsonrpc_client::methods::validators::RpcValidatorRequest,
+    ) -> Result<near_primitives::views::EpochValidatorInfo, JsonRpcError<RpcValidatorError>> {
+        let epoch_reference = request.epoch_reference.clone();
+        let started = std::time::Instant::now();
+        let response = self.inner.validators(request).await;
+        let elapsed = started.elapsed();
+        if self.is_slow(elapsed) {
+            tracing::warn!(
+                target: crate::LOGGER_MSG,
+                duration_ms = elapsed.as_millis() as u64,
+                method_name = "validators",
+                epoch = ?epoch_reference,
+                is_ok = response.is_ok(),
+                "Slow RPC call"
+            );
+        }
+        response
+    }
+
+    async fn protocol_config(
+        &self,
+        request: RpcProtocolConfigRequest,
+    ) -> Result<ProtocolConfigView, JsonRpcError<RpcProtocolConfigError>> {
+        let block_reference = request.block_reference.clone();
+        let started = std::time::Instant::now();
+        let response = self.inner.protocol_config(request).await;
+        let elapsed = started.elapsed();
+        if self.is_slow(elapsed) {
+            tracing::warn!(
+                target: crate::LOGGER_MSG,
+                duration_ms = elapsed.as_millis() as u64,
+                method_name = "EXPERIMENTAL_protocol_config",
+                block = ?block_reference,
+                is_ok = response.is_ok(),
+                "Slow RPC call"
+            );
+        }
+        response
+    }
+}
+
+/// Sends the calls for the recent blocks to the regular RPC node and the historical ones to the archival node.
+/// The head is taken from the regular node responses; until it's known, or if the regular node has already
+/// pruned the block, the call is repeated at the archival node.
+/// Without the archival node everything goes to the regular one, it should be archival itself then
+pub(crate) struct ArchivalRoutingRpcClient<T> {
+    regular: T,
+    archival: Option<T>,
+    head_height: std::sync::atomic::AtomicU64,
+}
+
+impl<T> ArchivalRoutingRpcClient<T> {
+    pub fn new(regular: T, archival: Option<T>) -> Self {
+        Self {
+            regular,
+            archival,
+            head_height: Default::default(),
+        }
+    }
+
+    /// The archival node, if the block is surely pruned at the regular one
+    fn archival_for(&self, block_height: u64) -> Option<&T> {
+        let head_height = self.head_height.load(std::sync::atomic::Ordering::Relaxed);
+        self.archival
+            .as_ref()
+            .filter(|_| head_height.saturating_sub(block_height) > REGULAR_RPC_BLOCKS)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: RpcApi> RpcApi for ArchivalRoutingRpcClient<T> {
+    async fn query(
+        &self,
+        request: near_jsonrpc_client::methods::query::RpcQueryRequest,
+    ) -> Result<RpcQueryResponse, JsonRpcError<RpcQueryError>> {
+        if let near_primitives::types::BlockReference::BlockId(
+            near_primitives::types::BlockId::Height(block_height),
+        ) = request.block_reference
+        {
+            if let Some(archival) = self.archival_for(block_height) {
+                return archival.query(request).await;
+            }
+        }
+        let archival = match &self.archival {
+            Some(archival) => archival,
+            None => return self.regular.query(request).await,
+        };
+
+        match self.regular.query(request.clone()).await {
+            Ok(response) => {
+                self.head_height
+                    .fetch_max(response.block_height, std::sync::atomic::Ordering::Relaxed);
+                Ok(response)
+            }
+            Err(err)
+                if matches!(
+                    err.handler_error(),
+                    Some(RpcQueryError::GarbageCollectedBlock { .. })
+                        | Some(RpcQueryError::UnknownBlock { .. })
+                ) =>
+            {
+                archival.query(request).await
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn validators(
+        &self,
+        request: near_jsonrpc_client::methods::validators::RpcValidatorRequest,
+    ) -> Result<near_primitives::views::EpochValidatorInfo, JsonRpcError<RpcValidatorError>> {
+        if let near_primitives::types::EpochReference::BlockId(
+            near_primitives::types::BlockId::Height(block_height),
+        ) = request.epoch_reference
+        {
+            if let Some(archival) = self.archival_for(block_height) {
+                return archival.validators(request).await;
+            }
+        }
+        let archival = match &self.archival {
+            Some(archival) => archival,
+            None => return self.regular.validators(request).await,
+        };
+
+        match self.regular.validators(request.clone()).await {
+            Err(err) if matches!(err.handler_error(), Some(RpcValidatorError::UnknownEpoch)) => {
+                archival.validators(request).await
+            }
+            response => response,
+        }
+    }
+
+    async fn protocol_config(
+        &self,
+        request: RpcProtocolConfigRequest,
+    ) -> Result<ProtocolConfigView, JsonRpcError<RpcProtocolConfigError>> {
+        let block_reference = request.block_reference.clone();
+        if let near_primitives::types::BlockReference::BlockId(
+            near_primitives::types::BlockId::Height(block_height),
+        ) = block_reference
+        {
+            if let Some(archival) = self.archival_for(block_height) {
+                return archival.protocol_config(request).await;
+            }
+        }
+        let archival = match &self.archival {
+            Some(archival) => archival,
+            None => return self.regular.protocol_config(request).await,
+        };
+
+        match self.regular.protocol_config(request).await {
+            Err(err)
+                if matches!(
+                    err.handler_error(),
+                    Some(RpcProtocolConfigError::UnknownBlock { .. })
+                ) =>
+            {
+                archival
+                    .protocol_config(RpcProtocolConfigRequest { block_reference })
+                    .await
+            }
+            response => response,
+        }
+    }
+}
+
+/// The account does not exist or has no contract
+fn is_not_found(error: &RpcQueryError) -> bool {
+    match error {
+        RpcQueryError::UnknownAccount { .. } => true,
+        RpcQueryError::ContractExecutionError { vm_error, .. } => is_code_not_found(vm_error),
+        _ => false,
+    }
+}
+
+fn is_code_not_found(vm_error: &str) -> bool {
+    vm_error.contains("CodeDoesNotExist")
+}
+
+fn is_contract_not_found(vm_error: &str) -> bool {
+    is_code_not_found(vm_error) || is_method_not_found(vm_error)
+}
+
+fn is_method_not_found(vm_error: &str) -> bool {
+    vm_error.contains("MethodNotFound")
+}
 
 pub(crate) fn get_function_call_request(
     block_height: u64,
@@ -21,11 +582,30 @@ pub(crate) fn get_function_call_request(
 }
 
 pub(crate) async fn wrapped_call(
-    rpc_client: &near_jsonrpc_client::JsonRpcClient,
+    rpc_client: &dyn RpcApi,
     request: near_jsonrpc_client::methods::query::RpcQueryRequest,
     block_height: u64,
     contract_id: &near_primitives::types::AccountId,
 ) -> crate::Result<near_primitives::views::CallResult> {
+    wrapped_optional_call(rpc_client, request, block_height, contract_id)
+        .await?
+        .ok_or_else(|| {
+            errors::ErrorKind::ContractNotFound {
+                contract_id: contract_id.to_string(),
+                block_height,
+            }
+            .into()
+        })
+}
+
+/// The same as `wrapped_call`, but gives `None` if the contract is deployed and does not have the method,
+/// e.g. for the optional extensions of the standards
+pub(crate) async fn wrapped_optional_call(
+    rpc_client: &dyn RpcApi,
+    request: near_jsonrpc_client::methods::query::RpcQueryRequest,
+    block_height: u64,
+    contract_id: &near_primitives::types::AccountId,
+) -> crate::Result<Option<near_primitives::views::CallResult>> {
     tracing::info!(
         target: crate::LOGGER_MSG,
         "RPC request: {:?}\nTo contract:{}, block {}",
@@ -33,9 +613,9 @@ pub(crate) async fn wrapped_call(
         contract_id,
         block_height
     );
-    match rpc_client.call(request).await {
+    match rpc_client.query(request).await {
         Ok(response) => match response.kind {
-            QueryResponseKind::CallResult(result) => Ok(result),
+            QueryResponseKind::CallResult(result) => Ok(Some(result)),
             _ => Err(errors::ErrorKind::RPCError(
                 "Unexpected type of the response after CallFunction request".to_string(),
             )
@@ -44,11 +624,14 @@ pub(crate) async fn wrapped_call(
         Err(x) => {
             if let Some(RpcQueryError::ContractExecutionError { vm_error, .. }) = x.handler_error()
             {
-                if vm_error.contains("CodeDoesNotExist") || vm_error.contains("MethodNotFound") {
-                    return Err(errors::ErrorKind::InvalidInput(format!(
-                        "The account `{}` does not implement any suitable contract at block {}",
-                        contract_id, block_height
-                    ))
+                if is_method_not_found(vm_error) {
+                    return Ok(None);
+                }
+                if is_contract_not_found(vm_error) {
+                    return Err(errors::ErrorKind::ContractNotFound {
+                        contract_id: contract_id.to_string(),
+                        block_height,
+                    }
                     .into());
                 }
             }
@@ -56,3 +639,371 @@ pub(crate) async fn wrapped_call(
         }
     }
 }
+
+pub(crate) async fn get_epoch_info(
+    rpc_client: &dyn RpcApi,
+    block_height: u64,
+) -> crate::Result<near_primitives::views::EpochValidatorInfo> {
+    let request = near_jsonrpc_client::methods::validators::RpcValidatorRequest {
+        epoch_reference: near_primitives::types::EpochReference::BlockId(
+            near_primitives::types::BlockId::Height(block_height),
+        ),
+    };
+    tracing::info!(
+        target: crate::LOGGER_MSG,
+        "RPC request: {:?}",
+        request
+    );
+    Ok(rpc_client.validators(request).await?)
+}
+
+/// The protocol and runtime parameters of the epoch the block belongs to
+pub(crate) async fn get_protocol_config(
+    rpc_client: &dyn RpcApi,
+    block_height: u64,
+) -> crate::Result<ProtocolConfigView> {
+    let request = RpcProtocolConfigRequest {
+        block_reference: near_primitives::types::BlockReference::BlockId(
+            near_primitives::types::BlockId::Height(block_height),
+        ),
+    };
+    tracing::info!(
+        target: crate::LOGGER_MSG,
+        "RPC request: {:?}",
+        request
+    );
+    Ok(rpc_client.protocol_config(request).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    type QueryResult = Result<RpcQueryResponse, JsonRpcError<RpcQueryError>>;
+
+    /// Answers `query` with `respond(block_height, method_name)`, `block_height` is `None` for the finality
+    /// requests, `method_name` is empty for the other queries. Counts the calls and the calls in flight.
+    /// `validators` and `protocol_config` are not faked, they fail with the internal error
+    struct FakeRpc {
+        respond: Box<dyn Fn(Option<u64>, &str) -> QueryResult + Send + Sync>,
+        delay: Option<std::time::Duration>,
+        calls: AtomicUsize,
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+    }
+
+    impl FakeRpc {
+        fn new(respond: impl Fn(Option<u64>, &str) -> QueryResult + Send + Sync + 'static) -> Self {
+            Self {
+                respond: Box::new(respond),
+                delay: None,
+                calls: Default::default(),
+                in_flight: Default::default(),
+                max_in_flight: Default::default(),
+            }
+        }
+
+        /// Every call succeeds
+        fn ok() -> Self {
+            Self::new(|block_height, _| call_result(block_height.unwrap_or_default()))
+        }
+
+        /// Each call takes `delay`, so they overlap
+        fn with_delay(mut self, delay: std::time::Duration) -> Self {
+            self.delay = Some(delay);
+            self
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    fn not_faked<T, E>() -> Result<T, JsonRpcError<E>> {
+        Err(JsonRpcError::ServerError(
+            JsonRpcServerError::InternalError {
+                info: Some("not faked".to_string()),
+            },
+        ))
+    }
+
+    fn call_result(block_height: u64) -> QueryResult {
+        Ok(RpcQueryResponse {
+            kind: QueryResponseKind::CallResult(near_primitives::views::CallResult {
+                result: vec![],
+                logs: vec![],
+            }),
+            block_height,
+            block_hash: Default::default(),
+        })
+    }
+
+    fn handler_error(error: RpcQueryError) -> QueryResult {
+        Err(JsonRpcError::ServerError(JsonRpcServerError::HandlerError(
+            error,
+        )))
+    }
+
+    fn vm_error(vm_error: &str, block_height: u64) -> QueryResult {
+        handler_error(RpcQueryError::ContractExecutionError {
+            vm_error: vm_error.to_string(),
+            block_height,
+            block_hash: Default::default(),
+        })
+    }
+
+    const CODE_DOES_NOT_EXIST: &str =
+        "wasm execution failed with error: CompilationError(CodeDoesNotExist)";
+    const METHOD_NOT_FOUND: &str =
+        "wasm execution failed with error: FunctionCallError(MethodResolveError(MethodNotFound))";
+
+    #[async_trait::async_trait]
+    impl RpcApi for FakeRpc {
+        async fn query(
+            &self,
+            request: near_jsonrpc_client::methods::query::RpcQueryRequest,
+        ) -> Result<RpcQueryResponse, JsonRpcError<RpcQueryError>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            if let Some(delay) = self.delay {
+                tokio::time::sleep(delay).await;
+            }
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            let block_height = match request.block_reference {
+                near_primitives::types::BlockReference::BlockId(
+                    near_primitives::types::BlockId::Height(block_height),
+                ) => Some(block_height),
+                _ => None,
+            };
+            let method_name = match &request.request {
+                near_primitives::views::QueryRequest::CallFunction { method_name, .. } => {
+                    method_name.as_str()
+                }
+                _ => "",
+            };
+            (self.respond)(block_height, method_name)
+        }
+
+        async fn validators(
+            &self,
+            _request: near_jsonrpc_client::methods::validators::RpcValidatorRequest,
+        ) -> Result<near_primitives::views::EpochValidatorInfo, JsonRpcError<RpcValidatorError>>
+        {
+            not_faked()
+        }
+
+        async fn protocol_config(
+            &self,
+            _request: RpcProtocolConfigRequest,
+        ) -> Result<ProtocolConfigView, JsonRpcError<RpcProtocolConfigError>> {
+            not_faked()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_limited_rpc_client_queues_calls() {
+        let rpc_client = LimitedRpcClient::new(
+            FakeRpc::ok().with_delay(std::time::Duration::from_millis(20)),
+            2,
+        );
+        let contract_id: near_primitives::types::AccountId = "token.near".parse().unwrap();
+
+        let calls = (0..6).map(|_| {
+            rpc_client.query(get_function_call_request(
+                1,
+                contract_id.clone(),
+                "ft_metadata",
+                serde_json::json!({}),
+            ))
+        });
+        for result in futures::future::join_all(calls).await {
+            assert!(result.is_ok());
+        }
+        assert_eq!(rpc_client.inner.max_in_flight.load(Ordering::SeqCst), 2);
+        assert!(rpc_client
+            .protocol_config(RpcProtocolConfigRequest {
+                block_reference: near_primitives::types::BlockReference::Finality(
+                    near_primitives::types::Finality::Final,
+                ),
+            })
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_slow_call_logging_rpc_client() {
+        let contract_id: near_primitives::types::AccountId = "token.near".parse().unwrap();
+        let request =
+            get_function_call_request(1, contract_id, "ft_metadata", serde_json::json!({}));
+
+        let rpc_client = SlowCallLoggingRpcClient::new(
+            FakeRpc::ok().with_delay(std::time::Duration::from_millis(20)),
+            Some(std::time::Duration::from_millis(10)),
+        );
+        assert!(rpc_client.query(request.clone()).await.is_ok());
+        assert!(rpc_client.is_slow(std::time::Duration::from_millis(20)));
+        assert!(!rpc_client.is_slow(std::time::Duration::from_millis(5)));
+
+        let rpc_client = SlowCallLoggingRpcClient::new(FakeRpc::ok(), None);
+        assert!(rpc_client.query(request).await.is_ok());
+        assert!(!rpc_client.is_slow(std::time::Duration::from_secs(60)));
+    }
+
+    #[tokio::test]
+    async fn test_negative_caching_rpc_client() {
+        // Every account is a plain account without the contract
+        let rpc_client = NegativeCachingRpcClient::new(FakeRpc::new(|block_height, _| {
+            vm_error(CODE_DOES_NOT_EXIST, block_height.unwrap_or_default())
+        }));
+        let account_id: near_primitives::types::AccountId = "olga.near".parse().unwrap();
+        let request = |block_height, method_name| {
+            get_function_call_request(
+                block_height,
+                account_id.clone(),
+                method_name,
+                serde_json::json!({}),
+            )
+        };
+
+        for _ in 0..3 {
+            let error = wrapped_call(&rpc_client, request(1, "ft_metadata"), 1, &account_id)
+                .await
+                .unwrap_err();
+            assert_eq!(error.error_code, "CONTRACT_NOT_FOUND");
+        }
+        assert_eq!(rpc_client.inner.calls(), 1);
+
+        // The other method and the other block are asked separately
+        assert!(rpc_client.query(request(1, "nft_metadata")).await.is_err());
+        assert_eq!(rpc_client.inner.calls(), 2);
+        assert!(rpc_client.query(request(2, "ft_metadata")).await.is_err());
+        assert_eq!(rpc_client.inner.calls(), 3);
+
+        // The missing method is not cached here
+        let rpc_client =
+            NegativeCachingRpcClient::new(FakeRpc::new(|_, _| vm_error(METHOD_NOT_FOUND, 1)));
+        for _ in 0..2 {
+            assert!(rpc_client.query(request(1, "ft_metadata")).await.is_err());
+        }
+        assert_eq!(rpc_client.inner.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_capability_caching_rpc_client() {
+        // The contract has `ft_metadata` only after `deployed_at`, the other methods always exist
+        let deployed_at = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(u64::MAX));
+        let mut rpc_client = CapabilityCachingRpcClient::new(FakeRpc::new({
+            let deployed_at = deployed_at.clone();
+            move |block_height, method_name| {
+                let block_height = block_height.unwrap_or(u64::MAX);
+                if method_name == "ft_metadata" && block_height < deployed_at.load(Ordering::SeqCst)
+                {
+                    vm_error(METHOD_NOT_FOUND, block_height)
+                } else {
+                    call_result(block_height)
+                }
+            }
+        }));
+        let contract_id: near_primitives::types::AccountId = "token.near".parse().unwrap();
+        let request = |block_height, method_name| {
+            get_function_call_request(
+                block_height,
+                contract_id.clone(),
+                method_name,
+                serde_json::json!({}),
+            )
+        };
+
+        let error = wrapped_call(&rpc_client, request(10, "ft_metadata"), 10, &contract_id)
+            .await
+            .unwrap_err();
+        assert_eq!(error.error_code, "CONTRACT_NOT_FOUND");
+        assert_eq!(rpc_client.inner.calls(), 1);
+
+        // The same block and the newer ones are answered from the table
+        for block_height in [10, 20] {
+            let error = wrapped_call(
+                &rpc_client,
+                request(block_height, "ft_metadata"),
+                block_height,
+                &contract_id,
+            )
+            .await
+            .unwrap_err();
+            assert_eq!(error.error_code, "CONTRACT_NOT_FOUND");
+        }
+        assert_eq!(rpc_client.inner.calls(), 1);
+
+        // The older block is asked, then the whole range is known
+        assert!(rpc_client.query(request(5, "ft_metadata")).await.is_err());
+        assert!(rpc_client.query(request(7, "ft_metadata")).await.is_err());
+        assert_eq!(rpc_client.inner.calls(), 2);
+
+        // The other methods are not affected
+        assert!(rpc_client.query(request(10, "ft_balance_of")).await.is_ok());
+        assert_eq!(rpc_client.inner.calls(), 3);
+
+        // After the recheck interval the newer blocks go to RPC again, the redeploy is noticed
+        deployed_at.store(15, Ordering::SeqCst);
+        rpc_client.recheck_after = std::time::Duration::ZERO;
+        assert!(rpc_client.query(request(7, "ft_metadata")).await.is_err());
+        assert_eq!(rpc_client.inner.calls(), 3);
+        assert!(rpc_client.query(request(20, "ft_metadata")).await.is_ok());
+        assert_eq!(rpc_client.inner.calls(), 4);
+        assert!(rpc_client.missing_methods().is_empty());
+    }
+
+    /// Regular node keeps the blocks since `pruned_below`, archival node has `pruned_below: 0`
+    fn pruning_rpc(pruned_below: u64) -> FakeRpc {
+        FakeRpc::new(move |block_height, _| {
+            let block_height = block_height.unwrap_or(u64::MAX);
+            if block_height < pruned_below {
+                handler_error(RpcQueryError::GarbageCollectedBlock {
+                    block_height,
+                    block_hash: Default::default(),
+                })
+            } else {
+                call_result(block_height)
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_archival_routing_rpc_client() {
+        let rpc_client = ArchivalRoutingRpcClient::new(pruning_rpc(450_000), Some(pruning_rpc(0)));
+        let contract_id: near_primitives::types::AccountId = "token.near".parse().unwrap();
+        let request = |block_height| {
+            get_function_call_request(
+                block_height,
+                contract_id.clone(),
+                "ft_metadata",
+                serde_json::json!({}),
+            )
+        };
+        let archival_calls = |rpc_client: &ArchivalRoutingRpcClient<FakeRpc>| {
+            rpc_client.archival.as_ref().unwrap().calls()
+        };
+
+        // The head is not known yet, the pruned block is asked again at the archival node
+        assert!(rpc_client.query(request(10)).await.is_ok());
+        assert_eq!(rpc_client.regular.calls(), 1);
+        assert_eq!(archival_calls(&rpc_client), 1);
+
+        // Now the head is known
+        assert!(rpc_client.query(request(500_000)).await.is_ok());
+        assert_eq!(rpc_client.regular.calls(), 2);
+        assert_eq!(archival_calls(&rpc_client), 1);
+
+        // Surely pruned, goes straight to the archival node
+        assert!(rpc_client.query(request(10)).await.is_ok());
+        assert_eq!(rpc_client.regular.calls(), 2);
+        assert_eq!(archival_calls(&rpc_client), 2);
+
+        // Recent enough to try, but already pruned by the regular node
+        assert!(rpc_client.query(request(440_000)).await.is_ok());
+        assert_eq!(rpc_client.regular.calls(), 3);
+        assert_eq!(archival_calls(&rpc_client), 3);
+    }
+}
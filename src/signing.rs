@@ -0,0 +1,372 @@
+//! Optional HMAC request signing for the enterprise setups.
+//! The client sends the API key, `X-Signature-Timestamp` (UNIX seconds) and `X-Signature`:
+//! hex HMAC-SHA256 of `{METHOD}\n{path with query}\n{timestamp}\n{hex SHA-256 of the body}` with the key secret
+//! (the body of GET is empty, its hash is still there).
+//! The secret never goes over the wire, so the leaked logs don't allow to send the requests,
+//! the intercepted signature can't be used with the other body, and the same signature can't be replayed.
+//!
+//! The key secrets are not stored anywhere: the secret is HMAC of `key_id` with the server
+//! master secret, the operator gives it to the key owner (see `/admin/keys/{key_id}/signing-secret`).
+use std::collections::HashMap;
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use futures::future::{ready, LocalBoxFuture, Ready};
+use hmac::Mac;
+use sha2::Digest;
+
+use crate::{api_keys, audit, config, errors};
+
+type HmacSha256 = hmac::Hmac<sha2::Sha256>;
+
+pub const SIGNATURE_HEADER: &str = "X-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Signature-Timestamp";
+/// If we see more signatures inside the clock skew window, the new ones are rejected until the old ones expire
+const MAX_SEEN_SIGNATURES: usize = 100_000;
+
+/// The signature of the request, the client should put it to `X-Signature` header
+pub fn sign(
+    secret: &str,
+    method: &str,
+    path_and_query: &str,
+    timestamp_secs: u64,
+    body: &[u8],
+) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(signed_message(method, path_and_query, timestamp_secs, body).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+fn signed_message(method: &str, path_and_query: &str, timestamp_secs: u64, body: &[u8]) -> String {
+    format!(
+        "{}\n{}\n{}\n{}",
+        method,
+        path_and_query,
+        timestamp_secs,
+        hex::encode(sha2::Sha256::digest(body))
+    )
+}
+
+/// The used signatures with their timestamps, kept until the timestamps are too old to pass the check
+struct SeenSignatures {
+    timestamps: std::sync::Mutex<HashMap<String, u64>>,
+    max_items: usize,
+}
+
+impl SeenSignatures {
+    fn new(max_items: usize) -> Self {
+        Self {
+            timestamps: std::sync::Mutex::new(HashMap::new()),
+            max_items,
+        }
+    }
+
+    /// Fails if the signature was already used. When it's full, only the expired signatures are
+    /// dropped: forgetting the fresh ones would allow to replay them, so the new ones wait for the room
+    fn remember(
+        &self,
+        signature: &str,
+        timestamp_secs: u64,
+        now_secs: u64,
+        max_clock_skew_secs: u64,
+    ) -> crate::Result<()> {
+        let mut timestamps = self
+            .timestamps
+            .lock()
+            .expect("seen signatures lock is poisoned");
+        if timestamps.contains_key(signature) {
+            return Err(unauthorized("the signature was already used"));
+        }
+        if timestamps.len() >= self.max_items {
+            timestamps.retain(|_, timestamp| *timestamp + max_clock_skew_secs >= now_secs);
+            if let Some(oldest) = timestamps.values().min() {
+                if timestamps.len() >= self.max_items {
+                    return Err(errors::ErrorKind::Unavailable {
+                        message: "too many signed requests, try again later".to_string(),
+                        retry_after_secs: oldest + max_clock_skew_secs + 1 - now_secs,
+                    }
+                    .into());
+                }
+            }
+        }
+        timestamps.insert(signature.to_string(), timestamp_secs);
+        Ok(())
+    }
+}
+
+struct Inner {
+    master_secret: String,
+    config: config::RequestSigningConfig,
+    seen_signatures: SeenSignatures,
+}
+
+impl Inner {
+    fn key_secret(&self, key_id: &str) -> String {
+        let mut mac = HmacSha256::new_from_slice(self.master_secret.as_bytes())
+            .expect("HMAC accepts keys of any size");
+        mac.update(key_id.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    /// `body` is empty for the unsigned requests, we don't read it for them
+    fn verify(&self, req: &ServiceRequest, body: &[u8], now_secs: u64) -> crate::Result<()> {
+        let headers = req.headers();
+        let signature = match headers.get(SIGNATURE_HEADER) {
+            Some(signature) => signature.to_str().unwrap_or_default(),
+            None if self
+                .config
+                .path_prefixes
+                .iter()
+                .any(|prefix| config::matches_path_prefix(prefix, req.path())) =>
+            {
+                return Err(unauthorized("the request signature is required"))
+            }
+            None => return Ok(()),
+        };
+
+        let timestamp_secs = headers
+            .get(TIMESTAMP_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+            .ok_or_else(|| unauthorized("the signature timestamp is missing or invalid"))?;
+        if now_secs.abs_diff(timestamp_secs) > self.config.max_clock_skew_secs {
+            return Err(unauthorized(
+                "the signature timestamp is too far from the server time",
+            ));
+        }
+        if api_keys::get_api_key(headers).is_none() {
+            return Err(unauthorized("the signed request should have the API key"));
+        }
+
+        let mut mac =
+            HmacSha256::new_from_slice(self.key_secret(&api_keys::get_key_id(headers)).as_bytes())
+                .expect("HMAC accepts keys of any size");
+        let path_and_query = req
+            .uri()
+            .path_and_query()
+            .map(|path_and_query| path_and_query.as_str())
+            .unwrap_or_else(|| req.path());
+        mac.update(
+            signed_message(req.method().as_str(), path_and_query, timestamp_secs, body).as_bytes(),
+        );
+        let signature_bytes =
+            hex::decode(signature).map_err(|_| unauthorized("the signature is not valid hex"))?;
+        mac.verify_slice(&signature_bytes)
+            .map_err(|_| unauthorized("the signature is invalid"))?;
+
+        self.seen_signatures.remember(
+            signature,
+            timestamp_secs,
+            now_secs,
+            self.config.max_clock_skew_secs,
+        )
+    }
+}
+
+fn unauthorized(message: &str) -> errors::Error {
+    errors::ErrorKind::Unauthorized(message.to_string()).into()
+}
+
+/// Create it once and clone to all the workers, so they share the seen signatures
+#[derive(Clone, Default)]
+pub struct SignatureVerifier {
+    /// `None` if the signing is disabled
+    inner: Option<std::sync::Arc<Inner>>,
+}
+
+impl SignatureVerifier {
+    /// Passes all the requests through
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn new(master_secret: String, config: config::RequestSigningConfig) -> Self {
+        Self {
+            inner: Some(std::sync::Arc::new(Inner {
+                master_secret,
+                config,
+                seen_signatures: SeenSignatures::new(MAX_SEEN_SIGNATURES),
+            })),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// The secret the owner of the key signs the requests with. `None` if the signing is disabled
+    pub(crate) fn key_secret(&self, key_id: &str) -> Option<String> {
+        self.inner.as_ref().map(|inner| inner.key_secret(key_id))
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for SignatureVerifier
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = SignatureVerifierMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(SignatureVerifierMiddleware {
+            service: std::rc::Rc::new(service),
+            inner: self.inner.clone(),
+        }))
+    }
+}
+
+pub struct SignatureVerifierMiddleware<S> {
+    service: std::rc::Rc<S>,
+    inner: Option<std::sync::Arc<Inner>>,
+}
+
+impl<S, B> Service<ServiceRequest> for SignatureVerifierMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let inner = match &self.inner {
+            Some(inner) => inner.clone(),
+            None => {
+                let response = self.service.call(req);
+                return Box::pin(async move { Ok(response.await?.map_into_boxed_body()) });
+            }
+        };
+
+        let service = self.service.clone();
+        Box::pin(async move {
+            let body = if req.headers().contains_key(SIGNATURE_HEADER) {
+                req.extract::<actix_web::web::Bytes>().await?
+            } else {
+                actix_web::web::Bytes::new()
+            };
+            let now_secs = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or_default();
+            if let Err(err) = inner.verify(&req, &body, now_secs) {
+                return Ok(req.error_response(err));
+            }
+            if !body.is_empty() {
+                req.set_payload(audit::bytes_to_payload(body));
+            }
+            Ok(service.call(req).await?.map_into_boxed_body())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW_SECS: u64 = 1_670_000_000;
+    const BODY: &[u8] = br#"{"account_id":"alice.near"}"#;
+
+    fn signed_request(
+        api_key: &str,
+        secret: &str,
+        path_and_query: &str,
+        timestamp_secs: u64,
+    ) -> ServiceRequest {
+        actix_web::test::TestRequest::post()
+            .uri(path_and_query)
+            .insert_header((api_keys::API_KEY_HEADER, api_key))
+            .insert_header((TIMESTAMP_HEADER, timestamp_secs.to_string()))
+            .insert_header((
+                SIGNATURE_HEADER,
+                sign(secret, "POST", path_and_query, timestamp_secs, BODY),
+            ))
+            .to_srv_request()
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let verifier = SignatureVerifier::new(
+            "master".to_string(),
+            config::RequestSigningConfig {
+                path_prefixes: vec!["/webhooks".to_string()],
+                ..Default::default()
+            },
+        );
+        let inner = verifier.inner.as_ref().unwrap();
+        let key_id = api_keys::get_key_id(
+            actix_web::test::TestRequest::default()
+                .insert_header((api_keys::API_KEY_HEADER, "key"))
+                .to_srv_request()
+                .headers(),
+        );
+        let secret = verifier.key_secret(&key_id).unwrap();
+
+        let request = signed_request("key", &secret, "/webhooks/1?x=y", NOW_SECS);
+        // The other body
+        assert!(inner.verify(&request, b"{}", NOW_SECS + 10).is_err());
+        assert!(inner.verify(&request, BODY, NOW_SECS + 10).is_ok());
+        // Replay
+        assert!(inner.verify(&request, BODY, NOW_SECS + 10).is_err());
+        // Too old
+        let request = signed_request("key", &secret, "/webhooks/1", NOW_SECS - 1000);
+        assert!(inner.verify(&request, BODY, NOW_SECS).is_err());
+        // The other key, or the secret of the other key
+        let request = signed_request("other_key", &secret, "/webhooks/1", NOW_SECS);
+        assert!(inner.verify(&request, BODY, NOW_SECS).is_err());
+
+        // The signature is required only for the configured routes
+        let request = actix_web::test::TestRequest::post()
+            .uri("/webhooks/1")
+            .to_srv_request();
+        assert_eq!(inner.verify(&request, &[], NOW_SECS).unwrap_err().code, 401);
+        let request = actix_web::test::TestRequest::get()
+            .uri("/accounts/alice.near/coins")
+            .to_srv_request();
+        assert!(inner.verify(&request, &[], NOW_SECS).is_ok());
+    }
+
+    #[test]
+    fn test_seen_signatures() {
+        let seen_signatures = SeenSignatures::new(2);
+        let skew = 300;
+        assert!(seen_signatures
+            .remember("a", NOW_SECS, NOW_SECS, skew)
+            .is_ok());
+        assert!(seen_signatures
+            .remember("b", NOW_SECS + 10, NOW_SECS, skew)
+            .is_ok());
+        assert_eq!(
+            seen_signatures
+                .remember("a", NOW_SECS, NOW_SECS, skew)
+                .unwrap_err()
+                .code,
+            401
+        );
+
+        // Full, and nothing is expired: the used signatures are not forgotten
+        let error = seen_signatures
+            .remember("c", NOW_SECS, NOW_SECS + 100, skew)
+            .unwrap_err();
+        assert_eq!(error.code, 503);
+        assert_eq!(error.retry_after_secs, Some(201));
+        assert!(seen_signatures
+            .remember("b", NOW_SECS + 10, NOW_SECS + 100, skew)
+            .is_err());
+
+        // `a` is expired, so there is room for `c`, `b` is still remembered
+        assert!(seen_signatures
+            .remember("c", NOW_SECS + 301, NOW_SECS + 301, skew)
+            .is_ok());
+        assert!(seen_signatures
+            .remember("b", NOW_SECS + 10, NOW_SECS + 301, skew)
+            .is_err());
+    }
+}
@@ -0,0 +1,240 @@
+//! Post-processing of the generated OpenAPI spec, so that it describes what the server really enforces.
+//! paperclip builds the spec from the types, the per-route settings from `config` are unknown to it:
+//! the middleware rewrites the spec responses and puts the page limits of each route into `limit` parameters.
+//! `export_spec` writes the same specs to the files, e.g. to generate the clients without the running server.
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use futures::future::{ready, LocalBoxFuture, Ready};
+use paperclip::actix::OpenApiExt;
+
+use crate::config::LimitsConfig;
+use crate::errors;
+
+/// Fills in the default and the maximum of `limit` query parameters, both for v2 and v3 specs.
+/// The route templates are matched against `path_prefix` of the routes config,
+/// so `{account_id}` segment matches only `*`
+pub fn apply_page_limits(spec: &mut serde_json::Value, limits: &LimitsConfig) {
+    let paths = match spec
+        .get_mut("paths")
+        .and_then(|paths| paths.as_object_mut())
+    {
+        Some(paths) => paths,
+        None => return,
+    };
+    for (path, item) in paths.iter_mut() {
+        let page_limits = limits.for_path(path).page_limits;
+        let operations = match item.as_object_mut() {
+            Some(operations) => operations,
+            None => continue,
+        };
+        for operation in operations.values_mut() {
+            let parameters = match operation
+                .get_mut("parameters")
+                .and_then(|parameters| parameters.as_array_mut())
+            {
+                Some(parameters) => parameters,
+                None => continue,
+            };
+            for parameter in parameters {
+                if parameter["in"] != "query" || parameter["name"] != "limit" {
+                    continue;
+                }
+                parameter["description"] = format!(
+                    "Maximum available limit {}, {} by default",
+                    page_limits.max_limit, page_limits.default_limit
+                )
+                .into();
+                // v3 describes the type in the nested schema, v2 in the parameter itself
+                let schema = if parameter.get("schema").is_some() {
+                    &mut parameter["schema"]
+                } else {
+                    parameter
+                };
+                schema["minimum"] = 1.into();
+                schema["maximum"] = page_limits.max_limit.into();
+                schema["default"] = page_limits.default_limit.into();
+            }
+        }
+    }
+}
+
+/// Rewrites the responses of the routes starting with `path_prefix`, see `apply_page_limits`
+#[derive(Clone)]
+pub struct SpecRewriter {
+    path_prefix: String,
+    limits: LimitsConfig,
+}
+
+impl SpecRewriter {
+    pub fn new(path_prefix: &str, limits: LimitsConfig) -> Self {
+        Self {
+            path_prefix: path_prefix.to_string(),
+            limits,
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for SpecRewriter
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = SpecRewriterMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(SpecRewriterMiddleware {
+            service,
+            path_prefix: self.path_prefix.clone(),
+            limits: std::rc::Rc::new(self.limits.clone()),
+        }))
+    }
+}
+
+pub struct SpecRewriterMiddleware<S> {
+    service: S,
+    path_prefix: String,
+    limits: std::rc::Rc<LimitsConfig>,
+}
+
+impl<S, B> Service<ServiceRequest> for SpecRewriterMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let is_spec = req.path().starts_with(&self.path_prefix);
+        let limits = self.limits.clone();
+        let response = self.service.call(req);
+        Box::pin(async move {
+            let response = response.await?;
+            if !is_spec || response.status() != actix_web::http::StatusCode::OK {
+                return Ok(response.map_into_boxed_body());
+            }
+            let (req, response) = response.into_parts();
+            let (response, body) = response.into_parts();
+            let body = actix_web::body::to_bytes(body).await.map_err(|err| {
+                let err: Box<dyn std::error::Error> = err.into();
+                errors::Error::from_error_kind(errors::ErrorKind::InternalError(format!(
+                    "Could not read the spec: {}",
+                    err
+                )))
+            })?;
+            let body = match serde_json::from_slice::<serde_json::Value>(&body) {
+                Ok(mut spec) => {
+                    apply_page_limits(&mut spec, &limits);
+                    actix_web::web::Bytes::from(
+                        serde_json::to_vec(&spec).map_err(errors::Error::from)?,
+                    )
+                }
+                Err(_) => body,
+            };
+            Ok(ServiceResponse::new(
+                req,
+                response.set_body(body).map_into_boxed_body(),
+            ))
+        })
+    }
+}
+
+/// Writes `v2.json` and `v3.json` to `dir`, the same specs the server gives at `/api/spec/`.
+/// The endpoints are registered with the mock data source, so nothing is connected and no port is bound
+pub async fn export_spec(
+    spec: paperclip::v2::models::DefaultApiRaw,
+    limits: &LimitsConfig,
+    dir: &std::path::Path,
+) -> std::io::Result<()> {
+    let ctx = crate::ServerContext::mock(None);
+    let app = actix_web::test::init_service(
+        actix_web::App::new()
+            .wrap(SpecRewriter::new("/api/spec/", limits.clone()))
+            .wrap_api_with_spec(spec)
+            .configure(|app| crate::configure(app, &ctx))
+            .with_json_spec_at("/api/spec/v2.json")
+            .with_json_spec_v3_at("/api/spec/v3.json")
+            .build(),
+    )
+    .await;
+    std::fs::create_dir_all(dir)?;
+    for version in ["v2", "v3"] {
+        let request = actix_web::test::TestRequest::get()
+            .uri(&format!("/api/spec/{}.json", version))
+            .to_request();
+        let body = actix_web::test::call_and_read_body(&app, request).await;
+        // Pretty-printed, so the diffs between the versions are readable
+        let spec: serde_json::Value = serde_json::from_slice(&body)?;
+        std::fs::write(
+            dir.join(format!("{}.json", version)),
+            serde_json::to_vec_pretty(&spec)?,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_page_limits() {
+        let limits = LimitsConfig {
+            routes: vec![crate::config::RouteLimitsConfig {
+                path_prefix: "/accounts/*/coins/NEAR/history".to_string(),
+                input_payload_max_size: None,
+                request_timeout_secs: None,
+                default_page_limit: Some(50),
+                max_page_limit: Some(500),
+            }],
+            ..Default::default()
+        };
+        let limit_v2 = serde_json::json!({"in": "query", "name": "limit", "type": "integer"});
+        let limit_v3 =
+            serde_json::json!({"in": "query", "name": "limit", "schema": {"type": "integer"}});
+        let mut spec = serde_json::json!({
+            "paths": {
+                "/accounts/{account_id}/coins/NEAR/history": {"get": {"parameters": [limit_v2]}},
+                "/accounts/{account_id}/coins": {"get": {"parameters": [limit_v3]}},
+            }
+        });
+        apply_page_limits(&mut spec, &limits);
+
+        let parameter =
+            &spec["paths"]["/accounts/{account_id}/coins/NEAR/history"]["get"]["parameters"][0];
+        assert_eq!(parameter["maximum"], 500);
+        assert_eq!(parameter["default"], 50);
+        assert_eq!(
+            parameter["description"],
+            "Maximum available limit 500, 50 by default"
+        );
+        let parameter = &spec["paths"]["/accounts/{account_id}/coins"]["get"]["parameters"][0];
+        assert_eq!(parameter["schema"]["maximum"], 100);
+        assert_eq!(parameter["schema"]["default"], 20);
+        assert!(parameter.get("maximum").is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_export_spec() {
+        let dir = std::env::temp_dir().join(format!("spec_{}", std::process::id()));
+        export_spec(Default::default(), &LimitsConfig::default(), &dir)
+            .await
+            .unwrap();
+
+        let read = |file_name: &str| -> serde_json::Value {
+            serde_json::from_slice(&std::fs::read(dir.join(file_name)).unwrap()).unwrap()
+        };
+        let (v2, v3) = (read("v2.json"), read("v3.json"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(v2["swagger"], "2.0");
+        assert!(v3["openapi"].as_str().unwrap().starts_with("3."));
+        assert!(!v2["paths"].as_object().unwrap().is_empty());
+    }
+}
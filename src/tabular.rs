@@ -0,0 +1,183 @@
+//! The tables of the bulk exports, written as CSV or Parquet.
+//! The rows are kept as strings, the same as the amounts in JSON: u128 does not fit the Parquet integers.
+//! Parquet gets the real numbers only for `ColumnType::UInt64` columns, the empty text values become nulls.
+use crate::{errors, types};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ColumnType {
+    Text,
+    UInt64,
+}
+
+pub(crate) struct Table {
+    columns: Vec<(&'static str, ColumnType)>,
+    rows: Vec<Vec<String>>,
+    max_rows: usize,
+}
+
+impl Table {
+    pub fn new(columns: Vec<(&'static str, ColumnType)>, max_rows: usize) -> Self {
+        Self {
+            columns,
+            rows: vec![],
+            max_rows,
+        }
+    }
+
+    pub fn push_row(&mut self, row: Vec<String>) -> crate::Result<()> {
+        if self.rows.len() >= self.max_rows {
+            return Err(errors::ErrorKind::InvalidInput(format!(
+                "the export has more than {} rows",
+                self.max_rows
+            ))
+            .into());
+        }
+        debug_assert_eq!(row.len(), self.columns.len());
+        self.rows.push(row);
+        Ok(())
+    }
+
+    pub fn write(&self, format: types::query_params::FileFormat) -> crate::Result<Vec<u8>> {
+        format.check_supported()?;
+        match format {
+            types::query_params::FileFormat::Csv => Ok(self.to_csv().into_bytes()),
+            #[cfg(feature = "parquet")]
+            types::query_params::FileFormat::Parquet => self.to_parquet(),
+            #[cfg(not(feature = "parquet"))]
+            types::query_params::FileFormat::Parquet => unreachable!("checked above"),
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        let header: Vec<&str> = self.columns.iter().map(|(name, _)| *name).collect();
+        let mut csv = format!("{}\n", header.join(","));
+        for row in &self.rows {
+            let fields: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    #[cfg(feature = "parquet")]
+    fn to_parquet(&self) -> crate::Result<Vec<u8>> {
+        use std::sync::Arc;
+
+        let mut fields = vec![];
+        let mut arrays: Vec<arrow::array::ArrayRef> = vec![];
+        for (index, (name, column_type)) in self.columns.iter().enumerate() {
+            let values = self.rows.iter().map(|row| row[index].as_str());
+            match column_type {
+                ColumnType::Text => {
+                    fields.push(arrow::datatypes::Field::new(
+                        name,
+                        arrow::datatypes::DataType::Utf8,
+                        true,
+                    ));
+                    arrays.push(Arc::new(arrow::array::StringArray::from_iter(
+                        values.map(|value| if value.is_empty() { None } else { Some(value) }),
+                    )));
+                }
+                ColumnType::UInt64 => {
+                    fields.push(arrow::datatypes::Field::new(
+                        name,
+                        arrow::datatypes::DataType::UInt64,
+                        false,
+                    ));
+                    let numbers = values
+                        .map(|value| {
+                            value.parse::<u64>().map_err(|err| {
+                                parquet_error(format!("{} is not a number: {}", value, err))
+                            })
+                        })
+                        .collect::<crate::Result<Vec<u64>>>()?;
+                    arrays.push(Arc::new(arrow::array::UInt64Array::from(numbers)));
+                }
+            }
+        }
+        let schema = Arc::new(arrow::datatypes::Schema::new(fields));
+        let batch = arrow::record_batch::RecordBatch::try_new(schema.clone(), arrays)
+            .map_err(parquet_error)?;
+        let properties = parquet::file::properties::WriterProperties::builder()
+            .set_compression(parquet::basic::Compression::SNAPPY)
+            .build();
+        let mut file = vec![];
+        let mut writer = parquet::arrow::ArrowWriter::try_new(&mut file, schema, Some(properties))
+            .map_err(parquet_error)?;
+        writer.write(&batch).map_err(parquet_error)?;
+        writer.close().map_err(parquet_error)?;
+        Ok(file)
+    }
+}
+
+/// The symbols and the statuses come from the contracts, they could have anything
+fn csv_field(value: &str) -> std::borrow::Cow<str> {
+    if value.contains(|c| matches!(c, ',' | '"' | '\n' | '\r')) {
+        format!("\"{}\"", value.replace('"', "\"\"")).into()
+    } else {
+        value.into()
+    }
+}
+
+#[cfg(feature = "parquet")]
+fn parquet_error(err: impl std::fmt::Display) -> errors::Error {
+    errors::ErrorKind::InternalError(format!("failed to write parquet: {}", err)).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_table() -> Table {
+        let mut table = Table::new(
+            vec![
+                ("block_timestamp_nanos", ColumnType::UInt64),
+                ("symbol", ColumnType::Text),
+            ],
+            2,
+        );
+        table
+            .push_row(vec!["1".to_string(), "USN".to_string()])
+            .unwrap();
+        table
+            .push_row(vec!["2".to_string(), "a,\"b\"".to_string()])
+            .unwrap();
+        table
+    }
+
+    #[test]
+    fn test_table_csv() {
+        let mut table = sample_table();
+        assert_eq!(
+            table.write(types::query_params::FileFormat::Csv).unwrap(),
+            b"block_timestamp_nanos,symbol\n1,USN\n2,\"a,\"\"b\"\"\"\n"
+        );
+        assert!(table
+            .push_row(vec!["3".to_string(), "USN".to_string()])
+            .is_err());
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_table_parquet() {
+        use parquet::file::reader::FileReader;
+
+        let file = sample_table()
+            .write(types::query_params::FileFormat::Parquet)
+            .unwrap();
+        let reader = parquet::file::serialized_reader::SerializedFileReader::new(
+            actix_web::web::Bytes::from(file),
+        )
+        .unwrap();
+        let metadata = reader.metadata().file_metadata();
+        assert_eq!(metadata.num_rows(), 2);
+        assert_eq!(
+            metadata.schema_descr().column(0).name(),
+            "block_timestamp_nanos"
+        );
+        assert_eq!(
+            metadata.schema_descr().column(0).physical_type(),
+            parquet::basic::Type::INT64
+        );
+    }
+}
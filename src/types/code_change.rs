@@ -0,0 +1,45 @@
+use paperclip::actix::Apiv2Schema;
+
+use crate::{errors, types};
+
+/// The successful deployment which changed the contract code.
+/// `code_hash` is base58, the same as RPC `view_account` gives
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct CodeChange {
+    pub code_hash: String,
+    /// `None` for the first deployment
+    pub previous_code_hash: Option<String>,
+    pub deployer_account_id: types::AccountId,
+    pub receipt_id: String,
+    pub transaction_hash: Option<String>,
+    pub block_timestamp_nanos: types::U64,
+    pub block_height: types::U64,
+    pub block_hash: String,
+}
+
+/// The indexer keeps sha256 of the code in hex, RPC and the wallets show it in base58
+pub(crate) fn get_code_hash(code_sha256: &str) -> crate::Result<String> {
+    let bytes = hex::decode(code_sha256).map_err(|err| {
+        errors::ErrorKind::InternalError(format!("Malformed code hash {}: {}", code_sha256, err))
+    })?;
+    let hash = near_primitives::hash::CryptoHash::try_from(bytes.as_slice()).map_err(|err| {
+        errors::ErrorKind::InternalError(format!("Malformed code hash {}: {}", code_sha256, err))
+    })?;
+    Ok(hash.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_hash() {
+        assert_eq!(
+            get_code_hash("0000000000000000000000000000000000000000000000000000000000000000")
+                .unwrap(),
+            "11111111111111111111111111111111"
+        );
+        assert!(get_code_hash("not hex").is_err());
+        assert!(get_code_hash("00").is_err());
+    }
+}
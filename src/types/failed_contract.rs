@@ -0,0 +1,29 @@
+use paperclip::actix::Apiv2Schema;
+
+use crate::{errors, types};
+
+/// The contract which was skipped in the aggregated response because its RPC call failed.
+/// The other entries of the response are still valid
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct FailedContract {
+    pub contract_account_id: types::AccountId,
+    /// Stable machine-readable code, see `Error` schema
+    pub error_code: String,
+    pub message: String,
+    /// The same request may succeed if submitted again
+    pub retriable: bool,
+}
+
+impl FailedContract {
+    pub(crate) fn new(
+        contract_id: &near_primitives::types::AccountId,
+        error: errors::Error,
+    ) -> Self {
+        Self {
+            contract_account_id: contract_id.clone().into(),
+            error_code: error.error_code,
+            message: error.message,
+            retriable: error.retriable,
+        }
+    }
+}
@@ -1,7 +1,13 @@
 pub(crate) mod account_id;
+pub(crate) mod code_change;
+pub(crate) mod failed_contract;
 pub(crate) mod numeric;
 pub mod query_params;
+pub(crate) mod response_meta;
 pub(crate) mod vector;
 
-pub(crate) use account_id::AccountId;
-pub(crate) use numeric::{I128, U128, U64};
+pub use account_id::AccountId;
+pub use code_change::CodeChange;
+pub use failed_contract::FailedContract;
+pub use numeric::{I128, U128, U64};
+pub use response_meta::ResponseMeta;
@@ -2,6 +2,7 @@ use borsh::{BorshDeserialize, BorshSerialize};
 use num_traits::cast::ToPrimitive;
 use paperclip::v2::{models::DataType, schema::TypedData};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::str::FromStr;
 
 use crate::{errors, BigDecimal};
 
@@ -23,6 +24,29 @@ pub(crate) fn to_u64(x: &BigDecimal) -> crate::Result<u64> {
     })
 }
 
+/// `x * y / z`, rounded down. For the proportions of the amounts, the product does not fit into u128.
+/// 0 if `z` is 0
+pub(crate) fn mul_div(x: u128, y: u128, z: u128) -> crate::Result<u128> {
+    if z == 0 {
+        return Ok(0);
+    }
+    let to_decimal = |value: u128| {
+        BigDecimal::from_str(&value.to_string()).map_err(|err| {
+            errors::Error::from(errors::ErrorKind::InternalError(format!(
+                "Failed to convert {} to decimal: {}",
+                value, err
+            )))
+        })
+    };
+    let result = to_decimal(x)? * to_decimal(y)? / to_decimal(z)?;
+    to_u128(&result.with_scale(0))
+}
+
+// All the amounts, heights and timestamps in `api_models` use these types: they are serialized
+// as decimal strings, JSON numbers lose the precision after 2^53 in JS clients.
+// The amounts are always in the smallest units (yoctoNEAR, or FT units without the decimals),
+// `?amount_float=true` adds the approximate values, see `response_format`.
+// Plain numbers are used only for the counters which never come close to 2^53.
 // Taken from https://github.com/near/near-sdk-rs/blob/master/near-sdk/src/json_types/integers.rs
 macro_rules! impl_str_type {
     ($iden: ident, $ty: tt) => {
@@ -78,3 +102,20 @@ macro_rules! impl_str_type {
 impl_str_type!(U128, u128);
 impl_str_type!(U64, u64);
 impl_str_type!(I128, i128);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mul_div() {
+        assert_eq!(mul_div(1000, 1, 3).unwrap(), 333);
+        assert_eq!(mul_div(1000, 0, 3).unwrap(), 0);
+        assert_eq!(mul_div(1000, 1, 0).unwrap(), 0);
+        // 10^30 * 10^24 overflows u128
+        assert_eq!(
+            mul_div(10u128.pow(30), 10u128.pow(24), 4 * 10u128.pow(24)).unwrap(),
+            25 * 10u128.pow(28)
+        );
+    }
+}
@@ -4,22 +4,28 @@ use paperclip::actix::Apiv2Schema;
 const DEFAULT_PAGE_LIMIT: u32 = 20;
 const MAX_PAGE_LIMIT: u32 = 100;
 
-#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+#[derive(
+    Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema,
+)]
 pub struct BlockParams {
     pub block_timestamp_nanos: Option<types::U64>,
     pub block_height: Option<types::U64>,
 }
 
 // Designed to use together with BlockParams
-#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+#[derive(
+    Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema,
+)]
 pub struct PaginationParams {
     // TODO PHASE 2 add index parameter
     // pub without_updates_after_index: Option<super::types::U128>,
-    /// Maximum available limit 100
+    /// The default and the maximum limits depend on the endpoint
     pub limit: Option<u32>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+#[derive(
+    Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema,
+)]
 pub struct HistoryPaginationParams {
     // pub after_timestamp_nanos: Option<super::types::U64>,
     // pub after_block_height: Option<super::types::U64>,
@@ -30,11 +36,161 @@ pub struct HistoryPaginationParams {
     // But I will add them only at the same moment with the indexes, so that the users can use both mechanisms and paginate properly.
     // TODO PHASE 2 add index parameter
     // pub after_index: Option<super::types::U128>,
+    /// The default and the maximum limits depend on the endpoint
     pub limit: Option<u32>,
+    /// `meta.next_cursor` from the previous page. All the pages are taken from the snapshot of the first page,
+    /// so the new events do not shift them
+    pub cursor: Option<String>,
+}
+
+// For the endpoints which aggregate many contracts
+#[derive(
+    Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema,
+)]
+pub struct SkipErrorsParams {
+    /// Omit the broken, deleted or non-compliant contracts instead of listing them in `failed_contracts`
+    pub skip_errors: Option<bool>,
+}
+
+// For the endpoints which give the token or collection metadata together with the holdings
+#[derive(
+    Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema,
+)]
+pub struct MetadataParams {
+    /// `at_block` by default
+    pub metadata: Option<MetadataMode>,
+}
+
+impl MetadataParams {
+    pub(crate) fn is_latest(&self) -> bool {
+        self.metadata == Some(MetadataMode::Latest)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataMode {
+    /// The metadata at the requested block, as the wallet showed it back then
+    AtBlock,
+    /// The current metadata, e.g. to show the old balances with the current names and icons
+    Latest,
+}
+
+// For the endpoints which give the files
+#[derive(
+    Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema,
+)]
+pub struct FileFormatParams {
+    /// `csv` by default
+    pub format: Option<FileFormat>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+#[serde(rename_all = "snake_case")]
+pub enum FileFormat {
+    Csv,
+    /// Typed columns for DuckDB, Spark, pandas. Available if the server is built with `parquet` feature
+    Parquet,
+}
+
+impl Default for FileFormat {
+    fn default() -> Self {
+        Self::Csv
+    }
+}
+
+impl FileFormat {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Parquet => "parquet",
+        }
+    }
+
+    pub(crate) fn content_type(&self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv",
+            Self::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    /// Parquet writer is the optional dependency
+    pub(crate) fn check_supported(&self) -> crate::Result<()> {
+        if *self == Self::Parquet && !cfg!(feature = "parquet") {
+            return Err(errors::ErrorKind::InvalidInput(
+                "format=parquet is not supported, the server is built without `parquet` feature"
+                    .to_string(),
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for FileFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "csv" => Ok(Self::Csv),
+            "parquet" => Ok(Self::Parquet),
+            _ => Err(format!("unknown file format {}", format)),
+        }
+    }
+}
+
+/// `?timestamp_format=&amount_float=` of any endpoint, see `response_format`
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ResponseFormatParams {
+    pub timestamp_format: Option<TimestampFormat>,
+    pub amount_float: Option<bool>,
+}
+
+/// `?debug=true` of any endpoint, see `error_debug`
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize)]
+pub struct DebugParams {
+    pub debug: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestampFormat {
+    /// `..._iso8601` strings with the nanoseconds, e.g. `2022-06-18T16:52:56.644255779Z`
+    Iso8601,
+    /// `..._ms` numbers, milliseconds since the Unix epoch
+    EpochMs,
+    /// `..._nanos` strings, nanoseconds since the Unix epoch
+    Nanos,
+}
+
+/// The period of time which ends at the given block (or at the last block)
+#[derive(
+    Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema,
+)]
+pub struct WindowParams {
+    /// Number of hours or days, e.g. `12h`, `30d`. Maximum available window is 365 days
+    pub window: Option<String>,
+}
+
+/// The default and the maximum `limit` of the route, see `config::LimitsConfig`.
+/// The server puts them into the request extensions, the constants are used if they are not there
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageLimits {
+    pub default_limit: u32,
+    pub max_limit: u32,
+}
+
+impl Default for PageLimits {
+    fn default() -> Self {
+        Self {
+            default_limit: DEFAULT_PAGE_LIMIT,
+            max_limit: MAX_PAGE_LIMIT,
+        }
+    }
 }
 
 // Helper for parsing the data from user
-pub(crate) struct Pagination {
+pub struct Pagination {
     pub limit: u32,
 }
 
@@ -54,15 +210,139 @@ impl From<HistoryPaginationParams> for Pagination {
     }
 }
 
-pub(crate) struct HistoryPagination {
+#[cfg(feature = "history")]
+pub struct HistoryPagination {
     // start_after. Not including this!
     pub block_height: u64,
     pub block_timestamp: u64,
-    // TODO PHASE 2 add index parameter
-    // pub index: u128,
+    /// The page goes strictly before this event, it's from the last block before `block_timestamp`
+    pub before_event: Option<EventIndex>,
     pub limit: u32,
 }
 
+#[cfg(feature = "history")]
+impl HistoryPagination {
+    /// The events strictly before this position go to the page, compare it with
+    /// `(block_timestamp, shard_id, index_in_chunk)` row in SQL.
+    /// Add the plain `block_timestamp <= bound` as well: the row comparison does not prune the time partitions
+    pub fn event_bound(&self) -> EventIndex {
+        self.before_event.unwrap_or(EventIndex {
+            block_timestamp: self.block_timestamp,
+            shard_id: 0,
+            index_in_chunk: 0,
+        })
+    }
+}
+
+/// The position of the event in the indexer's natural ordering: the block, the shard, the index in the chunk.
+/// The block is given by its timestamp, the balances DB does not know the heights
+#[cfg(feature = "history")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EventIndex {
+    pub block_timestamp: u64,
+    pub shard_id: u64,
+    pub index_in_chunk: u64,
+}
+
+#[cfg(feature = "history")]
+impl EventIndex {
+    pub(crate) fn from_db(
+        block_timestamp: &crate::BigDecimal,
+        shard_id: &crate::BigDecimal,
+        index_in_chunk: &crate::BigDecimal,
+    ) -> crate::Result<Self> {
+        Ok(Self {
+            block_timestamp: types::numeric::to_u64(block_timestamp)?,
+            shard_id: types::numeric::to_u64(shard_id)?,
+            index_in_chunk: types::numeric::to_u64(index_in_chunk)?,
+        })
+    }
+}
+
+/// Opaque `next_cursor` of the history endpoints
+#[cfg(feature = "history")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct HistoryCursor {
+    /// The snapshot of the first page
+    pub block_height: u64,
+    pub block_timestamp: u64,
+    /// The hash of the snapshot, so the next pages don't look the block up.
+    /// `None` for the cursors given before it was there
+    pub block_hash: Option<String>,
+    /// The last event of the previous page, the next page starts strictly before it
+    pub before_event: EventIndex,
+}
+
+#[cfg(feature = "history")]
+impl HistoryCursor {
+    pub(crate) fn encode(&self) -> String {
+        let mut cursor = format!(
+            "{}:{}:{}:{}:{}",
+            self.block_height,
+            self.block_timestamp,
+            self.before_event.block_timestamp,
+            self.before_event.shard_id,
+            self.before_event.index_in_chunk
+        );
+        // base58 has no `:`
+        if let Some(block_hash) = &self.block_hash {
+            cursor.push(':');
+            cursor.push_str(block_hash);
+        }
+        base64::encode_config(cursor, base64::URL_SAFE_NO_PAD)
+    }
+
+    pub(crate) fn decode(cursor: &str) -> crate::Result<Self> {
+        let invalid_cursor =
+            || errors::ErrorKind::InvalidInput(format!("Invalid cursor {}", cursor));
+        let decoded = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or_else(invalid_cursor)?;
+        let mut parts: Vec<&str> = decoded.split(':').collect();
+        let block_hash = match parts.len() {
+            6 => parts.pop().map(str::to_string),
+            _ => None,
+        };
+        let parts = parts
+            .into_iter()
+            .map(|part| part.parse::<u64>())
+            .collect::<Result<Vec<u64>, _>>()
+            .map_err(|_| invalid_cursor())?;
+        let (block_height, block_timestamp, before_event) = match parts[..] {
+            [block_height, block_timestamp, before_timestamp, shard_id, index_in_chunk] => (
+                block_height,
+                block_timestamp,
+                EventIndex {
+                    block_timestamp: before_timestamp,
+                    shard_id,
+                    index_in_chunk,
+                },
+            ),
+            // The cursors given before the event index was there: strictly before the moment
+            [block_height, block_timestamp, before_timestamp] => (
+                block_height,
+                block_timestamp,
+                EventIndex {
+                    block_timestamp: before_timestamp,
+                    shard_id: 0,
+                    index_in_chunk: 0,
+                },
+            ),
+            _ => return Err(invalid_cursor().into()),
+        };
+        if before_event.block_timestamp > block_timestamp {
+            return Err(invalid_cursor().into());
+        }
+        Ok(Self {
+            block_height,
+            block_timestamp,
+            block_hash,
+            before_event,
+        })
+    }
+}
+
 pub(crate) fn check_block_params(params: &BlockParams) -> crate::Result<()> {
     if params.block_height.is_some() && params.block_timestamp_nanos.is_some() {
         Err(errors::ErrorKind::InvalidInput(
@@ -75,15 +355,306 @@ pub(crate) fn check_block_params(params: &BlockParams) -> crate::Result<()> {
     }
 }
 
-pub(crate) fn check_limit(limit_param: Option<u32>) -> crate::Result<()> {
-    if let Some(limit) = limit_param {
-        if limit > MAX_PAGE_LIMIT || limit == 0 {
-            return Err(errors::ErrorKind::InvalidInput(format!(
-                "Limit should be in range [1, {}]",
-                MAX_PAGE_LIMIT
-            ))
-            .into());
-        }
+/// Checks `limit` against the limits of the route and fills in the default one
+pub(crate) fn check_limit(
+    request: &actix_web::HttpRequest,
+    limit_param: &mut Option<u32>,
+) -> crate::Result<()> {
+    use actix_web::HttpMessage;
+
+    let page_limits = request
+        .extensions()
+        .get::<PageLimits>()
+        .copied()
+        .unwrap_or_default();
+    let limit = limit_param.unwrap_or(page_limits.default_limit);
+    if limit > page_limits.max_limit || limit == 0 {
+        return Err(errors::ErrorKind::InvalidInput(format!(
+            "Limit should be in range [1, {}]",
+            page_limits.max_limit
+        ))
+        .into());
     }
+    *limit_param = Some(limit);
     Ok(())
 }
+
+/// The common query parameters checked together: block params, `limit`, `window`, `cursor` and the flags.
+/// All the problems are reported in one `INVALID_INPUT` response with 400 status, instead of one by one.
+/// Put it before the typed `web::Query` arguments of the handler: they still describe the parameters in the spec,
+/// but the request does not reach them if something is wrong
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidatedQuery {
+    /// `limit` of the request, or the default one of the route
+    pub limit: u32,
+}
+
+impl ValidatedQuery {
+    fn validate(
+        query: &std::collections::HashMap<String, String>,
+        page_limits: PageLimits,
+    ) -> Result<Self, Vec<String>> {
+        let mut problems = vec![];
+        for name in ["block_height", "block_timestamp_nanos"] {
+            if let Some(value) = query.get(name) {
+                if value.parse::<u64>().is_err() {
+                    problems.push(format!("{} should be a number, found {}", name, value));
+                }
+            }
+        }
+        if query.contains_key("block_height") && query.contains_key("block_timestamp_nanos") {
+            problems.push(
+                "Both block_height and block_timestamp_nanos found. Please provide only one of values"
+                    .to_string(),
+            );
+        }
+        let limit = match query.get("limit").map(|limit| limit.parse::<u32>()) {
+            None => page_limits.default_limit,
+            Some(Ok(limit)) if limit > 0 && limit <= page_limits.max_limit => limit,
+            Some(_) => {
+                problems.push(format!(
+                    "Limit should be in range [1, {}]",
+                    page_limits.max_limit
+                ));
+                page_limits.default_limit
+            }
+        };
+        if let Some(window) = query.get("window") {
+            if parse_window_hours(window).is_none() {
+                problems.push(window_problem(window));
+            }
+        }
+        #[cfg(feature = "history")]
+        if let Some(cursor) = query.get("cursor") {
+            if HistoryCursor::decode(cursor).is_err() {
+                problems.push(format!("Invalid cursor {}", cursor));
+            }
+        }
+        if let Some(metadata) = query.get("metadata") {
+            if !["at_block", "latest"].contains(&metadata.as_str()) {
+                problems.push(format!(
+                    "metadata should be at_block or latest, found {}",
+                    metadata
+                ));
+            }
+        }
+        for name in ["skip_errors", "hide_spam", "debug"] {
+            if let Some(value) = query.get(name) {
+                if value.parse::<bool>().is_err() {
+                    problems.push(format!("{} should be true or false, found {}", name, value));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(Self { limit })
+        } else {
+            Err(problems)
+        }
+    }
+}
+
+impl actix_web::FromRequest for ValidatedQuery {
+    type Error = actix_web::Error;
+    type Future = futures::future::Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &actix_web::HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
+        use actix_web::HttpMessage;
+
+        let page_limits = req
+            .extensions()
+            .get::<PageLimits>()
+            .copied()
+            .unwrap_or_default();
+        let result =
+            actix_web::web::Query::<std::collections::HashMap<String, String>>::from_query(
+                req.query_string(),
+            )
+            .map_err(|err| vec![err.to_string()])
+            .and_then(|query| Self::validate(&query, page_limits));
+        futures::future::ready(result.map_err(|problems| errors::bad_request(problems.join("; "))))
+    }
+}
+
+// The parameters are described by the typed `web::Query` arguments
+impl paperclip::v2::schema::Apiv2Schema for ValidatedQuery {}
+impl paperclip::actix::OperationModifier for ValidatedQuery {}
+
+/// Returns the window duration in nanoseconds
+pub(crate) fn get_window_nanos(params: &WindowParams, default_days: u64) -> crate::Result<u64> {
+    const HOUR_NANOS: u64 = 60 * 60 * 1_000_000_000;
+
+    match &params.window {
+        None => Ok(default_days * 24 * HOUR_NANOS),
+        Some(window) => match parse_window_hours(window) {
+            Some(hours) => Ok(hours * HOUR_NANOS),
+            None => Err(errors::ErrorKind::InvalidInput(window_problem(window)).into()),
+        },
+    }
+}
+
+/// `None` if the window is malformed or out of range
+fn parse_window_hours(window: &str) -> Option<u64> {
+    const MAX_WINDOW_HOURS: u64 = 365 * 24;
+
+    let window = window.trim();
+    let hours = if let Some(days) = window.strip_suffix('d') {
+        days.parse::<u64>()
+            .ok()
+            .and_then(|days| days.checked_mul(24))
+    } else if let Some(hours) = window.strip_suffix('h') {
+        hours.parse::<u64>().ok()
+    } else {
+        None
+    };
+    hours.filter(|hours| *hours > 0 && *hours <= MAX_WINDOW_HOURS)
+}
+
+fn window_problem(window: &str) -> String {
+    format!(
+        "window should look like `12h` or `30d`, and be in range [1h, 365d], found {}",
+        window.trim()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_parsing() {
+        let hour_nanos = 60 * 60 * 1_000_000_000;
+        let window = |value: &str| WindowParams {
+            window: Some(value.to_string()),
+        };
+        assert_eq!(
+            get_window_nanos(&WindowParams::default(), 30).unwrap(),
+            30 * 24 * hour_nanos
+        );
+        assert_eq!(
+            get_window_nanos(&window("12h"), 30).unwrap(),
+            12 * hour_nanos
+        );
+        assert_eq!(
+            get_window_nanos(&window("90d"), 30).unwrap(),
+            90 * 24 * hour_nanos
+        );
+        assert!(get_window_nanos(&window("0d"), 30).is_err());
+        assert!(get_window_nanos(&window("366d"), 30).is_err());
+        assert!(get_window_nanos(&window("1w"), 30).is_err());
+        assert!(get_window_nanos(&window("-1d"), 30).is_err());
+    }
+
+    #[test]
+    fn test_check_limit() {
+        use actix_web::HttpMessage;
+
+        let request = actix_web::test::TestRequest::default().to_http_request();
+        let mut limit = None;
+        check_limit(&request, &mut limit).unwrap();
+        assert_eq!(limit, Some(DEFAULT_PAGE_LIMIT));
+
+        request.extensions_mut().insert(PageLimits {
+            default_limit: 5,
+            max_limit: 10,
+        });
+        let mut limit = None;
+        check_limit(&request, &mut limit).unwrap();
+        assert_eq!(limit, Some(5));
+        let mut limit = Some(10);
+        check_limit(&request, &mut limit).unwrap();
+        assert_eq!(limit, Some(10));
+        assert!(check_limit(&request, &mut Some(11)).is_err());
+        assert!(check_limit(&request, &mut Some(0)).is_err());
+    }
+
+    #[test]
+    fn test_validated_query() {
+        let query = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<std::collections::HashMap<String, String>>()
+        };
+        let page_limits = PageLimits {
+            default_limit: 5,
+            max_limit: 10,
+        };
+
+        assert_eq!(
+            ValidatedQuery::validate(&query(&[("block_height", "100")]), page_limits),
+            Ok(ValidatedQuery { limit: 5 })
+        );
+        assert_eq!(
+            ValidatedQuery::validate(
+                &query(&[("limit", "10"), ("skip_errors", "true")]),
+                page_limits
+            ),
+            Ok(ValidatedQuery { limit: 10 })
+        );
+        // Everything is reported at once
+        let problems = ValidatedQuery::validate(
+            &query(&[
+                ("block_height", "latest"),
+                ("block_timestamp_nanos", "1600000000000000100"),
+                ("limit", "11"),
+                ("window", "1w"),
+                ("skip_errors", "yes"),
+                ("metadata", "current"),
+            ]),
+            page_limits,
+        )
+        .unwrap_err();
+        assert_eq!(problems.len(), 6);
+        assert_eq!(problems[2], "Limit should be in range [1, 10]");
+    }
+
+    #[cfg(feature = "history")]
+    #[test]
+    fn test_history_cursor() {
+        let cursor = HistoryCursor {
+            block_height: 104,
+            block_timestamp: 1600000000000000104,
+            block_hash: Some("GJ5yGVnVWBCPWDUBtVp7EuzRCqSJHy7kDUAtjBcZsNgE".to_string()),
+            before_event: EventIndex {
+                block_timestamp: 1600000000000000102,
+                shard_id: 1,
+                index_in_chunk: 3,
+            },
+        };
+        assert_eq!(HistoryCursor::decode(&cursor.encode()).unwrap(), cursor);
+
+        assert!(HistoryCursor::decode("garbage").is_err());
+        // The position is after the snapshot
+        let cursor = HistoryCursor {
+            before_event: EventIndex {
+                block_timestamp: 1600000000000000105,
+                ..cursor.before_event
+            },
+            ..cursor
+        };
+        assert!(HistoryCursor::decode(&cursor.encode()).is_err());
+
+        // The old cursors are still accepted, the position is the start of the block
+        let legacy = base64::encode_config(
+            "104:1600000000000000104:1600000000000000102",
+            base64::URL_SAFE_NO_PAD,
+        );
+        let legacy = HistoryCursor::decode(&legacy).unwrap();
+        assert_eq!(
+            legacy.before_event,
+            EventIndex {
+                block_timestamp: 1600000000000000102,
+                shard_id: 0,
+                index_in_chunk: 0,
+            }
+        );
+        assert_eq!(legacy.block_hash, None);
+        // Without the hash
+        let cursor = HistoryCursor {
+            block_hash: None,
+            ..cursor
+        };
+        assert_eq!(HistoryCursor::decode(&cursor.encode()).unwrap(), cursor);
+    }
+}
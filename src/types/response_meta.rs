@@ -0,0 +1,45 @@
+use paperclip::actix::Apiv2Schema;
+
+use crate::{db_helpers, types};
+
+/// The context of the response, the same in all the endpoints which read the chain state
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Apiv2Schema)]
+pub struct ResponseMeta {
+    /// The block the data is given for, see `block_height` and `block_timestamp_nanos` query params
+    pub block_height: types::U64,
+    pub block_hash: String,
+    pub block_timestamp_nanos: types::U64,
+    /// The newest indexed block when the response was given, the data could not be fresher than it.
+    /// Missing if the server has not learned it yet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_indexed_block_height: Option<types::U64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_indexed_block_timestamp_nanos: Option<types::U64>,
+    /// `X-Request-Id` of the request (or the generated one), please give it when reporting the problem
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Pass it as `cursor` to get the next page.
+    /// Missing on the last page and in the responses without pages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+impl ResponseMeta {
+    /// `last_indexed_block_*` and `request_id` are filled on the way out, see `response_meta::ResponseMetaFiller`
+    pub(crate) fn new(block: &db_helpers::Block) -> Self {
+        Self {
+            block_height: block.height.into(),
+            block_hash: block.hash.clone(),
+            block_timestamp_nanos: block.timestamp.into(),
+            last_indexed_block_height: None,
+            last_indexed_block_timestamp_nanos: None,
+            request_id: None,
+            next_cursor: None,
+        }
+    }
+
+    pub(crate) fn with_next_cursor(mut self, next_cursor: Option<String>) -> Self {
+        self.next_cursor = next_cursor;
+        self
+    }
+}
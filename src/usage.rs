@@ -0,0 +1,269 @@
+//! Actix middleware which counts the requests and the response bytes per API key and endpoint.
+//! The counters are accumulated in memory and flushed to `api_usage` table in batches,
+//! so the accounting doesn't add DB writes to every request.
+use std::collections::HashMap;
+
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use futures::future::{ready, LocalBoxFuture, Ready};
+
+use crate::{api_keys, LOGGER_MSG};
+
+const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+/// The requests which didn't match any route go here, we don't want the random paths in the DB
+const UNKNOWN_ENDPOINT: &str = "unknown";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct UsageKey {
+    key_id: String,
+    day_timestamp: u64,
+    endpoint: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct UsageCounters {
+    requests_count: i64,
+    response_bytes: i64,
+}
+
+type PendingUsage = std::sync::Arc<std::sync::Mutex<HashMap<UsageKey, UsageCounters>>>;
+
+/// Create it once and clone to all the workers, so they share the counters
+#[derive(Clone, Default)]
+pub struct UsageRecorder {
+    /// `None` if the accounting is disabled
+    pending: Option<PendingUsage>,
+}
+
+impl UsageRecorder {
+    /// Passes the requests through without counting
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Spawns the task which writes the counters to `pool` every `flush_interval`.
+    /// Should be called inside Tokio runtime
+    pub(crate) fn new(
+        pool: sqlx::Pool<sqlx::Postgres>,
+        flush_interval: std::time::Duration,
+    ) -> Self {
+        let pending = PendingUsage::default();
+        tokio::spawn(run_flusher(pool, pending.clone(), flush_interval));
+        Self {
+            pending: Some(pending),
+        }
+    }
+
+    fn record(&self, key_id: String, endpoint: String, response_bytes: u64) {
+        let pending = match &self.pending {
+            Some(pending) => pending,
+            None => return,
+        };
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_nanos() as u64)
+            .unwrap_or_default();
+        let key = UsageKey {
+            key_id,
+            day_timestamp: now / DAY_NANOS * DAY_NANOS,
+            endpoint,
+        };
+        let mut pending = pending.lock().expect("usage counters lock is poisoned");
+        let counters = pending.entry(key).or_default();
+        counters.requests_count += 1;
+        counters.response_bytes += response_bytes as i64;
+    }
+}
+
+async fn run_flusher(
+    pool: sqlx::Pool<sqlx::Postgres>,
+    pending: PendingUsage,
+    flush_interval: std::time::Duration,
+) {
+    let mut interval = tokio::time::interval(flush_interval);
+    loop {
+        interval.tick().await;
+        flush(&pool, &pending).await;
+    }
+}
+
+/// The counters stay in memory if the write fails, we try again with the next batch
+async fn flush(pool: &sqlx::Pool<sqlx::Postgres>, pending: &PendingUsage) {
+    let batch = std::mem::take(&mut *pending.lock().expect("usage counters lock is poisoned"));
+    if batch.is_empty() {
+        return;
+    }
+
+    let mut key_ids = Vec::with_capacity(batch.len());
+    let mut day_timestamps = Vec::with_capacity(batch.len());
+    let mut endpoints = Vec::with_capacity(batch.len());
+    let mut requests_counts = Vec::with_capacity(batch.len());
+    let mut response_bytes = Vec::with_capacity(batch.len());
+    for (key, counters) in &batch {
+        key_ids.push(key.key_id.clone());
+        day_timestamps.push(key.day_timestamp as i64);
+        endpoints.push(key.endpoint.clone());
+        requests_counts.push(counters.requests_count);
+        response_bytes.push(counters.response_bytes);
+    }
+
+    let result = sqlx::query(
+        r"INSERT INTO api_usage (key_id, day_timestamp, endpoint, requests_count, response_bytes)
+          SELECT * FROM UNNEST($1::text[], $2::bigint[], $3::text[], $4::bigint[], $5::bigint[])
+          ON CONFLICT (key_id, day_timestamp, endpoint) DO UPDATE
+          SET requests_count = api_usage.requests_count + EXCLUDED.requests_count,
+              response_bytes = api_usage.response_bytes + EXCLUDED.response_bytes",
+    )
+    .bind(key_ids)
+    .bind(day_timestamps)
+    .bind(endpoints)
+    .bind(requests_counts)
+    .bind(response_bytes)
+    .execute(pool)
+    .await;
+
+    if let Err(err) = result {
+        tracing::warn!(
+            target: LOGGER_MSG,
+            "Failed to write {} usage records, retrying with the next batch: {:#?}",
+            batch.len(),
+            err
+        );
+        let mut pending = pending.lock().expect("usage counters lock is poisoned");
+        for (key, counters) in batch {
+            let pending_counters = pending.entry(key).or_default();
+            pending_counters.requests_count += counters.requests_count;
+            pending_counters.response_bytes += counters.response_bytes;
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for UsageRecorder
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = UsageRecorderMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(UsageRecorderMiddleware {
+            service,
+            recorder: self.clone(),
+        }))
+    }
+}
+
+pub struct UsageRecorderMiddleware<S> {
+    service: S,
+    recorder: UsageRecorder,
+}
+
+impl<S, B> Service<ServiceRequest> for UsageRecorderMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if self.recorder.pending.is_none() {
+            return Box::pin(self.service.call(req));
+        }
+        let key_id = api_keys::get_key_id(req.headers());
+        let response = self.service.call(req);
+        let recorder = self.recorder.clone();
+        Box::pin(async move {
+            let response = response.await?;
+            let endpoint = response
+                .request()
+                .match_pattern()
+                .unwrap_or_else(|| UNKNOWN_ENDPOINT.to_string());
+            if !endpoint.contains(api_keys::ADMIN_PATH_SEGMENT) {
+                let response_bytes = match response.response().body().size() {
+                    BodySize::Sized(size) => size,
+                    // Streams are not counted, we don't have them for now
+                    BodySize::None | BodySize::Stream => 0,
+                };
+                recorder.record(key_id, endpoint, response_bytes);
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_usage_recorder() {
+        let recorder = UsageRecorder {
+            pending: Some(PendingUsage::default()),
+        };
+        let app = actix_web::test::init_service(
+            actix_web::App::new()
+                .wrap(recorder.clone())
+                .route(
+                    "/accounts/{account_id}",
+                    actix_web::web::get().to(|| async { "12345" }),
+                )
+                .route(
+                    "/admin/usage",
+                    actix_web::web::get().to(|| async { "admin" }),
+                ),
+        )
+        .await;
+
+        for uri in ["/accounts/alice.near", "/accounts/bob.near", "/admin/usage"] {
+            let request = actix_web::test::TestRequest::get()
+                .uri(uri)
+                .insert_header((api_keys::API_KEY_HEADER, "secret"))
+                .to_request();
+            actix_web::test::call_service(&app, request).await;
+        }
+        let request = actix_web::test::TestRequest::get()
+            .uri("/random/path")
+            .to_request();
+        actix_web::test::call_service(&app, request).await;
+
+        let pending = recorder.pending.unwrap();
+        let pending = pending.lock().unwrap();
+        let mut usage: Vec<_> = pending
+            .iter()
+            .map(|(key, counters)| {
+                let is_anonymous = key.key_id == api_keys::ANONYMOUS_KEY_ID;
+                (key.endpoint.as_str(), is_anonymous, *counters)
+            })
+            .collect();
+        usage.sort_by_key(|(endpoint, ..)| *endpoint);
+        assert_eq!(
+            usage,
+            vec![
+                (
+                    "/accounts/{account_id}",
+                    false,
+                    UsageCounters {
+                        requests_count: 2,
+                        response_bytes: 10,
+                    }
+                ),
+                (
+                    UNKNOWN_ENDPOINT,
+                    true,
+                    UsageCounters {
+                        requests_count: 1,
+                        response_bytes: 0,
+                    }
+                ),
+            ]
+        );
+    }
+}
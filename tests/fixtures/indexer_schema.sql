@@ -0,0 +1,171 @@
+-- The subset of NEAR Indexer for Explorer schema (and balances DB schema) which is used by the API.
+-- Column types follow the original schema, so the queries behave the same way.
+-- Safe to apply several times.
+
+DO $$ BEGIN
+    CREATE TYPE execution_outcome_status AS ENUM ('UNKNOWN', 'FAILURE', 'SUCCESS_VALUE', 'SUCCESS_RECEIPT_ID');
+EXCEPTION WHEN duplicate_object THEN NULL; END $$;
+
+DO $$ BEGIN
+    CREATE TYPE action_kind AS ENUM ('CREATE_ACCOUNT', 'DEPLOY_CONTRACT', 'FUNCTION_CALL', 'TRANSFER', 'STAKE', 'ADD_KEY', 'DELETE_KEY', 'DELETE_ACCOUNT');
+EXCEPTION WHEN duplicate_object THEN NULL; END $$;
+
+DO $$ BEGIN
+    CREATE TYPE ft_event_kind AS ENUM ('MINT', 'TRANSFER', 'BURN');
+EXCEPTION WHEN duplicate_object THEN NULL; END $$;
+
+DO $$ BEGIN
+    CREATE TYPE nft_event_kind AS ENUM ('MINT', 'TRANSFER', 'BURN');
+EXCEPTION WHEN duplicate_object THEN NULL; END $$;
+
+CREATE TABLE IF NOT EXISTS blocks
+(
+    block_height      numeric(20, 0) NOT NULL,
+    block_hash        text           NOT NULL PRIMARY KEY,
+    prev_block_hash   text           NOT NULL,
+    block_timestamp   numeric(20, 0) NOT NULL,
+    total_supply      numeric(45, 0) NOT NULL,
+    gas_price         numeric(45, 0) NOT NULL,
+    author_account_id text           NOT NULL
+);
+CREATE INDEX IF NOT EXISTS blocks_height_idx ON blocks (block_height);
+CREATE INDEX IF NOT EXISTS blocks_timestamp_idx ON blocks (block_timestamp);
+
+CREATE TABLE IF NOT EXISTS execution_outcomes
+(
+    receipt_id                 text                     NOT NULL PRIMARY KEY,
+    executed_in_block_hash     text                     NOT NULL,
+    executed_in_block_timestamp numeric(20, 0)          NOT NULL,
+    index_in_chunk             integer                  NOT NULL,
+    gas_burnt                  numeric(20, 0)           NOT NULL,
+    tokens_burnt               numeric(45, 0)           NOT NULL,
+    executor_account_id        text                     NOT NULL,
+    status                     execution_outcome_status NOT NULL,
+    shard_id                   numeric(20, 0)           NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS transactions
+(
+    transaction_hash          text                     NOT NULL PRIMARY KEY,
+    included_in_block_hash    text                     NOT NULL,
+    block_timestamp           numeric(20, 0)           NOT NULL,
+    signer_account_id         text                     NOT NULL,
+    receiver_account_id       text                     NOT NULL,
+    status                    execution_outcome_status NOT NULL,
+    converted_into_receipt_id text                     NOT NULL
+);
+CREATE INDEX IF NOT EXISTS transactions_signer_account_id_idx ON transactions (signer_account_id);
+-- Added later, the DBs of the built-in indexer created before get them with the defaults
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS signer_public_key text NOT NULL DEFAULT '';
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS receipt_conversion_tokens_burnt numeric(45, 0) NOT NULL DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS action_receipts
+(
+    receipt_id                       text           NOT NULL PRIMARY KEY,
+    included_in_block_timestamp      numeric(20, 0) NOT NULL,
+    predecessor_account_id           text           NOT NULL,
+    receiver_account_id              text           NOT NULL,
+    originated_from_transaction_hash text           NOT NULL,
+    signer_account_id                text           NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS action_receipt_actions
+(
+    receipt_id                          text           NOT NULL,
+    index_in_action_receipt             integer        NOT NULL,
+    action_kind                         action_kind    NOT NULL,
+    args                                jsonb          NOT NULL,
+    receipt_predecessor_account_id      text           NOT NULL,
+    receipt_receiver_account_id         text           NOT NULL,
+    receipt_included_in_block_timestamp numeric(20, 0) NOT NULL,
+    PRIMARY KEY (receipt_id, index_in_action_receipt)
+);
+-- `/accounts/{account_id}/activity/summary` needs both, the other account queries also benefit from them
+CREATE INDEX IF NOT EXISTS action_receipt_actions_predecessor_idx
+    ON action_receipt_actions (receipt_predecessor_account_id, receipt_included_in_block_timestamp);
+CREATE INDEX IF NOT EXISTS action_receipt_actions_receiver_idx
+    ON action_receipt_actions (receipt_receiver_account_id, receipt_included_in_block_timestamp);
+
+CREATE TABLE IF NOT EXISTS account_changes
+(
+    id                                 bigserial      NOT NULL PRIMARY KEY,
+    affected_account_id                text           NOT NULL,
+    changed_in_block_timestamp         numeric(20, 0) NOT NULL,
+    changed_in_block_hash              text           NOT NULL,
+    caused_by_transaction_hash         text,
+    caused_by_receipt_id               text,
+    update_reason                      text           NOT NULL,
+    affected_account_nonstaked_balance numeric(45, 0) NOT NULL,
+    affected_account_staked_balance    numeric(45, 0) NOT NULL,
+    affected_account_storage_usage     numeric(20, 0) NOT NULL,
+    index_in_block                     integer        NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS assets__fungible_token_events
+(
+    emitted_for_receipt_id                text           NOT NULL,
+    emitted_at_block_timestamp            numeric(20, 0) NOT NULL,
+    emitted_in_shard_id                   numeric(20, 0) NOT NULL,
+    emitted_index_of_event_entry_in_shard integer        NOT NULL,
+    emitted_by_contract_account_id        text           NOT NULL,
+    amount                                text           NOT NULL,
+    event_kind                            ft_event_kind  NOT NULL,
+    token_old_owner_account_id            text           NOT NULL,
+    token_new_owner_account_id            text           NOT NULL,
+    event_memo                            text           NOT NULL,
+    PRIMARY KEY (emitted_for_receipt_id, emitted_index_of_event_entry_in_shard)
+);
+
+CREATE TABLE IF NOT EXISTS assets__non_fungible_token_events
+(
+    emitted_for_receipt_id                text           NOT NULL,
+    emitted_at_block_timestamp            numeric(20, 0) NOT NULL,
+    emitted_in_shard_id                   numeric(20, 0) NOT NULL,
+    emitted_index_of_event_entry_in_shard integer        NOT NULL,
+    emitted_by_contract_account_id        text           NOT NULL,
+    token_id                              text           NOT NULL,
+    event_kind                            nft_event_kind NOT NULL,
+    token_old_owner_account_id            text           NOT NULL,
+    token_new_owner_account_id            text           NOT NULL,
+    token_authorized_account_id           text           NOT NULL,
+    event_memo                            text           NOT NULL,
+    PRIMARY KEY (emitted_for_receipt_id, emitted_index_of_event_entry_in_shard)
+);
+
+-- Not a part of NEAR Indexer for Explorer: the current NFT counts kept by the built-in indexer.
+-- Only the contracts from nft_indexed_contracts have the complete counts
+CREATE TABLE IF NOT EXISTS nft_owner_counts
+(
+    emitted_by_contract_account_id  text           NOT NULL,
+    owner_account_id                text           NOT NULL,
+    nft_count                       bigint         NOT NULL,
+    last_updated_at_block_timestamp numeric(20, 0) NOT NULL,
+    PRIMARY KEY (emitted_by_contract_account_id, owner_account_id)
+);
+CREATE INDEX IF NOT EXISTS nft_owner_counts_owner_idx ON nft_owner_counts (owner_account_id, last_updated_at_block_timestamp);
+
+CREATE TABLE IF NOT EXISTS nft_indexed_contracts
+(
+    contract_account_id        text           NOT NULL PRIMARY KEY,
+    indexed_at_block_timestamp numeric(20, 0) NOT NULL
+);
+
+-- Balances DB
+CREATE TABLE IF NOT EXISTS balance_changes
+(
+    block_timestamp           numeric(20, 0) NOT NULL,
+    receipt_id                text,
+    transaction_hash          text,
+    affected_account_id       text           NOT NULL,
+    involved_account_id       text,
+    direction                 text           NOT NULL,
+    cause                     text           NOT NULL,
+    status                    text           NOT NULL,
+    delta_nonstaked_amount    numeric(39, 0) NOT NULL,
+    absolute_nonstaked_amount numeric(39, 0) NOT NULL,
+    delta_staked_amount       numeric(39, 0) NOT NULL,
+    absolute_staked_amount    numeric(39, 0) NOT NULL,
+    shard_id                  integer        NOT NULL,
+    index_in_chunk            integer        NOT NULL
+);
+CREATE INDEX IF NOT EXISTS balance_changes_affected_account_idx ON balance_changes (affected_account_id, block_timestamp);
@@ -0,0 +1,168 @@
+-- Small deterministic dataset for the DB query layer tests (see `modules::tests::init_seeded_db`).
+-- Apply after `indexer_schema.sql`.
+--
+-- Blocks 100..103, timestamps 1600000000000000100..1600000000000000103, the hashes are in `modules::tests::SEEDED_BLOCK_HASHES`.
+-- alice.near: created at block 100, has NEAR balance changes.
+-- bob.near: created at block 100, deleted at block 101.
+-- nft.near token "1": minted to alice.near at 101 (by nft.near itself, with no deposit), transferred to bob.near at 102,
+-- failed transfer back at 103.
+-- nft.near: contract deployed at block 100, failed redeploy at 102.
+-- dao.near: contract deployed at block 100, the same code redeployed at 101, upgraded to the new code at 103.
+-- nft.near token "1": bought by bob.near at Paras for 5 NEAR at 102, failed purchase at 103.
+-- nft.near token "1": bob.near approved Paras at 102 and revoked the approval at 103.
+-- alice.near: staked to pool.poolv1.near at block 102. Signed the transactions at 100 and 102.
+-- alice.near: added the full access key at block 100, deleted it at 103.
+-- pool.poolv1.near: got validator rewards at blocks 101 and 103.
+-- alice.lockup.near: balance 1000 at block 100, 700 at block 102.
+-- bob.lockup.near: balance 5000 at block 101.
+-- carol.near: paid 20 and 10 for gas at blocks 101, 102, got 5 refunded at 103.
+-- token.near: 1000 minted to alice.near at 100, alice.near sent 300 to bob.near at 101,
+-- failed transfer of 50 back at 102. The transfer at 101 was made by alice.near through router.near.
+-- dave.near: called app.near `vote` and `claim` at 103, app.near called back `on_vote` in the same block.
+-- dave.near: added the function call key for app.near at 101, signed `vote` at 102 and the transaction above at 103 with it.
+-- token.near: price samples in 2 hourly buckets (`migrations/` should be applied before).
+
+INSERT INTO blocks VALUES
+    (100, '9N8SLwByDi25YTLNgsdCzWaV5AsWJEpp4UFD2x8VuMzU', '3aWdLwMUmZHgHJonnR5HUkpqmpMvw2UaeN1uGzhPwVGy', 1600000000000000100, 0, 100000000, 'validator.near'),
+    (101, 'EpH8jp6BVS6MMeVSjNjV6zX4iRjKGUG7GEoBVR9xafTq', '9N8SLwByDi25YTLNgsdCzWaV5AsWJEpp4UFD2x8VuMzU', 1600000000000000101, 0, 100000000, 'validator.near'),
+    (102, '6mGTj16KMeMHmBiVBgvNQcbzWGoAb2seoXPUyCVMfhBH', 'EpH8jp6BVS6MMeVSjNjV6zX4iRjKGUG7GEoBVR9xafTq', 1600000000000000102, 0, 100000000, 'validator.near'),
+    (103, '22wm1M3iXDuaiadUgZcysR9pvo6nh46jbtrLThJZCBBi', '6mGTj16KMeMHmBiVBgvNQcbzWGoAb2seoXPUyCVMfhBH', 1600000000000000103, 0, 100000000, 'validator.near');
+
+INSERT INTO transactions VALUES
+    ('tx_create_alice', '9N8SLwByDi25YTLNgsdCzWaV5AsWJEpp4UFD2x8VuMzU', 1600000000000000100, 'alice.near', 'alice.near', 'SUCCESS_VALUE', 'receipt_create_alice'),
+    ('tx_alice_stake', '6mGTj16KMeMHmBiVBgvNQcbzWGoAb2seoXPUyCVMfhBH', 1600000000000000102, 'alice.near', 'pool.poolv1.near', 'SUCCESS_VALUE', 'receipt_alice_stake');
+
+INSERT INTO transactions VALUES
+    ('tx_dave_early_vote', '6mGTj16KMeMHmBiVBgvNQcbzWGoAb2seoXPUyCVMfhBH', 1600000000000000102, 'dave.near', 'app.near', 'SUCCESS_VALUE', 'receipt_dave_early_vote',
+        'ed25519:dave_app', 200000000000000000000),
+    ('tx_dave_vote', '22wm1M3iXDuaiadUgZcysR9pvo6nh46jbtrLThJZCBBi', 1600000000000000103, 'dave.near', 'app.near', 'SUCCESS_VALUE', 'receipt_dave_vote',
+        'ed25519:dave_app', 200000000000000000000);
+
+INSERT INTO execution_outcomes VALUES
+    ('receipt_create_alice', '9N8SLwByDi25YTLNgsdCzWaV5AsWJEpp4UFD2x8VuMzU', 1600000000000000100, 0, 0, 0, 'alice.near', 'SUCCESS_VALUE', 0),
+    ('receipt_create_bob', '9N8SLwByDi25YTLNgsdCzWaV5AsWJEpp4UFD2x8VuMzU', 1600000000000000100, 1, 0, 0, 'bob.near', 'SUCCESS_VALUE', 0),
+    ('receipt_delete_bob', 'EpH8jp6BVS6MMeVSjNjV6zX4iRjKGUG7GEoBVR9xafTq', 1600000000000000101, 0, 0, 0, 'bob.near', 'SUCCESS_VALUE', 0),
+    ('receipt_nft_mint', 'EpH8jp6BVS6MMeVSjNjV6zX4iRjKGUG7GEoBVR9xafTq', 1600000000000000101, 1, 0, 0, 'nft.near', 'SUCCESS_VALUE', 0),
+    ('receipt_nft_transfer', '6mGTj16KMeMHmBiVBgvNQcbzWGoAb2seoXPUyCVMfhBH', 1600000000000000102, 0, 0, 0, 'nft.near', 'SUCCESS_VALUE', 0),
+    ('receipt_nft_failed_transfer', '22wm1M3iXDuaiadUgZcysR9pvo6nh46jbtrLThJZCBBi', 1600000000000000103, 0, 0, 0, 'nft.near', 'FAILURE', 0),
+    ('receipt_alice_stake', '6mGTj16KMeMHmBiVBgvNQcbzWGoAb2seoXPUyCVMfhBH', 1600000000000000102, 1, 0, 0, 'pool.poolv1.near', 'SUCCESS_VALUE', 0),
+    ('receipt_paras_buy', '6mGTj16KMeMHmBiVBgvNQcbzWGoAb2seoXPUyCVMfhBH', 1600000000000000102, 2, 0, 0, 'marketplace.paras.near', 'SUCCESS_VALUE', 0),
+    ('receipt_paras_failed_buy', '22wm1M3iXDuaiadUgZcysR9pvo6nh46jbtrLThJZCBBi', 1600000000000000103, 1, 0, 0, 'marketplace.paras.near', 'FAILURE', 0),
+    ('receipt_nft_deploy', '9N8SLwByDi25YTLNgsdCzWaV5AsWJEpp4UFD2x8VuMzU', 1600000000000000100, 2, 0, 0, 'nft.near', 'SUCCESS_VALUE', 0),
+    ('receipt_nft_failed_deploy', '6mGTj16KMeMHmBiVBgvNQcbzWGoAb2seoXPUyCVMfhBH', 1600000000000000102, 3, 0, 0, 'nft.near', 'FAILURE', 0),
+    ('receipt_ft_mint', '9N8SLwByDi25YTLNgsdCzWaV5AsWJEpp4UFD2x8VuMzU', 1600000000000000100, 3, 0, 0, 'token.near', 'SUCCESS_VALUE', 0),
+    ('receipt_ft_transfer', 'EpH8jp6BVS6MMeVSjNjV6zX4iRjKGUG7GEoBVR9xafTq', 1600000000000000101, 2, 0, 0, 'token.near', 'SUCCESS_VALUE', 0),
+    ('receipt_ft_failed_transfer', '6mGTj16KMeMHmBiVBgvNQcbzWGoAb2seoXPUyCVMfhBH', 1600000000000000102, 4, 0, 0, 'token.near', 'FAILURE', 0),
+    ('receipt_nft_approve', '6mGTj16KMeMHmBiVBgvNQcbzWGoAb2seoXPUyCVMfhBH', 1600000000000000102, 5, 0, 0, 'nft.near', 'SUCCESS_VALUE', 0),
+    ('receipt_nft_revoke', '22wm1M3iXDuaiadUgZcysR9pvo6nh46jbtrLThJZCBBi', 1600000000000000103, 2, 0, 0, 'nft.near', 'SUCCESS_VALUE', 0),
+    ('receipt_alice_delete_key', '22wm1M3iXDuaiadUgZcysR9pvo6nh46jbtrLThJZCBBi', 1600000000000000103, 3, 0, 0, 'alice.near', 'SUCCESS_VALUE', 0),
+    ('receipt_dao_deploy', '9N8SLwByDi25YTLNgsdCzWaV5AsWJEpp4UFD2x8VuMzU', 1600000000000000100, 4, 0, 0, 'dao.near', 'SUCCESS_VALUE', 0),
+    ('receipt_dao_redeploy', 'EpH8jp6BVS6MMeVSjNjV6zX4iRjKGUG7GEoBVR9xafTq', 1600000000000000101, 3, 0, 0, 'dao.near', 'SUCCESS_VALUE', 0),
+    ('receipt_dao_upgrade', '22wm1M3iXDuaiadUgZcysR9pvo6nh46jbtrLThJZCBBi', 1600000000000000103, 4, 0, 0, 'dao.near', 'SUCCESS_VALUE', 0),
+    ('receipt_dave_add_key', 'EpH8jp6BVS6MMeVSjNjV6zX4iRjKGUG7GEoBVR9xafTq', 1600000000000000101, 4, 0, 0, 'dave.near', 'SUCCESS_VALUE', 0),
+    ('receipt_dave_vote', '22wm1M3iXDuaiadUgZcysR9pvo6nh46jbtrLThJZCBBi', 1600000000000000103, 5, 2400000000000, 240000000000000000000, 'app.near',
+        'SUCCESS_RECEIPT_ID', 0),
+    ('receipt_app_callback', '22wm1M3iXDuaiadUgZcysR9pvo6nh46jbtrLThJZCBBi', 1600000000000000103, 6, 600000000000, 60000000000000000000, 'app.near',
+        'SUCCESS_VALUE', 0);
+
+INSERT INTO action_receipts VALUES
+    ('receipt_create_alice', 1600000000000000100, 'alice.near', 'alice.near', 'tx_create_alice', 'alice.near'),
+    ('receipt_alice_transfer', 1600000000000000101, 'alice.near', 'bob.near', 'tx_alice_transfer', 'alice.near'),
+    ('receipt_alice_stake', 1600000000000000102, 'alice.near', 'pool.poolv1.near', 'tx_alice_stake', 'alice.near'),
+    ('receipt_ft_mint', 1600000000000000100, 'token.near', 'token.near', 'tx_ft_mint', 'token.near'),
+    ('receipt_ft_transfer', 1600000000000000101, 'router.near', 'token.near', 'tx_ft_transfer', 'alice.near'),
+    ('receipt_ft_failed_transfer', 1600000000000000102, 'bob.near', 'token.near', 'tx_ft_failed_transfer', 'bob.near'),
+    ('receipt_alice_delete_key', 1600000000000000103, 'alice.near', 'alice.near', 'tx_alice_delete_key', 'alice.near'),
+    ('receipt_dao_upgrade', 1600000000000000103, 'dao.near', 'dao.near', 'tx_dao_upgrade', 'dao.near'),
+    ('receipt_dave_vote', 1600000000000000103, 'dave.near', 'app.near', 'tx_dave_vote', 'dave.near'),
+    ('receipt_app_callback', 1600000000000000103, 'app.near', 'app.near', 'tx_dave_vote', 'dave.near');
+
+INSERT INTO action_receipt_actions VALUES
+    ('receipt_create_alice', 0, 'CREATE_ACCOUNT', '{}', 'alice.near', 'alice.near', 1600000000000000100),
+    ('receipt_create_alice', 1, 'TRANSFER', '{"deposit": "1000"}', 'alice.near', 'alice.near', 1600000000000000100),
+    ('receipt_create_alice', 2, 'ADD_KEY',
+        '{"public_key": "ed25519:alice", "access_key": {"nonce": 0, "permission": {"permission_kind": "FULL_ACCESS"}}}',
+        'alice.near', 'alice.near', 1600000000000000100),
+    ('receipt_create_bob', 0, 'CREATE_ACCOUNT', '{}', 'bob.near', 'bob.near', 1600000000000000100),
+    ('receipt_delete_bob', 0, 'DELETE_ACCOUNT', '{}', 'bob.near', 'bob.near', 1600000000000000101),
+    ('receipt_alice_stake', 0, 'FUNCTION_CALL', '{"method_name": "deposit_and_stake", "args_base64": "e30=", "gas": 100000000000000, "deposit": "50"}',
+        'alice.near', 'pool.poolv1.near', 1600000000000000102),
+    ('receipt_paras_buy', 0, 'FUNCTION_CALL',
+        '{"method_name": "buy", "deposit": "5000000000000000000000000", "args_json": {"nft_contract_id": "nft.near", "token_id": "1"}}',
+        'bob.near', 'marketplace.paras.near', 1600000000000000102),
+    ('receipt_paras_failed_buy', 0, 'FUNCTION_CALL',
+        '{"method_name": "buy", "deposit": "1000000000000000000000000", "args_json": {"nft_contract_id": "nft.near", "token_id": "1"}}',
+        'alice.near', 'marketplace.paras.near', 1600000000000000103),
+    ('receipt_nft_deploy', 0, 'DEPLOY_CONTRACT', '{"code_sha256": "0000000000000000000000000000000000000000000000000000000000000000"}',
+        'nft.near', 'nft.near', 1600000000000000100),
+    ('receipt_nft_failed_deploy', 0, 'DEPLOY_CONTRACT', '{"code_sha256": "1111111111111111111111111111111111111111111111111111111111111111"}',
+        'nft.near', 'nft.near', 1600000000000000102),
+    ('receipt_nft_mint', 0, 'FUNCTION_CALL',
+        '{"method_name": "nft_mint", "deposit": "0", "args_json": {"token_id": "1", "receiver_id": "alice.near"}}',
+        'nft.near', 'nft.near', 1600000000000000101),
+    ('receipt_nft_approve', 0, 'FUNCTION_CALL',
+        '{"method_name": "nft_approve", "args_json": {"token_id": "1", "account_id": "marketplace.paras.near"}}',
+        'bob.near', 'nft.near', 1600000000000000102),
+    ('receipt_nft_revoke', 0, 'FUNCTION_CALL',
+        '{"method_name": "nft_revoke", "args_json": {"token_id": "1", "account_id": "marketplace.paras.near"}}',
+        'bob.near', 'nft.near', 1600000000000000103),
+    ('receipt_alice_delete_key', 0, 'DELETE_KEY', '{"public_key": "ed25519:alice"}', 'alice.near', 'alice.near', 1600000000000000103),
+    ('receipt_dao_deploy', 0, 'DEPLOY_CONTRACT', '{"code_sha256": "0000000000000000000000000000000000000000000000000000000000000000"}',
+        'dao.near', 'dao.near', 1600000000000000100),
+    ('receipt_dao_redeploy', 0, 'DEPLOY_CONTRACT', '{"code_sha256": "0000000000000000000000000000000000000000000000000000000000000000"}',
+        'dao.near', 'dao.near', 1600000000000000101),
+    ('receipt_dao_upgrade', 0, 'DEPLOY_CONTRACT', '{"code_sha256": "2222222222222222222222222222222222222222222222222222222222222222"}',
+        'dao.near', 'dao.near', 1600000000000000103),
+    ('receipt_dave_add_key', 0, 'ADD_KEY',
+        '{"public_key": "ed25519:dave_app", "access_key": {"nonce": 0, "permission": {"permission_kind": "FUNCTION_CALL",
+            "permission_details": {"allowance": "250000000000000000000000", "receiver_id": "app.near", "method_names": ["vote"]}}}}',
+        'dave.near', 'dave.near', 1600000000000000101),
+    ('receipt_dave_early_vote', 0, 'FUNCTION_CALL', '{"method_name": "vote", "args_base64": "e30=", "gas": 30000000000000, "deposit": "0"}',
+        'dave.near', 'app.near', 1600000000000000102),
+    ('receipt_dave_vote', 0, 'FUNCTION_CALL', '{"method_name": "vote", "args_base64": "e30=", "gas": 30000000000000, "deposit": "0"}',
+        'dave.near', 'app.near', 1600000000000000103),
+    ('receipt_dave_vote', 1, 'FUNCTION_CALL', '{"method_name": "claim", "args_base64": "e30=", "gas": 10000000000000, "deposit": "0"}',
+        'dave.near', 'app.near', 1600000000000000103),
+    ('receipt_app_callback', 0, 'FUNCTION_CALL', '{"method_name": "on_vote", "args_base64": "e30=", "gas": 5000000000000, "deposit": "0"}',
+        'app.near', 'app.near', 1600000000000000103);
+
+INSERT INTO assets__non_fungible_token_events VALUES
+    ('receipt_nft_mint', 1600000000000000101, 0, 0, 'nft.near', '1', 'MINT', '', 'alice.near', '', ''),
+    ('receipt_nft_transfer', 1600000000000000102, 0, 0, 'nft.near', '1', 'TRANSFER', 'alice.near', 'bob.near', '', ''),
+    ('receipt_nft_failed_transfer', 1600000000000000103, 0, 0, 'nft.near', '1', 'TRANSFER', 'bob.near', 'alice.near', '', '');
+
+INSERT INTO assets__fungible_token_events VALUES
+    ('receipt_ft_mint', 1600000000000000100, 0, 0, 'token.near', '1000', 'MINT', '', 'alice.near', ''),
+    ('receipt_ft_transfer', 1600000000000000101, 0, 0, 'token.near', '300', 'TRANSFER', 'alice.near', 'bob.near', ''),
+    ('receipt_ft_failed_transfer', 1600000000000000102, 0, 0, 'token.near', '50', 'TRANSFER', 'bob.near', 'alice.near', '');
+
+INSERT INTO balance_changes VALUES
+    (1600000000000000100, 'receipt_create_alice', NULL, 'alice.near', 'near', 'INBOUND', 'RECEIPT', 'SUCCESS',
+        1000, 1000, 0, 0, 0, 0),
+    (1600000000000000101, 'receipt_alice_transfer', NULL, 'alice.near', 'bob.near', 'OUTBOUND', 'TRANSFER', 'SUCCESS',
+        -300, 700, 0, 0, 0, 0),
+    (1600000000000000102, 'receipt_alice_stake', NULL, 'alice.near', 'pool.near', 'OUTBOUND', 'VALIDATORS_REWARD', 'SUCCESS',
+        0, 700, 50, 50, 0, 0),
+    (1600000000000000101, NULL, NULL, 'pool.poolv1.near', NULL, 'INBOUND', 'VALIDATORS_REWARD', 'SUCCESS',
+        0, 0, 100, 100000, 0, 0),
+    (1600000000000000103, NULL, NULL, 'pool.poolv1.near', NULL, 'INBOUND', 'VALIDATORS_REWARD', 'SUCCESS',
+        0, 0, 20, 100020, 0, 0),
+    (1600000000000000100, NULL, NULL, 'alice.lockup.near', NULL, 'INBOUND', 'TRANSFER', 'SUCCESS',
+        1000, 1000, 0, 0, 0, 0),
+    (1600000000000000102, NULL, NULL, 'alice.lockup.near', NULL, 'OUTBOUND', 'TRANSFER', 'SUCCESS',
+        -300, 700, 0, 0, 0, 0),
+    (1600000000000000101, NULL, NULL, 'bob.lockup.near', NULL, 'INBOUND', 'TRANSFER', 'SUCCESS',
+        5000, 4000, 0, 1000, 0, 0),
+    (1600000000000000101, NULL, 'tx_carol_1', 'carol.near', NULL, 'OUTBOUND', 'GAS', 'SUCCESS',
+        -20, 980, 0, 0, 0, 0),
+    (1600000000000000102, NULL, 'tx_carol_2', 'carol.near', NULL, 'OUTBOUND', 'GAS', 'SUCCESS',
+        -10, 970, 0, 0, 0, 0),
+    (1600000000000000103, 'receipt_carol_refund', NULL, 'carol.near', NULL, 'INBOUND', 'GAS_REFUND', 'SUCCESS',
+        5, 975, 0, 0, 0, 0);
+
+INSERT INTO token_prices VALUES
+    ('token.near', 1600000000000000000, 1.10),
+    ('token.near', 1600000600000000000, 1.25),
+    ('token.near', 1600001200000000000, 1.05),
+    ('token.near', 1600001800000000000, 1.20),
+    ('token.near', 1600002600000000000, 1.30);